@@ -0,0 +1,216 @@
+//! Benchmarks for the upload and download hot paths.
+//!
+//! Run with `cargo bench`; compare against a saved baseline with
+//! `cargo bench -- --save-baseline main` and `cargo bench -- --baseline main`.
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{DefaultBodyLimit, Multipart, Request},
+    routing::post,
+    Router,
+};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use filerunner_backend::{
+    handlers::file::read_blob_body,
+    utils::{gzip_compress, zstd_compress, zstd_decompress, MemoryBudget},
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use sha2::{Digest, Sha256};
+use std::{path::PathBuf, sync::Arc, time::Duration};
+use tokio::{io::AsyncWriteExt, runtime::Runtime};
+use tower::ServiceExt;
+
+/// Payload sizes: a small asset, a typical document and a large media file
+const SIZES: [usize; 3] = [64 * 1024, 1024 * 1024, 16 * 1024 * 1024];
+
+/// Same default as `STORAGE_COMPRESSION_LEVEL`
+const ZSTD_LEVEL: i32 = 3;
+
+const BOUNDARY: &str = "filerunner-bench-boundary";
+
+fn random_bytes(size: usize) -> Vec<u8> {
+    let mut data = vec![0u8; size];
+    StdRng::seed_from_u64(size as u64).fill(&mut data[..]);
+    data
+}
+
+/// Repetitive text, the kind of content at-rest compression is meant for
+fn text_bytes(size: usize) -> Vec<u8> {
+    let mut rng = StdRng::seed_from_u64(size as u64);
+    let mut text = String::with_capacity(size + 64);
+    while text.len() < size {
+        text.push_str(&format!(
+            "{},item-{},{}\n",
+            rng.gen::<u32>(),
+            rng.gen_range(0..1000),
+            rng.gen_range(0..10_000)
+        ));
+    }
+    text.truncate(size);
+    text.into_bytes()
+}
+
+/// A multipart body shaped like an `/api/upload` request
+fn multipart_body(data: &[u8]) -> Vec<u8> {
+    let mut body = Vec::with_capacity(data.len() + 512);
+    body.extend_from_slice(
+        format!(
+            "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"folder_path\"\r\n\r\nbench/files\r\n\
+             --{BOUNDARY}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"bench.bin\"\r\n\
+             Content-Type: application/octet-stream\r\n\r\n"
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(data);
+    body.extend_from_slice(format!("\r\n--{BOUNDARY}--\r\n").as_bytes());
+    body
+}
+
+/// Scratch file under the system temp dir, removed when dropped
+struct TempBlob(PathBuf);
+
+impl TempBlob {
+    fn new(name: &str) -> Self {
+        Self(std::env::temp_dir().join(format!("filerunner-bench-{}-{name}", std::process::id())))
+    }
+}
+
+impl Drop for TempBlob {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// Reads every field the way `upload_file` does
+async fn read_multipart(mut multipart: Multipart) -> String {
+    let mut received = 0;
+    while let Some(field) = multipart.next_field().await.unwrap() {
+        received += field.bytes().await.unwrap().len();
+    }
+    received.to_string()
+}
+
+fn bench_multipart(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let app = Router::new()
+        .route("/api/upload", post(read_multipart))
+        .layer(DefaultBodyLimit::disable());
+    let mut group = c.benchmark_group("multipart_parse");
+    group.sample_size(20);
+    for size in SIZES {
+        let body = bytes::Bytes::from(multipart_body(&random_bytes(size)));
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &body, |b, body| {
+            b.to_async(&rt).iter(|| async {
+                let request = Request::builder()
+                    .method("POST")
+                    .uri("/api/upload")
+                    .header(
+                        "content-type",
+                        format!("multipart/form-data; boundary={BOUNDARY}"),
+                    )
+                    .body(Body::from(body.clone()))
+                    .unwrap();
+                let response = app.clone().oneshot(request).await.unwrap();
+                to_bytes(response.into_body(), usize::MAX).await.unwrap()
+            });
+        });
+    }
+    group.finish();
+}
+
+fn bench_hashing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("content_hash");
+    for size in SIZES {
+        let data = random_bytes(size);
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &data, |b, data| {
+            b.iter(|| hex::encode(Sha256::digest(data)));
+        });
+    }
+    group.finish();
+}
+
+fn bench_compression(c: &mut Criterion) {
+    let mut group = c.benchmark_group("compression");
+    group.sample_size(20);
+    for size in SIZES {
+        let text = text_bytes(size);
+        let compressed = zstd_compress(&text, ZSTD_LEVEL).unwrap();
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::new("zstd_compress", size), &text, |b, text| {
+            b.iter(|| zstd_compress(text, ZSTD_LEVEL).unwrap());
+        });
+        group.bench_with_input(
+            BenchmarkId::new("zstd_decompress", size),
+            &compressed,
+            |b, compressed| b.iter(|| zstd_decompress(compressed).unwrap()),
+        );
+        // Downloads of zstd blobs by clients that only accept gzip
+        group.bench_with_input(
+            BenchmarkId::new("zstd_to_gzip", size),
+            &compressed,
+            |b, compressed| {
+                b.iter(|| gzip_compress(&zstd_decompress(compressed).unwrap()).unwrap())
+            },
+        );
+    }
+    group.finish();
+}
+
+fn bench_blob_write(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("blob_write");
+    group.sample_size(20);
+    for size in SIZES {
+        let data = random_bytes(size);
+        let blob = TempBlob::new(&format!("write-{size}"));
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &data, |b, data| {
+            b.to_async(&rt).iter(|| async {
+                let mut file = tokio::fs::File::create(&blob.0).await.unwrap();
+                file.write_all(data).await.unwrap();
+                file.flush().await.unwrap();
+            });
+        });
+    }
+    group.finish();
+}
+
+fn bench_download(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("download_body");
+    group.sample_size(20);
+    let budget = Arc::new(MemoryBudget::new(0, Duration::ZERO, 64));
+    for size in SIZES {
+        let blob = TempBlob::new(&format!("read-{size}"));
+        std::fs::write(&blob.0, random_bytes(size)).unwrap();
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::new("unthrottled", size), &blob, |b, blob| {
+            b.to_async(&rt).iter(|| async {
+                let (_, body) = read_blob_body(&budget, &blob.0, None).await.unwrap();
+                to_bytes(body, usize::MAX).await.unwrap().len()
+            });
+        });
+        // A limit far above the disk speed measures the streaming overhead, not the sleeps
+        group.bench_with_input(BenchmarkId::new("streamed", size), &blob, |b, blob| {
+            b.to_async(&rt).iter(|| async {
+                let (_, body) = read_blob_body(&budget, &blob.0, Some(i64::MAX / 2))
+                    .await
+                    .unwrap();
+                to_bytes(body, usize::MAX).await.unwrap().len()
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_multipart,
+    bench_hashing,
+    bench_compression,
+    bench_blob_write,
+    bench_download
+);
+criterion_main!(benches);
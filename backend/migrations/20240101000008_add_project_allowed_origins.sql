@@ -0,0 +1,3 @@
+-- Per-project CORS origins for cross-origin file downloads
+-- '*' allows any origin; empty means only the global CORS_ORIGINS apply
+ALTER TABLE projects ADD COLUMN allowed_origins TEXT[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,4 @@
+-- Per-project country restrictions for downloads (ISO 3166-1 alpha-2 codes)
+-- Requires GEOIP_DATABASE_PATH; ignored when no GeoIP database is configured
+ALTER TABLE projects ADD COLUMN geo_allowed_countries TEXT[] NOT NULL DEFAULT '{}';
+ALTER TABLE projects ADD COLUMN geo_blocked_countries TEXT[] NOT NULL DEFAULT '{}';
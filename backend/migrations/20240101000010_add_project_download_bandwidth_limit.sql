@@ -0,0 +1,2 @@
+-- Optional per-project download throughput cap in bytes/sec (NULL = unlimited)
+ALTER TABLE projects ADD COLUMN download_bandwidth_limit BIGINT CHECK (download_bandwidth_limit > 0);
@@ -0,0 +1,3 @@
+-- Optional per-project cap on simultaneous in-flight uploads
+-- NULL falls back to the server-wide MAX_CONCURRENT_UPLOADS default
+ALTER TABLE projects ADD COLUMN max_concurrent_uploads INTEGER CHECK (max_concurrent_uploads > 0);
@@ -0,0 +1,11 @@
+-- Idempotency keys for POST /api/upload so client retries don't store duplicates
+CREATE TABLE upload_idempotency_keys (
+    project_id UUID NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    idempotency_key VARCHAR(255) NOT NULL,
+    file_id UUID NOT NULL REFERENCES files(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (project_id, idempotency_key)
+);
+
+-- Index for expiry cleanup
+CREATE INDEX idx_upload_idempotency_keys_created_at ON upload_idempotency_keys(created_at);
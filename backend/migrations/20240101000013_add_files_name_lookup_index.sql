@@ -0,0 +1,2 @@
+-- Index for same-name conflict lookups during upload (on_conflict)
+CREATE INDEX idx_files_project_folder_name ON files(project_id, folder_id, original_name);
@@ -0,0 +1,5 @@
+-- SHA-256 of file content (hex), computed on upload; NULL for files uploaded before hashing
+ALTER TABLE files ADD COLUMN content_hash VARCHAR(64);
+
+-- Index for duplicate detection within a project
+CREATE INDEX idx_files_project_content_hash ON files(project_id, content_hash) WHERE content_hash IS NOT NULL;
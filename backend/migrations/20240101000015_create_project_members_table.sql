@@ -0,0 +1,15 @@
+-- Project collaborator roles
+CREATE TYPE project_role AS ENUM ('viewer', 'uploader', 'admin');
+
+-- Registered users invited to a project by its owner
+CREATE TABLE project_members (
+    project_id UUID NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    role project_role NOT NULL DEFAULT 'viewer',
+    invited_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (project_id, user_id)
+);
+
+-- Index for looking up a user's memberships
+CREATE INDEX idx_project_members_user_id ON project_members(user_id);
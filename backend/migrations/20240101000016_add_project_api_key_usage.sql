@@ -0,0 +1,4 @@
+-- API key usage, flushed periodically from an in-memory tracker
+ALTER TABLE projects ADD COLUMN api_key_last_used_at TIMESTAMPTZ;
+ALTER TABLE projects ADD COLUMN api_key_last_used_ip INET;
+ALTER TABLE projects ADD COLUMN api_key_request_count BIGINT NOT NULL DEFAULT 0;
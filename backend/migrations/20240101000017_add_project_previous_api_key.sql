@@ -0,0 +1,6 @@
+-- Previous API key kept valid for a grace period after rotation
+ALTER TABLE projects ADD COLUMN previous_api_key UUID;
+ALTER TABLE projects ADD COLUMN previous_api_key_expires_at TIMESTAMPTZ;
+
+-- Index for lookups by the previous key during the grace period
+CREATE INDEX idx_projects_previous_api_key ON projects(previous_api_key) WHERE previous_api_key IS NOT NULL;
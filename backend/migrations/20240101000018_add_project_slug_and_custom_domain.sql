@@ -0,0 +1,3 @@
+-- Host-based routing for public files: <slug>.<PUBLIC_FILES_DOMAIN> or a custom domain
+ALTER TABLE projects ADD COLUMN slug VARCHAR(63) UNIQUE;
+ALTER TABLE projects ADD COLUMN custom_domain VARCHAR(253) UNIQUE;
@@ -0,0 +1,4 @@
+-- At-rest compression: encoding of the stored blob and its size on disk
+-- (NULL encoding = stored as uploaded; files.size stays the original size)
+ALTER TABLE files ADD COLUMN storage_encoding VARCHAR(16);
+ALTER TABLE files ADD COLUMN stored_size BIGINT;
@@ -0,0 +1,3 @@
+-- Cached gzip/brotli variants of public web assets, stored next to the blob
+-- as <file_path>.gz / <file_path>.br
+ALTER TABLE files ADD COLUMN precompressed_encodings TEXT[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,11 @@
+-- Hot/cold storage tiers: projects opt in with a lifecycle rule moving blobs
+-- to cold storage after N days without access
+ALTER TABLE projects ADD COLUMN cold_storage_after_days INTEGER;
+
+ALTER TABLE files ADD COLUMN storage_tier VARCHAR(8) NOT NULL DEFAULT 'hot';
+ALTER TABLE files ADD COLUMN last_accessed_at TIMESTAMPTZ;
+
+-- Lifecycle sweep scans hot files by last activity
+CREATE INDEX idx_files_hot_last_activity
+    ON files (project_id, (COALESCE(last_accessed_at, upload_date)))
+    WHERE storage_tier = 'hot';
@@ -0,0 +1,27 @@
+-- Admin-triggered copies of all local blobs to the cold storage backend,
+-- followed by a cutover that serves them from there
+CREATE TABLE storage_migrations (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    -- running -> copied -> cutting_over -> completed (or failed)
+    status VARCHAR(16) NOT NULL DEFAULT 'running',
+    total_files BIGINT NOT NULL DEFAULT 0,
+    total_bytes BIGINT NOT NULL DEFAULT 0,
+    copied_files BIGINT NOT NULL DEFAULT 0,
+    copied_bytes BIGINT NOT NULL DEFAULT 0,
+    failed_files BIGINT NOT NULL DEFAULT 0,
+    cutover_files BIGINT NOT NULL DEFAULT 0,
+    last_error TEXT,
+    started_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    finished_at TIMESTAMPTZ,
+    cutover_at TIMESTAMPTZ
+);
+
+-- Blobs copied and verified by a migration; upload_date detects later overwrites
+CREATE TABLE storage_migration_files (
+    migration_id UUID NOT NULL REFERENCES storage_migrations(id) ON DELETE CASCADE,
+    file_id UUID NOT NULL REFERENCES files(id) ON DELETE CASCADE,
+    upload_date TIMESTAMPTZ NOT NULL,
+    sha256 VARCHAR(64) NOT NULL,
+    PRIMARY KEY (migration_id, file_id)
+);
@@ -0,0 +1,26 @@
+-- Backup runs: a manifest of every file (with the database WAL position it was
+-- taken at) plus incremental copies of new or changed blobs to the backup target
+CREATE TABLE backups (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    -- running -> completed (or failed)
+    status VARCHAR(16) NOT NULL DEFAULT 'running',
+    trigger VARCHAR(16) NOT NULL,
+    started_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    wal_lsn TEXT,
+    manifest_path TEXT,
+    file_count BIGINT NOT NULL DEFAULT 0,
+    total_bytes BIGINT NOT NULL DEFAULT 0,
+    blobs_copied BIGINT NOT NULL DEFAULT 0,
+    blobs_bytes BIGINT NOT NULL DEFAULT 0,
+    blobs_failed BIGINT NOT NULL DEFAULT 0,
+    last_error TEXT,
+    started_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    finished_at TIMESTAMPTZ
+);
+
+-- Latest blob version copied to the backup target, per file
+CREATE TABLE backup_blobs (
+    file_id UUID PRIMARY KEY REFERENCES files(id) ON DELETE CASCADE,
+    upload_date TIMESTAMPTZ NOT NULL,
+    backed_up_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,20 @@
+-- File events written in the same transaction as the mutation that caused them,
+-- delivered (at least once) and removed by the background dispatcher
+CREATE TABLE event_outbox (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    event_type VARCHAR(32) NOT NULL,
+    occurred_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    -- Snapshot of the file; no foreign key since deleted files still need their event
+    file_id UUID NOT NULL,
+    project_id UUID NOT NULL,
+    folder_id UUID,
+    original_name VARCHAR(500) NOT NULL,
+    size BIGINT NOT NULL,
+    mime_type VARCHAR(255) NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_error TEXT
+);
+
+CREATE INDEX idx_event_outbox_pending ON event_outbox(next_attempt_at, occurred_at);
+CREATE INDEX idx_event_outbox_file ON event_outbox(file_id);
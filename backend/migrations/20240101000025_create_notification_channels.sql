@@ -0,0 +1,32 @@
+-- Chat webhooks a project sends templated notifications to
+CREATE TYPE notification_kind AS ENUM ('slack', 'discord');
+
+CREATE TABLE notification_channels (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    project_id UUID NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    kind notification_kind NOT NULL,
+    webhook_url TEXT NOT NULL,
+    -- Subscribed events, e.g. {large_upload}
+    events TEXT[] NOT NULL DEFAULT '{}',
+    -- Message template with {placeholders}; NULL uses the built-in one per event
+    template TEXT,
+    -- Minimum size for large_upload; NULL uses the built-in default
+    large_upload_bytes BIGINT,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_notification_channels_project_id ON notification_channels(project_id);
+
+-- Rendered messages waiting to be posted, retried with backoff by the notification worker
+CREATE TABLE notification_jobs (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    channel_id UUID NOT NULL REFERENCES notification_channels(id) ON DELETE CASCADE,
+    message TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_notification_jobs_due ON notification_jobs(next_attempt_at);
@@ -0,0 +1,9 @@
+-- Optional per-project storage cap in bytes (sum of original file sizes)
+ALTER TABLE projects ADD COLUMN storage_quota_bytes BIGINT;
+
+-- Highest quota warning (80, 90 or 100 percent) already emailed to the owner;
+-- lowered again once usage drops so the next crossing warns again
+ALTER TABLE projects ADD COLUMN quota_warning_percent SMALLINT NOT NULL DEFAULT 0;
+
+-- Per-user email notification preferences
+ALTER TABLE users ADD COLUMN notify_quota_warnings BOOLEAN NOT NULL DEFAULT TRUE;
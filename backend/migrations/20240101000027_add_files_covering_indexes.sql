@@ -0,0 +1,11 @@
+-- Covering indexes for large files tables. Project listings (newest first)
+-- and quota usage sums can be served from the index without visiting the heap.
+CREATE INDEX idx_files_project_upload_date ON files(project_id, upload_date DESC)
+    INCLUDE (folder_id, original_name, size, mime_type);
+
+-- Per-folder file counts and sizes
+CREATE INDEX idx_files_folder_size ON files(folder_id) INCLUDE (size);
+
+-- Both are prefixes of the indexes above
+DROP INDEX idx_files_project_id;
+DROP INDEX idx_files_folder_id;
@@ -0,0 +1,11 @@
+-- Every folder path has a record for each of its ancestors (`a`, `a/b` for
+-- `a/b/c`) so trees can be built from the folders table alone. Earlier uploads
+-- only created the deepest folder; backfill the missing ancestors with the
+-- project's visibility, as new top-level folders get.
+INSERT INTO folders (project_id, path, is_public)
+SELECT DISTINCT f.project_id, array_to_string(parts[1:n], '/'), p.is_public
+FROM folders f
+JOIN projects p ON p.id = f.project_id
+CROSS JOIN LATERAL string_to_array(f.path, '/') AS parts
+CROSS JOIN LATERAL generate_series(1, cardinality(parts) - 1) AS n
+ON CONFLICT (project_id, path) DO NOTHING;
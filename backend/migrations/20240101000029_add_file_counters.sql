@@ -0,0 +1,154 @@
+-- Cached file counts and sizes per project and per folder, so listings don't
+-- aggregate the files table on every request. Kept current by statement-level
+-- triggers on files and checked by a periodic reconciliation job.
+CREATE TABLE project_stats (
+    project_id UUID PRIMARY KEY REFERENCES projects(id) ON DELETE CASCADE,
+    file_count BIGINT NOT NULL DEFAULT 0,
+    total_size BIGINT NOT NULL DEFAULT 0
+);
+
+ALTER TABLE folders
+    ADD COLUMN file_count BIGINT NOT NULL DEFAULT 0,
+    ADD COLUMN total_size BIGINT NOT NULL DEFAULT 0;
+
+-- No writes may slip in between the backfill and the triggers
+LOCK TABLE files IN SHARE ROW EXCLUSIVE MODE;
+
+INSERT INTO project_stats (project_id, file_count, total_size)
+SELECT p.id, COUNT(f.id), COALESCE(SUM(f.size), 0)
+FROM projects p
+LEFT JOIN files f ON f.project_id = p.id
+GROUP BY p.id;
+
+UPDATE folders fol
+SET file_count = agg.file_count, total_size = agg.total_size
+FROM (
+    SELECT folder_id, COUNT(*) AS file_count, SUM(size) AS total_size
+    FROM files
+    WHERE folder_id IS NOT NULL
+    GROUP BY folder_id
+) agg
+WHERE fol.id = agg.folder_id;
+
+-- Counter deltas are applied per statement, so bulk deletes and moves update
+-- each project and folder once. Folders are updated before project_stats, the
+-- order in which uploads (which hold their folder's row) take the locks.
+-- Deletes only update existing counters: inserting one could reference a
+-- project that is itself being deleted.
+CREATE FUNCTION files_counters_insert() RETURNS trigger
+LANGUAGE plpgsql AS $$
+BEGIN
+    UPDATE folders fol
+    SET file_count = fol.file_count + d.file_count, total_size = fol.total_size + d.total_size
+    FROM (
+        SELECT folder_id, COUNT(*) AS file_count, SUM(size) AS total_size
+        FROM new_rows
+        WHERE folder_id IS NOT NULL
+        GROUP BY folder_id
+    ) d
+    WHERE fol.id = d.folder_id;
+
+    INSERT INTO project_stats (project_id, file_count, total_size)
+    SELECT project_id, COUNT(*), SUM(size)
+    FROM new_rows
+    GROUP BY project_id
+    ON CONFLICT (project_id) DO UPDATE
+    SET file_count = project_stats.file_count + EXCLUDED.file_count,
+        total_size = project_stats.total_size + EXCLUDED.total_size;
+
+    RETURN NULL;
+END;
+$$;
+
+CREATE FUNCTION files_counters_delete() RETURNS trigger
+LANGUAGE plpgsql AS $$
+BEGIN
+    UPDATE folders fol
+    SET file_count = fol.file_count - d.file_count, total_size = fol.total_size - d.total_size
+    FROM (
+        SELECT folder_id, COUNT(*) AS file_count, SUM(size) AS total_size
+        FROM old_rows
+        WHERE folder_id IS NOT NULL
+        GROUP BY folder_id
+    ) d
+    WHERE fol.id = d.folder_id;
+
+    UPDATE project_stats s
+    SET file_count = s.file_count - d.file_count, total_size = s.total_size - d.total_size
+    FROM (
+        SELECT project_id, COUNT(*) AS file_count, SUM(size) AS total_size
+        FROM old_rows
+        GROUP BY project_id
+    ) d
+    WHERE s.project_id = d.project_id;
+
+    RETURN NULL;
+END;
+$$;
+
+-- Most updates (access times, storage tier) change no counted column and
+-- produce no deltas, so they never lock the counters
+CREATE FUNCTION files_counters_update() RETURNS trigger
+LANGUAGE plpgsql AS $$
+BEGIN
+    WITH changed AS (
+        SELECT o.folder_id AS old_folder_id, o.size AS old_size,
+               n.folder_id AS new_folder_id, n.size AS new_size
+        FROM old_rows o
+        JOIN new_rows n ON n.id = o.id
+        WHERE (o.folder_id, o.size) IS DISTINCT FROM (n.folder_id, n.size)
+    ), deltas AS (
+        SELECT old_folder_id AS folder_id, -1 AS file_count, -old_size AS total_size FROM changed
+        UNION ALL
+        SELECT new_folder_id, 1, new_size FROM changed
+    )
+    UPDATE folders fol
+    SET file_count = fol.file_count + d.file_count, total_size = fol.total_size + d.total_size
+    FROM (
+        SELECT folder_id, SUM(file_count) AS file_count, SUM(total_size) AS total_size
+        FROM deltas
+        WHERE folder_id IS NOT NULL
+        GROUP BY folder_id
+        HAVING SUM(file_count) <> 0 OR SUM(total_size) <> 0
+    ) d
+    WHERE fol.id = d.folder_id;
+
+    WITH changed AS (
+        SELECT o.project_id AS old_project_id, o.size AS old_size,
+               n.project_id AS new_project_id, n.size AS new_size
+        FROM old_rows o
+        JOIN new_rows n ON n.id = o.id
+        WHERE (o.project_id, o.size) IS DISTINCT FROM (n.project_id, n.size)
+    ), deltas AS (
+        SELECT old_project_id AS project_id, -1 AS file_count, -old_size AS total_size FROM changed
+        UNION ALL
+        SELECT new_project_id, 1, new_size FROM changed
+    )
+    UPDATE project_stats s
+    SET file_count = s.file_count + d.file_count, total_size = s.total_size + d.total_size
+    FROM (
+        SELECT project_id, SUM(file_count) AS file_count, SUM(total_size) AS total_size
+        FROM deltas
+        GROUP BY project_id
+        HAVING SUM(file_count) <> 0 OR SUM(total_size) <> 0
+    ) d
+    WHERE s.project_id = d.project_id;
+
+    RETURN NULL;
+END;
+$$;
+
+CREATE TRIGGER files_counters_insert
+    AFTER INSERT ON files
+    REFERENCING NEW TABLE AS new_rows
+    FOR EACH STATEMENT EXECUTE FUNCTION files_counters_insert();
+
+CREATE TRIGGER files_counters_delete
+    AFTER DELETE ON files
+    REFERENCING OLD TABLE AS old_rows
+    FOR EACH STATEMENT EXECUTE FUNCTION files_counters_delete();
+
+CREATE TRIGGER files_counters_update
+    AFTER UPDATE ON files
+    REFERENCING OLD TABLE AS old_rows NEW TABLE AS new_rows
+    FOR EACH STATEMENT EXECUTE FUNCTION files_counters_update();
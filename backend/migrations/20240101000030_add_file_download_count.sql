@@ -0,0 +1,6 @@
+-- Downloads per file, flushed in batches from the server's in-memory counter
+-- (last_accessed_at is updated by the same flush)
+ALTER TABLE files ADD COLUMN download_count BIGINT NOT NULL DEFAULT 0;
+
+-- Project listings sorted by popularity
+CREATE INDEX idx_files_project_download_count ON files(project_id, download_count DESC);
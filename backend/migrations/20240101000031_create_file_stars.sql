@@ -0,0 +1,10 @@
+-- Files a user starred, for the dashboard's starred list
+CREATE TABLE file_stars (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    file_id UUID NOT NULL REFERENCES files(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, file_id)
+);
+
+-- Star cleanup when files are deleted
+CREATE INDEX idx_file_stars_file_id ON file_stars(file_id);
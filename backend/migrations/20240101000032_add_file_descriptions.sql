@@ -0,0 +1,15 @@
+-- Free-text note on why a file exists, set at upload or via PATCH /api/v1/files/:id
+ALTER TABLE files ADD COLUMN description TEXT;
+
+-- Search document of a file: the words of its name and description, split on
+-- anything that isn't a letter or digit (`report-2024.csv` becomes `report`,
+-- `2024`, `csv`). Queries must call this function so they can use the index.
+CREATE FUNCTION file_search_document(name TEXT, description TEXT) RETURNS tsvector
+LANGUAGE sql IMMUTABLE PARALLEL SAFE AS $$
+    SELECT to_tsvector(
+        'simple',
+        regexp_replace(name || ' ' || COALESCE(description, ''), '[^[:alnum:]]+', ' ', 'g')
+    )
+$$;
+
+CREATE INDEX idx_files_search ON files USING GIN (file_search_document(original_name, description));
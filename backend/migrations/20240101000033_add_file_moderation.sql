@@ -0,0 +1,22 @@
+-- Uploads inspected by the moderation service (MODERATION_URL) stay
+-- `pending_review` until it or a project admin approves them; only approved
+-- files are served to readers
+CREATE TYPE moderation_status AS ENUM ('approved', 'pending_review', 'rejected');
+
+ALTER TABLE files
+    ADD COLUMN moderation_status moderation_status NOT NULL DEFAULT 'approved',
+    ADD COLUMN moderation_reason TEXT;
+
+CREATE INDEX idx_files_project_moderation ON files (project_id, moderation_status)
+    WHERE moderation_status <> 'approved';
+
+-- Files waiting for the moderation service; failed calls are retried with backoff
+CREATE TABLE moderation_jobs (
+    file_id UUID PRIMARY KEY REFERENCES files(id) ON DELETE CASCADE,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_moderation_jobs_due ON moderation_jobs(next_attempt_at);
@@ -0,0 +1,65 @@
+-- Legal hold: a held file, or any file in a held folder or its subfolders,
+-- can't be deleted or overwritten until the hold is released. The triggers
+-- below enforce this for every path, including project and user cascades;
+-- they raise SQLSTATE LH001, which the API reports as 423 Locked.
+ALTER TABLE files ADD COLUMN legal_hold BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE folders ADD COLUMN legal_hold BOOLEAN NOT NULL DEFAULT false;
+
+CREATE INDEX idx_folders_legal_hold ON folders (project_id) WHERE legal_hold;
+
+-- Whether a folder or one of its ancestors is held
+CREATE FUNCTION folder_under_legal_hold(target_folder_id UUID) RETURNS BOOLEAN
+LANGUAGE sql STABLE AS $$
+    SELECT EXISTS (
+        SELECT 1
+        FROM folders f
+        JOIN folders held ON held.project_id = f.project_id AND held.legal_hold
+        WHERE f.id = target_folder_id
+          AND (held.path = f.path OR starts_with(f.path, held.path || '/'))
+    )
+$$;
+
+CREATE FUNCTION files_enforce_legal_hold() RETURNS trigger
+LANGUAGE plpgsql AS $$
+BEGIN
+    IF TG_OP = 'UPDATE'
+        AND NEW.content_hash IS NOT DISTINCT FROM OLD.content_hash
+        AND NEW.size = OLD.size
+        AND NEW.stored_name = OLD.stored_name
+    THEN
+        RETURN NEW;
+    END IF;
+    IF OLD.legal_hold OR folder_under_legal_hold(OLD.folder_id) THEN
+        RAISE EXCEPTION 'File "%" is under legal hold', OLD.original_name
+            USING ERRCODE = 'LH001';
+    END IF;
+    IF TG_OP = 'DELETE' THEN
+        RETURN OLD;
+    END IF;
+    RETURN NEW;
+END;
+$$;
+
+CREATE TRIGGER files_legal_hold_delete
+    BEFORE DELETE ON files
+    FOR EACH ROW EXECUTE FUNCTION files_enforce_legal_hold();
+
+-- Overwrites replace the content in place (same row, new hash/size/blob)
+CREATE TRIGGER files_legal_hold_overwrite
+    BEFORE UPDATE OF content_hash, size, stored_name ON files
+    FOR EACH ROW EXECUTE FUNCTION files_enforce_legal_hold();
+
+CREATE FUNCTION folders_enforce_legal_hold() RETURNS trigger
+LANGUAGE plpgsql AS $$
+BEGIN
+    IF folder_under_legal_hold(OLD.id) THEN
+        RAISE EXCEPTION 'Folder "%" is under legal hold', OLD.path
+            USING ERRCODE = 'LH001';
+    END IF;
+    RETURN OLD;
+END;
+$$;
+
+CREATE TRIGGER folders_legal_hold_delete
+    BEFORE DELETE ON folders
+    FOR EACH ROW EXECUTE FUNCTION folders_enforce_legal_hold();
@@ -0,0 +1,3 @@
+-- Archived projects are read-only: uploads, moves and deletes are refused
+-- while downloads and listings keep working
+ALTER TABLE projects ADD COLUMN archived BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,23 @@
+-- Blob cleanup for deleted projects. The project's rows go with the project;
+-- a background worker then removes <storage_path>/<project_id> and the
+-- project's cold storage objects, recording its progress here.
+CREATE TABLE project_purges (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    -- No foreign key: the project is gone by the time the purge runs
+    project_id UUID NOT NULL,
+    project_name VARCHAR(255) NOT NULL,
+    requested_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    -- pending -> running -> completed (or failed)
+    status VARCHAR(16) NOT NULL DEFAULT 'pending',
+    total_blobs BIGINT NOT NULL DEFAULT 0,
+    total_bytes BIGINT NOT NULL DEFAULT 0,
+    removed_blobs BIGINT NOT NULL DEFAULT 0,
+    removed_bytes BIGINT NOT NULL DEFAULT 0,
+    failed_blobs BIGINT NOT NULL DEFAULT 0,
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    started_at TIMESTAMPTZ,
+    finished_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_project_purges_pending ON project_purges (created_at) WHERE status = 'pending';
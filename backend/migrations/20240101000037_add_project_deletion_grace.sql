@@ -0,0 +1,10 @@
+-- Deleting a project schedules it for deletion instead of removing it at once.
+-- Until deletion_scheduled_at it is frozen and can be restored; then the purge
+-- worker deletes the project row and its blobs.
+ALTER TABLE projects ADD COLUMN deletion_scheduled_at TIMESTAMPTZ;
+
+-- Purges wait out the grace period; a restore sets them to 'cancelled'
+ALTER TABLE project_purges ADD COLUMN run_after TIMESTAMPTZ NOT NULL DEFAULT NOW();
+
+DROP INDEX idx_project_purges_pending;
+CREATE INDEX idx_project_purges_pending ON project_purges (run_after) WHERE status = 'pending';
@@ -0,0 +1,30 @@
+-- Multipart uploads: a large file is sent as numbered parts, in parallel and in
+-- any order, and assembled into a regular file when the client completes it.
+-- Part blobs live under <storage_path>/<project_id>/.multipart/<upload_id>.
+CREATE TABLE multipart_uploads (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    project_id UUID NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    file_name VARCHAR(255) NOT NULL,
+    folder_path TEXT,
+    description TEXT,
+    on_conflict VARCHAR(16),
+    -- uploading -> completing (parts being assembled); removed once completed
+    status VARCHAR(16) NOT NULL DEFAULT 'uploading',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    -- Pushed back by every part; idle uploads are aborted after it
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX idx_multipart_uploads_project ON multipart_uploads (project_id);
+CREATE INDEX idx_multipart_uploads_expires ON multipart_uploads (expires_at) WHERE status = 'uploading';
+
+CREATE TABLE multipart_upload_parts (
+    upload_id UUID NOT NULL REFERENCES multipart_uploads(id) ON DELETE CASCADE,
+    part_number INTEGER NOT NULL,
+    -- Re-uploading a part writes a new blob, so a completion never reads a half-written one
+    stored_name VARCHAR(255) NOT NULL,
+    size BIGINT NOT NULL,
+    sha256 VARCHAR(64) NOT NULL,
+    uploaded_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (upload_id, part_number)
+);
@@ -0,0 +1,38 @@
+-- Integrity scrubbing: a periodic job re-reads stored blobs and compares them
+-- with the recorded size and SHA-256, least recently verified files first.
+ALTER TABLE files ADD COLUMN integrity_checked_at TIMESTAMPTZ;
+
+CREATE INDEX idx_files_integrity_checked_at ON files (integrity_checked_at NULLS FIRST);
+
+CREATE TABLE integrity_runs (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    -- running -> completed (or failed)
+    status VARCHAR(16) NOT NULL DEFAULT 'running',
+    -- Files the run was asked to check; NULL checks every file
+    sample_size BIGINT,
+    checked_files BIGINT NOT NULL DEFAULT 0,
+    checked_bytes BIGINT NOT NULL DEFAULT 0,
+    corrupted_files BIGINT NOT NULL DEFAULT 0,
+    -- Files that couldn't be checked (e.g. cold storage unreachable)
+    skipped_files BIGINT NOT NULL DEFAULT 0,
+    last_error TEXT,
+    started_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    finished_at TIMESTAMPTZ
+);
+
+-- A problem found with a file's blob. It stays open until a later check of the
+-- file passes again (e.g. after restoring the blob from a backup).
+CREATE TABLE integrity_failures (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    file_id UUID NOT NULL REFERENCES files(id) ON DELETE CASCADE,
+    project_id UUID NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    run_id UUID REFERENCES integrity_runs(id) ON DELETE SET NULL,
+    -- missing, size_mismatch, unreadable or hash_mismatch
+    problem VARCHAR(32) NOT NULL,
+    detail TEXT NOT NULL,
+    detected_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_seen_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    resolved_at TIMESTAMPTZ
+);
+
+CREATE UNIQUE INDEX idx_integrity_failures_open ON integrity_failures (file_id) WHERE resolved_at IS NULL;
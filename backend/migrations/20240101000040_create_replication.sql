@@ -0,0 +1,27 @@
+-- Replication to a standby instance: blobs of changed files are pushed to the
+-- peer asynchronously. The standby shares this database, so only blobs move.
+
+-- Files whose blob changed (uploaded, overwritten, moved or deleted) since it
+-- was last pushed; the worker compares the file with `replicated_blobs` to
+-- decide what to send
+CREATE TABLE replication_queue (
+    file_id UUID PRIMARY KEY,
+    -- Requeueing a file bumps this, so a push that raced a change is redone
+    queued_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    attempts INT NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_error TEXT
+);
+
+CREATE INDEX idx_replication_queue_next_attempt ON replication_queue (next_attempt_at);
+
+-- What the peer holds. No foreign key: rows outlive deleted files until the
+-- peer's copy is removed too.
+CREATE TABLE replicated_blobs (
+    file_id UUID PRIMARY KEY,
+    file_path TEXT NOT NULL,
+    upload_date TIMESTAMPTZ NOT NULL,
+    -- Precompressed variants pushed alongside the blob (`br`, `gzip`)
+    variants TEXT[] NOT NULL DEFAULT '{}',
+    replicated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
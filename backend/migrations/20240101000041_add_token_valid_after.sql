@@ -0,0 +1,4 @@
+-- Access tokens issued before this time are rejected, so logging out
+-- everywhere or changing the password cuts off sessions right away instead
+-- of when their access tokens expire
+ALTER TABLE users ADD COLUMN token_valid_after TIMESTAMPTZ;
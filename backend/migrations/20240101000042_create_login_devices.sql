@@ -0,0 +1,35 @@
+-- Devices and networks each user has signed in from, so a login from a new
+-- device or country can be flagged
+CREATE TABLE login_devices (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- User-Agent with version numbers removed, so browser updates don't count as new
+    device TEXT NOT NULL,
+    -- /24 (IPv4) or /48 (IPv6) network the login came from
+    ip_prefix CIDR NOT NULL,
+    country TEXT,
+    first_seen_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_seen_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (user_id, device, ip_prefix)
+);
+
+CREATE INDEX idx_login_devices_last_seen ON login_devices (last_seen_at);
+
+-- Logins from a new device or location. The alert email carries a token
+-- (stored hashed) to report the login, which signs its session out.
+CREATE TABLE login_alerts (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- Refresh token family of the session the login started
+    family_id UUID NOT NULL,
+    user_agent TEXT,
+    ip_address INET NOT NULL,
+    country TEXT,
+    new_device BOOLEAN NOT NULL,
+    new_location BOOLEAN NOT NULL,
+    report_token_hash TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    reported_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_login_alerts_created_at ON login_alerts (created_at);
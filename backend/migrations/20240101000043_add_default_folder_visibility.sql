@@ -0,0 +1,23 @@
+-- Visibility a project gives new folders:
+-- - inherit: the parent folder's, or the project's for top-level folders
+-- - project: the project's `is_public` at the time the folder is created
+-- - public / private: always that
+CREATE TYPE folder_visibility AS ENUM ('inherit', 'project', 'public', 'private');
+
+ALTER TABLE projects
+    ADD COLUMN default_folder_visibility folder_visibility NOT NULL DEFAULT 'inherit';
+
+-- `is_public` for a new folder under `rule`; `parent_public` is NULL at top level
+CREATE FUNCTION new_folder_is_public(
+    rule folder_visibility,
+    project_public BOOLEAN,
+    parent_public BOOLEAN
+) RETURNS BOOLEAN
+LANGUAGE sql IMMUTABLE AS $$
+    SELECT CASE rule
+        WHEN 'public' THEN TRUE
+        WHEN 'private' THEN FALSE
+        WHEN 'project' THEN project_public
+        ELSE COALESCE(parent_public, project_public)
+    END
+$$;
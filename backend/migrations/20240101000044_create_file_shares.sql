@@ -0,0 +1,25 @@
+-- Links that let anyone holding the token download one file until they
+-- expire. The token is only stored hashed.
+CREATE TABLE file_shares (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    file_id UUID NOT NULL REFERENCES files(id) ON DELETE CASCADE,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    message TEXT,
+    expires_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_file_shares_file_id ON file_shares (file_id);
+CREATE INDEX idx_file_shares_expires_at ON file_shares (expires_at);
+
+-- Who a share link was emailed to, for the audit trail
+CREATE TABLE file_share_recipients (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    share_id UUID NOT NULL REFERENCES file_shares(id) ON DELETE CASCADE,
+    email TEXT NOT NULL,
+    -- Set once the mail server accepted the message
+    sent_at TIMESTAMPTZ,
+    error TEXT,
+    UNIQUE (share_id, email)
+);
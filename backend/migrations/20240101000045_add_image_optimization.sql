@@ -0,0 +1,45 @@
+-- Upload-time image optimization. NULL image_max_dimension leaves image sizes alone.
+ALTER TABLE projects
+    ADD COLUMN image_max_dimension INTEGER,
+    ADD COLUMN image_quality INTEGER NOT NULL DEFAULT 85,
+    ADD COLUMN image_png_to_webp BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN image_keep_original BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Earlier content of a file kept next to its current content. Blobs live
+-- under <storage>/<project>/.versions/.
+CREATE TABLE file_versions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    file_id UUID NOT NULL REFERENCES files(id) ON DELETE CASCADE,
+    original_name TEXT NOT NULL,
+    file_path TEXT NOT NULL,
+    size BIGINT NOT NULL,
+    mime_type TEXT NOT NULL,
+    content_hash TEXT NOT NULL,
+    -- Why the version was kept, e.g. 'original' for an upload before image optimization
+    reason TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_file_versions_file_id ON file_versions (file_id, created_at DESC);
+
+-- Blobs of removed versions, however the row went (file delete, project
+-- purge, ...); the version_blob_cleanup job deletes them from disk
+CREATE TABLE version_blob_deletions (
+    file_path TEXT PRIMARY KEY,
+    queued_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE FUNCTION queue_version_blob_deletions() RETURNS trigger
+LANGUAGE plpgsql AS $$
+BEGIN
+    INSERT INTO version_blob_deletions (file_path)
+    SELECT file_path FROM old_rows
+    ON CONFLICT (file_path) DO NOTHING;
+    RETURN NULL;
+END;
+$$;
+
+CREATE TRIGGER file_versions_queue_blob_deletions
+    AFTER DELETE ON file_versions
+    REFERENCING OLD TABLE AS old_rows
+    FOR EACH STATEMENT EXECUTE FUNCTION queue_version_blob_deletions();
@@ -0,0 +1,65 @@
+-- Blobs derived from files (kept versions, media previews) share one
+-- deletion queue, drained by the derived_blob_cleanup job
+ALTER TABLE version_blob_deletions RENAME TO derived_blob_deletions;
+
+CREATE OR REPLACE FUNCTION queue_version_blob_deletions() RETURNS trigger
+LANGUAGE plpgsql AS $$
+BEGIN
+    INSERT INTO derived_blob_deletions (file_path)
+    SELECT file_path FROM old_rows
+    ON CONFLICT (file_path) DO NOTHING;
+    RETURN NULL;
+END;
+$$;
+
+-- Previews generated in the background for media uploads: a poster frame and
+-- optionally a short animated clip for videos. Blobs live under
+-- <storage>/<project>/.previews/ with a fresh name per generation.
+CREATE TABLE media_previews (
+    file_id UUID PRIMARY KEY REFERENCES files(id) ON DELETE CASCADE,
+    status VARCHAR(20) NOT NULL DEFAULT 'pending'
+        CHECK (status IN ('pending', 'ready', 'failed')),
+    poster_path TEXT,
+    animation_path TEXT,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_error TEXT,
+    -- Identifies this queueing of the file; new content requeues it with a new time
+    queued_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    generated_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_media_previews_due ON media_previews (next_attempt_at)
+    WHERE status = 'pending';
+
+-- Queue preview blobs that were dropped, whether the row went with its file
+-- or was requeued for new content
+CREATE FUNCTION queue_media_preview_blob_deletions() RETURNS trigger
+LANGUAGE plpgsql AS $$
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        INSERT INTO derived_blob_deletions (file_path)
+        SELECT path FROM old_rows, unnest(ARRAY[poster_path, animation_path]) AS path
+        WHERE path IS NOT NULL
+        ON CONFLICT (file_path) DO NOTHING;
+    ELSE
+        INSERT INTO derived_blob_deletions (file_path)
+        SELECT path FROM old_rows, unnest(ARRAY[poster_path, animation_path]) AS path
+        WHERE path IS NOT NULL
+        EXCEPT
+        SELECT path FROM new_rows, unnest(ARRAY[poster_path, animation_path]) AS path
+        ON CONFLICT (file_path) DO NOTHING;
+    END IF;
+    RETURN NULL;
+END;
+$$;
+
+CREATE TRIGGER media_previews_queue_blob_deletions_on_delete
+    AFTER DELETE ON media_previews
+    REFERENCING OLD TABLE AS old_rows
+    FOR EACH STATEMENT EXECUTE FUNCTION queue_media_preview_blob_deletions();
+
+CREATE TRIGGER media_previews_queue_blob_deletions_on_update
+    AFTER UPDATE ON media_previews
+    REFERENCING OLD TABLE AS old_rows NEW TABLE AS new_rows
+    FOR EACH STATEMENT EXECUTE FUNCTION queue_media_preview_blob_deletions();
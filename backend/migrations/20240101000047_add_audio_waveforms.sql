@@ -0,0 +1,4 @@
+-- Waveform peaks (0..1, largest sample per span) generated for audio uploads
+ALTER TABLE media_previews
+    ADD COLUMN waveform_peaks REAL[],
+    ADD COLUMN duration_secs DOUBLE PRECISION;
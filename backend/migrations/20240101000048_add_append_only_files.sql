@@ -0,0 +1,9 @@
+-- Append-only files (logs, CSV exports) only grow through
+-- POST /files/:id/append. append_hash_state is the SHA-256 state after the
+-- content's whole 64-byte blocks, so an append only hashes the new bytes; it
+-- is only valid while content_hash still equals append_hash_for, as any
+-- other write replaces the content.
+ALTER TABLE files
+    ADD COLUMN append_only BOOLEAN NOT NULL DEFAULT false,
+    ADD COLUMN append_hash_state BYTEA,
+    ADD COLUMN append_hash_for TEXT;
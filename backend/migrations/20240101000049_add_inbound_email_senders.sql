@@ -0,0 +1,4 @@
+-- Senders allowed to email attachments into a project: addresses, or
+-- @domain for a whole domain. Empty turns inbound email off for the project.
+ALTER TABLE projects
+    ADD COLUMN inbound_email_senders TEXT[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,27 @@
+-- Mirrors: a project that pulls the files of a project on another FileRunner
+-- instance, following its manifest
+CREATE TABLE project_mirrors (
+    project_id UUID PRIMARY KEY REFERENCES projects(id) ON DELETE CASCADE,
+    remote_url TEXT NOT NULL,
+    remote_project_id UUID NOT NULL,
+    remote_api_key TEXT NOT NULL,
+    -- Manifest version of the last complete sync, sent as If-None-Match
+    manifest_version TEXT,
+    last_synced_at TIMESTAMPTZ,
+    last_error TEXT,
+    -- Set while an instance syncs the mirror; stale claims are taken over
+    sync_started_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Local copies of remote files, with the remote entry they were pulled from.
+-- Deleting the copy or the mirror removes the row.
+CREATE TABLE mirrored_files (
+    file_id UUID PRIMARY KEY REFERENCES files(id) ON DELETE CASCADE,
+    project_id UUID NOT NULL REFERENCES project_mirrors(project_id) ON DELETE CASCADE,
+    remote_file_id UUID NOT NULL,
+    remote_path TEXT NOT NULL,
+    remote_sha256 VARCHAR(64),
+    remote_modified_at TIMESTAMPTZ NOT NULL,
+    UNIQUE (project_id, remote_file_id)
+);
@@ -0,0 +1,13 @@
+-- CDN cache purges: URLs of public files that were overwritten, moved or
+-- deleted, waiting to be sent to the configured CDN. Jobs are retried with
+-- backoff and dropped after a few failed attempts.
+CREATE TABLE cdn_purge_jobs (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    urls TEXT[] NOT NULL,
+    attempts INT NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_cdn_purge_jobs_due ON cdn_purge_jobs(next_attempt_at);
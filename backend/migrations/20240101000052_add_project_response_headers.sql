@@ -0,0 +1,3 @@
+-- Extra headers sent with a project's downloads, e.g. Content-Security-Policy
+-- or X-Robots-Tag: a JSON object of lowercase header name to value
+ALTER TABLE projects ADD COLUMN response_headers JSONB NOT NULL DEFAULT '{}';
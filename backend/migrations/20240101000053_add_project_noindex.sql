@@ -0,0 +1,3 @@
+-- Keep search engines from indexing a project's files: downloads carry
+-- X-Robots-Tag: noindex and the project's hosts serve a disallowing robots.txt
+ALTER TABLE projects ADD COLUMN noindex BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,17 @@
+-- Events kept after delivery for EVENT_RETENTION_DAYS, so they can be replayed
+-- to consumers that missed them
+CREATE TABLE delivered_events (
+    id UUID PRIMARY KEY,
+    event_type VARCHAR(32) NOT NULL,
+    occurred_at TIMESTAMPTZ NOT NULL,
+    file_id UUID NOT NULL,
+    project_id UUID NOT NULL,
+    folder_id UUID,
+    original_name VARCHAR(500) NOT NULL,
+    size BIGINT NOT NULL,
+    mime_type VARCHAR(255) NOT NULL,
+    delivered_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_delivered_events_project ON delivered_events(project_id, occurred_at);
+CREATE INDEX idx_delivered_events_delivered_at ON delivered_events(delivered_at);
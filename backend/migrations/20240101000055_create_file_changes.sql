@@ -0,0 +1,75 @@
+-- Log of file creations, updates and deletions for the changes feed, written
+-- by statement-level triggers on files. Rows get their feed position (`seq`)
+-- only once committed, in order, so a reader never sees a position before
+-- an earlier one has become visible.
+CREATE TABLE file_changes (
+    id BIGSERIAL PRIMARY KEY,
+    seq BIGINT UNIQUE,
+    project_id UUID NOT NULL,
+    file_id UUID NOT NULL,
+    change_type VARCHAR(16) NOT NULL,
+    changed_at TIMESTAMPTZ NOT NULL DEFAULT clock_timestamp()
+);
+
+CREATE INDEX idx_file_changes_project_seq ON file_changes(project_id, seq);
+CREATE INDEX idx_file_changes_unnumbered ON file_changes(id) WHERE seq IS NULL;
+CREATE INDEX idx_file_changes_changed_at ON file_changes(changed_at);
+
+-- Highest position handed out, and the highest one purged by retention
+CREATE TABLE file_change_positions (
+    id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    numbered_through BIGINT NOT NULL DEFAULT 0,
+    purged_through BIGINT NOT NULL DEFAULT 0
+);
+INSERT INTO file_change_positions DEFAULT VALUES;
+
+CREATE FUNCTION files_changes_insert() RETURNS trigger
+LANGUAGE plpgsql AS $$
+BEGIN
+    INSERT INTO file_changes (project_id, file_id, change_type)
+    SELECT project_id, id, 'created' FROM new_rows;
+    RETURN NULL;
+END;
+$$;
+
+CREATE FUNCTION files_changes_delete() RETURNS trigger
+LANGUAGE plpgsql AS $$
+BEGIN
+    INSERT INTO file_changes (project_id, file_id, change_type)
+    SELECT project_id, id, 'deleted' FROM old_rows;
+    RETURN NULL;
+END;
+$$;
+
+-- Only what a sync client sees counts: access times, download counts and the
+-- storage tier change all the time without changing the file
+CREATE FUNCTION files_changes_update() RETURNS trigger
+LANGUAGE plpgsql AS $$
+BEGIN
+    INSERT INTO file_changes (project_id, file_id, change_type)
+    SELECT n.project_id, n.id, 'updated'
+    FROM old_rows o
+    JOIN new_rows n ON n.id = o.id
+    WHERE (o.folder_id, o.original_name, o.size, o.content_hash, o.mime_type, o.description,
+           o.moderation_status, o.upload_date)
+        IS DISTINCT FROM
+          (n.folder_id, n.original_name, n.size, n.content_hash, n.mime_type, n.description,
+           n.moderation_status, n.upload_date);
+    RETURN NULL;
+END;
+$$;
+
+CREATE TRIGGER files_changes_insert
+    AFTER INSERT ON files
+    REFERENCING NEW TABLE AS new_rows
+    FOR EACH STATEMENT EXECUTE FUNCTION files_changes_insert();
+
+CREATE TRIGGER files_changes_delete
+    AFTER DELETE ON files
+    REFERENCING OLD TABLE AS old_rows
+    FOR EACH STATEMENT EXECUTE FUNCTION files_changes_delete();
+
+CREATE TRIGGER files_changes_update
+    AFTER UPDATE ON files
+    REFERENCING OLD TABLE AS old_rows NEW TABLE AS new_rows
+    FOR EACH STATEMENT EXECUTE FUNCTION files_changes_update();
@@ -0,0 +1,29 @@
+-- A custom domain only serves files once the owner proves control of it with
+-- a DNS TXT record holding the token. Setting or changing the domain issues a
+-- new token and clears the verification, whoever writes the row.
+ALTER TABLE projects
+    ADD COLUMN custom_domain_verification_token TEXT,
+    ADD COLUMN custom_domain_verified_at TIMESTAMPTZ;
+
+-- Domains claimed before verification existed must be verified too
+UPDATE projects
+SET custom_domain_verification_token = replace(gen_random_uuid()::text, '-', '')
+WHERE custom_domain IS NOT NULL;
+
+CREATE FUNCTION projects_reset_custom_domain_verification() RETURNS trigger
+LANGUAGE plpgsql AS $$
+BEGIN
+    IF TG_OP = 'INSERT' OR NEW.custom_domain IS DISTINCT FROM OLD.custom_domain THEN
+        NEW.custom_domain_verified_at := NULL;
+        NEW.custom_domain_verification_token := CASE
+            WHEN NEW.custom_domain IS NULL THEN NULL
+            ELSE replace(gen_random_uuid()::text, '-', '')
+        END;
+    END IF;
+    RETURN NEW;
+END;
+$$;
+
+CREATE TRIGGER projects_reset_custom_domain_verification
+    BEFORE INSERT OR UPDATE OF custom_domain ON projects
+    FOR EACH ROW EXECUTE FUNCTION projects_reset_custom_domain_verification();
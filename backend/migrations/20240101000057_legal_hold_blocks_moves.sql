@@ -0,0 +1,33 @@
+-- Legal hold also blocks moves: a held file, or one in a held folder, could
+-- otherwise be moved out from under the hold and then deleted. A move
+-- changes folder_id and file_path, so the overwrite trigger watches those too.
+CREATE OR REPLACE FUNCTION files_enforce_legal_hold() RETURNS trigger
+LANGUAGE plpgsql AS $$
+BEGIN
+    IF TG_OP = 'UPDATE'
+        AND NEW.content_hash IS NOT DISTINCT FROM OLD.content_hash
+        AND NEW.size = OLD.size
+        AND NEW.stored_name = OLD.stored_name
+        AND NEW.folder_id IS NOT DISTINCT FROM OLD.folder_id
+        AND NEW.file_path = OLD.file_path
+    THEN
+        RETURN NEW;
+    END IF;
+    IF OLD.legal_hold OR folder_under_legal_hold(OLD.folder_id) THEN
+        RAISE EXCEPTION 'File "%" is under legal hold', OLD.original_name
+            USING ERRCODE = 'LH001';
+    END IF;
+    IF TG_OP = 'DELETE' THEN
+        RETURN OLD;
+    END IF;
+    RETURN NEW;
+END;
+$$;
+
+DROP TRIGGER files_legal_hold_overwrite ON files;
+
+-- Overwrites replace the content in place (same row, new hash/size/blob);
+-- moves change the folder and path
+CREATE TRIGGER files_legal_hold_overwrite
+    BEFORE UPDATE OF content_hash, size, stored_name, folder_id, file_path ON files
+    FOR EACH ROW EXECUTE FUNCTION files_enforce_legal_hold();
@@ -0,0 +1,3 @@
+-- Total size the client expects to send, so progress can be shown as a share
+-- of it; optional and only informational
+ALTER TABLE multipart_uploads ADD COLUMN expected_size BIGINT;
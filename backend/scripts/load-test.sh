@@ -0,0 +1,62 @@
+#!/usr/bin/env bash
+# Load test the upload and download endpoints of a running server with oha
+# (https://github.com/hatoo/oha).
+#
+# Usage: API_KEY=<project api key> ./scripts/load-test.sh [duration] [concurrency]
+#
+# Environment:
+#   API_KEY      project API key (required)
+#   BASE_URL     server URL (default http://localhost:8000)
+#   SIZES        payload sizes in KiB (default "64 1024")
+#   UPLOAD_QPS   upload request rate; /api/upload is rate limited per IP (default 1)
+set -euo pipefail
+
+DURATION="${1:-30s}"
+CONCURRENCY="${2:-50}"
+BASE_URL="${BASE_URL:-http://localhost:8000}"
+SIZES="${SIZES:-64 1024}"
+UPLOAD_QPS="${UPLOAD_QPS:-1}"
+BOUNDARY="filerunner-load-test"
+
+: "${API_KEY:?API_KEY must be set to a project API key}"
+for tool in oha curl jq; do
+    command -v "$tool" >/dev/null || { echo "$tool is required" >&2; exit 1; }
+done
+
+WORK_DIR="$(mktemp -d)"
+trap 'rm -rf "$WORK_DIR"' EXIT
+
+for kib in $SIZES; do
+    payload="$WORK_DIR/payload-${kib}k.bin"
+    head -c "$((kib * 1024))" /dev/urandom > "$payload"
+
+    # Multipart body shaped like the SDK's uploads, reused for every request
+    body="$WORK_DIR/upload-${kib}k.multipart"
+    {
+        printf -- '--%s\r\nContent-Disposition: form-data; name="folder_path"\r\n\r\nload-test\r\n' "$BOUNDARY"
+        printf -- '--%s\r\nContent-Disposition: form-data; name="on_conflict"\r\n\r\nrename\r\n' "$BOUNDARY"
+        printf -- '--%s\r\nContent-Disposition: form-data; name="file"; filename="payload-%sk.bin"\r\n' "$BOUNDARY" "$kib"
+        printf 'Content-Type: application/octet-stream\r\n\r\n'
+        cat "$payload"
+        printf -- '\r\n--%s--\r\n' "$BOUNDARY"
+    } > "$body"
+
+    echo "== Upload ${kib} KiB (${UPLOAD_QPS} req/s for ${DURATION})"
+    oha --no-tui -z "$DURATION" -c 1 -q "$UPLOAD_QPS" -m POST \
+        -H "X-API-Key: $API_KEY" \
+        -T "multipart/form-data; boundary=$BOUNDARY" \
+        -D "$body" \
+        "$BASE_URL/api/upload"
+
+    # One file to download repeatedly
+    file_id="$(curl -fsS -H "X-API-Key: $API_KEY" \
+        -F folder_path=load-test -F on_conflict=rename -F "file=@$payload" \
+        "$BASE_URL/api/upload" | jq -r '.file_id')"
+
+    echo "== Download ${kib} KiB (${CONCURRENCY} connections for ${DURATION})"
+    oha --no-tui -z "$DURATION" -c "$CONCURRENCY" \
+        -H "X-API-Key: $API_KEY" \
+        "$BASE_URL/api/files/$file_id"
+done
+
+echo "Uploaded files are in the load-test folder; delete the folder when done"
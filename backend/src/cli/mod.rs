@@ -0,0 +1,417 @@
+mod seed;
+
+use clap::{Parser, Subcommand};
+use rand::{distributions::Alphanumeric, Rng};
+use sqlx::{FromRow, PgPool};
+use std::{
+    collections::HashSet,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+use uuid::Uuid;
+
+use crate::{
+    config::Config,
+    models::{File, UserRole},
+    utils::{
+        archive_entries, ensure_hot, hash_password, multipart_upload_dir, reconcile_file_counters,
+        revoke_access_tokens, write_zip_stream, ColdStorage, COLD_TIER,
+    },
+};
+
+type CliResult = Result<(), Box<dyn std::error::Error>>;
+
+/// Length of passwords generated when `--password` isn't given
+const GENERATED_PASSWORD_LENGTH: usize = 20;
+
+const FILE_COLUMNS: &str = "id, project_id, folder_id, original_name, stored_name, file_path, size, mime_type, upload_date, storage_encoding, precompressed_encodings, storage_tier, moderation_status";
+
+#[derive(Debug, Parser)]
+#[command(about = "FileRunner file hosting server")]
+pub struct Cli {
+    /// Same as the `migrate` subcommand (kept for existing deployments)
+    #[arg(long, hide = true)]
+    pub migrate: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the HTTP server (default)
+    Serve,
+    /// Apply pending database migrations and exit
+    Migrate,
+    /// Create an admin user, or promote an existing user to admin
+    CreateAdmin {
+        email: String,
+        /// Initial password; a random one is generated and printed if omitted
+        #[arg(long)]
+        password: Option<String>,
+    },
+    /// Set a new password for a user and sign out all of their sessions
+    ResetPassword {
+        email: String,
+        /// New password; a random one is generated and printed if omitted
+        #[arg(long)]
+        password: Option<String>,
+    },
+    /// Delete blobs in STORAGE_PATH that no file record points to
+    Gc {
+        /// Only list what would be deleted
+        #[arg(long)]
+        dry_run: bool,
+        /// Skip blobs modified more recently than this, so in-flight uploads survive
+        #[arg(long, default_value_t = 1)]
+        min_age_hours: u64,
+    },
+    /// Check that every file record has a blob of the expected size
+    Fsck,
+    /// Recount the cached file counts and sizes of every project and folder
+    Recount,
+    /// Write all files of a project to a zip archive, keeping the folder layout
+    ExportProject {
+        id: Uuid,
+        /// Archive to create (default `<project id>.zip`)
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Development helpers (don't run against production)
+    Dev {
+        #[command(subcommand)]
+        command: DevCommand,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum DevCommand {
+    /// Create sample users, projects, folders and files of various sizes and types
+    Seed {
+        #[arg(long, default_value_t = 3)]
+        users: u32,
+        #[arg(long, default_value_t = 2)]
+        projects_per_user: u32,
+        #[arg(long, default_value_t = 25)]
+        files_per_project: u32,
+        /// Random seed, so the same options produce the same data
+        #[arg(long, default_value_t = 42)]
+        seed: u64,
+    },
+}
+
+impl Command {
+    pub fn is_serve(&self) -> bool {
+        matches!(self, Command::Serve)
+    }
+}
+
+/// Run a maintenance command against an already-migrated database
+pub async fn run(command: Command, pool: &PgPool, config: &Config) -> CliResult {
+    match command {
+        Command::Serve | Command::Migrate => Ok(()),
+        Command::CreateAdmin { email, password } => {
+            create_admin(pool, config, &email, password).await
+        }
+        Command::ResetPassword { email, password } => {
+            reset_password(pool, config, &email, password).await
+        }
+        Command::Gc {
+            dry_run,
+            min_age_hours,
+        } => gc(pool, config, dry_run, min_age_hours).await,
+        Command::Fsck => fsck(pool, config).await,
+        Command::Recount => {
+            let corrected = reconcile_file_counters(pool).await?;
+            println!("Corrected {corrected} file counters");
+            Ok(())
+        }
+        Command::ExportProject { id, output } => export_project(pool, config, id, output).await,
+        Command::Dev {
+            command:
+                DevCommand::Seed {
+                    users,
+                    projects_per_user,
+                    files_per_project,
+                    seed,
+                },
+        } => {
+            let options = seed::SeedOptions {
+                users,
+                projects_per_user,
+                files_per_project,
+                seed,
+            };
+            seed::seed(pool, config, options).await
+        }
+    }
+}
+
+/// Use the given password or generate one, printing generated passwords once
+fn password_or_generate(password: Option<String>) -> Result<String, String> {
+    match password {
+        Some(password) if password.len() < 8 => {
+            Err("Password must be at least 8 characters".to_string())
+        }
+        Some(password) => Ok(password),
+        None => {
+            let password: String = rand::thread_rng()
+                .sample_iter(&Alphanumeric)
+                .take(GENERATED_PASSWORD_LENGTH)
+                .map(char::from)
+                .collect();
+            println!("Generated password: {password}");
+            Ok(password)
+        }
+    }
+}
+
+async fn create_admin(
+    pool: &PgPool,
+    config: &Config,
+    email: &str,
+    password: Option<String>,
+) -> CliResult {
+    let promoted = sqlx::query("UPDATE users SET role = $1 WHERE email = $2")
+        .bind(UserRole::Admin)
+        .bind(email)
+        .execute(pool)
+        .await?;
+    if promoted.rows_affected() > 0 {
+        println!("{email} already exists and is now an admin; their password was not changed");
+        return Ok(());
+    }
+
+    let password = password_or_generate(password)?;
+    sqlx::query(
+        "INSERT INTO users (email, password_hash, role, must_change_password) VALUES ($1, $2, $3, TRUE)",
+    )
+    .bind(email)
+    .bind(hash_password(&password, &config.password_hashing).map_err(|e| e.to_string())?)
+    .bind(UserRole::Admin)
+    .execute(pool)
+    .await?;
+    println!("Created admin {email} (password change required on first login)");
+    Ok(())
+}
+
+async fn reset_password(
+    pool: &PgPool,
+    config: &Config,
+    email: &str,
+    password: Option<String>,
+) -> CliResult {
+    let user_id: Uuid = sqlx::query_scalar("SELECT id FROM users WHERE email = $1")
+        .bind(email)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| format!("No user with email {email}"))?;
+
+    let password = password_or_generate(password)?;
+    let mut tx = pool.begin().await?;
+    sqlx::query("UPDATE users SET password_hash = $1, must_change_password = TRUE WHERE id = $2")
+        .bind(hash_password(&password, &config.password_hashing).map_err(|e| e.to_string())?)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(
+        r#"
+        UPDATE refresh_tokens
+        SET revoked_at = NOW(), revoked_reason = 'password_reset'
+        WHERE user_id = $1 AND revoked_at IS NULL
+        "#,
+    )
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+    revoke_access_tokens(&mut *tx, user_id).await?;
+    tx.commit().await?;
+
+    println!("Password reset for {email}; existing sessions were signed out");
+    Ok(())
+}
+
+/// Every regular file below `root`
+fn walk_blobs(root: PathBuf) -> std::io::Result<Vec<PathBuf>> {
+    let mut blobs = Vec::new();
+    let mut dirs = vec![root];
+    while let Some(dir) = dirs.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                dirs.push(entry.path());
+            } else if file_type.is_file() {
+                blobs.push(entry.path());
+            }
+        }
+    }
+    Ok(blobs)
+}
+
+async fn gc(pool: &PgPool, config: &Config, dry_run: bool, min_age_hours: u64) -> CliResult {
+    // Blobs and their precompressed `.br` / `.gz` variants
+    let file_paths: Vec<String> = sqlx::query_scalar("SELECT file_path FROM files")
+        .fetch_all(pool)
+        .await?;
+    let mut referenced = HashSet::with_capacity(file_paths.len() * 3);
+    for path in file_paths {
+        referenced.insert(format!("{path}.br"));
+        referenced.insert(format!("{path}.gz"));
+        referenced.insert(path);
+    }
+    // Parts of multipart uploads still in progress
+    let parts = sqlx::query_as::<_, (Uuid, Uuid, String)>(
+        "SELECT u.project_id, u.id, p.stored_name FROM multipart_upload_parts p JOIN multipart_uploads u ON u.id = p.upload_id",
+    )
+    .fetch_all(pool)
+    .await?;
+    for (project_id, upload_id, stored_name) in parts {
+        let path =
+            multipart_upload_dir(&config.storage_path, project_id, upload_id).join(stored_name);
+        referenced.insert(path.to_string_lossy().into_owned());
+    }
+
+    let root = PathBuf::from(&config.storage_path);
+    let blobs = tokio::task::spawn_blocking(move || walk_blobs(root)).await??;
+    let cutoff = SystemTime::now() - Duration::from_secs(min_age_hours * 3600);
+
+    let (mut removed, mut bytes) = (0u64, 0u64);
+    for blob in blobs {
+        if blob.to_str().is_some_and(|path| referenced.contains(path)) {
+            continue;
+        }
+        let metadata = tokio::fs::metadata(&blob).await?;
+        if metadata.modified()? > cutoff {
+            continue;
+        }
+        if dry_run {
+            println!("Would delete {}", blob.display());
+        } else if let Err(e) = tokio::fs::remove_file(&blob).await {
+            eprintln!("Failed to delete {}: {}", blob.display(), e);
+            continue;
+        }
+        removed += 1;
+        bytes += metadata.len();
+    }
+
+    let verb = if dry_run { "Would delete" } else { "Deleted" };
+    println!("{verb} {removed} unreferenced blobs ({bytes} bytes)");
+    Ok(())
+}
+
+/// A file with the size its blob should have on disk (after zstd compression)
+#[derive(FromRow)]
+struct StoredFile {
+    #[sqlx(flatten)]
+    file: File,
+    stored_size: i64,
+}
+
+async fn fsck(pool: &PgPool, config: &Config) -> CliResult {
+    let cold_storage = config
+        .cold_storage_url
+        .as_deref()
+        .map(ColdStorage::open)
+        .transpose()?;
+    let files = sqlx::query_as::<_, StoredFile>(&format!(
+        "SELECT {FILE_COLUMNS}, COALESCE(stored_size, size) AS stored_size FROM files ORDER BY id"
+    ))
+    .fetch_all(pool)
+    .await?;
+
+    let mut problems = 0u64;
+    for StoredFile {
+        file,
+        stored_size: expected,
+    } in &files
+    {
+        let (id, file_path) = (file.id, &file.file_path);
+        let actual = if file.storage_tier == COLD_TIER {
+            let Some(ref cold) = cold_storage else {
+                println!("{id}: in cold storage but COLD_STORAGE_URL is not set");
+                problems += 1;
+                continue;
+            };
+            cold.object_size(file).await?
+        } else {
+            match tokio::fs::metadata(file_path).await {
+                Ok(metadata) => Some(metadata.len()),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                Err(e) => return Err(e.into()),
+            }
+        };
+
+        match actual {
+            None => {
+                println!("{id}: blob missing ({file_path})");
+                problems += 1;
+            }
+            Some(size) if size != *expected as u64 => {
+                println!("{id}: blob is {size} bytes, expected {expected} ({file_path})");
+                problems += 1;
+            }
+            Some(_) => {}
+        }
+    }
+
+    println!("Checked {} files, {} problems", files.len(), problems);
+    if problems > 0 {
+        return Err(format!("{problems} files failed the check").into());
+    }
+    Ok(())
+}
+
+async fn export_project(
+    pool: &PgPool,
+    config: &Config,
+    project_id: Uuid,
+    output: Option<PathBuf>,
+) -> CliResult {
+    let project: String = sqlx::query_scalar("SELECT name FROM projects WHERE id = $1")
+        .bind(project_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| format!("No project with id {project_id}"))?;
+
+    // Cold files are restored first, the same as for archive downloads
+    let cold_storage = config
+        .cold_storage_url
+        .as_deref()
+        .map(ColdStorage::open)
+        .transpose()?;
+    let files = sqlx::query_as::<_, File>(&format!(
+        "SELECT {FILE_COLUMNS} FROM files WHERE project_id = $1"
+    ))
+    .bind(project_id)
+    .fetch_all(pool)
+    .await?;
+    for file in &files {
+        ensure_hot(pool, cold_storage.as_ref(), file).await?;
+    }
+
+    let rows = sqlx::query_as::<_, (String, String, Option<String>, Option<String>)>(
+        r#"
+        SELECT f.file_path, f.original_name, fol.path, f.storage_encoding
+        FROM files f
+        LEFT JOIN folders fol ON fol.id = f.folder_id
+        WHERE f.project_id = $1
+        ORDER BY fol.path NULLS FIRST, f.original_name
+        "#,
+    )
+    .bind(project_id)
+    .fetch_all(pool)
+    .await?;
+
+    let entries = archive_entries(rows);
+    let output = output.unwrap_or_else(|| PathBuf::from(format!("{project_id}.zip")));
+    let count = entries.len();
+    let writer = tokio::fs::File::create(&output).await?;
+    write_zip_stream(entries, writer).await?;
+
+    println!(
+        "Exported {count} files from \"{project}\" to {}",
+        output.display()
+    );
+    Ok(())
+}
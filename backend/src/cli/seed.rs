@@ -0,0 +1,195 @@
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+use crate::{
+    config::Config,
+    handlers::file::get_or_create_folder,
+    models::{ProjectRole, UserRole},
+    utils::hash_password,
+};
+
+use super::CliResult;
+
+/// Password of every seeded user
+const SEED_PASSWORD: &str = "password123";
+
+/// Folders created in each seeded project (files also go in the project root)
+const SEED_FOLDERS: [&str; 5] = ["docs", "images", "images/thumbnails", "data", "releases"];
+
+/// Sizes used for generated binary files, from a single byte to a few MiB
+const BINARY_SIZES: [usize; 6] = [1, 1024, 64 * 1024, 512 * 1024, 1024 * 1024, 5 * 1024 * 1024];
+
+/// Smallest valid PNG (1x1 transparent pixel)
+const PNG_PIXEL: [u8; 67] = [
+    0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52,
+    0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00, 0x00, 0x1f, 0x15, 0xc4,
+    0x89, 0x00, 0x00, 0x00, 0x0a, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9c, 0x63, 0x00, 0x01, 0x00, 0x00,
+    0x05, 0x00, 0x01, 0x0d, 0x0a, 0x2d, 0xb4, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e, 0x44, 0xae,
+    0x42, 0x60, 0x82,
+];
+
+const LOREM: &str = "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor incididunt ut labore et dolore magna aliqua. ";
+
+/// What to generate; see `filerunner-backend dev seed --help`
+pub struct SeedOptions {
+    pub users: u32,
+    pub projects_per_user: u32,
+    pub files_per_project: u32,
+    pub seed: u64,
+}
+
+/// A generated file: `(name, content)`
+fn sample_file(rng: &mut StdRng, n: u32) -> (String, Vec<u8>) {
+    match n % 6 {
+        0 => {
+            let paragraphs = rng.gen_range(1..50);
+            (
+                format!("notes-{n}.txt"),
+                LOREM.repeat(paragraphs).into_bytes(),
+            )
+        }
+        1 => {
+            let rows: Vec<String> = (0..rng.gen_range(1..20))
+                .map(|i| format!(r#"{{"id":{i},"value":{}}}"#, rng.gen::<u32>()))
+                .collect();
+            (
+                format!("data-{n}.json"),
+                format!("[{}]", rows.join(",")).into_bytes(),
+            )
+        }
+        2 => {
+            let mut csv = String::from("id,name,amount\n");
+            for i in 0..rng.gen_range(10..2000) {
+                csv.push_str(&format!("{i},item-{i},{}\n", rng.gen_range(0..10_000)));
+            }
+            (format!("report-{n}.csv"), csv.into_bytes())
+        }
+        3 => (format!("pixel-{n}.png"), PNG_PIXEL.to_vec()),
+        4 => (
+            format!("page-{n}.html"),
+            format!("<!doctype html><title>Page {n}</title><p>{LOREM}</p>").into_bytes(),
+        ),
+        _ => {
+            let size = *BINARY_SIZES.choose(rng).unwrap_or(&0);
+            let mut data = vec![0u8; size];
+            rng.fill(&mut data[..]);
+            (format!("blob-{n}.bin"), data)
+        }
+    }
+}
+
+/// Create sample users, projects, folders and files for local development.
+/// Users are `seed-user-<n>@example.com`; rerunning reuses them and adds more projects.
+pub async fn seed(pool: &PgPool, config: &Config, options: SeedOptions) -> CliResult {
+    let mut rng = StdRng::seed_from_u64(options.seed);
+    let password_hash =
+        hash_password(SEED_PASSWORD, &config.password_hashing).map_err(|e| e.to_string())?;
+
+    let mut user_ids = Vec::with_capacity(options.users as usize);
+    for n in 1..=options.users {
+        let user_id: Uuid = sqlx::query_scalar(
+            r#"
+            INSERT INTO users (email, password_hash, role)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (email) DO UPDATE SET email = EXCLUDED.email
+            RETURNING id
+            "#,
+        )
+        .bind(format!("seed-user-{n}@example.com"))
+        .bind(&password_hash)
+        .bind(UserRole::User)
+        .fetch_one(pool)
+        .await?;
+        user_ids.push(user_id);
+    }
+
+    let (mut projects, mut files, mut bytes) = (0u32, 0u32, 0u64);
+    for (index, &user_id) in user_ids.iter().enumerate() {
+        for p in 1..=options.projects_per_user {
+            let project_id: Uuid = sqlx::query_scalar(
+                "INSERT INTO projects (user_id, name, is_public) VALUES ($1, $2, $3) RETURNING id",
+            )
+            .bind(user_id)
+            .bind(format!("Seed project {}-{p}", index + 1))
+            .bind(p % 2 == 0)
+            .fetch_one(pool)
+            .await?;
+            projects += 1;
+
+            // Share with the next user so member permissions have something to act on
+            if let Some(&member_id) = user_ids.get((index + 1) % user_ids.len()) {
+                if member_id != user_id {
+                    let role = [ProjectRole::Viewer, ProjectRole::Uploader][p as usize % 2];
+                    sqlx::query(
+                        "INSERT INTO project_members (project_id, user_id, role, invited_by) VALUES ($1, $2, $3, $4)",
+                    )
+                    .bind(project_id)
+                    .bind(member_id)
+                    .bind(role)
+                    .bind(user_id)
+                    .execute(pool)
+                    .await?;
+                }
+            }
+
+            for n in 1..=options.files_per_project {
+                let folder = SEED_FOLDERS.choose(&mut rng).filter(|_| rng.gen_bool(0.7));
+                let folder_id = match folder {
+                    Some(path) => Some(
+                        get_or_create_folder(&mut *pool.acquire().await?, project_id, path).await?,
+                    ),
+                    None => None,
+                };
+
+                let (original_name, data) = sample_file(&mut rng, n);
+                let file_id = Uuid::new_v4();
+                let extension = original_name.rsplit_once('.').map_or("", |(_, ext)| ext);
+                let stored_name = format!("{file_id}.{extension}");
+
+                // Same layout as uploads: <storage>/<project>/<folder segments>/<stored name>
+                let mut file_path = PathBuf::from(&config.storage_path);
+                file_path.push(project_id.to_string());
+                if let Some(path) = folder {
+                    file_path.extend(path.split('/'));
+                }
+                tokio::fs::create_dir_all(&file_path).await?;
+                file_path.push(&stored_name);
+                tokio::fs::write(&file_path, &data).await?;
+
+                let mime_type = mime_guess::from_path(&original_name)
+                    .first_or_octet_stream()
+                    .to_string();
+                let age_days = rng.gen_range(0..180i32);
+                sqlx::query(
+                    r#"
+                    INSERT INTO files (id, project_id, folder_id, original_name, stored_name, file_path, size, mime_type, content_hash, stored_size, upload_date, last_accessed_at)
+                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $7, NOW() - make_interval(days => $10), NOW() - make_interval(days => $10))
+                    "#,
+                )
+                .bind(file_id)
+                .bind(project_id)
+                .bind(folder_id)
+                .bind(&original_name)
+                .bind(&stored_name)
+                .bind(file_path.to_str().unwrap())
+                .bind(data.len() as i64)
+                .bind(&mime_type)
+                .bind(hex::encode(Sha256::digest(&data)))
+                .bind(age_days)
+                .execute(pool)
+                .await?;
+                files += 1;
+                bytes += data.len() as u64;
+            }
+        }
+    }
+
+    println!(
+        "Seeded {} users, {projects} projects and {files} files ({bytes} bytes); password for all users: {SEED_PASSWORD}",
+        user_ids.len()
+    );
+    Ok(())
+}
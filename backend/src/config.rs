@@ -1,23 +1,127 @@
+use ipnet::IpNet;
 use serde::Deserialize;
-use std::env;
+use std::{env, net::Ipv4Addr};
+
+use crate::{
+    error::ErrorFormat,
+    utils::{
+        captcha::CaptchaProvider, cdn_purge::CdnProvider, events::EventSchema, mqtt::MqttRoute,
+        offload::DownloadOffload, password::PasswordHashing, password_policy::CharacterClass,
+    },
+};
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct Config {
     pub database_url: String,
+    pub database_read_urls: Vec<String>,
     pub jwt_secret: String,
+    pub token_revocation_cache_secs: u64,
+    #[serde(skip)]
+    pub password_hashing: PasswordHashing,
+    pub password_min_length: usize,
+    pub password_required_classes: Vec<CharacterClass>,
+    pub password_reject_common: bool,
+    pub password_reject_email: bool,
     pub server_port: u16,
     pub server_host: String,
+    pub server_reuse_port: bool,
+    pub shutdown_timeout_secs: u64,
+    pub http2: bool,
+    pub http2_max_concurrent_streams: u32,
+    pub http2_keep_alive_interval_secs: u64,
+    pub http_keep_alive: bool,
+    pub http_read_timeout_secs: u64,
+    pub http_write_timeout_secs: u64,
+    pub request_header_timeout_secs: u64,
+    pub request_timeout_secs: u64,
+    pub request_body_idle_timeout_secs: u64,
+    pub load_shed_max_in_flight: usize,
+    pub load_shed_max_lag_ms: u64,
+    pub load_shed_retry_after_secs: u64,
+    pub memory_budget_bytes: usize,
+    pub memory_budget_wait_secs: u64,
+    pub stream_buffer_pool_size: usize,
+    pub max_connections: usize,
     pub cors_origins: Vec<String>,
+    pub trusted_proxies: Vec<IpNet>,
     pub storage_path: String,
     pub max_file_size: usize,
+    pub download_offload: DownloadOffload,
+    pub download_offload_prefix: String,
+    pub storage_compression: bool,
+    pub storage_compression_level: i32,
+    pub precompress_public_assets: bool,
+    pub ffmpeg_path: String,
+    pub video_posters: bool,
+    pub video_preview_secs: u32,
+    pub audio_waveforms: bool,
+    pub max_concurrent_uploads: usize,
+    pub upload_dedup_window_secs: u64,
+    pub api_key_rotation_grace_hours: i64,
+    pub extract_max_entries: usize,
+    pub extract_max_total_size: u64,
+    pub geoip_database_path: Option<String>,
+    pub public_files_domain: Option<String>,
+    pub api_hosts: Vec<String>,
+    pub dns_over_https_url: String,
+    pub cold_storage_url: Option<String>,
+    pub lifecycle_interval_secs: u64,
+    pub presigned_downloads: bool,
+    pub presigned_url_ttl_secs: u64,
+    pub backup_path: String,
+    pub backup_target_url: Option<String>,
+    pub backup_interval_hours: u64,
+    pub project_deletion_grace_days: u32,
+    pub integrity_check_interval_hours: u64,
+    pub integrity_check_sample_size: i64,
+    pub replication_peer_url: Option<String>,
+    pub replication_token: Option<String>,
+    pub event_broker_url: Option<String>,
+    pub event_topic_prefix: String,
+    pub event_schema: EventSchema,
+    pub event_retention_days: i32,
+    pub changes_retention_days: i32,
+    pub mqtt_url: Option<String>,
+    pub mqtt_client_id: String,
+    pub mqtt_routes: Vec<MqttRoute>,
+    pub ftp_listen_addr: Option<String>,
+    pub ftp_passive_ports: (u16, u16),
+    pub ftp_public_host: Option<Ipv4Addr>,
+    pub ftp_tls_cert: Option<String>,
+    pub ftp_tls_key: Option<String>,
+    pub ftp_require_tls: bool,
+    pub inbound_email_domain: Option<String>,
+    pub inbound_email_token: Option<String>,
+    pub mirror_sync_interval_secs: u64,
+    pub cdn_purge_provider: CdnProvider,
+    pub cdn_purge_token: Option<String>,
+    pub cdn_purge_zone_id: Option<String>,
+    pub cdn_purge_webhook_url: Option<String>,
+    pub cdn_public_url: Option<String>,
+    pub robots_noindex: bool,
+    pub smtp_url: Option<String>,
+    pub mail_from: String,
+    pub app_url: Option<String>,
+    pub login_alerts: bool,
+    pub moderation_url: Option<String>,
+    pub moderation_token: Option<String>,
+    pub moderation_content_types: Vec<String>,
     pub allow_signup: bool,
     pub admin_email: String,
     pub admin_password: String,
     pub db_min_connections: u32,
     pub db_max_connections: u32,
+    pub db_slow_query_ms: u64,
+    pub migrate_on_start: bool,
+    pub metrics_token: Option<String>,
+    pub error_format: ErrorFormat,
     // Token expiry settings
     pub access_token_expiry_minutes: i64,
     pub refresh_token_expiry_days: i64,
+    // Bot protection for public auth endpoints
+    pub captcha_provider: CaptchaProvider,
+    pub captcha_secret: Option<String>,
+    pub captcha_pow_difficulty: u32,
 }
 
 impl Config {
@@ -32,18 +136,358 @@ impl Config {
             .map(|s| s.trim().to_string())
             .collect();
 
+        // Reverse proxies allowed to set X-Forwarded-For / Forwarded (comma-separated CIDRs)
+        let trusted_proxies = env::var("TRUSTED_PROXIES")
+            .unwrap_or_default()
+            .split(',')
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+            .map(|s| {
+                s.parse::<IpNet>()
+                    .or_else(|_| s.parse::<std::net::IpAddr>().map(IpNet::from))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Character classes new passwords must contain (comma-separated: lower, upper, digit, symbol)
+        let password_required_classes = env::var("PASSWORD_REQUIRED_CLASSES")
+            .unwrap_or_default()
+            .split(',')
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+            .map(str::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Read replicas for listings and downloads (comma-separated DSNs, optional)
+        let database_read_urls = env::var("DATABASE_READ_URLS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect();
+
         let config = Config {
             database_url: env::var("DATABASE_URL").expect("DATABASE_URL must be set"),
+            database_read_urls,
             jwt_secret: env::var("JWT_SECRET").expect("JWT_SECRET must be set"),
+            // How long a user's token cutoff (set by logout-all and password changes) is
+            // cached; revocations made on another instance take up to this long to apply
+            token_revocation_cache_secs: env::var("TOKEN_REVOCATION_CACHE_SECS")
+                .unwrap_or_else(|_| "10".to_string())
+                .parse()?,
+            // Argon2id cost for new password hashes (defaults: 19 MiB, 2 passes, 1 lane);
+            // hashes made with less are upgraded at the next login
+            password_hashing: PasswordHashing::new(
+                env::var("ARGON2_MEMORY_KIB")
+                    .unwrap_or_else(|_| "19456".to_string())
+                    .parse()?,
+                env::var("ARGON2_ITERATIONS")
+                    .unwrap_or_else(|_| "2".to_string())
+                    .parse()?,
+                env::var("ARGON2_PARALLELISM")
+                    .unwrap_or_else(|_| "1".to_string())
+                    .parse()?,
+            )?,
+            // Password policy for registration and password changes
+            password_min_length: env::var("PASSWORD_MIN_LENGTH")
+                .unwrap_or_else(|_| "8".to_string())
+                .parse()?,
+            password_required_classes,
+            password_reject_common: env::var("PASSWORD_REJECT_COMMON")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()?,
+            password_reject_email: env::var("PASSWORD_REJECT_EMAIL")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()?,
             server_port: env::var("SERVER_PORT")
                 .unwrap_or_else(|_| "8000".to_string())
                 .parse()?,
             server_host: env::var("SERVER_HOST").unwrap_or_else(|_| "0.0.0.0".to_string()),
+            // Upgrades: let a new binary bind the port while this one drains, and
+            // how long to drain open connections on SIGTERM (0 = until they finish)
+            server_reuse_port: env::var("SERVER_REUSE_PORT")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()?,
+            shutdown_timeout_secs: env::var("SHUTDOWN_TIMEOUT_SECS")
+                .unwrap_or_else(|_| "300".to_string())
+                .parse()?,
+            // Connection tuning; timeouts, keep-alive pings and the connection cap
+            // are off at 0
+            http2: env::var("HTTP2")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()?,
+            http2_max_concurrent_streams: env::var("HTTP2_MAX_CONCURRENT_STREAMS")
+                .unwrap_or_else(|_| "200".to_string())
+                .parse()?,
+            http2_keep_alive_interval_secs: env::var("HTTP2_KEEP_ALIVE_INTERVAL_SECS")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()?,
+            http_keep_alive: env::var("HTTP_KEEP_ALIVE")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()?,
+            http_read_timeout_secs: env::var("HTTP_READ_TIMEOUT_SECS")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()?,
+            http_write_timeout_secs: env::var("HTTP_WRITE_TIMEOUT_SECS")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()?,
+            // Slow-client protection: time to send request headers, to get a
+            // response, and between chunks of a request body (0 = off)
+            request_header_timeout_secs: env::var("REQUEST_HEADER_TIMEOUT_SECS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()?,
+            request_timeout_secs: env::var("REQUEST_TIMEOUT_SECS")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()?,
+            request_body_idle_timeout_secs: env::var("REQUEST_BODY_IDLE_TIMEOUT_SECS")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse()?,
+            // Shed listings with 503 once this many requests are in flight or the
+            // runtime is this far behind (0 = off)
+            load_shed_max_in_flight: env::var("LOAD_SHED_MAX_IN_FLIGHT")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()?,
+            load_shed_max_lag_ms: env::var("LOAD_SHED_MAX_LAG_MS")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()?,
+            load_shed_retry_after_secs: env::var("LOAD_SHED_RETRY_AFTER_SECS")
+                .unwrap_or_else(|_| "5".to_string())
+                .parse()?,
+            // Memory held by upload and download bodies at once (0 = unlimited);
+            // uploads wait this long for room before getting 503
+            memory_budget_bytes: env::var("MEMORY_BUDGET_BYTES")
+                .unwrap_or_else(|_| "1073741824".to_string())
+                .parse()?,
+            memory_budget_wait_secs: env::var("MEMORY_BUDGET_WAIT_SECS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()?,
+            // Idle 64 KiB download buffers kept for reuse
+            stream_buffer_pool_size: env::var("STREAM_BUFFER_POOL_SIZE")
+                .unwrap_or_else(|_| "256".to_string())
+                .parse()?,
+            max_connections: env::var("MAX_CONNECTIONS")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()?,
             cors_origins,
+            trusted_proxies,
             storage_path: env::var("STORAGE_PATH").unwrap_or_else(|_| "./storage".to_string()),
             max_file_size: env::var("MAX_FILE_SIZE")
                 .unwrap_or_else(|_| "104857600".to_string())
                 .parse()?,
+            // Let a reverse proxy send download bytes: none, x-accel-redirect (nginx,
+            // internal location under the prefix) or x-sendfile (Apache/lighttpd)
+            download_offload: env::var("DOWNLOAD_OFFLOAD")
+                .unwrap_or_else(|_| "none".to_string())
+                .parse()?,
+            download_offload_prefix: env::var("DOWNLOAD_OFFLOAD_PREFIX")
+                .unwrap_or_else(|_| "/internal-files".to_string()),
+            // At-rest zstd compression for text-like uploads (default: off, level 3)
+            storage_compression: env::var("STORAGE_COMPRESSION")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()?,
+            storage_compression_level: env::var("STORAGE_COMPRESSION_LEVEL")
+                .unwrap_or_else(|_| "3".to_string())
+                .parse()?,
+            // Background gzip/brotli variants for public web assets (default: off)
+            precompress_public_assets: env::var("PRECOMPRESS_PUBLIC_ASSETS")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()?,
+            // ffmpeg binary for media processing (image optimization, video posters, waveforms)
+            ffmpeg_path: env::var("FFMPEG_PATH").unwrap_or_else(|_| "ffmpeg".to_string()),
+            // Poster frames for video uploads, generated in the background (default: off)
+            video_posters: env::var("VIDEO_POSTERS")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()?,
+            // Length of the animated preview made with each poster (default: 0, none)
+            video_preview_secs: env::var("VIDEO_PREVIEW_SECONDS")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()?,
+            // Waveform peaks for audio uploads, generated in the background (default: off)
+            audio_waveforms: env::var("AUDIO_WAVEFORMS")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()?,
+            max_concurrent_uploads: env::var("MAX_CONCURRENT_UPLOADS")
+                .unwrap_or_else(|_| "4".to_string())
+                .parse()?,
+            // Same content uploaded to the same folder this recently returns the
+            // existing file (default: 0, off)
+            upload_dedup_window_secs: env::var("UPLOAD_DEDUP_WINDOW_SECS")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()?,
+            api_key_rotation_grace_hours: env::var("API_KEY_ROTATION_GRACE_HOURS")
+                .unwrap_or_else(|_| "24".to_string())
+                .parse()?,
+            // Archive extraction limits (defaults: 1000 entries, 1GB unpacked)
+            extract_max_entries: env::var("EXTRACT_MAX_ENTRIES")
+                .unwrap_or_else(|_| "1000".to_string())
+                .parse()?,
+            extract_max_total_size: env::var("EXTRACT_MAX_TOTAL_SIZE")
+                .unwrap_or_else(|_| "1073741824".to_string())
+                .parse()?,
+            geoip_database_path: env::var("GEOIP_DATABASE_PATH")
+                .ok()
+                .filter(|s| !s.is_empty()),
+            public_files_domain: env::var("PUBLIC_FILES_DOMAIN")
+                .ok()
+                .map(|s| s.trim().trim_start_matches('.').to_lowercase())
+                .filter(|s| !s.is_empty()),
+            // Hosts the API is served on (comma-separated), besides those of APP_URL,
+            // CDN_PUBLIC_URL and CORS_ORIGINS; no project can claim them as a custom domain
+            api_hosts: env::var("API_HOSTS")
+                .unwrap_or_default()
+                .split(',')
+                .map(|s| s.trim().to_lowercase())
+                .filter(|s| !s.is_empty())
+                .collect(),
+            // JSON DNS-over-HTTPS resolver that custom domain TXT records are checked with
+            dns_over_https_url: env::var("DNS_OVER_HTTPS_URL")
+                .unwrap_or_else(|_| "https://cloudflare-dns.com/dns-query".to_string()),
+            // Cold tier for projects with a lifecycle rule: s3://bucket[/prefix] or a directory
+            cold_storage_url: env::var("COLD_STORAGE_URL").ok().filter(|s| !s.is_empty()),
+            lifecycle_interval_secs: env::var("LIFECYCLE_INTERVAL_SECS")
+                .unwrap_or_else(|_| "3600".to_string())
+                .parse()?,
+            // Redirect downloads of files in S3 cold storage to presigned URLs
+            // instead of restoring them and streaming the bytes
+            presigned_downloads: env::var("PRESIGNED_DOWNLOADS")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()?,
+            presigned_url_ttl_secs: env::var("PRESIGNED_URL_TTL_SECS")
+                .unwrap_or_else(|_| "300".to_string())
+                .parse()?,
+            // Backup manifests are always written to BACKUP_PATH; blobs are copied
+            // incrementally only when a target is configured (interval 0 = manual only)
+            backup_path: env::var("BACKUP_PATH").unwrap_or_else(|_| "./backups".to_string()),
+            backup_target_url: env::var("BACKUP_TARGET_URL").ok().filter(|s| !s.is_empty()),
+            backup_interval_hours: env::var("BACKUP_INTERVAL_HOURS")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()?,
+            // Deleted projects can be restored for this long (0 purges them right away)
+            project_deletion_grace_days: env::var("PROJECT_DELETION_GRACE_DAYS")
+                .unwrap_or_else(|_| "7".to_string())
+                .parse()?,
+            // Integrity scrubbing re-hashes this many of the least recently verified
+            // files per run (sample 0 = every file, interval 0 = disabled)
+            integrity_check_interval_hours: env::var("INTEGRITY_CHECK_INTERVAL_HOURS")
+                .unwrap_or_else(|_| "24".to_string())
+                .parse()?,
+            integrity_check_sample_size: env::var("INTEGRITY_CHECK_SAMPLE_SIZE")
+                .unwrap_or_else(|_| "1000".to_string())
+                .parse()?,
+            // Standby instance that blobs are mirrored to; pushes between the two
+            // are authenticated with the shared token
+            replication_peer_url: env::var("REPLICATION_PEER_URL")
+                .ok()
+                .filter(|s| !s.is_empty()),
+            replication_token: env::var("REPLICATION_TOKEN").ok().filter(|s| !s.is_empty()),
+            // File lifecycle events: nats://host:4222 or a Kafka REST Proxy http(s):// URL
+            event_broker_url: env::var("EVENT_BROKER_URL").ok().filter(|s| !s.is_empty()),
+            event_topic_prefix: env::var("EVENT_TOPIC_PREFIX")
+                .unwrap_or_else(|_| "filerunner".to_string()),
+            event_schema: env::var("EVENT_SCHEMA")
+                .unwrap_or_else(|_| "native".to_string())
+                .parse()?,
+            // Days delivered events are kept for replays (default: 7, 0 = not kept)
+            event_retention_days: env::var("EVENT_RETENTION_DAYS")
+                .unwrap_or_else(|_| "7".to_string())
+                .parse()?,
+            // Days file changes are kept for the changes feed (default: 30);
+            // older cursors must resync from the manifest
+            changes_retention_days: env::var("CHANGES_RETENTION_DAYS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()?,
+            // Store messages from an MQTT broker (mqtt[s]://[user:password@]host[:port])
+            // as files; MQTT_ROUTES is a JSON array mapping topic filters to a
+            // project, folder and file name template
+            mqtt_url: env::var("MQTT_URL").ok().filter(|s| !s.is_empty()),
+            mqtt_client_id: env::var("MQTT_CLIENT_ID").unwrap_or_else(|_| "filerunner".to_string()),
+            mqtt_routes: serde_json::from_str(
+                &env::var("MQTT_ROUTES").unwrap_or_else(|_| "[]".to_string()),
+            )?,
+            // FTP gateway with project ID (or slug) and API key as login; off when unset
+            ftp_listen_addr: env::var("FTP_LISTEN_ADDR").ok().filter(|s| !s.is_empty()),
+            // Ports for passive data connections, e.g. 30000-30099
+            ftp_passive_ports: {
+                let range =
+                    env::var("FTP_PASSIVE_PORTS").unwrap_or_else(|_| "30000-30099".to_string());
+                let (start, end) = range
+                    .split_once('-')
+                    .ok_or("FTP_PASSIVE_PORTS must be a range like 30000-30099")?;
+                (start.trim().parse()?, end.trim().parse()?)
+            },
+            // IPv4 address announced for passive connections (behind NAT); defaults
+            // to the address the client connected to
+            ftp_public_host: env::var("FTP_PUBLIC_HOST")
+                .ok()
+                .filter(|s| !s.is_empty())
+                .map(|s| s.parse())
+                .transpose()?,
+            // PEM certificate chain and private key for FTPS (AUTH TLS)
+            ftp_tls_cert: env::var("FTP_TLS_CERT").ok().filter(|s| !s.is_empty()),
+            ftp_tls_key: env::var("FTP_TLS_KEY").ok().filter(|s| !s.is_empty()),
+            // Refuse logins and transfers that aren't encrypted
+            ftp_require_tls: env::var("FTP_REQUIRE_TLS")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()?,
+            // Inbound email: attachments sent to <project>+<folder>@INBOUND_EMAIL_DOMAIN
+            // are stored; off unless both the domain and the webhook token are set
+            inbound_email_domain: env::var("INBOUND_EMAIL_DOMAIN")
+                .ok()
+                .filter(|s| !s.is_empty())
+                .map(|s| s.to_ascii_lowercase()),
+            // Secret the provider's webhook URL carries as ?token=
+            inbound_email_token: env::var("INBOUND_EMAIL_TOKEN")
+                .ok()
+                .filter(|s| !s.is_empty()),
+            // How often mirror projects pull changes from their remote project
+            // (0 = only on request)
+            mirror_sync_interval_secs: env::var("MIRROR_SYNC_INTERVAL_SECS")
+                .unwrap_or_else(|_| "300".to_string())
+                .parse()?,
+            // CDN in front of public files (none, cloudflare, fastly or webhook);
+            // overwritten and deleted public files are purged from its cache
+            cdn_purge_provider: env::var("CDN_PURGE_PROVIDER")
+                .unwrap_or_else(|_| "none".to_string())
+                .parse()?,
+            // Cloudflare API token, Fastly API key or webhook bearer token
+            cdn_purge_token: env::var("CDN_PURGE_TOKEN").ok().filter(|s| !s.is_empty()),
+            cdn_purge_zone_id: env::var("CDN_PURGE_ZONE_ID").ok().filter(|s| !s.is_empty()),
+            // Receives {"urls": [...]} when CDN_PURGE_PROVIDER=webhook
+            cdn_purge_webhook_url: env::var("CDN_PURGE_WEBHOOK_URL")
+                .ok()
+                .filter(|s| !s.is_empty()),
+            // Address the CDN serves this API at, e.g. https://cdn.example.com, for
+            // purging download URLs; slug and custom domain URLs are always purged
+            cdn_public_url: env::var("CDN_PUBLIC_URL")
+                .ok()
+                .map(|url| url.trim_end_matches('/').to_string())
+                .filter(|url| !url.is_empty()),
+            // Keep search engines from indexing any file: X-Robots-Tag: noindex on
+            // downloads and a robots.txt disallowing everything
+            robots_noindex: env::var("ROBOTS_NOINDEX")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()?,
+            // Outgoing email (quota warnings); disabled when SMTP_URL is unset
+            smtp_url: env::var("SMTP_URL").ok().filter(|s| !s.is_empty()),
+            mail_from: env::var("MAIL_FROM")
+                .unwrap_or_else(|_| "FileRunner <noreply@localhost>".to_string()),
+            // Web app address used for links in emails, e.g. https://files.example.com
+            app_url: env::var("APP_URL")
+                .ok()
+                .map(|url| url.trim_end_matches('/').to_string())
+                .filter(|url| !url.is_empty()),
+            // Record sign-in devices and alert on logins from a new device or country
+            login_alerts: env::var("LOGIN_ALERTS")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()?,
+            // Uploads of these types are held until the moderation service approves them
+            moderation_url: env::var("MODERATION_URL").ok().filter(|s| !s.is_empty()),
+            moderation_token: env::var("MODERATION_TOKEN").ok().filter(|s| !s.is_empty()),
+            moderation_content_types: env::var("MODERATION_CONTENT_TYPES")
+                .unwrap_or_else(|_| "image/*".to_string())
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect(),
             allow_signup: env::var("ALLOW_SIGNUP")
                 .unwrap_or_else(|_| "true".to_string())
                 .parse()?,
@@ -56,6 +500,20 @@ impl Config {
             db_max_connections: env::var("DB_MAX_CONNECTIONS")
                 .unwrap_or_else(|_| "10".to_string())
                 .parse()?,
+            // Disable to run migrations as a separate deploy step (`--migrate`)
+            migrate_on_start: env::var("MIGRATE_ON_START")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()?,
+            // Log statements slower than this at WARN (0 disables)
+            db_slow_query_ms: env::var("DB_SLOW_QUERY_MS")
+                .unwrap_or_else(|_| "1000".to_string())
+                .parse()?,
+            // GET /metrics is only served (and query timings collected) when set
+            metrics_token: env::var("METRICS_TOKEN").ok().filter(|s| !s.is_empty()),
+            // Error bodies: `json` ({"error": ...}) or `problem` (RFC 7807 for every client)
+            error_format: env::var("ERROR_FORMAT")
+                .unwrap_or_else(|_| "json".to_string())
+                .parse()?,
             // Token expiry settings (defaults: access=15min, refresh=7days)
             access_token_expiry_minutes: env::var("ACCESS_TOKEN_EXPIRY_MINUTES")
                 .unwrap_or_else(|_| "15".to_string())
@@ -63,8 +521,38 @@ impl Config {
             refresh_token_expiry_days: env::var("REFRESH_TOKEN_EXPIRY_DAYS")
                 .unwrap_or_else(|_| "7".to_string())
                 .parse()?,
+            // Bot protection (defaults: disabled, PoW difficulty=20 bits)
+            captcha_provider: env::var("CAPTCHA_PROVIDER")
+                .unwrap_or_else(|_| "none".to_string())
+                .parse()?,
+            captcha_secret: env::var("CAPTCHA_SECRET").ok().filter(|s| !s.is_empty()),
+            captcha_pow_difficulty: env::var("CAPTCHA_POW_DIFFICULTY")
+                .unwrap_or_else(|_| "20".to_string())
+                .parse()?,
         };
 
         Ok(config)
     }
+
+    /// Hosts serving the API or the web app, which no project may take as a
+    /// custom domain: host routing would turn every request on them into a
+    /// file lookup
+    pub fn reserved_hosts(&self) -> Vec<String> {
+        let mut hosts: Vec<String> = self
+            .app_url
+            .iter()
+            .chain(&self.cdn_public_url)
+            .chain(&self.cors_origins)
+            .filter_map(|url| {
+                reqwest::Url::parse(url)
+                    .ok()?
+                    .host_str()
+                    .map(str::to_lowercase)
+            })
+            .collect();
+        hosts.extend(self.api_hosts.iter().cloned());
+        hosts.extend(self.inbound_email_domain.iter().cloned());
+        hosts.push("localhost".to_string());
+        hosts
+    }
 }
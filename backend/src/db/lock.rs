@@ -0,0 +1,57 @@
+use sqlx::{Connection, PgConnection};
+
+/// Whether holders of the same key may run at the same time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockMode {
+    Shared,
+    Exclusive,
+}
+
+/// Take a Postgres advisory lock on `key` until the current transaction ends.
+/// The lock lives in the database, so it also serializes other replicas.
+/// Call it on a transaction; in autocommit mode it is released immediately.
+pub async fn advisory_xact_lock(
+    conn: &mut PgConnection,
+    key: &str,
+    mode: LockMode,
+) -> Result<(), sqlx::Error> {
+    let sql = match mode {
+        LockMode::Shared => "SELECT pg_advisory_xact_lock_shared(hashtextextended($1, 0))",
+        LockMode::Exclusive => "SELECT pg_advisory_xact_lock(hashtextextended($1, 0))",
+    };
+    sqlx::query(sql).bind(key).execute(conn).await?;
+    Ok(())
+}
+
+/// A Postgres advisory lock held on a connection of its own, for work that
+/// runs too long to keep a pooled connection (and its transaction) busy.
+/// Dropping it closes the connection, which releases the lock as well.
+pub struct SessionLock {
+    conn: PgConnection,
+    key: String,
+}
+
+impl SessionLock {
+    /// Open a connection outside the pool and take an exclusive advisory lock
+    /// on `key`. It conflicts with `advisory_xact_lock` on the same key.
+    pub async fn acquire(database_url: &str, key: &str) -> Result<Self, sqlx::Error> {
+        let mut conn = PgConnection::connect(database_url).await?;
+        sqlx::query("SELECT pg_advisory_lock(hashtextextended($1, 0))")
+            .bind(key)
+            .execute(&mut conn)
+            .await?;
+        Ok(Self {
+            conn,
+            key: key.to_string(),
+        })
+    }
+
+    /// Release the lock and close its connection
+    pub async fn release(mut self) -> Result<(), sqlx::Error> {
+        sqlx::query("SELECT pg_advisory_unlock(hashtextextended($1, 0))")
+            .bind(&self.key)
+            .execute(&mut self.conn)
+            .await?;
+        self.conn.close().await
+    }
+}
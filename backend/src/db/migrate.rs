@@ -0,0 +1,32 @@
+use sqlx::{
+    migrate::{MigrateError, Migrator},
+    PgPool,
+};
+
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// Apply pending migrations. The migrator holds a Postgres advisory lock while
+/// it runs, so replicas migrating at the same time apply them one at a time.
+pub async fn run_migrations(pool: &PgPool) -> Result<(), MigrateError> {
+    MIGRATOR.run(pool).await
+}
+
+/// Versions of migrations that haven't been applied yet
+pub async fn pending_migrations(pool: &PgPool) -> Result<Vec<i64>, sqlx::Error> {
+    let applied = sqlx::query_scalar::<_, i64>(
+        "SELECT version FROM _sqlx_migrations WHERE success ORDER BY version",
+    )
+    .fetch_all(pool)
+    .await
+    .or_else(|e| match e {
+        // Fresh database: nothing applied yet
+        sqlx::Error::Database(ref db) if db.code().as_deref() == Some("42P01") => Ok(Vec::new()),
+        e => Err(e),
+    })?;
+
+    Ok(MIGRATOR
+        .iter()
+        .map(|migration| migration.version)
+        .filter(|version| applied.binary_search(version).is_err())
+        .collect())
+}
@@ -1,3 +1,9 @@
+pub mod lock;
+pub mod migrate;
 pub mod pool;
+pub mod read_pool;
 
-pub use pool::create_pool;
+pub use lock::{advisory_xact_lock, LockMode, SessionLock};
+pub use migrate::{pending_migrations, run_migrations};
+pub use pool::{create_pool, QueryLogging};
+pub use read_pool::ReadPool;
@@ -1,17 +1,42 @@
-use sqlx::{postgres::PgPoolOptions, PgPool};
-use std::time::Duration;
+use log::LevelFilter;
+use sqlx::{
+    postgres::{PgConnectOptions, PgPoolOptions},
+    ConnectOptions, PgPool,
+};
+use std::{str::FromStr, time::Duration};
+
+/// Statement logging for a pool's connections
+#[derive(Debug, Clone, Copy)]
+pub struct QueryLogging {
+    /// Statements slower than this are logged at WARN (`None` disables)
+    pub slow_threshold: Option<Duration>,
+    /// Emit every statement at TRACE so `QueryMetricsLayer` can time it
+    pub trace_all: bool,
+}
 
 pub async fn create_pool(
     database_url: &str,
     min_connections: u32,
     max_connections: u32,
+    logging: QueryLogging,
 ) -> Result<PgPool, sqlx::Error> {
+    let mut options =
+        PgConnectOptions::from_str(database_url)?.log_statements(if logging.trace_all {
+            LevelFilter::Trace
+        } else {
+            LevelFilter::Off
+        });
+    options = match logging.slow_threshold {
+        Some(threshold) => options.log_slow_statements(LevelFilter::Warn, threshold),
+        None => options.log_slow_statements(LevelFilter::Off, Duration::MAX),
+    };
+
     PgPoolOptions::new()
         .min_connections(min_connections)
         .max_connections(max_connections)
         .acquire_timeout(Duration::from_secs(30))
         .idle_timeout(Duration::from_secs(600))
         .max_lifetime(Duration::from_secs(1800))
-        .connect(database_url)
+        .connect_with(options)
         .await
 }
@@ -0,0 +1,52 @@
+use sqlx::PgPool;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use super::{create_pool, QueryLogging};
+
+/// Connections for read-heavy queries. Requests are spread round-robin over
+/// the configured replicas; without replicas every read goes to the primary.
+///
+/// Replicas lag slightly behind the primary, so only use this for reads where
+/// a just-written row being briefly missing or stale is acceptable.
+#[derive(Debug, Clone)]
+pub struct ReadPool {
+    primary: PgPool,
+    replicas: Arc<[PgPool]>,
+    next: Arc<AtomicUsize>,
+}
+
+impl ReadPool {
+    pub async fn connect(
+        primary: PgPool,
+        replica_urls: &[String],
+        min_connections: u32,
+        max_connections: u32,
+        logging: QueryLogging,
+    ) -> Result<Self, sqlx::Error> {
+        let mut replicas = Vec::with_capacity(replica_urls.len());
+        for url in replica_urls {
+            replicas.push(create_pool(url, min_connections, max_connections, logging).await?);
+        }
+        Ok(Self {
+            primary,
+            replicas: replicas.into(),
+            next: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    /// Pool to run the next read on
+    pub fn get(&self) -> &PgPool {
+        if self.replicas.is_empty() {
+            return &self.primary;
+        }
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % self.replicas.len();
+        &self.replicas[index]
+    }
+
+    pub fn replicas(&self) -> &[PgPool] {
+        &self.replicas
+    }
+}
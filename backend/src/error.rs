@@ -1,15 +1,49 @@
 use axum::{
-    http::StatusCode,
+    http::{header, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
-use serde_json::json;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
 use thiserror::Error;
 
+use crate::utils::password_policy::PolicyViolation;
+
+/// Body format for error responses (`ERROR_FORMAT`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum ErrorFormat {
+    /// `{"error": "...", ...}`
+    Json,
+    /// RFC 7807 `application/problem+json`
+    Problem,
+}
+
+impl std::str::FromStr for ErrorFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "" | "json" => Ok(ErrorFormat::Json),
+            "problem" => Ok(ErrorFormat::Problem),
+            other => Err(format!("Unknown ERROR_FORMAT: {other}")),
+        }
+    }
+}
+
+/// Attached to error responses so `problem_details_middleware` can re-render
+/// them as problem details when the client (or `ERROR_FORMAT`) asks for it
+#[derive(Debug, Clone)]
+pub struct ErrorDetails {
+    pub code: &'static str,
+    pub detail: String,
+    /// Members of the JSON body besides `error`, e.g. `retry_after`
+    pub extensions: Map<String, Value>,
+}
+
 #[derive(Error, Debug)]
 pub enum AppError {
     #[error("Database error: {0}")]
-    Database(#[from] sqlx::Error),
+    Database(sqlx::Error),
 
     #[error("Authentication failed")]
     Unauthorized,
@@ -36,6 +70,12 @@ pub enum AppError {
     #[error("Bad request: {0}")]
     BadRequest(String),
 
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    #[error("Precondition failed: {0}")]
+    PreconditionFailed(String),
+
     #[error("Internal server error: {0}")]
     InternalError(String),
 
@@ -45,12 +85,117 @@ pub enum AppError {
     #[error("Validation error: {0}")]
     ValidationError(String),
 
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
     #[error("Signup is disabled")]
     SignupDisabled,
+
+    #[error("CAPTCHA verification failed")]
+    CaptchaFailed,
+
+    #[error("You must change your password before continuing")]
+    PasswordChangeRequired,
+
+    #[error("Downloads are not available in your country")]
+    GeoBlocked(String),
+
+    #[error("Downloads are restricted to specific countries")]
+    GeoNotAllowed(Option<String>),
+
+    #[error("{0}")]
+    TooManyRequests(String, u64),
+
+    #[error("Project storage quota exceeded")]
+    QuotaExceeded { used: i64, quota: i64 },
+
+    #[error("{message}")]
+    InvalidUploadField {
+        field: String,
+        reason: &'static str,
+        message: String,
+    },
+
+    #[error("Password does not meet the password policy")]
+    WeakPassword {
+        field: String,
+        violations: Vec<PolicyViolation>,
+    },
+
+    #[error("{0}")]
+    LegalHold(String),
+
+    #[error("Project is archived")]
+    ProjectArchived,
+
+    #[error("Project is scheduled for deletion; restore it first")]
+    ProjectPendingDeletion,
+
+    #[error("{0}")]
+    RequestTimeout(String),
+
+    #[error("Server is busy, try again later")]
+    Overloaded(u64),
+
+    #[error("Cursor has expired; resync from the manifest")]
+    CursorExpired,
+}
+
+/// SQLSTATE raised by the legal hold triggers on `files` and `folders`
+const LEGAL_HOLD_SQLSTATE: &str = "LH001";
+
+impl From<sqlx::Error> for AppError {
+    fn from(e: sqlx::Error) -> Self {
+        if let sqlx::Error::Database(ref db) = e {
+            if db.code().as_deref() == Some(LEGAL_HOLD_SQLSTATE) {
+                return AppError::LegalHold(db.message().to_string());
+            }
+        }
+        AppError::Database(e)
+    }
+}
+
+impl AppError {
+    /// Stable machine-readable name of the error kind
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::Database(_) => "database_error",
+            AppError::Unauthorized => "unauthorized",
+            AppError::InvalidCredentials => "invalid_credentials",
+            AppError::TokenError(_) => "invalid_token",
+            AppError::RefreshTokenExpired => "refresh_token_expired",
+            AppError::RefreshTokenRevoked => "refresh_token_revoked",
+            AppError::TokenReuseDetected => "token_reuse_detected",
+            AppError::NotFound(_) => "not_found",
+            AppError::BadRequest(_) => "bad_request",
+            AppError::Conflict(_) => "conflict",
+            AppError::PreconditionFailed(_) => "precondition_failed",
+            AppError::InternalError(_) => "internal_error",
+            AppError::FileError(_) => "file_error",
+            AppError::ValidationError(_) => "validation_error",
+            AppError::Forbidden(_) => "forbidden",
+            AppError::SignupDisabled => "signup_disabled",
+            AppError::CaptchaFailed => "captcha_failed",
+            AppError::PasswordChangeRequired => "password_change_required",
+            AppError::GeoBlocked(_) => "geo_blocked",
+            AppError::GeoNotAllowed(_) => "geo_not_allowed",
+            AppError::TooManyRequests(..) => "too_many_requests",
+            AppError::QuotaExceeded { .. } => "quota_exceeded",
+            AppError::InvalidUploadField { .. } => "invalid_upload_field",
+            AppError::WeakPassword { .. } => "weak_password",
+            AppError::LegalHold(_) => "legal_hold",
+            AppError::ProjectArchived => "project_archived",
+            AppError::ProjectPendingDeletion => "project_pending_deletion",
+            AppError::RequestTimeout(_) => "request_timeout",
+            AppError::Overloaded(_) => "overloaded",
+            AppError::CursorExpired => "cursor_expired",
+        }
+    }
 }
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
+        let code = self.code();
         let (status, error_message) = match self {
             AppError::Database(ref e) => {
                 tracing::error!("Database error: {:?}", e);
@@ -79,6 +224,8 @@ impl IntoResponse for AppError {
             }
             AppError::NotFound(ref msg) => (StatusCode::NOT_FOUND, msg.clone()),
             AppError::BadRequest(ref msg) => (StatusCode::BAD_REQUEST, msg.clone()),
+            AppError::Conflict(ref msg) => (StatusCode::CONFLICT, msg.clone()),
+            AppError::PreconditionFailed(ref msg) => (StatusCode::PRECONDITION_FAILED, msg.clone()),
             AppError::InternalError(ref msg) => {
                 tracing::error!("Internal error: {}", msg);
                 (
@@ -91,14 +238,91 @@ impl IntoResponse for AppError {
                 (StatusCode::INTERNAL_SERVER_ERROR, msg.clone())
             }
             AppError::ValidationError(ref msg) => (StatusCode::BAD_REQUEST, msg.clone()),
+            AppError::Forbidden(ref msg) => (StatusCode::FORBIDDEN, msg.clone()),
             AppError::SignupDisabled => (StatusCode::FORBIDDEN, self.to_string()),
+            AppError::CaptchaFailed => (StatusCode::FORBIDDEN, self.to_string()),
+            AppError::PasswordChangeRequired => (StatusCode::FORBIDDEN, self.to_string()),
+            AppError::GeoBlocked(_) => {
+                (StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS, self.to_string())
+            }
+            AppError::GeoNotAllowed(_) => (StatusCode::FORBIDDEN, self.to_string()),
+            AppError::TooManyRequests(ref msg, _) => (StatusCode::TOO_MANY_REQUESTS, msg.clone()),
+            AppError::QuotaExceeded { .. } => (StatusCode::INSUFFICIENT_STORAGE, self.to_string()),
+            AppError::InvalidUploadField { ref message, .. } => {
+                (StatusCode::BAD_REQUEST, message.clone())
+            }
+            AppError::WeakPassword { .. } => (StatusCode::BAD_REQUEST, self.to_string()),
+            AppError::LegalHold(ref msg) => (StatusCode::LOCKED, msg.clone()),
+            AppError::ProjectArchived | AppError::ProjectPendingDeletion => {
+                (StatusCode::LOCKED, self.to_string())
+            }
+            AppError::RequestTimeout(ref msg) => (StatusCode::REQUEST_TIMEOUT, msg.clone()),
+            AppError::Overloaded(_) => (StatusCode::SERVICE_UNAVAILABLE, self.to_string()),
+            AppError::CursorExpired => (StatusCode::GONE, self.to_string()),
         };
 
-        let body = Json(json!({
-            "error": error_message,
-        }));
+        let extensions = match self {
+            AppError::GeoBlocked(ref country) => json!({
+                "code": code,
+                "country": country,
+            }),
+            AppError::GeoNotAllowed(ref country) => json!({
+                "code": code,
+                "country": country,
+            }),
+            AppError::QuotaExceeded { used, quota } => json!({
+                "code": code,
+                "used_bytes": used,
+                "quota_bytes": quota,
+            }),
+            AppError::TooManyRequests(_, retry_after) | AppError::Overloaded(retry_after) => {
+                json!({
+                    "retry_after": retry_after,
+                })
+            }
+            AppError::InvalidUploadField {
+                ref field, reason, ..
+            } => json!({
+                "code": code,
+                "field": field,
+                "reason": reason,
+            }),
+            AppError::PasswordChangeRequired | AppError::CursorExpired => json!({
+                "code": code,
+            }),
+            AppError::WeakPassword {
+                ref field,
+                ref violations,
+            } => json!({
+                "code": code,
+                "field": field,
+                "violations": violations,
+            }),
+            _ => json!({}),
+        };
+        let Value::Object(extensions) = extensions else {
+            unreachable!("error extensions are a JSON object")
+        };
 
-        (status, body).into_response()
+        let mut body = Map::new();
+        body.insert("error".to_string(), Value::from(error_message.clone()));
+        body.extend(extensions.clone());
+
+        let mut response = match self {
+            AppError::TooManyRequests(_, retry_after) | AppError::Overloaded(retry_after) => (
+                status,
+                [(header::RETRY_AFTER, retry_after.to_string())],
+                Json(body),
+            )
+                .into_response(),
+            _ => (status, Json(body)).into_response(),
+        };
+        response.extensions_mut().insert(ErrorDetails {
+            code,
+            detail: error_message,
+            extensions,
+        });
+        response
     }
 }
 
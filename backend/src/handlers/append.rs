@@ -0,0 +1,296 @@
+use std::io::SeekFrom;
+
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::HeaderMap,
+    Json,
+};
+use futures::StreamExt;
+use sqlx::FromRow;
+use tokio::{
+    fs,
+    io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt},
+};
+use uuid::Uuid;
+
+use crate::{
+    db::SessionLock,
+    error::{AppError, Result},
+    handlers::{
+        content::content_lock_key,
+        file::{load_file_metadata, load_file_scope, under_legal_hold, upload_permit},
+    },
+    middleware::OptionalAuthUser,
+    models::{AppendOnlyRequest, AppendResponse, File, FileMetadata, Project},
+    utils::{
+        admin_override, can_write, check_quota, ensure_hot, ensure_project_writable,
+        queue_cdn_purge, queue_replication, read_file_content, record_file_events, remove_variants,
+        spawn_quota_warnings, AdminQuery, AppendHasher, Credentials, FileEventKind, RolePermission,
+        SHA256_BLOCK_SIZE,
+    },
+    AppState,
+};
+
+/// Read size when hashing existing content without a saved state
+const HASH_READ_BUFFER_SIZE: usize = 64 * 1024;
+
+#[derive(FromRow)]
+struct AppendState {
+    append_only: bool,
+    content_hash: String,
+    append_hash_state: Option<Vec<u8>>,
+    append_hash_for: Option<String>,
+}
+
+/// Flag a file as append-only, so it only grows through appends, or clear
+/// the flag. Same authentication as `delete_file`.
+pub async fn set_file_append_only(
+    State(state): State<AppState>,
+    optional_auth: OptionalAuthUser,
+    headers: HeaderMap,
+    Path(file_id): Path<Uuid>,
+    Query(admin): Query<AdminQuery>,
+    Json(req): Json<AppendOnlyRequest>,
+) -> Result<Json<FileMetadata>> {
+    let (_, project, _) = load_file_scope(&state.pool, file_id).await?;
+
+    let credentials = match optional_auth.0 {
+        Some(ref user)
+            if admin_override(
+                user,
+                &admin,
+                RolePermission::AdminFilesWrite,
+                "set_file_append_only",
+                file_id,
+            )? =>
+        {
+            Credentials::Admin
+        }
+        _ => Credentials::resolve(&state.pool, &optional_auth, &headers, None).await?,
+    };
+    if !can_write(&project, &credentials) {
+        return Err(AppError::Unauthorized);
+    }
+    ensure_project_writable(&project)?;
+
+    sqlx::query("UPDATE files SET append_only = $1 WHERE id = $2")
+        .bind(req.append_only)
+        .bind(file_id)
+        .execute(&state.pool)
+        .await?;
+
+    Ok(Json(load_file_metadata(&state.pool, file_id).await?))
+}
+
+/// Hasher holding the first `size` bytes of a blob: resumed from the saved
+/// state when it still belongs to the content, otherwise by reading it all
+async fn resume_hasher(path: &str, size: u64, append: &AppendState) -> Result<AppendHasher> {
+    let read_error = |e: std::io::Error| AppError::FileError(format!("Failed to read file: {e}"));
+    let mut blob = fs::File::open(path).await.map_err(read_error)?;
+
+    let saved = append
+        .append_hash_state
+        .as_deref()
+        .filter(|_| append.append_hash_for.as_deref() == Some(append.content_hash.as_str()));
+    if let Some(saved) = saved {
+        let hashed = size - size % SHA256_BLOCK_SIZE;
+        let mut tail = vec![0u8; (size - hashed) as usize];
+        blob.seek(SeekFrom::Start(hashed))
+            .await
+            .map_err(read_error)?;
+        blob.read_exact(&mut tail).await.map_err(read_error)?;
+        if let Some(hasher) = AppendHasher::resume(saved, hashed, &tail) {
+            return Ok(hasher);
+        }
+        blob.rewind().await.map_err(read_error)?;
+    }
+
+    let mut hasher = AppendHasher::default();
+    let mut remaining = size;
+    let mut buffer = vec![0u8; HASH_READ_BUFFER_SIZE];
+    while remaining > 0 {
+        let len = remaining.min(HASH_READ_BUFFER_SIZE as u64) as usize;
+        blob.read_exact(&mut buffer[..len])
+            .await
+            .map_err(read_error)?;
+        hasher.update(&buffer[..len]);
+        remaining -= len as u64;
+    }
+    Ok(hasher)
+}
+
+/// Store a compressed blob as-is, so it can be appended to in place
+async fn decompress_in_place(state: &AppState, file: &mut File) -> Result<()> {
+    let data = read_file_content(&state.pool, state.cold_storage.as_deref(), file).await?;
+    let staging = format!("{}.append", file.file_path);
+    let written = async {
+        fs::write(&staging, &data).await?;
+        fs::rename(&staging, &file.file_path).await
+    }
+    .await;
+    if let Err(e) = written {
+        let _ = fs::remove_file(&staging).await;
+        return Err(AppError::FileError(format!(
+            "Failed to decompress file for appending: {e}"
+        )));
+    }
+    sqlx::query("UPDATE files SET storage_encoding = NULL, stored_size = size WHERE id = $1")
+        .bind(file.id)
+        .execute(&state.pool)
+        .await?;
+    file.storage_encoding = None;
+    Ok(())
+}
+
+/// Append the request body to an append-only file. Appends to one file are
+/// applied one at a time; the bytes only count once the new size and hash
+/// are recorded, and a failed append is cut off again.
+pub async fn append_to_file(
+    State(state): State<AppState>,
+    optional_auth: OptionalAuthUser,
+    headers: HeaderMap,
+    Path(file_id): Path<Uuid>,
+    body: Body,
+) -> Result<Json<AppendResponse>> {
+    let (_, project, _) = load_file_scope(&state.pool, file_id).await?;
+    let credentials = Credentials::resolve(&state.pool, &optional_auth, &headers, None).await?;
+    if !can_write(&project, &credentials) {
+        return Err(AppError::Unauthorized);
+    }
+    ensure_project_writable(&project)?;
+    let _upload_permit = upload_permit(&state, &project)?;
+
+    // Shared with content edits, so appends and edits of a file don't
+    // interleave. Held on its own connection while the body streams in; the
+    // pool is only used for the final update.
+    let lock = SessionLock::acquire(&state.config.database_url, &content_lock_key(file_id)).await?;
+    let result = append_locked(&state, &project, file_id, body).await;
+    if let Err(e) = lock.release().await {
+        tracing::warn!("Failed to release append lock on {}: {}", file_id, e);
+    }
+    result
+}
+
+/// The body of `append_to_file`, run while the file's content lock is held
+async fn append_locked(
+    state: &AppState,
+    project: &Project,
+    file_id: Uuid,
+    body: Body,
+) -> Result<Json<AppendResponse>> {
+    let (mut file, _, _) = load_file_scope(&state.pool, file_id).await?;
+    let append = sqlx::query_as::<_, AppendState>(
+        "SELECT append_only, content_hash, append_hash_state, append_hash_for FROM files WHERE id = $1",
+    )
+    .bind(file.id)
+    .fetch_one(&state.pool)
+    .await?;
+    if !append.append_only {
+        return Err(AppError::Conflict(format!(
+            "File \"{}\" is not append-only",
+            file.original_name
+        )));
+    }
+    if under_legal_hold(&state.pool, file.id).await? {
+        return Err(AppError::LegalHold(format!(
+            "File \"{}\" is under legal hold",
+            file.original_name
+        )));
+    }
+
+    ensure_hot(&state.pool, state.cold_storage.as_deref(), &file).await?;
+    if file.storage_encoding.is_some() {
+        decompress_in_place(state, &mut file).await?;
+    }
+    let original_size = file.size as u64;
+    let mut hasher = resume_hasher(&file.file_path, original_size, &append).await?;
+
+    let mut blob = fs::OpenOptions::new()
+        .write(true)
+        .open(&file.file_path)
+        .await
+        .map_err(|e| AppError::FileError(format!("Failed to open file: {e}")))?;
+    let max_append = (state.config.max_file_size as u64).saturating_sub(original_size);
+    let result: Result<File> = async {
+        let write_error = |e: std::io::Error| AppError::FileError(format!("Failed to append: {e}"));
+        // Bytes past the recorded size are left over from an interrupted append
+        blob.set_len(original_size).await.map_err(write_error)?;
+        blob.seek(SeekFrom::End(0)).await.map_err(write_error)?;
+
+        let mut appended = 0u64;
+        let mut stream = body.into_data_stream();
+        while let Some(chunk) = stream.next().await {
+            let chunk =
+                chunk.map_err(|e| AppError::BadRequest(format!("Failed to read body: {e}")))?;
+            appended += chunk.len() as u64;
+            if appended > max_append {
+                return Err(AppError::BadRequest(format!(
+                    "File size exceeds maximum of {} bytes",
+                    state.config.max_file_size
+                )));
+            }
+            hasher.update(&chunk);
+            blob.write_all(&chunk).await.map_err(write_error)?;
+        }
+        blob.flush().await.map_err(write_error)?;
+        if appended == 0 {
+            return Err(AppError::BadRequest("Nothing to append".to_string()));
+        }
+        check_quota(&state.pool, project.id, appended as i64).await?;
+
+        let mut tx = state.pool.begin().await?;
+        let size = (original_size + appended) as i64;
+        let content_hash = hex::encode(hasher.digest());
+        let file_record = sqlx::query_as::<_, File>(
+            r#"
+            UPDATE files
+            SET size = $1, stored_size = $1, content_hash = $2, append_hash_state = $3,
+                append_hash_for = $2, precompressed_encodings = '{}', upload_date = NOW()
+            WHERE id = $4
+            RETURNING id, project_id, folder_id, original_name, stored_name, file_path, size, mime_type, upload_date, storage_encoding, precompressed_encodings, storage_tier, moderation_status
+            "#,
+        )
+        .bind(size)
+        .bind(&content_hash)
+        .bind(hasher.saved_state())
+        .bind(file.id)
+        .fetch_one(&mut *tx)
+        .await?;
+        record_file_events(
+            &mut tx,
+            state.events.as_deref(),
+            FileEventKind::Uploaded,
+            [&file_record],
+        )
+        .await?;
+        queue_replication(&mut tx, state.replication.as_deref(), [&file_record]).await?;
+        queue_cdn_purge(&mut tx, state.cdn.as_deref(), [&file_record]).await?;
+        tx.commit().await?;
+        Ok(file_record)
+    }
+    .await;
+
+    let file_record = match result {
+        Ok(file_record) => file_record,
+        Err(e) => {
+            if let Err(truncate_error) = blob.set_len(original_size).await {
+                tracing::warn!(
+                    "Failed to cut off failed append to {}: {}",
+                    file.file_path,
+                    truncate_error
+                );
+            }
+            return Err(e);
+        }
+    };
+    spawn_quota_warnings(state.pool.clone(), state.mailer.clone(), project.id);
+    remove_variants(&file.file_path).await;
+
+    Ok(Json(AppendResponse {
+        file_id: file_record.id,
+        appended: file_record.size - original_size as i64,
+        size: file_record.size,
+        sha256: hex::encode(hasher.digest()),
+    }))
+}
@@ -1,4 +1,8 @@
-use axum::{extract::State, Json};
+use axum::{
+    extract::State,
+    http::{header::USER_AGENT, HeaderMap},
+    Json,
+};
 use chrono::{Duration, Utc};
 use sqlx::PgPool;
 use uuid::Uuid;
@@ -6,27 +10,66 @@ use validator::Validate;
 
 use crate::{
     error::{AppError, Result},
-    middleware::AuthUser,
+    middleware::{AuthUser, ClientIp},
     models::{
         AuthResponse, ChangePasswordRequest, ChangePasswordResponse, CreateUserRequest,
-        LoginRequest, LogoutAllResponse, LogoutRequest, LogoutResponse, RefreshRequest,
-        TokenAuthResponse, TokenRefreshResponse, User, UserInfo, UserRole,
+        LoginRequest, LogoutAllResponse, LogoutRequest, LogoutResponse, NotificationPreferences,
+        RefreshRequest, ReportLoginRequest, TokenAuthResponse, TokenRefreshResponse, User,
+        UserInfo, UserRole,
     },
     utils::{
-        create_access_token, create_refresh_token, create_token, hash_password, hash_token,
-        verify_password, verify_refresh_token,
+        captcha::{CaptchaProvider, PowChallenge},
+        check_password_policy, create_access_token, create_pow_challenge, create_refresh_token,
+        create_token, hash_password, hash_token, lookup_country, spawn_login_check, verify_captcha,
+        verify_password, verify_refresh_token, LoginEvent, PasswordHashing,
     },
     AppState,
 };
 
-/// Helper to create tokens and store refresh token in DB
+/// Re-hash a password that just verified when its stored hash uses an older
+/// algorithm or cheaper parameters than `ARGON2_*`. Failures are only logged.
+async fn upgrade_password_hash(state: &AppState, user: &User, password: &str) {
+    let hashing = &state.config.password_hashing;
+    if !hashing.needs_rehash(&user.password_hash) {
+        return;
+    }
+    let password_hash = match hash_password(password, hashing) {
+        Ok(hash) => hash,
+        Err(e) => {
+            tracing::warn!("Failed to re-hash password of user {}: {}", user.id, e);
+            return;
+        }
+    };
+    // Leave it alone if the password was changed in the meantime
+    let result =
+        sqlx::query("UPDATE users SET password_hash = $1 WHERE id = $2 AND password_hash = $3")
+            .bind(&password_hash)
+            .bind(user.id)
+            .bind(&user.password_hash)
+            .execute(&state.pool)
+            .await;
+    if let Err(e) = result {
+        tracing::warn!("Failed to upgrade password hash of user {}: {}", user.id, e);
+    }
+}
+
+/// Extract the User-Agent header for session records
+fn user_agent(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(USER_AGENT)
+        .and_then(|h| h.to_str().ok())
+        .map(|s| s.chars().take(512).collect())
+}
+
+/// Helper to create tokens and store refresh token in DB. Returns the access
+/// token, refresh token, access token lifetime and the new session's family.
 async fn create_token_pair(
     pool: &PgPool,
     user: &User,
     config: &crate::config::Config,
     user_agent: Option<String>,
     ip_address: Option<String>,
-) -> Result<(String, String, i64)> {
+) -> Result<(String, String, i64, Uuid)> {
     // Create access token
     let access_token = create_access_token(
         user.id,
@@ -71,11 +114,14 @@ async fn create_token_pair(
         access_token,
         refresh_token,
         config.access_token_expiry_minutes * 60, // expires_in in seconds
+        family_id,
     ))
 }
 
 pub async fn register(
     State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    headers: HeaderMap,
     Json(payload): Json<CreateUserRequest>,
 ) -> Result<Json<TokenAuthResponse>> {
     // Validate input
@@ -83,13 +129,23 @@ pub async fn register(
         .validate()
         .map_err(|e| AppError::ValidationError(e.to_string()))?;
 
+    // Bot protection (no-op unless CAPTCHA_PROVIDER is set)
+    verify_captcha(
+        &state.config,
+        &state.pow_replay,
+        payload.captcha_token.as_deref(),
+        Some(&client_ip.to_string()),
+    )
+    .await?;
+
     // Check if signup is allowed
     if !state.config.allow_signup {
         return Err(AppError::SignupDisabled);
     }
+    check_password_policy(&state.config, "password", &payload.password, &payload.email)?;
 
     // Hash password
-    let password_hash = hash_password(&payload.password)
+    let password_hash = hash_password(&payload.password, &state.config.password_hashing)
         .map_err(|e| AppError::InternalError(format!("Failed to hash password: {e}")))?;
 
     // Insert user (regular users don't need to change password)
@@ -113,8 +169,14 @@ pub async fn register(
     })?;
 
     // Create token pair
-    let (access_token, refresh_token, expires_in) =
-        create_token_pair(&state.pool, &user, &state.config, None, None).await?;
+    let (access_token, refresh_token, expires_in, _) = create_token_pair(
+        &state.pool,
+        &user,
+        &state.config,
+        user_agent(&headers),
+        Some(client_ip.to_string()),
+    )
+    .await?;
 
     Ok(Json(TokenAuthResponse {
         access_token,
@@ -127,6 +189,8 @@ pub async fn register(
 
 pub async fn login(
     State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    headers: HeaderMap,
     Json(payload): Json<LoginRequest>,
 ) -> Result<Json<TokenAuthResponse>> {
     // Validate input
@@ -134,6 +198,15 @@ pub async fn login(
         .validate()
         .map_err(|e| AppError::ValidationError(e.to_string()))?;
 
+    // Bot protection (no-op unless CAPTCHA_PROVIDER is set)
+    verify_captcha(
+        &state.config,
+        &state.pow_replay,
+        payload.captcha_token.as_deref(),
+        Some(&client_ip.to_string()),
+    )
+    .await?;
+
     // Get user by email
     let user = sqlx::query_as::<_, User>(
         r#"
@@ -154,10 +227,36 @@ pub async fn login(
     if !is_valid {
         return Err(AppError::InvalidCredentials);
     }
+    upgrade_password_hash(&state, &user, &payload.password).await;
 
     // Create token pair
-    let (access_token, refresh_token, expires_in) =
-        create_token_pair(&state.pool, &user, &state.config, None, None).await?;
+    let (access_token, refresh_token, expires_in, family_id) = create_token_pair(
+        &state.pool,
+        &user,
+        &state.config,
+        user_agent(&headers),
+        Some(client_ip.to_string()),
+    )
+    .await?;
+
+    if state.config.login_alerts {
+        spawn_login_check(
+            state.pool.clone(),
+            state.mailer.clone(),
+            state.config.app_url.clone(),
+            LoginEvent {
+                user_id: user.id,
+                email: user.email.clone(),
+                family_id,
+                ip: client_ip,
+                user_agent: user_agent(&headers),
+                country: state
+                    .geoip
+                    .as_deref()
+                    .and_then(|reader| lookup_country(reader, client_ip)),
+            },
+        );
+    }
 
     Ok(Json(TokenAuthResponse {
         access_token,
@@ -168,8 +267,24 @@ pub async fn login(
     }))
 }
 
+/// Issue a proof-of-work challenge for login/register (only when CAPTCHA_PROVIDER=pow)
+pub async fn get_pow_challenge(State(state): State<AppState>) -> Result<Json<PowChallenge>> {
+    if state.config.captcha_provider != CaptchaProvider::ProofOfWork {
+        return Err(AppError::NotFound(
+            "Proof-of-work is not enabled".to_string(),
+        ));
+    }
+
+    Ok(Json(create_pow_challenge(
+        &state.config.jwt_secret,
+        state.config.captcha_pow_difficulty,
+    )))
+}
+
 pub async fn refresh_token(
     State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    headers: HeaderMap,
     Json(payload): Json<RefreshRequest>,
 ) -> Result<Json<TokenRefreshResponse>> {
     // Verify the refresh token JWT
@@ -181,7 +296,7 @@ pub async fn refresh_token(
     // Look up token in database
     let stored_token = sqlx::query_as::<_, crate::models::RefreshToken>(
         r#"
-        SELECT id, user_id, token_hash, family_id, expires_at, created_at, revoked_at, revoked_reason, user_agent, ip_address
+        SELECT id, user_id, token_hash, family_id, expires_at, created_at, revoked_at, revoked_reason, user_agent, host(ip_address) AS ip_address
         FROM refresh_tokens
         WHERE token_hash = $1
         "#,
@@ -210,9 +325,10 @@ pub async fn refresh_token(
         .await?;
 
         tracing::error!(
-            "SECURITY: Token reuse detected for user {} family {}",
+            "SECURITY: Token reuse detected for user {} family {} from {}",
             stored_token.user_id,
-            stored_token.family_id
+            stored_token.family_id,
+            client_ip
         );
 
         return Err(AppError::TokenReuseDetected);
@@ -284,8 +400,8 @@ pub async fn refresh_token(
     .bind(&new_token_hash)
     .bind(family_id)
     .bind(expires_at)
-    .bind(&stored_token.user_agent)
-    .bind(&stored_token.ip_address)
+    .bind(user_agent(&headers).or(stored_token.user_agent))
+    .bind(client_ip.to_string())
     .execute(&state.pool)
     .await?;
 
@@ -339,12 +455,68 @@ pub async fn logout_all(
     .execute(&state.pool)
     .await?;
 
+    // Cut off access tokens too, including the one used for this request
+    state
+        .token_revocations
+        .revoke_all(&state.pool, auth_user.id)
+        .await?;
+
     Ok(Json(LogoutAllResponse {
         message: "All sessions logged out".to_string(),
         revoked_count: result.rows_affected() as i64,
     }))
 }
 
+/// "This wasn't me" for a login alert: sign out the session the login started,
+/// and cut off access tokens issued so far (other sessions get new ones on refresh)
+pub async fn report_login(
+    State(state): State<AppState>,
+    Json(payload): Json<ReportLoginRequest>,
+) -> Result<Json<LogoutAllResponse>> {
+    let alert: Option<(Uuid, Uuid, Uuid)> = sqlx::query_as(
+        r#"
+        UPDATE login_alerts
+        SET reported_at = COALESCE(reported_at, NOW())
+        WHERE report_token_hash = $1
+        RETURNING id, user_id, family_id
+        "#,
+    )
+    .bind(hash_token(&payload.token))
+    .fetch_optional(&state.pool)
+    .await?;
+    let (alert_id, user_id, family_id) =
+        alert.ok_or(AppError::NotFound("Login alert not found".to_string()))?;
+
+    let result = sqlx::query(
+        r#"
+        UPDATE refresh_tokens
+        SET revoked_at = NOW(), revoked_reason = 'reported_login'
+        WHERE family_id = $1 AND revoked_at IS NULL
+        "#,
+    )
+    .bind(family_id)
+    .execute(&state.pool)
+    .await?;
+    state
+        .token_revocations
+        .revoke_all(&state.pool, user_id)
+        .await?;
+
+    tracing::info!(
+        target: "audit",
+        %user_id,
+        %alert_id,
+        %family_id,
+        "Login reported as not made by the account owner"
+    );
+
+    Ok(Json(LogoutAllResponse {
+        message: "The session was signed out. Change your password if you haven't already."
+            .to_string(),
+        revoked_count: result.rows_affected() as i64,
+    }))
+}
+
 pub async fn get_current_user(
     State(state): State<AppState>,
     auth_user: AuthUser,
@@ -367,6 +539,8 @@ pub async fn get_current_user(
 pub async fn change_password(
     State(state): State<AppState>,
     auth_user: AuthUser,
+    ClientIp(client_ip): ClientIp,
+    headers: HeaderMap,
     Json(payload): Json<ChangePasswordRequest>,
 ) -> Result<Json<ChangePasswordResponse>> {
     // Validate input
@@ -395,9 +569,15 @@ pub async fn change_password(
             "Current password is incorrect".to_string(),
         ));
     }
+    check_password_policy(
+        &state.config,
+        "new_password",
+        &payload.new_password,
+        &user.email,
+    )?;
 
     // Hash new password
-    let new_password_hash = hash_password(&payload.new_password)
+    let new_password_hash = hash_password(&payload.new_password, &state.config.password_hashing)
         .map_err(|e| AppError::InternalError(format!("Failed to hash password: {e}")))?;
 
     // Update password and clear must_change_password flag
@@ -413,7 +593,7 @@ pub async fn change_password(
     .execute(&state.pool)
     .await?;
 
-    // Revoke all refresh tokens on password change (security)
+    // Revoke all refresh and access tokens on password change (security)
     sqlx::query(
         r#"
         UPDATE refresh_tokens
@@ -424,14 +604,67 @@ pub async fn change_password(
     .bind(auth_user.id)
     .execute(&state.pool)
     .await?;
+    state
+        .token_revocations
+        .revoke_all(&state.pool, auth_user.id)
+        .await?;
+
+    // Keep the caller signed in with a new session
+    let (access_token, refresh_token, expires_in, _) = create_token_pair(
+        &state.pool,
+        &user,
+        &state.config,
+        user_agent(&headers),
+        Some(client_ip.to_string()),
+    )
+    .await?;
 
     Ok(Json(ChangePasswordResponse {
         message: "Password changed successfully".to_string(),
+        access_token,
+        refresh_token,
+        token_type: "Bearer".to_string(),
+        expires_in,
     }))
 }
 
+pub async fn get_notification_preferences(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+) -> Result<Json<NotificationPreferences>> {
+    let preferences = sqlx::query_as::<_, NotificationPreferences>(
+        "SELECT notify_quota_warnings FROM users WHERE id = $1",
+    )
+    .bind(auth_user.id)
+    .fetch_one(&state.pool)
+    .await?;
+
+    Ok(Json(preferences))
+}
+
+pub async fn update_notification_preferences(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Json(payload): Json<NotificationPreferences>,
+) -> Result<Json<NotificationPreferences>> {
+    let preferences = sqlx::query_as::<_, NotificationPreferences>(
+        "UPDATE users SET notify_quota_warnings = $1 WHERE id = $2 RETURNING notify_quota_warnings",
+    )
+    .bind(payload.notify_quota_warnings)
+    .bind(auth_user.id)
+    .fetch_one(&state.pool)
+    .await?;
+
+    Ok(Json(preferences))
+}
+
 // Admin function to create admin user on startup
-pub async fn ensure_admin_user(pool: &PgPool, email: &str, password: &str) -> Result<()> {
+pub async fn ensure_admin_user(
+    pool: &PgPool,
+    email: &str,
+    password: &str,
+    hashing: &PasswordHashing,
+) -> Result<()> {
     // Check if admin already exists
     let admin_exists =
         sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM users WHERE role = 'admin')")
@@ -444,7 +677,7 @@ pub async fn ensure_admin_user(pool: &PgPool, email: &str, password: &str) -> Re
     }
 
     // Create admin user with must_change_password = true
-    let password_hash = hash_password(password)
+    let password_hash = hash_password(password, hashing)
         .map_err(|e| AppError::InternalError(format!("Failed to hash password: {e}")))?;
 
     sqlx::query(
@@ -475,6 +708,7 @@ pub async fn ensure_admin_user(pool: &PgPool, email: &str, password: &str) -> Re
 
 pub async fn login_legacy(
     State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
     Json(payload): Json<LoginRequest>,
 ) -> Result<Json<AuthResponse>> {
     // Validate input
@@ -482,6 +716,15 @@ pub async fn login_legacy(
         .validate()
         .map_err(|e| AppError::ValidationError(e.to_string()))?;
 
+    // Bot protection (no-op unless CAPTCHA_PROVIDER is set)
+    verify_captcha(
+        &state.config,
+        &state.pow_replay,
+        payload.captcha_token.as_deref(),
+        Some(&client_ip.to_string()),
+    )
+    .await?;
+
     // Get user by email
     let user = sqlx::query_as::<_, User>(
         r#"
@@ -502,6 +745,7 @@ pub async fn login_legacy(
     if !is_valid {
         return Err(AppError::InvalidCredentials);
     }
+    upgrade_password_hash(&state, &user, &payload.password).await;
 
     // Create legacy JWT token
     let token = create_token(
@@ -519,6 +763,7 @@ pub async fn login_legacy(
 
 pub async fn register_legacy(
     State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
     Json(payload): Json<CreateUserRequest>,
 ) -> Result<Json<AuthResponse>> {
     // Validate input
@@ -526,13 +771,23 @@ pub async fn register_legacy(
         .validate()
         .map_err(|e| AppError::ValidationError(e.to_string()))?;
 
+    // Bot protection (no-op unless CAPTCHA_PROVIDER is set)
+    verify_captcha(
+        &state.config,
+        &state.pow_replay,
+        payload.captcha_token.as_deref(),
+        Some(&client_ip.to_string()),
+    )
+    .await?;
+
     // Check if signup is allowed
     if !state.config.allow_signup {
         return Err(AppError::SignupDisabled);
     }
+    check_password_policy(&state.config, "password", &payload.password, &payload.email)?;
 
     // Hash password
-    let password_hash = hash_password(&payload.password)
+    let password_hash = hash_password(&payload.password, &state.config.password_hashing)
         .map_err(|e| AppError::InternalError(format!("Failed to hash password: {e}")))?;
 
     // Insert user
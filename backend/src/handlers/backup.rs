@@ -0,0 +1,38 @@
+use axum::{extract::State, Json};
+
+use crate::{
+    error::Result,
+    middleware::RequirePermission,
+    models::Backup,
+    utils::{begin_backup, perm, run_backup, BACKUP_COLUMNS},
+    AppState,
+};
+
+pub async fn list_backups(
+    State(state): State<AppState>,
+    _: RequirePermission<perm::AdminBackupsRead>,
+) -> Result<Json<Vec<Backup>>> {
+    let backups = sqlx::query_as::<_, Backup>(&format!(
+        "SELECT {BACKUP_COLUMNS} FROM backups ORDER BY started_at DESC LIMIT 100"
+    ))
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(Json(backups))
+}
+
+/// Start a backup now; it runs in the background and shows up in `GET /api/admin/backups`
+pub async fn trigger_backup(
+    State(state): State<AppState>,
+    RequirePermission(auth_user, _): RequirePermission<perm::AdminBackupsRun>,
+) -> Result<Json<Backup>> {
+    let backup = begin_backup(&state.pool, "manual", Some(auth_user.id)).await?;
+    tokio::spawn(run_backup(
+        state.pool.clone(),
+        state.config.backup_path.clone(),
+        state.backup_target.clone(),
+        backup.id,
+    ));
+
+    Ok(Json(backup))
+}
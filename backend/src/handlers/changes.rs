@@ -0,0 +1,154 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::HeaderMap,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::{
+    error::{AppError, Result},
+    middleware::OptionalAuthUser,
+    models::{ChangesFeed, FileChange, ManifestFile, Project, PROJECT_COLUMNS},
+    utils::{
+        can_list, can_write, decode_cursor, encode_cursor, number_file_changes, Credentials,
+        DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE,
+    },
+    AppState,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct ChangesQuery {
+    /// `cursor` of the last response, or `latest` to start from now; from
+    /// the oldest change kept when missing
+    pub since: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// A logged change with the current state of its file, if it still exists
+#[derive(FromRow)]
+struct FileChangeRow {
+    seq: i64,
+    change_type: String,
+    file_id: Uuid,
+    changed_at: DateTime<Utc>,
+    path: Option<String>,
+    size: Option<i64>,
+    sha256: Option<String>,
+    mime_type: Option<String>,
+    modified_at: Option<DateTime<Utc>>,
+}
+
+impl From<FileChangeRow> for FileChange {
+    fn from(row: FileChangeRow) -> Self {
+        let file = match (row.path, row.size, row.mime_type, row.modified_at) {
+            (Some(path), Some(size), Some(mime_type), Some(modified_at)) => Some(ManifestFile {
+                id: row.file_id,
+                path,
+                size,
+                sha256: row.sha256,
+                mime_type,
+                modified_at,
+            }),
+            _ => None,
+        };
+        FileChange {
+            change_type: row.change_type,
+            file_id: row.file_id,
+            changed_at: row.changed_at,
+            file,
+        }
+    }
+}
+
+/// Files of a project created, updated (overwritten, moved, renamed, edited
+/// or moderated) or deleted after `since`, oldest first, so a sync client
+/// can catch up without diffing manifests. Start from a manifest and
+/// `since=latest`, then keep sending back the returned `cursor`. A cursor
+/// older than `CHANGES_RETENTION_DAYS` gets `410 Gone`; resync from the
+/// manifest. Needs the project API key or a collaborator role; for those who
+/// can't write, a file that is awaiting or failing moderation shows up as
+/// deleted.
+pub async fn project_changes(
+    State(state): State<AppState>,
+    optional_auth: OptionalAuthUser,
+    headers: HeaderMap,
+    Path(project_id): Path<Uuid>,
+    Query(query): Query<ChangesQuery>,
+) -> Result<Json<ChangesFeed>> {
+    let project = sqlx::query_as::<_, Project>(&format!(
+        "SELECT {PROJECT_COLUMNS} FROM projects WHERE id = $1"
+    ))
+    .bind(project_id)
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or(AppError::NotFound("Project not found".to_string()))?;
+    let credentials = Credentials::resolve(&state.pool, &optional_auth, &headers, None).await?;
+    if !can_list(&project, &credentials) {
+        return Err(AppError::Unauthorized);
+    }
+
+    // Read from the primary: a replica may not have the changes numbered yet
+    let positions = number_file_changes(&state.pool).await?;
+    let since = match query.since.as_deref().filter(|s| !s.is_empty()) {
+        None => positions.purged_through,
+        Some("latest") => positions.numbered_through,
+        Some(cursor) => decode_cursor::<i64>(cursor)?,
+    };
+    if since > positions.numbered_through {
+        return Err(AppError::BadRequest("Invalid cursor".to_string()));
+    }
+    if since < positions.purged_through {
+        return Err(AppError::CursorExpired);
+    }
+
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    let mut rows = sqlx::query_as::<_, FileChangeRow>(
+        r#"
+        SELECT
+            c.seq,
+            CASE WHEN v.id IS NULL AND f.id IS NOT NULL THEN 'deleted' ELSE c.change_type END
+                AS change_type,
+            c.file_id,
+            c.changed_at,
+            COALESCE(fol.path || '/', '') || v.original_name AS path,
+            v.size,
+            v.content_hash AS sha256,
+            v.mime_type,
+            v.upload_date AS modified_at
+        FROM file_changes c
+        LEFT JOIN files f ON f.id = c.file_id
+        LEFT JOIN files v ON v.id = f.id AND ($4 OR v.moderation_status = 'approved')
+        LEFT JOIN folders fol ON fol.id = v.folder_id
+        WHERE c.project_id = $1 AND c.seq > $2 AND c.seq <= $3
+        ORDER BY c.seq
+        LIMIT $5
+        "#,
+    )
+    .bind(project_id)
+    .bind(since)
+    .bind(positions.numbered_through)
+    .bind(can_write(&project, &credentials))
+    .bind(limit + 1)
+    .fetch_all(&state.pool)
+    .await?;
+
+    let has_more = rows.len() as i64 > limit;
+    rows.truncate(limit as usize);
+    // Past the last change of any project when this is everything so far,
+    // so the next call skips what other projects logged in between
+    let cursor = match rows.last() {
+        Some(last) if has_more => last.seq,
+        _ => positions.numbered_through,
+    };
+    Ok(Json(ChangesFeed {
+        changes: rows.into_iter().map(FileChange::from).collect(),
+        cursor: encode_cursor(&cursor),
+        has_more,
+    }))
+}
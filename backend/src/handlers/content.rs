@@ -0,0 +1,186 @@
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::{
+    db::{advisory_xact_lock, LockMode},
+    error::{AppError, Result},
+    handlers::{
+        file::{load_file_scope, readable_file, store_upload, upload_permit, NewUpload},
+        render::text_format,
+    },
+    middleware::{ClientIp, OptionalAuthUser},
+    models::File,
+    utils::{can_write, ensure_project_writable, read_file_content, Credentials},
+    AppState,
+};
+
+/// Largest text file that can be read or written through the content API
+pub const MAX_TEXT_CONTENT_SIZE: usize = 1024 * 1024;
+
+#[derive(Debug, Deserialize)]
+pub struct ContentQuery {
+    pub api_key: Option<String>,
+}
+
+/// Advisory lock key serializing content edits of one file
+pub(crate) fn content_lock_key(file_id: Uuid) -> String {
+    format!("file-content:{file_id}")
+}
+
+/// Strong ETag of a file's content
+pub(crate) fn content_etag(content_hash: &str) -> String {
+    format!("\"{content_hash}\"")
+}
+
+/// Whether an `If-Match` header lets a write through against `etag`: `*` or
+/// any of the listed strong tags matches (weak tags never do)
+fn if_match_allows(if_match: &str, etag: &str) -> bool {
+    if_match
+        .split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag == etag)
+}
+
+fn ensure_text_file(file: &File) -> Result<()> {
+    if text_format(file).is_none() {
+        return Err(AppError::BadRequest(
+            "Only Markdown, code and text files can be edited".to_string(),
+        ));
+    }
+    if file.size as usize > MAX_TEXT_CONTENT_SIZE {
+        return Err(AppError::BadRequest(format!(
+            "Files over {} KB can't be edited",
+            MAX_TEXT_CONTENT_SIZE / 1024
+        )));
+    }
+    Ok(())
+}
+
+/// A small text file's content with its `ETag`, to send back in `If-Match`
+/// when saving an edit; the same access as downloading the file
+pub async fn get_file_content(
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    optional_auth: OptionalAuthUser,
+    headers: HeaderMap,
+    Path(file_id): Path<Uuid>,
+    Query(query): Query<ContentQuery>,
+) -> Result<Response> {
+    let (file, _) = readable_file(
+        &state,
+        &optional_auth,
+        &headers,
+        client_ip,
+        file_id,
+        query.api_key.as_deref(),
+    )
+    .await?;
+    ensure_text_file(&file)?;
+
+    let content = read_file_content(&state.pool, state.cold_storage.as_deref(), &file).await?;
+    let etag = content_etag(&hex::encode(Sha256::digest(&content)));
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, &file.mime_type)
+        .header(header::ETAG, etag)
+        .header(header::CACHE_CONTROL, "no-cache")
+        .body(content.into())
+        .map_err(|e| AppError::InternalError(format!("Failed to build response: {e}")))
+}
+
+/// Replace a small text file's content with the request body. With `If-Match`
+/// the write only goes through if the file still has that ETag (412
+/// otherwise); the previous content is kept as a version.
+pub async fn update_file_content(
+    State(state): State<AppState>,
+    optional_auth: OptionalAuthUser,
+    headers: HeaderMap,
+    Path(file_id): Path<Uuid>,
+    body: Bytes,
+) -> Result<Response> {
+    let (_, project, _) = load_file_scope(&state.pool, file_id).await?;
+    let credentials = Credentials::resolve(&state.pool, &optional_auth, &headers, None).await?;
+    if !can_write(&project, &credentials) {
+        return Err(AppError::Unauthorized);
+    }
+    ensure_project_writable(&project)?;
+    let _upload_permit = upload_permit(&state, &project)?;
+
+    if body.is_empty() {
+        return Err(AppError::BadRequest(
+            "Empty files cannot be uploaded".to_string(),
+        ));
+    }
+    if body.len() > MAX_TEXT_CONTENT_SIZE {
+        return Err(AppError::BadRequest(format!(
+            "Content must be at most {} KB",
+            MAX_TEXT_CONTENT_SIZE / 1024
+        )));
+    }
+    if std::str::from_utf8(&body).is_err() {
+        return Err(AppError::BadRequest(
+            "Content is not UTF-8 text".to_string(),
+        ));
+    }
+    let if_match = match headers.get(header::IF_MATCH) {
+        Some(value) => Some(
+            value
+                .to_str()
+                .map_err(|_| AppError::BadRequest("Invalid If-Match header".to_string()))?,
+        ),
+        None => None,
+    };
+
+    // Held until the new content is stored, so concurrent edits of the file
+    // compare against what the one before them wrote
+    let mut lock = state.pool.begin().await?;
+    advisory_xact_lock(&mut lock, &content_lock_key(file_id), LockMode::Exclusive).await?;
+    let (file, _, folder) = load_file_scope(&state.pool, file_id).await?;
+    ensure_text_file(&file)?;
+    let content_hash: String = sqlx::query_scalar("SELECT content_hash FROM files WHERE id = $1")
+        .bind(file.id)
+        .fetch_one(&state.pool)
+        .await?;
+    if let Some(if_match) = if_match {
+        if !if_match_allows(if_match, &content_etag(&content_hash)) {
+            return Err(AppError::PreconditionFailed(
+                "File has changed since it was read".to_string(),
+            ));
+        }
+    }
+
+    let response = store_upload(
+        &state,
+        &project,
+        NewUpload {
+            file_name: file.original_name.clone(),
+            folder_path: folder.map(|f| f.path),
+            on_conflict: None,
+            description: None,
+            data: body.to_vec(),
+            replaces: Some(file),
+            optimize_images: false,
+            keep_replaced: Some("edit"),
+            dedupe_recent: false,
+        },
+        None,
+    )
+    .await?;
+    lock.commit().await?;
+
+    let etag = content_etag(&hex::encode(Sha256::digest(&body)));
+    let mut response = Json(response).into_response();
+    response.headers_mut().insert(
+        header::ETAG,
+        HeaderValue::from_str(&etag).expect("hex ETags are valid header values"),
+    );
+    Ok(response)
+}
@@ -0,0 +1,52 @@
+use axum::{extract::State, Json};
+
+use crate::{
+    error::Result,
+    middleware::RequirePermission,
+    models::{DatabaseStats, IndexStats, TableStats},
+    utils::perm,
+    AppState,
+};
+
+/// Table, partition and index sizes of the application schema, largest first
+pub async fn database_stats(
+    State(state): State<AppState>,
+    _: RequirePermission<perm::AdminDatabaseRead>,
+) -> Result<Json<DatabaseStats>> {
+    let tables = sqlx::query_as::<_, TableStats>(
+        r#"
+        SELECT
+            c.relname::TEXT AS name,
+            parent.relname::TEXT AS parent,
+            c.relkind = 'p' AS partitioned,
+            GREATEST(c.reltuples, 0)::BIGINT AS estimated_rows,
+            pg_table_size(c.oid) AS table_bytes,
+            pg_indexes_size(c.oid) AS index_bytes
+        FROM pg_class c
+        JOIN pg_namespace n ON n.oid = c.relnamespace
+        LEFT JOIN pg_inherits i ON i.inhrelid = c.oid
+        LEFT JOIN pg_class parent ON parent.oid = i.inhparent
+        WHERE n.nspname = current_schema() AND c.relkind IN ('r', 'p')
+        ORDER BY pg_total_relation_size(c.oid) DESC, c.relname
+        "#,
+    )
+    .fetch_all(&state.pool)
+    .await?;
+
+    let indexes = sqlx::query_as::<_, IndexStats>(
+        r#"
+        SELECT
+            s.relname::TEXT AS table_name,
+            s.indexrelname::TEXT AS name,
+            pg_relation_size(s.indexrelid) AS bytes,
+            s.idx_scan AS scans
+        FROM pg_stat_user_indexes s
+        WHERE s.schemaname = current_schema()
+        ORDER BY pg_relation_size(s.indexrelid) DESC, s.indexrelname
+        "#,
+    )
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(Json(DatabaseStats { tables, indexes }))
+}
@@ -0,0 +1,188 @@
+use axum::{
+    extract::{Multipart, Path, Query, State},
+    http::HeaderMap,
+    Json,
+};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::{
+    error::{AppError, Result},
+    handlers::file::{load_file_scope, store_upload, upload_permit, NewUpload},
+    middleware::OptionalAuthUser,
+    models::{DeltaInstructions, FileSignatures, UploadResponse},
+    utils::{
+        apply_delta, block_signatures, can_write, ensure_project_writable, read_file_content,
+        Credentials, DEFAULT_DELTA_BLOCK_SIZE, MAX_DELTA_BLOCKS, MAX_DELTA_BLOCK_SIZE,
+        MIN_DELTA_BLOCK_SIZE,
+    },
+    AppState,
+};
+
+/// Longest accepted `delta` field (the JSON instructions)
+pub const MAX_DELTA_INSTRUCTIONS_SIZE: usize = 4 * 1024 * 1024;
+
+#[derive(Debug, Deserialize)]
+pub struct SignaturesQuery {
+    pub block_size: Option<usize>,
+}
+
+fn check_block_size(block_size: usize) -> Result<()> {
+    if !(MIN_DELTA_BLOCK_SIZE..=MAX_DELTA_BLOCK_SIZE).contains(&block_size) {
+        return Err(AppError::BadRequest(format!(
+            "block_size must be between {MIN_DELTA_BLOCK_SIZE} and {MAX_DELTA_BLOCK_SIZE}"
+        )));
+    }
+    Ok(())
+}
+
+/// Block signatures of a file's current content, so a client can work out which
+/// blocks of its new version are unchanged. Needs write access to the file.
+pub async fn file_signatures(
+    State(state): State<AppState>,
+    optional_auth: OptionalAuthUser,
+    headers: HeaderMap,
+    Path(file_id): Path<Uuid>,
+    Query(query): Query<SignaturesQuery>,
+) -> Result<Json<FileSignatures>> {
+    let (file, project, _) = load_file_scope(&state.pool, file_id).await?;
+    let credentials = Credentials::resolve(&state.pool, &optional_auth, &headers, None).await?;
+    if !can_write(&project, &credentials) {
+        return Err(AppError::Unauthorized);
+    }
+
+    let block_size = query.block_size.unwrap_or(DEFAULT_DELTA_BLOCK_SIZE);
+    check_block_size(block_size)?;
+    if (file.size as usize).div_ceil(block_size) > MAX_DELTA_BLOCKS {
+        return Err(AppError::BadRequest(format!(
+            "block_size is too small for this file (at most {MAX_DELTA_BLOCKS} blocks)"
+        )));
+    }
+
+    let data = read_file_content(&state.pool, state.cold_storage.as_deref(), &file).await?;
+    let (sha256, blocks) = tokio::task::spawn_blocking(move || {
+        (
+            hex::encode(Sha256::digest(&data)),
+            block_signatures(&data, block_size),
+        )
+    })
+    .await
+    .map_err(|e| AppError::InternalError(format!("Signature task failed: {e}")))?;
+
+    Ok(Json(FileSignatures {
+        file_id: file.id,
+        size: file.size,
+        sha256,
+        block_size,
+        blocks,
+    }))
+}
+
+/// Replace a file's content with a new version sent as a delta against its
+/// current content: a `delta` field with the instructions and a `data` field
+/// with the literal bytes they reference. The rebuilt file is stored like an
+/// overwrite, keeping its ID.
+pub async fn upload_delta(
+    State(state): State<AppState>,
+    optional_auth: OptionalAuthUser,
+    headers: HeaderMap,
+    Path(file_id): Path<Uuid>,
+    mut multipart: Multipart,
+) -> Result<Json<UploadResponse>> {
+    let (file, project, folder) = load_file_scope(&state.pool, file_id).await?;
+    let credentials = Credentials::resolve(&state.pool, &optional_auth, &headers, None).await?;
+    if !can_write(&project, &credentials) {
+        return Err(AppError::Unauthorized);
+    }
+    ensure_project_writable(&project)?;
+    let _upload_permit = upload_permit(&state, &project)?;
+
+    let mut instructions: Option<DeltaInstructions> = None;
+    let mut literals: Vec<u8> = Vec::new();
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| AppError::BadRequest(format!("Multipart error: {e}")))?
+    {
+        match field.name().unwrap_or("") {
+            "delta" => {
+                let text = field
+                    .text()
+                    .await
+                    .map_err(|e| AppError::BadRequest(format!("Failed to read delta: {e}")))?;
+                if text.len() > MAX_DELTA_INSTRUCTIONS_SIZE {
+                    return Err(AppError::BadRequest(format!(
+                        "delta must be at most {MAX_DELTA_INSTRUCTIONS_SIZE} bytes"
+                    )));
+                }
+                instructions = Some(
+                    serde_json::from_str(&text)
+                        .map_err(|e| AppError::BadRequest(format!("Invalid delta: {e}")))?,
+                );
+            }
+            "data" => {
+                literals = field
+                    .bytes()
+                    .await
+                    .map_err(|e| AppError::BadRequest(format!("Failed to read data: {e}")))?
+                    .to_vec();
+            }
+            _ => {}
+        }
+    }
+    let instructions = instructions.ok_or(AppError::BadRequest("No delta provided".to_string()))?;
+    check_block_size(instructions.block_size)?;
+
+    // Rebuild the new version from the current content
+    let base = read_file_content(&state.pool, state.cold_storage.as_deref(), &file).await?;
+    let max_size = state.config.max_file_size;
+    let data = tokio::task::spawn_blocking(move || {
+        if !hex::encode(Sha256::digest(&base)).eq_ignore_ascii_case(&instructions.base_sha256) {
+            return Err(AppError::Conflict(
+                "File has changed since its signatures were taken".to_string(),
+            ));
+        }
+        let data = apply_delta(
+            &base,
+            instructions.block_size,
+            &instructions.ops,
+            &literals,
+            max_size,
+        )
+        .map_err(AppError::BadRequest)?;
+        if !hex::encode(Sha256::digest(&data)).eq_ignore_ascii_case(&instructions.sha256) {
+            return Err(AppError::BadRequest(
+                "Rebuilt file does not match sha256".to_string(),
+            ));
+        }
+        Ok(data)
+    })
+    .await
+    .map_err(|e| AppError::InternalError(format!("Delta task failed: {e}")))??;
+    if data.is_empty() {
+        return Err(AppError::BadRequest(
+            "Empty files cannot be uploaded".to_string(),
+        ));
+    }
+
+    let response = store_upload(
+        &state,
+        &project,
+        NewUpload {
+            file_name: file.original_name.clone(),
+            folder_path: folder.map(|f| f.path),
+            on_conflict: None,
+            description: None,
+            data,
+            replaces: Some(file),
+            // The client computed the delta against the stored content
+            optimize_images: false,
+            keep_replaced: None,
+            dedupe_recent: false,
+        },
+        None,
+    )
+    .await?;
+    Ok(Json(response))
+}
@@ -0,0 +1,67 @@
+use axum::{
+    extract::{Path, State},
+    http::HeaderMap,
+    Json,
+};
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::{
+    error::{AppError, Result},
+    middleware::OptionalAuthUser,
+    models::{Project, ReplayEventsRequest, ReplayEventsResponse, PROJECT_COLUMNS},
+    utils::{can_write, replay_events, Credentials, FileEventKind},
+    AppState,
+};
+
+/// Send a project's delivered events from a time range to the broker again,
+/// for consumers that were down. Only events from the last
+/// `EVENT_RETENTION_DAYS` can be replayed. Needs the project API key or write
+/// access.
+pub async fn replay_project_events(
+    State(state): State<AppState>,
+    optional_auth: OptionalAuthUser,
+    headers: HeaderMap,
+    Path(project_id): Path<Uuid>,
+    Json(payload): Json<ReplayEventsRequest>,
+) -> Result<Json<ReplayEventsResponse>> {
+    let project = sqlx::query_as::<_, Project>(&format!(
+        "SELECT {PROJECT_COLUMNS} FROM projects WHERE id = $1"
+    ))
+    .bind(project_id)
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or(AppError::NotFound("Project not found".to_string()))?;
+    let credentials = Credentials::resolve(&state.pool, &optional_auth, &headers, None).await?;
+    if !can_write(&project, &credentials) {
+        return Err(AppError::Unauthorized);
+    }
+    if state.events.is_none() {
+        return Err(AppError::BadRequest(
+            "Event publishing is not configured".to_string(),
+        ));
+    }
+
+    let to = payload.to.unwrap_or_else(Utc::now);
+    if payload.from >= to {
+        return Err(AppError::ValidationError(
+            "from must be before to".to_string(),
+        ));
+    }
+    let kinds = payload
+        .event_types
+        .iter()
+        .map(|event_type| event_type.parse::<FileEventKind>())
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(AppError::ValidationError)?;
+
+    let replayed = replay_events(&state.pool, project_id, payload.from, to, &kinds).await?;
+    tracing::info!(
+        "Replaying {} events of project {} from {} to {}",
+        replayed,
+        project_id,
+        payload.from,
+        to
+    );
+    Ok(Json(ReplayEventsResponse { replayed }))
+}
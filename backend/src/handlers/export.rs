@@ -0,0 +1,207 @@
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::Response,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures::{channel::mpsc, SinkExt, TryStreamExt};
+use serde::Deserialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    error::{AppError, Result},
+    middleware::OptionalAuthUser,
+    models::{ExportFormat, FileExportRow, Project, PROJECT_COLUMNS},
+    utils::{can_list, can_write, Credentials},
+    AppState,
+};
+
+/// Rows are sent to the client in chunks of about this size
+const EXPORT_CHUNK_SIZE: usize = 64 * 1024;
+
+type ExportChunk = std::result::Result<Vec<u8>, std::io::Error>;
+
+const CSV_HEADER: &str = "id,folder_path,original_name,size,mime_type,sha256,download_count,upload_date,last_accessed_at,moderation_status,legal_hold,description\r\n";
+
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+/// A CSV field, quoted when it holds a separator, quote or line break
+fn push_csv_field(line: &mut String, value: &str) {
+    if value.contains([',', '"', '\r', '\n']) {
+        line.push('"');
+        line.push_str(&value.replace('"', "\"\""));
+        line.push('"');
+    } else {
+        line.push_str(value);
+    }
+}
+
+fn csv_line(row: &FileExportRow) -> String {
+    let timestamp = |t: &DateTime<Utc>| t.to_rfc3339_opts(SecondsFormat::Micros, true);
+    let fields = [
+        row.id.to_string(),
+        row.folder_path.clone().unwrap_or_default(),
+        row.original_name.clone(),
+        row.size.to_string(),
+        row.mime_type.clone(),
+        row.sha256.clone().unwrap_or_default(),
+        row.download_count.to_string(),
+        timestamp(&row.upload_date),
+        row.last_accessed_at
+            .as_ref()
+            .map(timestamp)
+            .unwrap_or_default(),
+        row.moderation_status.clone(),
+        row.legal_hold.to_string(),
+        row.description.clone().unwrap_or_default(),
+    ];
+    let mut line = String::new();
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            line.push(',');
+        }
+        push_csv_field(&mut line, field);
+    }
+    line.push_str("\r\n");
+    line
+}
+
+/// Send every file of the project in the format, reading the rows as a
+/// stream so the inventory is never held in memory
+async fn write_export(
+    pool: PgPool,
+    project_id: Uuid,
+    include_unapproved: bool,
+    format: ExportFormat,
+    sender: &mut mpsc::Sender<ExportChunk>,
+) -> std::result::Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut rows = sqlx::query_as::<_, FileExportRow>(
+        r#"
+        SELECT
+            f.id,
+            fol.path AS folder_path,
+            f.original_name,
+            f.size,
+            f.mime_type,
+            f.content_hash AS sha256,
+            f.download_count,
+            f.upload_date,
+            f.last_accessed_at,
+            f.moderation_status::text AS moderation_status,
+            f.legal_hold,
+            f.description
+        FROM files f
+        LEFT JOIN folders fol ON fol.id = f.folder_id
+        WHERE f.project_id = $1 AND ($2 OR f.moderation_status = 'approved')
+        ORDER BY fol.path COLLATE "C" NULLS FIRST, f.original_name COLLATE "C", f.id
+        "#,
+    )
+    .bind(project_id)
+    .bind(include_unapproved)
+    .fetch(&pool);
+
+    let mut chunk = match format {
+        ExportFormat::Csv => CSV_HEADER.to_string(),
+        ExportFormat::Json => "[".to_string(),
+        ExportFormat::Ndjson => String::new(),
+    };
+    let mut first = true;
+    while let Some(row) = rows.try_next().await? {
+        match format {
+            ExportFormat::Csv => chunk.push_str(&csv_line(&row)),
+            ExportFormat::Json => {
+                chunk.push_str(if first { "\n" } else { ",\n" });
+                chunk.push_str(&serde_json::to_string(&row)?);
+            }
+            ExportFormat::Ndjson => {
+                chunk.push_str(&serde_json::to_string(&row)?);
+                chunk.push('\n');
+            }
+        }
+        first = false;
+        if chunk.len() >= EXPORT_CHUNK_SIZE {
+            sender
+                .send(Ok(std::mem::take(&mut chunk).into_bytes()))
+                .await?;
+        }
+    }
+    if let ExportFormat::Json = format {
+        chunk.push_str("\n]\n");
+    }
+    sender.send(Ok(chunk.into_bytes())).await?;
+    Ok(())
+}
+
+/// Every file of a project with its folder, size, hash and download count, as
+/// CSV, a JSON array or NDJSON, streamed as it is read. Needs the project API
+/// key or a collaborator role; files awaiting or failing moderation are only
+/// included for those who can write. An export that fails part way aborts
+/// the response instead of ending it, so clients can't mistake it for a
+/// complete one.
+pub async fn export_project_files(
+    State(state): State<AppState>,
+    optional_auth: OptionalAuthUser,
+    headers: HeaderMap,
+    Path(project_id): Path<Uuid>,
+    Query(query): Query<ExportQuery>,
+) -> Result<Response> {
+    let project = sqlx::query_as::<_, Project>(&format!(
+        "SELECT {PROJECT_COLUMNS} FROM projects WHERE id = $1"
+    ))
+    .bind(project_id)
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or(AppError::NotFound("Project not found".to_string()))?;
+    let credentials = Credentials::resolve(&state.pool, &optional_auth, &headers, None).await?;
+    if !can_list(&project, &credentials) {
+        return Err(AppError::Unauthorized);
+    }
+
+    let (content_type, extension) = match query.format {
+        ExportFormat::Csv => ("text/csv; charset=utf-8", "csv"),
+        ExportFormat::Json => ("application/json", "json"),
+        ExportFormat::Ndjson => ("application/x-ndjson", "ndjson"),
+    };
+    let pool = state.read_pool.get().clone();
+    let include_unapproved = can_write(&project, &credentials);
+    let (mut sender, receiver) = mpsc::channel::<ExportChunk>(4);
+    tokio::spawn(async move {
+        let written = write_export(
+            pool,
+            project_id,
+            include_unapproved,
+            query.format,
+            &mut sender,
+        )
+        .await;
+        // A closed channel means the client went away
+        if let Err(e) = written {
+            if sender.is_closed() {
+                return;
+            }
+            tracing::warn!(
+                "Failed to stream file export of project {}: {}",
+                project_id,
+                e
+            );
+            let _ = sender.send(Err(std::io::Error::other(e.to_string()))).await;
+        }
+    });
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, content_type)
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"files-{project_id}.{extension}\""),
+        )
+        .header(header::CACHE_CONTROL, "no-store")
+        .body(Body::from_stream(receiver))
+        .map_err(|e| AppError::InternalError(format!("Failed to build response: {e}")))
+}
@@ -1,111 +1,587 @@
 use axum::{
     body::Body,
     extract::{Multipart, Path, State},
-    http::{header, HeaderMap, StatusCode},
-    response::Response,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
     Json,
 };
-use std::path::PathBuf;
+use sha2::{Digest, Sha256};
+use sqlx::{PgConnection, PgPool};
+use std::{collections::HashMap, net::IpAddr, path::PathBuf, sync::Arc};
 use tokio::fs;
 use tokio::io::AsyncWriteExt;
+use tokio::sync::OwnedSemaphorePermit;
 use uuid::Uuid;
+use validator::Validate;
 
 use crate::{
+    config::Config,
+    db::{advisory_xact_lock, LockMode},
     error::{AppError, Result},
-    middleware::{AuthUser, OptionalAuthUser},
-    models::{File, FileMetadata, Folder, Project, UploadResponse},
+    handlers::share::share_grants_read,
+    middleware::{AuthUser, ClientIp, OptionalAuthUser},
+    models::{
+        is_allowed_response_header, CompressionStats, ConflictStrategy, CopyFileRequest,
+        DeduplicateRequest, DuplicateGroup, DuplicatesReport, ExtractResponse, File, FileMetadata,
+        FileSort, Folder, FolderAccessRequest, FolderAccessResponse, LegalHoldRequest,
+        ModerationStatus, Project, ReviewFileRequest, UpdateFileRequest, UploadPolicyRequest,
+        UploadPolicyResponse, UploadResponse, PROJECT_COLUMNS,
+    },
+    utils::{
+        admin_override, archive_entries, can_list, can_read, can_upload, can_write,
+        check_geo_access, check_quota, clear_moderation, content_disposition,
+        create_folder_access_token, create_upload_policy_token, delete_cold_blob, ensure_hot,
+        ensure_project_writable, extract_archive, gzip_compress, is_allowed, is_compressible,
+        lookup_country, negotiate_encoding, notify_large_upload, offload_headers, optimize_image,
+        queue_cdn_purge, queue_media_preview, queue_moderation, queue_replication,
+        read_file_content, record_file_events, remove_variants, sanitize_file_name,
+        spawn_quota_warnings, throttled_chunk_size, throttled_stream, validate_folder_path,
+        variant_path, verify_folder_access_token, verify_upload_policy_token, version_blob_path,
+        write_zip_stream, zstd_compress, zstd_decompress, AdminQuery, ArchiveKind, Credentials,
+        ExtractLimits, FileEventKind, MemoryBudget, MemoryReservation, PageQuery, Paginated,
+        Permission, ResponseEncoding, RolePermission, COLD_TIER, MIN_COMPRESSIBLE_SIZE,
+        PRECOMPRESSED_ENCODINGS, STREAM_BUFFER_SIZE, ZSTD_ENCODING,
+    },
     AppState,
 };
 
-pub async fn upload_file(
-    State(state): State<AppState>,
-    headers: HeaderMap,
-    mut multipart: Multipart,
-) -> Result<Json<UploadResponse>> {
-    // Get API key from header
+/// Multipart fields accepted per upload request (file plus form options)
+const MAX_UPLOAD_FIELDS: usize = 16;
+
+/// Longest accepted value of a text form field such as `folder_path`
+const MAX_TEXT_FIELD_LENGTH: usize = 1024;
+
+fn invalid_upload_field(field: &str, reason: &'static str, message: impl Into<String>) -> AppError {
+    AppError::InvalidUploadField {
+        field: field.to_string(),
+        reason,
+        message: message.into(),
+    }
+}
+
+/// Read a text form field, rejecting oversized values
+async fn read_text_field(field: axum::extract::multipart::Field<'_>, name: &str) -> Result<String> {
+    let text = field
+        .text()
+        .await
+        .map_err(|e| AppError::BadRequest(format!("Failed to read {name}: {e}")))?;
+    if text.len() > MAX_TEXT_FIELD_LENGTH {
+        return Err(invalid_upload_field(
+            name,
+            "field_too_long",
+            format!("{name} must be at most {MAX_TEXT_FIELD_LENGTH} bytes"),
+        ));
+    }
+    Ok(text)
+}
+
+/// Trim a file description; a blank one means none
+pub(crate) fn normalize_description(text: &str) -> Result<Option<String>> {
+    let text = text.trim();
+    if text.len() > MAX_TEXT_FIELD_LENGTH {
+        return Err(invalid_upload_field(
+            "description",
+            "field_too_long",
+            format!("description must be at most {MAX_TEXT_FIELD_LENGTH} bytes"),
+        ));
+    }
+    Ok((!text.is_empty()).then(|| text.to_string()))
+}
+
+/// Get the folder ID for a path, creating it and any missing ancestors if needed.
+/// New folders get the visibility the project's `default_folder_visibility` picks.
+pub(crate) async fn get_or_create_folder(
+    conn: &mut PgConnection,
+    project_id: Uuid,
+    path: &str,
+) -> Result<Uuid> {
+    create_ancestor_folders(&mut *conn, project_id, path).await?;
+
+    let folder_id = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO folders (project_id, path, is_public)
+        SELECT p.id, $2, new_folder_is_public(p.default_folder_visibility, p.is_public, parent.is_public)
+        FROM projects p
+        LEFT JOIN folders parent ON parent.project_id = p.id AND parent.path = $3
+        WHERE p.id = $1
+        ON CONFLICT (project_id, path) DO UPDATE SET path = EXCLUDED.path
+        RETURNING id
+        "#,
+    )
+    .bind(project_id)
+    .bind(path)
+    .bind(parent_folder_path(path))
+    .fetch_one(conn)
+    .await?;
+
+    Ok(folder_id)
+}
+
+/// `a/b/c` → `a/b`; None for top-level folders
+pub(crate) fn parent_folder_path(path: &str) -> Option<&str> {
+    path.rsplit_once('/').map(|(parent, _)| parent)
+}
+
+/// Create the records of any missing ancestors of `path` (`a` and `a/b` for
+/// `a/b/c`). A new folder's `is_public` follows the project's
+/// `default_folder_visibility`, where `inherit` takes its nearest existing
+/// ancestor's, or the project's when it has none.
+pub(crate) async fn create_ancestor_folders(
+    conn: &mut PgConnection,
+    project_id: Uuid,
+    path: &str,
+) -> Result<()> {
+    if parent_folder_path(path).is_none() {
+        return Ok(());
+    }
+
+    sqlx::query(
+        r#"
+        WITH ancestors AS (
+            SELECT n, array_to_string(parts[1:n], '/') AS path
+            FROM string_to_array($2, '/') AS parts,
+                 generate_series(1, cardinality(parts) - 1) AS n
+        )
+        INSERT INTO folders (project_id, path, is_public)
+        SELECT p.id, a.path, new_folder_is_public(
+            p.default_folder_visibility,
+            p.is_public,
+            (SELECT f.is_public
+             FROM folders f
+             JOIN ancestors b ON b.path = f.path
+             WHERE f.project_id = p.id AND b.n < a.n
+             ORDER BY b.n DESC
+             LIMIT 1)
+        )
+        FROM projects p
+        CROSS JOIN ancestors a
+        WHERE p.id = $1
+        ORDER BY a.n
+        ON CONFLICT (project_id, path) DO NOTHING
+        "#,
+    )
+    .bind(project_id)
+    .bind(path)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Advisory lock key guarding the file set of one folder
+pub(crate) fn folder_lock_key(project_id: Uuid, path: &str) -> String {
+    format!("folder:{project_id}:{path}")
+}
+
+/// Hold the folder's shared lock until `conn`'s transaction ends and return its ID.
+/// `delete_folder_files` takes the lock exclusively, so the folder can't be
+/// deleted between this lookup and the caller's insert or move; a folder deleted
+/// before the lock was taken is recreated.
+async fn lock_folder(conn: &mut PgConnection, project_id: Uuid, path: &str) -> Result<Uuid> {
+    advisory_xact_lock(
+        &mut *conn,
+        &folder_lock_key(project_id, path),
+        LockMode::Shared,
+    )
+    .await?;
+    get_or_create_folder(&mut *conn, project_id, path).await
+}
+
+/// Unpack an uploaded ZIP/TAR archive into the target folder, creating a record per entry
+/// (each gets the upload's description, if any)
+async fn extract_upload(
+    state: &AppState,
+    project: &Project,
+    folder_path: Option<String>,
+    file_name: &str,
+    file_data: Vec<u8>,
+    description: Option<&str>,
+) -> Result<Json<ExtractResponse>> {
+    let kind = ArchiveKind::from_file_name(file_name).ok_or(AppError::BadRequest(
+        "extract=true requires a .zip, .tar, .tar.gz or .tgz file".to_string(),
+    ))?;
+
+    let limits = ExtractLimits {
+        max_entries: state.config.extract_max_entries,
+        max_total_size: state.config.extract_max_total_size,
+        max_entry_size: state.config.max_file_size as u64,
+    };
+    let mut project_root = PathBuf::from(&state.config.storage_path);
+    project_root.push(project.id.to_string());
+
+    let entries = tokio::task::spawn_blocking(move || {
+        extract_archive(
+            &file_data,
+            kind,
+            &project_root,
+            folder_path.as_deref(),
+            limits,
+        )
+    })
+    .await
+    .map_err(|e| AppError::InternalError(format!("Extraction task failed: {e}")))?
+    .map_err(|e| AppError::BadRequest(format!("Failed to extract archive: {e}")))?;
+
+    // Insert all records atomically; on failure remove the extracted blobs
+    let result: Result<Vec<UploadResponse>> = async {
+        check_quota(
+            &state.pool,
+            project.id,
+            entries.iter().map(|entry| entry.size).sum(),
+        )
+        .await?;
+
+        let mut folder_ids = HashMap::new();
+        let mut tx = state.pool.begin().await?;
+        let mut files = Vec::with_capacity(entries.len());
+        let mut records = Vec::with_capacity(entries.len());
+
+        for entry in &entries {
+            let folder_id = match entry.folder_path {
+                Some(ref path) => {
+                    if !folder_ids.contains_key(path) {
+                        let id = lock_folder(&mut tx, project.id, path).await?;
+                        folder_ids.insert(path.clone(), id);
+                    }
+                    folder_ids.get(path).copied()
+                }
+                None => None,
+            };
+
+            let mime_type = mime_guess::from_path(&entry.original_name)
+                .first_or_octet_stream()
+                .to_string();
+
+            let mut file = sqlx::query_as::<_, File>(
+                r#"
+                INSERT INTO files (id, project_id, folder_id, original_name, stored_name, file_path, size, mime_type, content_hash, description)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                RETURNING id, project_id, folder_id, original_name, stored_name, file_path, size, mime_type, upload_date, storage_encoding, precompressed_encodings, storage_tier, moderation_status
+                "#,
+            )
+            .bind(entry.file_id)
+            .bind(project.id)
+            .bind(folder_id)
+            .bind(&entry.original_name)
+            .bind(&entry.stored_name)
+            .bind(entry.file_path.to_str().unwrap())
+            .bind(entry.size)
+            .bind(&mime_type)
+            .bind(&entry.content_hash)
+            .bind(description)
+            .fetch_one(&mut *tx)
+            .await?;
+            file.moderation_status = moderate_upload(&mut tx, state, &file, false).await?;
+            queue_media_preview(&mut tx, &state.config, &file).await?;
+            notify_large_upload(&mut tx, project, &file, entry.folder_path.as_deref()).await?;
+
+            files.push(UploadResponse {
+                file_id: entry.file_id,
+                original_name: entry.original_name.clone(),
+                size: entry.size,
+                mime_type,
+                download_url: format!("/api/v1/files/{}", entry.file_id),
+                folder_path: entry.folder_path.clone(),
+                moderation_status: file.moderation_status,
+            });
+            records.push(file);
+        }
+
+        record_file_events(&mut tx, state.events.as_deref(), FileEventKind::Uploaded, &records)
+            .await?;
+        queue_replication(&mut tx, state.replication.as_deref(), &records).await?;
+        tx.commit().await?;
+        Ok(files)
+    }
+    .await;
+
+    let files = match result {
+        Ok(files) => files,
+        Err(e) => {
+            for entry in &entries {
+                let _ = fs::remove_file(&entry.file_path).await;
+            }
+            return Err(e);
+        }
+    };
+    spawn_quota_warnings(state.pool.clone(), state.mailer.clone(), project.id);
+
+    Ok(Json(ExtractResponse {
+        extracted_count: files.len(),
+        total_size: files.iter().map(|f| f.size).sum(),
+        files,
+    }))
+}
+
+/// Find a free name in the folder by appending ` (n)` before the extension
+async fn unique_file_name(
+    pool: &PgPool,
+    project_id: Uuid,
+    folder_id: Option<Uuid>,
+    file_name: &str,
+) -> Result<String> {
+    let (stem, extension) = match file_name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => (stem, format!(".{ext}")),
+        _ => (file_name, String::new()),
+    };
+
+    for n in 1..=1000 {
+        let candidate = format!("{stem} ({n}){extension}");
+        let taken = sqlx::query_scalar::<_, bool>(
+            "SELECT EXISTS(SELECT 1 FROM files WHERE project_id = $1 AND folder_id IS NOT DISTINCT FROM $2 AND original_name = $3)",
+        )
+        .bind(project_id)
+        .bind(folder_id)
+        .bind(&candidate)
+        .fetch_one(pool)
+        .await?;
+
+        if !taken {
+            return Ok(candidate);
+        }
+    }
+
+    Err(AppError::Conflict(format!(
+        "Could not find a free name for '{file_name}'"
+    )))
+}
+
+/// Reserve one of the project's concurrent upload slots until the permit is dropped
+pub(crate) fn upload_permit(state: &AppState, project: &Project) -> Result<OwnedSemaphorePermit> {
+    let upload_limit = project
+        .max_concurrent_uploads
+        .map(|limit| limit as usize)
+        .unwrap_or(state.config.max_concurrent_uploads);
+    state
+        .upload_limiter
+        .try_acquire(project.id, upload_limit)
+        .ok_or_else(|| {
+            AppError::TooManyRequests(
+                format!("Too many concurrent uploads (limit: {upload_limit})"),
+                1,
+            )
+        })
+}
+
+/// The project whose API key (or previous key, within its grace period) is in `X-API-Key`
+pub(crate) async fn project_for_api_key(pool: &PgPool, headers: &HeaderMap) -> Result<Project> {
     let api_key = headers
         .get("X-API-Key")
         .and_then(|h| h.to_str().ok())
         .ok_or(AppError::Unauthorized)?;
+    project_for_key(pool, api_key).await
+}
 
+/// The project whose API key (or previous key, within its grace period) is `api_key`
+pub(crate) async fn project_for_key(pool: &PgPool, api_key: &str) -> Result<Project> {
     let api_key_uuid = Uuid::parse_str(api_key).map_err(|_| AppError::Unauthorized)?;
 
-    // Get project by API key
-    let project = sqlx::query_as::<_, Project>(
-        "SELECT id, user_id, name, api_key, is_public, created_at FROM projects WHERE api_key = $1",
-    )
+    sqlx::query_as::<_, Project>(&format!(
+        "SELECT {PROJECT_COLUMNS} FROM projects WHERE api_key = $1 OR (previous_api_key = $1 AND previous_api_key_expires_at > NOW())"
+    ))
     .bind(api_key_uuid)
-    .fetch_optional(&state.pool)
+    .fetch_optional(pool)
     .await?
-    .ok_or(AppError::Unauthorized)?;
+    .ok_or(AppError::Unauthorized)
+}
+
+pub async fn upload_file(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    mut multipart: Multipart,
+) -> Result<Response> {
+    // Browsers upload with a signed policy instead of the API key
+    let policy = match headers.get("X-Upload-Policy").and_then(|h| h.to_str().ok()) {
+        Some(token) => Some(verify_upload_policy_token(token, &state.config.jwt_secret)?),
+        None => None,
+    };
+
+    let project = if let Some(ref policy) = policy {
+        let project_id = Uuid::parse_str(&policy.sub).map_err(|_| AppError::Unauthorized)?;
+        sqlx::query_as::<_, Project>(&format!(
+            "SELECT {PROJECT_COLUMNS} FROM projects WHERE id = $1"
+        ))
+        .bind(project_id)
+        .fetch_optional(&state.pool)
+        .await?
+        .ok_or(AppError::Unauthorized)?
+    } else {
+        project_for_api_key(&state.pool, &headers).await?
+    };
+    ensure_project_writable(&project)?;
+
+    // Replay the original response if this Idempotency-Key was already used
+    let idempotency_key = headers
+        .get("Idempotency-Key")
+        .and_then(|h| h.to_str().ok())
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty());
+
+    if let Some(ref key) = idempotency_key {
+        if key.len() > 255 {
+            return Err(AppError::BadRequest(
+                "Idempotency-Key must be at most 255 characters".to_string(),
+            ));
+        }
+
+        let previous =
+            sqlx::query_as::<_, (Uuid, String, i64, String, Option<String>, ModerationStatus)>(
+                r#"
+            SELECT f.id, f.original_name, f.size, f.mime_type, fol.path, f.moderation_status
+            FROM upload_idempotency_keys k
+            JOIN files f ON f.id = k.file_id
+            LEFT JOIN folders fol ON fol.id = f.folder_id
+            WHERE k.project_id = $1 AND k.idempotency_key = $2
+              AND k.created_at > NOW() - INTERVAL '24 hours'
+            "#,
+            )
+            .bind(project.id)
+            .bind(key)
+            .fetch_optional(&state.pool)
+            .await?;
+
+        if let Some((file_id, original_name, size, mime_type, folder_path, moderation_status)) =
+            previous
+        {
+            return Ok(Json(UploadResponse {
+                file_id,
+                original_name,
+                size,
+                mime_type,
+                download_url: format!("/api/v1/files/{file_id}"),
+                folder_path,
+                moderation_status,
+            })
+            .into_response());
+        }
+    }
+
+    // Limit simultaneous uploads per project; the permit is held until this handler returns
+    let _upload_permit = upload_permit(&state, &project)?;
+
+    // Hold room in the memory budget for the file before buffering it; the
+    // reservation grows if the body turns out larger than announced
+    let expected_size = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|s| s.parse::<usize>().ok())
+        .unwrap_or(0)
+        .min(state.config.max_file_size);
+    let mut reservation = reserve_memory(&state, expected_size).await?;
 
     let mut file_data: Option<Vec<u8>> = None;
     let mut file_name: Option<String> = None;
     let mut folder_path: Option<String> = None;
+    let mut on_conflict: Option<ConflictStrategy> = None;
+    let mut description: Option<String> = None;
+    let mut extract = false;
 
     // Parse multipart form
-    while let Some(field) = multipart
+    let mut field_count = 0;
+    while let Some(mut field) = multipart
         .next_field()
         .await
         .map_err(|e| AppError::BadRequest(format!("Multipart error: {e}")))?
     {
+        field_count += 1;
+        if field_count > MAX_UPLOAD_FIELDS {
+            return Err(invalid_upload_field(
+                field.name().unwrap_or(""),
+                "too_many_fields",
+                format!("At most {MAX_UPLOAD_FIELDS} form fields are allowed per upload"),
+            ));
+        }
         let name = field.name().unwrap_or("").to_string();
 
         match name.as_str() {
             "file" => {
-                file_name = field.file_name().map(|s| s.to_string());
-                file_data = Some(
-                    field
-                        .bytes()
-                        .await
-                        .map_err(|e| AppError::BadRequest(format!("Failed to read file: {e}")))?
-                        .to_vec(),
-                );
+                if file_data.is_some() {
+                    return Err(invalid_upload_field(
+                        "file",
+                        "duplicate_field",
+                        "Only one file can be uploaded per request",
+                    ));
+                }
+                file_name = field.file_name().map(sanitize_file_name).transpose()?;
+                let mut data = Vec::with_capacity(expected_size);
+                while let Some(chunk) = field
+                    .chunk()
+                    .await
+                    .map_err(|e| AppError::BadRequest(format!("Failed to read file: {e}")))?
+                {
+                    let size = data.len() + chunk.len();
+                    if size > state.config.max_file_size {
+                        return Err(AppError::BadRequest(format!(
+                            "File size exceeds maximum of {} bytes",
+                            state.config.max_file_size
+                        )));
+                    }
+                    grow_reservation(&state, &mut reservation, size).await?;
+                    data.extend_from_slice(&chunk);
+                }
+                file_data = Some(data);
             }
             "folder_path" => {
-                let text = field.text().await.map_err(|e| {
-                    AppError::BadRequest(format!("Failed to read folder_path: {e}"))
-                })?;
+                let text = read_text_field(field, "folder_path").await?;
+                if !text.is_empty() {
+                    folder_path = Some(validate_folder_path(&text)?);
+                }
+            }
+            "on_conflict" => {
+                let text = read_text_field(field, "on_conflict").await?;
                 if !text.is_empty() {
-                    folder_path = Some(text);
+                    on_conflict = Some(text.parse().map_err(AppError::BadRequest)?);
                 }
             }
+            "description" => {
+                let text = read_text_field(field, "description").await?;
+                description = normalize_description(&text)?;
+            }
+            "extract" => {
+                let text = read_text_field(field, "extract").await?;
+                extract = matches!(text.trim(), "true" | "1");
+            }
             _ => {}
         }
     }
 
     let file_data = file_data.ok_or(AppError::BadRequest("No file provided".to_string()))?;
     let file_name = file_name.ok_or(AppError::BadRequest("No filename provided".to_string()))?;
+    if file_data.is_empty() {
+        return Err(invalid_upload_field(
+            "file",
+            "empty_file",
+            "Empty files cannot be uploaded",
+        ));
+    }
 
-    // Validate folder_path to prevent path traversal attacks
-    if let Some(ref path) = folder_path {
-        // Check for path traversal attempts
-        if path.contains("..")
-            || path.starts_with('/')
-            || path.starts_with('\\')
-            || path.contains("//")
-            || path.contains("\\\\")
-            || path.contains('\0')
-        {
-            return Err(AppError::BadRequest(
-                "Invalid folder path: path traversal not allowed".to_string(),
-            ));
+    // Enforce upload policy constraints
+    if let Some(ref policy) = policy {
+        if let Some(ref policy_folder) = policy.folder_path {
+            if folder_path.as_ref().is_some_and(|p| p != policy_folder) {
+                return Err(AppError::Forbidden(
+                    "folder_path is not allowed by the upload policy".to_string(),
+                ));
+            }
+            folder_path = Some(policy_folder.clone());
         }
-        // Validate characters (alphanumeric, underscore, hyphen, forward slash, dot)
-        if !path
-            .chars()
-            .all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '/' || c == '.')
-        {
-            return Err(AppError::BadRequest(
-                "Invalid folder path: contains invalid characters".to_string(),
+        if extract || on_conflict == Some(ConflictStrategy::Overwrite) {
+            return Err(AppError::Forbidden(
+                "extract and on_conflict=overwrite are not allowed with an upload policy"
+                    .to_string(),
             ));
         }
-        // Prevent hidden folders (starting with dot)
-        if path.starts_with('.') || path.contains("/.") {
+        if policy.max_size.is_some_and(|max| file_data.len() > max) {
             return Err(AppError::BadRequest(
-                "Invalid folder path: hidden folders not allowed".to_string(),
+                "File size exceeds the upload policy limit".to_string(),
             ));
         }
+        let mime_type = mime_guess::from_path(&file_name).first_or_octet_stream();
+        if !content_type_allowed(mime_type.essence_str(), &policy.content_types) {
+            return Err(AppError::Forbidden(format!(
+                "Content type {mime_type} is not allowed by the upload policy"
+            )));
+        }
     }
 
     // Check file size
@@ -116,29 +592,254 @@ pub async fn upload_file(
         )));
     }
 
+    // Unpack archives server-side when requested
+    if extract {
+        return extract_upload(
+            &state,
+            &project,
+            folder_path,
+            &file_name,
+            file_data,
+            description.as_deref(),
+        )
+        .await
+        .map(IntoResponse::into_response);
+    }
+
+    store_upload(
+        &state,
+        &project,
+        NewUpload {
+            file_name,
+            folder_path,
+            on_conflict,
+            description,
+            data: file_data,
+            replaces: None,
+            optimize_images: true,
+            keep_replaced: None,
+            dedupe_recent: true,
+        },
+        idempotency_key.as_deref(),
+    )
+    .await
+    .map(|response| Json(response).into_response())
+}
+
+/// A validated upload, ready to be stored
+pub(crate) struct NewUpload {
+    pub file_name: String,
+    pub folder_path: Option<String>,
+    pub on_conflict: Option<ConflictStrategy>,
+    pub description: Option<String>,
+    pub data: Vec<u8>,
+    /// Replace this file's content instead of resolving conflicts by name
+    pub replaces: Option<File>,
+    /// Apply the project's image optimization rules
+    pub optimize_images: bool,
+    /// Keep the replaced content as a version, recorded with this reason
+    pub keep_replaced: Option<&'static str>,
+    /// Return a file with the same content uploaded to the same folder within
+    /// `UPLOAD_DEDUP_WINDOW_SECS` instead of storing it again
+    pub dedupe_recent: bool,
+}
+
+/// Content kept as a version next to the stored upload
+struct KeptVersion {
+    id: Uuid,
+    original_name: String,
+    path: PathBuf,
+    size: i64,
+    mime_type: String,
+    content_hash: String,
+    reason: &'static str,
+}
+
+/// Store an upload's content and record it: optimizes images, resolves
+/// same-name conflicts, enforces the quota, compresses at rest when enabled,
+/// and queues moderation and events. Shared by single-request, multipart and
+/// delta uploads.
+pub(crate) async fn store_upload(
+    state: &AppState,
+    project: &Project,
+    upload: NewUpload,
+    idempotency_key: Option<&str>,
+) -> Result<UploadResponse> {
+    let NewUpload {
+        mut file_name,
+        folder_path,
+        on_conflict,
+        description,
+        data: mut file_data,
+        replaces,
+        optimize_images,
+        keep_replaced,
+        dedupe_recent,
+    } = upload;
+
+    // Scale down or convert images per the project's rules; if ffmpeg fails
+    // the upload is stored as it came
+    let mut kept_contents = Vec::new();
+    if optimize_images {
+        let mime_type = mime_guess::from_path(&file_name).first_or_octet_stream();
+        match optimize_image(
+            &state.config.ffmpeg_path,
+            project,
+            &file_data,
+            mime_type.essence_str(),
+        )
+        .await
+        {
+            Ok(Some(optimized)) => {
+                let original_name = file_name.clone();
+                if let Some(extension) = optimized.extension {
+                    file_name = PathBuf::from(&file_name)
+                        .with_extension(extension)
+                        .to_string_lossy()
+                        .into_owned();
+                }
+                let original = std::mem::replace(&mut file_data, optimized.data);
+                if project.image_keep_original {
+                    kept_contents.push((
+                        original_name,
+                        mime_type.to_string(),
+                        original,
+                        "original",
+                    ));
+                }
+            }
+            Ok(None) => {}
+            Err(e) => tracing::warn!("Failed to optimize image {}: {}", file_name, e),
+        }
+    }
+
     // Get or create folder
     let folder_id = if let Some(ref path) = folder_path {
-        let folder = sqlx::query_as::<_, Folder>(
+        Some(get_or_create_folder(&mut *state.pool.acquire().await?, project.id, path).await?)
+    } else {
+        None
+    };
+
+    // Content hash for duplicate detection (always of the original bytes)
+    let content_hash = hex::encode(Sha256::digest(&file_data));
+
+    // A client retrying an upload it thinks failed gets the file it already stored
+    let dedup_window = state.config.upload_dedup_window_secs;
+    if dedupe_recent && replaces.is_none() && dedup_window > 0 {
+        let recent = sqlx::query_as::<_, File>(
             r#"
-            INSERT INTO folders (project_id, path, is_public)
-            VALUES ($1, $2, $3)
-            ON CONFLICT (project_id, path) DO UPDATE SET path = EXCLUDED.path
-            RETURNING id, project_id, path, is_public, created_at
+            SELECT id, project_id, folder_id, original_name, stored_name, file_path, size, mime_type, upload_date, storage_encoding, precompressed_encodings, storage_tier, moderation_status
+            FROM files
+            WHERE project_id = $1 AND folder_id IS NOT DISTINCT FROM $2 AND content_hash = $3
+                AND upload_date > NOW() - make_interval(secs => $4)
+            ORDER BY upload_date DESC
+            LIMIT 1
             "#,
         )
         .bind(project.id)
-        .bind(path)
-        .bind(project.is_public)
-        .fetch_one(&state.pool)
+        .bind(folder_id)
+        .bind(&content_hash)
+        .bind(dedup_window as f64)
+        .fetch_optional(&state.pool)
         .await?;
 
-        Some(folder.id)
-    } else {
-        None
-    };
+        if let Some(recent) = recent {
+            return Ok(UploadResponse {
+                file_id: recent.id,
+                original_name: recent.original_name,
+                size: recent.size,
+                mime_type: recent.mime_type,
+                download_url: format!("/api/v1/files/{}", recent.id),
+                folder_path,
+                moderation_status: recent.moderation_status,
+            });
+        }
+    }
+
+    // Resolve same-name conflicts in the target folder (only when on_conflict is given)
+    let mut overwrite_target = replaces;
+    if let Some(strategy) = on_conflict {
+        let existing = sqlx::query_as::<_, File>(
+            r#"
+            SELECT id, project_id, folder_id, original_name, stored_name, file_path, size, mime_type, upload_date, storage_encoding, precompressed_encodings, storage_tier, moderation_status
+            FROM files
+            WHERE project_id = $1 AND folder_id IS NOT DISTINCT FROM $2 AND original_name = $3
+            ORDER BY upload_date DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(project.id)
+        .bind(folder_id)
+        .bind(&file_name)
+        .fetch_optional(&state.pool)
+        .await?;
+
+        if let Some(existing) = existing {
+            match strategy {
+                ConflictStrategy::Error => {
+                    return Err(AppError::Conflict(format!(
+                        "A file named '{file_name}' already exists in this folder"
+                    )));
+                }
+                ConflictStrategy::Skip => {
+                    return Ok(UploadResponse {
+                        file_id: existing.id,
+                        original_name: existing.original_name,
+                        size: existing.size,
+                        mime_type: existing.mime_type,
+                        download_url: format!("/api/v1/files/{}", existing.id),
+                        folder_path,
+                        moderation_status: existing.moderation_status,
+                    });
+                }
+                ConflictStrategy::Overwrite => overwrite_target = Some(existing),
+                ConflictStrategy::Rename => {
+                    file_name =
+                        unique_file_name(&state.pool, project.id, folder_id, &file_name).await?;
+                }
+            }
+        }
+    }
+
+    // Checked before the new content is written over the held blob
+    if let Some(ref previous) = overwrite_target {
+        if under_legal_hold(&state.pool, previous.id).await? {
+            return Err(AppError::LegalHold(format!(
+                "File \"{file_name}\" is under legal hold"
+            )));
+        }
+        if is_append_only(&state.pool, previous.id).await? {
+            return Err(AppError::Conflict(format!(
+                "File \"{file_name}\" is append-only; append to it or clear the flag first"
+            )));
+        }
+    }
+
+    // Read the replaced content before an overwrite at the same path loses it
+    if let (Some(reason), Some(ref previous)) = (keep_replaced, &overwrite_target) {
+        let data = read_file_content(&state.pool, state.cold_storage.as_deref(), previous).await?;
+        kept_contents.push((
+            previous.original_name.clone(),
+            previous.mime_type.clone(),
+            data,
+            reason,
+        ));
+    }
+
+    // Enforce the storage quota; an overwrite only adds the size difference
+    let replaced_size = overwrite_target.as_ref().map_or(0, |f| f.size);
+    check_quota(
+        &state.pool,
+        project.id,
+        file_data.len() as i64 - replaced_size,
+    )
+    .await?;
 
-    // Generate unique stored name
-    let file_id = Uuid::new_v4();
+    // Generate unique stored name (overwrites keep the existing file ID)
+    let file_id = overwrite_target
+        .as_ref()
+        .map(|f| f.id)
+        .unwrap_or_else(Uuid::new_v4);
     let extension = PathBuf::from(&file_name)
         .extension()
         .and_then(|e| e.to_str())
@@ -167,132 +868,619 @@ pub async fn upload_file(
 
     storage_path.push(&stored_name);
 
-    // Write file to disk
-    let mut file = fs::File::create(&storage_path)
-        .await
-        .map_err(|e| AppError::FileError(format!("Failed to create file: {e}")))?;
-
-    file.write_all(&file_data)
-        .await
-        .map_err(|e| AppError::FileError(format!("Failed to write file: {e}")))?;
-
     // Detect MIME type
     let mime_type = mime_guess::from_path(&file_name)
         .first_or_octet_stream()
         .to_string();
 
-    // Save to database
-    let file_record = sqlx::query_as::<_, File>(
-        r#"
-        INSERT INTO files (id, project_id, folder_id, original_name, stored_name, file_path, size, mime_type)
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
-        RETURNING id, project_id, folder_id, original_name, stored_name, file_path, size, mime_type, upload_date
-        "#,
-    )
-    .bind(file_id)
-    .bind(project.id)
-    .bind(folder_id)
-    .bind(&file_name)
-    .bind(&stored_name)
-    .bind(storage_path.to_str().unwrap())
-    .bind(file_data.len() as i64)
-    .bind(&mime_type)
-    .fetch_one(&state.pool)
-    .await?;
+    // Compress text-like content at rest when enabled and it actually saves space
+    let original_size = file_data.len() as i64;
+    let (stored_data, storage_encoding) = if state.config.storage_compression
+        && file_data.len() >= MIN_COMPRESSIBLE_SIZE
+        && is_compressible(&mime_type)
+    {
+        let level = state.config.storage_compression_level;
+        let (original, compressed) = tokio::task::spawn_blocking(move || {
+            let compressed = zstd_compress(&file_data, level);
+            (file_data, compressed)
+        })
+        .await
+        .map_err(|e| AppError::InternalError(format!("Compression task failed: {e}")))?;
+        match compressed {
+            Ok(compressed) if compressed.len() < original.len() => {
+                (compressed, Some(ZSTD_ENCODING.to_string()))
+            }
+            Ok(_) => (original, None),
+            Err(e) => {
+                tracing::warn!("Failed to compress upload, storing as-is: {}", e);
+                (original, None)
+            }
+        }
+    } else {
+        (file_data, None)
+    };
+
+    // Write to a temp file next to the target; it's renamed into place only
+    // once the database commit succeeds, so an overwrite that reuses the old
+    // path never truncates the live blob or exposes a half-written one
+    let temp_path = storage_path.with_file_name(format!(
+        ".{}.{}.tmp",
+        storage_path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default(),
+        Uuid::new_v4()
+    ));
+    let mut file = fs::File::create(&temp_path)
+        .await
+        .map_err(|e| AppError::FileError(format!("Failed to create file: {e}")))?;
+
+    let written = async {
+        file.write_all(&stored_data).await?;
+        file.flush().await
+    }
+    .await;
+    if let Err(e) = written {
+        let _ = fs::remove_file(&temp_path).await;
+        return Err(AppError::FileError(format!("Failed to write file: {e}")));
+    }
+    drop(file);
+
+    // Keep the upload as it was before optimization and the replaced content
+    let mut kept_versions: Vec<KeptVersion> = Vec::new();
+    for (name, mime_type, data, reason) in kept_contents {
+        let version_id = Uuid::new_v4();
+        let path = version_blob_path(&state.config.storage_path, project.id, version_id);
+        let written = async {
+            fs::create_dir_all(path.parent().expect("version paths have a parent")).await?;
+            fs::write(&path, &data).await
+        }
+        .await;
+        if let Err(e) = written {
+            let _ = fs::remove_file(&temp_path).await;
+            for version in &kept_versions {
+                let _ = fs::remove_file(&version.path).await;
+            }
+            return Err(AppError::FileError(format!(
+                "Failed to write {reason} version: {e}"
+            )));
+        }
+        kept_versions.push(KeptVersion {
+            id: version_id,
+            original_name: name,
+            path,
+            size: data.len() as i64,
+            mime_type,
+            content_hash: hex::encode(Sha256::digest(&data)),
+            reason,
+        });
+    }
+
+    // Save to database; if the records can't be written, drop the temp blob
+    // and the old content stays in place
+    let result: Result<File> = async {
+        let mut tx = state.pool.begin().await?;
+        let folder_id = match folder_path {
+            Some(ref path) => Some(lock_folder(&mut tx, project.id, path).await?),
+            None => None,
+        };
+        let mut file_record = if let Some(ref previous) = overwrite_target {
+            sqlx::query_as::<_, File>(
+                r#"
+                UPDATE files
+                SET stored_name = $1, file_path = $2, size = $3, mime_type = $4, content_hash = $5,
+                    storage_encoding = $6, stored_size = $7, precompressed_encodings = '{}',
+                    storage_tier = 'hot', upload_date = NOW(),
+                    description = COALESCE($9, description)
+                WHERE id = $8
+                RETURNING id, project_id, folder_id, original_name, stored_name, file_path, size, mime_type, upload_date, storage_encoding, precompressed_encodings, storage_tier, moderation_status
+                "#,
+            )
+            .bind(&stored_name)
+            .bind(storage_path.to_str().unwrap())
+            .bind(original_size)
+            .bind(&mime_type)
+            .bind(&content_hash)
+            .bind(&storage_encoding)
+            .bind(stored_data.len() as i64)
+            .bind(previous.id)
+            .bind(&description)
+            .fetch_one(&mut *tx)
+            .await?
+        } else {
+            sqlx::query_as::<_, File>(
+                r#"
+                INSERT INTO files (id, project_id, folder_id, original_name, stored_name, file_path, size, mime_type, content_hash, storage_encoding, stored_size, description)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+                RETURNING id, project_id, folder_id, original_name, stored_name, file_path, size, mime_type, upload_date, storage_encoding, precompressed_encodings, storage_tier, moderation_status
+                "#,
+            )
+            .bind(file_id)
+            .bind(project.id)
+            .bind(folder_id)
+            .bind(&file_name)
+            .bind(&stored_name)
+            .bind(storage_path.to_str().unwrap())
+            .bind(original_size)
+            .bind(&mime_type)
+            .bind(&content_hash)
+            .bind(&storage_encoding)
+            .bind(stored_data.len() as i64)
+            .bind(&description)
+            .fetch_one(&mut *tx)
+            .await?
+        };
+        file_record.moderation_status =
+            moderate_upload(&mut tx, state, &file_record, overwrite_target.is_some()).await?;
+        queue_media_preview(&mut tx, &state.config, &file_record).await?;
+
+        for version in &kept_versions {
+            sqlx::query(
+                r#"
+                INSERT INTO file_versions (id, file_id, original_name, file_path, size, mime_type, content_hash, reason)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                "#,
+            )
+            .bind(version.id)
+            .bind(file_record.id)
+            .bind(&version.original_name)
+            .bind(version.path.to_str().unwrap())
+            .bind(version.size)
+            .bind(&version.mime_type)
+            .bind(&version.content_hash)
+            .bind(version.reason)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        // Remember the result for retries with the same Idempotency-Key (replaces expired entries)
+        if let Some(key) = idempotency_key {
+            sqlx::query(
+                r#"
+                INSERT INTO upload_idempotency_keys (project_id, idempotency_key, file_id)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (project_id, idempotency_key)
+                DO UPDATE SET file_id = EXCLUDED.file_id, created_at = NOW()
+                "#,
+            )
+            .bind(project.id)
+            .bind(key)
+            .bind(file_record.id)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        record_file_events(
+            &mut tx,
+            state.events.as_deref(),
+            FileEventKind::Uploaded,
+            [&file_record],
+        )
+        .await?;
+        queue_replication(&mut tx, state.replication.as_deref(), [&file_record]).await?;
+        queue_cdn_purge(&mut tx, state.cdn.as_deref(), overwrite_target.as_ref()).await?;
+        notify_large_upload(&mut tx, project, &file_record, folder_path.as_deref()).await?;
+        tx.commit().await?;
+        Ok(file_record)
+    }
+    .await;
+
+    let file_record = match result {
+        Ok(file_record) => file_record,
+        Err(e) => {
+            let _ = fs::remove_file(&temp_path).await;
+            for version in &kept_versions {
+                let _ = fs::remove_file(&version.path).await;
+            }
+            return Err(e);
+        }
+    };
+    if let Err(e) = fs::rename(&temp_path, &storage_path).await {
+        let _ = fs::remove_file(&temp_path).await;
+        return Err(AppError::FileError(format!(
+            "Failed to move upload into place: {e}"
+        )));
+    }
+    spawn_quota_warnings(state.pool.clone(), state.mailer.clone(), project.id);
+
+    // Cached variants belong to the old content; remove the old blob too if
+    // the new content landed at a different path
+    if let Some(ref previous) = overwrite_target {
+        remove_variants(&previous.file_path).await;
+        delete_cold_blob(state.cold_storage.as_deref(), previous).await;
+        if previous.file_path != file_record.file_path {
+            if let Err(e) = fs::remove_file(&previous.file_path).await {
+                tracing::warn!(
+                    "Failed to remove replaced file {}: {}",
+                    previous.file_path,
+                    e
+                );
+            }
+        }
+    }
 
-    let download_url = format!("/api/files/{}", file_record.id);
+    let download_url = format!("/api/v1/files/{}", file_record.id);
 
-    Ok(Json(UploadResponse {
+    Ok(UploadResponse {
         file_id: file_record.id,
         original_name: file_record.original_name,
         size: file_record.size,
         mime_type: file_record.mime_type,
         download_url,
         folder_path,
-    }))
+        moderation_status: file_record.moderation_status,
+    })
 }
 
-#[derive(serde::Deserialize)]
-pub struct DownloadQuery {
-    pub api_key: Option<String>,
-    pub download: Option<bool>,
-}
-
-pub async fn download_file(
-    State(state): State<AppState>,
-    headers: HeaderMap,
-    Path(file_id): Path<Uuid>,
-    axum::extract::Query(query): axum::extract::Query<DownloadQuery>,
-) -> Result<Response> {
-    // Get file from database
+/// Load a file together with its project and folder for an access check
+pub(crate) async fn load_file_scope(
+    pool: &PgPool,
+    file_id: Uuid,
+) -> Result<(File, Project, Option<Folder>)> {
     let file = sqlx::query_as::<_, File>(
-        "SELECT id, project_id, folder_id, original_name, stored_name, file_path, size, mime_type, upload_date FROM files WHERE id = $1"
+        "SELECT id, project_id, folder_id, original_name, stored_name, file_path, size, mime_type, upload_date, storage_encoding, precompressed_encodings, storage_tier, moderation_status FROM files WHERE id = $1"
     )
     .bind(file_id)
-    .fetch_optional(&state.pool)
+    .fetch_optional(pool)
     .await?
     .ok_or(AppError::NotFound("File not found".to_string()))?;
 
-    // Get project
-    let project = sqlx::query_as::<_, Project>(
-        "SELECT id, user_id, name, api_key, is_public, created_at FROM projects WHERE id = $1",
-    )
+    let project = sqlx::query_as::<_, Project>(&format!(
+        "SELECT {PROJECT_COLUMNS} FROM projects WHERE id = $1"
+    ))
     .bind(file.project_id)
-    .fetch_optional(&state.pool)
+    .fetch_optional(pool)
     .await?
     .ok_or(AppError::NotFound("Project not found".to_string()))?;
 
-    // Helper to get API key from header or query param
-    let get_api_key = || -> Option<&str> {
-        // First try header
-        if let Some(key) = headers.get("X-API-Key").and_then(|h| h.to_str().ok()) {
-            return Some(key);
-        }
-        // Then try query param
-        query.api_key.as_deref()
-    };
-
-    // Check access permissions
-    if !project.is_public {
-        // If folder exists, check folder visibility
-        if let Some(folder_id) = file.folder_id {
-            let folder = sqlx::query_as::<_, Folder>(
+    let folder = match file.folder_id {
+        Some(folder_id) => {
+            sqlx::query_as::<_, Folder>(
                 "SELECT id, project_id, path, is_public, created_at FROM folders WHERE id = $1",
             )
             .bind(folder_id)
-            .fetch_optional(&state.pool)
-            .await?;
+            .fetch_optional(pool)
+            .await?
+        }
+        None => None,
+    };
 
-            if let Some(folder) = folder {
-                if !folder.is_public {
-                    // Require API key (from header or query param)
-                    let api_key = get_api_key().ok_or(AppError::Unauthorized)?;
-                    let api_key_uuid =
-                        Uuid::parse_str(api_key).map_err(|_| AppError::Unauthorized)?;
+    Ok((file, project, folder))
+}
 
-                    if api_key_uuid != project.api_key {
-                        return Err(AppError::Unauthorized);
-                    }
-                }
-            }
-        } else {
-            // No folder, check project API key (from header or query param)
-            let api_key = get_api_key().ok_or(AppError::Unauthorized)?;
-            let api_key_uuid = Uuid::parse_str(api_key).map_err(|_| AppError::Unauthorized)?;
+/// Load a file for what is kept or generated next to it (versions, previews);
+/// the same access as downloading it, country restrictions included, without
+/// share links
+pub(crate) async fn readable_file(
+    state: &AppState,
+    optional_auth: &OptionalAuthUser,
+    headers: &HeaderMap,
+    client_ip: IpAddr,
+    file_id: Uuid,
+    api_key: Option<&str>,
+) -> Result<(File, Project)> {
+    let (file, project, folder) = load_file_scope(&state.pool, file_id).await?;
+    let credentials = Credentials::resolve(&state.pool, optional_auth, headers, api_key).await?;
+    if !can_read(&project, folder.as_ref(), &credentials) {
+        return Err(AppError::Unauthorized);
+    }
+    if file.moderation_status != ModerationStatus::Approved && !can_write(&project, &credentials) {
+        return Err(AppError::Forbidden(
+            "File is awaiting moderation review".to_string(),
+        ));
+    }
+    ensure_geo_allowed(state, &project, client_ip)?;
+    Ok((file, project))
+}
+
+/// Load the requested files the caller holds `permission` on.
+/// JWT callers get the subset they're allowed; API key and anonymous callers
+/// must be allowed every requested file.
+async fn authorized_files(
+    pool: &PgPool,
+    credentials: &Credentials,
+    file_ids: &[Uuid],
+    permission: Permission,
+) -> Result<Vec<File>> {
+    let mut file_ids = file_ids.to_vec();
+    file_ids.sort();
+    file_ids.dedup();
+
+    let files = sqlx::query_as::<_, File>(
+        "SELECT id, project_id, folder_id, original_name, stored_name, file_path, size, mime_type, upload_date, storage_encoding, precompressed_encodings, storage_tier, moderation_status FROM files WHERE id = ANY($1)"
+    )
+    .bind(&file_ids)
+    .fetch_all(pool)
+    .await?;
+
+    if files.is_empty() {
+        return Err(AppError::NotFound("No files found".to_string()));
+    }
 
-            if api_key_uuid != project.api_key {
-                return Err(AppError::Unauthorized);
+    let project_ids: Vec<Uuid> = files.iter().map(|f| f.project_id).collect();
+    let projects: HashMap<Uuid, Project> = sqlx::query_as::<_, Project>(&format!(
+        "SELECT {PROJECT_COLUMNS} FROM projects WHERE id = ANY($1)"
+    ))
+    .bind(&project_ids)
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|p| (p.id, p))
+    .collect();
+
+    let folder_ids: Vec<Uuid> = files.iter().filter_map(|f| f.folder_id).collect();
+    let folders: HashMap<Uuid, Folder> = sqlx::query_as::<_, Folder>(
+        "SELECT id, project_id, path, is_public, created_at FROM folders WHERE id = ANY($1)",
+    )
+    .bind(&folder_ids)
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|f| (f.id, f))
+    .collect();
+
+    let allowed: Vec<File> = files
+        .into_iter()
+        .filter(|file| {
+            projects.get(&file.project_id).is_some_and(|project| {
+                let folder = file.folder_id.and_then(|id| folders.get(&id));
+                is_allowed(permission, project, folder, credentials)
+                    && (permission != Permission::Read
+                        || file.moderation_status == ModerationStatus::Approved
+                        || can_write(project, credentials))
+            })
+        })
+        .collect();
+
+    if permission != Permission::Read {
+        for file in &allowed {
+            if let Some(project) = projects.get(&file.project_id) {
+                ensure_project_writable(project)?;
             }
         }
     }
 
-    // Read file from disk
-    let file_path = PathBuf::from(&file.file_path);
-    let file_data = fs::read(&file_path)
+    match credentials {
+        Credentials::User(..) | Credentials::Admin => Ok(allowed),
+        _ if allowed.is_empty() => Err(AppError::Unauthorized),
+        _ if allowed.len() != file_ids.len() => Err(AppError::BadRequest(
+            "With API key auth, all files must belong to the same project".to_string(),
+        )),
+        _ => Ok(allowed),
+    }
+}
+
+/// Resolve the `Access-Control-Allow-Origin` value for a cross-origin download.
+/// The global dashboard origins are always allowed, plus the project's `allowed_origins`.
+fn download_cors_origin(
+    headers: &HeaderMap,
+    project: &Project,
+    config: &Config,
+) -> Option<HeaderValue> {
+    let origin = headers.get(header::ORIGIN)?;
+    let origin_str = origin.to_str().ok()?;
+
+    if project.allowed_origins.iter().any(|o| o == "*") {
+        return Some(HeaderValue::from_static("*"));
+    }
+
+    let allowed = project
+        .allowed_origins
+        .iter()
+        .chain(config.cors_origins.iter())
+        .any(|o| o == origin_str);
+
+    allowed.then(|| origin.clone())
+}
+
+/// Answer CORS preflight requests for a download using the project's allowed origins
+pub async fn download_preflight(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(file_id): Path<Uuid>,
+) -> Result<Response> {
+    let project = sqlx::query_as::<_, Project>(&format!(
+        r#"
+        SELECT {PROJECT_COLUMNS}
+        FROM projects
+        WHERE id = (SELECT project_id FROM files WHERE id = $1)
+        "#
+    ))
+    .bind(file_id)
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or(AppError::NotFound("File not found".to_string()))?;
+
+    let mut response = Response::builder()
+        .status(StatusCode::NO_CONTENT)
+        .header(header::VARY, "Origin");
+
+    if let Some(origin) = download_cors_origin(&headers, &project, &state.config) {
+        response = response
+            .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin)
+            .header(header::ACCESS_CONTROL_ALLOW_METHODS, "GET, OPTIONS")
+            .header(
+                header::ACCESS_CONTROL_ALLOW_HEADERS,
+                "authorization, x-api-key, range",
+            )
+            .header(header::ACCESS_CONTROL_MAX_AGE, "600");
+    }
+
+    response
+        .body(Body::empty())
+        .map_err(|e| AppError::InternalError(format!("Failed to build response: {e}")))
+}
+
+/// Response body for a blob on disk, streamed through the memory budget's
+/// pooled buffers and at `bandwidth_limit` bytes/sec when set
+pub async fn read_blob_body(
+    budget: &Arc<MemoryBudget>,
+    path: &std::path::Path,
+    bandwidth_limit: Option<i64>,
+) -> Result<(usize, Body)> {
+    let handle = fs::File::open(path)
         .await
-        .map_err(|e| AppError::FileError(format!("Failed to read file: {e}")))?;
+        .map_err(|e| AppError::FileError(format!("Failed to open file: {e}")))?;
+    let metadata = handle
+        .metadata()
+        .await
+        .map_err(|e| AppError::FileError(format!("Failed to read file metadata: {e}")))?;
+    let body = match bandwidth_limit {
+        Some(limit) => {
+            let chunks = budget.stream_file(handle, throttled_chunk_size(limit as u64));
+            Body::from_stream(throttled_stream(chunks, limit as u64))
+        }
+        None => Body::from_stream(budget.stream_file(handle, STREAM_BUFFER_SIZE)),
+    };
+    Ok((metadata.len() as usize, body))
+}
+
+/// Reserve `bytes` of the memory budget for a transfer, answering 503 if the
+/// server is still out of room after `MEMORY_BUDGET_WAIT_SECS`
+pub(crate) async fn reserve_memory(state: &AppState, bytes: usize) -> Result<MemoryReservation> {
+    match state.memory_budget.reserve(bytes).await {
+        Some(reservation) => Ok(reservation),
+        None => {
+            state.metrics.record_memory_budget_exhausted();
+            Err(AppError::Overloaded(
+                state.config.load_shed_retry_after_secs,
+            ))
+        }
+    }
+}
+
+/// Grow an upload's reservation to `bytes` as its body arrives, answering 503
+/// like `reserve_memory` if there's no room
+pub(crate) async fn grow_reservation(
+    state: &AppState,
+    reservation: &mut MemoryReservation,
+    bytes: usize,
+) -> Result<()> {
+    if reservation.grow_to(bytes).await {
+        return Ok(());
+    }
+    state.metrics.record_memory_budget_exhausted();
+    Err(AppError::Overloaded(
+        state.config.load_shed_retry_after_secs,
+    ))
+}
+
+/// Redirect to a presigned URL for a file in S3 cold storage, so the bucket
+/// serves the bytes. `None` when presigned downloads don't apply: they are off,
+/// the file is hot, the project is throttled (S3 can't enforce the limit), or
+/// the blob is zstd-compressed and the client doesn't accept zstd.
+async fn presigned_download(
+    state: &AppState,
+    headers: &HeaderMap,
+    project: &Project,
+    file: &File,
+    disposition: &str,
+) -> Option<Response> {
+    let cold = state.cold_storage.as_deref()?;
+    if !state.config.presigned_downloads
+        || !cold.can_presign()
+        || file.storage_tier != COLD_TIER
+        || project.download_bandwidth_limit.is_some()
+    {
+        return None;
+    }
+    let stored_zstd = file.storage_encoding.as_deref() == Some(ZSTD_ENCODING);
+    if stored_zstd
+        && negotiate_encoding(headers, &[ResponseEncoding::Zstd]) != ResponseEncoding::Zstd
+    {
+        return None;
+    }
+
+    let content_disposition = content_disposition(disposition, &file.original_name);
+    let mut response_headers = vec![
+        ("response-content-type", file.mime_type.as_str()),
+        ("response-content-disposition", content_disposition.as_str()),
+    ];
+    if stored_zstd {
+        response_headers.push(("response-content-encoding", ZSTD_ENCODING));
+    }
+    let ttl = std::time::Duration::from_secs(state.config.presigned_url_ttl_secs);
+    let url = match cold.presigned_url(file, ttl, &response_headers).await {
+        Ok(url) => url,
+        Err(e) => {
+            // Serve it the usual way instead
+            tracing::warn!("Failed to presign download of file {}: {}", file.id, e);
+            return None;
+        }
+    };
+
+    let mut response = Response::builder()
+        .status(StatusCode::FOUND)
+        .header(header::LOCATION, url.as_str())
+        .header(header::CACHE_CONTROL, "no-store")
+        .header(header::VARY, "Origin, Accept-Encoding");
+    if let Some(origin) = download_cors_origin(headers, project, &state.config) {
+        response = response.header(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+    }
+    response.body(Body::empty()).ok()
+}
+
+#[derive(serde::Deserialize)]
+pub struct DownloadQuery {
+    pub api_key: Option<String>,
+    pub download: Option<bool>,
+    /// Token of a share link to the file
+    pub share: Option<String>,
+}
+
+/// Download a file. Private files need the project API key (`X-API-Key` header or
+/// `?api_key=`), the project owner's Bearer access token or a share link token
+/// (`?share=`)
+pub async fn download_file(
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    optional_auth: OptionalAuthUser,
+    headers: HeaderMap,
+    Path(file_id): Path<Uuid>,
+    axum::extract::Query(query): axum::extract::Query<DownloadQuery>,
+) -> Result<Response> {
+    // Read from a replica; a file uploaded moments ago may not have replicated yet
+    let (file, project, folder) = match load_file_scope(state.read_pool.get(), file_id).await {
+        Err(AppError::NotFound(_)) => load_file_scope(&state.pool, file_id).await?,
+        scope => scope?,
+    };
+
+    // API key may come from the header or the query param
+    let credentials = Credentials::resolve(
+        &state.pool,
+        &optional_auth,
+        &headers,
+        query.api_key.as_deref(),
+    )
+    .await?;
+    let readable = can_read(&project, folder.as_ref(), &credentials)
+        || folder_cookie_grants_read(
+            &headers,
+            &state.config.jwt_secret,
+            &project,
+            folder.as_ref(),
+        )
+        || match query.share {
+            Some(ref token) => share_grants_read(&state.pool, file.id, token).await?,
+            None => false,
+        };
+    if !readable {
+        return Err(AppError::Unauthorized);
+    }
+
+    // Files held by moderation are only served to those who can review them
+    if file.moderation_status != ModerationStatus::Approved && !can_write(&project, &credentials) {
+        return Err(AppError::Forbidden(
+            match file.moderation_status {
+                ModerationStatus::Rejected => "File was rejected by content moderation",
+                _ => "File is awaiting moderation review",
+            }
+            .to_string(),
+        ));
+    }
+
+    ensure_geo_allowed(&state, &project, client_ip)?;
 
-    // Build response with proper headers
     // Use "attachment" if download=true, otherwise "inline" for browser preview
     let disposition = if query.download.unwrap_or(false) {
         "attachment"
@@ -300,36 +1488,348 @@ pub async fn download_file(
         "inline"
     };
 
-    let response = Response::builder()
+    // Cold files can be fetched from the bucket directly; otherwise pull the
+    // blob back from cold storage if the lifecycle rule moved it there
+    if let Some(response) = presigned_download(&state, &headers, &project, &file, disposition).await
+    {
+        state.download_stats.record(file.id);
+        return Ok(response);
+    }
+    ensure_hot(&state.pool, state.cold_storage.as_deref(), &file).await?;
+    state.download_stats.record(file.id);
+
+    // Public web assets get cached gzip/brotli variants, generated in the background
+    let stored_zstd = file.storage_encoding.as_deref() == Some(ZSTD_ENCODING);
+    let compressible = is_compressible(&file.mime_type);
+    if state.config.precompress_public_assets
+        && compressible
+        && file.size >= MIN_COMPRESSIBLE_SIZE as i64
+        && file.precompressed_encodings.is_empty()
+        && can_read(&project, folder.as_ref(), &Credentials::Anonymous)
+    {
+        state.precompress.schedule(state.pool.clone(), &file);
+    }
+
+    // Encodings this file can be served in without compressing per request
+    // (zstd blobs are also re-encoded as gzip on the fly)
+    let mut available: Vec<ResponseEncoding> = PRECOMPRESSED_ENCODINGS
+        .into_iter()
+        .filter(|e| file.precompressed_encodings.iter().any(|p| p == e.as_str()))
+        .collect();
+    if stored_zstd {
+        available.extend([ResponseEncoding::Zstd, ResponseEncoding::Gzip]);
+    }
+    let encoding = negotiate_encoding(&headers, &available);
+
+    // Blob sent as stored, with its Content-Encoding: a cached variant, the
+    // blob itself, or a zstd blob to a client that accepts zstd
+    let file_path = PathBuf::from(&file.file_path);
+    let verbatim = if PRECOMPRESSED_ENCODINGS.contains(&encoding)
+        && file
+            .precompressed_encodings
+            .iter()
+            .any(|p| p == encoding.as_str())
+    {
+        Some((
+            variant_path(&file.file_path, encoding),
+            Some(encoding.as_str()),
+        ))
+    } else if !stored_zstd {
+        Some((file_path.clone(), None))
+    } else if encoding == ResponseEncoding::Zstd {
+        Some((file_path.clone(), Some("zstd")))
+    } else {
+        None
+    };
+
+    // Read file from disk - throttled projects stream at their configured rate -
+    // or hand the path to the reverse proxy when download offloading is on
+    let limit = project.download_bandwidth_limit;
+    let mut content_encoding = None;
+    let mut offload = None;
+    let (content_length, body) = if let Some((path, encoding)) = verbatim {
+        content_encoding = encoding;
+        offload = offload_headers(
+            state.config.download_offload,
+            &state.config.storage_path,
+            &state.config.download_offload_prefix,
+            &path,
+            limit,
+        )
+        .await;
+        if offload.is_some() {
+            (None, Body::empty())
+        } else {
+            let (content_length, body) = read_blob_body(&state.memory_budget, &path, limit).await?;
+            (Some(content_length), body)
+        }
+    } else {
+        // Compressed at rest and not accepted as is: decompress (and re-encode as
+        // gzip when that's accepted), holding the stored and decoded copies in
+        // the memory budget until the body has been sent
+        let stored_size = fs::metadata(&file_path)
+            .await
+            .map_or(0, |m| m.len() as usize);
+        let reservation = reserve_memory(&state, stored_size + file.size as usize).await?;
+        let stored = fs::read(&file_path)
+            .await
+            .map_err(|e| AppError::FileError(format!("Failed to read file: {e}")))?;
+        let (data, gzipped) = tokio::task::spawn_blocking(move || {
+            let data = zstd_decompress(&stored)?;
+            if encoding == ResponseEncoding::Gzip {
+                gzip_compress(&data).map(|d| (d, true))
+            } else {
+                Ok((data, false))
+            }
+        })
+        .await
+        .map_err(|e| AppError::InternalError(format!("Decompression task failed: {e}")))?
+        .map_err(|e| AppError::FileError(format!("Failed to decompress file: {e}")))?;
+        if gzipped {
+            content_encoding = Some("gzip");
+        }
+
+        let content_length = data.len();
+        let data = reservation.hold(data);
+        let body = match limit {
+            Some(limit) => {
+                let chunk_size = throttled_chunk_size(limit as u64);
+                let chunks = (0..data.len())
+                    .step_by(chunk_size)
+                    .map(move |start| Ok(data.slice(start..(start + chunk_size).min(data.len()))));
+                Body::from_stream(throttled_stream(
+                    futures::stream::iter(chunks),
+                    limit as u64,
+                ))
+            }
+            None => Body::from(data),
+        };
+        (Some(content_length), body)
+    };
+
+    // Build response with proper headers
+    let vary = if compressible {
+        "Origin, Accept-Encoding"
+    } else {
+        "Origin"
+    };
+    let mut response = Response::builder()
         .status(StatusCode::OK)
+        .header(header::VARY, vary);
+
+    if let Some(origin) = download_cors_origin(&headers, &project, &state.config) {
+        response = response.header(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+    }
+    if let Some(encoding) = content_encoding {
+        response = response.header(header::CONTENT_ENCODING, encoding);
+    }
+    // The proxy sets the length of the blob it sends
+    if let Some(content_length) = content_length {
+        response = response.header(header::CONTENT_LENGTH, content_length);
+    }
+    for (name, value) in offload.into_iter().flatten() {
+        response = response.header(name, value);
+    }
+
+    let mut response = response
         .header(header::CONTENT_TYPE, file.mime_type)
-        .header(header::CONTENT_LENGTH, file_data.len())
         .header(
             header::CONTENT_DISPOSITION,
-            format!("{disposition}; filename=\"{}\"", file.original_name),
+            content_disposition(disposition, &file.original_name),
         )
-        .body(Body::from(file_data))
+        .body(body)
         .map_err(|e| AppError::InternalError(format!("Failed to build response: {e}")))?;
 
+    if project.noindex || state.config.robots_noindex {
+        response.headers_mut().insert(
+            header::HeaderName::from_static("x-robots-tag"),
+            HeaderValue::from_static("noindex"),
+        );
+    }
+    // The project's own headers win over the defaults above (CORS included).
+    // Stored headers off the allowlist are never sent.
+    for (name, value) in project.response_headers.iter() {
+        if !is_allowed_response_header(name) {
+            continue;
+        }
+        if let (Ok(name), Ok(value)) = (
+            header::HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(value),
+        ) {
+            response.headers_mut().insert(name, value);
+        }
+    }
+
     Ok(response)
 }
 
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct ListFilesQuery {
+    #[serde(default)]
+    pub sort: FileSort,
+    /// Only files whose name or description contains words starting with
+    /// every word of this search
+    pub q: Option<String>,
+}
+
+/// `to_tsquery` text requiring every word of a search as a prefix
+/// (`final rep` becomes `final:* & rep:*`); `None` if it has no words
+fn search_tsquery(search: &str) -> Option<String> {
+    let terms: Vec<String> = search
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("{}:*", word.to_lowercase()))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" & "))
+}
+
 pub async fn list_project_files(
     State(state): State<AppState>,
     auth_user: AuthUser,
     Path(project_id): Path<Uuid>,
-) -> Result<Json<Vec<FileMetadata>>> {
-    // Check if project belongs to user
-    let _project = sqlx::query_as::<_, Project>(
-        "SELECT id, user_id, name, api_key, is_public, created_at FROM projects WHERE id = $1 AND user_id = $2"
-    )
+    axum::extract::Query(admin): axum::extract::Query<AdminQuery>,
+    axum::extract::Query(query): axum::extract::Query<ListFilesQuery>,
+    axum::extract::Query(page): axum::extract::Query<PageQuery>,
+) -> Result<Json<Paginated<FileMetadata>>> {
+    let as_admin = admin_override(
+        &auth_user,
+        &admin,
+        RolePermission::AdminProjectsRead,
+        "list_project_files",
+        project_id,
+    )?;
+
+    // Check the user owns or collaborates on the project (or an admin override is in effect)
+    let _project = sqlx::query_as::<_, Project>(&format!(
+        r#"
+        SELECT {PROJECT_COLUMNS}
+        FROM projects
+        WHERE id = $1 AND (
+            user_id = $2
+            OR $3
+            OR EXISTS (SELECT 1 FROM project_members m WHERE m.project_id = projects.id AND m.user_id = $2)
+        )
+        "#
+    ))
     .bind(project_id)
     .bind(auth_user.id)
+    .bind(as_admin)
     .fetch_optional(&state.pool)
     .await?
     .ok_or(AppError::NotFound("Project not found".to_string()))?;
 
+    // The cursor holds the sort key of the last file: its download count when
+    // sorting by downloads, then its upload date and ID
+    let limit = page.limit();
+    let (after_downloads, after) = match query.sort {
+        FileSort::Newest => (None, page.after::<(chrono::DateTime<chrono::Utc>, Uuid)>()?),
+        FileSort::MostDownloaded => {
+            match page.after::<(i64, chrono::DateTime<chrono::Utc>, Uuid)>()? {
+                Some((downloads, uploaded, id)) => (Some(downloads), Some((uploaded, id))),
+                None => (None, None),
+            }
+        }
+    };
+    let (order_by, cursor_filter) = match query.sort {
+        FileSort::Newest => (
+            "f.upload_date DESC, f.id DESC",
+            "AND ($3::timestamptz IS NULL OR (f.upload_date, f.id) < ($3, $4))",
+        ),
+        FileSort::MostDownloaded => (
+            "f.download_count DESC, f.upload_date DESC, f.id DESC",
+            "AND ($3::timestamptz IS NULL OR (f.download_count, f.upload_date, f.id) < ($5, $3, $4))",
+        ),
+    };
+    let tsquery = query.q.as_deref().and_then(search_tsquery);
+    let search_filter = if tsquery.is_some() {
+        "AND file_search_document(f.original_name, f.description) @@ to_tsquery('simple', $2)"
+    } else {
+        ""
+    };
+
     // Get all files with folder paths in a single query (avoid N+1)
+    let files = sqlx::query_as::<_, FileMetadata>(&format!(
+        r#"
+        SELECT
+            f.id,
+            f.project_id,
+            f.folder_id,
+            fol.path as folder_path,
+            f.original_name,
+            f.size,
+            f.mime_type,
+            f.upload_date,
+            '/api/v1/files/' || f.id::text as download_url,
+            f.download_count,
+            f.last_accessed_at,
+            f.description,
+            f.moderation_status,
+            f.moderation_reason,
+            f.legal_hold,
+            f.append_only
+        FROM files f
+        LEFT JOIN folders fol ON fol.id = f.folder_id
+        WHERE f.project_id = $1 {search_filter} {cursor_filter}
+        ORDER BY {order_by}
+        LIMIT $6
+        "#,
+    ))
+    .bind(project_id)
+    .bind(&tsquery)
+    .bind(after.map(|(uploaded, _)| uploaded))
+    .bind(after.map(|(_, id)| id))
+    .bind(after_downloads)
+    .bind(limit + 1)
+    .fetch_all(state.read_pool.get())
+    .await?;
+
+    // The cached counter, unless a search narrows the listing
+    let total: i64 = if tsquery.is_some() {
+        sqlx::query_scalar(&format!(
+            "SELECT COUNT(*) FROM files f WHERE f.project_id = $1 {search_filter}"
+        ))
+        .bind(project_id)
+        .bind(&tsquery)
+        .fetch_one(state.read_pool.get())
+        .await?
+    } else {
+        sqlx::query_scalar(
+            "SELECT COALESCE((SELECT file_count FROM project_stats WHERE project_id = $1), 0)",
+        )
+        .bind(project_id)
+        .fetch_one(state.read_pool.get())
+        .await?
+    };
+
+    Ok(Json(match query.sort {
+        FileSort::Newest => Paginated::new(files, limit, total, |f| (f.upload_date, f.id)),
+        FileSort::MostDownloaded => Paginated::new(files, limit, total, |f| {
+            (f.download_count, f.upload_date, f.id)
+        }),
+    }))
+}
+
+/// Default and largest page size of `GET /api/files/recent`
+const RECENT_FILES_DEFAULT_LIMIT: i64 = 20;
+const RECENT_FILES_MAX_LIMIT: i64 = 100;
+
+#[derive(Debug, serde::Deserialize)]
+pub struct RecentFilesQuery {
+    pub limit: Option<i64>,
+}
+
+/// Files recently uploaded or downloaded across every project the user owns or
+/// is a member of, most recent activity first
+pub async fn recent_files(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    axum::extract::Query(query): axum::extract::Query<RecentFilesQuery>,
+) -> Result<Json<Vec<FileMetadata>>> {
+    let limit = query
+        .limit
+        .unwrap_or(RECENT_FILES_DEFAULT_LIMIT)
+        .clamp(1, RECENT_FILES_MAX_LIMIT);
+
     let files = sqlx::query_as::<_, FileMetadata>(
         r#"
         SELECT
@@ -341,15 +1841,28 @@ pub async fn list_project_files(
             f.size,
             f.mime_type,
             f.upload_date,
-            '/api/files/' || f.id::text as download_url
+            '/api/v1/files/' || f.id::text as download_url,
+            f.download_count,
+            f.last_accessed_at,
+            f.description,
+            f.moderation_status,
+            f.moderation_reason,
+            f.legal_hold,
+            f.append_only
         FROM files f
         LEFT JOIN folders fol ON fol.id = f.folder_id
-        WHERE f.project_id = $1
-        ORDER BY f.upload_date DESC
+        WHERE f.project_id IN (
+            SELECT id FROM projects WHERE user_id = $1
+            UNION
+            SELECT project_id FROM project_members WHERE user_id = $1
+        )
+        ORDER BY GREATEST(f.upload_date, f.last_accessed_at) DESC
+        LIMIT $2
         "#,
     )
-    .bind(project_id)
-    .fetch_all(&state.pool)
+    .bind(auth_user.id)
+    .bind(limit)
+    .fetch_all(state.read_pool.get())
     .await?;
 
     Ok(Json(files))
@@ -363,66 +1876,265 @@ pub async fn delete_file(
     optional_auth: OptionalAuthUser,
     headers: HeaderMap,
     Path(file_id): Path<Uuid>,
+    axum::extract::Query(admin): axum::extract::Query<AdminQuery>,
 ) -> Result<Json<serde_json::Value>> {
-    // Get file first
-    let file = sqlx::query_as::<_, File>(
-        "SELECT id, project_id, folder_id, original_name, stored_name, file_path, size, mime_type, upload_date FROM files WHERE id = $1"
-    )
-    .bind(file_id)
-    .fetch_optional(&state.pool)
-    .await?
-    .ok_or(AppError::NotFound("File not found".to_string()))?;
-
-    // Get project
-    let project = sqlx::query_as::<_, Project>(
-        "SELECT id, user_id, name, api_key, is_public, created_at FROM projects WHERE id = $1",
-    )
-    .bind(file.project_id)
-    .fetch_optional(&state.pool)
-    .await?
-    .ok_or(AppError::NotFound("Project not found".to_string()))?;
-
-    // Check authorization - either JWT (user owns project) or API key
-    let authorized = if let Some(ref user) = optional_auth.0 {
-        // JWT auth - check user owns the project
-        project.user_id == user.id
-    } else {
-        // Try API key auth
-        if let Some(api_key) = headers.get("X-API-Key").and_then(|h| h.to_str().ok()) {
-            if let Ok(api_key_uuid) = Uuid::parse_str(api_key) {
-                api_key_uuid == project.api_key
-            } else {
-                false
-            }
-        } else {
-            false
+    let (file, project, _) = load_file_scope(&state.pool, file_id).await?;
+
+    let credentials = match optional_auth.0 {
+        Some(ref user)
+            if admin_override(
+                user,
+                &admin,
+                RolePermission::AdminFilesDelete,
+                "delete_file",
+                file_id,
+            )? =>
+        {
+            Credentials::Admin
         }
+        _ => Credentials::resolve(&state.pool, &optional_auth, &headers, None).await?,
     };
-
-    if !authorized {
+    if !can_write(&project, &credentials) {
         return Err(AppError::Unauthorized);
     }
+    ensure_project_writable(&project)?;
+    remove_file_record(&state, &file).await?;
+
+    Ok(Json(serde_json::json!({
+        "message": "File deleted successfully"
+    })))
+}
+
+/// Delete a file's record, then its blob, cached variants and cold copy
+pub(crate) async fn remove_file_record(state: &AppState, file: &File) -> Result<()> {
+    // Delete from database first; a failed delete leaves the file intact
+    let mut tx = state.pool.begin().await?;
+    sqlx::query("DELETE FROM files WHERE id = $1")
+        .bind(file.id)
+        .execute(&mut *tx)
+        .await?;
+    record_file_events(
+        &mut tx,
+        state.events.as_deref(),
+        FileEventKind::Deleted,
+        [file],
+    )
+    .await?;
+    queue_replication(&mut tx, state.replication.as_deref(), [file]).await?;
+    queue_cdn_purge(&mut tx, state.cdn.as_deref(), [file]).await?;
+    tx.commit().await?;
 
     // Delete file from disk
     let file_path = PathBuf::from(&file.file_path);
     if file_path.exists() {
-        fs::remove_file(&file_path)
-            .await
-            .map_err(|e| AppError::FileError(format!("Failed to delete file: {e}")))?;
+        if let Err(e) = fs::remove_file(&file_path).await {
+            tracing::warn!("Failed to delete file {}: {}", file_path.display(), e);
+        }
+    }
+    remove_variants(&file.file_path).await;
+    delete_cold_blob(state.cold_storage.as_deref(), file).await;
+    Ok(())
+}
+
+/// Edit a file's details - same authentication as `delete_file`
+pub async fn update_file(
+    State(state): State<AppState>,
+    optional_auth: OptionalAuthUser,
+    headers: HeaderMap,
+    Path(file_id): Path<Uuid>,
+    axum::extract::Query(admin): axum::extract::Query<AdminQuery>,
+    Json(req): Json<UpdateFileRequest>,
+) -> Result<Json<FileMetadata>> {
+    let (_, project, _) = load_file_scope(&state.pool, file_id).await?;
+
+    let credentials = match optional_auth.0 {
+        Some(ref user)
+            if admin_override(
+                user,
+                &admin,
+                RolePermission::AdminFilesWrite,
+                "update_file",
+                file_id,
+            )? =>
+        {
+            Credentials::Admin
+        }
+        _ => Credentials::resolve(&state.pool, &optional_auth, &headers, None).await?,
+    };
+    if !can_write(&project, &credentials) {
+        return Err(AppError::Unauthorized);
     }
+    ensure_project_writable(&project)?;
 
-    // Delete from database
-    sqlx::query("DELETE FROM files WHERE id = $1")
+    let description = req
+        .description
+        .as_deref()
+        .map(normalize_description)
+        .transpose()?
+        .flatten();
+
+    sqlx::query("UPDATE files SET description = $1 WHERE id = $2")
+        .bind(description)
         .bind(file_id)
         .execute(&state.pool)
         .await?;
 
-    Ok(Json(serde_json::json!({
-        "message": "File deleted successfully"
-    })))
+    Ok(Json(load_file_metadata(&state.pool, file_id).await?))
 }
 
-#[derive(serde::Deserialize)]
+/// Record a project admin's moderation decision (`approved` or `rejected`),
+/// overriding the moderation service. Same authentication as `delete_file`.
+pub async fn review_file(
+    State(state): State<AppState>,
+    optional_auth: OptionalAuthUser,
+    headers: HeaderMap,
+    Path(file_id): Path<Uuid>,
+    axum::extract::Query(admin): axum::extract::Query<AdminQuery>,
+    Json(req): Json<ReviewFileRequest>,
+) -> Result<Json<FileMetadata>> {
+    let (_, project, _) = load_file_scope(&state.pool, file_id).await?;
+
+    let credentials = match optional_auth.0 {
+        Some(ref user)
+            if admin_override(
+                user,
+                &admin,
+                RolePermission::AdminFilesWrite,
+                "review_file",
+                file_id,
+            )? =>
+        {
+            Credentials::Admin
+        }
+        _ => Credentials::resolve(&state.pool, &optional_auth, &headers, None).await?,
+    };
+    if !can_write(&project, &credentials) {
+        return Err(AppError::Unauthorized);
+    }
+
+    if req.status == ModerationStatus::PendingReview {
+        return Err(AppError::BadRequest(
+            "status must be approved or rejected".to_string(),
+        ));
+    }
+    let reason = req
+        .reason
+        .as_deref()
+        .map(str::trim)
+        .filter(|reason| !reason.is_empty());
+    if reason.is_some_and(|reason| reason.len() > MAX_TEXT_FIELD_LENGTH) {
+        return Err(AppError::BadRequest(format!(
+            "reason must be at most {MAX_TEXT_FIELD_LENGTH} bytes"
+        )));
+    }
+
+    // A decision replaces any check still queued for the moderation service
+    let mut tx = state.pool.begin().await?;
+    sqlx::query("UPDATE files SET moderation_status = $1, moderation_reason = $2 WHERE id = $3")
+        .bind(req.status)
+        .bind(reason)
+        .bind(file_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM moderation_jobs WHERE file_id = $1")
+        .bind(file_id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    Ok(Json(load_file_metadata(&state.pool, file_id).await?))
+}
+
+/// Place or release a legal hold on a file (project owner, or an admin with
+/// `?as_admin=true`). Changes are written to the audit log.
+pub async fn set_file_legal_hold(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(file_id): Path<Uuid>,
+    axum::extract::Query(admin): axum::extract::Query<AdminQuery>,
+    Json(req): Json<LegalHoldRequest>,
+) -> Result<Json<FileMetadata>> {
+    let (_, project, _) = load_file_scope(&state.pool, file_id).await?;
+    if project.user_id != auth_user.id
+        && !admin_override(
+            &auth_user,
+            &admin,
+            RolePermission::AdminFilesWrite,
+            "set_file_legal_hold",
+            file_id,
+        )?
+    {
+        return Err(AppError::NotFound("File not found".to_string()));
+    }
+
+    sqlx::query("UPDATE files SET legal_hold = $1 WHERE id = $2")
+        .bind(req.legal_hold)
+        .bind(file_id)
+        .execute(&state.pool)
+        .await?;
+    tracing::info!(
+        target: "audit",
+        user_id = %auth_user.id,
+        %file_id,
+        legal_hold = req.legal_hold,
+        "File legal hold changed"
+    );
+
+    Ok(Json(load_file_metadata(&state.pool, file_id).await?))
+}
+
+/// Whether a file is held, itself or through its folder or an ancestor folder
+pub(crate) async fn under_legal_hold(pool: &PgPool, file_id: Uuid) -> Result<bool> {
+    let held = sqlx::query_scalar(
+        "SELECT legal_hold OR folder_under_legal_hold(folder_id) FROM files WHERE id = $1",
+    )
+    .bind(file_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(held.unwrap_or(false))
+}
+
+async fn is_append_only(pool: &PgPool, file_id: Uuid) -> Result<bool> {
+    let append_only = sqlx::query_scalar("SELECT append_only FROM files WHERE id = $1")
+        .bind(file_id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(append_only.unwrap_or(false))
+}
+
+/// Listing entry of a single file
+pub(crate) async fn load_file_metadata(pool: &PgPool, file_id: Uuid) -> Result<FileMetadata> {
+    sqlx::query_as::<_, FileMetadata>(
+        r#"
+        SELECT
+            f.id,
+            f.project_id,
+            f.folder_id,
+            fol.path as folder_path,
+            f.original_name,
+            f.size,
+            f.mime_type,
+            f.upload_date,
+            '/api/v1/files/' || f.id::text as download_url,
+            f.download_count,
+            f.last_accessed_at,
+            f.description,
+            f.moderation_status,
+            f.moderation_reason,
+            f.legal_hold,
+            f.append_only
+        FROM files f
+        LEFT JOIN folders fol ON fol.id = f.folder_id
+        WHERE f.id = $1
+        "#,
+    )
+    .bind(file_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or(AppError::NotFound("File not found".to_string()))
+}
+
+#[derive(serde::Deserialize)]
 pub struct DeleteFolderFilesRequest {
     pub folder_path: String,
 }
@@ -443,38 +2155,26 @@ pub async fn delete_folder_files(
     let api_key_uuid = Uuid::parse_str(api_key).map_err(|_| AppError::Unauthorized)?;
 
     // Get project by API key
-    let project = sqlx::query_as::<_, Project>(
-        "SELECT id, user_id, name, api_key, is_public, created_at FROM projects WHERE api_key = $1",
-    )
+    let project = sqlx::query_as::<_, Project>(&format!(
+        "SELECT {PROJECT_COLUMNS} FROM projects WHERE api_key = $1 OR (previous_api_key = $1 AND previous_api_key_expires_at > NOW())"
+    ))
     .bind(api_key_uuid)
     .fetch_optional(&state.pool)
     .await?
     .ok_or(AppError::Unauthorized)?;
+    ensure_project_writable(&project)?;
 
-    let folder_path = &payload.folder_path;
+    let folder_path = &validate_folder_path(&payload.folder_path)?;
 
-    // Validate folder_path to prevent path traversal attacks
-    if folder_path.contains("..")
-        || folder_path.starts_with('/')
-        || folder_path.starts_with('\\')
-        || folder_path.contains("//")
-        || folder_path.contains("\\\\")
-        || folder_path.contains('\0')
-    {
-        return Err(AppError::BadRequest(
-            "Invalid folder path: path traversal not allowed".to_string(),
-        ));
-    }
-
-    // Validate characters (alphanumeric, underscore, hyphen, forward slash, dot)
-    if !folder_path
-        .chars()
-        .all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '/' || c == '.')
-    {
-        return Err(AppError::BadRequest(
-            "Invalid folder path: contains invalid characters".to_string(),
-        ));
-    }
+    // The exclusive folder lock waits for in-flight uploads, copies and moves
+    // into the folder and keeps new ones out until the delete commits
+    let mut tx = state.pool.begin().await?;
+    advisory_xact_lock(
+        &mut tx,
+        &folder_lock_key(project.id, folder_path),
+        LockMode::Exclusive,
+    )
+    .await?;
 
     // Get the folder for this project
     let folder = sqlx::query_as::<_, Folder>(
@@ -482,7 +2182,7 @@ pub async fn delete_folder_files(
     )
     .bind(project.id)
     .bind(folder_path)
-    .fetch_optional(&state.pool)
+    .fetch_optional(&mut *tx)
     .await?;
 
     let mut deleted_count = 0;
@@ -490,11 +2190,44 @@ pub async fn delete_folder_files(
     if let Some(folder) = folder {
         // Get all files in this folder
         let files = sqlx::query_as::<_, File>(
-            "SELECT id, project_id, folder_id, original_name, stored_name, file_path, size, mime_type, upload_date FROM files WHERE folder_id = $1"
+            "SELECT id, project_id, folder_id, original_name, stored_name, file_path, size, mime_type, upload_date, storage_encoding, precompressed_encodings, storage_tier, moderation_status FROM files WHERE folder_id = $1"
+        )
+        .bind(folder.id)
+        .fetch_all(&mut *tx)
+        .await?;
+        // Before the folder goes, while its visibility is known
+        queue_cdn_purge(&mut tx, state.cdn.as_deref(), &files).await?;
+
+        // Delete all files and the folder record from database
+        sqlx::query("DELETE FROM files WHERE folder_id = $1")
+            .bind(folder.id)
+            .execute(&mut *tx)
+            .await?;
+        // Subfolders keep their parent's record
+        sqlx::query(
+            r#"
+            DELETE FROM folders
+            WHERE id = $1
+              AND NOT EXISTS (
+                  SELECT 1 FROM folders
+                  WHERE project_id = $2 AND starts_with(path, $3 || '/')
+              )
+            "#,
         )
         .bind(folder.id)
-        .fetch_all(&state.pool)
+        .bind(project.id)
+        .bind(folder_path)
+        .execute(&mut *tx)
+        .await?;
+        record_file_events(
+            &mut tx,
+            state.events.as_deref(),
+            FileEventKind::Deleted,
+            &files,
+        )
         .await?;
+        queue_replication(&mut tx, state.replication.as_deref(), &files).await?;
+        tx.commit().await?;
 
         // Delete each file from disk
         for file in &files {
@@ -504,29 +2237,23 @@ pub async fn delete_folder_files(
                     tracing::warn!("Failed to delete file {}: {}", file_path.display(), e);
                 }
             }
+            remove_variants(&file.file_path).await;
+            delete_cold_blob(state.cold_storage.as_deref(), file).await;
             deleted_count += 1;
         }
 
-        // Delete all files from database
-        sqlx::query("DELETE FROM files WHERE folder_id = $1")
-            .bind(folder.id)
-            .execute(&state.pool)
-            .await?;
-
-        // Delete the folder record
-        sqlx::query("DELETE FROM folders WHERE id = $1")
-            .bind(folder.id)
-            .execute(&state.pool)
-            .await?;
-
-        // Try to remove the physical folder directory
+        // Remove the physical folder directory unless it still holds subfolders
+        // or a blob written by an upload that started after the delete
         let mut storage_path = PathBuf::from(&state.config.storage_path);
         storage_path.push(project.id.to_string());
         for segment in folder_path.split('/') {
             storage_path.push(segment);
         }
-        if storage_path.exists() {
-            if let Err(e) = fs::remove_dir_all(&storage_path).await {
+        if let Err(e) = fs::remove_dir(&storage_path).await {
+            if !matches!(
+                e.kind(),
+                std::io::ErrorKind::NotFound | std::io::ErrorKind::DirectoryNotEmpty
+            ) {
                 tracing::warn!(
                     "Failed to remove folder directory {}: {}",
                     storage_path.display(),
@@ -564,95 +2291,1005 @@ pub async fn bulk_delete_files(
         })));
     }
 
-    // Get all files first (without auth filter)
-    let all_files = sqlx::query_as::<_, File>(
-        "SELECT id, project_id, folder_id, original_name, stored_name, file_path, size, mime_type, upload_date FROM files WHERE id = ANY($1)"
+    let credentials = Credentials::resolve(&state.pool, &optional_auth, &headers, None).await?;
+    let authorized_files = authorized_files(
+        &state.pool,
+        &credentials,
+        &payload.file_ids,
+        Permission::Write,
     )
-    .bind(&payload.file_ids)
-    .fetch_all(&state.pool)
     .await?;
 
-    if all_files.is_empty() {
-        return Err(AppError::NotFound("No files found".to_string()));
+    if authorized_files.is_empty() {
+        return Err(AppError::NotFound(
+            "No files found or you don't have permission to delete them".to_string(),
+        ));
     }
 
-    // Determine which files the user is authorized to delete
-    let authorized_files: Vec<File> = if let Some(ref user) = optional_auth.0 {
-        // JWT auth - get files from projects owned by user
-        sqlx::query_as::<_, File>(
+    // Delete from database first, then the blobs of the committed deletes
+    let file_ids: Vec<Uuid> = authorized_files.iter().map(|f| f.id).collect();
+    let mut tx = state.pool.begin().await?;
+    sqlx::query("DELETE FROM files WHERE id = ANY($1)")
+        .bind(&file_ids)
+        .execute(&mut *tx)
+        .await?;
+    record_file_events(
+        &mut tx,
+        state.events.as_deref(),
+        FileEventKind::Deleted,
+        &authorized_files,
+    )
+    .await?;
+    queue_replication(&mut tx, state.replication.as_deref(), &authorized_files).await?;
+    queue_cdn_purge(&mut tx, state.cdn.as_deref(), &authorized_files).await?;
+    tx.commit().await?;
+
+    let mut deleted_count = 0;
+
+    // Delete each file from disk
+    for file in &authorized_files {
+        let file_path = PathBuf::from(&file.file_path);
+        if file_path.exists() {
+            if let Err(e) = fs::remove_file(&file_path).await {
+                tracing::warn!("Failed to delete file {}: {}", file_path.display(), e);
+            }
+        }
+        remove_variants(&file.file_path).await;
+        delete_cold_blob(state.cold_storage.as_deref(), file).await;
+        deleted_count += 1;
+    }
+
+    Ok(Json(serde_json::json!({
+        "message": "Files deleted successfully",
+        "deleted_count": deleted_count
+    })))
+}
+
+#[derive(sqlx::FromRow)]
+struct DuplicateRow {
+    content_hash: String,
+    #[sqlx(flatten)]
+    file: FileMetadata,
+}
+
+/// Report sets of files with identical content in a project
+/// Only files uploaded with content hashing are considered
+pub async fn list_duplicates(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(project_id): Path<Uuid>,
+) -> Result<Json<DuplicatesReport>> {
+    // Check if project belongs to user
+    let _project = sqlx::query_as::<_, Project>(&format!(
+        "SELECT {PROJECT_COLUMNS} FROM projects WHERE id = $1 AND user_id = $2"
+    ))
+    .bind(project_id)
+    .bind(auth_user.id)
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or(AppError::NotFound("Project not found".to_string()))?;
+
+    let rows = sqlx::query_as::<_, DuplicateRow>(
+        r#"
+        SELECT
+            f.content_hash,
+            f.id,
+            f.project_id,
+            f.folder_id,
+            fol.path as folder_path,
+            f.original_name,
+            f.size,
+            f.mime_type,
+            f.upload_date,
+            '/api/v1/files/' || f.id::text as download_url,
+            f.download_count,
+            f.last_accessed_at,
+            f.description,
+            f.moderation_status,
+            f.moderation_reason,
+            f.legal_hold,
+            f.append_only
+        FROM files f
+        LEFT JOIN folders fol ON fol.id = f.folder_id
+        WHERE f.project_id = $1
+          AND f.content_hash IN (
+              SELECT content_hash
+              FROM files
+              WHERE project_id = $1 AND content_hash IS NOT NULL
+              GROUP BY content_hash
+              HAVING COUNT(*) > 1
+          )
+        ORDER BY f.size DESC, f.content_hash, f.upload_date
+        "#,
+    )
+    .bind(project_id)
+    .fetch_all(state.read_pool.get())
+    .await?;
+
+    let mut groups: Vec<DuplicateGroup> = Vec::new();
+    for DuplicateRow { content_hash, file } in rows {
+        let size = file.size;
+        match groups.last_mut() {
+            Some(group) if group.content_hash == content_hash => {
+                group.file_count += 1;
+                group.wasted_bytes += size;
+                group.files.push(file);
+            }
+            _ => groups.push(DuplicateGroup {
+                content_hash,
+                size,
+                file_count: 1,
+                wasted_bytes: 0,
+                files: vec![file],
+            }),
+        }
+    }
+
+    let total_wasted_bytes = groups.iter().map(|g| g.wasted_bytes).sum();
+
+    Ok(Json(DuplicatesReport {
+        groups,
+        total_wasted_bytes,
+    }))
+}
+
+/// Delete redundant copies in the selected duplicate groups, keeping one file per group
+pub async fn deduplicate_files(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(project_id): Path<Uuid>,
+    Json(payload): Json<DeduplicateRequest>,
+) -> Result<Json<serde_json::Value>> {
+    // Check if project belongs to user
+    let project = sqlx::query_as::<_, Project>(&format!(
+        "SELECT {PROJECT_COLUMNS} FROM projects WHERE id = $1 AND user_id = $2"
+    ))
+    .bind(project_id)
+    .bind(auth_user.id)
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or(AppError::NotFound("Project not found".to_string()))?;
+    ensure_project_writable(&project)?;
+
+    let keep_order = match payload.keep.as_deref().unwrap_or("oldest") {
+        "oldest" => "ASC",
+        "newest" => "DESC",
+        other => {
+            return Err(AppError::BadRequest(format!(
+                "Invalid keep value '{other}': expected oldest or newest"
+            )))
+        }
+    };
+
+    if payload.content_hashes.is_empty() {
+        return Ok(Json(serde_json::json!({
+            "message": "No duplicate groups selected",
+            "deleted_count": 0,
+            "freed_bytes": 0
+        })));
+    }
+
+    // Serialize dedup runs per project: two concurrent runs keeping different
+    // copies (oldest vs newest) would otherwise delete every copy of a group
+    let mut tx = state.pool.begin().await?;
+    advisory_xact_lock(&mut tx, &format!("dedup:{project_id}"), LockMode::Exclusive).await?;
+
+    // Everything except the first file (by upload date) in each selected group
+    let redundant = sqlx::query_as::<_, File>(&format!(
+        r#"
+        SELECT id, project_id, folder_id, original_name, stored_name, file_path, size, mime_type, upload_date, storage_encoding, precompressed_encodings, storage_tier, moderation_status
+        FROM (
+            SELECT *, ROW_NUMBER() OVER (PARTITION BY content_hash ORDER BY upload_date {keep_order}, id) AS rn
+            FROM files
+            WHERE project_id = $1 AND content_hash = ANY($2)
+        ) ranked
+        WHERE rn > 1
+        "#
+    ))
+    .bind(project_id)
+    .bind(&payload.content_hashes)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let file_ids: Vec<Uuid> = redundant.iter().map(|f| f.id).collect();
+    sqlx::query("DELETE FROM files WHERE id = ANY($1)")
+        .bind(&file_ids)
+        .execute(&mut *tx)
+        .await?;
+    record_file_events(
+        &mut tx,
+        state.events.as_deref(),
+        FileEventKind::Deleted,
+        &redundant,
+    )
+    .await?;
+    queue_replication(&mut tx, state.replication.as_deref(), &redundant).await?;
+    queue_cdn_purge(&mut tx, state.cdn.as_deref(), &redundant).await?;
+    tx.commit().await?;
+
+    let mut freed_bytes = 0;
+    for file in &redundant {
+        let file_path = PathBuf::from(&file.file_path);
+        if file_path.exists() {
+            if let Err(e) = fs::remove_file(&file_path).await {
+                tracing::warn!("Failed to delete file {}: {}", file_path.display(), e);
+            }
+        }
+        remove_variants(&file.file_path).await;
+        delete_cold_blob(state.cold_storage.as_deref(), file).await;
+        freed_bytes += file.size;
+    }
+
+    Ok(Json(serde_json::json!({
+        "message": "Duplicates removed successfully",
+        "deleted_count": file_ids.len(),
+        "freed_bytes": freed_bytes
+    })))
+}
+
+/// Report how much disk space at-rest compression saves for a project
+pub async fn compression_stats(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(project_id): Path<Uuid>,
+) -> Result<Json<CompressionStats>> {
+    // Check if project belongs to user
+    let _project = sqlx::query_as::<_, Project>(&format!(
+        "SELECT {PROJECT_COLUMNS} FROM projects WHERE id = $1 AND user_id = $2"
+    ))
+    .bind(project_id)
+    .bind(auth_user.id)
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or(AppError::NotFound("Project not found".to_string()))?;
+
+    // Files without a recorded stored_size predate compression and are stored as-is
+    let (file_count, compressed_files, total_size, stored_size) =
+        sqlx::query_as::<_, (i64, i64, i64, i64)>(
             r#"
-            SELECT f.id, f.project_id, f.folder_id, f.original_name, f.stored_name, f.file_path, f.size, f.mime_type, f.upload_date
-            FROM files f
-            JOIN projects p ON f.project_id = p.id
-            WHERE f.id = ANY($1) AND p.user_id = $2
+            SELECT
+                COUNT(*),
+                COUNT(storage_encoding),
+                COALESCE(SUM(size), 0)::BIGINT,
+                COALESCE(SUM(COALESCE(stored_size, size)), 0)::BIGINT
+            FROM files
+            WHERE project_id = $1
             "#,
         )
-        .bind(&payload.file_ids)
-        .bind(user.id)
-        .fetch_all(&state.pool)
-        .await?
+        .bind(project_id)
+        .fetch_one(state.read_pool.get())
+        .await?;
+
+    let compression_ratio = if total_size > 0 {
+        stored_size as f64 / total_size as f64
     } else {
-        // Try API key auth
-        let api_key = headers
-            .get("X-API-Key")
-            .and_then(|h| h.to_str().ok())
-            .ok_or(AppError::Unauthorized)?;
+        1.0
+    };
 
-        let api_key_uuid = Uuid::parse_str(api_key).map_err(|_| AppError::Unauthorized)?;
+    Ok(Json(CompressionStats {
+        file_count,
+        compressed_files,
+        total_size,
+        stored_size,
+        compression_ratio,
+    }))
+}
 
-        // Get project by API key
-        let project = sqlx::query_as::<_, Project>(
-            "SELECT id, user_id, name, api_key, is_public, created_at FROM projects WHERE api_key = $1"
-        )
-        .bind(api_key_uuid)
-        .fetch_optional(&state.pool)
-        .await?
-        .ok_or(AppError::Unauthorized)?;
+#[derive(serde::Deserialize)]
+pub struct ArchiveRequest {
+    pub file_ids: Vec<Uuid>,
+}
 
-        // For API key auth, all files must belong to this project
-        let files_in_project: Vec<File> = all_files
-            .into_iter()
-            .filter(|f| f.project_id == project.id)
-            .collect();
+/// Enforce project country restrictions on a read (only when a GeoIP database
+/// is configured)
+pub(crate) fn ensure_geo_allowed(
+    state: &AppState,
+    project: &Project,
+    client_ip: IpAddr,
+) -> Result<()> {
+    if let Some(ref geoip) = state.geoip {
+        if !project.geo_allowed_countries.is_empty() || !project.geo_blocked_countries.is_empty() {
+            let country = lookup_country(geoip, client_ip);
+            check_geo_access(
+                country.as_deref(),
+                &project.geo_allowed_countries,
+                &project.geo_blocked_countries,
+            )?;
+        }
+    }
+    Ok(())
+}
 
-        // Check if all requested files belong to this project
-        if files_in_project.len() != payload.file_ids.len() {
-            return Err(AppError::BadRequest(
-                "With API key auth, all files must belong to the same project".to_string(),
-            ));
+/// Stream a ZIP containing the requested files, preserving their folder structure
+/// Uses the same read rules as single-file downloads:
+/// - JWT: only files the user may read are included
+/// - API Key / anonymous: every requested file must be readable
+///
+/// Country restrictions of every project involved apply, and the archive is
+/// streamed at the lowest bandwidth limit among them.
+pub async fn download_archive(
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    optional_auth: OptionalAuthUser,
+    headers: HeaderMap,
+    Json(mut payload): Json<ArchiveRequest>,
+) -> Result<Response> {
+    payload.file_ids.sort();
+    payload.file_ids.dedup();
+
+    if payload.file_ids.is_empty() {
+        return Err(AppError::BadRequest("No files requested".to_string()));
+    }
+
+    let credentials = Credentials::resolve(&state.pool, &optional_auth, &headers, None).await?;
+    let allowed = authorized_files(
+        &state.pool,
+        &credentials,
+        &payload.file_ids,
+        Permission::Read,
+    )
+    .await?;
+
+    let mut project_ids: Vec<Uuid> = allowed.iter().map(|f| f.project_id).collect();
+    project_ids.sort();
+    project_ids.dedup();
+    let projects = sqlx::query_as::<_, Project>(&format!(
+        "SELECT {PROJECT_COLUMNS} FROM projects WHERE id = ANY($1)"
+    ))
+    .bind(&project_ids)
+    .fetch_all(&state.pool)
+    .await?;
+    for project in &projects {
+        ensure_geo_allowed(&state, project, client_ip)?;
+    }
+    let bandwidth_limit = projects
+        .iter()
+        .filter_map(|p| p.download_bandwidth_limit)
+        .min();
+
+    for file in &allowed {
+        ensure_hot(&state.pool, state.cold_storage.as_deref(), file).await?;
+    }
+    let allowed_ids: Vec<Uuid> = allowed.iter().map(|f| f.id).collect();
+
+    // Files with their folder paths, in archive order
+    let files = sqlx::query_as::<_, (String, String, Option<String>, Option<String>)>(
+        r#"
+        SELECT f.file_path, f.original_name, fol.path, f.storage_encoding
+        FROM files f
+        LEFT JOIN folders fol ON fol.id = f.folder_id
+        WHERE f.id = ANY($1)
+        ORDER BY fol.path NULLS FIRST, f.original_name
+        "#,
+    )
+    .bind(&allowed_ids)
+    .fetch_all(&state.pool)
+    .await?;
+
+    if files.is_empty() {
+        return Err(AppError::NotFound(
+            "No files found or you don't have permission to download them".to_string(),
+        ));
+    }
+
+    let entries = archive_entries(files);
+
+    let (writer, reader) = tokio::io::duplex(64 * 1024);
+    tokio::spawn(async move {
+        if let Err(e) = write_zip_stream(entries, writer).await {
+            tracing::warn!("Failed to stream archive: {}", e);
         }
+    });
 
-        files_in_project
+    let body = match bandwidth_limit {
+        Some(limit) => {
+            let chunk_size = throttled_chunk_size(limit as u64);
+            let chunks = tokio_util::io::ReaderStream::with_capacity(reader, chunk_size);
+            Body::from_stream(throttled_stream(chunks, limit as u64))
+        }
+        None => Body::from_stream(tokio_util::io::ReaderStream::new(reader)),
     };
 
-    if authorized_files.is_empty() {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/zip")
+        .header(
+            header::CONTENT_DISPOSITION,
+            "attachment; filename=\"files.zip\"",
+        )
+        .body(body)
+        .map_err(|e| AppError::InternalError(format!("Failed to build response: {e}")))
+}
+
+/// Resolve the target folder for a bulk move/copy within a project
+async fn bulk_target_folder(
+    state: &AppState,
+    project_id: Uuid,
+    folder_path: Option<&str>,
+) -> Result<(Option<Uuid>, PathBuf)> {
+    let mut dir = PathBuf::from(&state.config.storage_path);
+    dir.push(project_id.to_string());
+
+    let folder_id = match folder_path {
+        Some(path) => {
+            for segment in path.split('/') {
+                dir.push(segment);
+            }
+            Some(get_or_create_folder(&mut *state.pool.acquire().await?, project_id, path).await?)
+        }
+        None => None,
+    };
+
+    fs::create_dir_all(&dir)
+        .await
+        .map_err(|e| AppError::FileError(format!("Failed to create directory: {e}")))?;
+
+    Ok((folder_id, dir))
+}
+
+#[derive(serde::Deserialize)]
+pub struct BulkTransferRequest {
+    pub file_ids: Vec<Uuid>,
+    /// Destination folder; omit or leave empty for the project root
+    pub folder_path: Option<String>,
+}
+
+/// Move multiple files into a folder of their own project
+/// Supports both JWT and API key authentication (same rules as bulk delete)
+pub async fn bulk_move_files(
+    State(state): State<AppState>,
+    optional_auth: OptionalAuthUser,
+    headers: HeaderMap,
+    Json(payload): Json<BulkTransferRequest>,
+) -> Result<Json<serde_json::Value>> {
+    let folder_path = payload
+        .folder_path
+        .filter(|p| !p.is_empty())
+        .map(|p| validate_folder_path(&p))
+        .transpose()?;
+    if payload.file_ids.is_empty() {
+        return Ok(Json(serde_json::json!({
+            "message": "No files to move",
+            "moved_count": 0
+        })));
+    }
+
+    let credentials = Credentials::resolve(&state.pool, &optional_auth, &headers, None).await?;
+    let files = authorized_files(
+        &state.pool,
+        &credentials,
+        &payload.file_ids,
+        Permission::Write,
+    )
+    .await?;
+    if files.is_empty() {
         return Err(AppError::NotFound(
-            "No files found or you don't have permission to delete them".to_string(),
+            "No files found or you don't have permission to modify them".to_string(),
         ));
     }
 
-    let mut deleted_count = 0;
+    // Held files can't be moved out from under their hold; refused before
+    // any blob is renamed
+    for file in &files {
+        if under_legal_hold(&state.pool, file.id).await? {
+            return Err(AppError::LegalHold(format!(
+                "File \"{}\" is under legal hold",
+                file.original_name
+            )));
+        }
+    }
 
-    // Delete each file from disk
-    for file in &authorized_files {
-        let file_path = PathBuf::from(&file.file_path);
-        if file_path.exists() {
-            if let Err(e) = fs::remove_file(&file_path).await {
-                tracing::warn!("Failed to delete file {}: {}", file_path.display(), e);
+    let mut targets: HashMap<Uuid, (Option<Uuid>, PathBuf)> = HashMap::new();
+    let mut moved_count = 0;
+
+    for file in &files {
+        let (folder_id, dir) = match targets.get(&file.project_id) {
+            Some(target) => target.clone(),
+            None => {
+                let target =
+                    bulk_target_folder(&state, file.project_id, folder_path.as_deref()).await?;
+                targets.insert(file.project_id, target.clone());
+                target
             }
+        };
+
+        if file.folder_id == folder_id {
+            continue;
         }
-        deleted_count += 1;
+
+        let mut tx = state.pool.begin().await?;
+        let folder_id = match folder_path {
+            Some(ref path) => Some(lock_folder(&mut tx, file.project_id, path).await?),
+            None => None,
+        };
+
+        // Cold blobs are keyed by file ID; they're restored to the new path on access
+        let new_path = dir.join(&file.stored_name);
+        let renamed = file.storage_tier != COLD_TIER;
+        if renamed {
+            fs::rename(&file.file_path, &new_path)
+                .await
+                .map_err(|e| AppError::FileError(format!("Failed to move file: {e}")))?;
+        }
+
+        // Variants are regenerated at the new path on the next public download
+        let result: Result<()> = async {
+            sqlx::query(
+                "UPDATE files SET folder_id = $1, file_path = $2, precompressed_encodings = '{}' WHERE id = $3",
+            )
+            .bind(folder_id)
+            .bind(new_path.to_str().unwrap())
+            .bind(file.id)
+            .execute(&mut *tx)
+            .await?;
+            queue_replication(&mut tx, state.replication.as_deref(), [file]).await?;
+            queue_cdn_purge(&mut tx, state.cdn.as_deref(), [file]).await?;
+            tx.commit().await?;
+            Ok(())
+        }
+        .await;
+
+        // The record still points at the old path; put the blob back there
+        if let Err(e) = result {
+            if renamed {
+                if let Err(err) = fs::rename(&new_path, &file.file_path).await {
+                    tracing::error!(
+                        "Failed to move {} back to {}: {}",
+                        new_path.display(),
+                        file.file_path,
+                        err
+                    );
+                }
+            }
+            return Err(e);
+        }
+        remove_variants(&file.file_path).await;
+
+        moved_count += 1;
     }
 
-    // Delete from database
-    let file_ids: Vec<Uuid> = authorized_files.iter().map(|f| f.id).collect();
-    sqlx::query("DELETE FROM files WHERE id = ANY($1)")
-        .bind(&file_ids)
-        .execute(&state.pool)
+    Ok(Json(serde_json::json!({
+        "message": "Files moved successfully",
+        "moved_count": moved_count
+    })))
+}
+
+/// Copy multiple files into a folder of their own project
+/// Supports both JWT and API key authentication (same rules as bulk delete)
+pub async fn bulk_copy_files(
+    State(state): State<AppState>,
+    optional_auth: OptionalAuthUser,
+    headers: HeaderMap,
+    Json(payload): Json<BulkTransferRequest>,
+) -> Result<Json<serde_json::Value>> {
+    let folder_path = payload
+        .folder_path
+        .filter(|p| !p.is_empty())
+        .map(|p| validate_folder_path(&p))
+        .transpose()?;
+    if payload.file_ids.is_empty() {
+        return Ok(Json(serde_json::json!({
+            "message": "No files to copy",
+            "file_ids": []
+        })));
+    }
+
+    let credentials = Credentials::resolve(&state.pool, &optional_auth, &headers, None).await?;
+    let files = authorized_files(
+        &state.pool,
+        &credentials,
+        &payload.file_ids,
+        Permission::Upload,
+    )
+    .await?;
+    if files.is_empty() {
+        return Err(AppError::NotFound(
+            "No files found or you don't have permission to modify them".to_string(),
+        ));
+    }
+
+    // Copies count against each project's storage quota
+    let mut incoming: HashMap<Uuid, i64> = HashMap::new();
+    for file in &files {
+        *incoming.entry(file.project_id).or_default() += file.size;
+    }
+    for (&project_id, &bytes) in &incoming {
+        check_quota(&state.pool, project_id, bytes).await?;
+    }
+
+    let mut targets: HashMap<Uuid, (Option<Uuid>, PathBuf)> = HashMap::new();
+    let mut new_ids = Vec::with_capacity(files.len());
+
+    for file in &files {
+        let (_, dir) = match targets.get(&file.project_id) {
+            Some(target) => target.clone(),
+            None => {
+                let target =
+                    bulk_target_folder(&state, file.project_id, folder_path.as_deref()).await?;
+                targets.insert(file.project_id, target.clone());
+                target
+            }
+        };
+
+        let new_id = Uuid::new_v4();
+        let stored_name = match file.stored_name.split_once('.') {
+            Some((_, ext)) => format!("{new_id}.{ext}"),
+            None => new_id.to_string(),
+        };
+        let new_path = dir.join(&stored_name);
+        ensure_hot(&state.pool, state.cold_storage.as_deref(), file).await?;
+        fs::copy(&file.file_path, &new_path)
+            .await
+            .map_err(|e| AppError::FileError(format!("Failed to copy file: {e}")))?;
+
+        let mut tx = state.pool.begin().await?;
+        let folder_id = match folder_path {
+            Some(ref path) => Some(lock_folder(&mut tx, file.project_id, path).await?),
+            None => None,
+        };
+        let copy = sqlx::query_as::<_, File>(
+            r#"
+            INSERT INTO files (id, project_id, folder_id, original_name, stored_name, file_path, size, mime_type, content_hash, storage_encoding, stored_size, description, moderation_status, moderation_reason)
+            SELECT $1, project_id, $2, original_name, $3, $4, size, mime_type, content_hash, storage_encoding, stored_size, description, moderation_status, moderation_reason
+            FROM files WHERE id = $5
+            RETURNING id, project_id, folder_id, original_name, stored_name, file_path, size, mime_type, upload_date, storage_encoding, precompressed_encodings, storage_tier, moderation_status
+            "#,
+        )
+        .bind(new_id)
+        .bind(folder_id)
+        .bind(&stored_name)
+        .bind(new_path.to_str().unwrap())
+        .bind(file.id)
+        .fetch_one(&mut *tx)
         .await?;
+        // Copies share the original's verdict; one still under review is checked on its own
+        if copy.moderation_status == ModerationStatus::PendingReview {
+            queue_moderation(&mut tx, copy.id).await?;
+        }
+        queue_media_preview(&mut tx, &state.config, &copy).await?;
+        record_file_events(
+            &mut tx,
+            state.events.as_deref(),
+            FileEventKind::Uploaded,
+            [&copy],
+        )
+        .await?;
+        queue_replication(&mut tx, state.replication.as_deref(), [&copy]).await?;
+        tx.commit().await?;
+
+        new_ids.push(new_id);
+    }
+    for &project_id in incoming.keys() {
+        spawn_quota_warnings(state.pool.clone(), state.mailer.clone(), project_id);
+    }
 
     Ok(Json(serde_json::json!({
-        "message": "Files deleted successfully",
-        "deleted_count": deleted_count
+        "message": "Files copied successfully",
+        "copied_count": new_ids.len(),
+        "file_ids": new_ids
     })))
 }
+
+/// Copy a file into another project owned by the same user, with its name,
+/// description and moderation verdict, e.g. to promote a build from a staging
+/// project to production. API keys of the source project may copy into any of
+/// the owner's projects; users need read access to the file and upload access
+/// to the target.
+pub async fn copy_file(
+    State(state): State<AppState>,
+    optional_auth: OptionalAuthUser,
+    headers: HeaderMap,
+    Path(file_id): Path<Uuid>,
+    Json(payload): Json<CopyFileRequest>,
+) -> Result<Json<FileMetadata>> {
+    let folder_path = payload
+        .folder_path
+        .filter(|p| !p.is_empty())
+        .map(|p| validate_folder_path(&p))
+        .transpose()?;
+    let on_conflict = payload
+        .on_conflict
+        .map(|s| s.parse::<ConflictStrategy>())
+        .transpose()
+        .map_err(AppError::BadRequest)?;
+    if on_conflict == Some(ConflictStrategy::Overwrite) {
+        return Err(AppError::BadRequest(
+            "on_conflict=overwrite is not supported for copies".to_string(),
+        ));
+    }
+
+    let (file, project, folder) = load_file_scope(&state.pool, file_id).await?;
+    let target = sqlx::query_as::<_, Project>(&format!(
+        "SELECT {PROJECT_COLUMNS} FROM projects WHERE id = $1"
+    ))
+    .bind(payload.target_project_id)
+    .fetch_optional(&state.pool)
+    .await?
+    .filter(|target| target.user_id == project.user_id)
+    .ok_or(AppError::NotFound("Target project not found".to_string()))?;
+
+    let credentials = Credentials::resolve(&state.pool, &optional_auth, &headers, None).await?;
+    let allowed = match credentials {
+        Credentials::ApiKey(_) => can_write(&project, &credentials),
+        _ => {
+            can_read(&project, folder.as_ref(), &credentials)
+                && (file.moderation_status == ModerationStatus::Approved
+                    || can_write(&project, &credentials))
+                && can_upload(&target, &credentials)
+        }
+    };
+    if !allowed {
+        return Err(AppError::Unauthorized);
+    }
+    ensure_project_writable(&target)?;
+    check_quota(&state.pool, target.id, file.size).await?;
+
+    let (folder_id, dir) = bulk_target_folder(&state, target.id, folder_path.as_deref()).await?;
+
+    let mut original_name = file.original_name.clone();
+    if let Some(strategy) = on_conflict {
+        let existing: Option<Uuid> = sqlx::query_scalar(
+            "SELECT id FROM files WHERE project_id = $1 AND folder_id IS NOT DISTINCT FROM $2 AND original_name = $3 ORDER BY upload_date DESC LIMIT 1",
+        )
+        .bind(target.id)
+        .bind(folder_id)
+        .bind(&original_name)
+        .fetch_optional(&state.pool)
+        .await?;
+        if let Some(existing) = existing {
+            match strategy {
+                ConflictStrategy::Error => {
+                    return Err(AppError::Conflict(format!(
+                        "A file named '{original_name}' already exists in this folder"
+                    )));
+                }
+                ConflictStrategy::Skip => {
+                    return Ok(Json(load_file_metadata(&state.pool, existing).await?));
+                }
+                _ => {
+                    original_name =
+                        unique_file_name(&state.pool, target.id, folder_id, &original_name).await?;
+                }
+            }
+        }
+    }
+
+    let new_id = Uuid::new_v4();
+    let stored_name = match file.stored_name.split_once('.') {
+        Some((_, ext)) => format!("{new_id}.{ext}"),
+        None => new_id.to_string(),
+    };
+    let new_path = dir.join(&stored_name);
+    ensure_hot(&state.pool, state.cold_storage.as_deref(), &file).await?;
+    fs::copy(&file.file_path, &new_path)
+        .await
+        .map_err(|e| AppError::FileError(format!("Failed to copy file: {e}")))?;
+
+    let mut tx = state.pool.begin().await?;
+    let folder_id = match folder_path {
+        Some(ref path) => Some(lock_folder(&mut tx, target.id, path).await?),
+        None => None,
+    };
+    let copy = sqlx::query_as::<_, File>(
+        r#"
+        INSERT INTO files (id, project_id, folder_id, original_name, stored_name, file_path, size, mime_type, content_hash, storage_encoding, stored_size, description, moderation_status, moderation_reason)
+        SELECT $1, $2, $3, $4, $5, $6, size, mime_type, content_hash, storage_encoding, stored_size, description, moderation_status, moderation_reason
+        FROM files WHERE id = $7
+        RETURNING id, project_id, folder_id, original_name, stored_name, file_path, size, mime_type, upload_date, storage_encoding, precompressed_encodings, storage_tier, moderation_status
+        "#,
+    )
+    .bind(new_id)
+    .bind(target.id)
+    .bind(folder_id)
+    .bind(&original_name)
+    .bind(&stored_name)
+    .bind(new_path.to_str().unwrap())
+    .bind(file.id)
+    .fetch_one(&mut *tx)
+    .await?;
+    if copy.moderation_status == ModerationStatus::PendingReview {
+        queue_moderation(&mut tx, copy.id).await?;
+    }
+    queue_media_preview(&mut tx, &state.config, &copy).await?;
+    record_file_events(
+        &mut tx,
+        state.events.as_deref(),
+        FileEventKind::Uploaded,
+        [&copy],
+    )
+    .await?;
+    queue_replication(&mut tx, state.replication.as_deref(), [&copy]).await?;
+    tx.commit().await?;
+    spawn_quota_warnings(state.pool.clone(), state.mailer.clone(), target.id);
+
+    Ok(Json(load_file_metadata(&state.pool, new_id).await?))
+}
+
+/// Hold an uploaded or replaced file for moderation when a moderation service is
+/// configured and `MODERATION_CONTENT_TYPES` covers its type. Returns the file's
+/// resulting status.
+async fn moderate_upload(
+    conn: &mut PgConnection,
+    state: &AppState,
+    file: &File,
+    replaced: bool,
+) -> Result<ModerationStatus> {
+    if state.moderator.is_some()
+        && content_type_allowed(&file.mime_type, &state.config.moderation_content_types)
+    {
+        queue_moderation(conn, file.id).await?;
+        Ok(ModerationStatus::PendingReview)
+    } else if replaced {
+        clear_moderation(conn, file.id).await?;
+        Ok(ModerationStatus::Approved)
+    } else {
+        Ok(file.moderation_status)
+    }
+}
+
+/// Match a MIME type against policy patterns (`image/png`, `image/*`); empty allows all
+fn content_type_allowed(mime_type: &str, patterns: &[String]) -> bool {
+    patterns.is_empty()
+        || patterns
+            .iter()
+            .any(|pattern| match pattern.strip_suffix("/*") {
+                Some(prefix) => mime_type
+                    .split_once('/')
+                    .is_some_and(|(kind, _)| kind.eq_ignore_ascii_case(prefix)),
+                None => pattern.eq_ignore_ascii_case(mime_type),
+            })
+}
+
+/// Cookie carrying a folder access grant for a project; one per project, so a
+/// new grant replaces the last
+fn folder_access_cookie_name(project_id: Uuid) -> String {
+    format!("fr_access_{}", project_id.simple())
+}
+
+/// Whether the request carries a valid folder access cookie for the project
+/// whose scope covers `folder`
+fn folder_cookie_grants_read(
+    headers: &HeaderMap,
+    secret: &str,
+    project: &Project,
+    folder: Option<&Folder>,
+) -> bool {
+    let name = folder_access_cookie_name(project.id);
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .filter(|(key, _)| *key == name)
+        .filter_map(|(_, value)| verify_folder_access_token(value, secret).ok())
+        .any(|claims| {
+            claims.sub == project.id.to_string()
+                && match claims.folder_path {
+                    None => true,
+                    Some(scope) => folder.is_some_and(|f| {
+                        f.path
+                            .strip_prefix(&scope)
+                            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+                    }),
+                }
+        })
+}
+
+/// Issue a short-lived signed cookie that lets a browser read every file in a
+/// folder and its subfolders, so a page can embed private files without a
+/// token in each URL. The grant can't be revoked before it expires.
+/// Requires the project API key or a collaborator role
+pub async fn create_folder_access_cookie(
+    State(state): State<AppState>,
+    optional_auth: OptionalAuthUser,
+    headers: HeaderMap,
+    Path(project_id): Path<Uuid>,
+    Json(payload): Json<FolderAccessRequest>,
+) -> Result<Response> {
+    payload
+        .validate()
+        .map_err(|e| AppError::ValidationError(e.to_string()))?;
+
+    let project = sqlx::query_as::<_, Project>(&format!(
+        "SELECT {PROJECT_COLUMNS} FROM projects WHERE id = $1"
+    ))
+    .bind(project_id)
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or(AppError::NotFound("Project not found".to_string()))?;
+    let credentials = Credentials::resolve(&state.pool, &optional_auth, &headers, None).await?;
+    if !can_list(&project, &credentials) {
+        return Err(AppError::Unauthorized);
+    }
+
+    let folder_path = payload
+        .folder_path
+        .filter(|p| !p.is_empty())
+        .map(|p| validate_folder_path(&p))
+        .transpose()?;
+    let expires_in = payload.expires_in.unwrap_or(3600);
+    let (token, expires_at) = create_folder_access_token(
+        project.id,
+        folder_path.clone(),
+        &state.config.jwt_secret,
+        expires_in,
+    )?;
+
+    // Pages embedding the files are usually on another site than the API, so
+    // the cookie is sent cross-site; it only ever grants reads of downloads
+    let cookie_name = folder_access_cookie_name(project.id);
+    let set_cookie = format!(
+        "{cookie_name}={token}; Path=/api; Max-Age={expires_in}; HttpOnly; Secure; SameSite=None"
+    );
+    Ok((
+        [(header::SET_COOKIE, set_cookie)],
+        Json(FolderAccessResponse {
+            cookie_name,
+            cookie_value: token,
+            folder_path,
+            expires_at,
+        }),
+    )
+        .into_response())
+}
+
+/// Issue a short-lived signed policy that lets a browser upload straight to
+/// `/api/upload` without the project API key
+/// Requires the owner's JWT or an uploader/admin collaborator role
+pub async fn create_upload_policy(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    headers: HeaderMap,
+    Path(project_id): Path<Uuid>,
+    Json(payload): Json<UploadPolicyRequest>,
+) -> Result<Json<UploadPolicyResponse>> {
+    payload
+        .validate()
+        .map_err(|e| AppError::ValidationError(e.to_string()))?;
+
+    let project = sqlx::query_as::<_, Project>(&format!(
+        "SELECT {PROJECT_COLUMNS} FROM projects WHERE id = $1"
+    ))
+    .bind(project_id)
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or(AppError::NotFound("Project not found".to_string()))?;
+
+    let credentials = Credentials::resolve(
+        &state.pool,
+        &OptionalAuthUser(Some(auth_user)),
+        &headers,
+        None,
+    )
+    .await?;
+    if !can_upload(&project, &credentials) {
+        return Err(AppError::NotFound("Project not found".to_string()));
+    }
+    ensure_project_writable(&project)?;
+
+    let folder_path = payload
+        .folder_path
+        .filter(|p| !p.is_empty())
+        .map(|p| validate_folder_path(&p))
+        .transpose()?;
+
+    let max_size = payload.max_size.map_or(state.config.max_file_size, |size| {
+        size.min(state.config.max_file_size)
+    });
+
+    let (policy, expires_at) = create_upload_policy_token(
+        project.id,
+        folder_path,
+        Some(max_size),
+        payload.content_types.unwrap_or_default(),
+        &state.config.jwt_secret,
+        payload.expires_in.unwrap_or(300),
+    )?;
+
+    Ok(Json(UploadPolicyResponse {
+        policy,
+        upload_url: "/api/v1/upload".to_string(),
+        expires_at,
+    }))
+}
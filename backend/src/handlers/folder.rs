@@ -3,19 +3,78 @@ use axum::{
     Json,
 };
 use serde::Deserialize;
+use sqlx::{PgConnection, PgPool};
+use std::collections::{BTreeMap, HashMap};
 use uuid::Uuid;
 use validator::Validate;
 
 use crate::{
     error::{AppError, Result},
+    handlers::file::{create_ancestor_folders, parent_folder_path},
     middleware::AuthUser,
-    models::{CreateFolderRequest, Folder, FolderResponse, Project, UpdateFolderVisibilityRequest},
+    models::{
+        CreateFolderRequest, Folder, FolderResponse, FolderTreeNode, FolderUsage,
+        FolderVisibilitySummary, LegalHoldRequest, Project, UpdateFolderVisibilityRequest,
+        PROJECT_COLUMNS,
+    },
+    utils::{
+        admin_override, ensure_project_writable, validate_folder_path, AdminQuery, PageQuery,
+        Paginated, RolePermission,
+    },
     AppState,
 };
 
 #[derive(Debug, Deserialize)]
 pub struct ListFoldersQuery {
     project_id: Uuid,
+    /// Add each folder's usage by kind of content
+    #[serde(default)]
+    breakdown: bool,
+}
+
+/// Kind of content of a file by its MIME type, as in `FolderUsage`
+const MIME_CATEGORY_SQL: &str = r#"
+    CASE
+        WHEN mime_type LIKE 'image/%' THEN 'images'
+        WHEN mime_type LIKE 'video/%' THEN 'video'
+        WHEN mime_type LIKE 'text/%'
+            OR mime_type IN ('application/pdf', 'application/rtf', 'application/epub+zip',
+                             'application/msword', 'application/vnd.ms-excel',
+                             'application/vnd.ms-powerpoint')
+            OR mime_type LIKE 'application/vnd.openxmlformats-officedocument.%'
+            OR mime_type LIKE 'application/vnd.oasis.opendocument.%'
+            THEN 'docs'
+        ELSE 'other'
+    END
+"#;
+
+/// Usage by kind of content of each of `folder_ids`, rolled up from their files
+async fn folder_usage(pool: &PgPool, folder_ids: &[Uuid]) -> Result<HashMap<Uuid, FolderUsage>> {
+    let rows = sqlx::query_as::<_, (Uuid, String, i64, i64)>(&format!(
+        r#"
+        SELECT folder_id, {MIME_CATEGORY_SQL} AS category, COUNT(*), COALESCE(SUM(size), 0)::BIGINT
+        FROM files
+        WHERE folder_id = ANY($1)
+        GROUP BY 1, 2
+        "#
+    ))
+    .bind(folder_ids)
+    .fetch_all(pool)
+    .await?;
+
+    let mut usage: HashMap<Uuid, FolderUsage> = HashMap::new();
+    for (folder_id, category, file_count, total_size) in rows {
+        let folder = usage.entry(folder_id).or_default();
+        let category = match category.as_str() {
+            "images" => &mut folder.images,
+            "video" => &mut folder.video,
+            "docs" => &mut folder.docs,
+            _ => &mut folder.other,
+        };
+        category.file_count = file_count;
+        category.total_size = total_size;
+    }
+    Ok(usage)
 }
 
 pub async fn create_folder(
@@ -26,83 +85,166 @@ pub async fn create_folder(
     payload
         .validate()
         .map_err(|e| AppError::ValidationError(e.to_string()))?;
+    let path = validate_folder_path(&payload.path)?;
 
     // Check if project belongs to user
-    let project = sqlx::query_as::<_, Project>(
-        "SELECT id, user_id, name, api_key, is_public, created_at FROM projects WHERE id = $1 AND user_id = $2"
-    )
+    let project = sqlx::query_as::<_, Project>(&format!(
+        "SELECT {PROJECT_COLUMNS} FROM projects WHERE id = $1 AND user_id = $2"
+    ))
     .bind(payload.project_id)
     .bind(auth_user.id)
     .fetch_optional(&state.pool)
     .await?
     .ok_or(AppError::NotFound("Project not found".to_string()))?;
+    ensure_project_writable(&project)?;
 
-    let is_public = payload.is_public.unwrap_or(project.is_public);
+    let mut tx = state.pool.begin().await?;
+    create_ancestor_folders(&mut tx, project.id, &path).await?;
 
+    // Without an explicit visibility the project's `default_folder_visibility` decides
     let folder = sqlx::query_as::<_, Folder>(
         r#"
         INSERT INTO folders (project_id, path, is_public)
-        VALUES ($1, $2, $3)
-        ON CONFLICT (project_id, path) DO UPDATE SET is_public = EXCLUDED.is_public
+        SELECT $1, $2, COALESCE($3, new_folder_is_public($4, $5, parent.is_public))
+        FROM (SELECT 1) AS one
+        LEFT JOIN folders parent ON parent.project_id = $1 AND parent.path = $6
+        ON CONFLICT (project_id, path) DO UPDATE SET is_public = COALESCE($3, folders.is_public)
         RETURNING id, project_id, path, is_public, created_at
         "#,
     )
-    .bind(payload.project_id)
-    .bind(&payload.path)
-    .bind(is_public)
-    .fetch_one(&state.pool)
+    .bind(project.id)
+    .bind(&path)
+    .bind(payload.is_public)
+    .bind(project.default_folder_visibility)
+    .bind(project.is_public)
+    .bind(parent_folder_path(&path))
+    .fetch_one(&mut *tx)
     .await?;
+    tx.commit().await?;
 
     Ok(Json(folder))
 }
 
+/// The project's folders by path
 pub async fn list_folders(
     State(state): State<AppState>,
     auth_user: AuthUser,
     Query(query): Query<ListFoldersQuery>,
-) -> Result<Json<Vec<FolderResponse>>> {
+    Query(page): Query<PageQuery>,
+) -> Result<Json<Paginated<FolderResponse>>> {
+    let limit = page.limit();
+    let after: Option<String> = page.after()?;
+
     // Check if project belongs to user
-    let _project = sqlx::query_as::<_, Project>(
-        "SELECT id, user_id, name, api_key, is_public, created_at FROM projects WHERE id = $1 AND user_id = $2"
+    let _project = sqlx::query_as::<_, Project>(&format!(
+        "SELECT {PROJECT_COLUMNS} FROM projects WHERE id = $1 AND user_id = $2"
+    ))
+    .bind(query.project_id)
+    .bind(auth_user.id)
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or(AppError::NotFound("Project not found".to_string()))?;
+
+    let mut folders = sqlx::query_as::<_, FolderResponse>(
+        "SELECT id, project_id, path, is_public, created_at, legal_hold, file_count, total_size FROM folders WHERE project_id = $1 AND ($2::text IS NULL OR path > $2) ORDER BY path LIMIT $3"
     )
     .bind(query.project_id)
+    .bind(&after)
+    .bind(limit + 1)
+    .fetch_all(state.read_pool.get())
+    .await?;
+    let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM folders WHERE project_id = $1")
+        .bind(query.project_id)
+        .fetch_one(state.read_pool.get())
+        .await?;
+
+    if query.breakdown {
+        let ids: Vec<Uuid> = folders.iter().map(|f| f.id).collect();
+        let mut usage = folder_usage(state.read_pool.get(), &ids).await?;
+        for folder in &mut folders {
+            folder.usage = Some(usage.remove(&folder.id).unwrap_or_default());
+        }
+    }
+
+    Ok(Json(Paginated::new(folders, limit, total, |f| {
+        f.path.clone()
+    })))
+}
+
+/// The project's folders as a tree of top-level folders and their subfolders
+pub async fn folder_tree(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Query(query): Query<ListFoldersQuery>,
+) -> Result<Json<Vec<FolderTreeNode>>> {
+    // Check if project belongs to user
+    let _project = sqlx::query_as::<_, Project>(&format!(
+        "SELECT {PROJECT_COLUMNS} FROM projects WHERE id = $1 AND user_id = $2"
+    ))
+    .bind(query.project_id)
     .bind(auth_user.id)
     .fetch_optional(&state.pool)
     .await?
     .ok_or(AppError::NotFound("Project not found".to_string()))?;
 
-    let folders = sqlx::query_as::<_, Folder>(
-        "SELECT id, project_id, path, is_public, created_at FROM folders WHERE project_id = $1 ORDER BY path"
+    let folders = sqlx::query_as::<_, FolderResponse>(
+        "SELECT id, project_id, path, is_public, created_at, legal_hold, file_count, total_size FROM folders WHERE project_id = $1",
     )
     .bind(query.project_id)
-    .fetch_all(&state.pool)
+    .fetch_all(state.read_pool.get())
     .await?;
 
-    let mut folder_responses = Vec::new();
-    for folder in folders {
-        let stats = sqlx::query_as::<_, (Option<i64>, Option<i64>)>(
-            r#"
-            SELECT COUNT(*)::bigint, COALESCE(SUM(size), 0)::bigint
-            FROM files
-            WHERE folder_id = $1
-            "#,
-        )
-        .bind(folder.id)
-        .fetch_one(&state.pool)
-        .await?;
+    let mut nodes: BTreeMap<String, FolderTreeNode> = folders
+        .into_iter()
+        .map(|folder| {
+            let name = folder
+                .path
+                .rsplit('/')
+                .next()
+                .unwrap_or(&folder.path)
+                .to_string();
+            let node = FolderTreeNode {
+                id: folder.id,
+                name,
+                path: folder.path.clone(),
+                is_public: folder.is_public,
+                created_at: folder.created_at,
+                legal_hold: folder.legal_hold,
+                file_count: folder.file_count.unwrap_or(0),
+                total_size: folder.total_size.unwrap_or(0),
+                children: Vec::new(),
+            };
+            (folder.path, node)
+        })
+        .collect();
 
-        folder_responses.push(FolderResponse {
-            id: folder.id,
-            project_id: folder.project_id,
-            path: folder.path,
-            is_public: folder.is_public,
-            created_at: folder.created_at,
-            file_count: stats.0,
-            total_size: stats.1,
-        });
+    // A path sorts after its ancestors, so walking in reverse attaches each
+    // folder only once all of its own subfolders have been attached to it.
+    // Folders whose parent has no record stay at the top level.
+    let paths: Vec<String> = nodes.keys().rev().cloned().collect();
+    for path in paths {
+        let Some(parent) = parent_folder_path(&path) else {
+            continue;
+        };
+        if nodes.contains_key(parent) {
+            let mut node = nodes.remove(&path).expect("path is a key");
+            node.children.reverse();
+            nodes
+                .get_mut(parent)
+                .expect("parent is a key")
+                .children
+                .push(node);
+        }
     }
 
-    Ok(Json(folder_responses))
+    let roots = nodes
+        .into_values()
+        .map(|mut node| {
+            node.children.reverse();
+            node
+        })
+        .collect();
+    Ok(Json(roots))
 }
 
 pub async fn update_folder_visibility(
@@ -112,7 +254,7 @@ pub async fn update_folder_visibility(
     Json(payload): Json<UpdateFolderVisibilityRequest>,
 ) -> Result<Json<Folder>> {
     // Check if folder's project belongs to user
-    let _folder = sqlx::query_as::<_, Folder>(
+    let folder = sqlx::query_as::<_, Folder>(
         r#"
         SELECT f.id, f.project_id, f.path, f.is_public, f.created_at
         FROM folders f
@@ -126,6 +268,7 @@ pub async fn update_folder_visibility(
     .await?
     .ok_or(AppError::NotFound("Folder not found".to_string()))?;
 
+    let mut tx = state.pool.begin().await?;
     let updated_folder = sqlx::query_as::<_, Folder>(
         r#"
         UPDATE folders
@@ -136,8 +279,163 @@ pub async fn update_folder_visibility(
     )
     .bind(payload.is_public)
     .bind(folder_id)
-    .fetch_one(&state.pool)
+    .fetch_one(&mut *tx)
     .await?;
 
+    if payload.recursive {
+        sqlx::query(
+            "UPDATE folders SET is_public = $1 WHERE project_id = $2 AND starts_with(path, $3 || '/')",
+        )
+        .bind(payload.is_public)
+        .bind(folder.project_id)
+        .bind(&folder.path)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+
     Ok(Json(updated_folder))
 }
+
+/// Set `is_public` on every folder of a project, counting the folders that
+/// changed and the files directly in them
+pub(crate) async fn set_project_folders_visibility(
+    conn: &mut PgConnection,
+    project_id: Uuid,
+    is_public: bool,
+) -> Result<FolderVisibilitySummary> {
+    let (folders_updated, files_affected): (i64, i64) = sqlx::query_as(
+        r#"
+        WITH updated AS (
+            UPDATE folders
+            SET is_public = $1
+            WHERE project_id = $2 AND is_public <> $1
+            RETURNING file_count
+        )
+        SELECT COUNT(*), COALESCE(SUM(file_count), 0)::BIGINT FROM updated
+        "#,
+    )
+    .bind(is_public)
+    .bind(project_id)
+    .fetch_one(conn)
+    .await?;
+
+    Ok(FolderVisibilitySummary {
+        is_public,
+        folders_updated,
+        files_affected,
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ProjectFolderVisibilityRequest {
+    pub is_public: bool,
+}
+
+/// Make every folder in a project public or private at once (project owner only)
+pub async fn set_project_folder_visibility(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(project_id): Path<Uuid>,
+    Json(payload): Json<ProjectFolderVisibilityRequest>,
+) -> Result<Json<FolderVisibilitySummary>> {
+    let owned: Option<Uuid> =
+        sqlx::query_scalar("SELECT id FROM projects WHERE id = $1 AND user_id = $2")
+            .bind(project_id)
+            .bind(auth_user.id)
+            .fetch_optional(&state.pool)
+            .await?;
+    if owned.is_none() {
+        return Err(AppError::NotFound("Project not found".to_string()));
+    }
+
+    let mut conn = state.pool.acquire().await?;
+    let summary = set_project_folders_visibility(&mut conn, project_id, payload.is_public).await?;
+    Ok(Json(summary))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BulkFolderVisibilityRequest {
+    pub folder_ids: Vec<Uuid>,
+    pub is_public: bool,
+}
+
+/// Change visibility of several folders at once (only folders in the user's projects)
+pub async fn bulk_update_folder_visibility(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Json(payload): Json<BulkFolderVisibilityRequest>,
+) -> Result<Json<Vec<Folder>>> {
+    let folders = sqlx::query_as::<_, Folder>(
+        r#"
+        UPDATE folders f
+        SET is_public = $1
+        FROM projects p
+        WHERE f.project_id = p.id AND f.id = ANY($2) AND p.user_id = $3
+        RETURNING f.id, f.project_id, f.path, f.is_public, f.created_at
+        "#,
+    )
+    .bind(payload.is_public)
+    .bind(&payload.folder_ids)
+    .bind(auth_user.id)
+    .fetch_all(&state.pool)
+    .await?;
+
+    if folders.is_empty() && !payload.folder_ids.is_empty() {
+        return Err(AppError::NotFound("Folders not found".to_string()));
+    }
+
+    Ok(Json(folders))
+}
+
+/// Place or release a legal hold on a folder, covering its files and subfolders
+/// (project owner, or an admin with `?as_admin=true`). Changes are written to
+/// the audit log.
+pub async fn set_folder_legal_hold(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(folder_id): Path<Uuid>,
+    Query(admin): Query<AdminQuery>,
+    Json(payload): Json<LegalHoldRequest>,
+) -> Result<Json<FolderResponse>> {
+    let owner_id: Uuid = sqlx::query_scalar(
+        "SELECT p.user_id FROM folders f JOIN projects p ON p.id = f.project_id WHERE f.id = $1",
+    )
+    .bind(folder_id)
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or(AppError::NotFound("Folder not found".to_string()))?;
+    if owner_id != auth_user.id
+        && !admin_override(
+            &auth_user,
+            &admin,
+            RolePermission::AdminFilesWrite,
+            "set_folder_legal_hold",
+            folder_id,
+        )?
+    {
+        return Err(AppError::NotFound("Folder not found".to_string()));
+    }
+
+    let folder = sqlx::query_as::<_, FolderResponse>(
+        r#"
+        UPDATE folders
+        SET legal_hold = $1
+        WHERE id = $2
+        RETURNING id, project_id, path, is_public, created_at, legal_hold, file_count, total_size
+        "#,
+    )
+    .bind(payload.legal_hold)
+    .bind(folder_id)
+    .fetch_one(&state.pool)
+    .await?;
+    tracing::info!(
+        target: "audit",
+        user_id = %auth_user.id,
+        %folder_id,
+        legal_hold = payload.legal_hold,
+        "Folder legal hold changed"
+    );
+
+    Ok(Json(folder))
+}
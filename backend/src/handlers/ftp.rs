@@ -0,0 +1,788 @@
+use std::{io, net::IpAddr, sync::Arc, time::Duration};
+
+use chrono::{DateTime, Datelike, Utc};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+    net::TcpListener,
+    sync::Semaphore,
+};
+use tokio_rustls::{
+    rustls::{
+        self,
+        pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer},
+    },
+    TlsAcceptor,
+};
+use uuid::Uuid;
+
+use crate::{
+    db::{advisory_xact_lock, LockMode},
+    error::{AppError, Result},
+    handlers::{
+        file::{
+            ensure_geo_allowed, folder_lock_key, get_or_create_folder, grow_reservation,
+            project_for_key, remove_file_record, reserve_memory, upload_permit,
+        },
+        ingest::ingest_file,
+    },
+    models::{ConflictStrategy, File, Project},
+    utils::{ensure_project_writable, read_file_content, sanitize_file_name, validate_folder_path},
+    AppState,
+};
+
+/// Seconds a session may sit idle before it is closed
+const FTP_IDLE_TIMEOUT_SECS: u64 = 300;
+
+/// Seconds to wait for the client to open a passive data connection
+const FTP_DATA_TIMEOUT_SECS: u64 = 30;
+
+/// Sessions served at once; further connections are turned away
+const MAX_FTP_SESSIONS: usize = 100;
+
+/// Failed logins before the connection is closed
+const MAX_FTP_LOGIN_FAILURES: u32 = 3;
+
+/// Longest command line accepted
+const MAX_FTP_LINE_LENGTH: u64 = 4096;
+
+/// Read size for uploads over the data connection
+const FTP_READ_BUFFER_SIZE: usize = 64 * 1024;
+
+trait Io: AsyncRead + AsyncWrite + Unpin + Send + Sync {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send + Sync> Io for T {}
+
+/// A plain or TLS connection
+type Stream = Box<dyn Io>;
+
+/// Reply to a command: code and text
+struct Reply(u16, String);
+
+impl Reply {
+    fn new(code: u16, text: impl Into<String>) -> Self {
+        Reply(code, text.into())
+    }
+}
+
+type Outcome = std::result::Result<Reply, Reply>;
+
+impl From<AppError> for Reply {
+    fn from(e: AppError) -> Self {
+        match e {
+            AppError::Unauthorized => Reply::new(530, "Not logged in"),
+            AppError::QuotaExceeded { .. } => Reply::new(552, e.to_string()),
+            AppError::TooManyRequests(..) | AppError::Overloaded(_) => {
+                Reply::new(450, e.to_string())
+            }
+            AppError::NotFound(_)
+            | AppError::BadRequest(_)
+            | AppError::ValidationError(_)
+            | AppError::InvalidUploadField { .. }
+            | AppError::Conflict(_)
+            | AppError::Forbidden(_)
+            | AppError::LegalHold(_)
+            | AppError::GeoBlocked(_)
+            | AppError::GeoNotAllowed(_)
+            | AppError::ProjectArchived
+            | AppError::ProjectPendingDeletion => Reply::new(550, e.to_string()),
+            _ => {
+                tracing::warn!("FTP command failed: {}", e);
+                Reply::new(451, "Local error in processing")
+            }
+        }
+    }
+}
+
+/// Data connection failures abort the transfer
+impl From<io::Error> for Reply {
+    fn from(e: io::Error) -> Self {
+        Reply::new(426, format!("Transfer aborted: {e}"))
+    }
+}
+
+fn tls_acceptor(cert_path: &str, key_path: &str) -> std::result::Result<TlsAcceptor, String> {
+    let certs = CertificateDer::pem_file_iter(cert_path)
+        .and_then(|certs| certs.collect::<std::result::Result<Vec<_>, _>>())
+        .map_err(|e| format!("Failed to read {cert_path}: {e}"))?;
+    let key = PrivateKeyDer::from_pem_file(key_path)
+        .map_err(|e| format!("Failed to read {key_path}: {e}"))?;
+    let config = rustls::ServerConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .map_err(|e| e.to_string())?
+    .with_no_client_auth()
+    .with_single_cert(certs, key)
+    .map_err(|e| format!("Invalid FTP TLS certificate: {e}"))?;
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// Serve the FTP gateway on `addr`: clients log in with a project ID (or
+/// slug) as the user name and its API key as the password, and see the
+/// project's folders and files. Explicit FTPS (`AUTH TLS`) is offered when
+/// a certificate is configured and required unless `FTP_REQUIRE_TLS` is off.
+pub async fn run_ftp_server(state: AppState, addr: String) {
+    let tls = match (&state.config.ftp_tls_cert, &state.config.ftp_tls_key) {
+        (Some(cert), Some(key)) => match tls_acceptor(cert, key) {
+            Ok(acceptor) => Some(acceptor),
+            Err(e) => {
+                tracing::error!("FTP gateway disabled: {}", e);
+                return;
+            }
+        },
+        _ => None,
+    };
+    if tls.is_none() && state.config.ftp_require_tls {
+        tracing::error!(
+            "FTP gateway disabled: FTP_REQUIRE_TLS is on but FTP_TLS_CERT and FTP_TLS_KEY are not set"
+        );
+        return;
+    }
+
+    let listener = match TcpListener::bind(&addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            tracing::error!("FTP gateway failed to listen on {}: {}", addr, e);
+            return;
+        }
+    };
+    tracing::info!("FTP gateway listening on {}", addr);
+
+    let sessions = Arc::new(Semaphore::new(MAX_FTP_SESSIONS));
+    loop {
+        let (mut socket, peer) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                tracing::warn!("FTP accept failed: {}", e);
+                continue;
+            }
+        };
+        let Ok(permit) = sessions.clone().try_acquire_owned() else {
+            let _ = socket
+                .write_all(b"421 Too many connections, try again later\r\n")
+                .await;
+            continue;
+        };
+        let Ok(local) = socket.local_addr() else {
+            continue;
+        };
+
+        let session = Session {
+            state: state.clone(),
+            tls: tls.clone(),
+            control: BufReader::new(Box::new(socket)),
+            local_ip: local.ip().to_canonical(),
+            peer_ip: peer.ip().to_canonical(),
+            secure: false,
+            protect_data: false,
+            user: None,
+            login: None,
+            failures: 0,
+            cwd: String::new(),
+            passive: None,
+        };
+        tokio::spawn(async move {
+            let _permit = permit;
+            if let Err(e) = session.run().await {
+                tracing::debug!("FTP session from {} ended: {}", peer, e);
+            }
+        });
+    }
+}
+
+struct Session {
+    state: AppState,
+    tls: Option<TlsAcceptor>,
+    control: BufReader<Stream>,
+    local_ip: IpAddr,
+    peer_ip: IpAddr,
+    /// The control connection is encrypted
+    secure: bool,
+    /// Data connections are encrypted (`PROT P`)
+    protect_data: bool,
+    /// Name from `USER`, waiting for `PASS`
+    user: Option<String>,
+    /// Project and API key of the login; the key is checked again for every
+    /// command, so rotating it ends the session's access
+    login: Option<(Uuid, String)>,
+    failures: u32,
+    /// Working directory, a folder path without the leading `/`
+    cwd: String,
+    /// Listener opened by `PASV`/`EPSV` for the next transfer
+    passive: Option<TcpListener>,
+}
+
+/// Split a path into its folder (`""` for the root) and last segment
+fn split_path(path: &str) -> (&str, &str) {
+    path.rsplit_once('/').unwrap_or(("", path))
+}
+
+/// Quote a path for a 257 reply, doubling any `"` in it
+fn quote_path(path: &str) -> String {
+    format!("\"/{}\"", path.replace('"', "\"\""))
+}
+
+/// A line in `ls -l` format, which is what clients parse `LIST` output as
+fn list_line(is_dir: bool, size: i64, modified: DateTime<Utc>, name: &str) -> String {
+    let date = if modified.year() == Utc::now().year() {
+        modified.format("%b %e %H:%M")
+    } else {
+        modified.format("%b %e  %Y")
+    };
+    let mode = if is_dir { "drwxr-xr-x" } else { "-rw-r--r--" };
+    format!("{mode} 1 ftp ftp {size:>12} {date} {name}\r\n")
+}
+
+impl Session {
+    async fn run(mut self) -> io::Result<()> {
+        self.reply(220, "FileRunner FTP gateway ready").await?;
+        loop {
+            let mut line = String::new();
+            let read = tokio::time::timeout(
+                Duration::from_secs(FTP_IDLE_TIMEOUT_SECS),
+                (&mut self.control)
+                    .take(MAX_FTP_LINE_LENGTH)
+                    .read_line(&mut line),
+            )
+            .await;
+            match read {
+                Ok(Ok(0)) => return Ok(()),
+                Ok(Ok(_)) if !line.ends_with('\n') => {
+                    return self.reply(500, "Command line too long").await;
+                }
+                Ok(result) => {
+                    result?;
+                }
+                Err(_) => return self.reply(421, "Idle timeout, closing connection").await,
+            }
+            let line = line.trim_end_matches(['\r', '\n']);
+            let (verb, arg) = line.split_once(' ').unwrap_or((line, ""));
+            let verb = verb.to_ascii_uppercase();
+
+            // Upgrading replaces the control connection, so it's handled here
+            if verb == "AUTH" {
+                let Some(acceptor) = self.tls.clone() else {
+                    self.reply(502, "TLS is not configured").await?;
+                    continue;
+                };
+                if self.secure {
+                    self.reply(503, "TLS is already active").await?;
+                } else if ["TLS", "TLS-C", "SSL"].contains(&arg.to_ascii_uppercase().as_str()) {
+                    self.reply(234, "Proceed with TLS negotiation").await?;
+                    let stream = acceptor.accept(self.control.into_inner()).await?;
+                    self.control = BufReader::new(Box::new(stream));
+                    self.secure = true;
+                    self.user = None;
+                    self.login = None;
+                } else {
+                    self.reply(504, format!("AUTH {arg} is not supported"))
+                        .await?;
+                }
+                continue;
+            }
+
+            if !self.command(&verb, arg).await? {
+                return Ok(());
+            }
+        }
+    }
+
+    async fn reply(&mut self, code: u16, text: impl AsRef<str>) -> io::Result<()> {
+        let text = text.as_ref().replace(['\r', '\n'], " ");
+        let stream = self.control.get_mut();
+        stream
+            .write_all(format!("{code} {text}\r\n").as_bytes())
+            .await?;
+        stream.flush().await
+    }
+
+    /// Handle one command; `false` ends the session
+    async fn command(&mut self, verb: &str, arg: &str) -> io::Result<bool> {
+        let reply = match verb {
+            "USER" => {
+                self.login = None;
+                if self.state.config.ftp_require_tls && !self.secure {
+                    Reply::new(530, "Use AUTH TLS before logging in")
+                } else {
+                    self.user = Some(arg.to_string());
+                    Reply::new(331, "Password required")
+                }
+            }
+            "PASS" => {
+                let Some(user) = self.user.take() else {
+                    self.reply(503, "Send USER first").await?;
+                    return Ok(true);
+                };
+                match self.check_login(&user, arg).await {
+                    Some(project_id) => {
+                        self.login = Some((project_id, arg.to_string()));
+                        self.cwd.clear();
+                        Reply::new(230, "Logged in")
+                    }
+                    None => {
+                        self.failures += 1;
+                        tokio::time::sleep(Duration::from_secs(1)).await;
+                        if self.failures >= MAX_FTP_LOGIN_FAILURES {
+                            self.reply(530, "Login incorrect").await?;
+                            return Ok(false);
+                        }
+                        Reply::new(530, "Login incorrect")
+                    }
+                }
+            }
+            "QUIT" => {
+                self.reply(221, "Goodbye").await?;
+                return Ok(false);
+            }
+            "PBSZ" if self.secure => Reply::new(200, "PBSZ=0"),
+            "PBSZ" => Reply::new(503, "Use AUTH TLS first"),
+            "PROT" => match arg.to_ascii_uppercase().as_str() {
+                "P" if self.secure => {
+                    self.protect_data = true;
+                    Reply::new(200, "Data connections will be protected")
+                }
+                "P" => Reply::new(503, "Use AUTH TLS first"),
+                "C" if self.state.config.ftp_require_tls => {
+                    Reply::new(534, "Data connections must be protected")
+                }
+                "C" => {
+                    self.protect_data = false;
+                    Reply::new(200, "Data connections will not be protected")
+                }
+                _ => Reply::new(504, format!("PROT {arg} is not supported")),
+            },
+            "FEAT" => {
+                let mut features = String::from("211-Features:\r\n");
+                if self.tls.is_some() {
+                    features.push_str(" AUTH TLS\r\n PBSZ\r\n PROT\r\n");
+                }
+                features.push_str(" EPSV\r\n PASV\r\n SIZE\r\n MDTM\r\n UTF8\r\n211 End\r\n");
+                let stream = self.control.get_mut();
+                stream.write_all(features.as_bytes()).await?;
+                stream.flush().await?;
+                return Ok(true);
+            }
+            "SYST" => Reply::new(215, "UNIX Type: L8"),
+            "NOOP" => Reply::new(200, "OK"),
+            "OPTS" if arg.eq_ignore_ascii_case("UTF8 ON") => Reply::new(200, "UTF8 mode on"),
+            "OPTS" => Reply::new(501, "Option not supported"),
+            // Content is always sent as stored
+            "TYPE" => match arg.to_ascii_uppercase().as_str() {
+                "I" | "L 8" | "A" | "A N" => Reply::new(200, format!("Type set to {arg}")),
+                _ => Reply::new(504, format!("Type {arg} is not supported")),
+            },
+            "MODE" if arg.eq_ignore_ascii_case("S") => Reply::new(200, "Mode set to S"),
+            "MODE" => Reply::new(504, "Only stream mode is supported"),
+            "STRU" if arg.eq_ignore_ascii_case("F") => Reply::new(200, "Structure set to F"),
+            "STRU" => Reply::new(504, "Only file structure is supported"),
+            "PORT" | "EPRT" => Reply::new(502, "Active mode is not supported; use PASV or EPSV"),
+            _ if self.login.is_none() => Reply::new(530, "Log in with USER and PASS"),
+            _ => match self.file_command(verb, arg).await {
+                Ok(reply) | Err(reply) => reply,
+            },
+        };
+        self.reply(reply.0, reply.1).await?;
+        Ok(true)
+    }
+
+    /// The project whose ID or slug is `user` if `api_key` is its key
+    async fn check_login(&self, user: &str, api_key: &str) -> Option<Uuid> {
+        let project = project_for_key(&self.state.pool, api_key).await.ok()?;
+        let matches =
+            Uuid::parse_str(user).ok() == Some(project.id) || project.slug.as_deref() == Some(user);
+        matches.then_some(project.id)
+    }
+
+    /// Commands that need a login
+    async fn file_command(&mut self, verb: &str, arg: &str) -> Outcome {
+        match verb {
+            "PWD" | "XPWD" => Ok(Reply(
+                257,
+                format!("{} is the current directory", quote_path(&self.cwd)),
+            )),
+            "CWD" | "XCWD" => self.change_dir(arg).await,
+            "CDUP" | "XCUP" => self.change_dir("..").await,
+            "PASV" => self.passive(false).await,
+            "EPSV" => self.passive(true).await,
+            "LIST" | "NLST" => self.list(arg, verb == "NLST").await,
+            "RETR" => self.retrieve(arg).await,
+            "STOR" => self.store(arg).await,
+            "DELE" => self.delete(arg).await,
+            "MKD" | "XMKD" => self.make_dir(arg).await,
+            "RMD" | "XRMD" => self.remove_dir(arg).await,
+            "SIZE" => {
+                let project = self.project().await?;
+                let file = self.file(&project, &self.resolve(arg)).await?;
+                Ok(Reply(213, file.size.to_string()))
+            }
+            "MDTM" => {
+                let project = self.project().await?;
+                let file = self.file(&project, &self.resolve(arg)).await?;
+                Ok(Reply(
+                    213,
+                    file.upload_date.format("%Y%m%d%H%M%S").to_string(),
+                ))
+            }
+            "ALLO" => Ok(Reply::new(202, "No storage allocation necessary")),
+            _ => Err(Reply(502, format!("{verb} is not implemented"))),
+        }
+    }
+
+    /// The logged-in project, looked up by its key again
+    async fn project(&self) -> Result<Project> {
+        let Some((project_id, ref api_key)) = self.login else {
+            return Err(AppError::Unauthorized);
+        };
+        let project = project_for_key(&self.state.pool, api_key).await?;
+        if project.id != project_id {
+            return Err(AppError::Unauthorized);
+        }
+        Ok(project)
+    }
+
+    /// Path of `arg` from the root, without the leading `/`
+    fn resolve(&self, arg: &str) -> String {
+        let mut parts: Vec<&str> = if arg.starts_with('/') {
+            Vec::new()
+        } else {
+            self.cwd
+                .split('/')
+                .filter(|part| !part.is_empty())
+                .collect()
+        };
+        for part in arg.split('/') {
+            match part {
+                "" | "." => {}
+                ".." => {
+                    parts.pop();
+                }
+                part => parts.push(part),
+            }
+        }
+        parts.join("/")
+    }
+
+    /// ID of the folder at `path`; `None` for the root
+    async fn folder(
+        &self,
+        project: &Project,
+        path: &str,
+    ) -> std::result::Result<Option<Uuid>, Reply> {
+        if path.is_empty() {
+            return Ok(None);
+        }
+        let folder_id: Option<Uuid> =
+            sqlx::query_scalar("SELECT id FROM folders WHERE project_id = $1 AND path = $2")
+                .bind(project.id)
+                .bind(path)
+                .fetch_optional(&self.state.pool)
+                .await
+                .map_err(AppError::from)?;
+        match folder_id {
+            Some(folder_id) => Ok(Some(folder_id)),
+            None => Err(Reply(550, format!("/{path}: No such directory"))),
+        }
+    }
+
+    /// The file at `path`; the newest one when several share its name
+    async fn file(&self, project: &Project, path: &str) -> std::result::Result<File, Reply> {
+        let (folder_path, name) = split_path(path);
+        let folder_id = self.folder(project, folder_path).await?;
+        let file = sqlx::query_as::<_, File>(
+            r#"
+            SELECT id, project_id, folder_id, original_name, stored_name, file_path, size, mime_type, upload_date, storage_encoding, precompressed_encodings, storage_tier, moderation_status
+            FROM files
+            WHERE project_id = $1 AND folder_id IS NOT DISTINCT FROM $2 AND original_name = $3
+            ORDER BY upload_date DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(project.id)
+        .bind(folder_id)
+        .bind(name)
+        .fetch_optional(&self.state.pool)
+        .await
+        .map_err(AppError::from)?;
+        file.ok_or_else(|| Reply(550, format!("/{path}: No such file")))
+    }
+
+    async fn change_dir(&mut self, arg: &str) -> Outcome {
+        let project = self.project().await?;
+        let path = self.resolve(arg);
+        self.folder(&project, &path).await?;
+        self.cwd = path;
+        Ok(Reply(
+            250,
+            format!("Directory changed to {}", quote_path(&self.cwd)),
+        ))
+    }
+
+    /// Open a passive port for the next transfer, on the address the client
+    /// reached us on
+    async fn passive(&mut self, extended: bool) -> Outcome {
+        self.passive = None;
+        let (first, last) = self.state.config.ftp_passive_ports;
+        let count = u32::from(last.saturating_sub(first)) + 1;
+        let offset = rand::random::<u32>() % count;
+        let mut listener = None;
+        for i in 0..count {
+            let port = first + ((offset + i) % count) as u16;
+            if let Ok(bound) = TcpListener::bind((self.local_ip, port)).await {
+                listener = Some(bound);
+                break;
+            }
+        }
+        let listener = listener.ok_or_else(|| Reply::new(425, "No passive port is free"))?;
+        let port = listener.local_addr()?.port();
+
+        let reply = if extended {
+            Reply(229, format!("Entering Extended Passive Mode (|||{port}|)"))
+        } else {
+            let host = match (self.state.config.ftp_public_host, self.local_ip) {
+                (Some(host), _) => host,
+                (None, IpAddr::V4(host)) => host,
+                (None, IpAddr::V6(_)) => return Err(Reply::new(425, "Use EPSV over IPv6")),
+            };
+            let [a, b, c, d] = host.octets();
+            Reply(
+                227,
+                format!(
+                    "Entering Passive Mode ({a},{b},{c},{d},{},{})",
+                    port >> 8,
+                    port & 0xff
+                ),
+            )
+        };
+        self.passive = Some(listener);
+        Ok(reply)
+    }
+
+    /// Accept the data connection for a transfer on the port from
+    /// `PASV`/`EPSV`; only the client's own address may connect
+    async fn open_data(&mut self) -> std::result::Result<Stream, Reply> {
+        if self.state.config.ftp_require_tls && !self.protect_data {
+            return Err(Reply::new(
+                521,
+                "Data connections must be protected; use PROT P",
+            ));
+        }
+        let listener = self
+            .passive
+            .take()
+            .ok_or_else(|| Reply::new(425, "Use PASV or EPSV first"))?;
+        self.reply(150, "Opening data connection").await?;
+
+        let accepted = tokio::time::timeout(
+            Duration::from_secs(FTP_DATA_TIMEOUT_SECS),
+            listener.accept(),
+        )
+        .await;
+        let socket = match accepted {
+            Ok(Ok((socket, peer))) if peer.ip().to_canonical() == self.peer_ip => socket,
+            Ok(Ok(_)) => {
+                return Err(Reply::new(
+                    425,
+                    "Data connection from another address refused",
+                ))
+            }
+            _ => return Err(Reply::new(425, "Data connection was not opened")),
+        };
+        if !self.protect_data {
+            return Ok(Box::new(socket));
+        }
+        let acceptor = self.tls.clone().expect("PROT P is only accepted with TLS");
+        match acceptor.accept(socket).await {
+            Ok(stream) => Ok(Box::new(stream)),
+            Err(e) => Err(Reply(425, format!("TLS negotiation failed: {e}"))),
+        }
+    }
+
+    async fn list(&mut self, arg: &str, names_only: bool) -> Outcome {
+        let project = self.project().await?;
+        // Options like `-la` are for `ls`; the listing is always the same
+        let arg = arg
+            .split(' ')
+            .filter(|part| !part.starts_with('-'))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let path = self.resolve(&arg);
+        let folder_id = self.folder(&project, &path).await?;
+        let prefix = if path.is_empty() {
+            String::new()
+        } else {
+            format!("{path}/")
+        };
+
+        let folders: Vec<(String, DateTime<Utc>)> = sqlx::query_as(
+            r#"
+            SELECT path, created_at FROM folders
+            WHERE project_id = $1 AND starts_with(path, $2)
+              AND strpos(substr(path, length($2) + 1), '/') = 0
+            ORDER BY path
+            "#,
+        )
+        .bind(project.id)
+        .bind(&prefix)
+        .fetch_all(&self.state.pool)
+        .await
+        .map_err(AppError::from)?;
+        let files: Vec<(String, i64, DateTime<Utc>)> = sqlx::query_as(
+            r#"
+            SELECT DISTINCT ON (original_name) original_name, size, upload_date FROM files
+            WHERE project_id = $1 AND folder_id IS NOT DISTINCT FROM $2
+            ORDER BY original_name, upload_date DESC
+            "#,
+        )
+        .bind(project.id)
+        .bind(folder_id)
+        .fetch_all(&self.state.pool)
+        .await
+        .map_err(AppError::from)?;
+
+        let mut listing = String::new();
+        for (folder_path, created_at) in &folders {
+            let name = &folder_path[prefix.len()..];
+            if names_only {
+                listing.push_str(&format!("{name}\r\n"));
+            } else {
+                listing.push_str(&list_line(true, 0, *created_at, name));
+            }
+        }
+        for (name, size, upload_date) in &files {
+            if names_only {
+                listing.push_str(&format!("{name}\r\n"));
+            } else {
+                listing.push_str(&list_line(false, *size, *upload_date, name));
+            }
+        }
+
+        let mut data = self.open_data().await?;
+        data.write_all(listing.as_bytes()).await?;
+        data.shutdown().await?;
+        Ok(Reply::new(226, "Listing sent"))
+    }
+
+    async fn retrieve(&mut self, arg: &str) -> Outcome {
+        let project = self.project().await?;
+        let file = self.file(&project, &self.resolve(arg)).await?;
+        ensure_geo_allowed(&self.state, &project, self.peer_ip)?;
+        let _reservation = reserve_memory(&self.state, file.size as usize).await?;
+        let content =
+            read_file_content(&self.state.pool, self.state.cold_storage.as_deref(), &file).await?;
+
+        let mut data = self.open_data().await?;
+        data.write_all(&content).await?;
+        data.shutdown().await?;
+        Ok(Reply::new(226, "Transfer complete"))
+    }
+
+    /// Upload a file, replacing one of the same name
+    async fn store(&mut self, arg: &str) -> Outcome {
+        let project = self.project().await?;
+        ensure_project_writable(&project)?;
+        let path = self.resolve(arg);
+        let (folder_path, name) = split_path(&path);
+        let name = sanitize_file_name(name).map_err(|e| Reply(553, e.to_string()))?;
+        self.folder(&project, folder_path).await?;
+        let _upload_permit = upload_permit(&self.state, &project)?;
+
+        let max_file_size = self.state.config.max_file_size;
+        let mut reservation = reserve_memory(&self.state, 0).await?;
+        let mut data = self.open_data().await?;
+        let mut content = Vec::new();
+        let mut buffer = vec![0u8; FTP_READ_BUFFER_SIZE];
+        loop {
+            let read = data.read(&mut buffer).await?;
+            if read == 0 {
+                break;
+            }
+            if content.len() + read > max_file_size {
+                return Err(Reply(
+                    552,
+                    format!("File size exceeds maximum of {max_file_size} bytes"),
+                ));
+            }
+            grow_reservation(&self.state, &mut reservation, content.len() + read).await?;
+            content.extend_from_slice(&buffer[..read]);
+        }
+        // TLS clients wait for our close_notify before reading the reply
+        let _ = data.shutdown().await;
+        drop(data);
+
+        ingest_file(
+            &self.state,
+            project.id,
+            Some(folder_path),
+            &name,
+            content,
+            ConflictStrategy::Overwrite,
+        )
+        .await?;
+        Ok(Reply::new(226, "Transfer complete"))
+    }
+
+    async fn delete(&mut self, arg: &str) -> Outcome {
+        let project = self.project().await?;
+        ensure_project_writable(&project)?;
+        let file = self.file(&project, &self.resolve(arg)).await?;
+        remove_file_record(&self.state, &file).await?;
+        Ok(Reply::new(250, "File deleted"))
+    }
+
+    async fn make_dir(&mut self, arg: &str) -> Outcome {
+        let project = self.project().await?;
+        ensure_project_writable(&project)?;
+        let path = validate_folder_path(&self.resolve(arg))?;
+        if self.folder(&project, &path).await.is_ok() {
+            return Err(Reply(550, format!("/{path}: Directory exists")));
+        }
+        let mut conn = self.state.pool.acquire().await.map_err(AppError::from)?;
+        get_or_create_folder(&mut conn, project.id, &path).await?;
+        Ok(Reply(257, format!("{} created", quote_path(&path))))
+    }
+
+    /// Remove an empty folder; the exclusive folder lock keeps uploads out
+    /// while it's checked
+    async fn remove_dir(&mut self, arg: &str) -> Outcome {
+        let project = self.project().await?;
+        ensure_project_writable(&project)?;
+        let path = self.resolve(arg);
+        if path.is_empty() {
+            return Err(Reply::new(550, "The root directory can't be removed"));
+        }
+        self.folder(&project, &path).await?;
+
+        let removed = async {
+            let mut tx = self.state.pool.begin().await?;
+            advisory_xact_lock(
+                &mut tx,
+                &folder_lock_key(project.id, &path),
+                LockMode::Exclusive,
+            )
+            .await?;
+            let removed = sqlx::query(
+                r#"
+                DELETE FROM folders f
+                WHERE f.project_id = $1 AND f.path = $2
+                  AND NOT EXISTS (SELECT 1 FROM files WHERE folder_id = f.id)
+                  AND NOT EXISTS (
+                      SELECT 1 FROM folders
+                      WHERE project_id = $1 AND starts_with(path, $2 || '/')
+                  )
+                "#,
+            )
+            .bind(project.id)
+            .bind(&path)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+            tx.commit().await?;
+            Ok::<_, AppError>(removed)
+        }
+        .await?;
+        if removed == 0 {
+            return Err(Reply(550, format!("/{path}: Directory not empty")));
+        }
+        Ok(Reply::new(250, "Directory removed"))
+    }
+}
@@ -0,0 +1,564 @@
+use std::collections::HashSet;
+
+use axum::{
+    body::Bytes,
+    extract::{FromRequest, Multipart, Path, Query, Request, State},
+    Json,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::Deserialize;
+use subtle::ConstantTimeEq;
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::{
+    error::{AppError, Result},
+    handlers::{ingest::ingest_file, member::ensure_project_admin},
+    middleware::AuthUser,
+    models::{
+        ConflictStrategy, InboundEmailResponse, InboundEmailSettings, UpdateInboundEmailRequest,
+    },
+    utils::{
+        attachment_base_name, email_address, mime_attachments, sender_allowed, EmailAttachment,
+        InboundEmail,
+    },
+    AppState,
+};
+
+/// Largest webhook body accepted; providers deliver messages of up to
+/// 25 MB (Mailgun) and 35 MB (Postmark, base64 in JSON)
+pub const MAX_INBOUND_EMAIL_SIZE: usize = 64 * 1024 * 1024;
+
+#[derive(Debug, Deserialize)]
+pub struct InboundEmailQuery {
+    pub token: Option<String>,
+}
+
+/// Webhook URLs carry `?token=<INBOUND_EMAIL_TOKEN>`; the endpoint is hidden
+/// entirely when inbound email isn't configured. Returns the mail domain.
+fn check_inbound_email_token<'a>(state: &'a AppState, token: Option<&str>) -> Result<&'a str> {
+    let (Some(domain), Some(expected)) = (
+        state.config.inbound_email_domain.as_deref(),
+        state.config.inbound_email_token.as_deref(),
+    ) else {
+        return Err(AppError::NotFound("Not found".to_string()));
+    };
+    // Constant time, so the token can't be guessed byte by byte from timings
+    let matches = token.is_some_and(|t| bool::from(t.as_bytes().ct_eq(expected.as_bytes())));
+    if !matches {
+        return Err(AppError::Unauthorized);
+    }
+    Ok(domain)
+}
+
+async fn inbound_email_settings(
+    state: &AppState,
+    project_id: Uuid,
+) -> Result<InboundEmailSettings> {
+    let (slug, allowed_senders): (Option<String>, Vec<String>) =
+        sqlx::query_as("SELECT slug, inbound_email_senders FROM projects WHERE id = $1")
+            .bind(project_id)
+            .fetch_optional(&state.pool)
+            .await?
+            .ok_or(AppError::NotFound("Project not found".to_string()))?;
+    let address = state
+        .config
+        .inbound_email_domain
+        .as_deref()
+        .filter(|_| state.config.inbound_email_token.is_some())
+        .map(|domain| {
+            format!(
+                "{}@{domain}",
+                slug.unwrap_or_else(|| project_id.to_string())
+            )
+        });
+    Ok(InboundEmailSettings {
+        address,
+        allowed_senders,
+    })
+}
+
+/// The project's email address and sender allowlist (owner or project admin)
+pub async fn get_inbound_email(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(project_id): Path<Uuid>,
+) -> Result<Json<InboundEmailSettings>> {
+    ensure_project_admin(&state, &auth_user, project_id).await?;
+    Ok(Json(inbound_email_settings(&state, project_id).await?))
+}
+
+/// Replace who may email files into the project (owner or project admin)
+pub async fn update_inbound_email(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(project_id): Path<Uuid>,
+    Json(payload): Json<UpdateInboundEmailRequest>,
+) -> Result<Json<InboundEmailSettings>> {
+    payload
+        .validate()
+        .map_err(|e| AppError::ValidationError(e.to_string()))?;
+    ensure_project_admin(&state, &auth_user, project_id).await?;
+
+    let mut senders: Vec<String> = payload
+        .allowed_senders
+        .iter()
+        .map(|sender| sender.trim().to_lowercase())
+        .collect();
+    senders.sort_unstable();
+    senders.dedup();
+    sqlx::query("UPDATE projects SET inbound_email_senders = $1 WHERE id = $2")
+        .bind(&senders)
+        .bind(project_id)
+        .execute(&state.pool)
+        .await?;
+
+    Ok(Json(inbound_email_settings(&state, project_id).await?))
+}
+
+/// Whether a provider's SPF or DKIM verdict (`Pass`, `pass (...)`) passed;
+/// a missing verdict counts as a failure
+fn verdict_passed(verdict: Option<&str>) -> bool {
+    verdict.is_some_and(|verdict| {
+        verdict
+            .trim_start()
+            .get(..4)
+            .is_some_and(|result| result.eq_ignore_ascii_case("pass"))
+    })
+}
+
+/// Whether an `Authentication-Results` header records a passing SPF, DKIM
+/// or DMARC check
+fn authentication_results_passed(header: &str) -> bool {
+    header.split(';').skip(1).any(|result| {
+        let result = result.trim().to_ascii_lowercase();
+        ["spf=pass", "dkim=pass", "dmarc=pass"]
+            .iter()
+            .any(|check| result.starts_with(check))
+    })
+}
+
+/// Mailgun's parsed-message webhook: form fields with `attachment-N` files.
+/// `None` when Mailgun verified neither SPF nor DKIM for the sender.
+async fn mailgun_email(mut multipart: Multipart) -> Result<Option<InboundEmail>> {
+    let mut email = InboundEmail {
+        sender: None,
+        recipients: Vec::new(),
+        attachments: Vec::new(),
+    };
+    let mut spf = None;
+    let mut dkim = None;
+    let mut attachment_fields = Vec::new();
+    let mut inline_fields = HashSet::new();
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| AppError::BadRequest(format!("Multipart error: {e}")))?
+    {
+        let name = field.name().unwrap_or("").to_string();
+        let file_name = field.file_name().map(str::to_string);
+        let data = field
+            .bytes()
+            .await
+            .map_err(|e| AppError::BadRequest(format!("Failed to read {name}: {e}")))?;
+        match name.as_str() {
+            "recipient" => email.recipients.extend(
+                String::from_utf8_lossy(&data)
+                    .split(',')
+                    .map(|recipient| recipient.trim().to_string()),
+            ),
+            "from" => email.sender = email_address(&String::from_utf8_lossy(&data)),
+            "X-Mailgun-Spf" => spf = Some(String::from_utf8_lossy(&data).into_owned()),
+            "X-Mailgun-Dkim-Check-Result" => {
+                dkim = Some(String::from_utf8_lossy(&data).into_owned())
+            }
+            // Maps Content-IDs of images shown in the body to their fields
+            "content-id-map" => {
+                if let Ok(map) =
+                    serde_json::from_slice::<std::collections::HashMap<String, String>>(&data)
+                {
+                    inline_fields.extend(map.into_values());
+                }
+            }
+            _ if name.starts_with("attachment-") => {
+                attachment_fields.push((name, file_name, data));
+            }
+            _ => {}
+        }
+    }
+    email.attachments = attachment_fields
+        .into_iter()
+        .filter(|(field, _, _)| !inline_fields.contains(field))
+        .map(|(field, file_name, data)| EmailAttachment {
+            name: file_name.unwrap_or(field),
+            data: data.to_vec(),
+        })
+        .collect();
+    // The allowlist trusts `From`, so it must be one the sender could send as
+    if !verdict_passed(spf.as_deref()) && !verdict_passed(dkim.as_deref()) {
+        tracing::warn!("Dropped inbound email that failed SPF and DKIM");
+        return Ok(None);
+    }
+    Ok(Some(email))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct PostmarkEmail {
+    from_full: Option<PostmarkAddress>,
+    original_recipient: Option<String>,
+    #[serde(default)]
+    to_full: Vec<PostmarkAddress>,
+    #[serde(default)]
+    cc_full: Vec<PostmarkAddress>,
+    #[serde(default)]
+    bcc_full: Vec<PostmarkAddress>,
+    #[serde(default)]
+    attachments: Vec<PostmarkAttachment>,
+    #[serde(default)]
+    headers: Vec<PostmarkHeader>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct PostmarkHeader {
+    name: String,
+    value: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct PostmarkAddress {
+    email: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct PostmarkAttachment {
+    name: String,
+    content: String,
+    #[serde(rename = "ContentID", default)]
+    content_id: Option<String>,
+}
+
+/// Postmark's inbound webhook: JSON with base64 attachments. `None` when
+/// Postmark verified neither SPF nor DKIM for the sender.
+fn postmark_email(body: &[u8]) -> Result<Option<InboundEmail>> {
+    let message: PostmarkEmail = serde_json::from_slice(body)
+        .map_err(|e| AppError::BadRequest(format!("Invalid Postmark message: {e}")))?;
+    // Postmark adds its verdicts on top; later copies may come from the sender
+    let header = |name: &str| {
+        message
+            .headers
+            .iter()
+            .find(|header| header.name.eq_ignore_ascii_case(name))
+            .map(|header| header.value.as_str())
+    };
+    let authenticated = verdict_passed(header("Received-SPF"))
+        || header("Authentication-Results").is_some_and(authentication_results_passed);
+    if !authenticated {
+        tracing::warn!("Dropped inbound email that failed SPF and DKIM");
+        return Ok(None);
+    }
+    let recipients = message
+        .original_recipient
+        .into_iter()
+        .chain(
+            [message.to_full, message.cc_full, message.bcc_full]
+                .into_iter()
+                .flatten()
+                .map(|address| address.email),
+        )
+        .collect();
+    let mut attachments = Vec::new();
+    for attachment in message.attachments {
+        // Images shown in the body have a Content-ID
+        if attachment.content_id.is_some_and(|id| !id.is_empty()) {
+            continue;
+        }
+        let data = STANDARD.decode(&attachment.content).map_err(|_| {
+            AppError::BadRequest(format!("Attachment {} is not base64", attachment.name))
+        })?;
+        attachments.push(EmailAttachment {
+            name: attachment.name,
+            data,
+        });
+    }
+    Ok(Some(InboundEmail {
+        sender: message
+            .from_full
+            .and_then(|address| email_address(&address.email)),
+        recipients,
+        attachments,
+    }))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SnsMessage {
+    #[serde(rename = "Type")]
+    kind: String,
+    message: Option<String>,
+    #[serde(rename = "SubscribeURL")]
+    subscribe_url: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SesNotification {
+    notification_type: String,
+    mail: SesMail,
+    receipt: SesReceipt,
+    content: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SesMail {
+    common_headers: SesCommonHeaders,
+}
+
+#[derive(Deserialize)]
+struct SesCommonHeaders {
+    #[serde(default)]
+    from: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SesReceipt {
+    recipients: Vec<String>,
+    dmarc_verdict: Option<SesVerdict>,
+    action: SesAction,
+}
+
+#[derive(Deserialize)]
+struct SesVerdict {
+    status: String,
+}
+
+#[derive(Deserialize)]
+struct SesAction {
+    encoding: Option<String>,
+}
+
+/// SES receipt rules with an SNS action: the SNS notification carries the
+/// raw message. Subscription confirmations are answered here; `None` when
+/// there's no message to store.
+async fn ses_email(body: &[u8]) -> Result<Option<InboundEmail>> {
+    let sns: SnsMessage = serde_json::from_slice(body)
+        .map_err(|e| AppError::BadRequest(format!("Invalid SNS message: {e}")))?;
+    match sns.kind.as_str() {
+        "SubscriptionConfirmation" => {
+            let url = sns
+                .subscribe_url
+                .as_deref()
+                .and_then(|url| reqwest::Url::parse(url).ok())
+                .filter(|url| {
+                    url.scheme() == "https"
+                        && url.host_str().is_some_and(|host| {
+                            host.starts_with("sns.") && host.ends_with(".amazonaws.com")
+                        })
+                })
+                .ok_or(AppError::BadRequest("Invalid SubscribeURL".to_string()))?;
+            reqwest::Client::new()
+                .get(url)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .map_err(|e| {
+                    AppError::InternalError(format!("Failed to confirm SNS subscription: {e}"))
+                })?;
+            tracing::info!("Confirmed SNS subscription for inbound email");
+            return Ok(None);
+        }
+        "Notification" => {}
+        _ => return Ok(None),
+    }
+
+    let notification: SesNotification =
+        serde_json::from_str(sns.message.as_deref().unwrap_or_default())
+            .map_err(|e| AppError::BadRequest(format!("Invalid SES notification: {e}")))?;
+    if notification.notification_type != "Received" {
+        return Ok(None);
+    }
+    if notification
+        .receipt
+        .dmarc_verdict
+        .is_some_and(|verdict| verdict.status == "FAIL")
+    {
+        tracing::warn!("Dropped inbound email that failed DMARC");
+        return Ok(None);
+    }
+    let content = notification.content.ok_or(AppError::BadRequest(
+        "SES notification has no content; the receipt rule needs an SNS action".to_string(),
+    ))?;
+    let raw = match notification.receipt.action.encoding.as_deref() {
+        Some("BASE64") => STANDARD
+            .decode(content.trim())
+            .map_err(|_| AppError::BadRequest("SES content is not base64".to_string()))?,
+        _ => content.into_bytes(),
+    };
+    Ok(Some(InboundEmail {
+        sender: notification
+            .mail
+            .common_headers
+            .from
+            .first()
+            .and_then(|from| email_address(from)),
+        recipients: notification.receipt.recipients,
+        attachments: mime_attachments(&raw),
+    }))
+}
+
+/// Store the attachments of an email for each recipient at the inbound
+/// domain: `<project>+<folder>@<domain>`, where the project is its slug or
+/// ID and further `+`s separate folder levels. The sender must be on the
+/// project's allowlist and the folder must exist.
+async fn store_email(
+    state: &AppState,
+    domain: &str,
+    email: InboundEmail,
+) -> Result<InboundEmailResponse> {
+    let mut response = InboundEmailResponse {
+        files: Vec::new(),
+        skipped: Vec::new(),
+    };
+    let Some(sender) = email.sender else {
+        response
+            .skipped
+            .push("Message has no sender address".to_string());
+        return Ok(response);
+    };
+    if email.attachments.is_empty() {
+        response
+            .skipped
+            .push("Message has no attachments".to_string());
+        return Ok(response);
+    }
+
+    let mut seen = HashSet::new();
+    for recipient in email.recipients.iter().filter_map(|r| email_address(r)) {
+        // Other recipients (copies to people) aren't ours
+        let Some(local) = recipient
+            .strip_suffix(domain)
+            .and_then(|local| local.strip_suffix('@'))
+        else {
+            continue;
+        };
+        if !seen.insert(recipient.clone()) {
+            continue;
+        }
+        let (project_key, folder_path) = match local.split_once('+') {
+            Some((project, folder)) => (project, folder.replace('+', "/")),
+            None => (local, String::new()),
+        };
+
+        let target: Option<(Uuid, Vec<String>)> = match Uuid::parse_str(project_key) {
+            Ok(project_id) => {
+                sqlx::query_as("SELECT id, inbound_email_senders FROM projects WHERE id = $1")
+                    .bind(project_id)
+                    .fetch_optional(&state.pool)
+                    .await?
+            }
+            Err(_) => {
+                sqlx::query_as("SELECT id, inbound_email_senders FROM projects WHERE slug = $1")
+                    .bind(project_key.to_ascii_lowercase())
+                    .fetch_optional(&state.pool)
+                    .await?
+            }
+        };
+        let Some((project_id, allowed_senders)) = target else {
+            response
+                .skipped
+                .push(format!("{recipient}: no such project"));
+            continue;
+        };
+        if !sender_allowed(&allowed_senders, &sender) {
+            tracing::warn!(
+                "Inbound email from {} to {} rejected: sender not allowed",
+                sender,
+                recipient
+            );
+            response
+                .skipped
+                .push(format!("{recipient}: {sender} is not an allowed sender"));
+            continue;
+        }
+        // Typos shouldn't scatter files into new folders
+        if !folder_path.is_empty() {
+            let exists: bool = sqlx::query_scalar(
+                "SELECT EXISTS (SELECT 1 FROM folders WHERE project_id = $1 AND path = $2)",
+            )
+            .bind(project_id)
+            .bind(&folder_path)
+            .fetch_one(&state.pool)
+            .await?;
+            if !exists {
+                response
+                    .skipped
+                    .push(format!("{recipient}: no folder {folder_path}"));
+                continue;
+            }
+        }
+
+        for attachment in &email.attachments {
+            let name = attachment_base_name(&attachment.name);
+            match ingest_file(
+                state,
+                project_id,
+                Some(&folder_path),
+                name,
+                attachment.data.clone(),
+                ConflictStrategy::Rename,
+            )
+            .await
+            {
+                Ok(upload) => response.files.push(upload),
+                Err(e) => response
+                    .skipped
+                    .push(format!("{recipient}: {}: {e}", attachment.name)),
+            }
+        }
+    }
+    Ok(response)
+}
+
+/// Inbound email webhook for `mailgun`, `postmark` or `ses` (through SNS).
+/// Messages that can't be stored are still acknowledged with 200, listing
+/// why in `skipped`, so the provider doesn't retry them.
+pub async fn receive_inbound_email(
+    State(state): State<AppState>,
+    Path(provider): Path<String>,
+    Query(query): Query<InboundEmailQuery>,
+    request: Request,
+) -> Result<Json<InboundEmailResponse>> {
+    let domain = check_inbound_email_token(&state, query.token.as_deref())?;
+    let email = match provider.as_str() {
+        "mailgun" => {
+            let multipart = Multipart::from_request(request, &state)
+                .await
+                .map_err(|e| AppError::BadRequest(format!("Multipart error: {e}")))?;
+            mailgun_email(multipart).await?
+        }
+        "postmark" | "ses" => {
+            let body = Bytes::from_request(request, &state)
+                .await
+                .map_err(|e| AppError::BadRequest(format!("Failed to read body: {e}")))?;
+            if provider == "postmark" {
+                postmark_email(&body)?
+            } else {
+                ses_email(&body).await?
+            }
+        }
+        _ => return Err(AppError::NotFound("Not found".to_string())),
+    };
+
+    match email {
+        Some(email) => Ok(Json(store_email(&state, domain, email).await?)),
+        None => Ok(Json(InboundEmailResponse {
+            files: Vec::new(),
+            skipped: Vec::new(),
+        })),
+    }
+}
@@ -0,0 +1,159 @@
+use std::time::Duration;
+
+use uuid::Uuid;
+
+use crate::{
+    error::{AppError, Result},
+    handlers::file::{store_upload, NewUpload},
+    models::{ConflictStrategy, Project, UploadResponse, PROJECT_COLUMNS},
+    utils::{
+        ensure_project_writable, sanitize_file_name, validate_folder_path, MqttClient, MqttRoute,
+    },
+    AppState,
+};
+
+/// Seconds before reconnecting after the MQTT connection fails
+const MQTT_RECONNECT_DELAY_SECS: u64 = 10;
+
+/// Store content that arrived other than through an upload request (an MQTT
+/// message, an FTP upload) as a new file in `project_id`, like an upload with
+/// the API key
+pub(crate) async fn ingest_file(
+    state: &AppState,
+    project_id: Uuid,
+    folder_path: Option<&str>,
+    file_name: &str,
+    data: Vec<u8>,
+    on_conflict: ConflictStrategy,
+) -> Result<UploadResponse> {
+    let project = sqlx::query_as::<_, Project>(&format!(
+        "SELECT {PROJECT_COLUMNS} FROM projects WHERE id = $1"
+    ))
+    .bind(project_id)
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or(AppError::NotFound("Project not found".to_string()))?;
+    ensure_project_writable(&project)?;
+
+    let file_name = sanitize_file_name(file_name)?;
+    let folder_path = folder_path
+        .filter(|path| !path.is_empty())
+        .map(validate_folder_path)
+        .transpose()?;
+    if data.is_empty() {
+        return Err(AppError::BadRequest(
+            "Empty files cannot be uploaded".to_string(),
+        ));
+    }
+    if data.len() > state.config.max_file_size {
+        return Err(AppError::BadRequest(format!(
+            "File size exceeds maximum of {} bytes",
+            state.config.max_file_size
+        )));
+    }
+
+    store_upload(
+        state,
+        &project,
+        NewUpload {
+            file_name,
+            folder_path,
+            on_conflict: Some(on_conflict),
+            description: None,
+            data,
+            replaces: None,
+            optimize_images: true,
+            keep_replaced: None,
+            dedupe_recent: false,
+        },
+        None,
+    )
+    .await
+}
+
+/// Subscribe to the `MQTT_ROUTES` topics and store each message as a file,
+/// reconnecting whenever the connection drops. Messages are acknowledged
+/// once stored; ones that can't be stored are logged and dropped.
+pub async fn run_mqtt_bridge(state: AppState, url: String) {
+    let routes = state.config.mqtt_routes.clone();
+    let mut strategies = Vec::with_capacity(routes.len());
+    for route in &routes {
+        match route.on_conflict.as_deref().unwrap_or("rename").parse() {
+            Ok(strategy) => strategies.push(strategy),
+            Err(e) => {
+                tracing::error!("MQTT route for {} is invalid: {}", route.topic, e);
+                return;
+            }
+        }
+    }
+    if routes.is_empty() {
+        tracing::warn!("MQTT_URL is set but MQTT_ROUTES is empty; not connecting");
+        return;
+    }
+
+    loop {
+        if let Err(e) = mqtt_session(&state, &url, &routes, &strategies).await {
+            tracing::warn!("MQTT connection failed: {}", e);
+        }
+        tokio::time::sleep(Duration::from_secs(MQTT_RECONNECT_DELAY_SECS)).await;
+    }
+}
+
+async fn mqtt_session(
+    state: &AppState,
+    url: &str,
+    routes: &[MqttRoute],
+    strategies: &[ConflictStrategy],
+) -> std::io::Result<()> {
+    let mut client = MqttClient::connect(
+        url,
+        &state.config.mqtt_client_id,
+        state.config.max_file_size,
+    )
+    .await?;
+    let filters: Vec<&str> = routes.iter().map(|route| route.topic.as_str()).collect();
+    client.subscribe(&filters).await?;
+    tracing::info!(
+        "Connected to MQTT broker, subscribed to {}",
+        filters.join(", ")
+    );
+
+    loop {
+        let message = client.next_message().await?;
+        // The first route whose filter matches decides where the message goes
+        match routes
+            .iter()
+            .position(|route| route.matches(&message.topic))
+        {
+            Some(index) => {
+                let route = &routes[index];
+                let folder = route
+                    .folder
+                    .as_deref()
+                    .map(|template| MqttRoute::render(template, &message.topic));
+                let name = MqttRoute::render(&route.name, &message.topic);
+                match ingest_file(
+                    state,
+                    route.project_id,
+                    folder.as_deref(),
+                    &name,
+                    message.payload,
+                    strategies[index],
+                )
+                .await
+                {
+                    Ok(upload) => tracing::debug!(
+                        "Stored MQTT message on {} as file {}",
+                        message.topic,
+                        upload.file_id
+                    ),
+                    Err(e) => tracing::warn!("Dropped MQTT message on {}: {}", message.topic, e),
+                }
+            }
+            None => tracing::debug!("No MQTT route for {}", message.topic),
+        }
+        if let Some(packet_id) = message.packet_id {
+            client.ack(packet_id).await?;
+        }
+    }
+}
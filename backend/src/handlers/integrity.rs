@@ -0,0 +1,91 @@
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use crate::{
+    error::{AppError, Result},
+    middleware::RequirePermission,
+    models::{IntegrityFailure, IntegrityReport, IntegrityRun},
+    utils::{begin_integrity_run, perm, run_integrity_check, INTEGRITY_RUN_COLUMNS},
+    AppState,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct IntegrityCheckQuery {
+    /// Files to check; defaults to `INTEGRITY_CHECK_SAMPLE_SIZE` (0 = every file)
+    pub sample_size: Option<i64>,
+}
+
+/// Open integrity failures, recent runs and how much of the store has been verified
+pub async fn integrity_report(
+    State(state): State<AppState>,
+    _: RequirePermission<perm::AdminIntegrityRead>,
+) -> Result<Json<IntegrityReport>> {
+    let (total_files, unchecked_files, oldest_check_at) =
+        sqlx::query_as::<_, (i64, i64, Option<DateTime<Utc>>)>(
+            r#"
+            SELECT COUNT(*), COUNT(*) FILTER (WHERE integrity_checked_at IS NULL),
+                   MIN(integrity_checked_at)
+            FROM files
+            "#,
+        )
+        .fetch_one(&state.pool)
+        .await?;
+
+    let open_failures = sqlx::query_as::<_, IntegrityFailure>(
+        r#"
+        SELECT i.id, i.file_id, f.original_name AS file_name, i.project_id, p.name AS project_name,
+               i.problem, i.detail, i.detected_at, i.last_seen_at
+        FROM integrity_failures i
+        JOIN files f ON f.id = i.file_id
+        JOIN projects p ON p.id = i.project_id
+        WHERE i.resolved_at IS NULL
+        ORDER BY i.detected_at DESC
+        "#,
+    )
+    .fetch_all(&state.pool)
+    .await?;
+
+    let runs = sqlx::query_as::<_, IntegrityRun>(&format!(
+        "SELECT {INTEGRITY_RUN_COLUMNS} FROM integrity_runs ORDER BY started_at DESC LIMIT 10"
+    ))
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(Json(IntegrityReport {
+        total_files,
+        unchecked_files,
+        oldest_check_at,
+        open_failures,
+        runs,
+    }))
+}
+
+/// Start an integrity check now; it runs in the background and shows up in
+/// `GET /api/admin/integrity`
+pub async fn trigger_integrity_check(
+    State(state): State<AppState>,
+    _: RequirePermission<perm::AdminIntegrityRun>,
+    Query(query): Query<IntegrityCheckQuery>,
+) -> Result<Json<IntegrityRun>> {
+    let sample_size = query
+        .sample_size
+        .unwrap_or(state.config.integrity_check_sample_size);
+    if sample_size < 0 {
+        return Err(AppError::BadRequest(
+            "sample_size must not be negative".to_string(),
+        ));
+    }
+    let run = begin_integrity_run(&state.pool, Some(sample_size).filter(|&n| n > 0)).await?;
+    tokio::spawn(run_integrity_check(
+        state.pool.clone(),
+        state.cold_storage.clone(),
+        state.metrics.clone(),
+        run.clone(),
+    ));
+
+    Ok(Json(run))
+}
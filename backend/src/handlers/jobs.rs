@@ -0,0 +1,13 @@
+use axum::{extract::State, Json};
+
+use crate::{
+    error::Result, middleware::RequirePermission, scheduler::JobStatus, utils::perm, AppState,
+};
+
+/// Last run, duration and error of each recurring background task
+pub async fn list_jobs(
+    State(state): State<AppState>,
+    _: RequirePermission<perm::AdminJobsRead>,
+) -> Result<Json<Vec<JobStatus>>> {
+    Ok(Json(state.scheduler.statuses()))
+}
@@ -0,0 +1,128 @@
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::{
+    error::{AppError, Result},
+    middleware::OptionalAuthUser,
+    models::{ManifestFile, ManifestFolder, Project, ProjectManifest, PROJECT_COLUMNS},
+    utils::{can_list, can_write, Credentials},
+    AppState,
+};
+
+/// Version of a manifest: a digest of every entry in order, so any added,
+/// removed, renamed or changed file or folder gives a new one
+fn manifest_version(folders: &[ManifestFolder], files: &[ManifestFile]) -> String {
+    let mut hasher = Sha256::new();
+    for folder in folders {
+        hasher.update(format!("d\t{}\t{}\n", folder.path, folder.is_public));
+    }
+    for file in files {
+        hasher.update(format!(
+            "f\t{}\t{}\t{}\t{}\t{}\t{}\n",
+            file.id,
+            file.path,
+            file.size,
+            file.sha256.as_deref().unwrap_or(""),
+            file.mime_type,
+            file.modified_at.timestamp_micros()
+        ));
+    }
+    hex::encode(&hasher.finalize()[..16])
+}
+
+/// Whether an `If-None-Match` header names the current version: `*`, or any
+/// listed tag, quoted or not (weak tags compare equal)
+fn version_matches(if_none_match: &str, version: &str) -> bool {
+    if_none_match.split(',').map(str::trim).any(|tag| {
+        let tag = tag.strip_prefix("W/").unwrap_or(tag);
+        tag == "*" || tag.trim_matches('"') == version
+    })
+}
+
+/// Every folder and file of a project with sizes, hashes and modification
+/// times, for a sync or mount client. Send the last version back in
+/// `If-None-Match` to get `304 Not Modified` while nothing has changed.
+/// Needs the project API key or a collaborator role; files awaiting or
+/// failing moderation are only listed for those who can write.
+pub async fn project_manifest(
+    State(state): State<AppState>,
+    optional_auth: OptionalAuthUser,
+    headers: HeaderMap,
+    Path(project_id): Path<Uuid>,
+) -> Result<Response> {
+    let project = sqlx::query_as::<_, Project>(&format!(
+        "SELECT {PROJECT_COLUMNS} FROM projects WHERE id = $1"
+    ))
+    .bind(project_id)
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or(AppError::NotFound("Project not found".to_string()))?;
+    let credentials = Credentials::resolve(&state.pool, &optional_auth, &headers, None).await?;
+    if !can_list(&project, &credentials) {
+        return Err(AppError::Unauthorized);
+    }
+
+    // Byte order ("C"), so the order and the version don't depend on the
+    // database's locale
+    let folders = sqlx::query_as::<_, ManifestFolder>(
+        r#"SELECT path, is_public FROM folders WHERE project_id = $1 ORDER BY path COLLATE "C""#,
+    )
+    .bind(project_id)
+    .fetch_all(state.read_pool.get())
+    .await?;
+    let files = sqlx::query_as::<_, ManifestFile>(
+        r#"
+        SELECT
+            f.id,
+            COALESCE(fol.path || '/', '') || f.original_name AS path,
+            f.size,
+            f.content_hash AS sha256,
+            f.mime_type,
+            f.upload_date AS modified_at
+        FROM files f
+        LEFT JOIN folders fol ON fol.id = f.folder_id
+        WHERE f.project_id = $1 AND ($2 OR f.moderation_status = 'approved')
+        ORDER BY COALESCE(fol.path || '/', '') || f.original_name COLLATE "C", f.id
+        "#,
+    )
+    .bind(project_id)
+    .bind(can_write(&project, &credentials))
+    .fetch_all(state.read_pool.get())
+    .await?;
+
+    let version = manifest_version(&folders, &files);
+    let etag = format!("\"{version}\"");
+    let headers_out = [
+        (header::ETAG, etag),
+        (header::CACHE_CONTROL, "no-cache".to_string()),
+        // The version without quotes, for clients that don't speak ETags
+        (
+            header::HeaderName::from_static("x-manifest-version"),
+            version.clone(),
+        ),
+    ];
+    let not_modified = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| version_matches(value, &version));
+    if not_modified {
+        return Ok((StatusCode::NOT_MODIFIED, headers_out).into_response());
+    }
+
+    Ok((
+        headers_out,
+        Json(ProjectManifest {
+            project_id,
+            version,
+            folders,
+            files,
+        }),
+    )
+        .into_response())
+}
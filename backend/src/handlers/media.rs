@@ -0,0 +1,171 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::Response,
+    Json,
+};
+use serde::Deserialize;
+use sqlx::FromRow;
+use std::net::IpAddr;
+use uuid::Uuid;
+
+use crate::{
+    error::{AppError, Result},
+    handlers::file::{read_blob_body, readable_file},
+    middleware::{ClientIp, OptionalAuthUser},
+    models::{Project, WaveformResponse},
+    utils::{downsample_peaks, WAVEFORM_PEAKS},
+    AppState,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct PosterQuery {
+    pub api_key: Option<String>,
+    /// Serve the short animated preview instead of the still poster
+    pub animated: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WaveformQuery {
+    pub api_key: Option<String>,
+    /// Number of peaks to return (default and max: all stored)
+    pub points: Option<usize>,
+}
+
+#[derive(FromRow)]
+struct MediaPreview {
+    status: String,
+    poster_path: Option<String>,
+    animation_path: Option<String>,
+    waveform_peaks: Option<Vec<f32>>,
+    duration_secs: Option<f64>,
+}
+
+/// Load a readable file's generated previews once they are ready; `kind`
+/// names the preview asked for in errors
+async fn ready_preview(
+    state: &AppState,
+    optional_auth: &OptionalAuthUser,
+    headers: &HeaderMap,
+    client_ip: IpAddr,
+    file_id: Uuid,
+    api_key: Option<&str>,
+    kind: &str,
+) -> Result<(Project, MediaPreview)> {
+    let (file, project) =
+        readable_file(state, optional_auth, headers, client_ip, file_id, api_key).await?;
+    let preview = sqlx::query_as::<_, MediaPreview>(
+        "SELECT status, poster_path, animation_path, waveform_peaks, duration_secs FROM media_previews WHERE file_id = $1",
+    )
+    .bind(file.id)
+    .fetch_optional(&state.pool)
+    .await?;
+
+    match preview {
+        None => Err(AppError::NotFound(format!(
+            "No {kind} is generated for this file"
+        ))),
+        Some(preview) => match preview.status.as_str() {
+            "ready" => Ok((project, preview)),
+            "failed" => Err(AppError::NotFound(format!(
+                "The {kind} for this file could not be generated"
+            ))),
+            _ => Err(AppError::NotFound(format!(
+                "The {kind} for this file is not ready yet"
+            ))),
+        },
+    }
+}
+
+/// Poster frame (or animated preview) generated for a video upload; the same
+/// access as downloading the video
+pub async fn get_file_poster(
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    optional_auth: OptionalAuthUser,
+    headers: HeaderMap,
+    Path(file_id): Path<Uuid>,
+    Query(query): Query<PosterQuery>,
+) -> Result<Response> {
+    let animated = query.animated.unwrap_or(false);
+    let kind = if animated {
+        "animated preview"
+    } else {
+        "poster"
+    };
+    let (project, preview) = ready_preview(
+        &state,
+        &optional_auth,
+        &headers,
+        client_ip,
+        file_id,
+        query.api_key.as_deref(),
+        kind,
+    )
+    .await?;
+    let (path, content_type) = if animated {
+        (preview.animation_path, "image/webp")
+    } else {
+        (preview.poster_path, "image/jpeg")
+    };
+    let path = path.ok_or(AppError::NotFound(format!(
+        "No {kind} is generated for this file"
+    )))?;
+
+    let (length, body) = read_blob_body(
+        &state.memory_budget,
+        std::path::Path::new(&path),
+        project.download_bandwidth_limit,
+    )
+    .await?;
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::CONTENT_LENGTH, length)
+        .body(body)
+        .map_err(|e| AppError::InternalError(format!("Failed to build response: {e}")))
+}
+
+/// Waveform peaks generated for an audio upload, so players can draw it
+/// without downloading the audio; the same access as downloading the file
+pub async fn get_file_waveform(
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    optional_auth: OptionalAuthUser,
+    headers: HeaderMap,
+    Path(file_id): Path<Uuid>,
+    Query(query): Query<WaveformQuery>,
+) -> Result<Json<WaveformResponse>> {
+    if query
+        .points
+        .is_some_and(|points| !(1..=WAVEFORM_PEAKS).contains(&points))
+    {
+        return Err(AppError::BadRequest(format!(
+            "points must be between 1 and {WAVEFORM_PEAKS}"
+        )));
+    }
+    let (_, preview) = ready_preview(
+        &state,
+        &optional_auth,
+        &headers,
+        client_ip,
+        file_id,
+        query.api_key.as_deref(),
+        "waveform",
+    )
+    .await?;
+    let (Some(peaks), Some(duration_secs)) = (preview.waveform_peaks, preview.duration_secs) else {
+        return Err(AppError::NotFound(
+            "No waveform is generated for this file".to_string(),
+        ));
+    };
+
+    let peaks = match query.points {
+        Some(points) => downsample_peaks(&peaks, points),
+        None => peaks,
+    };
+    Ok(Json(WaveformResponse {
+        duration_secs,
+        peaks,
+    }))
+}
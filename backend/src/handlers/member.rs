@@ -0,0 +1,156 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::{
+    error::{AppError, Result},
+    middleware::AuthUser,
+    models::{AddMemberRequest, ProjectMemberResponse, ProjectRole},
+    AppState,
+};
+
+/// Ensure the user owns the project or is one of its admins
+pub(crate) async fn ensure_project_admin(
+    state: &AppState,
+    auth_user: &AuthUser,
+    project_id: Uuid,
+) -> Result<Uuid> {
+    sqlx::query_scalar::<_, Uuid>(
+        r#"
+        SELECT p.user_id
+        FROM projects p
+        WHERE p.id = $1 AND (
+            p.user_id = $2
+            OR EXISTS (
+                SELECT 1 FROM project_members m
+                WHERE m.project_id = p.id AND m.user_id = $2 AND m.role = 'admin'
+            )
+        )
+        "#,
+    )
+    .bind(project_id)
+    .bind(auth_user.id)
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or(AppError::NotFound("Project not found".to_string()))
+}
+
+/// Invite a registered user to the project, or change their role if already a member
+pub async fn add_member(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(project_id): Path<Uuid>,
+    Json(payload): Json<AddMemberRequest>,
+) -> Result<Json<ProjectMemberResponse>> {
+    payload
+        .validate()
+        .map_err(|e| AppError::ValidationError(e.to_string()))?;
+
+    let owner_id = ensure_project_admin(&state, &auth_user, project_id).await?;
+
+    let user_id = sqlx::query_scalar::<_, Uuid>("SELECT id FROM users WHERE email = $1")
+        .bind(&payload.email)
+        .fetch_optional(&state.pool)
+        .await?
+        .ok_or(AppError::NotFound("User not found".to_string()))?;
+
+    if user_id == owner_id {
+        return Err(AppError::BadRequest(
+            "The project owner can't be added as a member".to_string(),
+        ));
+    }
+
+    let member = sqlx::query_as::<_, ProjectMemberResponse>(
+        r#"
+        WITH upserted AS (
+            INSERT INTO project_members (project_id, user_id, role, invited_by)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (project_id, user_id) DO UPDATE SET role = EXCLUDED.role
+            RETURNING user_id, role, created_at
+        )
+        SELECT upserted.user_id, u.email, upserted.role, upserted.created_at
+        FROM upserted
+        JOIN users u ON u.id = upserted.user_id
+        "#,
+    )
+    .bind(project_id)
+    .bind(user_id)
+    .bind(payload.role)
+    .bind(auth_user.id)
+    .fetch_one(&state.pool)
+    .await?;
+
+    Ok(Json(member))
+}
+
+/// List collaborators of a project (visible to the owner and all members)
+pub async fn list_members(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(project_id): Path<Uuid>,
+) -> Result<Json<Vec<ProjectMemberResponse>>> {
+    let visible = sqlx::query_scalar::<_, bool>(
+        r#"
+        SELECT EXISTS(
+            SELECT 1 FROM projects p
+            WHERE p.id = $1 AND (
+                p.user_id = $2
+                OR EXISTS (SELECT 1 FROM project_members m WHERE m.project_id = p.id AND m.user_id = $2)
+            )
+        )
+        "#,
+    )
+    .bind(project_id)
+    .bind(auth_user.id)
+    .fetch_one(&state.pool)
+    .await?;
+
+    if !visible {
+        return Err(AppError::NotFound("Project not found".to_string()));
+    }
+
+    let members = sqlx::query_as::<_, ProjectMemberResponse>(
+        r#"
+        SELECT m.user_id, u.email, m.role, m.created_at
+        FROM project_members m
+        JOIN users u ON u.id = m.user_id
+        WHERE m.project_id = $1
+        ORDER BY m.created_at
+        "#,
+    )
+    .bind(project_id)
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(Json(members))
+}
+
+/// Remove a collaborator. Members may also remove themselves.
+pub async fn remove_member(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path((project_id, user_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<serde_json::Value>> {
+    if user_id != auth_user.id {
+        ensure_project_admin(&state, &auth_user, project_id).await?;
+    }
+
+    let removed: Option<ProjectRole> = sqlx::query_scalar(
+        "DELETE FROM project_members WHERE project_id = $1 AND user_id = $2 RETURNING role",
+    )
+    .bind(project_id)
+    .bind(user_id)
+    .fetch_optional(&state.pool)
+    .await?;
+
+    if removed.is_none() {
+        return Err(AppError::NotFound("Member not found".to_string()));
+    }
+
+    Ok(Json(serde_json::json!({
+        "message": "Member removed successfully"
+    })))
+}
@@ -0,0 +1,34 @@
+use axum::{
+    extract::State,
+    http::{header, HeaderMap},
+    response::{IntoResponse, Response},
+};
+use subtle::ConstantTimeEq;
+
+use crate::{
+    error::{AppError, Result},
+    AppState,
+};
+
+/// Prometheus scrape endpoint; requires `Authorization: Bearer <METRICS_TOKEN>`
+/// and is hidden entirely when no token is configured
+pub async fn serve_metrics(State(state): State<AppState>, headers: HeaderMap) -> Result<Response> {
+    let Some(ref token) = state.config.metrics_token else {
+        return Err(AppError::NotFound("Not found".to_string()));
+    };
+    let presented = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "));
+    // Constant time, so the token can't be guessed byte by byte from timings
+    let matches = presented.is_some_and(|p| bool::from(p.as_bytes().ct_eq(token.as_bytes())));
+    if !matches {
+        return Err(AppError::Unauthorized);
+    }
+
+    Ok((
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render(),
+    )
+        .into_response())
+}
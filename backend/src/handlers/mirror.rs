@@ -0,0 +1,570 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::{
+    error::{AppError, Result},
+    handlers::{
+        file::{get_or_create_folder, remove_file_record, store_upload, NewUpload},
+        member::ensure_project_admin,
+    },
+    middleware::AuthUser,
+    models::{
+        ConflictStrategy, File, ManifestFile, Project, ProjectManifest, ProjectMirror,
+        SetProjectMirrorRequest, PROJECT_COLUMNS,
+    },
+    utils::{
+        ensure_project_writable, ensure_public_url, sanitize_file_name, validate_folder_path,
+        PublicResolver,
+    },
+    AppState,
+};
+
+const MIRROR_COLUMNS: &str = "project_id, remote_url, remote_project_id, remote_api_key, manifest_version, last_synced_at, last_error, sync_started_at, created_at, (SELECT COUNT(*) FROM mirrored_files m WHERE m.project_id = project_mirrors.project_id) AS file_count";
+
+/// A sync still marked as running after this long is assumed to have died
+/// with its instance and is taken over
+const MIRROR_CLAIM_SECS: f64 = 3600.0;
+
+/// Longest a request to the remote instance may take (downloads included)
+const MIRROR_REQUEST_TIMEOUT_SECS: u64 = 300;
+
+/// A remote file as last pulled
+#[derive(Debug, sqlx::FromRow)]
+struct MirroredFile {
+    file_id: Uuid,
+    remote_file_id: Uuid,
+    remote_path: String,
+    remote_sha256: Option<String>,
+    remote_modified_at: DateTime<Utc>,
+}
+
+impl MirroredFile {
+    /// Whether `remote` still matches the local copy; without a hash the
+    /// modification time decides
+    fn is_current(&self, remote: &ManifestFile) -> bool {
+        self.remote_path == remote.path
+            && self.remote_sha256 == remote.sha256
+            && (remote.sha256.is_some() || self.remote_modified_at == remote.modified_at)
+    }
+}
+
+/// Client for the remote instance. It only connects to public addresses and
+/// doesn't follow redirects, which could lead anywhere.
+fn mirror_client() -> std::result::Result<reqwest::Client, String> {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(MIRROR_REQUEST_TIMEOUT_SECS))
+        .redirect(reqwest::redirect::Policy::none())
+        .dns_resolver(Arc::new(PublicResolver))
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+/// The remote project's manifest, or None when it still has `version`.
+/// Errors are kept generic as they're shown to the project's admins; the
+/// details are logged.
+async fn fetch_manifest(
+    client: &reqwest::Client,
+    mirror: &ProjectMirror,
+    version: Option<&str>,
+) -> std::result::Result<Option<ProjectManifest>, String> {
+    let mut request = client
+        .get(format!(
+            "{}/api/v1/projects/{}/manifest",
+            mirror.remote_url, mirror.remote_project_id
+        ))
+        .header("X-API-Key", &mirror.remote_api_key);
+    if let Some(version) = version {
+        request = request.header(header::IF_NONE_MATCH, format!("\"{version}\""));
+    }
+    let response = request.send().await.map_err(|e| {
+        tracing::warn!("Mirror request to {} failed: {}", mirror.remote_url, e);
+        "Remote instance could not be reached".to_string()
+    })?;
+    match response.status() {
+        reqwest::StatusCode::NOT_MODIFIED => Ok(None),
+        status if status.is_success() => response
+            .json::<ProjectManifest>()
+            .await
+            .map(Some)
+            .map_err(|e| {
+                tracing::warn!("Invalid manifest from {}: {}", mirror.remote_url, e);
+                "Remote returned an invalid manifest".to_string()
+            }),
+        status => {
+            tracing::warn!(
+                "Remote {} returned {} for the manifest",
+                mirror.remote_url,
+                status
+            );
+            Err("Remote refused to serve the manifest".to_string())
+        }
+    }
+}
+
+/// Download a remote file, checking its size and hash against the manifest
+async fn download_remote_file(
+    client: &reqwest::Client,
+    mirror: &ProjectMirror,
+    remote: &ManifestFile,
+    max_size: usize,
+) -> std::result::Result<Vec<u8>, String> {
+    if remote.size as usize > max_size {
+        return Err(format!(
+            "{} exceeds the maximum file size of {max_size} bytes",
+            remote.path
+        ));
+    }
+    let failed = |e: &dyn std::fmt::Display| {
+        tracing::warn!(
+            "Mirror download of {} from {} failed: {}",
+            remote.path,
+            mirror.remote_url,
+            e
+        );
+        format!("Failed to download {}", remote.path)
+    };
+    let mut response = client
+        .get(format!("{}/api/v1/files/{}", mirror.remote_url, remote.id))
+        .header("X-API-Key", &mirror.remote_api_key)
+        .send()
+        .await
+        .map_err(|e| failed(&e))?;
+    if !response.status().is_success() {
+        return Err(failed(&response.status()));
+    }
+
+    let mut data = Vec::with_capacity(remote.size as usize);
+    while let Some(chunk) = response.chunk().await.map_err(|e| failed(&e))? {
+        if data.len() + chunk.len() > max_size {
+            return Err(format!(
+                "{} exceeds the maximum file size of {max_size} bytes",
+                remote.path
+            ));
+        }
+        data.extend_from_slice(&chunk);
+    }
+    if let Some(ref expected) = remote.sha256 {
+        if hex::encode(Sha256::digest(&data)) != *expected {
+            return Err(format!(
+                "{} doesn't match the SHA-256 in the manifest",
+                remote.path
+            ));
+        }
+    }
+    Ok(data)
+}
+
+async fn load_file(state: &AppState, file_id: Uuid) -> Result<File> {
+    sqlx::query_as::<_, File>(
+        "SELECT id, project_id, folder_id, original_name, stored_name, file_path, size, mime_type, upload_date, storage_encoding, precompressed_encodings, storage_tier, moderation_status FROM files WHERE id = $1",
+    )
+    .bind(file_id)
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or(AppError::NotFound("File not found".to_string()))
+}
+
+/// Store the downloaded content of `remote` and record it as its copy. An
+/// existing copy at the same path is overwritten in place; one at an old path
+/// is replaced by a new file.
+async fn store_remote_file(
+    state: &AppState,
+    project: &Project,
+    remote: &ManifestFile,
+    previous: Option<&MirroredFile>,
+    data: Vec<u8>,
+) -> Result<()> {
+    let (folder_path, file_name) = match remote.path.rsplit_once('/') {
+        Some((folder, name)) => (Some(validate_folder_path(folder)?), name),
+        None => (None, remote.path.as_str()),
+    };
+    let file_name = sanitize_file_name(file_name)?;
+
+    let replaces = match previous {
+        Some(previous) if previous.remote_path == remote.path => {
+            Some(load_file(state, previous.file_id).await?)
+        }
+        _ => None,
+    };
+    let in_place = replaces.is_some();
+    let upload = store_upload(
+        state,
+        project,
+        NewUpload {
+            file_name,
+            folder_path,
+            // A local file with the same name is kept; the copy gets a new name
+            on_conflict: (!in_place).then_some(ConflictStrategy::Rename),
+            description: None,
+            data,
+            replaces,
+            // Kept byte for byte, so the hash keeps matching the remote's
+            optimize_images: false,
+            keep_replaced: None,
+            dedupe_recent: false,
+        },
+        None,
+    )
+    .await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO mirrored_files (file_id, project_id, remote_file_id, remote_path, remote_sha256, remote_modified_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (project_id, remote_file_id) DO UPDATE
+        SET file_id = EXCLUDED.file_id, remote_path = EXCLUDED.remote_path,
+            remote_sha256 = EXCLUDED.remote_sha256, remote_modified_at = EXCLUDED.remote_modified_at
+        "#,
+    )
+    .bind(upload.file_id)
+    .bind(project.id)
+    .bind(remote.id)
+    .bind(&remote.path)
+    .bind(&remote.sha256)
+    .bind(remote.modified_at)
+    .execute(&state.pool)
+    .await?;
+
+    // The copy at the old path is no longer mapped to the remote file
+    if let Some(previous) = previous.filter(|_| !in_place) {
+        remove_file_record(state, &load_file(state, previous.file_id).await?).await?;
+    }
+    Ok(())
+}
+
+/// Bring a mirror up to date with its remote project: new and changed files
+/// are downloaded, copies of files removed remotely are deleted and remote
+/// folders are created with the remote's visibility. Returns the manifest
+/// version now held, or None when the remote hasn't changed. Files that fail
+/// don't stop the others; the sync then fails and is retried in full.
+async fn pull_mirror(
+    state: &AppState,
+    mirror: &ProjectMirror,
+) -> std::result::Result<Option<String>, String> {
+    let project = sqlx::query_as::<_, Project>(&format!(
+        "SELECT {PROJECT_COLUMNS} FROM projects WHERE id = $1"
+    ))
+    .bind(mirror.project_id)
+    .fetch_one(&state.pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    ensure_project_writable(&project).map_err(|e| e.to_string())?;
+    // The name may have been re-pointed since; IP literals skip the resolver
+    let remote_url = reqwest::Url::parse(&mirror.remote_url).map_err(|e| e.to_string())?;
+    ensure_public_url(&remote_url)
+        .await
+        .map_err(|_| "Remote address is not public".to_string())?;
+
+    let client = mirror_client()?;
+    let Some(manifest) =
+        fetch_manifest(&client, mirror, mirror.manifest_version.as_deref()).await?
+    else {
+        return Ok(None);
+    };
+    let mut errors = Vec::new();
+
+    {
+        let mut conn = state.pool.acquire().await.map_err(|e| e.to_string())?;
+        for folder in &manifest.folders {
+            let created = async {
+                let path = validate_folder_path(&folder.path)?;
+                let folder_id = get_or_create_folder(&mut conn, project.id, &path).await?;
+                sqlx::query("UPDATE folders SET is_public = $1 WHERE id = $2")
+                    .bind(folder.is_public)
+                    .bind(folder_id)
+                    .execute(&mut *conn)
+                    .await?;
+                Ok::<_, AppError>(())
+            }
+            .await;
+            if let Err(e) = created {
+                errors.push(format!("Folder {}: {e}", folder.path));
+            }
+        }
+    }
+
+    let mirrored = sqlx::query_as::<_, MirroredFile>(
+        "SELECT file_id, remote_file_id, remote_path, remote_sha256, remote_modified_at FROM mirrored_files WHERE project_id = $1",
+    )
+    .bind(project.id)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    let mut mirrored: HashMap<Uuid, MirroredFile> = mirrored
+        .into_iter()
+        .map(|file| (file.remote_file_id, file))
+        .collect();
+
+    // Removed remotely: delete the local copies first, which frees their
+    // names for files that took them over
+    let remote_ids: HashSet<Uuid> = manifest.files.iter().map(|file| file.id).collect();
+    let removed: Vec<Uuid> = mirrored
+        .keys()
+        .filter(|id| !remote_ids.contains(id))
+        .copied()
+        .collect();
+    for remote_id in removed {
+        let copy = mirrored.remove(&remote_id).expect("listed above");
+        let deleted =
+            async { remove_file_record(state, &load_file(state, copy.file_id).await?).await }.await;
+        if let Err(e) = deleted {
+            errors.push(format!("{}: {e}", copy.remote_path));
+        }
+    }
+
+    for remote in &manifest.files {
+        let previous = mirrored.get(&remote.id);
+        if previous.is_some_and(|copy| copy.is_current(remote)) {
+            continue;
+        }
+        let data =
+            match download_remote_file(&client, mirror, remote, state.config.max_file_size).await {
+                Ok(data) => data,
+                Err(e) => {
+                    errors.push(e);
+                    continue;
+                }
+            };
+        if let Err(e) = store_remote_file(state, &project, remote, previous, data).await {
+            errors.push(format!("{}: {e}", remote.path));
+        }
+    }
+
+    match errors.len() {
+        0 => Ok(Some(manifest.version)),
+        1 => Err(errors.remove(0)),
+        n => Err(format!("{} (and {} more)", errors[0], n - 1)),
+    }
+}
+
+/// Sync one mirror unless another sync of it is running. Returns whether the
+/// sync succeeded; the outcome is recorded on the mirror either way.
+pub async fn sync_mirror(state: &AppState, project_id: Uuid) -> std::result::Result<bool, String> {
+    let claimed = sqlx::query_as::<_, ProjectMirror>(&format!(
+        r#"
+        UPDATE project_mirrors SET sync_started_at = NOW()
+        WHERE project_id = $1
+          AND (sync_started_at IS NULL OR sync_started_at < NOW() - make_interval(secs => $2))
+        RETURNING {MIRROR_COLUMNS}
+        "#
+    ))
+    .bind(project_id)
+    .bind(MIRROR_CLAIM_SECS)
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    let Some(mirror) = claimed else {
+        return Ok(true);
+    };
+
+    let result = pull_mirror(state, &mirror).await;
+    if let Err(ref e) = result {
+        tracing::warn!("Mirror sync of project {} failed: {}", project_id, e);
+    }
+    let (version, error) = match result {
+        Ok(version) => (version, None),
+        Err(e) => (None, Some(e)),
+    };
+    sqlx::query(
+        r#"
+        UPDATE project_mirrors
+        SET sync_started_at = NULL, last_error = $2,
+            manifest_version = COALESCE($3, manifest_version),
+            last_synced_at = CASE WHEN $2 IS NULL THEN NOW() ELSE last_synced_at END
+        WHERE project_id = $1
+        "#,
+    )
+    .bind(project_id)
+    .bind(&error)
+    .bind(version)
+    .execute(&state.pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(error.is_none())
+}
+
+/// Sync every mirror, least recently synced first
+pub async fn run_mirror_syncs(state: &AppState) -> std::result::Result<(), String> {
+    let project_ids = sqlx::query_scalar::<_, Uuid>(
+        "SELECT project_id FROM project_mirrors ORDER BY last_synced_at NULLS FIRST",
+    )
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let mut failed = 0;
+    for project_id in project_ids {
+        if !sync_mirror(state, project_id).await? {
+            failed += 1;
+        }
+    }
+    if failed > 0 {
+        return Err(format!("{failed} mirrors failed to sync"));
+    }
+    Ok(())
+}
+
+async fn load_mirror(state: &AppState, project_id: Uuid) -> Result<ProjectMirror> {
+    sqlx::query_as::<_, ProjectMirror>(&format!(
+        "SELECT {MIRROR_COLUMNS} FROM project_mirrors WHERE project_id = $1"
+    ))
+    .bind(project_id)
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or(AppError::NotFound("Project is not a mirror".to_string()))
+}
+
+/// Sync status of a mirror project (owner or project admin)
+pub async fn get_project_mirror(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(project_id): Path<Uuid>,
+) -> Result<Json<ProjectMirror>> {
+    ensure_project_admin(&state, &auth_user, project_id).await?;
+    Ok(Json(load_mirror(&state, project_id).await?))
+}
+
+/// Make the project a mirror of a project on another instance, or point it
+/// at a different one, and start the first sync. The remote manifest must be
+/// readable with the key. Files already in the project stay; copies pulled
+/// from a previous remote become ordinary files.
+pub async fn set_project_mirror(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(project_id): Path<Uuid>,
+    Json(payload): Json<SetProjectMirrorRequest>,
+) -> Result<(StatusCode, Json<ProjectMirror>)> {
+    payload
+        .validate()
+        .map_err(|e| AppError::ValidationError(e.to_string()))?;
+    ensure_project_admin(&state, &auth_user, project_id).await?;
+
+    let url = reqwest::Url::parse(payload.remote_url.trim())
+        .ok()
+        .filter(|url| {
+            matches!(url.scheme(), "http" | "https")
+                && url.host().is_some()
+                && url.query().is_none()
+                && url.fragment().is_none()
+        })
+        .ok_or(AppError::BadRequest(
+            "remote_url must be an http(s) URL without a query".to_string(),
+        ))?;
+    ensure_public_url(&url).await.map_err(|_| {
+        AppError::BadRequest("remote_url must point to a public address".to_string())
+    })?;
+    let remote_url = url.as_str().trim_end_matches('/').to_string();
+
+    let mirror = ProjectMirror {
+        project_id,
+        remote_url,
+        remote_project_id: payload.remote_project_id,
+        remote_api_key: payload.api_key,
+        manifest_version: None,
+        last_synced_at: None,
+        last_error: None,
+        sync_started_at: None,
+        created_at: Utc::now(),
+        file_count: 0,
+    };
+    let client = mirror_client().map_err(AppError::InternalError)?;
+    fetch_manifest(&client, &mirror, None)
+        .await
+        .map_err(|e| AppError::BadRequest(format!("Remote project can't be read: {e}")))?;
+
+    let mut tx = state.pool.begin().await?;
+    // Copies of another remote's files are no longer tracked
+    sqlx::query(
+        r#"
+        DELETE FROM mirrored_files m USING project_mirrors p
+        WHERE m.project_id = p.project_id AND p.project_id = $1
+          AND (p.remote_url <> $2 OR p.remote_project_id <> $3)
+        "#,
+    )
+    .bind(project_id)
+    .bind(&mirror.remote_url)
+    .bind(mirror.remote_project_id)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query(
+        r#"
+        INSERT INTO project_mirrors (project_id, remote_url, remote_project_id, remote_api_key)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (project_id) DO UPDATE
+        SET remote_url = EXCLUDED.remote_url, remote_project_id = EXCLUDED.remote_project_id,
+            remote_api_key = EXCLUDED.remote_api_key, manifest_version = NULL, last_error = NULL
+        "#,
+    )
+    .bind(project_id)
+    .bind(&mirror.remote_url)
+    .bind(mirror.remote_project_id)
+    .bind(&mirror.remote_api_key)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    let sync_state = state.clone();
+    tokio::spawn(async move {
+        if let Err(e) = sync_mirror(&sync_state, project_id).await {
+            tracing::warn!("Mirror sync of project {} could not run: {}", project_id, e);
+        }
+    });
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(load_mirror(&state, project_id).await?),
+    ))
+}
+
+/// Start a sync now instead of waiting for the next scheduled one
+pub async fn sync_project_mirror(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(project_id): Path<Uuid>,
+) -> Result<(StatusCode, Json<ProjectMirror>)> {
+    ensure_project_admin(&state, &auth_user, project_id).await?;
+    let mirror = load_mirror(&state, project_id).await?;
+
+    let sync_state = state.clone();
+    tokio::spawn(async move {
+        if let Err(e) = sync_mirror(&sync_state, project_id).await {
+            tracing::warn!("Mirror sync of project {} could not run: {}", project_id, e);
+        }
+    });
+
+    Ok((StatusCode::ACCEPTED, Json(mirror)))
+}
+
+/// Stop mirroring; the pulled files stay as ordinary files
+pub async fn delete_project_mirror(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(project_id): Path<Uuid>,
+) -> Result<Json<serde_json::Value>> {
+    ensure_project_admin(&state, &auth_user, project_id).await?;
+    let deleted = sqlx::query("DELETE FROM project_mirrors WHERE project_id = $1")
+        .bind(project_id)
+        .execute(&state.pool)
+        .await?
+        .rows_affected();
+    if deleted == 0 {
+        return Err(AppError::NotFound("Project is not a mirror".to_string()));
+    }
+
+    Ok(Json(serde_json::json!({
+        "message": "Mirroring stopped"
+    })))
+}
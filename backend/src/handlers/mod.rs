@@ -1,4 +1,33 @@
+pub mod append;
 pub mod auth;
+pub mod backup;
+pub mod changes;
+pub mod content;
+pub mod database;
+pub mod delta;
+pub mod event;
+pub mod export;
 pub mod file;
 pub mod folder;
+pub mod ftp;
+pub mod inbound_email;
+pub mod ingest;
+pub mod integrity;
+pub mod jobs;
+pub mod manifest;
+pub mod media;
+pub mod member;
+pub mod metrics;
+pub mod mirror;
+pub mod multipart;
+pub mod notification;
 pub mod project;
+pub mod purge;
+pub mod render;
+pub mod replication;
+pub mod report;
+pub mod robots;
+pub mod share;
+pub mod star;
+pub mod storage;
+pub mod version;
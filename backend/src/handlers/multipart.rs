@@ -0,0 +1,504 @@
+use std::{convert::Infallible, time::Duration};
+
+use axum::{
+    body::Body,
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::sse::{Event, KeepAlive, Sse},
+    Json,
+};
+use futures::{Stream, StreamExt};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use tokio::{fs, io::AsyncWriteExt};
+use uuid::Uuid;
+
+use crate::{
+    error::{AppError, Result},
+    handlers::file::{
+        normalize_description, project_for_api_key, store_upload, upload_permit, NewUpload,
+    },
+    models::{
+        CompleteMultipartRequest, ConflictStrategy, InitiateMultipartRequest, MultipartPart,
+        MultipartUpload, MultipartUploadProgress, MultipartUploadStatus, UploadResponse,
+    },
+    utils::{
+        ensure_project_writable, multipart_upload_dir, remove_multipart_parts, sanitize_file_name,
+        validate_folder_path, MAX_MULTIPART_PARTS, MULTIPART_UPLOAD_COLUMNS,
+        MULTIPART_UPLOAD_EXPIRY_HOURS,
+    },
+    AppState,
+};
+
+/// How often an upload's progress is checked for its event stream
+const MULTIPART_PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A multipart upload of the calling project
+async fn load_upload(pool: &PgPool, project_id: Uuid, upload_id: Uuid) -> Result<MultipartUpload> {
+    sqlx::query_as::<_, MultipartUpload>(&format!(
+        "SELECT {MULTIPART_UPLOAD_COLUMNS} FROM multipart_uploads WHERE id = $1 AND project_id = $2"
+    ))
+    .bind(upload_id)
+    .bind(project_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or(AppError::NotFound("Upload not found".to_string()))
+}
+
+async fn load_parts(pool: &PgPool, upload_id: Uuid) -> Result<Vec<MultipartPart>> {
+    Ok(sqlx::query_as::<_, MultipartPart>(
+        "SELECT part_number, stored_name, size, sha256, uploaded_at FROM multipart_upload_parts WHERE upload_id = $1 ORDER BY part_number",
+    )
+    .bind(upload_id)
+    .fetch_all(pool)
+    .await?)
+}
+
+fn upload_status(
+    state: &AppState,
+    upload: MultipartUpload,
+    parts: Vec<MultipartPart>,
+) -> MultipartUploadStatus {
+    MultipartUploadStatus {
+        uploaded_bytes: parts.iter().map(|part| part.size).sum(),
+        receiving_bytes: state.multipart_progress.receiving(upload.id),
+        upload,
+        parts,
+    }
+}
+
+/// Progress of an upload of the project; `None` once it's gone (completed,
+/// aborted or expired)
+async fn load_progress(
+    state: &AppState,
+    project_id: Uuid,
+    upload_id: Uuid,
+) -> Result<Option<MultipartUploadProgress>> {
+    let row: Option<(String, Option<i64>, i64, i64)> = sqlx::query_as(
+        r#"
+        SELECT u.status, u.expected_size, COUNT(p.part_number), COALESCE(SUM(p.size), 0)::BIGINT
+        FROM multipart_uploads u
+        LEFT JOIN multipart_upload_parts p ON p.upload_id = u.id
+        WHERE u.id = $1 AND u.project_id = $2
+        GROUP BY u.id
+        "#,
+    )
+    .bind(upload_id)
+    .bind(project_id)
+    .fetch_optional(&state.pool)
+    .await?;
+    Ok(row.map(
+        |(status, expected_size, parts_received, uploaded_bytes)| MultipartUploadProgress {
+            status,
+            parts_received,
+            uploaded_bytes,
+            receiving_bytes: state.multipart_progress.receiving(upload_id),
+            expected_size,
+        },
+    ))
+}
+
+/// Start a multipart upload. The name, folder and options are checked now and
+/// applied when the upload is completed.
+pub async fn initiate_multipart_upload(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<InitiateMultipartRequest>,
+) -> Result<(StatusCode, Json<MultipartUploadStatus>)> {
+    let project = project_for_api_key(&state.pool, &headers).await?;
+    ensure_project_writable(&project)?;
+
+    let file_name = sanitize_file_name(&payload.file_name)?;
+    let folder_path = payload
+        .folder_path
+        .filter(|p| !p.is_empty())
+        .map(|p| validate_folder_path(&p))
+        .transpose()?;
+    let description = payload
+        .description
+        .as_deref()
+        .map(normalize_description)
+        .transpose()?
+        .flatten();
+    let on_conflict = payload
+        .on_conflict
+        .filter(|s| !s.trim().is_empty())
+        .map(|s| {
+            s.parse::<ConflictStrategy>()
+                .map(|_| s.trim().to_lowercase())
+        })
+        .transpose()
+        .map_err(AppError::BadRequest)?;
+    if let Some(expected_size) = payload.expected_size {
+        if !(1..=state.config.max_file_size as i64).contains(&expected_size) {
+            return Err(AppError::BadRequest(format!(
+                "expected_size must be between 1 and {} bytes",
+                state.config.max_file_size
+            )));
+        }
+    }
+
+    let upload = sqlx::query_as::<_, MultipartUpload>(&format!(
+        r#"
+        INSERT INTO multipart_uploads (project_id, file_name, folder_path, description, on_conflict, expires_at, expected_size)
+        VALUES ($1, $2, $3, $4, $5, NOW() + make_interval(hours => $6), $7)
+        RETURNING {MULTIPART_UPLOAD_COLUMNS}
+        "#
+    ))
+    .bind(project.id)
+    .bind(&file_name)
+    .bind(&folder_path)
+    .bind(&description)
+    .bind(&on_conflict)
+    .bind(MULTIPART_UPLOAD_EXPIRY_HOURS)
+    .bind(payload.expected_size)
+    .fetch_one(&state.pool)
+    .await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(upload_status(&state, upload, Vec::new())),
+    ))
+}
+
+/// An upload with the parts received so far, e.g. to resume after a restart
+pub async fn get_multipart_upload(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(upload_id): Path<Uuid>,
+) -> Result<Json<MultipartUploadStatus>> {
+    let project = project_for_api_key(&state.pool, &headers).await?;
+    let upload = load_upload(&state.pool, project.id, upload_id).await?;
+    let parts = load_parts(&state.pool, upload.id).await?;
+    Ok(Json(upload_status(&state, upload, parts)))
+}
+
+/// Server-sent events for an upload's progress: a `progress` event whenever
+/// it changes, then `end` once the upload is completed, aborted or expired
+pub async fn multipart_upload_events(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(upload_id): Path<Uuid>,
+) -> Result<Sse<impl Stream<Item = std::result::Result<Event, Infallible>>>> {
+    let project = project_for_api_key(&state.pool, &headers).await?;
+    load_upload(&state.pool, project.id, upload_id).await?;
+
+    let events = futures::stream::unfold(
+        (state, None::<MultipartUploadProgress>, false),
+        move |(state, last, ended)| async move {
+            if ended {
+                return None;
+            }
+            let mut first = last.is_none();
+            loop {
+                if !first {
+                    tokio::time::sleep(MULTIPART_PROGRESS_INTERVAL).await;
+                }
+                first = false;
+                match load_progress(&state, project.id, upload_id).await {
+                    Ok(Some(progress)) if last.as_ref() == Some(&progress) => {}
+                    Ok(Some(progress)) => {
+                        let event = Event::default()
+                            .event("progress")
+                            .json_data(&progress)
+                            .expect("progress serializes to JSON");
+                        return Some((Ok(event), (state, Some(progress), false)));
+                    }
+                    Ok(None) => {
+                        let event = Event::default().event("end").data("{}");
+                        return Some((Ok(event), (state, last, true)));
+                    }
+                    Err(e) => {
+                        tracing::warn!("Failed to check progress of upload {}: {}", upload_id, e)
+                    }
+                }
+            }
+        },
+    );
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+/// Receive one part (the raw request body). Parts may arrive concurrently and
+/// in any order; sending a part number again replaces that part.
+pub async fn upload_multipart_part(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path((upload_id, part_number)): Path<(Uuid, i32)>,
+    body: Body,
+) -> Result<Json<MultipartPart>> {
+    let project = project_for_api_key(&state.pool, &headers).await?;
+    ensure_project_writable(&project)?;
+    if !(1..=MAX_MULTIPART_PARTS).contains(&part_number) {
+        return Err(AppError::BadRequest(format!(
+            "Part numbers must be between 1 and {MAX_MULTIPART_PARTS}"
+        )));
+    }
+    let upload = load_upload(&state.pool, project.id, upload_id).await?;
+    if upload.status != "uploading" {
+        return Err(AppError::Conflict(
+            "Upload is being completed and no longer accepts parts".to_string(),
+        ));
+    }
+    let _upload_permit = upload_permit(&state, &project)?;
+
+    // The assembled file must still fit within the file size limit
+    let other_parts: i64 = sqlx::query_scalar(
+        "SELECT COALESCE(SUM(size), 0)::BIGINT FROM multipart_upload_parts WHERE upload_id = $1 AND part_number <> $2",
+    )
+    .bind(upload.id)
+    .bind(part_number)
+    .fetch_one(&state.pool)
+    .await?;
+    let max_size = state.config.max_file_size as i64 - other_parts;
+
+    let dir = multipart_upload_dir(&state.config.storage_path, project.id, upload.id);
+    fs::create_dir_all(&dir)
+        .await
+        .map_err(|e| AppError::FileError(format!("Failed to create directory: {e}")))?;
+    let stored_name = format!("{part_number}-{}", Uuid::new_v4());
+    let part_path = dir.join(&stored_name);
+
+    // Stream the body to disk, hashing as it goes; an unusable part is removed again
+    let mut receiving = state.multipart_progress.start_part(upload.id);
+    let written: Result<(i64, String)> = async {
+        let mut file = fs::File::create(&part_path)
+            .await
+            .map_err(|e| AppError::FileError(format!("Failed to create part: {e}")))?;
+        let mut hasher = Sha256::new();
+        let mut size = 0i64;
+        let mut stream = body.into_data_stream();
+        while let Some(chunk) = stream.next().await {
+            let chunk =
+                chunk.map_err(|e| AppError::BadRequest(format!("Failed to read part: {e}")))?;
+            size += chunk.len() as i64;
+            if size > max_size {
+                return Err(AppError::BadRequest(format!(
+                    "File size exceeds maximum of {} bytes",
+                    state.config.max_file_size
+                )));
+            }
+            hasher.update(&chunk);
+            file.write_all(&chunk)
+                .await
+                .map_err(|e| AppError::FileError(format!("Failed to write part: {e}")))?;
+            receiving.add(chunk.len() as i64);
+        }
+        file.flush()
+            .await
+            .map_err(|e| AppError::FileError(format!("Failed to write part: {e}")))?;
+        if size == 0 {
+            return Err(AppError::BadRequest("Parts cannot be empty".to_string()));
+        }
+        Ok((size, hex::encode(hasher.finalize())))
+    }
+    .await;
+
+    // Record the part unless a completion has started in the meantime
+    let recorded: Result<(MultipartPart, Option<String>)> = async {
+        let (size, sha256) = written?;
+        let mut tx = state.pool.begin().await?;
+        let status: String =
+            sqlx::query_scalar("SELECT status FROM multipart_uploads WHERE id = $1 FOR UPDATE")
+                .bind(upload.id)
+                .fetch_optional(&mut *tx)
+                .await?
+                .ok_or(AppError::NotFound("Upload not found".to_string()))?;
+        if status != "uploading" {
+            return Err(AppError::Conflict(
+                "Upload is being completed and no longer accepts parts".to_string(),
+            ));
+        }
+        let replaced: Option<String> = sqlx::query_scalar(
+            "SELECT stored_name FROM multipart_upload_parts WHERE upload_id = $1 AND part_number = $2",
+        )
+        .bind(upload.id)
+        .bind(part_number)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let part = sqlx::query_as::<_, MultipartPart>(
+            r#"
+            INSERT INTO multipart_upload_parts (upload_id, part_number, stored_name, size, sha256)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (upload_id, part_number) DO UPDATE
+            SET stored_name = EXCLUDED.stored_name, size = EXCLUDED.size,
+                sha256 = EXCLUDED.sha256, uploaded_at = NOW()
+            RETURNING part_number, stored_name, size, sha256, uploaded_at
+            "#,
+        )
+        .bind(upload.id)
+        .bind(part_number)
+        .bind(&stored_name)
+        .bind(size)
+        .bind(&sha256)
+        .fetch_one(&mut *tx)
+        .await?;
+        sqlx::query(
+            "UPDATE multipart_uploads SET expires_at = NOW() + make_interval(hours => $2) WHERE id = $1",
+        )
+        .bind(upload.id)
+        .bind(MULTIPART_UPLOAD_EXPIRY_HOURS)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok((part, replaced))
+    }
+    .await;
+    drop(receiving);
+
+    let (part, replaced) = match recorded {
+        Ok(recorded) => recorded,
+        Err(e) => {
+            let _ = fs::remove_file(&part_path).await;
+            return Err(e);
+        }
+    };
+    if let Some(replaced) = replaced {
+        let _ = fs::remove_file(dir.join(replaced)).await;
+    }
+
+    Ok(Json(part))
+}
+
+/// Assemble the listed parts into a file and store it like a regular upload.
+/// Parts that aren't listed are discarded.
+pub async fn complete_multipart_upload(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(upload_id): Path<Uuid>,
+    Json(payload): Json<CompleteMultipartRequest>,
+) -> Result<Json<UploadResponse>> {
+    let project = project_for_api_key(&state.pool, &headers).await?;
+    ensure_project_writable(&project)?;
+    load_upload(&state.pool, project.id, upload_id).await?;
+
+    if payload.parts.is_empty() {
+        return Err(AppError::BadRequest("No parts listed".to_string()));
+    }
+    if payload
+        .parts
+        .windows(2)
+        .any(|pair| pair[0].part_number >= pair[1].part_number)
+    {
+        return Err(AppError::BadRequest(
+            "Parts must be listed in ascending part number order".to_string(),
+        ));
+    }
+    let _upload_permit = upload_permit(&state, &project)?;
+
+    // Claim the upload; parts arriving from now on are rejected
+    let upload = sqlx::query_as::<_, MultipartUpload>(&format!(
+        "UPDATE multipart_uploads SET status = 'completing' WHERE id = $1 AND status = 'uploading' RETURNING {MULTIPART_UPLOAD_COLUMNS}"
+    ))
+    .bind(upload_id)
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or(AppError::Conflict(
+        "Upload is already being completed".to_string(),
+    ))?;
+
+    let result: Result<UploadResponse> = async {
+        let parts = load_parts(&state.pool, upload.id).await?;
+        let dir = multipart_upload_dir(&state.config.storage_path, project.id, upload.id);
+
+        let mut data = Vec::new();
+        for listed in &payload.parts {
+            let part = parts
+                .iter()
+                .find(|part| part.part_number == listed.part_number)
+                .ok_or_else(|| {
+                    AppError::BadRequest(format!("Part {} was not uploaded", listed.part_number))
+                })?;
+            if !part.sha256.eq_ignore_ascii_case(listed.sha256.trim()) {
+                return Err(AppError::BadRequest(format!(
+                    "Part {} does not match the uploaded part's sha256",
+                    listed.part_number
+                )));
+            }
+            if data.len() as i64 + part.size > state.config.max_file_size as i64 {
+                return Err(AppError::BadRequest(format!(
+                    "File size exceeds maximum of {} bytes",
+                    state.config.max_file_size
+                )));
+            }
+            let content = fs::read(dir.join(&part.stored_name))
+                .await
+                .map_err(|e| AppError::FileError(format!("Failed to read part: {e}")))?;
+            data.extend_from_slice(&content);
+        }
+
+        let on_conflict = upload
+            .on_conflict
+            .as_deref()
+            .map(str::parse::<ConflictStrategy>)
+            .transpose()
+            .map_err(AppError::InternalError)?;
+        store_upload(
+            &state,
+            &project,
+            NewUpload {
+                file_name: upload.file_name.clone(),
+                folder_path: upload.folder_path.clone(),
+                on_conflict,
+                description: upload.description.clone(),
+                data,
+                replaces: None,
+                optimize_images: true,
+                keep_replaced: None,
+                dedupe_recent: true,
+            },
+            None,
+        )
+        .await
+    }
+    .await;
+
+    match result {
+        Ok(response) => {
+            if let Err(e) = sqlx::query("DELETE FROM multipart_uploads WHERE id = $1")
+                .bind(upload.id)
+                .execute(&state.pool)
+                .await
+            {
+                tracing::warn!(
+                    "Failed to remove completed multipart upload {}: {}",
+                    upload.id,
+                    e
+                );
+            }
+            remove_multipart_parts(&state.config.storage_path, project.id, upload.id).await;
+            Ok(Json(response))
+        }
+        Err(e) => {
+            // Keep the parts so the client can fix the part list and try again
+            sqlx::query("UPDATE multipart_uploads SET status = 'uploading' WHERE id = $1")
+                .bind(upload.id)
+                .execute(&state.pool)
+                .await?;
+            Err(e)
+        }
+    }
+}
+
+/// Abort an upload and discard its parts
+pub async fn abort_multipart_upload(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(upload_id): Path<Uuid>,
+) -> Result<StatusCode> {
+    let project = project_for_api_key(&state.pool, &headers).await?;
+    let upload = load_upload(&state.pool, project.id, upload_id).await?;
+
+    let aborted =
+        sqlx::query("DELETE FROM multipart_uploads WHERE id = $1 AND status = 'uploading'")
+            .bind(upload.id)
+            .execute(&state.pool)
+            .await?;
+    if aborted.rows_affected() == 0 {
+        return Err(AppError::Conflict(
+            "Upload is being completed and can't be aborted".to_string(),
+        ));
+    }
+    remove_multipart_parts(&state.config.storage_path, project.id, upload.id).await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
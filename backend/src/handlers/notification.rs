@@ -0,0 +1,103 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::{
+    error::{AppError, Result},
+    middleware::AuthUser,
+    models::{CreateNotificationChannelRequest, NotificationChannel},
+    AppState,
+};
+
+use super::member::ensure_project_admin;
+
+const CHANNEL_COLUMNS: &str =
+    "id, project_id, kind, webhook_url, events, template, large_upload_bytes, created_at";
+
+/// Add a Slack or Discord webhook that receives messages for the selected events
+pub async fn create_notification_channel(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(project_id): Path<Uuid>,
+    Json(payload): Json<CreateNotificationChannelRequest>,
+) -> Result<Json<NotificationChannel>> {
+    payload
+        .validate()
+        .map_err(|e| AppError::ValidationError(e.to_string()))?;
+    ensure_project_admin(&state, &auth_user, project_id).await?;
+
+    if !payload.kind.accepts_webhook_url(&payload.webhook_url) {
+        return Err(AppError::BadRequest(format!(
+            "Not a {:?} incoming webhook URL",
+            payload.kind
+        )));
+    }
+
+    let mut events: Vec<&str> = payload.events.iter().map(|e| e.as_str()).collect();
+    events.sort_unstable();
+    events.dedup();
+
+    let channel = sqlx::query_as::<_, NotificationChannel>(&format!(
+        r#"
+        INSERT INTO notification_channels (project_id, kind, webhook_url, events, template, large_upload_bytes, created_by)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING {CHANNEL_COLUMNS}
+        "#
+    ))
+    .bind(project_id)
+    .bind(payload.kind)
+    .bind(&payload.webhook_url)
+    .bind(&events)
+    .bind(&payload.template)
+    .bind(payload.large_upload_bytes)
+    .bind(auth_user.id)
+    .fetch_one(&state.pool)
+    .await?;
+
+    Ok(Json(channel))
+}
+
+pub async fn list_notification_channels(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(project_id): Path<Uuid>,
+) -> Result<Json<Vec<NotificationChannel>>> {
+    ensure_project_admin(&state, &auth_user, project_id).await?;
+
+    let channels = sqlx::query_as::<_, NotificationChannel>(&format!(
+        "SELECT {CHANNEL_COLUMNS} FROM notification_channels WHERE project_id = $1 ORDER BY created_at"
+    ))
+    .bind(project_id)
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(Json(channels))
+}
+
+/// Remove a channel; messages still queued for it are dropped
+pub async fn delete_notification_channel(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path((project_id, channel_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<serde_json::Value>> {
+    ensure_project_admin(&state, &auth_user, project_id).await?;
+
+    let result = sqlx::query("DELETE FROM notification_channels WHERE id = $1 AND project_id = $2")
+        .bind(channel_id)
+        .bind(project_id)
+        .execute(&state.pool)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound(
+            "Notification channel not found".to_string(),
+        ));
+    }
+
+    Ok(Json(serde_json::json!({
+        "message": "Notification channel deleted successfully"
+    })))
+}
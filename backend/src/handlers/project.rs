@@ -1,22 +1,85 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     Json,
 };
-use std::path::PathBuf;
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use std::{collections::BTreeMap, path::PathBuf};
 use tokio::fs;
 use uuid::Uuid;
 use validator::Validate;
 
 use crate::{
+    config::Config,
     error::{AppError, Result},
-    middleware::AuthUser,
-    models::{CreateProjectRequest, File, Project, ProjectResponse, UpdateProjectRequest},
+    handlers::folder::set_project_folders_visibility,
+    middleware::{AuthUser, RequirePermission},
+    models::{
+        AdminProjectResponse, CreateProjectRequest, CustomDomainVerification, File, Project,
+        ProjectResponse, PublicFoldersWarning, UpdateProjectRequest, UpdateProjectResponse,
+        PROJECT_COLUMNS,
+    },
+    utils::{
+        admin_override, delete_cold_blob, ensure_project_writable, has_verification_record, perm,
+        queue_cdn_purge, queue_project_purge, queue_replication, record_file_events,
+        verification_record_name, AdminQuery, FileEventKind, PageQuery, Paginated, RolePermission,
+        MULTIPART_DIR,
+    },
     AppState,
 };
 
+/// Custom domains are stored lowercase and may not sit under the shared files
+/// domain or be one of the hosts serving the API
+fn normalize_custom_domain(domain: &str, config: &Config) -> Result<String> {
+    let domain = domain.to_lowercase();
+    if config.reserved_hosts().contains(&domain) {
+        return Err(AppError::ValidationError(format!(
+            "{domain} serves this API and can't be a custom domain"
+        )));
+    }
+    if let Some(ref files_domain) = config.public_files_domain {
+        if domain == *files_domain || domain.ends_with(&format!(".{files_domain}")) {
+            return Err(AppError::ValidationError(format!(
+                "Custom domain can't be under {files_domain}; set a slug instead"
+            )));
+        }
+    }
+    Ok(domain)
+}
+
+/// Slug and custom domain are unique across projects
+fn map_host_conflict(e: sqlx::Error) -> AppError {
+    match e {
+        sqlx::Error::Database(ref db_err) if db_err.is_unique_violation() => {
+            AppError::Conflict("Slug or custom domain is already in use".to_string())
+        }
+        e => e.into(),
+    }
+}
+
+/// Store response header names lowercased, as they are sent
+fn normalize_response_headers(
+    headers: Option<BTreeMap<String, String>>,
+) -> BTreeMap<String, String> {
+    headers
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(name, value)| (name.to_ascii_lowercase(), value))
+        .collect()
+}
+
+/// Store country codes uppercased so lookups compare consistently
+fn normalize_country_codes(codes: Option<Vec<String>>) -> Vec<String> {
+    codes
+        .unwrap_or_default()
+        .into_iter()
+        .map(|c| c.to_uppercase())
+        .collect()
+}
+
 pub async fn create_project(
     State(state): State<AppState>,
-    auth_user: AuthUser,
+    RequirePermission(auth_user, _): RequirePermission<perm::ProjectsCreate>,
     Json(payload): Json<CreateProjectRequest>,
 ) -> Result<Json<Project>> {
     payload
@@ -24,78 +87,173 @@ pub async fn create_project(
         .map_err(|e| AppError::ValidationError(e.to_string()))?;
 
     let is_public = payload.is_public.unwrap_or(false);
+    let allowed_origins = payload.allowed_origins.unwrap_or_default();
+    let geo_allowed_countries = normalize_country_codes(payload.geo_allowed_countries);
+    let geo_blocked_countries = normalize_country_codes(payload.geo_blocked_countries);
+    let custom_domain = payload
+        .custom_domain
+        .map(|d| normalize_custom_domain(&d, &state.config))
+        .transpose()?;
+    let response_headers = normalize_response_headers(payload.response_headers);
 
-    let project = sqlx::query_as::<_, Project>(
+    let project = sqlx::query_as::<_, Project>(&format!(
         r#"
-        INSERT INTO projects (user_id, name, is_public)
-        VALUES ($1, $2, $3)
-        RETURNING id, user_id, name, api_key, is_public, created_at
-        "#,
-    )
+        INSERT INTO projects (user_id, name, is_public, allowed_origins, geo_allowed_countries, geo_blocked_countries, download_bandwidth_limit, max_concurrent_uploads, slug, custom_domain, cold_storage_after_days, storage_quota_bytes, default_folder_visibility, image_max_dimension, image_quality, image_png_to_webp, image_keep_original, response_headers, noindex)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, COALESCE($15, 85), COALESCE($16, FALSE), COALESCE($17, FALSE), $18, COALESCE($19, FALSE))
+        RETURNING {PROJECT_COLUMNS}
+        "#
+    ))
     .bind(auth_user.id)
     .bind(&payload.name)
     .bind(is_public)
+    .bind(&allowed_origins)
+    .bind(&geo_allowed_countries)
+    .bind(&geo_blocked_countries)
+    .bind(payload.download_bandwidth_limit)
+    .bind(payload.max_concurrent_uploads)
+    .bind(&payload.slug)
+    .bind(&custom_domain)
+    .bind(payload.cold_storage_after_days)
+    .bind(payload.storage_quota_bytes)
+    .bind(payload.default_folder_visibility.unwrap_or_default())
+    .bind(payload.image_max_dimension)
+    .bind(payload.image_quality)
+    .bind(payload.image_png_to_webp)
+    .bind(payload.image_keep_original)
+    .bind(sqlx::types::Json(response_headers))
+    .bind(payload.noindex)
     .fetch_one(&state.pool)
-    .await?;
+    .await
+    .map_err(map_host_conflict)?;
 
     Ok(Json(project))
 }
 
+/// The user's projects, newest first
 pub async fn list_projects(
     State(state): State<AppState>,
     auth_user: AuthUser,
-) -> Result<Json<Vec<ProjectResponse>>> {
-    // Single query with LEFT JOIN to avoid N+1 problem
+    Query(page): Query<PageQuery>,
+) -> Result<Json<Paginated<ProjectResponse>>> {
+    let limit = page.limit();
+    let after: Option<(DateTime<Utc>, Uuid)> = page.after()?;
+
+    // File counts and sizes come from the cached counters, not the files table
     let projects = sqlx::query_as::<_, ProjectResponse>(
         r#"
         SELECT
             p.id,
             p.name,
             p.api_key,
+            p.previous_api_key,
+            p.previous_api_key_expires_at,
+            p.slug,
+            p.custom_domain,
+            p.custom_domain_verification_token,
+            p.custom_domain_verified_at,
             p.is_public,
             p.created_at,
-            COUNT(f.id)::bigint as file_count,
-            COALESCE(SUM(f.size), 0)::bigint as total_size
+            p.allowed_origins,
+            p.geo_allowed_countries,
+            p.geo_blocked_countries,
+            p.download_bandwidth_limit,
+            p.max_concurrent_uploads,
+            p.cold_storage_after_days,
+            p.storage_quota_bytes,
+            p.archived,
+            p.deletion_scheduled_at,
+            p.default_folder_visibility,
+            p.image_max_dimension,
+            p.image_quality,
+            p.image_png_to_webp,
+            p.image_keep_original,
+            p.response_headers,
+            p.noindex,
+            COALESCE(s.file_count, 0) as file_count,
+            COALESCE(s.total_size, 0) as total_size,
+            p.api_key_last_used_at,
+            host(p.api_key_last_used_ip) as api_key_last_used_ip,
+            p.api_key_request_count
         FROM projects p
-        LEFT JOIN files f ON f.project_id = p.id
-        WHERE p.user_id = $1
-        GROUP BY p.id, p.name, p.api_key, p.is_public, p.created_at
-        ORDER BY p.created_at DESC
+        LEFT JOIN project_stats s ON s.project_id = p.id
+        WHERE p.user_id = $1 AND ($2::timestamptz IS NULL OR (p.created_at, p.id) < ($2, $3))
+        ORDER BY p.created_at DESC, p.id DESC
+        LIMIT $4
         "#,
     )
     .bind(auth_user.id)
-    .fetch_all(&state.pool)
+    .bind(after.map(|(created_at, _)| created_at))
+    .bind(after.map(|(_, id)| id))
+    .bind(limit + 1)
+    .fetch_all(state.read_pool.get())
     .await?;
+    let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM projects WHERE user_id = $1")
+        .bind(auth_user.id)
+        .fetch_one(state.read_pool.get())
+        .await?;
 
-    Ok(Json(projects))
+    Ok(Json(Paginated::new(projects, limit, total, |p| {
+        (p.created_at, p.id)
+    })))
 }
 
 pub async fn get_project(
     State(state): State<AppState>,
     auth_user: AuthUser,
     Path(id): Path<Uuid>,
+    Query(admin): Query<AdminQuery>,
 ) -> Result<Json<ProjectResponse>> {
-    let project = sqlx::query_as::<_, Project>(
+    let as_admin = admin_override(
+        &auth_user,
+        &admin,
+        RolePermission::AdminProjectsRead,
+        "get_project",
+        id,
+    )?;
+
+    let project = sqlx::query_as::<_, Project>(&format!(
         r#"
-        SELECT id, user_id, name, api_key, is_public, created_at
+        SELECT {PROJECT_COLUMNS}
         FROM projects
-        WHERE id = $1 AND user_id = $2
-        "#,
-    )
+        WHERE id = $1 AND (user_id = $2 OR $3)
+        "#
+    ))
     .bind(id)
     .bind(auth_user.id)
+    .bind(as_admin)
     .fetch_optional(&state.pool)
     .await?
     .ok_or(AppError::NotFound("Project not found".to_string()))?;
 
     let stats = sqlx::query_as::<_, (Option<i64>, Option<i64>)>(
         r#"
-        SELECT COUNT(*)::bigint, COALESCE(SUM(size), 0)::bigint
-        FROM files
+        SELECT COALESCE(MAX(file_count), 0), COALESCE(MAX(total_size), 0)
+        FROM project_stats
         WHERE project_id = $1
         "#,
     )
     .bind(project.id)
+    .fetch_one(state.read_pool.get())
+    .await?;
+
+    let usage = sqlx::query_as::<
+        _,
+        (
+            Option<DateTime<Utc>>,
+            Option<String>,
+            i64,
+            Option<String>,
+            Option<DateTime<Utc>>,
+        ),
+    >(
+        r#"
+        SELECT api_key_last_used_at, host(api_key_last_used_ip), api_key_request_count,
+            custom_domain_verification_token, custom_domain_verified_at
+        FROM projects
+        WHERE id = $1
+        "#,
+    )
+    .bind(project.id)
     .fetch_one(&state.pool)
     .await?;
 
@@ -103,10 +261,35 @@ pub async fn get_project(
         id: project.id,
         name: project.name,
         api_key: project.api_key,
+        previous_api_key: project.previous_api_key,
+        previous_api_key_expires_at: project.previous_api_key_expires_at,
+        slug: project.slug,
+        custom_domain: project.custom_domain,
+        custom_domain_verification_token: usage.3,
+        custom_domain_verified_at: usage.4,
         is_public: project.is_public,
         created_at: project.created_at,
+        allowed_origins: project.allowed_origins,
+        geo_allowed_countries: project.geo_allowed_countries,
+        geo_blocked_countries: project.geo_blocked_countries,
+        download_bandwidth_limit: project.download_bandwidth_limit,
+        max_concurrent_uploads: project.max_concurrent_uploads,
+        cold_storage_after_days: project.cold_storage_after_days,
+        storage_quota_bytes: project.storage_quota_bytes,
+        archived: project.archived,
+        deletion_scheduled_at: project.deletion_scheduled_at,
+        default_folder_visibility: project.default_folder_visibility,
+        image_max_dimension: project.image_max_dimension,
+        image_quality: project.image_quality,
+        image_png_to_webp: project.image_png_to_webp,
+        image_keep_original: project.image_keep_original,
+        response_headers: project.response_headers,
+        noindex: project.noindex,
         file_count: stats.0,
         total_size: stats.1,
+        api_key_last_used_at: usage.0,
+        api_key_last_used_ip: usage.1,
+        api_key_request_count: usage.2,
     }))
 }
 
@@ -115,15 +298,15 @@ pub async fn update_project(
     auth_user: AuthUser,
     Path(id): Path<Uuid>,
     Json(payload): Json<UpdateProjectRequest>,
-) -> Result<Json<Project>> {
+) -> Result<Json<UpdateProjectResponse>> {
     payload
         .validate()
         .map_err(|e| AppError::ValidationError(e.to_string()))?;
 
     // Check if project exists and belongs to user
-    let existing = sqlx::query_as::<_, Project>(
-        "SELECT id, user_id, name, api_key, is_public, created_at FROM projects WHERE id = $1 AND user_id = $2"
-    )
+    let existing = sqlx::query_as::<_, Project>(&format!(
+        "SELECT {PROJECT_COLUMNS} FROM projects WHERE id = $1 AND user_id = $2"
+    ))
     .bind(id)
     .bind(auth_user.id)
     .fetch_optional(&state.pool)
@@ -132,58 +315,419 @@ pub async fn update_project(
 
     let name = payload.name.unwrap_or(existing.name);
     let is_public = payload.is_public.unwrap_or(existing.is_public);
+    let allowed_origins = payload.allowed_origins.unwrap_or(existing.allowed_origins);
+    let geo_allowed_countries = match payload.geo_allowed_countries {
+        Some(codes) => normalize_country_codes(Some(codes)),
+        None => existing.geo_allowed_countries,
+    };
+    let geo_blocked_countries = match payload.geo_blocked_countries {
+        Some(codes) => normalize_country_codes(Some(codes)),
+        None => existing.geo_blocked_countries,
+    };
+    let download_bandwidth_limit = match payload.download_bandwidth_limit {
+        Some(0) => None,
+        Some(limit) => Some(limit),
+        None => existing.download_bandwidth_limit,
+    };
+    let max_concurrent_uploads = match payload.max_concurrent_uploads {
+        Some(0) => None,
+        Some(limit) => Some(limit),
+        None => existing.max_concurrent_uploads,
+    };
+    let cold_storage_after_days = match payload.cold_storage_after_days {
+        Some(0) => None,
+        Some(days) => Some(days),
+        None => existing.cold_storage_after_days,
+    };
+    let storage_quota_bytes = match payload.storage_quota_bytes {
+        Some(0) => None,
+        Some(quota) => Some(quota),
+        None => existing.storage_quota_bytes,
+    };
+    let slug = match payload.slug {
+        Some(slug) if slug.is_empty() => None,
+        Some(slug) => Some(slug),
+        None => existing.slug,
+    };
+    let custom_domain = match payload.custom_domain {
+        Some(domain) if domain.is_empty() => None,
+        Some(domain) => Some(normalize_custom_domain(&domain, &state.config)?),
+        None => existing.custom_domain.clone(),
+    };
+    let archived = payload.archived.unwrap_or(existing.archived);
+    let default_folder_visibility = payload
+        .default_folder_visibility
+        .unwrap_or(existing.default_folder_visibility);
+    let image_max_dimension = match payload.image_max_dimension {
+        Some(0) => None,
+        Some(dimension) => Some(dimension),
+        None => existing.image_max_dimension,
+    };
+    let image_quality = payload.image_quality.unwrap_or(existing.image_quality);
+    let image_png_to_webp = payload
+        .image_png_to_webp
+        .unwrap_or(existing.image_png_to_webp);
+    let image_keep_original = payload
+        .image_keep_original
+        .unwrap_or(existing.image_keep_original);
+    let response_headers = match payload.response_headers {
+        Some(headers) => sqlx::types::Json(normalize_response_headers(Some(headers))),
+        None => existing.response_headers,
+    };
+    let noindex = payload.noindex.unwrap_or(existing.noindex);
 
-    let project = sqlx::query_as::<_, Project>(
+    let mut tx = state.pool.begin().await?;
+    let project = sqlx::query_as::<_, Project>(&format!(
         r#"
         UPDATE projects
-        SET name = $1, is_public = $2
-        WHERE id = $3
-        RETURNING id, user_id, name, api_key, is_public, created_at
-        "#,
-    )
+        SET name = $1, is_public = $2, allowed_origins = $3,
+            geo_allowed_countries = $4, geo_blocked_countries = $5,
+            download_bandwidth_limit = $6, max_concurrent_uploads = $7,
+            slug = $8, custom_domain = $9, cold_storage_after_days = $10,
+            storage_quota_bytes = $11, archived = $12, default_folder_visibility = $13,
+            image_max_dimension = $14, image_quality = $15, image_png_to_webp = $16,
+            image_keep_original = $17, response_headers = $18, noindex = $19
+        WHERE id = $20
+        RETURNING {PROJECT_COLUMNS}
+        "#
+    ))
     .bind(&name)
     .bind(is_public)
+    .bind(&allowed_origins)
+    .bind(&geo_allowed_countries)
+    .bind(&geo_blocked_countries)
+    .bind(download_bandwidth_limit)
+    .bind(max_concurrent_uploads)
+    .bind(&slug)
+    .bind(&custom_domain)
+    .bind(cold_storage_after_days)
+    .bind(storage_quota_bytes)
+    .bind(archived)
+    .bind(default_folder_visibility)
+    .bind(image_max_dimension)
+    .bind(image_quality)
+    .bind(image_png_to_webp)
+    .bind(image_keep_original)
+    .bind(&response_headers)
+    .bind(noindex)
     .bind(id)
-    .fetch_one(&state.pool)
-    .await?;
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(map_host_conflict)?;
 
-    Ok(Json(project))
+    let mut folders = None;
+    let mut warning = None;
+    if payload.cascade_to_folders {
+        folders = Some(set_project_folders_visibility(&mut tx, id, is_public).await?);
+    } else if existing.is_public && !is_public {
+        // Files in a public folder stay public even when the project isn't
+        let (public_folders, public_files): (i64, i64) = sqlx::query_as(
+            "SELECT COUNT(*), COALESCE(SUM(file_count), 0)::BIGINT FROM folders WHERE project_id = $1 AND is_public",
+        )
+        .bind(id)
+        .fetch_one(&mut *tx)
+        .await?;
+        if public_folders > 0 {
+            warning = Some(PublicFoldersWarning {
+                message: format!(
+                    "{public_folders} folders are still public, so their {public_files} files can still be downloaded without a key. Set cascade_to_folders to make them private too."
+                ),
+                public_folders,
+                public_files,
+            });
+        }
+    }
+    tx.commit().await?;
+    // The old domain stops serving files right away
+    if let Some(ref old_domain) = existing.custom_domain {
+        if project.custom_domain.as_ref() != Some(old_domain) {
+            state.host_projects.remove(old_domain);
+        }
+    }
+    if project.archived != existing.archived {
+        tracing::info!(
+            target: "audit",
+            user_id = %auth_user.id,
+            project_id = %id,
+            archived = project.archived,
+            "Project archive state changed"
+        );
+    }
+
+    Ok(Json(UpdateProjectResponse {
+        project,
+        folders,
+        warning,
+    }))
 }
 
-pub async fn delete_project(
+/// Check the project's custom domain for its `_filerunner-challenge` TXT
+/// record, and serve files on it once the record holds the verification token
+pub async fn verify_custom_domain(
     State(state): State<AppState>,
     auth_user: AuthUser,
     Path(id): Path<Uuid>,
-) -> Result<Json<serde_json::Value>> {
-    let result = sqlx::query("DELETE FROM projects WHERE id = $1 AND user_id = $2")
+) -> Result<Json<CustomDomainVerification>> {
+    let (custom_domain, token, verified_at) =
+        sqlx::query_as::<_, (Option<String>, Option<String>, Option<DateTime<Utc>>)>(
+            "SELECT custom_domain, custom_domain_verification_token, custom_domain_verified_at FROM projects WHERE id = $1 AND user_id = $2",
+        )
         .bind(id)
         .bind(auth_user.id)
-        .execute(&state.pool)
-        .await?;
+        .fetch_optional(&state.pool)
+        .await?
+        .ok_or(AppError::NotFound("Project not found".to_string()))?;
+    let (Some(custom_domain), Some(token)) = (custom_domain, token) else {
+        return Err(AppError::BadRequest(
+            "Project has no custom domain".to_string(),
+        ));
+    };
+    let record_name = verification_record_name(&custom_domain);
+
+    let verified_at = match verified_at {
+        Some(verified_at) => verified_at,
+        None => {
+            if !has_verification_record(&state.config.dns_over_https_url, &custom_domain, &token)
+                .await?
+            {
+                return Err(AppError::ValidationError(format!(
+                    "No TXT record {record_name} with value {token} found; DNS changes can take a while to appear"
+                )));
+            }
+            // Only if the domain wasn't changed while DNS was checked
+            let verified_at = sqlx::query_scalar::<_, DateTime<Utc>>(
+                r#"
+                UPDATE projects SET custom_domain_verified_at = NOW()
+                WHERE id = $1 AND custom_domain = $2 AND custom_domain_verification_token = $3
+                RETURNING custom_domain_verified_at
+                "#,
+            )
+            .bind(id)
+            .bind(&custom_domain)
+            .bind(&token)
+            .fetch_optional(&state.pool)
+            .await?
+            .ok_or(AppError::Conflict(
+                "Custom domain changed during verification".to_string(),
+            ))?;
+            state.host_projects.remove(&custom_domain);
+            tracing::info!(
+                target: "audit",
+                user_id = %auth_user.id,
+                project_id = %id,
+                custom_domain = %custom_domain,
+                "Custom domain verified"
+            );
+            verified_at
+        }
+    };
+
+    Ok(Json(CustomDomainVerification {
+        custom_domain,
+        record_name,
+        record_value: token,
+        verified_at,
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteProjectQuery {
+    /// The project's name, typed again to confirm the delete
+    pub confirm: Option<String>,
+}
+
+/// Schedule a project for deletion. It is frozen for `PROJECT_DELETION_GRACE_DAYS`
+/// and can be restored until then; afterwards the purge worker removes the
+/// project, its rows and its blobs.
+pub async fn delete_project(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(id): Path<Uuid>,
+    Query(query): Query<DeleteProjectQuery>,
+) -> Result<Json<serde_json::Value>> {
+    let project = sqlx::query_as::<_, Project>(&format!(
+        "SELECT {PROJECT_COLUMNS} FROM projects WHERE id = $1 AND user_id = $2"
+    ))
+    .bind(id)
+    .bind(auth_user.id)
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or(AppError::NotFound("Project not found".to_string()))?;
 
-    if result.rows_affected() == 0 {
-        return Err(AppError::NotFound("Project not found".to_string()));
+    if query.confirm.as_deref() != Some(project.name.as_str()) {
+        return Err(AppError::BadRequest(
+            "Confirm the delete by passing the project name as ?confirm=<name>".to_string(),
+        ));
     }
+    ensure_project_writable(&project)?;
+
+    // Fail now rather than when the purge runs
+    let held: bool = sqlx::query_scalar(
+        r#"
+        SELECT EXISTS (SELECT 1 FROM files WHERE project_id = $1 AND legal_hold)
+            OR EXISTS (SELECT 1 FROM folders WHERE project_id = $1 AND legal_hold)
+        "#,
+    )
+    .bind(id)
+    .fetch_one(&state.pool)
+    .await?;
+    if held {
+        return Err(AppError::LegalHold(format!(
+            "Project \"{}\" has files under legal hold",
+            project.name
+        )));
+    }
+
+    let mut tx = state.pool.begin().await?;
+    let scheduled_at: DateTime<Utc> = sqlx::query_scalar(
+        r#"
+        UPDATE projects
+        SET deletion_scheduled_at = NOW() + make_interval(days => $2)
+        WHERE id = $1 AND NOT archived AND deletion_scheduled_at IS NULL
+        RETURNING deletion_scheduled_at
+        "#,
+    )
+    .bind(id)
+    .bind(state.config.project_deletion_grace_days as i32)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(AppError::Conflict(
+        "Project was archived or deleted meanwhile".to_string(),
+    ))?;
+    let purge =
+        queue_project_purge(&mut tx, id, &project.name, Some(auth_user.id), scheduled_at).await?;
+    tx.commit().await?;
+
+    tracing::info!(
+        target: "audit",
+        user_id = %auth_user.id,
+        project_id = %id,
+        %scheduled_at,
+        "Project scheduled for deletion"
+    );
 
     Ok(Json(serde_json::json!({
-        "message": "Project deleted successfully"
+        "message": "Project scheduled for deletion",
+        "deletion_scheduled_at": scheduled_at,
+        "purge": purge
     })))
 }
 
-pub async fn regenerate_api_key(
+/// Cancel a scheduled delete while the grace period lasts (or after its purge
+/// failed before removing the project)
+pub async fn restore_project(
     State(state): State<AppState>,
     auth_user: AuthUser,
     Path(id): Path<Uuid>,
 ) -> Result<Json<Project>> {
-    let project = sqlx::query_as::<_, Project>(
+    let mut tx = state.pool.begin().await?;
+    let project = sqlx::query_as::<_, Project>(&format!(
         r#"
         UPDATE projects
-        SET api_key = gen_random_uuid()
-        WHERE id = $1 AND user_id = $2
-        RETURNING id, user_id, name, api_key, is_public, created_at
+        SET deletion_scheduled_at = NULL
+        WHERE id = $1 AND user_id = $2 AND deletion_scheduled_at IS NOT NULL
+        RETURNING {PROJECT_COLUMNS}
+        "#
+    ))
+    .bind(id)
+    .bind(auth_user.id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(AppError::NotFound(
+        "No project scheduled for deletion found".to_string(),
+    ))?;
+
+    // A purge that already started can't be called back
+    let cancelled = sqlx::query(
+        r#"
+        UPDATE project_purges
+        SET status = 'cancelled', finished_at = NOW()
+        WHERE project_id = $1 AND status IN ('pending', 'failed')
         "#,
     )
     .bind(id)
+    .execute(&mut *tx)
+    .await?;
+    if cancelled.rows_affected() == 0 {
+        return Err(AppError::Conflict(
+            "Project deletion is already in progress".to_string(),
+        ));
+    }
+    tx.commit().await?;
+
+    tracing::info!(
+        target: "audit",
+        user_id = %auth_user.id,
+        project_id = %id,
+        "Project restored"
+    );
+
+    Ok(Json(project))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RegenerateKeyQuery {
+    /// Hours the old key keeps working; defaults to `API_KEY_ROTATION_GRACE_HOURS`, 0 revokes it at once
+    pub grace_hours: Option<i64>,
+}
+
+/// Rotate the project API key. The old key stays valid for a grace period so
+/// running producers can be switched over without downtime.
+pub async fn regenerate_api_key(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(id): Path<Uuid>,
+    Query(query): Query<RegenerateKeyQuery>,
+) -> Result<Json<Project>> {
+    let grace_hours = query
+        .grace_hours
+        .unwrap_or(state.config.api_key_rotation_grace_hours);
+    if !(0..=24 * 30).contains(&grace_hours) {
+        return Err(AppError::BadRequest(
+            "grace_hours must be between 0 and 720".to_string(),
+        ));
+    }
+    let previous_expires_at = (grace_hours > 0).then(|| Utc::now() + Duration::hours(grace_hours));
+
+    let project = sqlx::query_as::<_, Project>(&format!(
+        r#"
+        UPDATE projects
+        SET api_key = gen_random_uuid(),
+            previous_api_key = CASE WHEN $3::timestamptz IS NULL THEN NULL ELSE api_key END,
+            previous_api_key_expires_at = $3,
+            api_key_last_used_at = NULL,
+            api_key_last_used_ip = NULL,
+            api_key_request_count = 0
+        WHERE id = $1 AND user_id = $2
+        RETURNING {PROJECT_COLUMNS}
+        "#
+    ))
+    .bind(id)
+    .bind(auth_user.id)
+    .bind(previous_expires_at)
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or(AppError::NotFound("Project not found".to_string()))?;
+
+    Ok(Json(project))
+}
+
+/// Revoke the previous API key before its grace period ends
+pub async fn revoke_previous_api_key(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Project>> {
+    let project = sqlx::query_as::<_, Project>(&format!(
+        r#"
+        UPDATE projects
+        SET previous_api_key = NULL,
+            previous_api_key_expires_at = NULL
+        WHERE id = $1 AND user_id = $2
+        RETURNING {PROJECT_COLUMNS}
+        "#
+    ))
+    .bind(id)
     .bind(auth_user.id)
     .fetch_optional(&state.pool)
     .await?
@@ -200,26 +744,44 @@ pub async fn empty_project(
     Path(project_id): Path<Uuid>,
 ) -> Result<Json<serde_json::Value>> {
     // Verify project exists and user owns it
-    let project = sqlx::query_as::<_, Project>(
-        "SELECT id, user_id, name, api_key, is_public, created_at FROM projects WHERE id = $1 AND user_id = $2",
-    )
+    let project = sqlx::query_as::<_, Project>(&format!(
+        "SELECT {PROJECT_COLUMNS} FROM projects WHERE id = $1 AND user_id = $2"
+    ))
     .bind(project_id)
     .bind(auth_user.id)
     .fetch_optional(&state.pool)
     .await?
     .ok_or(AppError::NotFound("Project not found".to_string()))?;
+    ensure_project_writable(&project)?;
 
-    // Get all files for this project
+    // Delete all files and folders from database; RETURNING yields exactly the
+    // rows removed, including uploads that landed after the request started
+    let mut tx = state.pool.begin().await?;
     let files = sqlx::query_as::<_, File>(
         r#"
-        SELECT id, project_id, folder_id, original_name, stored_name, file_path, size, mime_type, upload_date
-        FROM files
+        DELETE FROM files
         WHERE project_id = $1
+        RETURNING id, project_id, folder_id, original_name, stored_name, file_path, size, mime_type, upload_date, storage_encoding, precompressed_encodings, storage_tier, moderation_status
         "#,
     )
     .bind(project_id)
-    .fetch_all(&state.pool)
+    .fetch_all(&mut *tx)
     .await?;
+    // Before the folders go, while their visibility is known
+    queue_cdn_purge(&mut tx, state.cdn.as_deref(), &files).await?;
+    sqlx::query("DELETE FROM folders WHERE project_id = $1")
+        .bind(project_id)
+        .execute(&mut *tx)
+        .await?;
+    record_file_events(
+        &mut tx,
+        state.events.as_deref(),
+        FileEventKind::Deleted,
+        &files,
+    )
+    .await?;
+    queue_replication(&mut tx, state.replication.as_deref(), &files).await?;
+    tx.commit().await?;
 
     let mut deleted_count = 0;
 
@@ -231,29 +793,21 @@ pub async fn empty_project(
                 tracing::warn!("Failed to delete file {}: {}", file_path.display(), e);
             }
         }
+        delete_cold_blob(state.cold_storage.as_deref(), file).await;
         deleted_count += 1;
     }
 
-    // Delete all files from database
-    sqlx::query("DELETE FROM files WHERE project_id = $1")
-        .bind(project_id)
-        .execute(&state.pool)
-        .await?;
-
-    // Delete all folders for this project
-    sqlx::query("DELETE FROM folders WHERE project_id = $1")
-        .bind(project_id)
-        .execute(&state.pool)
-        .await?;
-
     // Try to clean up the project's storage directory
     let mut storage_path = PathBuf::from(&state.config.storage_path);
     storage_path.push(project.id.to_string());
     if storage_path.exists() {
-        // Remove all contents but keep the directory
+        // Remove all contents but keep the directory (and in-progress multipart uploads)
         if let Ok(mut entries) = tokio::fs::read_dir(&storage_path).await {
             while let Ok(Some(entry)) = entries.next_entry().await {
                 let path = entry.path();
+                if entry.file_name() == MULTIPART_DIR {
+                    continue;
+                }
                 if path.is_dir() {
                     let _ = fs::remove_dir_all(&path).await;
                 } else {
@@ -268,3 +822,45 @@ pub async fn empty_project(
         "deleted_count": deleted_count
     })))
 }
+
+/// List every project across all users (admin only)
+pub async fn admin_list_projects(
+    State(state): State<AppState>,
+    _: RequirePermission<perm::AdminProjectsRead>,
+    Query(page): Query<PageQuery>,
+) -> Result<Json<Paginated<AdminProjectResponse>>> {
+    let limit = page.limit();
+    let after: Option<(DateTime<Utc>, Uuid)> = page.after()?;
+
+    let projects = sqlx::query_as::<_, AdminProjectResponse>(
+        r#"
+        SELECT
+            p.id,
+            p.user_id,
+            u.email as owner_email,
+            p.name,
+            p.is_public,
+            p.created_at,
+            COALESCE(s.file_count, 0) as file_count,
+            COALESCE(s.total_size, 0) as total_size
+        FROM projects p
+        JOIN users u ON u.id = p.user_id
+        LEFT JOIN project_stats s ON s.project_id = p.id
+        WHERE $1::timestamptz IS NULL OR (p.created_at, p.id) < ($1, $2)
+        ORDER BY p.created_at DESC, p.id DESC
+        LIMIT $3
+        "#,
+    )
+    .bind(after.map(|(created_at, _)| created_at))
+    .bind(after.map(|(_, id)| id))
+    .bind(limit + 1)
+    .fetch_all(state.read_pool.get())
+    .await?;
+    let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM projects")
+        .fetch_one(state.read_pool.get())
+        .await?;
+
+    Ok(Json(Paginated::new(projects, limit, total, |p| {
+        (p.created_at, p.id)
+    })))
+}
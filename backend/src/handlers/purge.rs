@@ -0,0 +1,85 @@
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use uuid::Uuid;
+
+use crate::{
+    error::{AppError, Result},
+    middleware::{AuthUser, RequirePermission},
+    models::ProjectPurge,
+    utils::{admin_override, perm, AdminQuery, RolePermission, PURGE_COLUMNS},
+    AppState,
+};
+
+/// Blob cleanup of every deleted project, newest first
+pub async fn list_project_purges(
+    State(state): State<AppState>,
+    _: RequirePermission<perm::AdminPurgesRead>,
+) -> Result<Json<Vec<ProjectPurge>>> {
+    let purges = sqlx::query_as::<_, ProjectPurge>(&format!(
+        "SELECT {PURGE_COLUMNS} FROM project_purges ORDER BY created_at DESC LIMIT 100"
+    ))
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(Json(purges))
+}
+
+/// Progress of the blob cleanup after a project delete (the user who deleted it,
+/// or an admin with `?as_admin=true`)
+pub async fn get_project_purge(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(id): Path<Uuid>,
+    Query(admin): Query<AdminQuery>,
+) -> Result<Json<ProjectPurge>> {
+    let as_admin = admin_override(
+        &auth_user,
+        &admin,
+        RolePermission::AdminProjectsRead,
+        "get_project_purge",
+        id,
+    )?;
+
+    let purge = sqlx::query_as::<_, ProjectPurge>(&format!(
+        "SELECT {PURGE_COLUMNS} FROM project_purges WHERE id = $1 AND (requested_by = $2 OR $3)"
+    ))
+    .bind(id)
+    .bind(auth_user.id)
+    .bind(as_admin)
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or(AppError::NotFound("Purge not found".to_string()))?;
+
+    Ok(Json(purge))
+}
+
+/// Queue a failed purge again; blobs already removed are simply skipped
+pub async fn retry_project_purge(
+    State(state): State<AppState>,
+    _: RequirePermission<perm::AdminPurgesWrite>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<ProjectPurge>> {
+    let purge = sqlx::query_as::<_, ProjectPurge>(&format!(
+        "UPDATE project_purges SET status = 'pending' WHERE id = $1 AND status = 'failed' RETURNING {PURGE_COLUMNS}"
+    ))
+    .bind(id)
+    .fetch_optional(&state.pool)
+    .await?;
+
+    match purge {
+        Some(purge) => Ok(Json(purge)),
+        None => {
+            let status: String =
+                sqlx::query_scalar("SELECT status FROM project_purges WHERE id = $1")
+                    .bind(id)
+                    .fetch_optional(&state.pool)
+                    .await?
+                    .ok_or(AppError::NotFound("Purge not found".to_string()))?;
+            Err(AppError::Conflict(format!(
+                "Purge is {status}; only failed purges can be retried"
+            )))
+        }
+    }
+}
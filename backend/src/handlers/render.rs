@@ -0,0 +1,113 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::HeaderMap,
+    Json,
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    error::{AppError, Result},
+    handlers::file::readable_file,
+    middleware::{ClientIp, OptionalAuthUser},
+    models::{File, RenderResponse},
+    utils::{find_language, read_file_content, render_code_block, render_markdown, Language},
+    AppState,
+};
+
+/// Largest file rendered; bigger files are previewed by downloading them
+const MAX_RENDER_SIZE: i64 = 1024 * 1024;
+
+const MARKDOWN_EXTENSIONS: &[&str] = &["md", "markdown", "mdown", "mkd"];
+
+/// What kind of text a file holds
+#[derive(Clone, Copy)]
+pub(crate) enum TextFormat {
+    Markdown,
+    Code(&'static Language),
+    Text,
+}
+
+/// Tell a file's text format from its extension and MIME type; `None` for
+/// files that aren't text
+pub(crate) fn text_format(file: &File) -> Option<TextFormat> {
+    let extension = std::path::Path::new(&file.original_name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_ascii_lowercase();
+    if MARKDOWN_EXTENSIONS.contains(&extension.as_str()) {
+        return Some(TextFormat::Markdown);
+    }
+    if let Some(language) = find_language(&extension).filter(|_| !extension.is_empty()) {
+        return Some(TextFormat::Code(language));
+    }
+    file.mime_type
+        .starts_with("text/")
+        .then_some(TextFormat::Text)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RenderQuery {
+    pub api_key: Option<String>,
+}
+
+/// Markdown as sanitized HTML, code files syntax-highlighted and other text
+/// escaped, for previews in the dashboard; the same access as downloading
+pub async fn render_file(
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    optional_auth: OptionalAuthUser,
+    headers: HeaderMap,
+    Path(file_id): Path<Uuid>,
+    Query(query): Query<RenderQuery>,
+) -> Result<Json<RenderResponse>> {
+    let (file, _) = readable_file(
+        &state,
+        &optional_auth,
+        &headers,
+        client_ip,
+        file_id,
+        query.api_key.as_deref(),
+    )
+    .await?;
+
+    let format = text_format(&file).ok_or(AppError::BadRequest(
+        "Only Markdown, code and text files can be rendered".to_string(),
+    ))?;
+    if file.size > MAX_RENDER_SIZE {
+        return Err(AppError::BadRequest(format!(
+            "Files over {} KB can't be rendered",
+            MAX_RENDER_SIZE / 1024
+        )));
+    }
+
+    let content = read_file_content(&state.pool, state.cold_storage.as_deref(), &file).await?;
+    let text = String::from_utf8(content)
+        .map_err(|_| AppError::BadRequest("File is not UTF-8 text".to_string()))?;
+    let language = match format {
+        TextFormat::Code(language) => Some(language),
+        _ => None,
+    };
+    let html = tokio::task::spawn_blocking(move || {
+        if let TextFormat::Markdown = format {
+            render_markdown(&text)
+        } else {
+            let mut html = String::new();
+            render_code_block(text.trim_end_matches(['\r', '\n']), language, &mut html);
+            html
+        }
+    })
+    .await
+    .map_err(|e| AppError::InternalError(format!("Render task failed: {e}")))?;
+
+    Ok(Json(RenderResponse {
+        format: match format {
+            TextFormat::Markdown => "markdown",
+            TextFormat::Code(_) => "code",
+            TextFormat::Text => "text",
+        },
+        language: language.map(|language| language.name),
+        html,
+    }))
+}
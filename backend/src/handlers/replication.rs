@@ -0,0 +1,169 @@
+use axum::{
+    body::Body,
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+use tokio::{fs, io::AsyncWriteExt};
+use uuid::Uuid;
+
+use crate::{
+    error::{AppError, Result},
+    middleware::RequirePermission,
+    models::ReplicationStatus,
+    utils::{perm, remove_variants, replica_path, REPLICATION_SHA256_HEADER},
+    AppState,
+};
+
+/// Pushes from the primary carry `Authorization: Bearer <REPLICATION_TOKEN>`;
+/// the endpoints are hidden entirely when no token is configured
+fn check_replication_token(state: &AppState, headers: &HeaderMap) -> Result<()> {
+    let Some(ref token) = state.config.replication_token else {
+        return Err(AppError::NotFound("Not found".to_string()));
+    };
+    let presented = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "));
+    // Constant time, so the token can't be guessed byte by byte from timings
+    let matches = presented.is_some_and(|p| bool::from(p.as_bytes().ct_eq(token.as_bytes())));
+    if !matches {
+        return Err(AppError::Unauthorized);
+    }
+    Ok(())
+}
+
+fn resolve_replica_path(state: &AppState, path: &str) -> Result<std::path::PathBuf> {
+    replica_path(&state.config.storage_path, path)
+        .ok_or(AppError::BadRequest("Invalid blob path".to_string()))
+}
+
+/// Store a blob pushed by the primary at the same path under this instance's
+/// `STORAGE_PATH`. Written to a temp file and checked against its SHA-256
+/// first, so readers never see a partial blob.
+pub async fn receive_replicated_blob(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(path): Path<String>,
+    body: Body,
+) -> Result<StatusCode> {
+    check_replication_token(&state, &headers)?;
+    let target = resolve_replica_path(&state, &path)?;
+    let expected = headers
+        .get(REPLICATION_SHA256_HEADER)
+        .and_then(|h| h.to_str().ok())
+        .ok_or(AppError::BadRequest(format!(
+            "{REPLICATION_SHA256_HEADER} is required"
+        )))?
+        .to_string();
+
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)
+            .await
+            .map_err(|e| AppError::FileError(format!("Failed to create directory: {e}")))?;
+    }
+    let temp = target.with_extension(format!("replica-{}", Uuid::new_v4().simple()));
+
+    let written: Result<()> = async {
+        let mut file = fs::File::create(&temp)
+            .await
+            .map_err(|e| AppError::FileError(format!("Failed to create file: {e}")))?;
+        let mut hasher = Sha256::new();
+        let mut size = 0usize;
+        let mut stream = body.into_data_stream();
+        while let Some(chunk) = stream.next().await {
+            let chunk =
+                chunk.map_err(|e| AppError::BadRequest(format!("Failed to read blob: {e}")))?;
+            size += chunk.len();
+            if size > state.config.max_file_size {
+                return Err(AppError::BadRequest(format!(
+                    "File size exceeds maximum of {} bytes",
+                    state.config.max_file_size
+                )));
+            }
+            hasher.update(&chunk);
+            file.write_all(&chunk)
+                .await
+                .map_err(|e| AppError::FileError(format!("Failed to write file: {e}")))?;
+        }
+        file.flush()
+            .await
+            .map_err(|e| AppError::FileError(format!("Failed to write file: {e}")))?;
+        if !hex::encode(hasher.finalize()).eq_ignore_ascii_case(&expected) {
+            return Err(AppError::BadRequest(
+                "Blob does not match its SHA-256".to_string(),
+            ));
+        }
+        // Variants of the previous content are pushed again if the primary has them
+        remove_variants(target.to_string_lossy().as_ref()).await;
+        fs::rename(&temp, &target)
+            .await
+            .map_err(|e| AppError::FileError(format!("Failed to store file: {e}")))
+    }
+    .await;
+
+    if let Err(e) = written {
+        let _ = fs::remove_file(&temp).await;
+        return Err(e);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Remove a blob (and its cached variants) the primary no longer has
+pub async fn delete_replicated_blob(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(path): Path<String>,
+) -> Result<StatusCode> {
+    check_replication_token(&state, &headers)?;
+    let target = resolve_replica_path(&state, &path)?;
+
+    match fs::remove_file(&target).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            return Err(AppError::FileError(format!("Failed to delete file: {e}")));
+        }
+        _ => {}
+    }
+    remove_variants(target.to_string_lossy().as_ref()).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Replication backlog and how much the peer holds
+pub async fn replication_status(
+    State(state): State<AppState>,
+    _: RequirePermission<perm::AdminReplicationRead>,
+) -> Result<Json<ReplicationStatus>> {
+    let (backlog, failing, oldest_queued_at, last_error) =
+        sqlx::query_as::<_, (i64, i64, Option<DateTime<Utc>>, Option<String>)>(
+            r#"
+            SELECT COUNT(*), COUNT(*) FILTER (WHERE attempts > 0), MIN(queued_at),
+                   (SELECT last_error FROM replication_queue
+                    WHERE last_error IS NOT NULL ORDER BY next_attempt_at DESC LIMIT 1)
+            FROM replication_queue
+            "#,
+        )
+        .fetch_one(&state.pool)
+        .await?;
+    let (replicated_files, last_replicated_at) = sqlx::query_as::<_, (i64, Option<DateTime<Utc>>)>(
+        "SELECT COUNT(*), MAX(replicated_at) FROM replicated_blobs",
+    )
+    .fetch_one(&state.pool)
+    .await?;
+
+    Ok(Json(ReplicationStatus {
+        peer_url: state
+            .replication
+            .as_ref()
+            .map(|peer| peer.base_url().to_string()),
+        backlog,
+        failing,
+        oldest_queued_at,
+        last_error,
+        replicated_files,
+        last_replicated_at,
+    }))
+}
@@ -0,0 +1,235 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::HeaderMap,
+    Json,
+};
+use serde::Deserialize;
+use std::path::PathBuf;
+use tokio::fs;
+use uuid::Uuid;
+
+use crate::{
+    error::{AppError, Result},
+    middleware::OptionalAuthUser,
+    models::{
+        CleanupReport, DeleteStaleFilesRequest, File, FileMetadata, Project, PROJECT_COLUMNS,
+    },
+    utils::{
+        can_list, can_write, delete_cold_blob, ensure_project_writable, queue_cdn_purge,
+        queue_replication, record_file_events, remove_variants, Credentials, FileEventKind,
+        DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE,
+    },
+    AppState,
+};
+
+/// Default of `unused_for_days` for the stale files report
+const DEFAULT_UNUSED_FOR_DAYS: i32 = 90;
+
+/// Files that can be deleted: neither held themselves nor in a held folder
+const NOT_HELD: &str = "NOT f.legal_hold AND NOT folder_under_legal_hold(f.folder_id)";
+
+/// Not downloaded in the last `$2` days, or never downloaded and uploaded
+/// before then; every file when `$2` is null
+const UNUSED_FOR_DAYS: &str = "($2::int IS NULL OR COALESCE(f.last_accessed_at, f.upload_date) < NOW() - make_interval(days => $2))";
+
+#[derive(Debug, Deserialize)]
+pub struct LargestFilesQuery {
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StaleFilesQuery {
+    pub unused_for_days: Option<i32>,
+    pub limit: Option<i64>,
+}
+
+fn report_limit(limit: Option<i64>) -> i64 {
+    limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)
+}
+
+fn validate_unused_for_days(days: i32) -> Result<i32> {
+    if days < 1 {
+        return Err(AppError::ValidationError(
+            "unused_for_days must be at least 1".to_string(),
+        ));
+    }
+    Ok(days)
+}
+
+async fn authorized_project(
+    state: &AppState,
+    optional_auth: &OptionalAuthUser,
+    headers: &HeaderMap,
+    project_id: Uuid,
+) -> Result<(Project, Credentials)> {
+    let project = sqlx::query_as::<_, Project>(&format!(
+        "SELECT {PROJECT_COLUMNS} FROM projects WHERE id = $1"
+    ))
+    .bind(project_id)
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or(AppError::NotFound("Project not found".to_string()))?;
+    let credentials = Credentials::resolve(&state.pool, optional_auth, headers, None).await?;
+    if !can_list(&project, &credentials) {
+        return Err(AppError::Unauthorized);
+    }
+    Ok((project, credentials))
+}
+
+/// Up to `limit` deletable files, unused for `unused_for_days` if given,
+/// largest first, with the count and size of every match
+async fn cleanup_report(
+    state: &AppState,
+    project_id: Uuid,
+    unused_for_days: Option<i32>,
+    limit: i64,
+) -> Result<CleanupReport> {
+    let files = sqlx::query_as::<_, FileMetadata>(&format!(
+        r#"
+        SELECT
+            f.id,
+            f.project_id,
+            f.folder_id,
+            fol.path as folder_path,
+            f.original_name,
+            f.size,
+            f.mime_type,
+            f.upload_date,
+            '/api/v1/files/' || f.id::text as download_url,
+            f.download_count,
+            f.last_accessed_at,
+            f.description,
+            f.moderation_status,
+            f.moderation_reason,
+            f.legal_hold,
+            f.append_only
+        FROM files f
+        LEFT JOIN folders fol ON fol.id = f.folder_id
+        WHERE f.project_id = $1 AND {NOT_HELD} AND {UNUSED_FOR_DAYS}
+        ORDER BY f.size DESC, f.id
+        LIMIT $3
+        "#
+    ))
+    .bind(project_id)
+    .bind(unused_for_days)
+    .bind(limit)
+    .fetch_all(state.read_pool.get())
+    .await?;
+
+    let (file_count, total_size) = sqlx::query_as::<_, (i64, i64)>(&format!(
+        "SELECT COUNT(*), COALESCE(SUM(f.size), 0)::BIGINT FROM files f WHERE f.project_id = $1 AND {NOT_HELD} AND {UNUSED_FOR_DAYS}"
+    ))
+    .bind(project_id)
+    .bind(unused_for_days)
+    .fetch_one(state.read_pool.get())
+    .await?;
+
+    Ok(CleanupReport {
+        files,
+        file_count,
+        total_size,
+    })
+}
+
+/// A project's largest files, biggest first, as candidates for cleanup.
+/// Files under legal hold are left out. Delete the ones you pick with
+/// `POST /api/v1/files/bulk-delete`. Needs the project API key or a
+/// collaborator role.
+pub async fn largest_files_report(
+    State(state): State<AppState>,
+    optional_auth: OptionalAuthUser,
+    headers: HeaderMap,
+    Path(project_id): Path<Uuid>,
+    Query(query): Query<LargestFilesQuery>,
+) -> Result<Json<CleanupReport>> {
+    authorized_project(&state, &optional_auth, &headers, project_id).await?;
+    let report = cleanup_report(&state, project_id, None, report_limit(query.limit)).await?;
+    Ok(Json(report))
+}
+
+/// Files of a project not downloaded for `unused_for_days` (default 90),
+/// including old files never downloaded at all, biggest first. Files under
+/// legal hold are left out. Needs the project API key or a collaborator role.
+pub async fn stale_files_report(
+    State(state): State<AppState>,
+    optional_auth: OptionalAuthUser,
+    headers: HeaderMap,
+    Path(project_id): Path<Uuid>,
+    Query(query): Query<StaleFilesQuery>,
+) -> Result<Json<CleanupReport>> {
+    let days = validate_unused_for_days(query.unused_for_days.unwrap_or(DEFAULT_UNUSED_FOR_DAYS))?;
+    authorized_project(&state, &optional_auth, &headers, project_id).await?;
+    let report = cleanup_report(&state, project_id, Some(days), report_limit(query.limit)).await?;
+    Ok(Json(report))
+}
+
+/// Delete the files the stale files report lists for `unused_for_days`, or
+/// just the selected `file_ids` among them. Staleness is checked again, so a
+/// file downloaded since the report was fetched is kept. Needs the project
+/// API key or write access.
+pub async fn delete_stale_files(
+    State(state): State<AppState>,
+    optional_auth: OptionalAuthUser,
+    headers: HeaderMap,
+    Path(project_id): Path<Uuid>,
+    Json(payload): Json<DeleteStaleFilesRequest>,
+) -> Result<Json<serde_json::Value>> {
+    let days = validate_unused_for_days(payload.unused_for_days)?;
+    let (project, credentials) =
+        authorized_project(&state, &optional_auth, &headers, project_id).await?;
+    if !can_write(&project, &credentials) {
+        return Err(AppError::Unauthorized);
+    }
+    ensure_project_writable(&project)?;
+
+    let mut tx = state.pool.begin().await?;
+    let stale = sqlx::query_as::<_, File>(&format!(
+        r#"
+        SELECT f.id, f.project_id, f.folder_id, f.original_name, f.stored_name, f.file_path, f.size, f.mime_type, f.upload_date, f.storage_encoding, f.precompressed_encodings, f.storage_tier, f.moderation_status
+        FROM files f
+        WHERE f.project_id = $1 AND {NOT_HELD} AND {UNUSED_FOR_DAYS}
+          AND ($3::uuid[] IS NULL OR f.id = ANY($3))
+        FOR UPDATE
+        "#
+    ))
+    .bind(project_id)
+    .bind(days)
+    .bind(&payload.file_ids)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let file_ids: Vec<Uuid> = stale.iter().map(|f| f.id).collect();
+    sqlx::query("DELETE FROM files WHERE id = ANY($1)")
+        .bind(&file_ids)
+        .execute(&mut *tx)
+        .await?;
+    record_file_events(
+        &mut tx,
+        state.events.as_deref(),
+        FileEventKind::Deleted,
+        &stale,
+    )
+    .await?;
+    queue_replication(&mut tx, state.replication.as_deref(), &stale).await?;
+    queue_cdn_purge(&mut tx, state.cdn.as_deref(), &stale).await?;
+    tx.commit().await?;
+
+    let mut freed_bytes = 0;
+    for file in &stale {
+        let file_path = PathBuf::from(&file.file_path);
+        if file_path.exists() {
+            if let Err(e) = fs::remove_file(&file_path).await {
+                tracing::warn!("Failed to delete file {}: {}", file_path.display(), e);
+            }
+        }
+        remove_variants(&file.file_path).await;
+        delete_cold_blob(state.cold_storage.as_deref(), file).await;
+        freed_bytes += file.size;
+    }
+
+    Ok(Json(serde_json::json!({
+        "message": "Stale files deleted successfully",
+        "deleted_count": file_ids.len(),
+        "freed_bytes": freed_bytes
+    })))
+}
@@ -0,0 +1,23 @@
+use axum::{
+    extract::State,
+    http::header,
+    response::{IntoResponse, Response},
+};
+
+use crate::AppState;
+
+/// `robots.txt` that disallows every path, or none
+pub(crate) fn robots_response(noindex: bool) -> Response {
+    let body = if noindex {
+        "User-agent: *\nDisallow: /\n"
+    } else {
+        "User-agent: *\nDisallow:\n"
+    };
+    ([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], body).into_response()
+}
+
+/// `robots.txt` of the API host; keeps crawlers out entirely with `ROBOTS_NOINDEX`.
+/// Project hosts get their own from the host routing.
+pub async fn robots_txt(State(state): State<AppState>) -> Response {
+    robots_response(state.config.robots_noindex)
+}
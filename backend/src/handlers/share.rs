@@ -0,0 +1,161 @@
+use axum::{
+    extract::{Path, State},
+    http::HeaderMap,
+    Json,
+};
+use chrono::{Duration, Utc};
+use rand::{distributions::Alphanumeric, Rng};
+use sqlx::PgPool;
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::{
+    error::{AppError, Result},
+    handlers::file::load_file_scope,
+    middleware::OptionalAuthUser,
+    models::{FileShareResponse, ModerationStatus, ShareFileEmailRequest, ShareRecipientStatus},
+    utils::{can_write, hash_token, Credentials},
+    AppState,
+};
+
+/// Length of the token in a share link
+const SHARE_TOKEN_LENGTH: usize = 43;
+
+/// How long a share link works when the request doesn't say
+const DEFAULT_SHARE_HOURS: i64 = 24 * 7;
+
+/// Whether `token` is an unexpired share link to `file_id`
+pub(crate) async fn share_grants_read(pool: &PgPool, file_id: Uuid, token: &str) -> Result<bool> {
+    Ok(sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM file_shares WHERE token_hash = $1 AND file_id = $2 AND expires_at > NOW())",
+    )
+    .bind(hash_token(token))
+    .bind(file_id)
+    .fetch_one(pool)
+    .await?)
+}
+
+/// Create a share link to a file and email it to each recipient, with an
+/// optional message. Anyone with the link can download the file until it
+/// expires, so sharing needs write access to the file. Each recipient and
+/// whether the mail server accepted their message is recorded.
+pub async fn email_share_link(
+    State(state): State<AppState>,
+    optional_auth: OptionalAuthUser,
+    headers: HeaderMap,
+    Path(file_id): Path<Uuid>,
+    Json(payload): Json<ShareFileEmailRequest>,
+) -> Result<Json<FileShareResponse>> {
+    payload
+        .validate()
+        .map_err(|e| AppError::ValidationError(e.to_string()))?;
+    let (Some(mailer), Some(app_url)) = (state.mailer.as_deref(), state.config.app_url.as_deref())
+    else {
+        return Err(AppError::BadRequest(
+            "Sharing by email needs SMTP_URL and APP_URL to be configured".to_string(),
+        ));
+    };
+
+    let (file, project, _) = load_file_scope(&state.pool, file_id).await?;
+    let credentials = Credentials::resolve(&state.pool, &optional_auth, &headers, None).await?;
+    if !can_write(&project, &credentials) {
+        return Err(AppError::Unauthorized);
+    }
+    if file.moderation_status != ModerationStatus::Approved {
+        return Err(AppError::BadRequest(
+            "Files held by content moderation can't be shared".to_string(),
+        ));
+    }
+
+    let mut recipients: Vec<String> = payload
+        .recipients
+        .iter()
+        .map(|r| r.trim().to_lowercase())
+        .collect();
+    recipients.sort_unstable();
+    recipients.dedup();
+    let message = payload
+        .message
+        .map(|m| m.trim().to_string())
+        .filter(|m| !m.is_empty());
+    let expires_at =
+        Utc::now() + Duration::hours(payload.expires_in_hours.unwrap_or(DEFAULT_SHARE_HOURS));
+    let sender = optional_auth.0.as_ref();
+
+    let token: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(SHARE_TOKEN_LENGTH)
+        .map(char::from)
+        .collect();
+    let share_id: Uuid = sqlx::query_scalar(
+        r#"
+        INSERT INTO file_shares (file_id, created_by, token_hash, message, expires_at)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id
+        "#,
+    )
+    .bind(file.id)
+    .bind(sender.map(|user| user.id))
+    .bind(hash_token(&token))
+    .bind(&message)
+    .bind(expires_at)
+    .fetch_one(&state.pool)
+    .await?;
+
+    let url = format!("{app_url}/share?file={}&token={token}", file.id);
+    let shared_by = sender.map_or(project.name.as_str(), |user| user.email.as_str());
+    let subject = format!("{shared_by} shared \"{}\" with you", file.original_name);
+    let body = format!(
+        "{shared_by} shared \"{}\" with you on FileRunner.\n\n{}Download it here:\n\n{url}\n\n\
+         The link works until {}.\n",
+        file.original_name,
+        message
+            .as_ref()
+            .map_or(String::new(), |m| format!("{m}\n\n")),
+        expires_at.format("%Y-%m-%d %H:%M UTC"),
+    );
+
+    let mut statuses = Vec::with_capacity(recipients.len());
+    for email in recipients {
+        let error = mailer.send(&email, &subject, body.clone()).await.err();
+        if let Some(ref e) = error {
+            tracing::warn!("Failed to email share {} to {}: {}", share_id, email, e);
+        }
+        sqlx::query(
+            r#"
+            INSERT INTO file_share_recipients (share_id, email, sent_at, error)
+            VALUES ($1, $2, CASE WHEN $3::text IS NULL THEN NOW() END, $3)
+            "#,
+        )
+        .bind(share_id)
+        .bind(&email)
+        .bind(&error)
+        .execute(&state.pool)
+        .await?;
+        statuses.push(ShareRecipientStatus {
+            email,
+            sent: error.is_none(),
+            error,
+        });
+    }
+
+    tracing::info!(
+        target: "audit",
+        %share_id,
+        file_id = %file.id,
+        project_id = %project.id,
+        shared_by,
+        recipients = %statuses.iter().map(|s| s.email.as_str()).collect::<Vec<_>>().join(","),
+        sent = statuses.iter().filter(|s| s.sent).count(),
+        %expires_at,
+        "File share link emailed"
+    );
+
+    Ok(Json(FileShareResponse {
+        id: share_id,
+        file_id: file.id,
+        url,
+        expires_at,
+        recipients: statuses,
+    }))
+}
@@ -0,0 +1,113 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use uuid::Uuid;
+
+use crate::{
+    error::{AppError, Result},
+    middleware::AuthUser,
+    models::FileMetadata,
+    AppState,
+};
+
+/// SQL condition: user `$1` may read file `f` (with project `p` and folder `fol`
+/// joined), the same rule as `can_read`
+const READABLE_BY_USER: &str = r#"(
+    p.user_id = $1
+    OR p.is_public
+    OR COALESCE(fol.is_public, false)
+    OR EXISTS (SELECT 1 FROM project_members m WHERE m.project_id = p.id AND m.user_id = $1)
+)"#;
+
+/// Star a file the user can read. Starring twice is a no-op.
+pub async fn star_file(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(file_id): Path<Uuid>,
+) -> Result<StatusCode> {
+    let readable: bool = sqlx::query_scalar(&format!(
+        r#"
+        SELECT EXISTS (
+            SELECT 1
+            FROM files f
+            JOIN projects p ON p.id = f.project_id
+            LEFT JOIN folders fol ON fol.id = f.folder_id
+            WHERE f.id = $2 AND {READABLE_BY_USER}
+        )
+        "#
+    ))
+    .bind(auth_user.id)
+    .bind(file_id)
+    .fetch_one(&state.pool)
+    .await?;
+    if !readable {
+        return Err(AppError::NotFound("File not found".to_string()));
+    }
+
+    sqlx::query(
+        "INSERT INTO file_stars (user_id, file_id) VALUES ($1, $2) ON CONFLICT (user_id, file_id) DO NOTHING",
+    )
+    .bind(auth_user.id)
+    .bind(file_id)
+    .execute(&state.pool)
+    .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Remove a star; unstarring a file that isn't starred is a no-op
+pub async fn unstar_file(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(file_id): Path<Uuid>,
+) -> Result<StatusCode> {
+    sqlx::query("DELETE FROM file_stars WHERE user_id = $1 AND file_id = $2")
+        .bind(auth_user.id)
+        .bind(file_id)
+        .execute(&state.pool)
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The user's starred files, most recently starred first. Files the user has
+/// since lost access to are left out (their stars are kept).
+pub async fn list_starred_files(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+) -> Result<Json<Vec<FileMetadata>>> {
+    let files = sqlx::query_as::<_, FileMetadata>(&format!(
+        r#"
+        SELECT
+            f.id,
+            f.project_id,
+            f.folder_id,
+            fol.path as folder_path,
+            f.original_name,
+            f.size,
+            f.mime_type,
+            f.upload_date,
+            '/api/v1/files/' || f.id::text as download_url,
+            f.download_count,
+            f.last_accessed_at,
+            f.description,
+            f.moderation_status,
+            f.moderation_reason,
+            f.legal_hold,
+            f.append_only
+        FROM file_stars s
+        JOIN files f ON f.id = s.file_id
+        JOIN projects p ON p.id = f.project_id
+        LEFT JOIN folders fol ON fol.id = f.folder_id
+        WHERE s.user_id = $1 AND {READABLE_BY_USER}
+        ORDER BY s.created_at DESC
+        "#
+    ))
+    .bind(auth_user.id)
+    .fetch_all(state.read_pool.get())
+    .await?;
+
+    Ok(Json(files))
+}
@@ -0,0 +1,301 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::{
+    error::{AppError, Result},
+    middleware::RequirePermission,
+    models::{File, StorageMigration},
+    utils::{perm, switch_to_cold, ColdStorage},
+    AppState,
+};
+
+/// Files processed per batch while copying or cutting over
+const MIGRATION_BATCH_SIZE: i64 = 200;
+
+const MIGRATION_COLUMNS: &str = "id, status, total_files, total_bytes, copied_files, copied_bytes, failed_files, cutover_files, last_error, started_by, created_at, finished_at, cutover_at";
+
+fn cold_storage(state: &AppState) -> Result<Arc<ColdStorage>> {
+    state.cold_storage.clone().ok_or_else(|| {
+        AppError::BadRequest("COLD_STORAGE_URL must be configured to migrate storage".to_string())
+    })
+}
+
+async fn load_migration(pool: &PgPool, id: Uuid) -> Result<StorageMigration> {
+    sqlx::query_as::<_, StorageMigration>(&format!(
+        "SELECT {MIGRATION_COLUMNS} FROM storage_migrations WHERE id = $1"
+    ))
+    .bind(id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or(AppError::NotFound(
+        "Storage migration not found".to_string(),
+    ))
+}
+
+/// Next batch of local (hot) files after `after`, in ID order
+async fn next_hot_batch(pool: &PgPool, after: Uuid) -> Result<Vec<File>> {
+    Ok(sqlx::query_as::<_, File>(
+        r#"
+        SELECT id, project_id, folder_id, original_name, stored_name, file_path, size, mime_type, upload_date, storage_encoding, precompressed_encodings, storage_tier, moderation_status
+        FROM files
+        WHERE storage_tier = 'hot' AND id > $1
+        ORDER BY id
+        LIMIT $2
+        "#,
+    )
+    .bind(after)
+    .bind(MIGRATION_BATCH_SIZE)
+    .fetch_all(pool)
+    .await?)
+}
+
+async fn fail_migration(pool: &PgPool, id: Uuid, error: &str) {
+    tracing::warn!("Storage migration {} failed: {}", id, error);
+    let result = sqlx::query(
+        "UPDATE storage_migrations SET status = 'failed', last_error = $1, finished_at = NOW() WHERE id = $2",
+    )
+    .bind(error)
+    .bind(id)
+    .execute(pool)
+    .await;
+    if let Err(e) = result {
+        tracing::warn!("Failed to record storage migration failure: {}", e);
+    }
+}
+
+/// Copy every local blob to cold storage, verifying each copy by checksum
+async fn copy_all(pool: &PgPool, cold: &ColdStorage, id: Uuid) -> Result<()> {
+    let mut after = Uuid::nil();
+    loop {
+        let batch = next_hot_batch(pool, after).await?;
+        let Some(last) = batch.last() else {
+            break;
+        };
+        after = last.id;
+
+        let (mut copied_files, mut copied_bytes, mut failed_files) = (0i64, 0i64, 0i64);
+        let mut last_error = None;
+        for file in &batch {
+            match cold.copy_verified(file).await {
+                Ok((size, sha256)) => {
+                    sqlx::query(
+                        r#"
+                        INSERT INTO storage_migration_files (migration_id, file_id, upload_date, sha256)
+                        VALUES ($1, $2, $3, $4)
+                        ON CONFLICT (migration_id, file_id)
+                        DO UPDATE SET upload_date = EXCLUDED.upload_date, sha256 = EXCLUDED.sha256
+                        "#,
+                    )
+                    .bind(id)
+                    .bind(file.id)
+                    .bind(file.upload_date)
+                    .bind(&sha256)
+                    .execute(pool)
+                    .await?;
+                    copied_files += 1;
+                    copied_bytes += size as i64;
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to migrate file {}: {}", file.id, e);
+                    failed_files += 1;
+                    last_error = Some(format!("File {}: {e}", file.id));
+                }
+            }
+        }
+
+        sqlx::query(
+            r#"
+            UPDATE storage_migrations
+            SET copied_files = copied_files + $1, copied_bytes = copied_bytes + $2,
+                failed_files = failed_files + $3, last_error = COALESCE($4, last_error)
+            WHERE id = $5
+            "#,
+        )
+        .bind(copied_files)
+        .bind(copied_bytes)
+        .bind(failed_files)
+        .bind(&last_error)
+        .bind(id)
+        .execute(pool)
+        .await?;
+    }
+
+    sqlx::query(
+        "UPDATE storage_migrations SET status = 'copied', finished_at = NOW() WHERE id = $1",
+    )
+    .bind(id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Serve every local file from cold storage. Files uploaded or replaced since the
+/// copy phase are copied (and verified) now, so nothing is left behind.
+async fn cut_over(pool: &PgPool, cold: &ColdStorage, id: Uuid) -> Result<()> {
+    let mut after = Uuid::nil();
+    loop {
+        let batch = next_hot_batch(pool, after).await?;
+        let Some(last) = batch.last() else {
+            break;
+        };
+        after = last.id;
+
+        let file_ids: Vec<Uuid> = batch.iter().map(|f| f.id).collect();
+        let copied: std::collections::HashMap<Uuid, chrono::DateTime<chrono::Utc>> =
+            sqlx::query_as::<_, (Uuid, chrono::DateTime<chrono::Utc>)>(
+                "SELECT file_id, upload_date FROM storage_migration_files WHERE migration_id = $1 AND file_id = ANY($2)",
+            )
+            .bind(id)
+            .bind(&file_ids)
+            .fetch_all(pool)
+            .await?
+            .into_iter()
+            .collect();
+
+        let mut switched = 0i64;
+        for file in &batch {
+            if copied.get(&file.id) != Some(&file.upload_date) {
+                if let Err(e) = cold.copy_verified(file).await {
+                    tracing::warn!("Failed to migrate file {} during cutover: {}", file.id, e);
+                    continue;
+                }
+            }
+            if switch_to_cold(pool, file, false).await? {
+                switched += 1;
+            }
+        }
+
+        sqlx::query(
+            "UPDATE storage_migrations SET cutover_files = cutover_files + $1 WHERE id = $2",
+        )
+        .bind(switched)
+        .bind(id)
+        .execute(pool)
+        .await?;
+    }
+
+    sqlx::query(
+        "UPDATE storage_migrations SET status = 'completed', cutover_at = NOW() WHERE id = $1",
+    )
+    .bind(id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Mark migrations left running by a previous process as failed (called at startup)
+pub async fn fail_interrupted_migrations(pool: &PgPool) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE storage_migrations
+        SET status = 'failed', last_error = 'Interrupted by server restart', finished_at = NOW()
+        WHERE status IN ('running', 'cutting_over')
+        "#,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Start copying all local blobs to the configured cold storage backend.
+/// Progress is reported by `GET /api/admin/storage/migrate/:id`.
+pub async fn start_storage_migration(
+    State(state): State<AppState>,
+    RequirePermission(auth_user, _): RequirePermission<perm::AdminStorageWrite>,
+) -> Result<Json<StorageMigration>> {
+    let cold = cold_storage(&state)?;
+
+    // One migration at a time
+    let active: Option<Uuid> = sqlx::query_scalar(
+        "SELECT id FROM storage_migrations WHERE status IN ('running', 'cutting_over') LIMIT 1",
+    )
+    .fetch_optional(&state.pool)
+    .await?;
+    if let Some(active) = active {
+        return Err(AppError::Conflict(format!(
+            "Storage migration {active} is already in progress"
+        )));
+    }
+
+    let migration = sqlx::query_as::<_, StorageMigration>(&format!(
+        r#"
+        INSERT INTO storage_migrations (total_files, total_bytes, started_by)
+        SELECT COUNT(*), COALESCE(SUM(COALESCE(stored_size, size)), 0)::BIGINT, $1
+        FROM files
+        WHERE storage_tier = 'hot'
+        RETURNING {MIGRATION_COLUMNS}
+        "#
+    ))
+    .bind(auth_user.id)
+    .fetch_one(&state.pool)
+    .await?;
+
+    let pool = state.pool.clone();
+    let id = migration.id;
+    tokio::spawn(async move {
+        if let Err(e) = copy_all(&pool, &cold, id).await {
+            fail_migration(&pool, id, &e.to_string()).await;
+        }
+    });
+
+    Ok(Json(migration))
+}
+
+pub async fn list_storage_migrations(
+    State(state): State<AppState>,
+    _: RequirePermission<perm::AdminStorageRead>,
+) -> Result<Json<Vec<StorageMigration>>> {
+    let migrations = sqlx::query_as::<_, StorageMigration>(&format!(
+        "SELECT {MIGRATION_COLUMNS} FROM storage_migrations ORDER BY created_at DESC"
+    ))
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(Json(migrations))
+}
+
+pub async fn get_storage_migration(
+    State(state): State<AppState>,
+    _: RequirePermission<perm::AdminStorageRead>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<StorageMigration>> {
+    Ok(Json(load_migration(&state.pool, id).await?))
+}
+
+/// Final step: switch every local file (including ones uploaded since the copy)
+/// to cold storage and free the local disk. Downloads keep working throughout.
+pub async fn cutover_storage_migration(
+    State(state): State<AppState>,
+    _: RequirePermission<perm::AdminStorageWrite>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<StorageMigration>> {
+    let cold = cold_storage(&state)?;
+
+    let updated = sqlx::query(
+        "UPDATE storage_migrations SET status = 'cutting_over' WHERE id = $1 AND status = 'copied'",
+    )
+    .bind(id)
+    .execute(&state.pool)
+    .await?;
+    if updated.rows_affected() == 0 {
+        let migration = load_migration(&state.pool, id).await?;
+        return Err(AppError::Conflict(format!(
+            "Migration is {}; only copied migrations can be cut over",
+            migration.status
+        )));
+    }
+
+    let pool = state.pool.clone();
+    tokio::spawn(async move {
+        if let Err(e) = cut_over(&pool, &cold, id).await {
+            fail_migration(&pool, id, &e.to_string()).await;
+        }
+    });
+
+    Ok(Json(load_migration(&state.pool, id).await?))
+}
@@ -0,0 +1,86 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::Response,
+    Json,
+};
+use uuid::Uuid;
+
+use crate::{
+    error::{AppError, Result},
+    handlers::file::{read_blob_body, readable_file, DownloadQuery},
+    middleware::{ClientIp, OptionalAuthUser},
+    models::FileVersion,
+    utils::{content_disposition, FILE_VERSION_COLUMNS},
+    AppState,
+};
+
+/// Earlier versions of a file kept next to its current content, newest first
+pub async fn list_file_versions(
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    optional_auth: OptionalAuthUser,
+    headers: HeaderMap,
+    Path(file_id): Path<Uuid>,
+) -> Result<Json<Vec<FileVersion>>> {
+    let (file, _) =
+        readable_file(&state, &optional_auth, &headers, client_ip, file_id, None).await?;
+    let versions = sqlx::query_as::<_, FileVersion>(&format!(
+        "SELECT {FILE_VERSION_COLUMNS} FROM file_versions WHERE file_id = $1 ORDER BY created_at DESC"
+    ))
+    .bind(file.id)
+    .fetch_all(&state.pool)
+    .await?;
+    Ok(Json(versions))
+}
+
+/// Download a kept version; the same access as downloading the file itself
+/// (share links only cover the current content)
+pub async fn download_file_version(
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    optional_auth: OptionalAuthUser,
+    headers: HeaderMap,
+    Path((file_id, version_id)): Path<(Uuid, Uuid)>,
+    Query(query): Query<DownloadQuery>,
+) -> Result<Response> {
+    let (file, project) = readable_file(
+        &state,
+        &optional_auth,
+        &headers,
+        client_ip,
+        file_id,
+        query.api_key.as_deref(),
+    )
+    .await?;
+    let version = sqlx::query_as::<_, FileVersion>(&format!(
+        "SELECT {FILE_VERSION_COLUMNS} FROM file_versions WHERE id = $1 AND file_id = $2"
+    ))
+    .bind(version_id)
+    .bind(file.id)
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or(AppError::NotFound("Version not found".to_string()))?;
+
+    let (length, body) = read_blob_body(
+        &state.memory_budget,
+        std::path::Path::new(&version.file_path),
+        project.download_bandwidth_limit,
+    )
+    .await?;
+    let disposition = if query.download.unwrap_or(false) {
+        "attachment"
+    } else {
+        "inline"
+    };
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, &version.mime_type)
+        .header(header::CONTENT_LENGTH, length)
+        .header(
+            header::CONTENT_DISPOSITION,
+            content_disposition(disposition, &version.original_name),
+        )
+        .body(body)
+        .map_err(|e| AppError::InternalError(format!("Failed to build response: {e}")))
+}
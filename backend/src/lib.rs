@@ -0,0 +1,56 @@
+pub mod cli;
+pub mod config;
+pub mod db;
+pub mod error;
+pub mod handlers;
+pub mod middleware;
+pub mod models;
+pub mod scheduler;
+pub mod server;
+pub mod utils;
+
+use sqlx::PgPool;
+use std::sync::Arc;
+
+use config::Config;
+use scheduler::Scheduler;
+use utils::{
+    ApiKeyUsageTracker, BackupTarget, CdnPurger, ColdStorage, DownloadTracker, EventPublisher,
+    GeoIpReader, HostProjectCache, LoadShedder, Mailer, MemoryBudget, Metrics, Moderator,
+    MultipartProgress, PowReplayCache, PrecompressQueue, ReplicationPeer, SignatureReplayCache,
+    TokenRevocationCache, UploadLimiter,
+};
+
+/// Shared state handed to every handler and middleware
+#[derive(Clone)]
+pub struct AppState {
+    pub pool: PgPool,
+    pub read_pool: db::ReadPool,
+    pub config: Arc<Config>,
+    pub geoip: Option<Arc<GeoIpReader>>,
+    pub upload_limiter: Arc<UploadLimiter>,
+    /// Bytes of multipart parts still arriving, for upload progress
+    pub multipart_progress: Arc<MultipartProgress>,
+    pub api_key_usage: Arc<ApiKeyUsageTracker>,
+    pub download_stats: Arc<DownloadTracker>,
+    pub load_shedder: Arc<LoadShedder>,
+    pub memory_budget: Arc<MemoryBudget>,
+    pub signature_replay: Arc<SignatureReplayCache>,
+    /// Solved proof-of-work challenges, rejected if submitted again
+    pub pow_replay: Arc<PowReplayCache>,
+    pub host_projects: Arc<HostProjectCache>,
+    pub token_revocations: Arc<TokenRevocationCache>,
+    pub precompress: Arc<PrecompressQueue>,
+    pub cold_storage: Option<Arc<ColdStorage>>,
+    pub backup_target: Option<Arc<BackupTarget>>,
+    pub events: Option<Arc<EventPublisher>>,
+    pub mailer: Option<Arc<Mailer>>,
+    /// Content moderation for uploads (`MODERATION_URL`)
+    pub moderator: Option<Arc<dyn Moderator>>,
+    /// Standby that blobs are mirrored to (`REPLICATION_PEER_URL`)
+    pub replication: Option<Arc<ReplicationPeer>>,
+    /// CDN that changed public files are purged from (`CDN_PURGE_PROVIDER`)
+    pub cdn: Option<Arc<CdnPurger>>,
+    pub scheduler: Arc<Scheduler>,
+    pub metrics: Arc<Metrics>,
+}
@@ -1,59 +1,178 @@
-mod config;
-mod db;
-mod error;
-mod handlers;
-mod middleware;
-mod models;
-mod utils;
-
 use axum::http::HeaderValue;
 use axum::http::{header, Method};
 use axum::{
+    extract::DefaultBodyLimit,
     middleware as axum_middleware,
     routing::{delete, get, post, put},
     Router,
 };
-use sqlx::PgPool;
-use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
+use tower::Layer;
 use tower_governor::{governor::GovernorConfigBuilder, GovernorLayer};
 use tower_http::cors::CorsLayer;
 use tower_http::set_header::SetResponseHeaderLayer;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use tracing::Level;
+use tracing_subscriber::{
+    filter::Targets, layer::SubscriberExt, util::SubscriberInitExt, Layer as _,
+};
 
+use clap::Parser;
+use filerunner_backend::{
+    cli, config, db, handlers, middleware, scheduler, server, utils, AppState,
+};
+
+use cli::{Cli, Command};
 use config::Config;
 use handlers::{
+    append::{append_to_file, set_file_append_only},
     auth::{
-        change_password, ensure_admin_user, get_current_user, login, login_legacy, logout,
-        logout_all, refresh_token, register, register_legacy,
+        change_password, ensure_admin_user, get_current_user, get_notification_preferences,
+        get_pow_challenge, login, login_legacy, logout, logout_all, refresh_token, register,
+        register_legacy, report_login, update_notification_preferences,
     },
+    backup::{list_backups, trigger_backup},
+    changes::project_changes,
+    content::{get_file_content, update_file_content, MAX_TEXT_CONTENT_SIZE},
+    database::database_stats,
+    delta::{file_signatures, upload_delta, MAX_DELTA_INSTRUCTIONS_SIZE},
+    event::replay_project_events,
+    export::export_project_files,
     file::{
-        bulk_delete_files, delete_file, delete_folder_files, download_file, list_project_files,
+        bulk_copy_files, bulk_delete_files, bulk_move_files, compression_stats, copy_file,
+        create_folder_access_cookie, create_upload_policy, deduplicate_files, delete_file,
+        delete_folder_files, download_archive, download_file, download_preflight, list_duplicates,
+        list_project_files, recent_files, review_file, set_file_legal_hold, update_file,
         upload_file,
     },
-    folder::{create_folder, list_folders, update_folder_visibility},
+    folder::{
+        bulk_update_folder_visibility, create_folder, folder_tree, list_folders,
+        set_folder_legal_hold, set_project_folder_visibility, update_folder_visibility,
+    },
+    ftp::run_ftp_server,
+    inbound_email::{
+        get_inbound_email, receive_inbound_email, update_inbound_email, MAX_INBOUND_EMAIL_SIZE,
+    },
+    ingest::run_mqtt_bridge,
+    integrity::{integrity_report, trigger_integrity_check},
+    jobs::list_jobs,
+    manifest::project_manifest,
+    media::{get_file_poster, get_file_waveform},
+    member::{add_member, list_members, remove_member},
+    metrics::serve_metrics,
+    mirror::{
+        delete_project_mirror, get_project_mirror, run_mirror_syncs, set_project_mirror,
+        sync_project_mirror,
+    },
+    multipart::{
+        abort_multipart_upload, complete_multipart_upload, get_multipart_upload,
+        initiate_multipart_upload, multipart_upload_events, upload_multipart_part,
+    },
+    notification::{
+        create_notification_channel, delete_notification_channel, list_notification_channels,
+    },
     project::{
-        create_project, delete_project, empty_project, get_project, list_projects,
-        regenerate_api_key, update_project,
+        admin_list_projects, create_project, delete_project, empty_project, get_project,
+        list_projects, regenerate_api_key, restore_project, revoke_previous_api_key,
+        update_project, verify_custom_domain,
     },
+    purge::{get_project_purge, list_project_purges, retry_project_purge},
+    render::render_file,
+    replication::{delete_replicated_blob, receive_replicated_blob, replication_status},
+    report::{delete_stale_files, largest_files_report, stale_files_report},
+    robots::robots_txt,
+    share::email_share_link,
+    star::{list_starred_files, star_file, unstar_file},
+    storage::{
+        cutover_storage_migration, fail_interrupted_migrations, get_storage_migration,
+        list_storage_migrations, start_storage_migration,
+    },
+    version::{download_file_version, list_file_versions},
+};
+use middleware::{
+    api_key_usage_middleware, api_version_middleware, client_ip_middleware,
+    host_routing_middleware, load_shed_middleware, optional_auth, problem_details_middleware,
+    request_signature_middleware, request_timeout_middleware, require_auth,
+    require_password_changed, ClientIpKeyExtractor,
+};
+use scheduler::{
+    purge_expired_refresh_tokens, purge_idempotency_keys, purge_login_history, Scheduler,
+};
+use utils::{
+    begin_backup, begin_integrity_run, deliver_notifications, dispatch_outbox,
+    expire_multipart_uploads, fail_interrupted_backups, fail_interrupted_integrity_runs,
+    open_geoip_database, purge_delivered_events, purge_file_changes, queue_replication_catch_up,
+    reconcile_file_counters, remove_deleted_derived_blobs, reopen_interrupted_multipart_uploads,
+    requeue_interrupted_purges, run_backup, run_cdn_purges, run_integrity_check, run_lifecycle,
+    run_media_previews, run_moderation, run_project_purges, run_replication, ApiKeyUsageTracker,
+    BackupTarget, CdnPurger, ColdStorage, DownloadTracker, EventPublisher, HostProjectCache,
+    HttpModerator, LoadShedder, Mailer, MemoryBudget, Metrics, Moderator, MultipartProgress,
+    PowReplayCache, PrecompressQueue, QueryMetricsLayer, ReplicationPeer, SignatureReplayCache,
+    TokenRevocationCache, UploadLimiter, QUERY_LOG_TARGET,
 };
-use middleware::{optional_auth, require_auth};
 
-#[derive(Clone)]
-pub struct AppState {
-    pub pool: PgPool,
-    pub config: Arc<Config>,
-}
+/// How often buffered API key usage is written to the database
+const API_KEY_USAGE_FLUSH_INTERVAL_SECS: u64 = 30;
+
+/// How often buffered file download counts are written to the database
+const DOWNLOAD_STATS_FLUSH_INTERVAL_SECS: u64 = 30;
+
+/// How often connection pool metrics are sampled
+const POOL_METRICS_INTERVAL_SECS: u64 = 10;
+
+/// How often expired refresh tokens and idempotency keys are deleted
+const PURGE_INTERVAL_SECS: u64 = 3600;
+
+/// How often blobs of deleted file versions and media previews are removed from disk
+const DERIVED_BLOB_CLEANUP_INTERVAL_SECS: u64 = 600;
+
+/// How often cached file counts and sizes are recounted from the files table
+const COUNTER_RECONCILE_INTERVAL_SECS: u64 = 6 * 3600;
+
+/// How often the event outbox is checked for undelivered events
+const OUTBOX_POLL_INTERVAL_SECS: u64 = 1;
+
+/// How often queued Slack/Discord notifications are posted
+const NOTIFICATION_POLL_INTERVAL_SECS: u64 = 5;
+
+/// How often uploads held for moderation are sent to the moderation service
+const MODERATION_POLL_INTERVAL_SECS: u64 = 5;
+
+/// How often queued video posters and audio waveforms are generated
+const MEDIA_PREVIEW_POLL_INTERVAL_SECS: u64 = 10;
+
+/// How often blobs of deleted projects are looked for
+const PROJECT_PURGE_POLL_INTERVAL_SECS: u64 = 10;
+
+/// How often idle multipart uploads are looked for and aborted
+const MULTIPART_CLEANUP_INTERVAL_SECS: u64 = 3600;
+
+/// How often queued blobs are pushed to the replication peer
+const REPLICATION_POLL_INTERVAL_SECS: u64 = 5;
+
+/// How often files the replication peer is behind on are looked for
+const REPLICATION_CATCH_UP_INTERVAL_SECS: u64 = 3600;
+
+/// How often queued CDN purges are sent
+const CDN_PURGE_POLL_INTERVAL_SECS: u64 = 5;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Initialize tracing
+    let cli = Cli::parse();
+    let command = cli.command.unwrap_or(Command::Serve);
+
+    // Initialize tracing; query timings are fed to the metrics regardless of the log filter
+    let metrics = Arc::new(Metrics::new());
     tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().with_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| {
+                "filerunner_backend=debug,tower_http=debug,sqlx::query=warn".into()
+            }),
+        ))
         .with(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "filerunner_backend=debug,tower_http=debug".into()),
+            QueryMetricsLayer::new(metrics.clone())
+                .with_filter(Targets::new().with_target(QUERY_LOG_TARGET, Level::TRACE)),
         )
-        .with(tracing_subscriber::fmt::layer())
         .init();
 
     // Load configuration
@@ -61,10 +180,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     tracing::info!("Configuration loaded");
 
     // Create database pool
+    let query_logging = db::QueryLogging {
+        slow_threshold: (config.db_slow_query_ms > 0)
+            .then(|| Duration::from_millis(config.db_slow_query_ms)),
+        trace_all: config.metrics_token.is_some(),
+    };
     let pool = db::create_pool(
         &config.database_url,
         config.db_min_connections,
         config.db_max_connections,
+        query_logging,
     )
     .await?;
     tracing::info!(
@@ -73,23 +198,676 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         config.db_max_connections
     );
 
-    // Run migrations
-    tracing::info!("Running database migrations...");
-    sqlx::migrate!("./migrations").run(&pool).await?;
-    tracing::info!("Migrations completed");
+    // Run migrations, or with MIGRATE_ON_START=false require a prior `migrate` run
+    let migrate_only = cli.migrate || matches!(command, Command::Migrate);
+    if migrate_only || config.migrate_on_start {
+        tracing::info!("Running database migrations...");
+        db::run_migrations(&pool).await?;
+        tracing::info!("Migrations completed");
+        if migrate_only {
+            return Ok(());
+        }
+    } else {
+        let pending = db::pending_migrations(&pool).await?;
+        if !pending.is_empty() {
+            return Err(format!(
+                "Database has {} pending migrations (first: {}); run `migrate` first",
+                pending.len(),
+                pending[0]
+            )
+            .into());
+        }
+    }
+
+    // Maintenance subcommands only need the primary database
+    if !command.is_serve() {
+        return cli::run(command, &pool, &config).await;
+    }
+
+    // Read replicas share the pool size settings
+    let read_pool = db::ReadPool::connect(
+        pool.clone(),
+        &config.database_read_urls,
+        config.db_min_connections,
+        config.db_max_connections,
+        query_logging,
+    )
+    .await?;
+    if !read_pool.replicas().is_empty() {
+        tracing::info!("Routing reads to {} replicas", read_pool.replicas().len());
+    }
 
     // Ensure admin user exists
-    ensure_admin_user(&pool, &config.admin_email, &config.admin_password).await?;
+    ensure_admin_user(
+        &pool,
+        &config.admin_email,
+        &config.admin_password,
+        &config.password_hashing,
+    )
+    .await?;
+
+    // Storage migrations don't survive a restart; mark them failed so they can be rerun
+    fail_interrupted_migrations(&pool).await?;
+    fail_interrupted_backups(&pool).await?;
+    fail_interrupted_integrity_runs(&pool).await?;
+    requeue_interrupted_purges(&pool).await?;
+    reopen_interrupted_multipart_uploads(&pool).await?;
 
     // Create storage directory if it doesn't exist
     tokio::fs::create_dir_all(&config.storage_path).await?;
     tracing::info!("Storage directory ready: {}", config.storage_path);
 
+    // Load GeoIP database for download geo-restrictions (optional)
+    let geoip = match config.geoip_database_path {
+        Some(ref path) => {
+            let reader = open_geoip_database(path)?;
+            tracing::info!("GeoIP database loaded: {}", path);
+            Some(Arc::new(reader))
+        }
+        None => None,
+    };
+
+    // Cold storage tier for lifecycle rules (optional)
+    let cold_storage = match config.cold_storage_url {
+        Some(ref url) => {
+            let cold = ColdStorage::open(url)?;
+            tracing::info!("Cold storage ready: {}", url);
+            Some(Arc::new(cold))
+        }
+        None => None,
+    };
+
+    // Backup target for incremental blob copies (optional)
+    let backup_target = match config.backup_target_url {
+        Some(ref url) => {
+            let target = BackupTarget::open(url)?;
+            tracing::info!("Backup target ready: {}", url);
+            Some(Arc::new(target))
+        }
+        None => None,
+    };
+
+    // Broker for file lifecycle events (optional)
+    let events = match config.event_broker_url {
+        Some(ref url) => {
+            let publisher = EventPublisher::connect(
+                url,
+                config.event_topic_prefix.clone(),
+                config.event_schema,
+            )
+            .await?;
+            tracing::info!("Publishing file events to {}", url);
+            Some(Arc::new(publisher))
+        }
+        None => None,
+    };
+
+    // SMTP mailer for account notifications (optional)
+    let mailer = match config.smtp_url {
+        Some(ref url) => {
+            let mailer = Mailer::new(url, &config.mail_from)?;
+            tracing::info!("Outgoing email enabled");
+            Some(Arc::new(mailer))
+        }
+        None => None,
+    };
+
+    // Content moderation service for uploads (optional)
+    let moderator = match config.moderation_url {
+        Some(ref url) => {
+            let moderator = HttpModerator::new(url, config.moderation_token.clone())?;
+            tracing::info!("Moderating uploads with {}", url);
+            Some(Arc::new(moderator) as Arc<dyn Moderator>)
+        }
+        None => None,
+    };
+
+    // Standby instance that blobs are mirrored to (optional)
+    let replication = match config.replication_peer_url {
+        Some(ref url) => {
+            let token = config
+                .replication_token
+                .clone()
+                .ok_or("REPLICATION_TOKEN must be set when REPLICATION_PEER_URL is")?;
+            let peer = ReplicationPeer::new(url, token, &config.storage_path)?;
+            let queued = queue_replication_catch_up(&pool).await?;
+            tracing::info!("Replicating blobs to {} ({} files behind)", url, queued);
+            Some(Arc::new(peer))
+        }
+        None => None,
+    };
+
+    // CDN that overwritten and deleted public files are purged from (optional)
+    let cdn = CdnPurger::from_config(&config)?.map(|cdn| {
+        tracing::info!(
+            "Purging changed public files from the CDN ({:?})",
+            cdn.provider()
+        );
+        Arc::new(cdn)
+    });
+
     let app_state = AppState {
         pool,
+        read_pool,
         config: Arc::new(config.clone()),
+        geoip,
+        upload_limiter: Arc::new(UploadLimiter::new()),
+        multipart_progress: Arc::new(MultipartProgress::new()),
+        api_key_usage: Arc::new(ApiKeyUsageTracker::new()),
+        download_stats: Arc::new(DownloadTracker::new()),
+        load_shedder: Arc::new(LoadShedder::new()),
+        memory_budget: Arc::new(MemoryBudget::new(
+            config.memory_budget_bytes,
+            Duration::from_secs(config.memory_budget_wait_secs),
+            config.stream_buffer_pool_size,
+        )),
+        signature_replay: Arc::new(SignatureReplayCache::new()),
+        pow_replay: Arc::new(PowReplayCache::new()),
+        host_projects: Arc::new(HostProjectCache::new()),
+        token_revocations: Arc::new(TokenRevocationCache::new(Duration::from_secs(
+            config.token_revocation_cache_secs,
+        ))),
+        precompress: Arc::new(PrecompressQueue::new()),
+        cold_storage,
+        backup_target,
+        events,
+        mailer,
+        moderator,
+        replication,
+        cdn,
+        scheduler: Arc::new(Scheduler::new()),
+        metrics,
     };
 
+    let scheduler = &app_state.scheduler;
+
+    // Periodically persist API key usage collected by the usage middleware
+    {
+        let pool = app_state.pool.clone();
+        let tracker = app_state.api_key_usage.clone();
+        scheduler.spawn(
+            "api_key_usage_flush",
+            Duration::from_secs(API_KEY_USAGE_FLUSH_INTERVAL_SECS),
+            move || {
+                let pool = pool.clone();
+                let tracker = tracker.clone();
+                async move { tracker.flush(&pool).await.map_err(|e| e.to_string()) }
+            },
+        );
+    }
+
+    // Periodically persist download counts and access times recorded by downloads
+    {
+        let pool = app_state.pool.clone();
+        let tracker = app_state.download_stats.clone();
+        scheduler.spawn(
+            "download_stats_flush",
+            Duration::from_secs(DOWNLOAD_STATS_FLUSH_INTERVAL_SECS),
+            move || {
+                let pool = pool.clone();
+                let tracker = tracker.clone();
+                async move { tracker.flush(&pool).await.map_err(|e| e.to_string()) }
+            },
+        );
+    }
+
+    // Connection pool gauges and checkout latency for GET /metrics
+    if config.metrics_token.is_some() {
+        let pool = app_state.pool.clone();
+        let read_pool = app_state.read_pool.clone();
+        let metrics = app_state.metrics.clone();
+        scheduler.spawn(
+            "db_pool_metrics",
+            Duration::from_secs(POOL_METRICS_INTERVAL_SECS),
+            move || {
+                let pool = pool.clone();
+                let read_pool = read_pool.clone();
+                let metrics = metrics.clone();
+                async move {
+                    metrics
+                        .sample_pool("primary", &pool)
+                        .await
+                        .map_err(|e| e.to_string())?;
+                    for (i, replica) in read_pool.replicas().iter().enumerate() {
+                        metrics
+                            .sample_pool(&format!("replica{i}"), replica)
+                            .await
+                            .map_err(|e| e.to_string())?;
+                    }
+                    Ok(())
+                }
+            },
+        );
+    }
+
+    // Retention: drop expired sessions, stale idempotency keys and old login history
+    {
+        let pool = app_state.pool.clone();
+        scheduler.spawn(
+            "refresh_token_purge",
+            Duration::from_secs(PURGE_INTERVAL_SECS),
+            move || {
+                let pool = pool.clone();
+                async move { purge_expired_refresh_tokens(&pool).await }
+            },
+        );
+        let pool = app_state.pool.clone();
+        scheduler.spawn(
+            "idempotency_key_purge",
+            Duration::from_secs(PURGE_INTERVAL_SECS),
+            move || {
+                let pool = pool.clone();
+                async move { purge_idempotency_keys(&pool).await }
+            },
+        );
+        let pool = app_state.pool.clone();
+        scheduler.spawn(
+            "login_history_purge",
+            Duration::from_secs(PURGE_INTERVAL_SECS),
+            move || {
+                let pool = pool.clone();
+                async move { purge_login_history(&pool).await }
+            },
+        );
+    }
+
+    // Forget file changes too old for the changes feed
+    {
+        let pool = app_state.pool.clone();
+        let retention_days = config.changes_retention_days;
+        scheduler.spawn(
+            "file_change_purge",
+            Duration::from_secs(PURGE_INTERVAL_SECS),
+            move || {
+                let pool = pool.clone();
+                async move {
+                    purge_file_changes(&pool, retention_days)
+                        .await
+                        .map(|_| ())
+                        .map_err(|e| e.to_string())
+                }
+            },
+        );
+    }
+
+    // Delete blobs of file versions and media previews removed with their files
+    {
+        let pool = app_state.pool.clone();
+        scheduler.spawn(
+            "derived_blob_cleanup",
+            Duration::from_secs(DERIVED_BLOB_CLEANUP_INTERVAL_SECS),
+            move || {
+                let pool = pool.clone();
+                async move { remove_deleted_derived_blobs(&pool).await }
+            },
+        );
+    }
+
+    // Correct any drift in the cached project and folder file counters
+    {
+        let pool = app_state.pool.clone();
+        scheduler.spawn(
+            "file_counter_reconcile",
+            Duration::from_secs(COUNTER_RECONCILE_INTERVAL_SECS),
+            move || {
+                let pool = pool.clone();
+                async move {
+                    let corrected = reconcile_file_counters(&pool)
+                        .await
+                        .map_err(|e| e.to_string())?;
+                    if corrected > 0 {
+                        tracing::warn!("Corrected {} drifted file counters", corrected);
+                    }
+                    Ok(())
+                }
+            },
+        );
+    }
+
+    // Sample runtime scheduling lag for load shedding, and it and memory budget
+    // use for GET /metrics
+    if config.load_shed_max_lag_ms > 0 || config.metrics_token.is_some() {
+        let shedder = app_state.load_shedder.clone();
+        let budget = app_state.memory_budget.clone();
+        let metrics = app_state.metrics.clone();
+        tokio::spawn(async move {
+            loop {
+                shedder.sample_lag().await;
+                metrics.record_load(shedder.in_flight(), shedder.lag());
+                metrics.record_memory(budget.in_use(), budget.capacity(), budget.pooled_buffers());
+            }
+        });
+    }
+
+    // Deliver file events recorded in the outbox to the broker
+    if let Some(ref events) = app_state.events {
+        let pool = app_state.pool.clone();
+        let events = events.clone();
+        let retention_days = config.event_retention_days;
+        tokio::spawn(async move {
+            let mut interval =
+                tokio::time::interval(std::time::Duration::from_secs(OUTBOX_POLL_INTERVAL_SECS));
+            loop {
+                interval.tick().await;
+                // Drain everything that is due before waiting again
+                loop {
+                    match dispatch_outbox(&pool, &events, retention_days).await {
+                        Ok(delivered) if delivered > 0 => continue,
+                        Ok(_) => break,
+                        Err(e) => {
+                            tracing::warn!("Event outbox dispatch failed: {}", e);
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+
+        // Forget delivered events once they're too old to replay
+        let pool = app_state.pool.clone();
+        let retention_days = config.event_retention_days;
+        scheduler.spawn(
+            "delivered_event_purge",
+            Duration::from_secs(PURGE_INTERVAL_SECS),
+            move || {
+                let pool = pool.clone();
+                async move {
+                    purge_delivered_events(&pool, retention_days)
+                        .await
+                        .map(|_| ())
+                        .map_err(|e| e.to_string())
+                }
+            },
+        );
+    }
+
+    // Store messages from the MQTT broker as files
+    if let Some(ref url) = config.mqtt_url {
+        tokio::spawn(run_mqtt_bridge(app_state.clone(), url.clone()));
+    }
+    if let Some(ref addr) = config.ftp_listen_addr {
+        tokio::spawn(run_ftp_server(app_state.clone(), addr.clone()));
+    }
+
+    // Post queued Slack/Discord notifications
+    {
+        let pool = app_state.pool.clone();
+        tokio::spawn(async move {
+            let client = reqwest::Client::builder()
+                .timeout(std::time::Duration::from_secs(10))
+                .build()
+                .expect("Failed to build HTTP client");
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(
+                NOTIFICATION_POLL_INTERVAL_SECS,
+            ));
+            loop {
+                interval.tick().await;
+                loop {
+                    match deliver_notifications(&pool, &client).await {
+                        Ok(delivered) if delivered > 0 => continue,
+                        Ok(_) => break,
+                        Err(e) => {
+                            tracing::warn!("Notification delivery failed: {}", e);
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    // Send uploads held for moderation to the moderation service
+    if let Some(ref moderator) = app_state.moderator {
+        let pool = app_state.pool.clone();
+        let moderator = moderator.clone();
+        let cold = app_state.cold_storage.clone();
+        let mailer = app_state.mailer.clone();
+        scheduler.spawn(
+            "content_moderation",
+            Duration::from_secs(MODERATION_POLL_INTERVAL_SECS),
+            move || {
+                let pool = pool.clone();
+                let moderator = moderator.clone();
+                let cold = cold.clone();
+                let mailer = mailer.clone();
+                async move {
+                    // Work through everything that is due before waiting again
+                    while run_moderation(
+                        &pool,
+                        moderator.as_ref(),
+                        cold.as_deref(),
+                        mailer.as_deref(),
+                    )
+                    .await
+                    .map_err(|e| e.to_string())?
+                        > 0
+                    {}
+                    Ok(())
+                }
+            },
+        );
+    }
+
+    // Generate posters (and animated previews) for video uploads and waveforms
+    // for audio uploads
+    if config.video_posters || config.audio_waveforms {
+        let pool = app_state.pool.clone();
+        let cold = app_state.cold_storage.clone();
+        let config = app_state.config.clone();
+        scheduler.spawn(
+            "media_previews",
+            Duration::from_secs(MEDIA_PREVIEW_POLL_INTERVAL_SECS),
+            move || {
+                let pool = pool.clone();
+                let cold = cold.clone();
+                let config = config.clone();
+                async move {
+                    // Work through everything that is due before waiting again
+                    while run_media_previews(&pool, cold.as_deref(), &config)
+                        .await
+                        .map_err(|e| e.to_string())?
+                        > 0
+                    {}
+                    Ok(())
+                }
+            },
+        );
+    }
+
+    // Remove the blobs of deleted projects
+    {
+        let pool = app_state.pool.clone();
+        let storage_path = config.storage_path.clone();
+        let cold = app_state.cold_storage.clone();
+        scheduler.spawn(
+            "project_purge",
+            Duration::from_secs(PROJECT_PURGE_POLL_INTERVAL_SECS),
+            move || {
+                let pool = pool.clone();
+                let storage_path = storage_path.clone();
+                let cold = cold.clone();
+                async move {
+                    run_project_purges(&pool, &storage_path, cold.as_deref())
+                        .await
+                        .map_err(|e| e.to_string())?;
+                    Ok(())
+                }
+            },
+        );
+    }
+
+    // Abort multipart uploads that were abandoned
+    {
+        let pool = app_state.pool.clone();
+        let storage_path = config.storage_path.clone();
+        scheduler.spawn(
+            "multipart_upload_cleanup",
+            Duration::from_secs(MULTIPART_CLEANUP_INTERVAL_SECS),
+            move || {
+                let pool = pool.clone();
+                let storage_path = storage_path.clone();
+                async move {
+                    let aborted = expire_multipart_uploads(&pool, &storage_path)
+                        .await
+                        .map_err(|e| e.to_string())?;
+                    if aborted > 0 {
+                        tracing::info!("Aborted {} idle multipart uploads", aborted);
+                    }
+                    Ok(())
+                }
+            },
+        );
+    }
+
+    // Push changed blobs to the replication peer, and look for anything it
+    // missed (changes made while replication was off, purges, cold moves)
+    if let Some(ref peer) = app_state.replication {
+        let pool = app_state.pool.clone();
+        let peer = peer.clone();
+        scheduler.spawn(
+            "replication",
+            Duration::from_secs(REPLICATION_POLL_INTERVAL_SECS),
+            move || {
+                let pool = pool.clone();
+                let peer = peer.clone();
+                async move {
+                    // Work through everything that is due before waiting again
+                    while run_replication(&pool, &peer)
+                        .await
+                        .map_err(|e| e.to_string())?
+                        > 0
+                    {}
+                    Ok(())
+                }
+            },
+        );
+
+        let pool = app_state.pool.clone();
+        scheduler.spawn(
+            "replication_catch_up",
+            Duration::from_secs(REPLICATION_CATCH_UP_INTERVAL_SECS),
+            move || {
+                let pool = pool.clone();
+                async move {
+                    let queued = queue_replication_catch_up(&pool)
+                        .await
+                        .map_err(|e| e.to_string())?;
+                    if queued > 0 {
+                        tracing::info!("Queued {} files the replication peer is behind on", queued);
+                    }
+                    Ok(())
+                }
+            },
+        );
+    }
+
+    // Purge overwritten and deleted public files from the CDN
+    if let Some(ref cdn) = app_state.cdn {
+        let pool = app_state.pool.clone();
+        let cdn = cdn.clone();
+        scheduler.spawn(
+            "cdn_purge",
+            Duration::from_secs(CDN_PURGE_POLL_INTERVAL_SECS),
+            move || {
+                let pool = pool.clone();
+                let cdn = cdn.clone();
+                async move {
+                    while run_cdn_purges(&pool, &cdn)
+                        .await
+                        .map_err(|e| e.to_string())?
+                        > 0
+                    {}
+                    Ok(())
+                }
+            },
+        );
+    }
+
+    // Move idle files of projects with a lifecycle rule to cold storage
+    if let Some(ref cold) = app_state.cold_storage {
+        let pool = app_state.pool.clone();
+        let cold = cold.clone();
+        scheduler.spawn(
+            "storage_lifecycle",
+            Duration::from_secs(config.lifecycle_interval_secs.max(60)),
+            move || {
+                let pool = pool.clone();
+                let cold = cold.clone();
+                async move {
+                    let moved = run_lifecycle(&pool, &cold)
+                        .await
+                        .map_err(|e| e.to_string())?;
+                    if moved > 0 {
+                        tracing::info!("Moved {} files to cold storage", moved);
+                    }
+                    Ok(())
+                }
+            },
+        );
+    }
+
+    // Pull changes into mirror projects (MIRROR_SYNC_INTERVAL_SECS = 0 leaves
+    // only syncs started through the API)
+    if config.mirror_sync_interval_secs > 0 {
+        let state = app_state.clone();
+        scheduler.spawn(
+            "mirror_sync",
+            Duration::from_secs(config.mirror_sync_interval_secs.max(30)),
+            move || {
+                let state = state.clone();
+                async move { run_mirror_syncs(&state).await }
+            },
+        );
+    }
+
+    // Scheduled backups (BACKUP_INTERVAL_HOURS = 0 leaves only manual runs)
+    if config.backup_interval_hours > 0 {
+        let pool = app_state.pool.clone();
+        let backup_path = config.backup_path.clone();
+        let target = app_state.backup_target.clone();
+        scheduler.spawn(
+            "scheduled_backup",
+            Duration::from_secs(config.backup_interval_hours * 3600),
+            move || {
+                let pool = pool.clone();
+                let backup_path = backup_path.clone();
+                let target = target.clone();
+                async move {
+                    let backup = begin_backup(&pool, "scheduled", None)
+                        .await
+                        .map_err(|e| format!("Skipping scheduled backup: {e}"))?;
+                    run_backup(pool, backup_path, target, backup.id).await;
+                    Ok(())
+                }
+            },
+        );
+    }
+
+    // Re-hash stored blobs against their recorded checksums
+    // (INTEGRITY_CHECK_INTERVAL_HOURS = 0 leaves only manual runs)
+    if config.integrity_check_interval_hours > 0 {
+        let pool = app_state.pool.clone();
+        let cold = app_state.cold_storage.clone();
+        let metrics = app_state.metrics.clone();
+        let sample_size = Some(config.integrity_check_sample_size).filter(|&n| n > 0);
+        scheduler.spawn(
+            "integrity_check",
+            Duration::from_secs(config.integrity_check_interval_hours * 3600),
+            move || {
+                let pool = pool.clone();
+                let cold = cold.clone();
+                let metrics = metrics.clone();
+                async move {
+                    let run = begin_integrity_run(&pool, sample_size)
+                        .await
+                        .map_err(|e| format!("Skipping integrity check: {e}"))?;
+                    run_integrity_check(pool, cold, metrics, run).await;
+                    Ok(())
+                }
+            },
+        );
+    }
+
     // Configure CORS with specific methods and headers for security
     let cors = CorsLayer::new()
         .allow_origin(
@@ -110,10 +888,19 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             header::AUTHORIZATION,
             header::CONTENT_TYPE,
             header::HeaderName::from_static("x-api-key"),
+            header::HeaderName::from_static("idempotency-key"),
+            header::HeaderName::from_static("x-upload-policy"),
+            header::HeaderName::from_static("x-signature"),
+            header::HeaderName::from_static("x-timestamp"),
+            header::HeaderName::from_static("x-project-id"),
+            header::HeaderName::from_static("x-api-version"),
+            header::HeaderName::from_static("x-request-id"),
         ]);
 
     // Configure rate limiting for auth endpoints (5 requests per second per IP)
+    // Keyed on the real client IP (resolved through TRUSTED_PROXIES)
     let auth_rate_limit = GovernorConfigBuilder::default()
+        .key_extractor(ClientIpKeyExtractor::new(config.trusted_proxies.clone()))
         .per_second(5)
         .burst_size(10)
         .finish()
@@ -121,6 +908,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Configure rate limiting for file uploads (10 requests per minute per IP)
     let upload_rate_limit = GovernorConfigBuilder::default()
+        .key_extractor(ClientIpKeyExtractor::new(config.trusted_proxies.clone()))
         .per_second(1)
         .burst_size(10)
         .finish()
@@ -129,53 +917,256 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Auth routes with rate limiting (public - no JWT required)
     let auth_routes = Router::new()
         // New dual-token endpoints
-        .route("/api/auth/register", post(register))
-        .route("/api/auth/login", post(login))
-        .route("/api/auth/refresh", post(refresh_token))
+        .route("/api/v1/auth/register", post(register))
+        .route("/api/v1/auth/login", post(login))
+        .route("/api/v1/auth/refresh", post(refresh_token))
+        .route("/api/v1/auth/challenge", get(get_pow_challenge))
+        .route("/api/v1/auth/login-alerts/report", post(report_login))
         // Legacy single-token endpoints (for backward compatibility)
-        .route("/api/auth/register-legacy", post(register_legacy))
-        .route("/api/auth/login-legacy", post(login_legacy))
+        .route("/api/v1/auth/register-legacy", post(register_legacy))
+        .route("/api/v1/auth/login-legacy", post(login_legacy))
         .layer(GovernorLayer {
             config: Arc::new(auth_rate_limit),
         });
 
     // Upload routes with rate limiting (API key based)
     let upload_routes = Router::new()
-        .route("/api/upload", post(upload_file))
-        .route("/api/folders/delete", post(delete_folder_files))
+        .route("/api/v1/upload", post(upload_file))
+        .route("/api/v1/uploads/multipart", post(initiate_multipart_upload))
+        .route(
+            "/api/v1/uploads/multipart/:id",
+            delete(abort_multipart_upload),
+        )
+        .route(
+            "/api/v1/uploads/multipart/:id/complete",
+            post(complete_multipart_upload),
+        )
+        .route("/api/v1/folders/delete", post(delete_folder_files))
         .layer(GovernorLayer {
             config: Arc::new(upload_rate_limit),
         });
 
+    // Parts of a multipart upload and its status, outside the upload rate
+    // limit: one upload sends up to 10000 parts. Parts in flight are capped
+    // per project by MAX_CONCURRENT_UPLOADS instead.
+    let multipart_part_routes = Router::new()
+        .route("/api/v1/uploads/multipart/:id", get(get_multipart_upload))
+        .route(
+            "/api/v1/uploads/multipart/:id/events",
+            get(multipart_upload_events),
+        )
+        .route(
+            "/api/v1/uploads/multipart/:id/parts/:part_number",
+            put(upload_multipart_part),
+        );
+
     // Protected routes (require authentication)
     let protected_routes = Router::new()
         // Auth routes (protected)
-        .route("/api/auth/me", get(get_current_user))
-        .route("/api/auth/change-password", put(change_password))
-        .route("/api/auth/logout", post(logout))
-        .route("/api/auth/logout-all", post(logout_all))
+        .route("/api/v1/auth/me", get(get_current_user))
+        .route("/api/v1/auth/change-password", put(change_password))
+        .route("/api/v1/auth/logout", post(logout))
+        .route("/api/v1/auth/logout-all", post(logout_all))
+        .route(
+            "/api/v1/auth/notification-preferences",
+            get(get_notification_preferences),
+        )
+        .route(
+            "/api/v1/auth/notification-preferences",
+            put(update_notification_preferences),
+        )
         // Project routes (protected)
-        .route("/api/projects", post(create_project))
-        .route("/api/projects", get(list_projects))
-        .route("/api/projects/:id", get(get_project))
-        .route("/api/projects/:id", put(update_project))
-        .route("/api/projects/:id", delete(delete_project))
-        .route("/api/projects/:id/regenerate-key", post(regenerate_api_key))
-        .route("/api/projects/:id/files", get(list_project_files))
-        .route("/api/projects/:id/empty", delete(empty_project))
+        .route("/api/v1/projects", post(create_project))
+        .route("/api/v1/projects", get(list_projects))
+        .route("/api/v1/projects/:id", get(get_project))
+        .route("/api/v1/projects/:id", put(update_project))
+        .route("/api/v1/projects/:id", delete(delete_project))
+        .route(
+            "/api/v1/projects/:id/regenerate-key",
+            post(regenerate_api_key),
+        )
+        .route(
+            "/api/v1/projects/:id/previous-key",
+            delete(revoke_previous_api_key),
+        )
+        .route("/api/v1/projects/:id/files", get(list_project_files))
+        .route(
+            "/api/v1/projects/:id/upload-policy",
+            post(create_upload_policy),
+        )
+        .route("/api/v1/projects/:id/empty", delete(empty_project))
+        .route("/api/v1/projects/:id/restore", post(restore_project))
+        .route(
+            "/api/v1/projects/:id/custom-domain/verify",
+            post(verify_custom_domain),
+        )
+        .route("/api/v1/projects/purges/:id", get(get_project_purge))
+        .route("/api/v1/projects/:id/duplicates", get(list_duplicates))
+        .route("/api/v1/projects/:id/compression", get(compression_stats))
+        .route(
+            "/api/v1/projects/:id/duplicates/deduplicate",
+            post(deduplicate_files),
+        )
+        .route(
+            "/api/v1/projects/:id/members",
+            post(add_member).get(list_members),
+        )
+        .route(
+            "/api/v1/projects/:id/members/:user_id",
+            delete(remove_member),
+        )
+        .route(
+            "/api/v1/projects/:id/notifications",
+            post(create_notification_channel).get(list_notification_channels),
+        )
+        .route(
+            "/api/v1/projects/:id/notifications/:channel_id",
+            delete(delete_notification_channel),
+        )
+        .route(
+            "/api/v1/projects/:id/inbound-email",
+            get(get_inbound_email).put(update_inbound_email),
+        )
+        .route(
+            "/api/v1/projects/:id/mirror",
+            get(get_project_mirror)
+                .put(set_project_mirror)
+                .delete(delete_project_mirror),
+        )
+        .route(
+            "/api/v1/projects/:id/mirror/sync",
+            post(sync_project_mirror),
+        )
+        // Dashboard file lists and stars (protected)
+        .route("/api/v1/files/recent", get(recent_files))
+        .route("/api/v1/files/starred", get(list_starred_files))
+        .route(
+            "/api/v1/files/:id/star",
+            post(star_file).delete(unstar_file),
+        )
+        .route("/api/v1/files/:id/legal-hold", put(set_file_legal_hold))
+        // Admin routes (protected, admin role checked in handlers)
+        .route("/api/v1/admin/projects", get(admin_list_projects))
+        .route(
+            "/api/v1/admin/storage/migrate",
+            post(start_storage_migration).get(list_storage_migrations),
+        )
+        .route(
+            "/api/v1/admin/storage/migrate/:id",
+            get(get_storage_migration),
+        )
+        .route(
+            "/api/v1/admin/backups",
+            get(list_backups).post(trigger_backup),
+        )
+        .route(
+            "/api/v1/admin/integrity",
+            get(integrity_report).post(trigger_integrity_check),
+        )
+        .route("/api/v1/admin/replication", get(replication_status))
+        .route("/api/v1/admin/jobs", get(list_jobs))
+        .route("/api/v1/admin/purges", get(list_project_purges))
+        .route("/api/v1/admin/purges/:id/retry", post(retry_project_purge))
+        .route("/api/v1/admin/database", get(database_stats))
+        .route(
+            "/api/v1/admin/storage/migrate/:id/cutover",
+            post(cutover_storage_migration),
+        )
         // Folder routes (protected)
-        .route("/api/folders", post(create_folder))
-        .route("/api/folders", get(list_folders))
-        .route("/api/folders/:id/visibility", put(update_folder_visibility))
+        .route("/api/v1/folders", post(create_folder))
+        .route("/api/v1/folders", get(list_folders))
+        .route("/api/v1/folders/tree", get(folder_tree))
+        .route(
+            "/api/v1/folders/:id/visibility",
+            put(update_folder_visibility),
+        )
+        .route("/api/v1/folders/:id/legal-hold", put(set_folder_legal_hold))
+        .route(
+            "/api/v1/folders/bulk-visibility",
+            put(bulk_update_folder_visibility),
+        )
+        .route(
+            "/api/v1/projects/:id/folders/visibility",
+            put(set_project_folder_visibility),
+        )
+        .layer(axum_middleware::from_fn_with_state(
+            app_state.clone(),
+            require_password_changed,
+        ))
         .layer(axum_middleware::from_fn_with_state(
             app_state.clone(),
             require_auth,
         ));
 
-    // File delete routes (support both JWT and API key authentication)
+    // File delete/edit/bulk/archive routes (support both JWT and API key authentication)
     let file_delete_routes = Router::new()
-        .route("/api/files/bulk", delete(bulk_delete_files))
-        .route("/api/files/:id", delete(delete_file))
+        .route("/api/v1/files/bulk", delete(bulk_delete_files))
+        .route("/api/v1/files/bulk-delete", post(bulk_delete_files))
+        .route("/api/v1/files/bulk-move", post(bulk_move_files))
+        .route("/api/v1/files/bulk-copy", post(bulk_copy_files))
+        .route("/api/v1/files/archive", post(download_archive))
+        .route("/api/v1/files/:id", delete(delete_file).patch(update_file))
+        .route("/api/v1/files/:id/moderation", put(review_file))
+        .route("/api/v1/files/:id/copy", post(copy_file))
+        .route("/api/v1/files/:id/share/email", post(email_share_link))
+        .route("/api/v1/files/:id/versions", get(list_file_versions))
+        .route(
+            "/api/v1/files/:id/versions/:version_id",
+            get(download_file_version),
+        )
+        .route("/api/v1/files/:id/poster", get(get_file_poster))
+        .route("/api/v1/files/:id/waveform", get(get_file_waveform))
+        .route("/api/v1/files/:id/render", get(render_file))
+        .route(
+            "/api/v1/files/:id/content",
+            get(get_file_content)
+                .put(update_file_content)
+                .layer(DefaultBodyLimit::max(MAX_TEXT_CONTENT_SIZE)),
+        )
+        .route("/api/v1/files/:id/append-only", put(set_file_append_only))
+        .route(
+            "/api/v1/files/:id/append",
+            // Limited by the file size cap while streaming
+            post(append_to_file).layer(DefaultBodyLimit::disable()),
+        )
+        .route("/api/v1/files/:id/signatures", get(file_signatures))
+        .route("/api/v1/projects/:id/manifest", get(project_manifest))
+        .route("/api/v1/projects/:id/changes", get(project_changes))
+        .route(
+            "/api/v1/projects/:id/reports/largest",
+            get(largest_files_report),
+        )
+        .route(
+            "/api/v1/projects/:id/reports/stale",
+            get(stale_files_report),
+        )
+        .route(
+            "/api/v1/projects/:id/reports/stale/delete",
+            post(delete_stale_files),
+        )
+        .route(
+            "/api/v1/projects/:id/files/export",
+            get(export_project_files),
+        )
+        .route(
+            "/api/v1/projects/:id/events/replay",
+            post(replay_project_events),
+        )
+        .route(
+            "/api/v1/projects/:id/access-cookie",
+            post(create_folder_access_cookie),
+        )
+        .route(
+            "/api/v1/files/:id/delta",
+            // The literal data may be as large as a whole file
+            post(upload_delta).layer(DefaultBodyLimit::max(
+                config.max_file_size + MAX_DELTA_INSTRUCTIONS_SIZE,
+            )),
+        )
+        .layer(axum_middleware::from_fn_with_state(
+            app_state.clone(),
+            require_password_changed,
+        ))
         .layer(axum_middleware::from_fn_with_state(
             app_state.clone(),
             optional_auth,
@@ -189,47 +1180,103 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .merge(auth_routes)
         // Merge rate-limited upload routes (API key based, no JWT auth)
         .merge(upload_routes)
+        // Merge multipart part routes (API key based, limited by concurrency)
+        .merge(multipart_part_routes)
         // Merge file delete routes (support both JWT and API key)
         .merge(file_delete_routes)
-        // File download (API key based, no rate limit needed for downloads)
-        .route("/api/files/:id", get(download_file))
         // Health check
         .route("/health", get(|| async { "OK" }))
+        .route("/robots.txt", get(robots_txt))
+        .route("/metrics", get(serve_metrics))
+        // Blob pushes from a replication primary (REPLICATION_TOKEN)
+        .route(
+            "/api/v1/replication/blobs/*path",
+            put(receive_replicated_blob).delete(delete_replicated_blob),
+        )
+        // Inbound email webhooks from Mailgun, Postmark or SES (INBOUND_EMAIL_TOKEN)
+        .route(
+            "/api/v1/inbound-email/:provider",
+            post(receive_inbound_email).layer(DefaultBodyLimit::max(MAX_INBOUND_EMAIL_SIZE)),
+        )
         .layer(cors)
-        // Security headers
-        .layer(SetResponseHeaderLayer::overriding(
+        // File download (API key or owner JWT, no rate limit needed for downloads)
+        // Registered after the global CORS layer: CORS is resolved per project
+        .route(
+            "/api/v1/files/:id",
+            get(download_file)
+                .layer(axum_middleware::from_fn_with_state(
+                    app_state.clone(),
+                    require_password_changed,
+                ))
+                .layer(axum_middleware::from_fn_with_state(
+                    app_state.clone(),
+                    optional_auth,
+                ))
+                .options(download_preflight),
+        )
+        // Shed listings when saturated (a route layer, so the matched route is known)
+        .route_layer(axum_middleware::from_fn_with_state(
+            app_state.clone(),
+            load_shed_middleware,
+        ))
+        // Track API key usage (runs inside the client IP layer so the IP is resolved)
+        .layer(axum_middleware::from_fn_with_state(
+            app_state.clone(),
+            api_key_usage_middleware,
+        ))
+        // Verify HMAC-signed machine requests and attach their API key
+        .layer(axum_middleware::from_fn_with_state(
+            app_state.clone(),
+            request_signature_middleware,
+        ))
+        // Resolve the real client IP before rate limiting and handlers see the request
+        .layer(axum_middleware::from_fn_with_state(
+            app_state.clone(),
+            client_ip_middleware,
+        ))
+        // Security headers, unless a project's download headers set their own
+        .layer(SetResponseHeaderLayer::if_not_present(
             header::X_CONTENT_TYPE_OPTIONS,
             HeaderValue::from_static("nosniff"),
         ))
-        .layer(SetResponseHeaderLayer::overriding(
+        .layer(SetResponseHeaderLayer::if_not_present(
             header::X_FRAME_OPTIONS,
             HeaderValue::from_static("DENY"),
         ))
-        .layer(SetResponseHeaderLayer::overriding(
+        .layer(SetResponseHeaderLayer::if_not_present(
             header::HeaderName::from_static("x-xss-protection"),
             HeaderValue::from_static("1; mode=block"),
         ))
-        .layer(SetResponseHeaderLayer::overriding(
+        .layer(SetResponseHeaderLayer::if_not_present(
             header::HeaderName::from_static("referrer-policy"),
             HeaderValue::from_static("strict-origin-when-cross-origin"),
         ))
-        .layer(SetResponseHeaderLayer::overriding(
+        .layer(SetResponseHeaderLayer::if_not_present(
             header::HeaderName::from_static("permissions-policy"),
             HeaderValue::from_static("camera=(), microphone=(), geolocation=()"),
         ))
-        .with_state(app_state);
+        .with_state(app_state.clone());
+
+    // Project subdomains / custom domains serve public files by path. This wraps the
+    // whole router because URI rewrites inside Router::layer happen after routing.
+    // Unversioned /api paths are rewritten to a version before routing, for the same reason
+    let app = axum_middleware::from_fn(api_version_middleware).layer(app);
+    let app =
+        axum_middleware::from_fn_with_state(app_state.clone(), host_routing_middleware).layer(app);
+    // Timeouts drop everything inside, and their 408s still get problem+json
+    let app = axum_middleware::from_fn_with_state(app_state.clone(), request_timeout_middleware)
+        .layer(app);
+    // Outermost, so errors from every layer above get a request ID and problem+json
+    let app = axum_middleware::from_fn_with_state(app_state, problem_details_middleware).layer(app);
 
-    let addr = format!("{}:{}", config.server_host, config.server_port);
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    let listener = server::listen(&config).await?;
+    let addr = listener.local_addr()?;
 
     tracing::info!("FileRunner backend listening on {}", addr);
     tracing::info!("API documentation available at http://{}/", addr);
 
-    axum::serve(
-        listener,
-        app.into_make_service_with_connect_info::<SocketAddr>(),
-    )
-    .await?;
+    server::serve(listener, app, &config).await;
+    tracing::info!("FileRunner backend stopped");
 
     Ok(())
 }
@@ -0,0 +1,48 @@
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::Response,
+};
+use uuid::Uuid;
+
+use crate::{middleware::ClientIp, AppState};
+
+/// API key from the `X-API-Key` header or the `api_key` query param (downloads)
+fn request_api_key(request: &Request) -> Option<Uuid> {
+    let from_header = request
+        .headers()
+        .get("X-API-Key")
+        .and_then(|h| h.to_str().ok());
+    let from_query = || {
+        request
+            .uri()
+            .query()?
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("api_key="))
+    };
+
+    from_header
+        .or_else(from_query)
+        .and_then(|key| Uuid::parse_str(key).ok())
+}
+
+/// Record API key usage (count, time, client IP) for requests that weren't rejected
+/// as unauthorized. Buffered in memory; see `ApiKeyUsageTracker::flush`.
+pub async fn api_key_usage_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let usage = request_api_key(&request).zip(request.extensions().get::<ClientIp>().copied());
+
+    let response = next.run(request).await;
+
+    if let Some((api_key, ClientIp(ip))) = usage {
+        if response.status() != StatusCode::UNAUTHORIZED {
+            state.api_key_usage.record(api_key, ip);
+        }
+    }
+
+    response
+}
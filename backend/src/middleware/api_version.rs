@@ -0,0 +1,110 @@
+use axum::{
+    extract::{OriginalUri, Request},
+    http::{header::ACCEPT, HeaderValue, Uri},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::error::{AppError, Result};
+
+/// API versions this server implements, oldest first
+pub const SUPPORTED_API_VERSIONS: [u32; 1] = [1];
+
+/// Version served to unversioned requests that don't ask for one
+pub const DEFAULT_API_VERSION: u32 = 1;
+
+/// Media type prefix for requesting a version via `Accept`, e.g.
+/// `application/vnd.filerunner.v1+json`
+const VENDOR_MEDIA_TYPE_PREFIX: &str = "application/vnd.filerunner.v";
+
+/// API version a request is served with; handlers can read it as `Extension<ApiVersion>`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ApiVersion(pub u32);
+
+/// `/api/v<n>/...` → `(n, rest)`
+fn split_versioned_path(path: &str) -> Option<(&str, &str)> {
+    let rest = path.strip_prefix("/api/v")?;
+    let end = rest.find('/').unwrap_or(rest.len());
+    let (version, rest) = rest.split_at(end);
+    (!version.is_empty() && version.bytes().all(|b| b.is_ascii_digit())).then_some((version, rest))
+}
+
+/// Version asked for by an unversioned request: `X-API-Version: <n>` or an
+/// `Accept: application/vnd.filerunner.v<n>+json` media type
+fn requested_version(request: &Request) -> Option<String> {
+    if let Some(version) = request
+        .headers()
+        .get("X-API-Version")
+        .and_then(|h| h.to_str().ok())
+    {
+        return Some(version.trim().to_string());
+    }
+    request
+        .headers()
+        .get_all(ACCEPT)
+        .iter()
+        .filter_map(|h| h.to_str().ok())
+        .flat_map(|h| h.split(','))
+        .find_map(|media_type| {
+            let media_type = media_type.split(';').next()?.trim();
+            let version = media_type.strip_prefix(VENDOR_MEDIA_TYPE_PREFIX)?;
+            Some(version.split('+').next()?.to_string())
+        })
+}
+
+fn parse_version(version: &str) -> Result<u32> {
+    version
+        .parse()
+        .ok()
+        .filter(|v| SUPPORTED_API_VERSIONS.contains(v))
+        .ok_or_else(|| {
+            AppError::BadRequest(format!(
+                "Unsupported API version '{version}'; supported versions: {}",
+                SUPPORTED_API_VERSIONS.map(|v| v.to_string()).join(", ")
+            ))
+        })
+}
+
+/// Route `/api/...` requests to a versioned API. `/api/v<n>/...` is served as is;
+/// unversioned paths are a compatibility shim, rewritten to the version requested
+/// via header (or the default), so existing clients keep working across breaking
+/// changes. The original URI stays available as `OriginalUri` (request signatures
+/// cover the path the client sent). Must wrap the whole router so the rewritten URI
+/// is what gets routed.
+pub async fn api_version_middleware(mut request: Request, next: Next) -> Result<Response> {
+    let path = request.uri().path();
+    if path != "/api" && !path.starts_with("/api/") {
+        return Ok(next.run(request).await);
+    }
+
+    let version = match split_versioned_path(path) {
+        Some((version, _)) => parse_version(version)?,
+        None => {
+            let version = match requested_version(&request) {
+                Some(version) => parse_version(&version)?,
+                None => DEFAULT_API_VERSION,
+            };
+            let rest = &path["/api".len()..];
+            let rewritten = match request.uri().query() {
+                Some(query) => format!("/api/v{version}{rest}?{query}"),
+                None => format!("/api/v{version}{rest}"),
+            };
+            let rewritten = rewritten
+                .parse::<Uri>()
+                .map_err(|e| AppError::InternalError(format!("Failed to rewrite URI: {e}")))?;
+            let original = std::mem::replace(request.uri_mut(), rewritten);
+            if request.extensions().get::<OriginalUri>().is_none() {
+                request.extensions_mut().insert(OriginalUri(original));
+            }
+            version
+        }
+    };
+
+    request.extensions_mut().insert(ApiVersion(version));
+    let mut response = next.run(request).await;
+    response.headers_mut().insert(
+        "x-api-version",
+        HeaderValue::from_str(&version.to_string()).expect("number is a valid header value"),
+    );
+    Ok(response)
+}
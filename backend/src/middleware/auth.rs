@@ -1,20 +1,29 @@
 use axum::{
     async_trait,
-    extract::{FromRequestParts, Request, State},
+    extract::{FromRequestParts, MatchedPath, Request, State},
     http::{header::AUTHORIZATION, request::Parts},
     middleware::Next,
     response::Response,
 };
 use serde::{Deserialize, Serialize};
+use std::marker::PhantomData;
 use uuid::Uuid;
 
 use crate::{
     error::{AppError, Result},
     models::UserRole,
-    utils::{verify_access_token, verify_token},
+    utils::{verify_access_token, verify_token, PermissionMarker, RolePermission},
     AppState,
 };
 
+/// Routes a user who must change their password can still use
+const PASSWORD_CHANGE_ROUTES: [&str; 4] = [
+    "/api/v1/auth/me",
+    "/api/v1/auth/change-password",
+    "/api/v1/auth/logout",
+    "/api/v1/auth/logout-all",
+];
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuthUser {
     pub id: Uuid,
@@ -22,6 +31,22 @@ pub struct AuthUser {
     pub role: UserRole,
 }
 
+impl AuthUser {
+    pub fn has_permission(&self, permission: RolePermission) -> bool {
+        self.role.has_permission(permission)
+    }
+
+    /// Reject the request unless the user's role grants `permission`
+    pub fn require_permission(&self, permission: RolePermission) -> Result<()> {
+        if !self.has_permission(permission) {
+            return Err(AppError::Forbidden(format!(
+                "Permission required: {permission}"
+            )));
+        }
+        Ok(())
+    }
+}
+
 #[async_trait]
 impl<S> FromRequestParts<S> for AuthUser
 where
@@ -38,6 +63,43 @@ where
     }
 }
 
+/// Authenticated user whose role grants the permission named by `P`, e.g.
+/// `RequirePermission<perm::AdminJobsRead>`. Admin permissions are written to the audit log.
+#[derive(Debug, Clone)]
+pub struct RequirePermission<P>(pub AuthUser, pub PhantomData<P>);
+
+#[async_trait]
+impl<S, P> FromRequestParts<S> for RequirePermission<P>
+where
+    S: Send + Sync,
+    P: PermissionMarker + Send,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self> {
+        let user = AuthUser::from_request_parts(parts, state).await?;
+        let permission = P::PERMISSION;
+        user.require_permission(permission)?;
+
+        if permission.is_audited() {
+            let path = parts
+                .extensions
+                .get::<MatchedPath>()
+                .map_or_else(|| parts.uri.path(), MatchedPath::as_str);
+            tracing::info!(
+                target: "audit",
+                admin_id = %user.id,
+                admin_email = %user.email,
+                %permission,
+                method = %parts.method,
+                path,
+                "Admin operation"
+            );
+        }
+        Ok(RequirePermission(user, PhantomData))
+    }
+}
+
 /// Optional auth user extractor - returns None instead of error if not authenticated
 /// Use this for endpoints that support both JWT and API key authentication
 #[derive(Debug, Clone)]
@@ -73,12 +135,12 @@ pub async fn require_auth(
         .ok_or(AppError::Unauthorized)?;
 
     // Try to verify as access token first (new dual-token system)
-    let (user_id, email, role_str) =
+    let (user_id, email, role_str, issued_at) =
         if let Ok(claims) = verify_access_token(token, &state.config.jwt_secret) {
-            (claims.sub, claims.email, claims.role)
+            (claims.sub, claims.email, claims.role, claims.iat)
         } else if let Ok(claims) = verify_token(token, &state.config.jwt_secret) {
             // Fall back to legacy token verification for backward compatibility
-            (claims.sub, claims.email, claims.role)
+            (claims.sub, claims.email, claims.role, claims.iat)
         } else {
             return Err(AppError::Unauthorized);
         };
@@ -86,6 +148,14 @@ pub async fn require_auth(
     let user_id = Uuid::parse_str(&user_id)
         .map_err(|_| AppError::TokenError("Invalid user ID in token".to_string()))?;
 
+    if state
+        .token_revocations
+        .is_revoked(&state.pool, user_id, issued_at)
+        .await?
+    {
+        return Err(AppError::TokenError("Token has been revoked".to_string()));
+    }
+
     let role = match role_str.as_str() {
         "admin" => UserRole::Admin,
         "user" => UserRole::User,
@@ -120,16 +190,29 @@ pub async fn optional_auth(
             // Try to verify as access token first (new dual-token system)
             let auth_result =
                 if let Ok(claims) = verify_access_token(token, &state.config.jwt_secret) {
-                    Some((claims.sub, claims.email, claims.role))
+                    Some((claims.sub, claims.email, claims.role, claims.iat))
                 } else if let Ok(claims) = verify_token(token, &state.config.jwt_secret) {
                     // Fall back to legacy token verification for backward compatibility
-                    Some((claims.sub, claims.email, claims.role))
+                    Some((claims.sub, claims.email, claims.role, claims.iat))
                 } else {
                     None
                 };
 
-            if let Some((user_id, email, role_str)) = auth_result {
+            if let Some((user_id, email, role_str, issued_at)) = auth_result {
                 if let Ok(user_id) = Uuid::parse_str(&user_id) {
+                    // A revoked token counts as no token; a failed lookup too, as the
+                    // handler couldn't reach the database either
+                    let revoked = state
+                        .token_revocations
+                        .is_revoked(&state.pool, user_id, issued_at)
+                        .await
+                        .unwrap_or_else(|e| {
+                            tracing::warn!("Token revocation check failed: {}", e);
+                            true
+                        });
+                    if revoked {
+                        return next.run(request).await;
+                    }
                     let role = match role_str.as_str() {
                         "admin" => UserRole::Admin,
                         _ => UserRole::User,
@@ -150,3 +233,35 @@ pub async fn optional_auth(
     // Always continue to next handler, regardless of auth result
     next.run(request).await
 }
+
+/// Hold users flagged with `must_change_password` (e.g. the first admin, or
+/// after `reset-password`) to their profile, changing the password and
+/// logging out until they change it. Layer inside `require_auth` /
+/// `optional_auth`; requests without a signed-in user pass through.
+pub async fn require_password_changed(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response> {
+    let Some(user_id) = request.extensions().get::<AuthUser>().map(|u| u.id) else {
+        return Ok(next.run(request).await);
+    };
+    let allowed = request
+        .extensions()
+        .get::<MatchedPath>()
+        .is_some_and(|path| PASSWORD_CHANGE_ROUTES.contains(&path.as_str()));
+    if allowed {
+        return Ok(next.run(request).await);
+    }
+
+    // Read from the primary so a password just changed is seen right away
+    let must_change: Option<bool> =
+        sqlx::query_scalar("SELECT must_change_password FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_optional(&state.pool)
+            .await?;
+    if must_change == Some(true) {
+        return Err(AppError::PasswordChangeRequired);
+    }
+    Ok(next.run(request).await)
+}
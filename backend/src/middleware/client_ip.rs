@@ -0,0 +1,91 @@
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts, Request, State},
+    http::request::Parts,
+    middleware::Next,
+    response::Response,
+};
+use ipnet::IpNet;
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+use tower_governor::{key_extractor::KeyExtractor, GovernorError};
+
+use crate::{
+    error::{AppError, Result},
+    utils::resolve_client_ip,
+    AppState,
+};
+
+/// Real client IP, resolved through trusted proxies by `client_ip_middleware`
+#[derive(Debug, Clone, Copy)]
+pub struct ClientIp(pub IpAddr);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientIp
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self> {
+        if let Some(ip) = parts.extensions.get::<ClientIp>() {
+            return Ok(*ip);
+        }
+        parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| ClientIp(addr.ip()))
+            .ok_or(AppError::InternalError(
+                "Client address unavailable".to_string(),
+            ))
+    }
+}
+
+/// Resolve the client IP once per request so auth, rate limiting and logs agree on it
+pub async fn client_ip_middleware(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if let Some(ConnectInfo(peer)) = request.extensions().get::<ConnectInfo<SocketAddr>>() {
+        let ip = resolve_client_ip(peer.ip(), request.headers(), &state.config.trusted_proxies);
+        request.extensions_mut().insert(ClientIp(ip));
+    }
+
+    next.run(request).await
+}
+
+/// Rate-limit key extractor that uses the trusted-proxy aware client IP
+#[derive(Debug, Clone)]
+pub struct ClientIpKeyExtractor {
+    trusted_proxies: Arc<Vec<IpNet>>,
+}
+
+impl ClientIpKeyExtractor {
+    pub fn new(trusted_proxies: Vec<IpNet>) -> Self {
+        ClientIpKeyExtractor {
+            trusted_proxies: Arc::new(trusted_proxies),
+        }
+    }
+}
+
+impl KeyExtractor for ClientIpKeyExtractor {
+    type Key = IpAddr;
+
+    fn extract<T>(
+        &self,
+        req: &axum::http::Request<T>,
+    ) -> std::result::Result<IpAddr, GovernorError> {
+        if let Some(ClientIp(ip)) = req.extensions().get::<ClientIp>() {
+            return Ok(*ip);
+        }
+        req.extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| {
+                resolve_client_ip(addr.ip(), req.headers(), &self.trusted_proxies)
+            })
+            .ok_or(GovernorError::UnableToExtractKey)
+    }
+}
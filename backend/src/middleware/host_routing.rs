@@ -0,0 +1,134 @@
+use axum::{
+    extract::{Request, State},
+    http::{header::HOST, Method, Uri},
+    middleware::Next,
+    response::Response,
+};
+use percent_encoding::percent_decode_str;
+use uuid::Uuid;
+
+use crate::{
+    error::{AppError, Result},
+    handlers::robots::robots_response,
+    AppState,
+};
+
+use super::DEFAULT_API_VERSION;
+
+/// Project serving public files for `host`: `<slug>.<PUBLIC_FILES_DOMAIN>` or
+/// a verified custom domain. The API's own hosts never resolve to a project.
+async fn resolve_host_project(state: &AppState, host: &str) -> Result<Option<Uuid>> {
+    if let Some(cached) = state.host_projects.get(host) {
+        return Ok(cached);
+    }
+    if state.config.reserved_hosts().iter().any(|h| h == host) {
+        state.host_projects.insert(host.to_string(), None);
+        return Ok(None);
+    }
+
+    let slug = state
+        .config
+        .public_files_domain
+        .as_deref()
+        .and_then(|domain| host.strip_suffix(domain)?.strip_suffix('.'))
+        .filter(|slug| !slug.contains('.'));
+
+    let project_id = match slug {
+        Some(slug) => {
+            sqlx::query_scalar::<_, Uuid>("SELECT id FROM projects WHERE slug = $1")
+                .bind(slug)
+                .fetch_optional(&state.pool)
+                .await?
+        }
+        None => {
+            sqlx::query_scalar::<_, Uuid>(
+                "SELECT id FROM projects WHERE custom_domain = $1 AND custom_domain_verified_at IS NOT NULL",
+            )
+            .bind(host)
+            .fetch_optional(&state.pool)
+            .await?
+        }
+    };
+
+    state.host_projects.insert(host.to_string(), project_id);
+    Ok(project_id)
+}
+
+/// Serve public files by path on project hosts: `GET https://<host>/<folder>/<name>`
+/// is rewritten to the regular download route (`/api/v1/files/:id`), which still applies
+/// visibility, geo, bandwidth and CORS rules. Other hosts pass through untouched.
+/// Must wrap the whole router so the rewritten URI is what gets routed.
+pub async fn host_routing_middleware(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response> {
+    let host = request
+        .headers()
+        .get(HOST)
+        .and_then(|h| h.to_str().ok())
+        .map(|h| h.split(':').next().unwrap_or(h).to_lowercase());
+
+    let Some(host) = host else {
+        return Ok(next.run(request).await);
+    };
+    let Some(project_id) = resolve_host_project(&state, &host).await? else {
+        return Ok(next.run(request).await);
+    };
+
+    if !matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    ) {
+        return Err(AppError::NotFound("Not found".to_string()));
+    }
+
+    let path = percent_decode_str(request.uri().path().trim_start_matches('/'))
+        .decode_utf8()
+        .map_err(|_| AppError::NotFound("File not found".to_string()))?;
+    let (folder_path, file_name) = match path.rsplit_once('/') {
+        Some((folder, name)) => (Some(folder), name),
+        None => (None, path.as_ref()),
+    };
+    if file_name.is_empty() {
+        return Err(AppError::NotFound("File not found".to_string()));
+    }
+
+    let file_id = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        SELECT f.id
+        FROM files f
+        LEFT JOIN folders fol ON fol.id = f.folder_id
+        WHERE f.project_id = $1 AND f.original_name = $2 AND fol.path IS NOT DISTINCT FROM $3
+        ORDER BY f.upload_date DESC
+        LIMIT 1
+        "#,
+    )
+    .bind(project_id)
+    .bind(file_name)
+    .bind(folder_path)
+    .fetch_optional(&state.pool)
+    .await?;
+    // A project without its own robots.txt gets one following its `noindex`
+    let file_id = match file_id {
+        Some(file_id) => file_id,
+        None if folder_path.is_none() && file_name == "robots.txt" => {
+            let noindex: bool = sqlx::query_scalar("SELECT noindex FROM projects WHERE id = $1")
+                .bind(project_id)
+                .fetch_one(&state.pool)
+                .await?;
+            return Ok(robots_response(noindex || state.config.robots_noindex));
+        }
+        None => return Err(AppError::NotFound("File not found".to_string())),
+    };
+
+    let rewritten = match request.uri().query() {
+        Some(query) => format!("/api/v{DEFAULT_API_VERSION}/files/{file_id}?{query}"),
+        None => format!("/api/v{DEFAULT_API_VERSION}/files/{file_id}"),
+    };
+    *request.uri_mut() = rewritten
+        .parse::<Uri>()
+        .map_err(|e| AppError::InternalError(format!("Failed to rewrite URI: {e}")))?;
+
+    Ok(next.run(request).await)
+}
@@ -0,0 +1,66 @@
+use axum::{
+    body::Body,
+    extract::{MatchedPath, Request, State},
+    http::Method,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use http_body_util::BodyExt;
+use std::time::Duration;
+
+use crate::{error::AppError, AppState};
+
+/// Routes whose GET requests are dropped first under load: listings and
+/// reports a client can simply refresh later
+const LOW_PRIORITY_ROUTES: [&str; 15] = [
+    "/api/v1/projects",
+    "/api/v1/projects/:id/files",
+    "/api/v1/projects/:id/files/export",
+    "/api/v1/projects/:id/changes",
+    "/api/v1/projects/:id/members",
+    "/api/v1/projects/:id/duplicates",
+    "/api/v1/projects/:id/compression",
+    "/api/v1/projects/:id/reports/largest",
+    "/api/v1/projects/:id/reports/stale",
+    "/api/v1/folders",
+    "/api/v1/folders/tree",
+    "/api/v1/files/recent",
+    "/api/v1/files/starred",
+    "/api/v1/admin/projects",
+    "/api/v1/admin/database",
+];
+
+fn is_low_priority(request: &Request) -> bool {
+    request.method() == Method::GET
+        && request
+            .extensions()
+            .get::<MatchedPath>()
+            .is_some_and(|path| LOW_PRIORITY_ROUTES.contains(&path.as_str()))
+}
+
+/// Count requests in flight until their response body is sent, and answer
+/// low-priority ones with 503 + `Retry-After` while the server is saturated
+/// (`LOAD_SHED_MAX_IN_FLIGHT` / `LOAD_SHED_MAX_LAG_MS`), so transfers already
+/// running keep their share of the server
+pub async fn load_shed_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let config = &state.config;
+    let shedder = &state.load_shedder;
+    let max_lag = Duration::from_millis(config.load_shed_max_lag_ms);
+    if is_low_priority(&request) && shedder.is_saturated(config.load_shed_max_in_flight, max_lag) {
+        state.metrics.record_shed_request();
+        return AppError::Overloaded(config.load_shed_retry_after_secs).into_response();
+    }
+
+    let in_flight = shedder.begin();
+    let response = next.run(request).await;
+    response.map(|body| {
+        Body::new(body.map_frame(move |frame| {
+            let _ = &in_flight;
+            frame
+        }))
+    })
+}
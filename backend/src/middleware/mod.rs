@@ -1,3 +1,22 @@
+pub mod api_key_usage;
+pub mod api_version;
 pub mod auth;
+pub mod client_ip;
+pub mod host_routing;
+pub mod load_shed;
+pub mod problem;
+pub mod signature;
+pub mod timeout;
 
-pub use auth::{optional_auth, require_auth, AuthUser, OptionalAuthUser};
+pub use api_key_usage::api_key_usage_middleware;
+pub use api_version::{api_version_middleware, ApiVersion, DEFAULT_API_VERSION};
+pub use auth::{
+    optional_auth, require_auth, require_password_changed, AuthUser, OptionalAuthUser,
+    RequirePermission,
+};
+pub use client_ip::{client_ip_middleware, ClientIp, ClientIpKeyExtractor};
+pub use host_routing::host_routing_middleware;
+pub use load_shed::load_shed_middleware;
+pub use problem::problem_details_middleware;
+pub use signature::request_signature_middleware;
+pub use timeout::request_timeout_middleware;
@@ -0,0 +1,102 @@
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{
+        header::{ACCEPT, CONTENT_LENGTH, CONTENT_TYPE},
+        HeaderMap, HeaderValue,
+    },
+    middleware::Next,
+    response::Response,
+};
+use serde_json::{Map, Value};
+use tracing::Instrument;
+use uuid::Uuid;
+
+use crate::{
+    error::{ErrorDetails, ErrorFormat},
+    AppState,
+};
+
+const PROBLEM_JSON: &str = "application/problem+json";
+
+/// Problem `type` URIs are `urn:filerunner:problem:<code>`, e.g. `...:quota_exceeded`
+const PROBLEM_TYPE_PREFIX: &str = "urn:filerunner:problem:";
+
+fn accepts_problem_json(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(ACCEPT)
+        .iter()
+        .filter_map(|h| h.to_str().ok())
+        .flat_map(|h| h.split(','))
+        .any(|media_type| {
+            media_type
+                .split(';')
+                .next()
+                .is_some_and(|m| m.trim().eq_ignore_ascii_case(PROBLEM_JSON))
+        })
+}
+
+/// Re-render an error response as RFC 7807 problem details, keeping status and headers
+fn problem_response(response: Response, details: ErrorDetails, request_id: Uuid) -> Response {
+    let (mut parts, _) = response.into_parts();
+
+    let mut problem = Map::new();
+    problem.insert(
+        "type".to_string(),
+        Value::from(format!("{PROBLEM_TYPE_PREFIX}{}", details.code)),
+    );
+    problem.insert(
+        "title".to_string(),
+        Value::from(parts.status.canonical_reason().unwrap_or("Error")),
+    );
+    problem.insert("status".to_string(), Value::from(parts.status.as_u16()));
+    problem.insert("detail".to_string(), Value::from(details.detail));
+    problem.insert(
+        "instance".to_string(),
+        Value::from(request_id.urn().to_string()),
+    );
+    problem.insert("code".to_string(), Value::from(details.code));
+    for (key, value) in details.extensions {
+        problem.entry(key).or_insert(value);
+    }
+
+    parts
+        .headers
+        .insert(CONTENT_TYPE, HeaderValue::from_static(PROBLEM_JSON));
+    parts.headers.remove(CONTENT_LENGTH);
+    let body = serde_json::to_vec(&problem).unwrap_or_default();
+    Response::from_parts(parts, Body::from(body))
+}
+
+/// Tag each request with an ID (the client's `X-Request-Id` if it is a UUID, so
+/// proxies can pass theirs through), returned as `X-Request-Id` and attached to
+/// log lines. Error responses are rendered as `application/problem+json` when the
+/// client accepts it or `ERROR_FORMAT=problem`, with the request ID as `instance`.
+pub async fn problem_details_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let request_id = request
+        .headers()
+        .get("X-Request-Id")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|id| Uuid::parse_str(id.trim()).ok())
+        .unwrap_or_else(Uuid::new_v4);
+    let wants_problem = state.config.error_format == ErrorFormat::Problem
+        || accepts_problem_json(request.headers());
+
+    let span = tracing::info_span!("request", request_id = %request_id);
+    let mut response = next.run(request).instrument(span).await;
+
+    if let Some(details) = response.extensions_mut().remove::<ErrorDetails>() {
+        if wants_problem {
+            response = problem_response(response, details, request_id);
+        }
+    }
+    response.headers_mut().insert(
+        "x-request-id",
+        HeaderValue::from_str(&request_id.to_string()).expect("UUID is a valid header value"),
+    );
+    response
+}
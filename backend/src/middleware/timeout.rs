@@ -0,0 +1,83 @@
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use futures::StreamExt;
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use crate::{error::AppError, server::optional_secs, AppState};
+
+/// Request body that fails once the client sends nothing for `idle`, flagging
+/// `timed_out`. The clock only runs while the handler is waiting for data.
+fn idle_timeout_body(body: Body, idle: Duration, timed_out: Arc<AtomicBool>) -> Body {
+    let stream = futures::stream::unfold(Some(body.into_data_stream()), move |data| {
+        let timed_out = Arc::clone(&timed_out);
+        async move {
+            let mut data = data?;
+            match tokio::time::timeout(idle, data.next()).await {
+                Ok(Some(chunk)) => Some((chunk.map_err(io::Error::other), Some(data))),
+                Ok(None) => None,
+                Err(_) => {
+                    timed_out.store(true, Ordering::Relaxed);
+                    let e = io::Error::new(io::ErrorKind::TimedOut, "Request body stalled");
+                    Some((Err(e), None))
+                }
+            }
+        }
+    });
+    Body::from_stream(stream)
+}
+
+/// Answer 408 when a handler takes longer than `REQUEST_TIMEOUT_SECS` to produce
+/// a response, or the request body stalls for `REQUEST_BODY_IDLE_TIMEOUT_SECS`.
+/// The handler is dropped at that point, so a slow client can't hold a database
+/// connection or upload slot indefinitely. Streaming response bodies (downloads)
+/// aren't limited; the server's write timeout covers those.
+pub async fn request_timeout_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let body_timed_out = Arc::new(AtomicBool::new(false));
+    let body_idle_timeout = optional_secs(state.config.request_body_idle_timeout_secs);
+    let request = match body_idle_timeout {
+        Some(idle) => {
+            let timed_out = Arc::clone(&body_timed_out);
+            request.map(|body| idle_timeout_body(body, idle, timed_out))
+        }
+        None => request,
+    };
+
+    let response = match optional_secs(state.config.request_timeout_secs) {
+        Some(limit) => match tokio::time::timeout(limit, next.run(request)).await {
+            Ok(response) => response,
+            Err(_) => {
+                return AppError::RequestTimeout(format!(
+                    "Request was not completed within {}s",
+                    limit.as_secs()
+                ))
+                .into_response()
+            }
+        },
+        None => next.run(request).await,
+    };
+
+    // Whatever the handler made of the failed body read, report the timeout
+    if body_timed_out.load(Ordering::Relaxed) {
+        return AppError::RequestTimeout(format!(
+            "Request body stalled for more than {}s",
+            state.config.request_body_idle_timeout_secs
+        ))
+        .into_response();
+    }
+    response
+}
@@ -0,0 +1,25 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::FromRow;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct Backup {
+    pub id: Uuid,
+    /// running, completed or failed
+    pub status: String,
+    /// scheduled or manual
+    pub trigger: String,
+    pub started_by: Option<Uuid>,
+    /// Database WAL position the manifest is consistent with (for point-in-time restore)
+    pub wal_lsn: Option<String>,
+    pub manifest_path: Option<String>,
+    pub file_count: i64,
+    pub total_bytes: i64,
+    pub blobs_copied: i64,
+    pub blobs_bytes: i64,
+    pub blobs_failed: i64,
+    pub last_error: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
@@ -0,0 +1,31 @@
+use serde::Serialize;
+use sqlx::FromRow;
+
+/// Size of one table or partition
+#[derive(Debug, Serialize, FromRow)]
+pub struct TableStats {
+    pub name: String,
+    /// Parent table when this is a partition
+    pub parent: Option<String>,
+    /// Whether the table is split into partitions (its own sizes are then 0)
+    pub partitioned: bool,
+    /// Planner estimate, refreshed by autovacuum/ANALYZE
+    pub estimated_rows: i64,
+    pub table_bytes: i64,
+    pub index_bytes: i64,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct IndexStats {
+    pub table_name: String,
+    pub name: String,
+    pub bytes: i64,
+    /// Index scans since statistics were last reset; 0 suggests an unused index
+    pub scans: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DatabaseStats {
+    pub tables: Vec<TableStats>,
+    pub indexes: Vec<IndexStats>,
+}
@@ -0,0 +1,20 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Delivered events to send to the broker again
+#[derive(Debug, Deserialize)]
+pub struct ReplayEventsRequest {
+    /// Events that occurred at or after this time
+    pub from: DateTime<Utc>,
+    /// Events that occurred before this time (default: now)
+    pub to: Option<DateTime<Utc>>,
+    /// Only these types, e.g. `file.deleted`; every type when empty
+    #[serde(default)]
+    pub event_types: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReplayEventsResponse {
+    /// Events queued for delivery
+    pub replayed: u64,
+}
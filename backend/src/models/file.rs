@@ -2,6 +2,7 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
+use validator::{Validate, ValidateEmail, ValidationError};
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct File {
@@ -14,6 +15,28 @@ pub struct File {
     pub size: i64,
     pub mime_type: String,
     pub upload_date: DateTime<Utc>,
+    /// `zstd` when the blob on disk is compressed
+    #[serde(skip)]
+    pub storage_encoding: Option<String>,
+    /// Cached response variants (`br`, `gzip`) for public web assets
+    #[serde(skip)]
+    pub precompressed_encodings: Vec<String>,
+    /// `hot` (local disk) or `cold` (cold storage backend)
+    #[serde(skip)]
+    pub storage_tier: String,
+    pub moderation_status: ModerationStatus,
+}
+
+/// Where a file stands with content moderation; only approved files are served
+/// to readers without write access
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "moderation_status", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum ModerationStatus {
+    Approved,
+    /// Waiting for the moderation service or a project admin
+    PendingReview,
+    Rejected,
 }
 
 #[derive(Debug, Serialize, FromRow)]
@@ -27,6 +50,155 @@ pub struct FileMetadata {
     pub mime_type: String,
     pub upload_date: DateTime<Utc>,
     pub download_url: String,
+    pub download_count: i64,
+    pub last_accessed_at: Option<DateTime<Utc>>,
+    pub description: Option<String>,
+    pub moderation_status: ModerationStatus,
+    /// Why the file was rejected or flagged for review
+    pub moderation_reason: Option<String>,
+    /// Set on the file itself; files in a held folder are protected as well
+    pub legal_hold: bool,
+    /// Only grows through appends; overwrites are refused
+    pub append_only: bool,
+}
+
+/// Order of a project's file listing (`?sort=`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileSort {
+    /// Most recently uploaded first
+    #[default]
+    Newest,
+    /// Most downloaded first (counts lag downloads by up to 30 seconds)
+    MostDownloaded,
+}
+
+/// Earlier content of a file, e.g. the upload as it was before image optimization
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct FileVersion {
+    pub id: Uuid,
+    pub file_id: Uuid,
+    pub original_name: String,
+    #[serde(skip_serializing)]
+    pub file_path: String,
+    pub size: i64,
+    pub mime_type: String,
+    pub content_hash: String,
+    /// Why the version was kept: `original` for an optimized upload
+    pub reason: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Editable file details (`PATCH /api/v1/files/:id`)
+#[derive(Debug, Deserialize)]
+pub struct UpdateFileRequest {
+    /// Free-text note on the file; `null` or blank clears it
+    pub description: Option<String>,
+}
+
+/// A project admin's moderation decision (`PUT /api/v1/files/:id/moderation`)
+#[derive(Debug, Deserialize)]
+pub struct ReviewFileRequest {
+    /// `approved` or `rejected`
+    pub status: ModerationStatus,
+    pub reason: Option<String>,
+}
+
+/// Recipients must be email addresses
+fn validate_recipients(recipients: &[String]) -> Result<(), ValidationError> {
+    if recipients.iter().all(|r| r.validate_email()) {
+        Ok(())
+    } else {
+        let mut err = ValidationError::new("recipients");
+        err.message = Some("Recipients must be email addresses".into());
+        Err(err)
+    }
+}
+
+/// Email a share link to the file (`POST /api/v1/files/:id/share/email`)
+#[derive(Debug, Deserialize, Validate)]
+pub struct ShareFileEmailRequest {
+    #[validate(
+        length(min = 1, max = 20, message = "Send to 1-20 recipients"),
+        custom(function = "validate_recipients")
+    )]
+    pub recipients: Vec<String>,
+    /// Note included in the email
+    #[validate(length(max = 2000, message = "Message must be at most 2000 characters"))]
+    pub message: Option<String>,
+    /// How long the link works; defaults to 7 days, at most 30
+    #[validate(range(min = 1, max = 720, message = "Expiry must be between 1 and 720 hours"))]
+    pub expires_in_hours: Option<i64>,
+}
+
+/// Delivery of a share link to one recipient
+#[derive(Debug, Serialize)]
+pub struct ShareRecipientStatus {
+    pub email: String,
+    pub sent: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FileShareResponse {
+    pub id: Uuid,
+    pub file_id: Uuid,
+    pub url: String,
+    pub expires_at: DateTime<Utc>,
+    pub recipients: Vec<ShareRecipientStatus>,
+}
+
+/// Copy a file into another project of the same owner (`POST /api/v1/files/:id/copy`)
+#[derive(Debug, Deserialize)]
+pub struct CopyFileRequest {
+    pub target_project_id: Uuid,
+    /// Destination folder; omit or leave empty for the project root
+    pub folder_path: Option<String>,
+    /// `rename`, `error` or `skip` for a same-name file in the destination;
+    /// without it the copy is added alongside
+    pub on_conflict: Option<String>,
+}
+
+/// Place or release a legal hold on a file or folder
+#[derive(Debug, Deserialize)]
+pub struct LegalHoldRequest {
+    pub legal_hold: bool,
+}
+
+/// Flag a file as append-only or clear the flag
+#[derive(Debug, Deserialize)]
+pub struct AppendOnlyRequest {
+    pub append_only: bool,
+}
+
+/// How an upload resolves a same-name file already in the target folder
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictStrategy {
+    /// Store under a new name like `report (1).pdf`
+    Rename,
+    /// Replace the existing file's content, keeping its ID
+    Overwrite,
+    /// Reject the upload with 409 Conflict
+    Error,
+    /// Keep the existing file and return it without storing the upload
+    Skip,
+}
+
+impl std::str::FromStr for ConflictStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "rename" => Ok(ConflictStrategy::Rename),
+            "overwrite" => Ok(ConflictStrategy::Overwrite),
+            "error" => Ok(ConflictStrategy::Error),
+            "skip" => Ok(ConflictStrategy::Skip),
+            other => Err(format!(
+                "Invalid on_conflict value '{other}': expected rename, overwrite, error or skip"
+            )),
+        }
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -37,4 +209,234 @@ pub struct UploadResponse {
     pub mime_type: String,
     pub download_url: String,
     pub folder_path: Option<String>,
+    /// `pending_review` until the moderation service approves the file
+    pub moderation_status: ModerationStatus,
+}
+
+/// Result of uploading an archive with `extract=true`
+#[derive(Debug, Serialize)]
+pub struct ExtractResponse {
+    pub extracted_count: usize,
+    pub total_size: i64,
+    pub files: Vec<UploadResponse>,
+}
+
+/// Files sharing identical content within a project
+#[derive(Debug, Serialize)]
+pub struct DuplicateGroup {
+    pub content_hash: String,
+    pub size: i64,
+    pub file_count: i64,
+    /// Bytes reclaimable by keeping a single copy
+    pub wasted_bytes: i64,
+    pub files: Vec<FileMetadata>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DuplicatesReport {
+    pub groups: Vec<DuplicateGroup>,
+    pub total_wasted_bytes: i64,
+}
+
+/// At-rest compression savings for a project
+#[derive(Debug, Serialize)]
+pub struct CompressionStats {
+    pub file_count: i64,
+    pub compressed_files: i64,
+    /// Sum of original (uncompressed) sizes
+    pub total_size: i64,
+    /// Bytes actually used on disk
+    pub stored_size: i64,
+    /// `stored_size / total_size`; 1.0 when nothing is compressed
+    pub compression_ratio: f64,
+}
+
+/// Files that could be deleted to reclaim space
+/// (`GET /api/v1/projects/:id/reports/largest` and `/reports/stale`)
+#[derive(Debug, Serialize)]
+pub struct CleanupReport {
+    /// Largest first, up to `limit`
+    pub files: Vec<FileMetadata>,
+    /// Every file the report matches, including those past `limit`
+    pub file_count: i64,
+    /// Bytes freed by deleting all of them
+    pub total_size: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteStaleFilesRequest {
+    pub unused_for_days: i32,
+    /// Only these of the stale files; all of them when missing
+    pub file_ids: Option<Vec<Uuid>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeduplicateRequest {
+    pub content_hashes: Vec<String>,
+    /// Which copy to keep: "oldest" (default) or "newest"
+    pub keep: Option<String>,
+}
+
+/// Constraints for a browser direct-upload policy
+#[derive(Debug, Deserialize, Validate)]
+pub struct UploadPolicyRequest {
+    /// Uploads are forced into this folder
+    pub folder_path: Option<String>,
+    /// Maximum file size in bytes (capped by MAX_FILE_SIZE)
+    #[validate(range(min = 1, message = "max_size must be positive"))]
+    pub max_size: Option<usize>,
+    /// Allowed MIME types, e.g. `image/png` or `image/*`
+    pub content_types: Option<Vec<String>>,
+    /// Policy lifetime in seconds (default 300, max 3600)
+    #[validate(range(min = 1, max = 3600, message = "expires_in must be between 1 and 3600"))]
+    pub expires_in: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UploadPolicyResponse {
+    /// Send as the `X-Upload-Policy` header on `POST /api/upload`
+    pub policy: String,
+    pub upload_url: String,
+    pub expires_at: i64,
+}
+
+/// Scope of a folder access cookie
+#[derive(Debug, Deserialize, Validate)]
+pub struct FolderAccessRequest {
+    /// Files in this folder and its subfolders can be read; omit for the whole project
+    pub folder_path: Option<String>,
+    /// Cookie lifetime in seconds (default 3600, max 86400)
+    #[validate(range(
+        min = 1,
+        max = 86400,
+        message = "expires_in must be between 1 and 86400"
+    ))]
+    pub expires_in: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FolderAccessResponse {
+    /// Name and value of the cookie, also set on this response, for a web
+    /// app that sets it on its own responses
+    pub cookie_name: String,
+    pub cookie_value: String,
+    pub folder_path: Option<String>,
+    pub expires_at: i64,
+}
+
+/// Waveform of an audio file (`GET /api/v1/files/:id/waveform`)
+#[derive(Debug, Serialize)]
+pub struct WaveformResponse {
+    pub duration_secs: f64,
+    /// Largest sample of each equal span of the audio, 0..=1
+    pub peaks: Vec<f32>,
+}
+
+/// A text file rendered for previews (`GET /api/v1/files/:id/render`)
+#[derive(Debug, Serialize)]
+pub struct RenderResponse {
+    /// `markdown`, `code` or `text`
+    pub format: &'static str,
+    /// Language a code file was highlighted as
+    pub language: Option<&'static str>,
+    /// Sanitized HTML. Code is wrapped in `tok-keyword`, `tok-string`,
+    /// `tok-comment` and `tok-number` spans.
+    pub html: String,
+}
+
+/// Result of appending to an append-only file (`POST /api/v1/files/:id/append`)
+#[derive(Debug, Serialize)]
+pub struct AppendResponse {
+    pub file_id: Uuid,
+    /// Bytes added by this request
+    pub appended: i64,
+    /// Size of the whole file afterwards
+    pub size: i64,
+    /// SHA-256 of the whole file afterwards
+    pub sha256: String,
+}
+
+/// A project's folders and files (`GET /api/v1/projects/:id/manifest`), for
+/// sync and mount clients to diff against the last manifest they saw
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProjectManifest {
+    pub project_id: Uuid,
+    /// Changes whenever an entry does; also sent as the `ETag`
+    pub version: String,
+    /// Sorted by path
+    pub folders: Vec<ManifestFolder>,
+    /// Sorted by path, then ID
+    pub files: Vec<ManifestFile>,
+}
+
+/// Format of `GET /api/v1/projects/:id/files/export`
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    /// One JSON array
+    Json,
+    /// One JSON object per line
+    Ndjson,
+}
+
+/// A file in a project's inventory export
+#[derive(Debug, Serialize, FromRow)]
+pub struct FileExportRow {
+    pub id: Uuid,
+    pub folder_path: Option<String>,
+    pub original_name: String,
+    pub size: i64,
+    pub mime_type: String,
+    /// SHA-256 of the content; `None` for files uploaded before hashing
+    pub sha256: Option<String>,
+    pub download_count: i64,
+    pub upload_date: DateTime<Utc>,
+    pub last_accessed_at: Option<DateTime<Utc>>,
+    pub moderation_status: String,
+    pub legal_hold: bool,
+    pub description: Option<String>,
+}
+
+/// A file created, updated or deleted (`GET /api/v1/projects/:id/changes`)
+#[derive(Debug, Serialize)]
+pub struct FileChange {
+    /// `created`, `updated` or `deleted`
+    #[serde(rename = "type")]
+    pub change_type: String,
+    pub file_id: Uuid,
+    pub changed_at: DateTime<Utc>,
+    /// The file as it is now; `None` once it's deleted or hidden
+    pub file: Option<ManifestFile>,
+}
+
+/// A page of a project's changes feed
+#[derive(Debug, Serialize)]
+pub struct ChangesFeed {
+    /// In the order they happened
+    pub changes: Vec<FileChange>,
+    /// Send as `since` to get the changes after these
+    pub cursor: String,
+    /// More changes are waiting; ask again right away
+    pub has_more: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct ManifestFolder {
+    pub path: String,
+    pub is_public: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct ManifestFile {
+    pub id: Uuid,
+    /// Folder path and name, e.g. `reports/2024/q1.pdf`
+    pub path: String,
+    pub size: i64,
+    /// SHA-256 of the content; `None` for files uploaded before hashing
+    pub sha256: Option<String>,
+    pub mime_type: String,
+    /// Upload time, or the last overwrite, edit or append
+    pub modified_at: DateTime<Utc>,
 }
@@ -28,15 +28,65 @@ pub struct CreateFolderRequest {
 #[derive(Debug, Deserialize)]
 pub struct UpdateFolderVisibilityRequest {
     pub is_public: bool,
+    /// Also apply to every subfolder
+    #[serde(default)]
+    pub recursive: bool,
 }
 
+/// Outcome of setting the visibility of every folder in a project
 #[derive(Debug, Serialize)]
+pub struct FolderVisibilitySummary {
+    pub is_public: bool,
+    /// Folders whose visibility changed
+    pub folders_updated: i64,
+    /// Files directly in those folders
+    pub files_affected: i64,
+}
+
+#[derive(Debug, Serialize, FromRow)]
 pub struct FolderResponse {
     pub id: Uuid,
     pub project_id: Uuid,
     pub path: String,
     pub is_public: bool,
     pub created_at: DateTime<Utc>,
+    pub legal_hold: bool,
     pub file_count: Option<i64>,
     pub total_size: Option<i64>,
+    /// Files and bytes by kind of content, with `?breakdown=true`
+    #[sqlx(skip)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<FolderUsage>,
+}
+
+/// Where a folder's storage goes, by MIME type of its own files
+#[derive(Debug, Default, Serialize)]
+pub struct FolderUsage {
+    /// `image/*`
+    pub images: CategoryUsage,
+    /// `video/*`
+    pub video: CategoryUsage,
+    /// PDFs, office documents, e-books and `text/*`
+    pub docs: CategoryUsage,
+    pub other: CategoryUsage,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct CategoryUsage {
+    pub file_count: i64,
+    pub total_size: i64,
+}
+
+/// A folder and its subfolders; file counts and sizes cover the folder's own files
+#[derive(Debug, Serialize)]
+pub struct FolderTreeNode {
+    pub id: Uuid,
+    pub name: String,
+    pub path: String,
+    pub is_public: bool,
+    pub created_at: DateTime<Utc>,
+    pub legal_hold: bool,
+    pub file_count: i64,
+    pub total_size: i64,
+    pub children: Vec<FolderTreeNode>,
 }
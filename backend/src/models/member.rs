@@ -0,0 +1,35 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+use validator::Validate;
+
+/// Role a collaborator holds on a project
+/// - viewer: read private files
+/// - uploader: viewer + add files (copy into the project)
+/// - admin: uploader + delete/move files and manage members
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, sqlx::Type,
+)]
+#[sqlx(type_name = "project_role", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ProjectRole {
+    Viewer,
+    Uploader,
+    Admin,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct AddMemberRequest {
+    #[validate(email(message = "Invalid email format"))]
+    pub email: String,
+    pub role: ProjectRole,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct ProjectMemberResponse {
+    pub user_id: Uuid,
+    pub email: String,
+    pub role: ProjectRole,
+    pub created_at: DateTime<Utc>,
+}
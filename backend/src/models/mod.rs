@@ -1,17 +1,56 @@
+pub mod backup;
+pub mod database;
+pub mod event;
 pub mod file;
 pub mod folder;
+pub mod member;
+pub mod notification;
 pub mod project;
 pub mod refresh_token;
+pub mod storage;
+pub mod upload;
 pub mod user;
 
-pub use file::{File, FileMetadata, UploadResponse};
-pub use folder::{CreateFolderRequest, Folder, FolderResponse, UpdateFolderVisibilityRequest};
-pub use project::{CreateProjectRequest, Project, ProjectResponse, UpdateProjectRequest};
+pub use backup::Backup;
+pub use database::{DatabaseStats, IndexStats, TableStats};
+pub use event::{ReplayEventsRequest, ReplayEventsResponse};
+pub use file::{
+    AppendOnlyRequest, AppendResponse, ChangesFeed, CleanupReport, CompressionStats,
+    ConflictStrategy, CopyFileRequest, DeduplicateRequest, DeleteStaleFilesRequest, DuplicateGroup,
+    DuplicatesReport, ExportFormat, ExtractResponse, File, FileChange, FileExportRow, FileMetadata,
+    FileShareResponse, FileSort, FileVersion, FolderAccessRequest, FolderAccessResponse,
+    LegalHoldRequest, ManifestFile, ManifestFolder, ModerationStatus, ProjectManifest,
+    RenderResponse, ReviewFileRequest, ShareFileEmailRequest, ShareRecipientStatus,
+    UpdateFileRequest, UploadPolicyRequest, UploadPolicyResponse, UploadResponse, WaveformResponse,
+};
+pub use folder::{
+    CategoryUsage, CreateFolderRequest, Folder, FolderResponse, FolderTreeNode, FolderUsage,
+    FolderVisibilitySummary, UpdateFolderVisibilityRequest,
+};
+pub use member::{AddMemberRequest, ProjectMemberResponse, ProjectRole};
+pub use notification::{
+    CreateNotificationChannelRequest, NotificationChannel, NotificationEvent, NotificationKind,
+};
+pub use project::{
+    is_allowed_response_header, AdminProjectResponse, CreateProjectRequest,
+    CustomDomainVerification, FolderVisibility, InboundEmailResponse, InboundEmailSettings,
+    Project, ProjectMirror, ProjectResponse, PublicFoldersWarning, SetProjectMirrorRequest,
+    UpdateInboundEmailRequest, UpdateProjectRequest, UpdateProjectResponse, PROJECT_COLUMNS,
+};
 pub use refresh_token::{
     LogoutAllResponse, LogoutRequest, LogoutResponse, RefreshRequest, RefreshToken,
-    TokenAuthResponse, TokenRefreshResponse,
+    ReportLoginRequest, TokenAuthResponse, TokenRefreshResponse,
+};
+pub use storage::{
+    IntegrityFailure, IntegrityReport, IntegrityRun, ProjectPurge, ReplicationStatus,
+    StorageMigration,
+};
+pub use upload::{
+    BlockSignature, CompleteMultipartRequest, CompletedPart, DeltaInstructions, DeltaOp,
+    FileSignatures, InitiateMultipartRequest, MultipartPart, MultipartUpload,
+    MultipartUploadProgress, MultipartUploadStatus,
 };
 pub use user::{
     AuthResponse, ChangePasswordRequest, ChangePasswordResponse, CreateUserRequest, LoginRequest,
-    User, UserInfo, UserRole,
+    NotificationPreferences, User, UserInfo, UserRole,
 };
@@ -0,0 +1,74 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+use validator::Validate;
+
+/// Chat service a notification channel posts to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "notification_kind", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationKind {
+    Slack,
+    Discord,
+}
+
+impl NotificationKind {
+    /// Only the service's own incoming-webhook URLs are accepted, so channels
+    /// can't be used to make the server call arbitrary hosts
+    pub fn accepts_webhook_url(self, url: &str) -> bool {
+        let prefixes: &[&str] = match self {
+            NotificationKind::Slack => &["https://hooks.slack.com/"],
+            NotificationKind::Discord => &[
+                "https://discord.com/api/webhooks/",
+                "https://discordapp.com/api/webhooks/",
+            ],
+        };
+        prefixes.iter().any(|prefix| url.starts_with(prefix))
+    }
+}
+
+/// Events a channel can subscribe to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationEvent {
+    /// A file at least `large_upload_bytes` in size was uploaded
+    LargeUpload,
+    /// The integrity check found a file's blob missing or damaged
+    FileCorrupted,
+}
+
+impl NotificationEvent {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            NotificationEvent::LargeUpload => "large_upload",
+            NotificationEvent::FileCorrupted => "file_corrupted",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct NotificationChannel {
+    pub id: Uuid,
+    pub project_id: Uuid,
+    pub kind: NotificationKind,
+    pub webhook_url: String,
+    pub events: Vec<String>,
+    pub template: Option<String>,
+    pub large_upload_bytes: Option<i64>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateNotificationChannelRequest {
+    pub kind: NotificationKind,
+    #[validate(length(max = 2048, message = "Webhook URL is too long"))]
+    pub webhook_url: String,
+    #[validate(length(min = 1, message = "Select at least one event"))]
+    pub events: Vec<NotificationEvent>,
+    /// Placeholders: `{project}`, `{event}`, `{file_name}`, `{folder}`, `{size}`
+    #[validate(length(min = 1, max = 2000, message = "Template must be 1-2000 characters"))]
+    pub template: Option<String>,
+    #[validate(range(min = 1, message = "Large upload threshold must be positive"))]
+    pub large_upload_bytes: Option<i64>,
+}
@@ -1,8 +1,29 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::FromRow;
+use sqlx::{types::Json, FromRow};
+use std::collections::BTreeMap;
 use uuid::Uuid;
-use validator::Validate;
+use validator::{Validate, ValidationError};
+
+use super::{FolderVisibilitySummary, UploadResponse};
+
+/// Visibility a project gives new folders (`default_folder_visibility`)
+/// - inherit: the parent folder's, or the project's at the top level
+/// - project: the project's `is_public` when the folder is created
+/// - public / private: always that
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "folder_visibility", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum FolderVisibility {
+    #[default]
+    Inherit,
+    Project,
+    Public,
+    Private,
+}
+
+/// Columns of `projects` read into a `Project`, in field order
+pub const PROJECT_COLUMNS: &str = "id, user_id, name, api_key, is_public, created_at, allowed_origins, geo_allowed_countries, geo_blocked_countries, download_bandwidth_limit, max_concurrent_uploads, previous_api_key, previous_api_key_expires_at, slug, custom_domain, cold_storage_after_days, storage_quota_bytes, archived, deletion_scheduled_at, default_folder_visibility, image_max_dimension, image_quality, image_png_to_webp, image_keep_original, response_headers, noindex";
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Project {
@@ -12,6 +33,222 @@ pub struct Project {
     pub api_key: Uuid,
     pub is_public: bool,
     pub created_at: DateTime<Utc>,
+    pub allowed_origins: Vec<String>,
+    pub geo_allowed_countries: Vec<String>,
+    pub geo_blocked_countries: Vec<String>,
+    pub download_bandwidth_limit: Option<i64>,
+    pub max_concurrent_uploads: Option<i32>,
+    /// Key replaced by the last rotation, still accepted until `previous_api_key_expires_at`
+    pub previous_api_key: Option<Uuid>,
+    pub previous_api_key_expires_at: Option<DateTime<Utc>>,
+    /// Serves public files at `<slug>.<PUBLIC_FILES_DOMAIN>`
+    pub slug: Option<String>,
+    /// Custom host (CNAME to the files domain) serving public files
+    pub custom_domain: Option<String>,
+    /// Lifecycle rule: move blobs to cold storage after this many days without access
+    pub cold_storage_after_days: Option<i32>,
+    /// Storage cap in bytes; uploads that would exceed it are rejected
+    pub storage_quota_bytes: Option<i64>,
+    /// Frozen: files can be downloaded and listed, but not added, changed or removed
+    pub archived: bool,
+    /// Set by a delete: the project and its blobs are purged at this time unless
+    /// it is restored first. Frozen like `archived` until then.
+    pub deletion_scheduled_at: Option<DateTime<Utc>>,
+    pub default_folder_visibility: FolderVisibility,
+    /// Uploaded JPEG and PNG images larger than this (width or height, in
+    /// pixels) are scaled down to fit
+    pub image_max_dimension: Option<i32>,
+    /// JPEG quality (1-100) of scaled-down images
+    pub image_quality: i32,
+    /// Store uploaded PNGs as lossless WebP
+    pub image_png_to_webp: bool,
+    /// Keep the upload as an `original` version when it was optimized
+    pub image_keep_original: bool,
+    /// Extra headers sent with every download, by lowercase name
+    pub response_headers: Json<BTreeMap<String, String>>,
+    /// Ask search engines not to index downloads (`X-Robots-Tag: noindex`)
+    /// and the project's hosts (`/robots.txt`)
+    pub noindex: bool,
+}
+
+impl Project {
+    /// Whether `key` is the current API key or a rotated key still in its grace period
+    pub fn accepts_api_key(&self, key: Uuid) -> bool {
+        key == self.api_key
+            || (self.previous_api_key == Some(key)
+                && self
+                    .previous_api_key_expires_at
+                    .is_some_and(|expires| expires > Utc::now()))
+    }
+}
+
+/// Country codes must be ISO 3166-1 alpha-2 (e.g. "US", "DE")
+fn validate_country_codes(codes: &[String]) -> Result<(), ValidationError> {
+    if codes
+        .iter()
+        .all(|c| c.len() == 2 && c.chars().all(|ch| ch.is_ascii_alphabetic()))
+    {
+        Ok(())
+    } else {
+        let mut err = ValidationError::new("country_codes");
+        err.message = Some("Country codes must be ISO 3166-1 alpha-2 (e.g. US)".into());
+        Err(err)
+    }
+}
+
+/// Slugs are a single DNS label: 3-63 lowercase letters, digits or hyphens,
+/// not starting or ending with a hyphen
+fn validate_slug(slug: &str) -> Result<(), ValidationError> {
+    let valid = (3..=63).contains(&slug.len())
+        && slug
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        && !slug.starts_with('-')
+        && !slug.ends_with('-');
+
+    if valid {
+        Ok(())
+    } else {
+        let mut err = ValidationError::new("slug");
+        err.message = Some(
+            "Slug must be 3-63 lowercase letters, digits or hyphens, not starting or ending with a hyphen"
+                .into(),
+        );
+        Err(err)
+    }
+}
+
+/// Custom domains must be a fully qualified hostname without scheme, port or path
+fn validate_custom_domain(domain: &str) -> Result<(), ValidationError> {
+    let labels: Vec<&str> = domain.split('.').collect();
+    let valid = domain.len() <= 253
+        && labels.len() >= 2
+        && labels.iter().all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+        })
+        && labels
+            .last()
+            .is_some_and(|tld| tld.chars().all(|c| c.is_ascii_alphabetic()));
+
+    if valid {
+        Ok(())
+    } else {
+        let mut err = ValidationError::new("custom_domain");
+        err.message = Some("Custom domain must be a hostname like files.example.com".into());
+        Err(err)
+    }
+}
+
+/// Each origin must be `*` or a bare `scheme://host[:port]` with no path
+fn validate_origins(origins: &[String]) -> Result<(), ValidationError> {
+    let valid = origins.iter().all(|origin| {
+        origin == "*"
+            || origin
+                .strip_prefix("https://")
+                .or_else(|| origin.strip_prefix("http://"))
+                .is_some_and(|host| !host.is_empty() && !host.contains('/'))
+    });
+
+    if valid {
+        Ok(())
+    } else {
+        let mut err = ValidationError::new("allowed_origins");
+        err.message = Some("Origins must be '*' or scheme://host[:port] without a path".into());
+        Err(err)
+    }
+}
+
+/// Most headers a project can add to its downloads
+pub const MAX_RESPONSE_HEADERS: usize = 20;
+
+/// Headers a project can add to its downloads, besides the prefixes below
+const ALLOWED_RESPONSE_HEADERS: [&str; 6] = [
+    "cache-control",
+    "content-security-policy",
+    "permissions-policy",
+    "referrer-policy",
+    "x-frame-options",
+    "x-robots-tag",
+];
+
+/// Header families a project can add: CORS, cross-origin isolation and
+/// custom `x-` headers
+const ALLOWED_RESPONSE_HEADER_PREFIXES: [&str; 3] = ["access-control-", "cross-origin-", "x-"];
+
+/// Matched by an allowed prefix but refused: credentialed CORS would expose
+/// the API's cookies to other sites, and the rest are reverse proxy
+/// instructions
+const REFUSED_RESPONSE_HEADER_PREFIXES: [&str; 3] =
+    ["access-control-allow-credentials", "x-accel-", "x-sendfile"];
+
+/// Whether a project may set response header `name` (lowercase) on its downloads
+pub fn is_allowed_response_header(name: &str) -> bool {
+    (ALLOWED_RESPONSE_HEADERS.contains(&name)
+        || ALLOWED_RESPONSE_HEADER_PREFIXES
+            .iter()
+            .any(|prefix| name.starts_with(prefix)))
+        && !REFUSED_RESPONSE_HEADER_PREFIXES
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+}
+
+/// Response headers must be valid HTTP header names on the allowlist, with
+/// values of at most 1024 characters
+fn validate_response_headers(headers: &BTreeMap<String, String>) -> Result<(), ValidationError> {
+    let message = if headers.len() > MAX_RESPONSE_HEADERS {
+        Some(format!("At most {MAX_RESPONSE_HEADERS} response headers"))
+    } else {
+        headers.iter().find_map(|(name, value)| {
+            if http::HeaderName::from_bytes(name.as_bytes()).is_err() {
+                Some(format!("'{name}' is not a valid header name"))
+            } else if !is_allowed_response_header(&name.to_ascii_lowercase()) {
+                Some(format!("'{name}' can't be set as a response header"))
+            } else if value.len() > 1024 || http::HeaderValue::from_str(value).is_err() {
+                Some(format!("Invalid value for header '{name}'"))
+            } else {
+                None
+            }
+        })
+    };
+
+    match message {
+        Some(message) => {
+            let mut err = ValidationError::new("response_headers");
+            err.message = Some(message.into());
+            Err(err)
+        }
+        None => Ok(()),
+    }
+}
+
+/// Allowed senders are an address (`alice@example.com`) or a whole domain
+/// (`@example.com`)
+fn validate_email_senders(senders: &[String]) -> Result<(), ValidationError> {
+    let valid = senders.iter().all(|sender| {
+        sender
+            .trim()
+            .split_once('@')
+            .is_some_and(|(local, domain)| {
+                !local.contains(char::is_whitespace)
+                    && domain.contains('.')
+                    && !domain.starts_with('.')
+                    && !domain.ends_with('.')
+                    && !domain.contains(|c: char| c.is_whitespace() || c == '@')
+            })
+    });
+
+    if valid {
+        Ok(())
+    } else {
+        let mut err = ValidationError::new("allowed_senders");
+        err.message =
+            Some("Allowed senders must be addresses or @domain (e.g. @example.com)".into());
+        Err(err)
+    }
 }
 
 #[derive(Debug, Deserialize, Validate)]
@@ -23,6 +260,57 @@ pub struct CreateProjectRequest {
     ))]
     pub name: String,
     pub is_public: Option<bool>,
+    #[validate(custom(function = "validate_origins"))]
+    pub allowed_origins: Option<Vec<String>>,
+    #[validate(custom(function = "validate_country_codes"))]
+    pub geo_allowed_countries: Option<Vec<String>>,
+    #[validate(custom(function = "validate_country_codes"))]
+    pub geo_blocked_countries: Option<Vec<String>>,
+    /// Download throughput cap in bytes/sec
+    #[validate(range(min = 1, message = "Bandwidth limit must be positive"))]
+    pub download_bandwidth_limit: Option<i64>,
+    /// Maximum simultaneous uploads for this project's API key
+    #[validate(range(
+        min = 1,
+        max = 1000,
+        message = "Upload limit must be between 1 and 1000"
+    ))]
+    pub max_concurrent_uploads: Option<i32>,
+    #[validate(custom(function = "validate_slug"))]
+    pub slug: Option<String>,
+    #[validate(custom(function = "validate_custom_domain"))]
+    pub custom_domain: Option<String>,
+    /// Days without access before files move to cold storage
+    #[validate(range(
+        min = 1,
+        max = 3650,
+        message = "Cold storage rule must be between 1 and 3650 days"
+    ))]
+    pub cold_storage_after_days: Option<i32>,
+    /// Storage cap in bytes (sum of original file sizes)
+    #[validate(range(min = 1, message = "Storage quota must be positive"))]
+    pub storage_quota_bytes: Option<i64>,
+    pub default_folder_visibility: Option<FolderVisibility>,
+    /// Scale down uploaded images larger than this many pixels wide or high
+    #[validate(range(
+        min = 16,
+        max = 16384,
+        message = "Image size limit must be between 16 and 16384 pixels"
+    ))]
+    pub image_max_dimension: Option<i32>,
+    #[validate(range(
+        min = 1,
+        max = 100,
+        message = "Image quality must be between 1 and 100"
+    ))]
+    pub image_quality: Option<i32>,
+    pub image_png_to_webp: Option<bool>,
+    pub image_keep_original: Option<bool>,
+    /// Extra headers for downloads, e.g. `{"X-Robots-Tag": "noindex"}`
+    #[validate(custom(function = "validate_response_headers"))]
+    pub response_headers: Option<BTreeMap<String, String>>,
+    /// Keep search engines from indexing the project's files
+    pub noindex: Option<bool>,
 }
 
 #[derive(Debug, Deserialize, Validate)]
@@ -34,6 +322,107 @@ pub struct UpdateProjectRequest {
     ))]
     pub name: Option<String>,
     pub is_public: Option<bool>,
+    #[validate(custom(function = "validate_origins"))]
+    pub allowed_origins: Option<Vec<String>>,
+    #[validate(custom(function = "validate_country_codes"))]
+    pub geo_allowed_countries: Option<Vec<String>>,
+    #[validate(custom(function = "validate_country_codes"))]
+    pub geo_blocked_countries: Option<Vec<String>>,
+    /// Download throughput cap in bytes/sec (0 removes the limit)
+    #[validate(range(min = 0, message = "Bandwidth limit cannot be negative"))]
+    pub download_bandwidth_limit: Option<i64>,
+    /// Maximum simultaneous uploads for this project's API key (0 restores the server default)
+    #[validate(range(
+        min = 0,
+        max = 1000,
+        message = "Upload limit must be between 0 and 1000"
+    ))]
+    pub max_concurrent_uploads: Option<i32>,
+    /// Empty string removes the slug
+    #[validate(custom(function = "validate_optional_slug"))]
+    pub slug: Option<String>,
+    /// Empty string removes the custom domain
+    #[validate(custom(function = "validate_optional_custom_domain"))]
+    pub custom_domain: Option<String>,
+    /// Days without access before files move to cold storage (0 disables the rule)
+    #[validate(range(
+        min = 0,
+        max = 3650,
+        message = "Cold storage rule must be between 0 and 3650 days"
+    ))]
+    pub cold_storage_after_days: Option<i32>,
+    /// Storage cap in bytes (0 removes the quota)
+    #[validate(range(min = 0, message = "Storage quota cannot be negative"))]
+    pub storage_quota_bytes: Option<i64>,
+    /// Freeze (`true`) or unfreeze (`false`) the project's files
+    pub archived: Option<bool>,
+    pub default_folder_visibility: Option<FolderVisibility>,
+    /// Scale down uploaded images larger than this many pixels wide or high
+    /// (0 turns scaling off)
+    #[validate(custom(function = "validate_optional_image_dimension"))]
+    pub image_max_dimension: Option<i32>,
+    #[validate(range(
+        min = 1,
+        max = 100,
+        message = "Image quality must be between 1 and 100"
+    ))]
+    pub image_quality: Option<i32>,
+    pub image_png_to_webp: Option<bool>,
+    pub image_keep_original: Option<bool>,
+    /// Replaces all extra download headers (`{}` removes them)
+    #[validate(custom(function = "validate_response_headers"))]
+    pub response_headers: Option<BTreeMap<String, String>>,
+    pub noindex: Option<bool>,
+    /// Give every existing folder the project's (new) `is_public` as well
+    #[serde(default)]
+    pub cascade_to_folders: bool,
+}
+
+/// Folders left public when a project is made private without
+/// `cascade_to_folders`; their files are still served publicly
+#[derive(Debug, Serialize)]
+pub struct PublicFoldersWarning {
+    pub message: String,
+    pub public_folders: i64,
+    pub public_files: i64,
+}
+
+/// `update_project` result: the project, plus how its folders were affected
+/// when `cascade_to_folders` was set, or a warning about folders still public
+#[derive(Debug, Serialize)]
+pub struct UpdateProjectResponse {
+    #[serde(flatten)]
+    pub project: Project,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub folders: Option<FolderVisibilitySummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<PublicFoldersWarning>,
+}
+
+fn validate_optional_slug(slug: &str) -> Result<(), ValidationError> {
+    if slug.is_empty() {
+        Ok(())
+    } else {
+        validate_slug(slug)
+    }
+}
+
+fn validate_optional_image_dimension(dimension: i32) -> Result<(), ValidationError> {
+    if dimension == 0 || (16..=16384).contains(&dimension) {
+        Ok(())
+    } else {
+        let mut err = ValidationError::new("image_max_dimension");
+        err.message = Some("Image size limit must be 0 or between 16 and 16384 pixels".into());
+        Err(err)
+    }
+}
+
+fn validate_optional_custom_domain(domain: &str) -> Result<(), ValidationError> {
+    if domain.is_empty() {
+        Ok(())
+    } else {
+        validate_custom_domain(domain)
+    }
 }
 
 #[derive(Debug, Serialize, FromRow)]
@@ -41,8 +430,117 @@ pub struct ProjectResponse {
     pub id: Uuid,
     pub name: String,
     pub api_key: Uuid,
+    pub previous_api_key: Option<Uuid>,
+    pub previous_api_key_expires_at: Option<DateTime<Utc>>,
+    pub slug: Option<String>,
+    pub custom_domain: Option<String>,
+    /// Value of the `_filerunner-challenge.<custom_domain>` TXT record that
+    /// proves control of the domain
+    pub custom_domain_verification_token: Option<String>,
+    /// Files are only served on the custom domain once this is set
+    pub custom_domain_verified_at: Option<DateTime<Utc>>,
+    pub is_public: bool,
+    pub created_at: DateTime<Utc>,
+    pub allowed_origins: Vec<String>,
+    pub geo_allowed_countries: Vec<String>,
+    pub geo_blocked_countries: Vec<String>,
+    pub download_bandwidth_limit: Option<i64>,
+    pub max_concurrent_uploads: Option<i32>,
+    pub cold_storage_after_days: Option<i32>,
+    pub storage_quota_bytes: Option<i64>,
+    pub archived: bool,
+    pub deletion_scheduled_at: Option<DateTime<Utc>>,
+    pub default_folder_visibility: FolderVisibility,
+    pub image_max_dimension: Option<i32>,
+    pub image_quality: i32,
+    pub image_png_to_webp: bool,
+    pub image_keep_original: bool,
+    pub response_headers: Json<BTreeMap<String, String>>,
+    pub noindex: bool,
+    pub file_count: Option<i64>,
+    pub total_size: Option<i64>,
+    /// API key usage (flushed periodically, so may lag by up to a minute)
+    pub api_key_last_used_at: Option<DateTime<Utc>>,
+    pub api_key_last_used_ip: Option<String>,
+    pub api_key_request_count: i64,
+}
+
+/// Outcome of `POST /api/v1/projects/:id/custom-domain/verify`
+#[derive(Debug, Serialize)]
+pub struct CustomDomainVerification {
+    pub custom_domain: String,
+    pub record_name: String,
+    pub record_value: String,
+    pub verified_at: DateTime<Utc>,
+}
+
+/// Project summary for the cross-user admin listing (API key omitted)
+#[derive(Debug, Serialize, FromRow)]
+pub struct AdminProjectResponse {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub owner_email: String,
+    pub name: String,
     pub is_public: bool,
     pub created_at: DateTime<Utc>,
     pub file_count: Option<i64>,
     pub total_size: Option<i64>,
 }
+
+/// Who may email files into a project
+#[derive(Debug, Deserialize, Validate)]
+pub struct UpdateInboundEmailRequest {
+    #[validate(
+        length(max = 100, message = "At most 100 allowed senders"),
+        custom(function = "validate_email_senders")
+    )]
+    pub allowed_senders: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct InboundEmailSettings {
+    /// Attachments sent here go to the project root; `+folder` before the `@`
+    /// picks a folder. None when inbound email isn't configured.
+    pub address: Option<String>,
+    /// Addresses or `@domain`s; nobody can email files in while it's empty
+    pub allowed_senders: Vec<String>,
+}
+
+/// What an inbound email webhook stored
+#[derive(Debug, Serialize)]
+pub struct InboundEmailResponse {
+    pub files: Vec<UploadResponse>,
+    /// Recipients and attachments that weren't stored, and why
+    pub skipped: Vec<String>,
+}
+
+/// Make a project a mirror of a project on another FileRunner instance
+#[derive(Debug, Deserialize, Validate)]
+pub struct SetProjectMirrorRequest {
+    /// Base URL of the other instance, e.g. `https://eu.files.example.com`
+    #[validate(length(min = 1, max = 2000, message = "remote_url is required"))]
+    pub remote_url: String,
+    pub remote_project_id: Uuid,
+    /// The remote project's API key
+    #[validate(length(min = 1, max = 200, message = "api_key is required"))]
+    pub api_key: String,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct ProjectMirror {
+    pub project_id: Uuid,
+    pub remote_url: String,
+    pub remote_project_id: Uuid,
+    #[serde(skip_serializing)]
+    pub remote_api_key: String,
+    /// Remote manifest version of the last complete sync
+    pub manifest_version: Option<String>,
+    pub last_synced_at: Option<DateTime<Utc>>,
+    /// Why the last sync failed; None once a sync succeeds
+    pub last_error: Option<String>,
+    /// Set while a sync is running
+    pub sync_started_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    /// Local files that are copies of remote ones
+    pub file_count: i64,
+}
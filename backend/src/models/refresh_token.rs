@@ -26,6 +26,12 @@ pub struct RefreshRequest {
     pub refresh_token: String,
 }
 
+/// Token from a login alert email
+#[derive(Debug, Deserialize)]
+pub struct ReportLoginRequest {
+    pub token: String,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct LogoutRequest {
     pub refresh_token: Option<String>,
@@ -0,0 +1,106 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// Progress of a blob migration to the cold storage backend
+#[derive(Debug, Serialize, FromRow)]
+pub struct StorageMigration {
+    pub id: Uuid,
+    /// running, copied, cutting_over, completed or failed
+    pub status: String,
+    pub total_files: i64,
+    pub total_bytes: i64,
+    pub copied_files: i64,
+    pub copied_bytes: i64,
+    pub failed_files: i64,
+    pub cutover_files: i64,
+    pub last_error: Option<String>,
+    pub started_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub cutover_at: Option<DateTime<Utc>>,
+}
+
+/// Progress of removing a deleted project's blobs
+#[derive(Debug, Serialize, FromRow)]
+pub struct ProjectPurge {
+    pub id: Uuid,
+    pub project_id: Uuid,
+    pub project_name: String,
+    pub requested_by: Option<Uuid>,
+    /// pending, running, completed, failed or cancelled (project restored)
+    pub status: String,
+    /// Local and cold blobs found when the purge started
+    pub total_blobs: i64,
+    pub total_bytes: i64,
+    pub removed_blobs: i64,
+    pub removed_bytes: i64,
+    pub failed_blobs: i64,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// End of the grace period; the purge doesn't start before it
+    pub run_after: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+/// One pass of the integrity scrubbing job
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct IntegrityRun {
+    pub id: Uuid,
+    /// running, completed or failed
+    pub status: String,
+    /// Files the run was asked to check; `None` checks every file
+    pub sample_size: Option<i64>,
+    pub checked_files: i64,
+    pub checked_bytes: i64,
+    pub corrupted_files: i64,
+    pub skipped_files: i64,
+    pub last_error: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+/// An open problem with a file's blob
+#[derive(Debug, Serialize, FromRow)]
+pub struct IntegrityFailure {
+    pub id: Uuid,
+    pub file_id: Uuid,
+    pub file_name: String,
+    pub project_id: Uuid,
+    pub project_name: String,
+    /// missing, size_mismatch, unreadable or hash_mismatch
+    pub problem: String,
+    pub detail: String,
+    pub detected_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+}
+
+/// `GET /api/v1/admin/integrity`
+#[derive(Debug, Serialize)]
+pub struct IntegrityReport {
+    pub total_files: i64,
+    /// Files no run has verified yet
+    pub unchecked_files: i64,
+    pub oldest_check_at: Option<DateTime<Utc>>,
+    pub open_failures: Vec<IntegrityFailure>,
+    /// Most recent first
+    pub runs: Vec<IntegrityRun>,
+}
+
+/// `GET /api/v1/admin/replication`
+#[derive(Debug, Serialize)]
+pub struct ReplicationStatus {
+    /// `None` when replication isn't configured on this instance
+    pub peer_url: Option<String>,
+    /// Files waiting to be pushed to the peer
+    pub backlog: i64,
+    /// Queued files whose last push failed
+    pub failing: i64,
+    pub oldest_queued_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    /// Files the peer holds a copy of
+    pub replicated_files: i64,
+    pub last_replicated_at: Option<DateTime<Utc>>,
+}
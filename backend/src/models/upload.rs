@@ -0,0 +1,123 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// Start a multipart upload (`POST /api/v1/uploads/multipart`)
+#[derive(Debug, Deserialize)]
+pub struct InitiateMultipartRequest {
+    pub file_name: String,
+    /// Destination folder; omit or leave empty for the project root
+    pub folder_path: Option<String>,
+    pub description: Option<String>,
+    /// Same as the `on_conflict` upload field, applied on completion
+    pub on_conflict: Option<String>,
+    /// Total size in bytes the client will send, for progress reporting
+    pub expected_size: Option<i64>,
+}
+
+/// A multipart upload that hasn't been completed or aborted yet
+#[derive(Debug, Serialize, FromRow)]
+pub struct MultipartUpload {
+    pub id: Uuid,
+    pub project_id: Uuid,
+    pub file_name: String,
+    pub folder_path: Option<String>,
+    pub description: Option<String>,
+    pub on_conflict: Option<String>,
+    /// uploading, or completing while the parts are assembled
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub expected_size: Option<i64>,
+}
+
+/// A received part of a multipart upload
+#[derive(Debug, Serialize, FromRow)]
+pub struct MultipartPart {
+    pub part_number: i32,
+    #[serde(skip)]
+    pub stored_name: String,
+    pub size: i64,
+    /// Hex SHA-256 of the part; echoed back in the completion's part list
+    pub sha256: String,
+    pub uploaded_at: DateTime<Utc>,
+}
+
+/// A multipart upload with the parts received so far
+#[derive(Debug, Serialize)]
+pub struct MultipartUploadStatus {
+    #[serde(flatten)]
+    pub upload: MultipartUpload,
+    pub parts: Vec<MultipartPart>,
+    pub uploaded_bytes: i64,
+    /// Bytes of parts still streaming in to this server
+    pub receiving_bytes: i64,
+}
+
+/// How far a multipart upload has got, sent as `progress` events
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MultipartUploadProgress {
+    pub status: String,
+    pub parts_received: i64,
+    /// Bytes of the parts received so far
+    pub uploaded_bytes: i64,
+    /// Bytes of parts still streaming in to this server
+    pub receiving_bytes: i64,
+    pub expected_size: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CompletedPart {
+    pub part_number: i32,
+    pub sha256: String,
+}
+
+/// Signature of one block of a file, for delta uploads
+#[derive(Debug, Serialize)]
+pub struct BlockSignature {
+    pub index: u64,
+    /// rsync rolling checksum, see `utils::rolling_checksum`
+    pub weak: u32,
+    /// Hex SHA-256 of the block
+    pub strong: String,
+}
+
+/// Block signatures of a file's current content (`GET /api/v1/files/:id/signatures`)
+#[derive(Debug, Serialize)]
+pub struct FileSignatures {
+    pub file_id: Uuid,
+    pub size: i64,
+    /// Hex SHA-256 of the whole content; sent back as `base_sha256` with the delta
+    pub sha256: String,
+    pub block_size: usize,
+    /// In file order; the last block may be shorter
+    pub blocks: Vec<BlockSignature>,
+}
+
+/// One step of rebuilding a file from a delta
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeltaOp {
+    /// Reuse `count` consecutive blocks of the current content, from block `block`
+    Copy { block: u64, count: u64 },
+    /// Take the next this many bytes of the upload's `data` field
+    Data(u64),
+}
+
+/// Instructions of a delta upload (the `delta` field of `POST /api/v1/files/:id/delta`)
+#[derive(Debug, Deserialize)]
+pub struct DeltaInstructions {
+    /// `sha256` of the signatures the delta was computed against
+    pub base_sha256: String,
+    pub block_size: usize,
+    pub ops: Vec<DeltaOp>,
+    /// Hex SHA-256 of the new content, checked after rebuilding it
+    pub sha256: String,
+}
+
+/// Assemble the listed parts, in ascending part order, into the file
+#[derive(Debug, Deserialize)]
+pub struct CompleteMultipartRequest {
+    pub parts: Vec<CompletedPart>,
+}
@@ -35,8 +35,10 @@ pub struct User {
 pub struct CreateUserRequest {
     #[validate(email(message = "Invalid email address"))]
     pub email: String,
-    #[validate(length(min = 8, message = "Password must be at least 8 characters"))]
+    /// Checked against the password policy (`PASSWORD_*`)
     pub password: String,
+    /// hCaptcha/Turnstile response token or proof-of-work solution
+    pub captcha_token: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Validate)]
@@ -44,6 +46,8 @@ pub struct LoginRequest {
     #[validate(email(message = "Invalid email address"))]
     pub email: String,
     pub password: String,
+    /// hCaptcha/Turnstile response token or proof-of-work solution
+    pub captcha_token: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -76,11 +80,23 @@ impl From<User> for UserInfo {
 #[derive(Debug, Deserialize, Validate)]
 pub struct ChangePasswordRequest {
     pub current_password: String,
-    #[validate(length(min = 8, message = "Password must be at least 8 characters"))]
+    /// Checked against the password policy (`PASSWORD_*`)
     pub new_password: String,
 }
 
+/// Changing the password signs out every session, so the caller gets a new token pair
 #[derive(Debug, Serialize)]
 pub struct ChangePasswordResponse {
     pub message: String,
+    pub access_token: String,
+    pub refresh_token: String,
+    pub token_type: String,
+    pub expires_in: i64,
+}
+
+/// Which notification emails the user receives
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct NotificationPreferences {
+    /// Email when an owned project crosses 80/90/100% of its storage quota
+    pub notify_quota_warnings: bool,
 }
@@ -0,0 +1,155 @@
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use serde::Serialize;
+use sqlx::PgPool;
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Random delay added to each run, as a fraction of the interval, so replicas
+/// started together don't hit the database at the same moment
+const JITTER_FRACTION: f64 = 0.1;
+
+/// Idempotency keys are honoured for this long (see `upload_file`)
+const IDEMPOTENCY_KEY_RETENTION_HOURS: i32 = 24;
+
+/// Sign-in devices (and login alerts) are remembered for this long
+const LOGIN_DEVICE_RETENTION_DAYS: i32 = 180;
+
+/// Run history of one recurring task, as shown by `GET /api/admin/jobs`
+#[derive(Debug, Clone, Serialize)]
+pub struct JobStatus {
+    pub name: &'static str,
+    pub interval_secs: u64,
+    pub running: bool,
+    pub run_count: u64,
+    pub failure_count: u64,
+    pub last_started_at: Option<DateTime<Utc>>,
+    pub last_duration_ms: Option<u64>,
+    pub last_error: Option<String>,
+    pub next_run_at: Option<DateTime<Utc>>,
+}
+
+/// Owns the server's recurring maintenance tasks and records how each run went
+#[derive(Debug, Default)]
+pub struct Scheduler {
+    jobs: Mutex<Vec<JobStatus>>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `task` every `interval` (plus jitter), starting one interval after
+    /// startup. The next run is only scheduled once the previous one finished,
+    /// so a slow run delays the job instead of overlapping with itself.
+    pub fn spawn<F, Fut>(self: &Arc<Self>, name: &'static str, interval: Duration, task: F)
+    where
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<(), String>> + Send,
+    {
+        let index = {
+            let mut jobs = self.jobs.lock().unwrap();
+            jobs.push(JobStatus {
+                name,
+                interval_secs: interval.as_secs(),
+                running: false,
+                run_count: 0,
+                failure_count: 0,
+                last_started_at: None,
+                last_duration_ms: None,
+                last_error: None,
+                next_run_at: None,
+            });
+            jobs.len() - 1
+        };
+
+        let scheduler = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let jitter = interval.mul_f64(rand::thread_rng().gen_range(0.0..JITTER_FRACTION));
+                let delay = interval + jitter;
+                scheduler.update(index, |job| {
+                    job.next_run_at = chrono::Duration::from_std(delay)
+                        .ok()
+                        .map(|delay| Utc::now() + delay);
+                });
+                tokio::time::sleep(delay).await;
+
+                scheduler.update(index, |job| {
+                    job.running = true;
+                    job.last_started_at = Some(Utc::now());
+                    job.next_run_at = None;
+                });
+                let started = Instant::now();
+                let result = task().await;
+                let elapsed = started.elapsed();
+
+                if let Err(ref e) = result {
+                    tracing::warn!("Scheduled job {} failed: {}", name, e);
+                }
+                scheduler.update(index, |job| {
+                    job.running = false;
+                    job.run_count += 1;
+                    job.last_duration_ms = Some(elapsed.as_millis() as u64);
+                    job.last_error = result.err();
+                    if job.last_error.is_some() {
+                        job.failure_count += 1;
+                    }
+                });
+            }
+        });
+    }
+
+    fn update(&self, index: usize, f: impl FnOnce(&mut JobStatus)) {
+        f(&mut self.jobs.lock().unwrap()[index]);
+    }
+
+    /// Current status of every registered job, in registration order
+    pub fn statuses(&self) -> Vec<JobStatus> {
+        self.jobs.lock().unwrap().clone()
+    }
+}
+
+/// Delete refresh tokens past their expiry; they can no longer be used or rotated
+pub async fn purge_expired_refresh_tokens(pool: &PgPool) -> Result<(), String> {
+    let result = sqlx::query("DELETE FROM refresh_tokens WHERE expires_at < NOW()")
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    if result.rows_affected() > 0 {
+        tracing::info!("Purged {} expired refresh tokens", result.rows_affected());
+    }
+    Ok(())
+}
+
+/// Forget sign-in devices unused for `LOGIN_DEVICE_RETENTION_DAYS` (a login
+/// from one again counts as new) and login alerts as old
+pub async fn purge_login_history(pool: &PgPool) -> Result<(), String> {
+    sqlx::query("DELETE FROM login_devices WHERE last_seen_at < NOW() - make_interval(days => $1)")
+        .bind(LOGIN_DEVICE_RETENTION_DAYS)
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    sqlx::query("DELETE FROM login_alerts WHERE created_at < NOW() - make_interval(days => $1)")
+        .bind(LOGIN_DEVICE_RETENTION_DAYS)
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Delete upload idempotency keys that are too old to be replayed
+pub async fn purge_idempotency_keys(pool: &PgPool) -> Result<(), String> {
+    sqlx::query(
+        "DELETE FROM upload_idempotency_keys WHERE created_at < NOW() - make_interval(hours => $1)",
+    )
+    .bind(IDEMPOTENCY_KEY_RETENTION_HOURS)
+    .execute(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
@@ -0,0 +1,367 @@
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, Request},
+    response::Response,
+};
+use hyper::body::Incoming;
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo, TokioTimer},
+    server::{conn::auto, graceful::GracefulShutdown},
+};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::{
+    convert::Infallible,
+    future::Future,
+    io,
+    net::SocketAddr,
+    pin::Pin,
+    sync::Arc,
+    task::{ready, Context, Poll},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::{TcpListener, TcpStream},
+    sync::Semaphore,
+    time::{Instant, Sleep},
+};
+use tower::{Service, ServiceExt};
+
+use crate::config::Config;
+
+/// Pause after a failed `accept` (e.g. out of file descriptors) before retrying
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Pending connections the kernel queues for a bound listener
+const LISTEN_BACKLOG: i32 = 1024;
+
+/// First file descriptor passed by systemd socket activation
+#[cfg(unix)]
+const SD_LISTEN_FDS_START: std::os::fd::RawFd = 3;
+
+/// Duration of a seconds setting where 0 means off
+pub fn optional_secs(secs: u64) -> Option<Duration> {
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
+
+/// Listening socket handed over by systemd socket activation (`LISTEN_FDS`),
+/// if this process was started that way. Only the first socket is used.
+#[cfg(unix)]
+fn activated_listener() -> io::Result<Option<std::net::TcpListener>> {
+    use std::os::fd::FromRawFd;
+
+    let for_this_process = std::env::var("LISTEN_PID")
+        .ok()
+        .and_then(|pid| pid.parse::<u32>().ok())
+        == Some(std::process::id());
+    let fds: i32 = std::env::var("LISTEN_FDS")
+        .ok()
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(0);
+    if !for_this_process || fds < 1 {
+        return Ok(None);
+    }
+    if fds > 1 {
+        tracing::warn!("systemd passed {} sockets; only the first is used", fds);
+    }
+    // SAFETY: systemd passes the sockets open, starting at fd 3, and nothing
+    // else in this process takes ownership of them
+    let listener = unsafe { std::net::TcpListener::from_raw_fd(SD_LISTEN_FDS_START) };
+    listener.local_addr().map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("Activation socket is not a TCP listener: {e}"),
+        )
+    })?;
+    Ok(Some(listener))
+}
+
+#[cfg(not(unix))]
+fn activated_listener() -> io::Result<Option<std::net::TcpListener>> {
+    Ok(None)
+}
+
+/// Bind `addr`, sharing the port with other processes when `reuse_port` is on
+fn bind(addr: SocketAddr, reuse_port: bool) -> io::Result<std::net::TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    socket.set_reuse_address(true)?;
+    #[cfg(unix)]
+    socket.set_reuse_port(reuse_port)?;
+    #[cfg(not(unix))]
+    if reuse_port {
+        tracing::warn!("SERVER_REUSE_PORT is only supported on Unix");
+    }
+    socket.bind(&addr.into())?;
+    socket.listen(LISTEN_BACKLOG)?;
+    Ok(socket.into())
+}
+
+/// The socket to serve on: the one passed by systemd socket activation when
+/// present, otherwise `SERVER_HOST:SERVER_PORT` bound here (with `SO_REUSEPORT`
+/// when `SERVER_REUSE_PORT` is on, so a new binary can bind it alongside this one)
+pub async fn listen(config: &Config) -> io::Result<TcpListener> {
+    let listener = match activated_listener()? {
+        Some(listener) => {
+            tracing::info!("Using socket passed by systemd");
+            listener
+        }
+        None => {
+            let addr = format!("{}:{}", config.server_host, config.server_port);
+            let addr = tokio::net::lookup_host(&addr)
+                .await?
+                .next()
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("{addr} did not resolve"),
+                    )
+                })?;
+            bind(addr, config.server_reuse_port)?
+        }
+    };
+    listener.set_nonblocking(true)?;
+    TcpListener::from_std(listener)
+}
+
+/// Resolves on SIGTERM (what systemd and `docker stop` send) or Ctrl-C
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::warn!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = terminate => {}
+    }
+}
+
+/// Serve `app` on `listener` with the connection settings from `Config`:
+/// HTTP/1 keep-alive, HTTP/2 (on by default, negotiated per connection),
+/// read/write timeouts and a cap on open connections. Like `axum::serve` with
+/// `into_make_service_with_connect_info`, requests carry `ConnectInfo<SocketAddr>`.
+///
+/// On SIGTERM or Ctrl-C the listener is closed and open connections finish
+/// their requests (idle keep-alive connections are closed) for up to
+/// `SHUTDOWN_TIMEOUT_SECS` before this returns.
+pub async fn serve<S>(listener: TcpListener, app: S, config: &Config)
+where
+    S: Service<Request, Response = Response, Error = Infallible> + Clone + Send + 'static,
+    S::Future: Send,
+{
+    let mut builder = auto::Builder::new(TokioExecutor::new());
+    builder
+        .http1()
+        .timer(TokioTimer::new())
+        .keep_alive(config.http_keep_alive)
+        .header_read_timeout(optional_secs(config.request_header_timeout_secs));
+    builder
+        .http2()
+        .timer(TokioTimer::new())
+        .max_concurrent_streams(config.http2_max_concurrent_streams)
+        .keep_alive_interval(optional_secs(config.http2_keep_alive_interval_secs));
+    let builder = Arc::new(if config.http2 {
+        builder
+    } else {
+        builder.http1_only()
+    });
+
+    let read_timeout = optional_secs(config.http_read_timeout_secs);
+    let write_timeout = optional_secs(config.http_write_timeout_secs);
+    // New connections wait in the listen backlog while the server is at the cap
+    let connections =
+        (config.max_connections > 0).then(|| Arc::new(Semaphore::new(config.max_connections)));
+    let graceful = GracefulShutdown::new();
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+
+    loop {
+        let permit = match connections {
+            Some(ref connections) => tokio::select! {
+                permit = Arc::clone(connections).acquire_owned() => {
+                    Some(permit.expect("connection semaphore is never closed"))
+                }
+                _ = &mut shutdown => break,
+            },
+            None => None,
+        };
+        let accepted = tokio::select! {
+            accepted = listener.accept() => accepted,
+            _ = &mut shutdown => break,
+        };
+        let (stream, remote_addr) = match accepted {
+            Ok(connection) => connection,
+            Err(e) => {
+                tracing::warn!("Failed to accept connection: {}", e);
+                tokio::time::sleep(ACCEPT_RETRY_DELAY).await;
+                continue;
+            }
+        };
+
+        let io = TokioIo::new(TimeoutStream::new(stream, read_timeout, write_timeout));
+        let app = app.clone();
+        let service = hyper::service::service_fn(move |mut request: hyper::Request<Incoming>| {
+            request.extensions_mut().insert(ConnectInfo(remote_addr));
+            app.clone().oneshot(request.map(Body::new))
+        });
+        let builder = Arc::clone(&builder);
+        let watcher = graceful.watcher();
+        tokio::spawn(async move {
+            let connection = builder.serve_connection_with_upgrades(io, service);
+            if let Err(e) = watcher.watch(connection).await {
+                tracing::debug!("Connection from {} ended: {}", remote_addr, e);
+            }
+            drop(permit);
+        });
+    }
+
+    // Stop accepting, so a new process sharing or inheriting the socket takes over
+    drop(listener);
+    tracing::info!(
+        "Shutting down; waiting for {} open connections to finish",
+        graceful.count()
+    );
+    match optional_secs(config.shutdown_timeout_secs) {
+        Some(timeout) => {
+            if tokio::time::timeout(timeout, graceful.shutdown())
+                .await
+                .is_err()
+            {
+                tracing::warn!(
+                    "Connections still open after {}s; closing them",
+                    timeout.as_secs()
+                );
+            }
+        }
+        None => graceful.shutdown().await,
+    }
+}
+
+/// Poll the deadline of a pending operation, starting it on the first poll
+fn poll_deadline(
+    deadline: &mut Option<Pin<Box<Sleep>>>,
+    timeout: Option<Duration>,
+    cx: &mut Context<'_>,
+    operation: &str,
+) -> Poll<io::Error> {
+    let Some(timeout) = timeout else {
+        return Poll::Pending;
+    };
+    let sleep = deadline.get_or_insert_with(|| Box::pin(tokio::time::sleep(timeout)));
+    ready!(sleep.as_mut().poll(cx));
+    Poll::Ready(io::Error::new(
+        io::ErrorKind::TimedOut,
+        format!("{operation} timed out after {}s", timeout.as_secs()),
+    ))
+}
+
+/// TCP stream that fails a read or write left pending for longer than its
+/// timeout. Writes making progress push the read deadline back, so a client
+/// busy receiving a long download isn't cut off for not sending anything.
+struct TimeoutStream {
+    inner: TcpStream,
+    read_timeout: Option<Duration>,
+    write_timeout: Option<Duration>,
+    read_deadline: Option<Pin<Box<Sleep>>>,
+    write_deadline: Option<Pin<Box<Sleep>>>,
+}
+
+impl TimeoutStream {
+    fn new(
+        inner: TcpStream,
+        read_timeout: Option<Duration>,
+        write_timeout: Option<Duration>,
+    ) -> Self {
+        Self {
+            inner,
+            read_timeout,
+            write_timeout,
+            read_deadline: None,
+            write_deadline: None,
+        }
+    }
+
+    fn poll_write_with<T>(
+        &mut self,
+        cx: &mut Context<'_>,
+        write: impl FnOnce(Pin<&mut TcpStream>, &mut Context<'_>) -> Poll<io::Result<T>>,
+    ) -> Poll<io::Result<T>> {
+        match write(Pin::new(&mut self.inner), cx) {
+            Poll::Ready(result) => {
+                self.write_deadline = None;
+                if let (Some(sleep), Some(timeout)) =
+                    (self.read_deadline.as_mut(), self.read_timeout)
+                {
+                    sleep.as_mut().reset(Instant::now() + timeout);
+                }
+                Poll::Ready(result)
+            }
+            Poll::Pending => {
+                poll_deadline(&mut self.write_deadline, self.write_timeout, cx, "Write").map(Err)
+            }
+        }
+    }
+}
+
+impl AsyncRead for TimeoutStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        match Pin::new(&mut this.inner).poll_read(cx, buf) {
+            Poll::Ready(result) => {
+                this.read_deadline = None;
+                Poll::Ready(result)
+            }
+            Poll::Pending => {
+                poll_deadline(&mut this.read_deadline, this.read_timeout, cx, "Read").map(Err)
+            }
+        }
+    }
+}
+
+impl AsyncWrite for TimeoutStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut()
+            .poll_write_with(cx, |inner, cx| inner.poll_write(cx, buf))
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut()
+            .poll_write_with(cx, |inner, cx| inner.poll_write_vectored(cx, bufs))
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut()
+            .poll_write_with(cx, |inner, cx| inner.poll_flush(cx))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut()
+            .poll_write_with(cx, |inner, cx| inner.poll_shutdown(cx))
+    }
+}
@@ -0,0 +1,385 @@
+use axum::http::HeaderMap;
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::{
+    error::{AppError, Result},
+    middleware::{AuthUser, OptionalAuthUser},
+    models::{Folder, Project, ProjectRole},
+    utils::RolePermission,
+};
+
+/// `?as_admin=true` lets an admin act on projects they don't own
+#[derive(Debug, Default, Deserialize)]
+pub struct AdminQuery {
+    pub as_admin: Option<bool>,
+}
+
+/// Resolve an explicit admin override. Returns true (and writes an audit log line)
+/// when a user holding `permission` asked for it; anyone else asking is rejected.
+pub fn admin_override(
+    user: &AuthUser,
+    query: &AdminQuery,
+    permission: RolePermission,
+    action: &str,
+    target: Uuid,
+) -> Result<bool> {
+    if !query.as_admin.unwrap_or(false) {
+        return Ok(false);
+    }
+    user.require_permission(permission)?;
+
+    tracing::info!(
+        target: "audit",
+        admin_id = %user.id,
+        admin_email = %user.email,
+        %permission,
+        action,
+        %target,
+        "Admin override"
+    );
+    Ok(true)
+}
+
+/// Credentials presented with a request, resolved once per handler
+#[derive(Debug, Clone)]
+pub enum Credentials {
+    /// Bearer token (access or legacy JWT) with the user's project memberships
+    User(AuthUser, HashMap<Uuid, ProjectRole>),
+    /// Admin acting through an audited `?as_admin=true` override
+    Admin,
+    /// Project API key from `X-API-Key` (or `?api_key=` on downloads)
+    ApiKey(Uuid),
+    Anonymous,
+}
+
+impl Credentials {
+    /// JWT takes precedence over an API key; malformed keys count as anonymous
+    pub async fn resolve(
+        pool: &PgPool,
+        optional_auth: &OptionalAuthUser,
+        headers: &HeaderMap,
+        query_api_key: Option<&str>,
+    ) -> Result<Self> {
+        if let Some(ref user) = optional_auth.0 {
+            let memberships = sqlx::query_as::<_, (Uuid, ProjectRole)>(
+                "SELECT project_id, role FROM project_members WHERE user_id = $1",
+            )
+            .bind(user.id)
+            .fetch_all(pool)
+            .await?
+            .into_iter()
+            .collect();
+            return Ok(Credentials::User(user.clone(), memberships));
+        }
+
+        Ok(headers
+            .get("X-API-Key")
+            .and_then(|h| h.to_str().ok())
+            .or(query_api_key)
+            .and_then(|key| Uuid::parse_str(key).ok())
+            .map_or(Credentials::Anonymous, Credentials::ApiKey))
+    }
+
+    /// Effective role on `project`. Owners, the project API key and admin
+    /// overrides act with full (`Admin`) rights.
+    fn role_on(&self, project: &Project) -> Option<ProjectRole> {
+        match self {
+            Credentials::User(user, _) if user.id == project.user_id => Some(ProjectRole::Admin),
+            Credentials::User(_, memberships) => memberships.get(&project.id).copied(),
+            Credentials::Admin => Some(ProjectRole::Admin),
+            Credentials::ApiKey(key) if project.accepts_api_key(*key) => Some(ProjectRole::Admin),
+            Credentials::ApiKey(_) | Credentials::Anonymous => None,
+        }
+    }
+}
+
+/// Whether a file in `project` (optionally inside `folder`) may be downloaded.
+/// Public projects and public folders are open to everyone; everything else
+/// needs the owner's token, the project API key or any collaborator role.
+pub fn can_read(project: &Project, folder: Option<&Folder>, credentials: &Credentials) -> bool {
+    if project.is_public || folder.is_some_and(|f| f.is_public) {
+        return true;
+    }
+    credentials.role_on(project).is_some()
+}
+
+/// Whether a project's complete file list may be read: any collaborator role,
+/// the owner or the API key (public projects don't list their files)
+pub fn can_list(project: &Project, credentials: &Credentials) -> bool {
+    credentials.role_on(project).is_some()
+}
+
+/// Whether new files may be added to `project` (uploader role or above)
+pub fn can_upload(project: &Project, credentials: &Credentials) -> bool {
+    credentials
+        .role_on(project)
+        .is_some_and(|role| role >= ProjectRole::Uploader)
+}
+
+/// Whether files in `project` may be modified or removed (delete, move).
+/// Also gates member management.
+pub fn can_write(project: &Project, credentials: &Credentials) -> bool {
+    credentials.role_on(project) == Some(ProjectRole::Admin)
+}
+
+/// Reject changes to the files of an archived project or one scheduled for
+/// deletion (uploads, moves, deletes); reads stay allowed
+pub fn ensure_project_writable(project: &Project) -> Result<()> {
+    if project.archived {
+        return Err(AppError::ProjectArchived);
+    }
+    if project.deletion_scheduled_at.is_some() {
+        return Err(AppError::ProjectPendingDeletion);
+    }
+    Ok(())
+}
+
+/// Kind of access being checked for a batch of files
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    Read,
+    Upload,
+    Write,
+}
+
+pub fn is_allowed(
+    permission: Permission,
+    project: &Project,
+    folder: Option<&Folder>,
+    credentials: &Credentials,
+) -> bool {
+    match permission {
+        Permission::Read => can_read(project, folder, credentials),
+        Permission::Upload => can_upload(project, credentials),
+        Permission::Write => can_write(project, credentials),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{FolderVisibility, UserRole};
+    use chrono::{Duration, Utc};
+    use sqlx::types::Json;
+    use std::collections::BTreeMap;
+
+    const PROJECT: Uuid = Uuid::from_u128(1);
+    const OTHER_PROJECT: Uuid = Uuid::from_u128(2);
+    const OWNER: Uuid = Uuid::from_u128(10);
+    const MEMBER: Uuid = Uuid::from_u128(11);
+    const ADMIN: Uuid = Uuid::from_u128(12);
+    const API_KEY: Uuid = Uuid::from_u128(20);
+    const PREVIOUS_API_KEY: Uuid = Uuid::from_u128(21);
+    const WRONG_API_KEY: Uuid = Uuid::from_u128(22);
+
+    #[derive(Debug, Clone, Copy)]
+    enum Caller {
+        Owner,
+        Member(ProjectRole),
+        /// Admin of another project only
+        NonMember,
+        ApiKey,
+        PreviousKeyInGrace,
+        PreviousKeyExpired,
+        WrongKey,
+        AdminWithOverride,
+        AdminWithoutOverride,
+        Anonymous,
+    }
+
+    /// Expected access on a private project and private folder
+    struct Expected {
+        read: bool,
+        list: bool,
+        upload: bool,
+        write: bool,
+    }
+
+    const fn expect(read: bool, list: bool, upload: bool, write: bool) -> Expected {
+        Expected {
+            read,
+            list,
+            upload,
+            write,
+        }
+    }
+
+    const CASES: &[(Caller, Expected)] = &[
+        (Caller::Owner, expect(true, true, true, true)),
+        (
+            Caller::Member(ProjectRole::Admin),
+            expect(true, true, true, true),
+        ),
+        (
+            Caller::Member(ProjectRole::Uploader),
+            expect(true, true, true, false),
+        ),
+        (
+            Caller::Member(ProjectRole::Viewer),
+            expect(true, true, false, false),
+        ),
+        (Caller::NonMember, expect(false, false, false, false)),
+        (Caller::ApiKey, expect(true, true, true, true)),
+        (Caller::PreviousKeyInGrace, expect(true, true, true, true)),
+        (
+            Caller::PreviousKeyExpired,
+            expect(false, false, false, false),
+        ),
+        (Caller::WrongKey, expect(false, false, false, false)),
+        (Caller::AdminWithOverride, expect(true, true, true, true)),
+        (
+            Caller::AdminWithoutOverride,
+            expect(false, false, false, false),
+        ),
+        (Caller::Anonymous, expect(false, false, false, false)),
+    ];
+
+    fn project(is_public: bool) -> Project {
+        Project {
+            id: PROJECT,
+            user_id: OWNER,
+            name: "test".to_string(),
+            api_key: API_KEY,
+            is_public,
+            created_at: Utc::now(),
+            allowed_origins: Vec::new(),
+            geo_allowed_countries: Vec::new(),
+            geo_blocked_countries: Vec::new(),
+            download_bandwidth_limit: None,
+            max_concurrent_uploads: None,
+            previous_api_key: Some(PREVIOUS_API_KEY),
+            previous_api_key_expires_at: Some(Utc::now() + Duration::hours(1)),
+            slug: None,
+            custom_domain: None,
+            cold_storage_after_days: None,
+            storage_quota_bytes: None,
+            archived: false,
+            deletion_scheduled_at: None,
+            default_folder_visibility: FolderVisibility::Inherit,
+            image_max_dimension: None,
+            image_quality: 85,
+            image_png_to_webp: false,
+            image_keep_original: false,
+            response_headers: Json(BTreeMap::new()),
+            noindex: false,
+        }
+    }
+
+    fn folder(is_public: bool) -> Folder {
+        Folder {
+            id: Uuid::from_u128(30),
+            project_id: PROJECT,
+            path: "docs".to_string(),
+            is_public,
+            created_at: Utc::now(),
+        }
+    }
+
+    fn user(id: Uuid, role: UserRole) -> AuthUser {
+        AuthUser {
+            id,
+            email: format!("{id}@example.com"),
+            role,
+        }
+    }
+
+    /// Credentials as handlers build them, adjusting `project` where the case
+    /// depends on its key rotation
+    fn credentials(caller: Caller, project: &mut Project) -> Credentials {
+        match caller {
+            Caller::Owner => Credentials::User(user(OWNER, UserRole::User), HashMap::new()),
+            Caller::Member(role) => Credentials::User(
+                user(MEMBER, UserRole::User),
+                HashMap::from([(PROJECT, role)]),
+            ),
+            Caller::NonMember => Credentials::User(
+                user(MEMBER, UserRole::User),
+                HashMap::from([(OTHER_PROJECT, ProjectRole::Admin)]),
+            ),
+            Caller::ApiKey => Credentials::ApiKey(API_KEY),
+            Caller::PreviousKeyInGrace => Credentials::ApiKey(PREVIOUS_API_KEY),
+            Caller::PreviousKeyExpired => {
+                project.previous_api_key_expires_at = Some(Utc::now() - Duration::minutes(1));
+                Credentials::ApiKey(PREVIOUS_API_KEY)
+            }
+            Caller::WrongKey => Credentials::ApiKey(WRONG_API_KEY),
+            Caller::AdminWithOverride | Caller::AdminWithoutOverride => {
+                let admin = user(ADMIN, UserRole::Admin);
+                let query = AdminQuery {
+                    as_admin: Some(matches!(caller, Caller::AdminWithOverride)),
+                };
+                let overridden = admin_override(
+                    &admin,
+                    &query,
+                    RolePermission::AdminFilesWrite,
+                    "test",
+                    PROJECT,
+                )
+                .unwrap();
+                if overridden {
+                    Credentials::Admin
+                } else {
+                    Credentials::User(admin, HashMap::new())
+                }
+            }
+            Caller::Anonymous => Credentials::Anonymous,
+        }
+    }
+
+    #[test]
+    fn access_table() {
+        for (caller, expected) in CASES {
+            for project_public in [false, true] {
+                for folder_public in [None, Some(false), Some(true)] {
+                    let mut project = project(project_public);
+                    let credentials = credentials(*caller, &mut project);
+                    let folder = folder_public.map(folder);
+                    let case = format!(
+                        "{caller:?}, public project: {project_public}, public folder: {folder_public:?}"
+                    );
+                    let open = project_public || folder_public == Some(true);
+
+                    assert_eq!(
+                        can_read(&project, folder.as_ref(), &credentials),
+                        open || expected.read,
+                        "can_read: {case}"
+                    );
+                    // Publicity opens downloads only
+                    assert_eq!(
+                        can_list(&project, &credentials),
+                        expected.list,
+                        "can_list: {case}"
+                    );
+                    assert_eq!(
+                        can_upload(&project, &credentials),
+                        expected.upload,
+                        "can_upload: {case}"
+                    );
+                    assert_eq!(
+                        can_write(&project, &credentials),
+                        expected.write,
+                        "can_write: {case}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn override_needs_admin_role() {
+        let query = AdminQuery {
+            as_admin: Some(true),
+        };
+        let result = admin_override(
+            &user(MEMBER, UserRole::User),
+            &query,
+            RolePermission::AdminFilesWrite,
+            "test",
+            PROJECT,
+        );
+        assert!(matches!(result, Err(AppError::Forbidden(_))));
+    }
+}
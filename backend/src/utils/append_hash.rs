@@ -0,0 +1,106 @@
+use sha2::{compress256, digest::generic_array::GenericArray};
+
+/// SHA-256 block size; the saved state covers whole blocks only
+pub const SHA256_BLOCK_SIZE: u64 = 64;
+
+/// Length of a saved state: eight big-endian words
+const STATE_LEN: usize = 32;
+
+const INITIAL_STATE: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+/// SHA-256 of content that keeps growing. The state after the content's
+/// whole 64-byte blocks can be saved and resumed later with just the bytes
+/// past the last whole block, so appending only hashes the new bytes.
+#[derive(Clone)]
+pub struct AppendHasher {
+    state: [u32; 8],
+    /// Bytes folded into `state` (a multiple of the block size)
+    hashed: u64,
+    /// Bytes past the last whole block
+    tail: Vec<u8>,
+}
+
+impl Default for AppendHasher {
+    fn default() -> Self {
+        AppendHasher {
+            state: INITIAL_STATE,
+            hashed: 0,
+            tail: Vec::new(),
+        }
+    }
+}
+
+impl AppendHasher {
+    /// Pick up from a saved state covering the first `hashed` bytes; `tail`
+    /// is the content after them. `None` if the state isn't one we saved.
+    pub fn resume(saved: &[u8], hashed: u64, tail: &[u8]) -> Option<Self> {
+        if saved.len() != STATE_LEN || !hashed.is_multiple_of(SHA256_BLOCK_SIZE) {
+            return None;
+        }
+        let mut state = [0u32; 8];
+        for (word, bytes) in state.iter_mut().zip(saved.chunks_exact(4)) {
+            *word = u32::from_be_bytes(bytes.try_into().expect("chunks are 4 bytes"));
+        }
+        let mut hasher = AppendHasher {
+            state,
+            hashed,
+            tail: Vec::new(),
+        };
+        hasher.update(tail);
+        Some(hasher)
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        if !self.tail.is_empty() {
+            let needed = SHA256_BLOCK_SIZE as usize - self.tail.len();
+            let taken = needed.min(data.len());
+            self.tail.extend_from_slice(&data[..taken]);
+            data = &data[taken..];
+            if self.tail.len() < SHA256_BLOCK_SIZE as usize {
+                return;
+            }
+            let block = std::mem::take(&mut self.tail);
+            self.compress(&block);
+        }
+        let whole = data.len() - data.len() % SHA256_BLOCK_SIZE as usize;
+        self.compress(&data[..whole]);
+        self.tail.extend_from_slice(&data[whole..]);
+    }
+
+    fn compress(&mut self, blocks: &[u8]) {
+        for block in blocks.chunks_exact(SHA256_BLOCK_SIZE as usize) {
+            compress256(&mut self.state, &[*GenericArray::from_slice(block)]);
+        }
+        self.hashed += blocks.len() as u64;
+    }
+
+    /// State after the whole blocks hashed so far, to save with the content
+    pub fn saved_state(&self) -> Vec<u8> {
+        self.state
+            .iter()
+            .flat_map(|word| word.to_be_bytes())
+            .collect()
+    }
+
+    /// Digest of everything hashed so far; the hasher can keep going
+    pub fn digest(&self) -> [u8; 32] {
+        let total_bits = (self.hashed + self.tail.len() as u64) * 8;
+        let mut padded = self.tail.clone();
+        padded.push(0x80);
+        while padded.len() % SHA256_BLOCK_SIZE as usize != 56 {
+            padded.push(0);
+        }
+        padded.extend_from_slice(&total_bits.to_be_bytes());
+
+        let mut finished = self.clone();
+        finished.tail.clear();
+        finished.compress(&padded);
+        let mut digest = [0u8; 32];
+        for (bytes, word) in digest.chunks_exact_mut(4).zip(finished.state) {
+            bytes.copy_from_slice(&word.to_be_bytes());
+        }
+        digest
+    }
+}
@@ -0,0 +1,325 @@
+use sha2::{Digest, Sha256};
+use std::{
+    fs,
+    io::{self, Read, Write},
+    path::{Component, Path, PathBuf},
+};
+use uuid::Uuid;
+
+use super::{compression::ZSTD_ENCODING, filename::sanitize_file_name, path::validate_folder_path};
+
+/// Archive formats supported for server-side extraction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+impl ArchiveKind {
+    /// Detect the archive format from the uploaded file name
+    pub fn from_file_name(name: &str) -> Option<Self> {
+        let lower = name.to_lowercase();
+        if lower.ends_with(".zip") {
+            Some(ArchiveKind::Zip)
+        } else if lower.ends_with(".tar.gz") || lower.ends_with(".tgz") {
+            Some(ArchiveKind::TarGz)
+        } else if lower.ends_with(".tar") {
+            Some(ArchiveKind::Tar)
+        } else {
+            None
+        }
+    }
+}
+
+/// Safety limits applied while unpacking
+#[derive(Debug, Clone, Copy)]
+pub struct ExtractLimits {
+    pub max_entries: usize,
+    pub max_total_size: u64,
+    pub max_entry_size: u64,
+}
+
+/// A file written to storage from an archive entry
+#[derive(Debug)]
+pub struct ExtractedEntry {
+    pub file_id: Uuid,
+    /// Folder path relative to the project root, if any
+    pub folder_path: Option<String>,
+    pub original_name: String,
+    pub stored_name: String,
+    pub file_path: PathBuf,
+    pub size: i64,
+    pub content_hash: String,
+}
+
+fn invalid(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+/// Split an archive entry path into folder segments and a file name.
+/// Rejects absolute paths and `..` (zip-slip); returns None for entries to skip
+/// (hidden files and OS metadata like `__MACOSX`).
+fn split_entry_path(path: &Path) -> io::Result<Option<(Vec<String>, String)>> {
+    let mut parts = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => {
+                let part = part
+                    .to_str()
+                    .ok_or_else(|| invalid("Archive entry name is not valid UTF-8"))?;
+                if part.starts_with('.') || part == "__MACOSX" {
+                    return Ok(None);
+                }
+                parts.push(part.to_string());
+            }
+            Component::CurDir => {}
+            _ => return Err(invalid("Archive entry escapes the target folder")),
+        }
+    }
+
+    let Some(file_name) = parts.pop() else {
+        return Ok(None);
+    };
+    Ok(Some((parts, file_name)))
+}
+
+struct Extractor<'a> {
+    project_root: &'a Path,
+    base_folder: Option<&'a str>,
+    limits: ExtractLimits,
+    total_size: u64,
+    entries: Vec<ExtractedEntry>,
+}
+
+impl Extractor<'_> {
+    fn add_entry(&mut self, entry_path: &Path, reader: &mut dyn Read) -> io::Result<()> {
+        let Some((folders, original_name)) = split_entry_path(entry_path)? else {
+            return Ok(());
+        };
+        let original_name = sanitize_file_name(&original_name)
+            .map_err(|e| invalid(format!("Archive entry {}: {e}", entry_path.display())))?;
+
+        if self.entries.len() >= self.limits.max_entries {
+            return Err(invalid(format!(
+                "Archive contains more than {} files",
+                self.limits.max_entries
+            )));
+        }
+
+        let folder_path = self
+            .base_folder
+            .map(str::to_string)
+            .into_iter()
+            .chain(folders)
+            .collect::<Vec<_>>()
+            .join("/");
+        // Checked like any other folder path, so entries can't create
+        // folders the API wouldn't
+        let folder_path = if folder_path.is_empty() {
+            None
+        } else {
+            let path = validate_folder_path(&folder_path)
+                .map_err(|e| invalid(format!("Archive entry {}: {e}", entry_path.display())))?;
+            Some(path)
+        };
+
+        let mut dir = self.project_root.to_path_buf();
+        if let Some(ref path) = folder_path {
+            for segment in path.split('/') {
+                dir.push(segment);
+            }
+        }
+        fs::create_dir_all(&dir)?;
+
+        let file_id = Uuid::new_v4();
+        let stored_name = match Path::new(&original_name)
+            .extension()
+            .and_then(|e| e.to_str())
+        {
+            Some(ext) if !ext.is_empty() => format!("{file_id}.{ext}"),
+            _ => file_id.to_string(),
+        };
+        let file_path = dir.join(&stored_name);
+
+        // Copy with a hard cap so entries lying about their size can't exhaust the disk
+        let remaining = self.limits.max_total_size - self.total_size;
+        let cap = remaining.min(self.limits.max_entry_size);
+        let mut limited = reader.take(cap + 1);
+        let mut output = fs::File::create(&file_path)?;
+        let mut hasher = Sha256::new();
+        let mut buf = [0u8; 64 * 1024];
+        let mut size: u64 = 0;
+        loop {
+            let n = limited.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            size += n as u64;
+            if size > cap {
+                drop(output);
+                let _ = fs::remove_file(&file_path);
+                return Err(invalid("Archive exceeds the extraction size limit"));
+            }
+            hasher.update(&buf[..n]);
+            output.write_all(&buf[..n])?;
+        }
+
+        self.total_size += size;
+        self.entries.push(ExtractedEntry {
+            file_id,
+            folder_path,
+            original_name,
+            stored_name,
+            file_path,
+            size: size as i64,
+            content_hash: hex::encode(hasher.finalize()),
+        });
+        Ok(())
+    }
+
+    fn extract_zip(&mut self, data: &[u8]) -> io::Result<()> {
+        let mut archive = zip::ZipArchive::new(io::Cursor::new(data)).map_err(io::Error::other)?;
+        for index in 0..archive.len() {
+            let mut entry = archive.by_index(index).map_err(io::Error::other)?;
+            if entry.is_dir() {
+                continue;
+            }
+            let path = entry
+                .enclosed_name()
+                .ok_or_else(|| invalid("Archive entry escapes the target folder"))?;
+            self.add_entry(&path, &mut entry)?;
+        }
+        Ok(())
+    }
+
+    fn extract_tar<R: Read>(&mut self, reader: R) -> io::Result<()> {
+        let mut archive = tar::Archive::new(reader);
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            // Only regular files; links and devices are ignored
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            let path = entry.path()?.into_owned();
+            self.add_entry(&path, &mut entry)?;
+        }
+        Ok(())
+    }
+
+    /// Remove everything written so far (used when extraction fails part-way)
+    fn cleanup(&self) {
+        for entry in &self.entries {
+            let _ = fs::remove_file(&entry.file_path);
+        }
+    }
+}
+
+/// Unpack an archive into `project_root/<base_folder>/...`, writing each entry as a
+/// stored file. Blocking; run inside `spawn_blocking`. On error nothing is left on disk.
+pub fn extract_archive(
+    data: &[u8],
+    kind: ArchiveKind,
+    project_root: &Path,
+    base_folder: Option<&str>,
+    limits: ExtractLimits,
+) -> io::Result<Vec<ExtractedEntry>> {
+    let mut extractor = Extractor {
+        project_root,
+        base_folder,
+        limits,
+        total_size: 0,
+        entries: Vec::new(),
+    };
+
+    let result = match kind {
+        ArchiveKind::Zip => extractor.extract_zip(data),
+        ArchiveKind::Tar => extractor.extract_tar(data),
+        ArchiveKind::TarGz => extractor.extract_tar(flate2::read::GzDecoder::new(data)),
+    };
+
+    match result {
+        Ok(()) => Ok(extractor.entries),
+        Err(e) => {
+            extractor.cleanup();
+            Err(e)
+        }
+    }
+}
+
+/// Turn `(file path, original name, folder path, storage encoding)` rows into
+/// `write_zip_stream` entries. Archive paths mirror folders; same-name files get
+/// a " (n)" suffix.
+pub fn archive_entries(
+    rows: Vec<(String, String, Option<String>, Option<String>)>,
+) -> Vec<(String, PathBuf, bool)> {
+    let mut used_names = std::collections::HashSet::new();
+    let mut entries = Vec::with_capacity(rows.len());
+    for (file_path, original_name, folder_path, storage_encoding) in rows {
+        let base = match folder_path {
+            Some(folder) => format!("{folder}/{original_name}"),
+            None => original_name,
+        };
+        let mut name = base.clone();
+        let mut n = 1;
+        while !used_names.insert(name.clone()) {
+            name = match base.rsplit_once('.') {
+                Some((stem, ext)) if !stem.ends_with('/') => format!("{stem} ({n}).{ext}"),
+                _ => format!("{base} ({n})"),
+            };
+            n += 1;
+        }
+        let compressed = storage_encoding.as_deref() == Some(ZSTD_ENCODING);
+        entries.push((name, PathBuf::from(file_path), compressed));
+    }
+    entries
+}
+
+/// Write a ZIP of the given files to `writer` without buffering the archive.
+/// Entries are `(path inside the archive, path on disk, zstd-compressed at rest)`; content
+/// is stored uncompressed since most uploads (images, video, archives) are already compressed.
+pub async fn write_zip_stream<W>(entries: Vec<(String, PathBuf, bool)>, writer: W) -> io::Result<()>
+where
+    W: tokio::io::AsyncWrite + Unpin,
+{
+    use async_zip::{tokio::write::ZipFileWriter, Compression, ZipEntryBuilder};
+    use futures::AsyncWriteExt;
+    use tokio::io::AsyncReadExt;
+
+    let mut zip = ZipFileWriter::with_tokio(writer);
+    let mut buf = vec![0u8; 64 * 1024];
+
+    for (name, disk_path, compressed) in entries {
+        let entry = ZipEntryBuilder::new(name.into(), Compression::Stored);
+
+        // Compressed blobs are text-like and small enough to inflate in memory
+        if compressed {
+            let stored = tokio::fs::read(&disk_path).await?;
+            let data = tokio::task::spawn_blocking(move || zstd::decode_all(stored.as_slice()))
+                .await
+                .map_err(io::Error::other)??;
+            zip.write_entry_whole(entry, &data)
+                .await
+                .map_err(io::Error::other)?;
+            continue;
+        }
+
+        let mut source = tokio::fs::File::open(&disk_path).await?;
+        let mut entry_writer = zip
+            .write_entry_stream(entry)
+            .await
+            .map_err(io::Error::other)?;
+
+        loop {
+            let n = source.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            entry_writer.write_all(&buf[..n]).await?;
+        }
+        entry_writer.close().await.map_err(io::Error::other)?;
+    }
+
+    zip.close().await.map_err(io::Error::other)?;
+    Ok(())
+}
@@ -0,0 +1,324 @@
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use object_store::{buffered::BufWriter, path::Path as ObjectPath, ObjectStore};
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use std::{io, path::PathBuf, sync::Arc};
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+use crate::{
+    error::{AppError, Result},
+    models::{Backup, File},
+};
+
+use super::cold_storage::open_object_store;
+
+pub const BACKUP_COLUMNS: &str = "id, status, trigger, started_by, wal_lsn, manifest_path, file_count, total_bytes, blobs_copied, blobs_bytes, blobs_failed, last_error, started_at, finished_at";
+
+/// Blobs copied per progress update
+const BACKUP_BATCH_SIZE: i64 = 200;
+
+/// Destination for manifests and incremental blob copies
+#[derive(Debug)]
+pub struct BackupTarget {
+    store: Arc<dyn ObjectStore>,
+}
+
+impl BackupTarget {
+    /// Same URL forms as `COLD_STORAGE_URL`: `s3://bucket[/prefix]` or a directory
+    pub fn open(url: &str) -> std::result::Result<Self, object_store::Error> {
+        Ok(Self {
+            store: open_object_store(url)?,
+        })
+    }
+
+    /// Stream a local file to `key`, returning the bytes written
+    async fn put_file(&self, key: &str, path: &std::path::Path) -> io::Result<u64> {
+        let mut source = tokio::fs::File::open(path).await?;
+        let mut writer = BufWriter::new(Arc::clone(&self.store), ObjectPath::from(key));
+        let size = tokio::io::copy(&mut source, &mut writer).await?;
+        writer.shutdown().await?;
+        Ok(size)
+    }
+}
+
+/// First line of a manifest
+#[derive(Serialize)]
+struct ManifestHeader {
+    backup_id: Uuid,
+    created_at: DateTime<Utc>,
+    wal_lsn: Option<String>,
+}
+
+/// One line per file in a manifest
+#[derive(Serialize, FromRow)]
+struct ManifestEntry {
+    id: Uuid,
+    project_id: Uuid,
+    folder_path: Option<String>,
+    original_name: String,
+    file_path: String,
+    size: i64,
+    stored_size: Option<i64>,
+    mime_type: String,
+    content_hash: Option<String>,
+    storage_encoding: Option<String>,
+    storage_tier: String,
+    upload_date: DateTime<Utc>,
+}
+
+/// Record a new backup run; only one may run at a time
+pub async fn begin_backup(
+    pool: &PgPool,
+    trigger: &str,
+    started_by: Option<Uuid>,
+) -> Result<Backup> {
+    let active: Option<Uuid> =
+        sqlx::query_scalar("SELECT id FROM backups WHERE status = 'running' LIMIT 1")
+            .fetch_optional(pool)
+            .await?;
+    if let Some(active) = active {
+        return Err(AppError::Conflict(format!(
+            "Backup {active} is already in progress"
+        )));
+    }
+
+    Ok(sqlx::query_as::<_, Backup>(&format!(
+        "INSERT INTO backups (trigger, started_by) VALUES ($1, $2) RETURNING {BACKUP_COLUMNS}"
+    ))
+    .bind(trigger)
+    .bind(started_by)
+    .fetch_one(pool)
+    .await?)
+}
+
+/// Write the file manifest from a single repeatable-read snapshot so it matches
+/// the database state at the recorded WAL position
+async fn write_manifest(
+    pool: &PgPool,
+    backup_dir: &str,
+    backup_id: Uuid,
+) -> Result<(PathBuf, Option<String>, i64, i64)> {
+    let created_at = Utc::now();
+    let dir = PathBuf::from(backup_dir);
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .map_err(|e| AppError::FileError(format!("Failed to create backup directory: {e}")))?;
+    let path = dir.join(format!(
+        "manifest-{}-{}.ndjson",
+        created_at.format("%Y%m%dT%H%M%SZ"),
+        backup_id.simple()
+    ));
+
+    let mut tx = pool.begin().await?;
+    sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+        .execute(&mut *tx)
+        .await?;
+    let wal_lsn: Option<String> = sqlx::query_scalar(
+        "SELECT (CASE WHEN pg_is_in_recovery() THEN pg_last_wal_replay_lsn() ELSE pg_current_wal_lsn() END)::text",
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let write_err = |e: io::Error| AppError::FileError(format!("Failed to write manifest: {e}"));
+    let mut output =
+        tokio::io::BufWriter::new(tokio::fs::File::create(&path).await.map_err(write_err)?);
+    let header = ManifestHeader {
+        backup_id,
+        created_at,
+        wal_lsn: wal_lsn.clone(),
+    };
+    let mut line =
+        serde_json::to_vec(&header).map_err(|e| AppError::InternalError(e.to_string()))?;
+    line.push(b'\n');
+    output.write_all(&line).await.map_err(write_err)?;
+
+    let (mut file_count, mut total_bytes) = (0i64, 0i64);
+    {
+        let mut rows = sqlx::query_as::<_, ManifestEntry>(
+            r#"
+            SELECT f.id, f.project_id, fol.path AS folder_path, f.original_name, f.file_path,
+                   f.size, f.stored_size, f.mime_type, f.content_hash, f.storage_encoding,
+                   f.storage_tier, f.upload_date
+            FROM files f
+            LEFT JOIN folders fol ON fol.id = f.folder_id
+            ORDER BY f.id
+            "#,
+        )
+        .fetch(&mut *tx);
+
+        while let Some(entry) = rows.try_next().await? {
+            file_count += 1;
+            total_bytes += entry.size;
+            let mut line =
+                serde_json::to_vec(&entry).map_err(|e| AppError::InternalError(e.to_string()))?;
+            line.push(b'\n');
+            output.write_all(&line).await.map_err(write_err)?;
+        }
+    }
+    output.flush().await.map_err(write_err)?;
+    tx.commit().await?;
+
+    Ok((path, wal_lsn, file_count, total_bytes))
+}
+
+/// Copy local blobs that are new or changed since the last backup
+async fn copy_changed_blobs(pool: &PgPool, target: &BackupTarget, backup_id: Uuid) -> Result<()> {
+    let mut after = Uuid::nil();
+    loop {
+        let batch = sqlx::query_as::<_, File>(
+            r#"
+            SELECT f.id, f.project_id, f.folder_id, f.original_name, f.stored_name, f.file_path, f.size, f.mime_type, f.upload_date, f.storage_encoding, f.precompressed_encodings, f.storage_tier, f.moderation_status
+            FROM files f
+            LEFT JOIN backup_blobs b ON b.file_id = f.id
+            WHERE f.storage_tier = 'hot' AND f.id > $1
+              AND (b.file_id IS NULL OR b.upload_date <> f.upload_date)
+            ORDER BY f.id
+            LIMIT $2
+            "#,
+        )
+        .bind(after)
+        .bind(BACKUP_BATCH_SIZE)
+        .fetch_all(pool)
+        .await?;
+        let Some(last) = batch.last() else {
+            break;
+        };
+        after = last.id;
+
+        let (mut copied, mut bytes, mut failed) = (0i64, 0i64, 0i64);
+        let mut last_error = None;
+        for file in &batch {
+            let key = format!("blobs/{}/{}", file.project_id, file.id);
+            match target
+                .put_file(&key, std::path::Path::new(&file.file_path))
+                .await
+            {
+                Ok(size) => {
+                    sqlx::query(
+                        r#"
+                        INSERT INTO backup_blobs (file_id, upload_date)
+                        VALUES ($1, $2)
+                        ON CONFLICT (file_id)
+                        DO UPDATE SET upload_date = EXCLUDED.upload_date, backed_up_at = NOW()
+                        "#,
+                    )
+                    .bind(file.id)
+                    .bind(file.upload_date)
+                    .execute(pool)
+                    .await?;
+                    copied += 1;
+                    bytes += size as i64;
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to back up file {}: {}", file.id, e);
+                    failed += 1;
+                    last_error = Some(format!("File {}: {e}", file.id));
+                }
+            }
+        }
+
+        sqlx::query(
+            r#"
+            UPDATE backups
+            SET blobs_copied = blobs_copied + $1, blobs_bytes = blobs_bytes + $2,
+                blobs_failed = blobs_failed + $3, last_error = COALESCE($4, last_error)
+            WHERE id = $5
+            "#,
+        )
+        .bind(copied)
+        .bind(bytes)
+        .bind(failed)
+        .bind(&last_error)
+        .bind(backup_id)
+        .execute(pool)
+        .await?;
+    }
+    Ok(())
+}
+
+async fn perform_backup(
+    pool: &PgPool,
+    backup_dir: &str,
+    target: Option<&BackupTarget>,
+    backup_id: Uuid,
+) -> Result<()> {
+    let (manifest, wal_lsn, file_count, total_bytes) =
+        write_manifest(pool, backup_dir, backup_id).await?;
+
+    if let Some(target) = target {
+        let name = manifest
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default();
+        target
+            .put_file(&format!("manifests/{name}"), &manifest)
+            .await
+            .map_err(|e| AppError::FileError(format!("Failed to upload manifest: {e}")))?;
+    }
+
+    sqlx::query(
+        r#"
+        UPDATE backups
+        SET wal_lsn = $1, manifest_path = $2, file_count = $3, total_bytes = $4
+        WHERE id = $5
+        "#,
+    )
+    .bind(&wal_lsn)
+    .bind(manifest.to_string_lossy().as_ref())
+    .bind(file_count)
+    .bind(total_bytes)
+    .bind(backup_id)
+    .execute(pool)
+    .await?;
+
+    if let Some(target) = target {
+        copy_changed_blobs(pool, target, backup_id).await?;
+    }
+
+    sqlx::query("UPDATE backups SET status = 'completed', finished_at = NOW() WHERE id = $1")
+        .bind(backup_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Run a backup started with `begin_backup`, recording failure on the backup row
+pub async fn run_backup(
+    pool: PgPool,
+    backup_dir: String,
+    target: Option<Arc<BackupTarget>>,
+    backup_id: Uuid,
+) {
+    let Err(e) = perform_backup(&pool, &backup_dir, target.as_deref(), backup_id).await else {
+        tracing::info!("Backup {} completed", backup_id);
+        return;
+    };
+
+    tracing::warn!("Backup {} failed: {}", backup_id, e);
+    let result = sqlx::query(
+        "UPDATE backups SET status = 'failed', last_error = $1, finished_at = NOW() WHERE id = $2",
+    )
+    .bind(e.to_string())
+    .bind(backup_id)
+    .execute(&pool)
+    .await;
+    if let Err(e) = result {
+        tracing::warn!("Failed to record backup failure: {}", e);
+    }
+}
+
+/// Mark backups left running by a previous process as failed (called at startup)
+pub async fn fail_interrupted_backups(pool: &PgPool) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE backups
+        SET status = 'failed', last_error = 'Interrupted by server restart', finished_at = NOW()
+        WHERE status = 'running'
+        "#,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,220 @@
+use chrono::{Duration, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{collections::HashMap, sync::Mutex};
+use uuid::Uuid;
+
+use crate::{
+    config::Config,
+    error::{AppError, Result},
+};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Proof-of-work challenges are valid for this many minutes after issue
+const POW_CHALLENGE_EXPIRY_MINUTES: i64 = 5;
+
+const HCAPTCHA_VERIFY_URL: &str = "https://api.hcaptcha.com/siteverify";
+const TURNSTILE_VERIFY_URL: &str = "https://challenges.cloudflare.com/turnstile/v0/siteverify";
+
+/// Bot protection provider for public auth endpoints
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum CaptchaProvider {
+    None,
+    HCaptcha,
+    Turnstile,
+    ProofOfWork,
+}
+
+impl std::str::FromStr for CaptchaProvider {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "" | "none" => Ok(CaptchaProvider::None),
+            "hcaptcha" => Ok(CaptchaProvider::HCaptcha),
+            "turnstile" => Ok(CaptchaProvider::Turnstile),
+            "pow" => Ok(CaptchaProvider::ProofOfWork),
+            other => Err(format!("Unknown CAPTCHA_PROVIDER: {other}")),
+        }
+    }
+}
+
+/// Proof-of-work challenge handed out to clients before login/register
+#[derive(Debug, Serialize)]
+pub struct PowChallenge {
+    pub challenge: String,
+    pub difficulty: u32,
+    pub expires_at: i64,
+}
+
+/// Remembers the salts of solved proof-of-work challenges until they expire
+/// so one solution can't be replayed
+#[derive(Debug, Default)]
+pub struct PowReplayCache {
+    used: Mutex<HashMap<String, i64>>,
+}
+
+impl PowReplayCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a challenge salt; returns false if it was already used
+    pub fn insert(&self, salt: &str, expires_at: i64) -> bool {
+        let now = Utc::now().timestamp();
+        let mut used = self.used.lock().unwrap();
+        used.retain(|_, expiry| *expiry >= now);
+        used.insert(salt.to_string(), expires_at).is_none()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SiteVerifyResponse {
+    success: bool,
+    #[serde(rename = "error-codes", default)]
+    error_codes: Vec<String>,
+}
+
+fn challenge_mac(payload: &str, secret: &str) -> HmacSha256 {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(payload.as_bytes());
+    mac
+}
+
+fn sign_challenge(payload: &str, secret: &str) -> String {
+    hex::encode(challenge_mac(payload, secret).finalize().into_bytes())
+}
+
+/// Issue a signed, stateless proof-of-work challenge.
+/// Format: `<expires_at>.<salt>.<signature>`
+pub fn create_pow_challenge(secret: &str, difficulty: u32) -> PowChallenge {
+    let expires_at = (Utc::now() + Duration::minutes(POW_CHALLENGE_EXPIRY_MINUTES)).timestamp();
+    let payload = format!("{expires_at}.{}", Uuid::new_v4().simple());
+    let signature = sign_challenge(&payload, secret);
+
+    PowChallenge {
+        challenge: format!("{payload}.{signature}"),
+        difficulty,
+        expires_at,
+    }
+}
+
+fn leading_zero_bits(digest: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in digest {
+        if *byte == 0 {
+            bits += 8;
+        } else {
+            bits += byte.leading_zeros();
+            break;
+        }
+    }
+    bits
+}
+
+/// Verify a proof-of-work solution of the form `<challenge>:<nonce>`.
+/// SHA-256 of the whole string must have at least `difficulty` leading zero bits.
+/// Each challenge can be solved once; its salt is recorded in `used_challenges`.
+fn verify_pow(
+    solution: &str,
+    secret: &str,
+    difficulty: u32,
+    used_challenges: &PowReplayCache,
+) -> Result<()> {
+    let (challenge, _nonce) = solution.rsplit_once(':').ok_or(AppError::CaptchaFailed)?;
+
+    let (payload, signature) = challenge.rsplit_once('.').ok_or(AppError::CaptchaFailed)?;
+    let signature = hex::decode(signature).map_err(|_| AppError::CaptchaFailed)?;
+    challenge_mac(payload, secret)
+        .verify_slice(&signature)
+        .map_err(|_| AppError::CaptchaFailed)?;
+
+    let (expires_at, salt) = payload.split_once('.').ok_or(AppError::CaptchaFailed)?;
+    let expires_at: i64 = expires_at.parse().map_err(|_| AppError::CaptchaFailed)?;
+    if expires_at < Utc::now().timestamp() {
+        return Err(AppError::CaptchaFailed);
+    }
+
+    let digest = Sha256::digest(solution.as_bytes());
+    if leading_zero_bits(&digest) < difficulty {
+        return Err(AppError::CaptchaFailed);
+    }
+
+    if !used_challenges.insert(salt, expires_at) {
+        return Err(AppError::CaptchaFailed);
+    }
+
+    Ok(())
+}
+
+async fn verify_site_token(
+    url: &str,
+    secret: &str,
+    token: &str,
+    remote_ip: Option<&str>,
+) -> Result<()> {
+    let mut form = vec![("secret", secret), ("response", token)];
+    if let Some(ip) = remote_ip {
+        form.push(("remoteip", ip));
+    }
+
+    let response = reqwest::Client::new()
+        .post(url)
+        .form(&form)
+        .send()
+        .await
+        .map_err(|e| AppError::InternalError(format!("CAPTCHA verification request failed: {e}")))?
+        .json::<SiteVerifyResponse>()
+        .await
+        .map_err(|e| {
+            AppError::InternalError(format!("Invalid CAPTCHA verification response: {e}"))
+        })?;
+
+    if !response.success {
+        tracing::warn!("CAPTCHA rejected: {:?}", response.error_codes);
+        return Err(AppError::CaptchaFailed);
+    }
+
+    Ok(())
+}
+
+/// Verify the CAPTCHA / proof-of-work token submitted with a public auth request.
+/// No-op when `CAPTCHA_PROVIDER` is `none`.
+pub async fn verify_captcha(
+    config: &Config,
+    used_challenges: &PowReplayCache,
+    token: Option<&str>,
+    remote_ip: Option<&str>,
+) -> Result<()> {
+    if config.captcha_provider == CaptchaProvider::None {
+        return Ok(());
+    }
+
+    let token = token
+        .filter(|t| !t.is_empty())
+        .ok_or(AppError::CaptchaFailed)?;
+
+    match config.captcha_provider {
+        CaptchaProvider::None => Ok(()),
+        CaptchaProvider::ProofOfWork => verify_pow(
+            token,
+            &config.jwt_secret,
+            config.captcha_pow_difficulty,
+            used_challenges,
+        ),
+        CaptchaProvider::HCaptcha | CaptchaProvider::Turnstile => {
+            let secret = config.captcha_secret.as_deref().ok_or_else(|| {
+                AppError::InternalError("CAPTCHA_SECRET is not configured".to_string())
+            })?;
+            let url = if config.captcha_provider == CaptchaProvider::HCaptcha {
+                HCAPTCHA_VERIFY_URL
+            } else {
+                TURNSTILE_VERIFY_URL
+            };
+            verify_site_token(url, secret, token, remote_ip).await
+        }
+    }
+}
@@ -0,0 +1,342 @@
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::Deserialize;
+use sqlx::{FromRow, PgConnection, PgPool};
+use std::{collections::HashMap, time::Duration};
+use uuid::Uuid;
+
+use crate::{config::Config, error::Result, models::File};
+
+/// Purge jobs sent per worker pass
+const CDN_PURGE_BATCH_SIZE: i64 = 20;
+
+/// URLs per job; also the most a Cloudflare purge call accepts
+const CDN_PURGE_URLS_PER_JOB: i32 = 30;
+
+/// Failed purges are retried with backoff, then dropped after this many attempts
+const CDN_PURGE_MAX_ATTEMPTS: i32 = 8;
+
+/// Characters left unescaped in a path segment of a purged URL
+const SEGMENT_ESCAPES: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// CDN whose cache public files are purged from when they change
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum CdnProvider {
+    None,
+    /// `purge_cache` API of the zone in `CDN_PURGE_ZONE_ID`
+    Cloudflare,
+    /// `PURGE` request to each URL
+    Fastly,
+    /// `POST {"urls": [...]}` to `CDN_PURGE_WEBHOOK_URL`
+    Webhook,
+}
+
+impl std::str::FromStr for CdnProvider {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "" | "none" => Ok(CdnProvider::None),
+            "cloudflare" => Ok(CdnProvider::Cloudflare),
+            "fastly" => Ok(CdnProvider::Fastly),
+            "webhook" => Ok(CdnProvider::Webhook),
+            other => Err(format!("Unknown CDN_PURGE_PROVIDER: {other}")),
+        }
+    }
+}
+
+/// Where purge requests go and which URLs a public file is cached under
+#[derive(Debug)]
+pub struct CdnPurger {
+    client: reqwest::Client,
+    provider: CdnProvider,
+    token: Option<String>,
+    /// Cloudflare `purge_cache` endpoint or the webhook URL
+    endpoint: Option<String>,
+    public_url: Option<String>,
+    public_files_domain: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CloudflareResponse {
+    success: bool,
+    #[serde(default)]
+    errors: Vec<serde_json::Value>,
+}
+
+impl CdnPurger {
+    /// The purger for the configured provider, or `None` when purging is off
+    pub fn from_config(
+        config: &Config,
+    ) -> std::result::Result<Option<Self>, Box<dyn std::error::Error>> {
+        let endpoint = match config.cdn_purge_provider {
+            CdnProvider::None => return Ok(None),
+            CdnProvider::Cloudflare => {
+                if config.cdn_purge_token.is_none() {
+                    return Err("CDN_PURGE_TOKEN must be set for Cloudflare purges".into());
+                }
+                let zone = config
+                    .cdn_purge_zone_id
+                    .as_deref()
+                    .ok_or("CDN_PURGE_ZONE_ID must be set for Cloudflare purges")?;
+                Some(format!(
+                    "https://api.cloudflare.com/client/v4/zones/{zone}/purge_cache"
+                ))
+            }
+            CdnProvider::Fastly => None,
+            CdnProvider::Webhook => {
+                let url = config
+                    .cdn_purge_webhook_url
+                    .as_deref()
+                    .ok_or("CDN_PURGE_WEBHOOK_URL must be set for webhook purges")?;
+                reqwest::Url::parse(url)?;
+                Some(url.to_string())
+            }
+        };
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()?;
+        Ok(Some(Self {
+            client,
+            provider: config.cdn_purge_provider,
+            token: config.cdn_purge_token.clone(),
+            endpoint,
+            public_url: config.cdn_public_url.clone(),
+            public_files_domain: config.public_files_domain.clone(),
+        }))
+    }
+
+    pub fn provider(&self) -> CdnProvider {
+        self.provider
+    }
+
+    /// URLs a public file can be cached under: its download routes under
+    /// `CDN_PUBLIC_URL`, and its path on the project's slug and custom domain hosts
+    fn file_urls(&self, file: &PublicFile, urls: &mut Vec<String>) {
+        if let Some(ref base) = self.public_url {
+            urls.push(format!("{base}/api/v1/files/{}", file.id));
+            urls.push(format!("{base}/api/files/{}", file.id));
+        }
+
+        let mut path = String::new();
+        for segment in file
+            .folder_path
+            .iter()
+            .flat_map(|p| p.split('/'))
+            .chain([file.original_name.as_str()])
+        {
+            path.push('/');
+            path.extend(utf8_percent_encode(segment, SEGMENT_ESCAPES));
+        }
+        if let (Some(slug), Some(domain)) = (&file.slug, &self.public_files_domain) {
+            urls.push(format!("https://{slug}.{domain}{path}"));
+        }
+        if let Some(ref custom_domain) = file.custom_domain {
+            urls.push(format!("https://{custom_domain}{path}"));
+        }
+    }
+
+    async fn purge(&self, urls: &[String]) -> std::result::Result<(), String> {
+        match self.provider {
+            CdnProvider::None => Ok(()),
+            CdnProvider::Cloudflare => {
+                let endpoint = self.endpoint.as_deref().unwrap_or_default();
+                let response = self
+                    .client
+                    .post(endpoint)
+                    .bearer_auth(self.token.as_deref().unwrap_or_default())
+                    .json(&serde_json::json!({ "files": urls }))
+                    .send()
+                    .await
+                    .map_err(|e| e.to_string())?;
+                let status = response.status();
+                let body: CloudflareResponse = response
+                    .json()
+                    .await
+                    .map_err(|e| format!("Cloudflare returned {status}: {e}"))?;
+                if !status.is_success() || !body.success {
+                    return Err(format!(
+                        "Cloudflare returned {status}: {}",
+                        serde_json::Value::from(body.errors)
+                    ));
+                }
+                Ok(())
+            }
+            CdnProvider::Fastly => {
+                let method = reqwest::Method::from_bytes(b"PURGE").map_err(|e| e.to_string())?;
+                for url in urls {
+                    let mut request = self.client.request(method.clone(), url);
+                    if let Some(ref token) = self.token {
+                        request = request.header("Fastly-Key", token);
+                    }
+                    let response = request.send().await.map_err(|e| e.to_string())?;
+                    // Purging a URL that was never cached is fine
+                    if !response.status().is_success()
+                        && response.status() != reqwest::StatusCode::NOT_FOUND
+                    {
+                        return Err(format!("Fastly returned {} for {url}", response.status()));
+                    }
+                }
+                Ok(())
+            }
+            CdnProvider::Webhook => {
+                let endpoint = self.endpoint.as_deref().unwrap_or_default();
+                let mut request = self
+                    .client
+                    .post(endpoint)
+                    .json(&serde_json::json!({ "urls": urls }));
+                if let Some(ref token) = self.token {
+                    request = request.bearer_auth(token);
+                }
+                let response = request.send().await.map_err(|e| e.to_string())?;
+                if !response.status().is_success() {
+                    return Err(format!("Webhook returned {}", response.status()));
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(FromRow)]
+struct PublicFile {
+    id: Uuid,
+    original_name: String,
+    folder_path: Option<String>,
+    slug: Option<String>,
+    custom_domain: Option<String>,
+}
+
+/// Queue a CDN purge for the public ones among `files`, as they were before
+/// being overwritten, moved or deleted. Call inside the transaction making the
+/// change, before their folder is deleted, so the purge only happens if it commits.
+pub async fn queue_cdn_purge<'a>(
+    conn: &mut PgConnection,
+    cdn: Option<&CdnPurger>,
+    files: impl IntoIterator<Item = &'a File>,
+) -> Result<()> {
+    let Some(cdn) = cdn else {
+        return Ok(());
+    };
+
+    let files: HashMap<Uuid, &File> = files.into_iter().map(|f| (f.id, f)).collect();
+    if files.is_empty() {
+        return Ok(());
+    }
+    let (mut ids, mut project_ids, mut folder_ids, mut names) =
+        (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+    for file in files.values() {
+        ids.push(file.id);
+        project_ids.push(file.project_id);
+        folder_ids.push(file.folder_id);
+        names.push(file.original_name.as_str());
+    }
+    // Visible without credentials: the project or the folder is public
+    let public = sqlx::query_as::<_, PublicFile>(
+        r#"
+        SELECT f.id, f.original_name, fol.path AS folder_path, p.slug, p.custom_domain
+        FROM UNNEST($1::uuid[], $2::uuid[], $3::uuid[], $4::text[])
+            AS f(id, project_id, folder_id, original_name)
+        JOIN projects p ON p.id = f.project_id
+        LEFT JOIN folders fol ON fol.id = f.folder_id
+        WHERE p.is_public OR COALESCE(fol.is_public, FALSE)
+        "#,
+    )
+    .bind(&ids)
+    .bind(&project_ids)
+    .bind(&folder_ids)
+    .bind(&names)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut urls = Vec::new();
+    for file in &public {
+        cdn.file_urls(file, &mut urls);
+    }
+    if urls.is_empty() {
+        return Ok(());
+    }
+    sqlx::query(
+        r#"
+        INSERT INTO cdn_purge_jobs (urls)
+        SELECT array_agg(url ORDER BY n)
+        FROM UNNEST($1::text[]) WITH ORDINALITY AS u(url, n)
+        GROUP BY (n - 1) / $2
+        "#,
+    )
+    .bind(&urls)
+    .bind(CDN_PURGE_URLS_PER_JOB)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+#[derive(FromRow)]
+struct CdnPurgeJob {
+    id: Uuid,
+    urls: Vec<String>,
+    attempts: i32,
+}
+
+/// Send one batch of due purge jobs to the CDN. Returns how many were purged.
+pub async fn run_cdn_purges(pool: &PgPool, cdn: &CdnPurger) -> Result<usize> {
+    let mut tx = pool.begin().await?;
+    let jobs = sqlx::query_as::<_, CdnPurgeJob>(
+        r#"
+        SELECT id, urls, attempts
+        FROM cdn_purge_jobs
+        WHERE next_attempt_at <= NOW()
+        ORDER BY created_at
+        LIMIT $1
+        FOR UPDATE SKIP LOCKED
+        "#,
+    )
+    .bind(CDN_PURGE_BATCH_SIZE)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut finished = Vec::with_capacity(jobs.len());
+    let mut purged = 0;
+    for job in &jobs {
+        match cdn.purge(&job.urls).await {
+            Ok(()) => {
+                finished.push(job.id);
+                purged += 1;
+            }
+            Err(e) if job.attempts + 1 >= CDN_PURGE_MAX_ATTEMPTS => {
+                tracing::warn!(
+                    "Dropping CDN purge of {} URLs after {} attempts: {}",
+                    job.urls.len(),
+                    job.attempts + 1,
+                    e
+                );
+                finished.push(job.id);
+            }
+            Err(e) => {
+                tracing::warn!("CDN purge failed: {}", e);
+                sqlx::query(
+                    r#"
+                    UPDATE cdn_purge_jobs
+                    SET attempts = attempts + 1, last_error = $1,
+                        next_attempt_at = NOW() + make_interval(secs => POWER(4, attempts + 1))
+                    WHERE id = $2
+                    "#,
+                )
+                .bind(&e)
+                .bind(job.id)
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+    }
+
+    sqlx::query("DELETE FROM cdn_purge_jobs WHERE id = ANY($1)")
+        .bind(&finished)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(purged)
+}
@@ -0,0 +1,76 @@
+use sqlx::{FromRow, PgPool};
+
+use crate::{
+    db::{advisory_xact_lock, LockMode},
+    error::Result,
+};
+
+/// Feed positions handed out so far and the highest one dropped by retention
+#[derive(Debug, Clone, Copy, FromRow)]
+pub struct ChangePositions {
+    pub numbered_through: i64,
+    pub purged_through: i64,
+}
+
+/// Give every committed, unnumbered change in `file_changes` its feed
+/// position, in the order they were logged, and return the positions. Only
+/// one caller numbers at a time, so a change committed later never gets a
+/// position below one a reader has already been given.
+pub async fn number_file_changes(pool: &PgPool) -> Result<ChangePositions> {
+    let mut tx = pool.begin().await?;
+    advisory_xact_lock(&mut tx, "file_changes", LockMode::Exclusive).await?;
+    let positions = sqlx::query_as::<_, ChangePositions>(
+        r#"
+        WITH pending AS (
+            SELECT c.id, p.numbered_through + row_number() OVER (ORDER BY c.id) AS seq
+            FROM file_changes c, file_change_positions p
+            WHERE c.seq IS NULL
+        ),
+        numbered AS (
+            UPDATE file_changes c SET seq = pending.seq
+            FROM pending
+            WHERE c.id = pending.id
+            RETURNING c.seq
+        )
+        UPDATE file_change_positions
+        SET numbered_through = COALESCE((SELECT MAX(seq) FROM numbered), numbered_through)
+        RETURNING numbered_through, purged_through
+        "#,
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(positions)
+}
+
+/// Drop changes older than `retention_days`. Cursors from before the newest
+/// dropped change can no longer be followed and must resync.
+pub async fn purge_file_changes(pool: &PgPool, retention_days: i32) -> Result<u64> {
+    // Numbered first, so every dropped change moves `purged_through`
+    number_file_changes(pool).await?;
+    let mut tx = pool.begin().await?;
+    advisory_xact_lock(&mut tx, "file_changes", LockMode::Exclusive).await?;
+    let (purged, purged_through) = sqlx::query_as::<_, (i64, Option<i64>)>(
+        r#"
+        WITH purged AS (
+            DELETE FROM file_changes
+            WHERE seq IS NOT NULL AND changed_at < NOW() - make_interval(days => $1)
+            RETURNING seq
+        )
+        SELECT COUNT(*), MAX(seq) FROM purged
+        "#,
+    )
+    .bind(retention_days)
+    .fetch_one(&mut *tx)
+    .await?;
+    if let Some(purged_through) = purged_through {
+        sqlx::query(
+            "UPDATE file_change_positions SET purged_through = GREATEST(purged_through, $1)",
+        )
+        .bind(purged_through)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(purged as u64)
+}
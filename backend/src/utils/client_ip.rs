@@ -0,0 +1,73 @@
+use axum::http::HeaderMap;
+use ipnet::IpNet;
+use std::net::IpAddr;
+
+fn is_trusted(ip: &IpAddr, trusted: &[IpNet]) -> bool {
+    trusted.iter().any(|net| net.contains(ip))
+}
+
+/// Parse a single `for=` node from an RFC 7239 `Forwarded` header.
+/// Handles quoting, bracketed IPv6 and optional ports; obfuscated identifiers yield None.
+fn parse_forwarded_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+    if let Some(rest) = node.strip_prefix('[') {
+        return rest.split(']').next()?.parse().ok();
+    }
+    if let Ok(ip) = node.parse() {
+        return Some(ip);
+    }
+    // IPv4 with port
+    node.rsplit_once(':')
+        .and_then(|(host, _port)| host.parse().ok())
+}
+
+/// Hop chain from the `X-Forwarded-For` header, or the `for=` entries of `Forwarded`
+fn forwarded_chain(headers: &HeaderMap) -> Vec<IpAddr> {
+    let xff: Vec<IpAddr> = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .filter_map(|s| s.trim().parse().ok())
+        .collect();
+    if !xff.is_empty() {
+        return xff;
+    }
+
+    headers
+        .get_all("forwarded")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .filter_map(|element| {
+            element.split(';').find_map(|pair| {
+                let (key, value) = pair.split_once('=')?;
+                if key.trim().eq_ignore_ascii_case("for") {
+                    parse_forwarded_node(value)
+                } else {
+                    None
+                }
+            })
+        })
+        .collect()
+}
+
+/// Resolve the real client IP for a request.
+///
+/// Forwarding headers are only honoured when the direct peer is a trusted proxy.
+/// The chain is walked right-to-left, skipping trusted hops, so a client cannot
+/// spoof its address by prepending entries to `X-Forwarded-For`.
+pub fn resolve_client_ip(peer: IpAddr, headers: &HeaderMap, trusted: &[IpNet]) -> IpAddr {
+    if !is_trusted(&peer, trusted) {
+        return peer;
+    }
+
+    let mut client = peer;
+    for hop in forwarded_chain(headers).into_iter().rev() {
+        client = hop;
+        if !is_trusted(&hop, trusted) {
+            break;
+        }
+    }
+    client
+}
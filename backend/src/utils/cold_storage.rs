@@ -0,0 +1,487 @@
+use chrono::Utc;
+use futures::StreamExt;
+use hmac::{Hmac, Mac};
+use object_store::{
+    aws::{AmazonS3, AmazonS3Builder, AmazonS3ConfigKey},
+    buffered::BufWriter,
+    local::LocalFileSystem,
+    path::Path as ObjectPath,
+    prefix::PrefixStore,
+    signer::Signer,
+    ObjectStore,
+};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::{io, path::Path, sync::Arc, time::Duration};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use uuid::Uuid;
+
+use crate::{
+    error::{AppError, Result},
+    models::File,
+};
+
+use super::precompress::remove_variants;
+
+/// `files.storage_tier` values
+pub const HOT_TIER: &str = "hot";
+pub const COLD_TIER: &str = "cold";
+
+/// Files moved to cold storage per lifecycle pass
+const LIFECYCLE_BATCH_SIZE: i64 = 500;
+
+/// Characters SigV4 leaves unescaped in query parameters
+const SIGV4_ESCAPES: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Second storage backend for blobs that haven't been accessed in a while.
+/// Objects are keyed `<project_id>/<file_id>`, so moves don't touch cold storage.
+#[derive(Debug)]
+pub struct ColdStorage {
+    store: Arc<dyn ObjectStore>,
+    /// Set when cold storage is an S3 bucket, for presigned download URLs
+    s3: Option<S3Bucket>,
+}
+
+#[derive(Debug)]
+struct S3Bucket {
+    client: AmazonS3,
+    region: String,
+    prefix: String,
+}
+
+fn object_key(file: &File) -> ObjectPath {
+    ObjectPath::from(format!("{}/{}", file.project_id, file.id))
+}
+
+fn is_not_found(e: &object_store::Error) -> bool {
+    matches!(e, object_store::Error::NotFound { .. })
+}
+
+/// Open an object store from `s3://bucket[/prefix]` (credentials and region from the
+/// standard `AWS_*` variables) or a local directory such as a mounted HDD or network share
+pub fn open_object_store(
+    url: &str,
+) -> std::result::Result<Arc<dyn ObjectStore>, object_store::Error> {
+    if let Some(bucket) = open_s3_bucket(url)? {
+        Ok(match bucket.prefix.as_str() {
+            "" => Arc::new(bucket.client),
+            prefix => Arc::new(PrefixStore::new(bucket.client, prefix)),
+        })
+    } else {
+        let dir = url.strip_prefix("file://").unwrap_or(url);
+        std::fs::create_dir_all(dir).map_err(|e| object_store::Error::Generic {
+            store: "LocalFileSystem",
+            source: Box::new(e),
+        })?;
+        Ok(Arc::new(LocalFileSystem::new_with_prefix(dir)?))
+    }
+}
+
+/// The bucket of an `s3://bucket[/prefix]` URL, or `None` for other URLs
+fn open_s3_bucket(url: &str) -> std::result::Result<Option<S3Bucket>, object_store::Error> {
+    let Some(rest) = url.strip_prefix("s3://") else {
+        return Ok(None);
+    };
+    let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+    let builder = AmazonS3Builder::from_env().with_bucket_name(bucket);
+    let region = builder
+        .get_config_value(&AmazonS3ConfigKey::Region)
+        .unwrap_or_else(|| "us-east-1".to_string());
+    Ok(Some(S3Bucket {
+        client: builder.build()?,
+        region,
+        prefix: prefix.trim_matches('/').to_string(),
+    }))
+}
+
+fn hmac_sha256(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+impl S3Bucket {
+    /// SigV4 query-string signed GET URL for `key`. Unlike `Signer::signed_url`,
+    /// `response-*` parameters can be added, which S3 only honours when signed.
+    async fn presigned_get(
+        &self,
+        key: &ObjectPath,
+        expires_in: Duration,
+        response_headers: &[(&str, &str)],
+    ) -> io::Result<reqwest::Url> {
+        let key = match self.prefix.as_str() {
+            "" => key.clone(),
+            prefix => ObjectPath::from(format!("{prefix}/{key}")),
+        };
+        // The store's own signed URL gives the object's URL for the configured
+        // endpoint and addressing style
+        let mut url = self
+            .client
+            .signed_url(reqwest::Method::GET, &key, expires_in)
+            .await
+            .map_err(io::Error::other)?;
+        url.set_query(None);
+        let credential = self
+            .client
+            .credentials()
+            .get_credential()
+            .await
+            .map_err(io::Error::other)?;
+
+        let now = Utc::now();
+        let date = now.format("%Y%m%d").to_string();
+        let timestamp = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let scope = format!("{date}/{}/s3/aws4_request", self.region);
+        let mut params = vec![
+            (
+                "X-Amz-Algorithm".to_string(),
+                "AWS4-HMAC-SHA256".to_string(),
+            ),
+            (
+                "X-Amz-Credential".to_string(),
+                format!("{}/{scope}", credential.key_id),
+            ),
+            ("X-Amz-Date".to_string(), timestamp.clone()),
+            (
+                "X-Amz-Expires".to_string(),
+                expires_in.as_secs().to_string(),
+            ),
+            ("X-Amz-SignedHeaders".to_string(), "host".to_string()),
+        ];
+        if let Some(ref token) = credential.token {
+            params.push(("X-Amz-Security-Token".to_string(), token.clone()));
+        }
+        for (name, value) in response_headers {
+            params.push((name.to_string(), value.to_string()));
+        }
+        let mut params: Vec<String> = params
+            .iter()
+            .map(|(name, value)| {
+                format!(
+                    "{}={}",
+                    utf8_percent_encode(name, SIGV4_ESCAPES),
+                    utf8_percent_encode(value, SIGV4_ESCAPES)
+                )
+            })
+            .collect();
+        params.sort();
+        let query = params.join("&");
+
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{host}:{port}"),
+            (Some(host), None) => host.to_string(),
+            (None, _) => return Err(io::Error::other("S3 URL has no host")),
+        };
+        let canonical_request = format!(
+            "GET\n{}\n{query}\nhost:{host}\n\nhost\nUNSIGNED-PAYLOAD",
+            url.path()
+        );
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{timestamp}\n{scope}\n{}",
+            hex::encode(Sha256::digest(canonical_request.as_bytes()))
+        );
+        let signing_key = [date.as_str(), &self.region, "s3", "aws4_request"]
+            .iter()
+            .fold(
+                format!("AWS4{}", credential.secret_key).into_bytes(),
+                |key, part| hmac_sha256(&key, part),
+            );
+        let signature = hex::encode(hmac_sha256(&signing_key, &string_to_sign));
+        url.set_query(Some(&format!("{query}&X-Amz-Signature={signature}")));
+        Ok(url)
+    }
+}
+
+impl ColdStorage {
+    pub fn open(url: &str) -> std::result::Result<Self, object_store::Error> {
+        let s3 = open_s3_bucket(url)?;
+        let store: Arc<dyn ObjectStore> = match s3 {
+            Some(ref bucket) => match bucket.prefix.as_str() {
+                "" => Arc::new(bucket.client.clone()),
+                prefix => Arc::new(PrefixStore::new(bucket.client.clone(), prefix)),
+            },
+            None => open_object_store(url)?,
+        };
+        Ok(Self { store, s3 })
+    }
+
+    /// Whether cold copies can be handed out as presigned URLs (S3 only)
+    pub fn can_presign(&self) -> bool {
+        self.s3.is_some()
+    }
+
+    /// Short-lived URL a client can download a file's cold copy from directly,
+    /// served with the given `response-*` headers (e.g. `response-content-type`)
+    pub async fn presigned_url(
+        &self,
+        file: &File,
+        expires_in: Duration,
+        response_headers: &[(&str, &str)],
+    ) -> io::Result<reqwest::Url> {
+        let s3 = self
+            .s3
+            .as_ref()
+            .ok_or_else(|| io::Error::other("Cold storage is not an S3 bucket"))?;
+        s3.presigned_get(&object_key(file), expires_in, response_headers)
+            .await
+    }
+
+    /// Stream a local blob into cold storage
+    async fn upload(&self, file: &File) -> io::Result<()> {
+        let mut source = tokio::fs::File::open(&file.file_path).await?;
+        let mut writer = BufWriter::new(Arc::clone(&self.store), object_key(file));
+        tokio::io::copy(&mut source, &mut writer).await?;
+        writer.shutdown().await
+    }
+
+    /// Copy a local blob to cold storage and read it back, checking the SHA-256 of
+    /// both sides match. Returns `(bytes copied, hex digest)`.
+    pub async fn copy_verified(&self, file: &File) -> io::Result<(u64, String)> {
+        let key = object_key(file);
+        let mut source = tokio::fs::File::open(&file.file_path).await?;
+        let mut writer = BufWriter::new(Arc::clone(&self.store), key.clone());
+        let mut hasher = Sha256::new();
+        let mut buf = vec![0u8; 64 * 1024];
+        let mut size: u64 = 0;
+        loop {
+            let n = source.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+            writer.write_all(&buf[..n]).await?;
+            size += n as u64;
+        }
+        writer.shutdown().await?;
+        let expected = hex::encode(hasher.finalize());
+
+        let mut stream = self
+            .store
+            .get(&key)
+            .await
+            .map_err(io::Error::other)?
+            .into_stream();
+        let mut hasher = Sha256::new();
+        while let Some(chunk) = stream.next().await {
+            hasher.update(chunk.map_err(io::Error::other)?);
+        }
+        if hex::encode(hasher.finalize()) != expected {
+            self.delete(file).await;
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Checksum mismatch after copying to cold storage",
+            ));
+        }
+
+        Ok((size, expected))
+    }
+
+    /// Download a cold blob back to its local path (via a temp file, so readers
+    /// never see a partial blob)
+    async fn download(&self, file: &File) -> io::Result<()> {
+        let target = Path::new(&file.file_path);
+        if let Some(parent) = target.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let temp = target.with_extension(format!("restore-{}", Uuid::new_v4().simple()));
+
+        let result = async {
+            let mut stream = self
+                .store
+                .get(&object_key(file))
+                .await
+                .map_err(io::Error::other)?
+                .into_stream();
+            let mut output = tokio::fs::File::create(&temp).await?;
+            while let Some(chunk) = stream.next().await {
+                output.write_all(&chunk.map_err(io::Error::other)?).await?;
+            }
+            output.flush().await?;
+            tokio::fs::rename(&temp, target).await
+        }
+        .await;
+
+        if result.is_err() {
+            let _ = tokio::fs::remove_file(&temp).await;
+        }
+        result
+    }
+
+    /// Contents of a file's cold copy, or `None` if it is missing
+    pub async fn read(&self, file: &File) -> io::Result<Option<Vec<u8>>> {
+        let result = match self.store.get(&object_key(file)).await {
+            Ok(result) => result,
+            Err(e) if is_not_found(&e) => return Ok(None),
+            Err(e) => return Err(io::Error::other(e)),
+        };
+        let bytes = result.bytes().await.map_err(io::Error::other)?;
+        Ok(Some(bytes.to_vec()))
+    }
+
+    /// Size of a file's cold copy, or `None` if it is missing
+    pub async fn object_size(&self, file: &File) -> io::Result<Option<u64>> {
+        match self.store.head(&object_key(file)).await {
+            Ok(meta) => Ok(Some(meta.size as u64)),
+            Err(e) if is_not_found(&e) => Ok(None),
+            Err(e) => Err(io::Error::other(e)),
+        }
+    }
+
+    /// Every cold object of a project (keys under `<project_id>/`), with its size
+    pub async fn project_objects(&self, project_id: Uuid) -> io::Result<Vec<(ObjectPath, u64)>> {
+        let prefix = ObjectPath::from(project_id.to_string());
+        let mut objects = Vec::new();
+        let mut listing = self.store.list(Some(&prefix));
+        while let Some(meta) = listing.next().await {
+            let meta = meta.map_err(io::Error::other)?;
+            objects.push((meta.location, meta.size as u64));
+        }
+        Ok(objects)
+    }
+
+    /// Remove an object listed by `project_objects` (already-missing objects are fine)
+    pub async fn delete_object(&self, key: &ObjectPath) -> io::Result<()> {
+        match self.store.delete(key).await {
+            Err(e) if !is_not_found(&e) => Err(io::Error::other(e)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Remove a file's cold copy (already-missing objects are fine)
+    pub async fn delete(&self, file: &File) {
+        if let Err(e) = self.store.delete(&object_key(file)).await {
+            if !is_not_found(&e) {
+                tracing::warn!("Failed to delete cold copy of file {}: {}", file.id, e);
+            }
+        }
+    }
+}
+
+/// Remove the cold copy of a file being deleted, if it has one
+pub async fn delete_cold_blob(cold_storage: Option<&ColdStorage>, file: &File) {
+    if file.storage_tier != COLD_TIER {
+        return;
+    }
+    match cold_storage {
+        Some(cold) => cold.delete(file).await,
+        None => tracing::warn!(
+            "File {} is in cold storage but COLD_STORAGE_URL is not set; its cold copy was not removed",
+            file.id
+        ),
+    }
+}
+
+/// Make sure a file's blob is on local disk, pulling it back from cold storage if needed
+pub async fn ensure_hot(
+    pool: &PgPool,
+    cold_storage: Option<&ColdStorage>,
+    file: &File,
+) -> Result<()> {
+    if file.storage_tier != COLD_TIER {
+        return Ok(());
+    }
+    let cold = cold_storage.ok_or_else(|| {
+        AppError::FileError("File is in cold storage but COLD_STORAGE_URL is not set".to_string())
+    })?;
+
+    if let Err(e) = cold.download(file).await {
+        // A concurrent request may have restored (and removed the cold copy) first
+        let tier: Option<String> =
+            sqlx::query_scalar("SELECT storage_tier FROM files WHERE id = $1")
+                .bind(file.id)
+                .fetch_optional(pool)
+                .await?;
+        if tier.as_deref() == Some(HOT_TIER) {
+            return Ok(());
+        }
+        return Err(AppError::FileError(format!(
+            "Failed to restore file from cold storage: {e}"
+        )));
+    }
+
+    sqlx::query("UPDATE files SET storage_tier = 'hot', last_accessed_at = NOW() WHERE id = $1")
+        .bind(file.id)
+        .execute(pool)
+        .await?;
+    cold.delete(file).await;
+
+    tracing::info!("Restored file {} from cold storage", file.id);
+    Ok(())
+}
+
+/// Move files idle for longer than their project's `cold_storage_after_days` to
+/// cold storage. Returns how many files were moved.
+pub async fn run_lifecycle(pool: &PgPool, cold: &ColdStorage) -> Result<usize> {
+    let candidates = sqlx::query_as::<_, File>(
+        r#"
+        SELECT f.id, f.project_id, f.folder_id, f.original_name, f.stored_name, f.file_path, f.size, f.mime_type, f.upload_date, f.storage_encoding, f.precompressed_encodings, f.storage_tier, f.moderation_status
+        FROM files f
+        JOIN projects p ON p.id = f.project_id
+        WHERE f.storage_tier = 'hot'
+          AND p.cold_storage_after_days IS NOT NULL
+          AND COALESCE(f.last_accessed_at, f.upload_date)
+              < NOW() - make_interval(days => p.cold_storage_after_days)
+        ORDER BY COALESCE(f.last_accessed_at, f.upload_date)
+        LIMIT $1
+        "#,
+    )
+    .bind(LIFECYCLE_BATCH_SIZE)
+    .fetch_all(pool)
+    .await?;
+
+    let mut moved = 0;
+    for file in candidates {
+        if let Err(e) = cold.upload(&file).await {
+            tracing::warn!("Failed to move file {} to cold storage: {}", file.id, e);
+            continue;
+        }
+
+        if switch_to_cold(pool, &file, true).await? {
+            moved += 1;
+        } else {
+            cold.delete(&file).await;
+        }
+    }
+
+    Ok(moved)
+}
+
+/// Mark a file whose blob was copied to cold storage as cold and remove the local
+/// copy. Returns false (leaving the file hot) if it was replaced or moved meanwhile,
+/// or, with `idle_only`, downloaded since it became eligible.
+pub async fn switch_to_cold(pool: &PgPool, file: &File, idle_only: bool) -> Result<bool> {
+    let updated = sqlx::query(
+        r#"
+        UPDATE files f
+        SET storage_tier = 'cold', precompressed_encodings = '{}'
+        FROM projects p
+        WHERE f.id = $1 AND p.id = f.project_id
+          AND f.storage_tier = 'hot' AND f.file_path = $2 AND f.upload_date = $3
+          AND (NOT $4 OR COALESCE(f.last_accessed_at, f.upload_date)
+              < NOW() - make_interval(days => p.cold_storage_after_days))
+        "#,
+    )
+    .bind(file.id)
+    .bind(&file.file_path)
+    .bind(file.upload_date)
+    .bind(idle_only)
+    .execute(pool)
+    .await?;
+
+    if updated.rows_affected() == 0 {
+        return Ok(false);
+    }
+
+    if let Err(e) = tokio::fs::remove_file(&file.file_path).await {
+        tracing::warn!("Failed to delete local copy of {}: {}", file.file_path, e);
+    }
+    remove_variants(&file.file_path).await;
+    Ok(true)
+}
@@ -0,0 +1,114 @@
+use axum::http::{header::ACCEPT_ENCODING, HeaderMap};
+use std::io::{self, Write};
+
+/// `files.storage_encoding` value for zstd-compressed blobs
+pub const ZSTD_ENCODING: &str = "zstd";
+
+/// Files smaller than this aren't worth compressing
+pub const MIN_COMPRESSIBLE_SIZE: usize = 1024;
+
+/// Text-like types that compress well; media and archives are already compressed
+pub fn is_compressible(mime_type: &str) -> bool {
+    let essence = mime_type.split(';').next().unwrap_or("").trim();
+    essence.starts_with("text/")
+        || essence.ends_with("+json")
+        || essence.ends_with("+xml")
+        || matches!(
+            essence,
+            "application/json"
+                | "application/javascript"
+                | "application/xml"
+                | "application/x-yaml"
+                | "application/yaml"
+                | "application/toml"
+                | "application/sql"
+                | "application/x-sh"
+                | "application/wasm"
+                | "image/svg+xml"
+                | "image/bmp"
+        )
+}
+
+pub fn zstd_compress(data: &[u8], level: i32) -> io::Result<Vec<u8>> {
+    zstd::encode_all(data, level)
+}
+
+pub fn zstd_decompress(data: &[u8]) -> io::Result<Vec<u8>> {
+    zstd::decode_all(data)
+}
+
+pub fn gzip_compress(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+/// Maximum-effort gzip for variants that are generated once and served many times
+pub fn gzip_compress_best(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+pub fn brotli_compress(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut output = Vec::new();
+    let params = brotli::enc::BrotliEncoderParams {
+        quality: 11,
+        ..Default::default()
+    };
+    brotli::BrotliCompress(&mut io::Cursor::new(data), &mut output, &params)?;
+    Ok(output)
+}
+
+/// Content encodings a response can be served in, most preferred first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseEncoding {
+    Brotli,
+    Zstd,
+    Gzip,
+    Identity,
+}
+
+impl ResponseEncoding {
+    /// `Content-Encoding` token (also the name stored in `precompressed_encodings`)
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ResponseEncoding::Brotli => "br",
+            ResponseEncoding::Zstd => "zstd",
+            ResponseEncoding::Gzip => "gzip",
+            ResponseEncoding::Identity => "identity",
+        }
+    }
+}
+
+/// Pick the most preferred of the `available` encodings the client accepts in
+/// `Accept-Encoding` (entries with `q=0` are refused); Identity if none match
+pub fn negotiate_encoding(headers: &HeaderMap, available: &[ResponseEncoding]) -> ResponseEncoding {
+    let accepted: Vec<String> = headers
+        .get_all(ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|h| h.to_str().ok())
+        .flat_map(|h| h.split(','))
+        .filter_map(|entry| {
+            let mut parts = entry.split(';').map(str::trim);
+            let coding = parts.next()?.to_lowercase();
+            let refused = parts.any(|p| {
+                p.strip_prefix("q=")
+                    .and_then(|q| q.parse::<f32>().ok())
+                    .is_some_and(|q| q == 0.0)
+            });
+            (!refused).then_some(coding)
+        })
+        .collect();
+
+    [
+        ResponseEncoding::Brotli,
+        ResponseEncoding::Zstd,
+        ResponseEncoding::Gzip,
+    ]
+    .into_iter()
+    .find(|encoding| {
+        available.contains(encoding) && accepted.iter().any(|c| c == encoding.as_str())
+    })
+    .unwrap_or(ResponseEncoding::Identity)
+}
@@ -0,0 +1,83 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::error::Result;
+
+/// Recount one project's cached file counters (`project_stats` and the
+/// `file_count`/`total_size` columns of its folders), which triggers on `files`
+/// keep current. Returns how many counters were wrong.
+///
+/// The counter rows are locked before counting, in the same order the triggers
+/// take them (folders, then the project). A concurrent statement on `files` has
+/// then either committed, and is counted, or applies its delta after this commit.
+async fn reconcile_project(pool: &PgPool, project_id: Uuid) -> Result<u64> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query("SELECT 1 FROM folders WHERE project_id = $1 ORDER BY id FOR UPDATE")
+        .bind(project_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(
+        r#"
+        INSERT INTO project_stats (project_id) VALUES ($1)
+        ON CONFLICT (project_id) DO UPDATE SET project_id = EXCLUDED.project_id
+        "#,
+    )
+    .bind(project_id)
+    .execute(&mut *tx)
+    .await?;
+
+    let folders = sqlx::query(
+        r#"
+        UPDATE folders fol
+        SET file_count = c.file_count, total_size = c.total_size
+        FROM (
+            SELECT f.id, COUNT(fi.id) AS file_count, COALESCE(SUM(fi.size), 0) AS total_size
+            FROM folders f
+            LEFT JOIN files fi ON fi.folder_id = f.id
+            WHERE f.project_id = $1
+            GROUP BY f.id
+        ) c
+        WHERE fol.id = c.id
+          AND (fol.file_count, fol.total_size) IS DISTINCT FROM (c.file_count, c.total_size)
+        "#,
+    )
+    .bind(project_id)
+    .execute(&mut *tx)
+    .await?;
+
+    let project = sqlx::query(
+        r#"
+        UPDATE project_stats s
+        SET file_count = c.file_count, total_size = c.total_size
+        FROM (
+            SELECT COUNT(*) AS file_count, COALESCE(SUM(size), 0) AS total_size
+            FROM files
+            WHERE project_id = $1
+        ) c
+        WHERE s.project_id = $1
+          AND (s.file_count, s.total_size) IS DISTINCT FROM (c.file_count, c.total_size)
+        "#,
+    )
+    .bind(project_id)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(folders.rows_affected() + project.rows_affected())
+}
+
+/// Recount the cached file counters of every project, one project per
+/// transaction. Returns how many counters were corrected; anything above zero
+/// means a write bypassed the triggers.
+pub async fn reconcile_file_counters(pool: &PgPool) -> Result<u64> {
+    let project_ids: Vec<Uuid> = sqlx::query_scalar("SELECT id FROM projects ORDER BY id")
+        .fetch_all(pool)
+        .await?;
+
+    let mut corrected = 0;
+    for project_id in project_ids {
+        corrected += reconcile_project(pool, project_id).await?;
+    }
+    Ok(corrected)
+}
@@ -0,0 +1,89 @@
+use sha2::{Digest, Sha256};
+
+use crate::models::{BlockSignature, DeltaOp};
+
+/// Block size of signatures when the client doesn't ask for one
+pub const DEFAULT_DELTA_BLOCK_SIZE: usize = 64 * 1024;
+
+pub const MIN_DELTA_BLOCK_SIZE: usize = 512;
+pub const MAX_DELTA_BLOCK_SIZE: usize = 16 * 1024 * 1024;
+
+/// Most blocks in one signature list; larger files need a larger block size
+pub const MAX_DELTA_BLOCKS: usize = 100_000;
+
+/// rsync's weak checksum of a block: `a` is the sum of the bytes and `b` the sum
+/// of the running values of `a`, both mod 2^16, combined as `a | b << 16`.
+/// Sliding the window one byte (dropping `out`, adding `in` to a window of `n`
+/// bytes) updates it as `a += in - out`, `b += a - n * out`.
+pub fn rolling_checksum(block: &[u8]) -> u32 {
+    let (mut a, mut b) = (0u32, 0u32);
+    for &byte in block {
+        a = a.wrapping_add(byte as u32);
+        b = b.wrapping_add(a);
+    }
+    (a & 0xffff) | (b << 16)
+}
+
+/// Weak and strong checksums of each `block_size` block of `data`
+pub fn block_signatures(data: &[u8], block_size: usize) -> Vec<BlockSignature> {
+    data.chunks(block_size)
+        .enumerate()
+        .map(|(index, block)| BlockSignature {
+            index: index as u64,
+            weak: rolling_checksum(block),
+            strong: hex::encode(Sha256::digest(block)),
+        })
+        .collect()
+}
+
+/// Rebuild content from `base` (the version the signatures were taken of),
+/// copying its blocks and taking literal bytes from `literals` in order.
+/// Every literal byte must be used, and the result may be at most `max_size` bytes.
+pub fn apply_delta(
+    base: &[u8],
+    block_size: usize,
+    ops: &[DeltaOp],
+    literals: &[u8],
+    max_size: usize,
+) -> Result<Vec<u8>, String> {
+    let mut output = Vec::new();
+    let mut literal_offset = 0usize;
+
+    for op in ops {
+        let bytes = match *op {
+            DeltaOp::Copy { block, count } => {
+                let start = (block as usize).saturating_mul(block_size);
+                if count == 0 || start >= base.len() {
+                    return Err(format!("Invalid copy of {count} blocks from block {block}"));
+                }
+                let end = (block.saturating_add(count) as usize)
+                    .saturating_mul(block_size)
+                    .min(base.len());
+                &base[start..end]
+            }
+            DeltaOp::Data(length) => {
+                let end = literal_offset.saturating_add(length as usize);
+                if length == 0 || end > literals.len() {
+                    return Err(format!(
+                        "Data of {length} bytes runs past the end of the literal data"
+                    ));
+                }
+                let bytes = &literals[literal_offset..end];
+                literal_offset = end;
+                bytes
+            }
+        };
+        if output.len() + bytes.len() > max_size {
+            return Err(format!("File size exceeds maximum of {max_size} bytes"));
+        }
+        output.extend_from_slice(bytes);
+    }
+
+    if literal_offset != literals.len() {
+        return Err(format!(
+            "{} bytes of literal data are not used by any instruction",
+            literals.len() - literal_offset
+        ));
+    }
+    Ok(output)
+}
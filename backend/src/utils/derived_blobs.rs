@@ -0,0 +1,38 @@
+use sqlx::PgPool;
+
+/// Queued blobs deleted per cleanup query
+const DERIVED_BLOB_DELETION_BATCH: i64 = 500;
+
+/// Delete blobs derived from files (kept versions, media previews) once
+/// triggers have queued them, whichever way their rows went (file delete,
+/// project purge, regeneration, ...). Blobs that can't be deleted stay queued
+/// for the next run.
+pub async fn remove_deleted_derived_blobs(pool: &PgPool) -> Result<(), String> {
+    loop {
+        let paths: Vec<String> = sqlx::query_scalar(
+            "SELECT file_path FROM derived_blob_deletions ORDER BY queued_at LIMIT $1",
+        )
+        .bind(DERIVED_BLOB_DELETION_BATCH)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let mut removed = Vec::with_capacity(paths.len());
+        for path in &paths {
+            match tokio::fs::remove_file(path).await {
+                Ok(()) => removed.push(path.as_str()),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => removed.push(path.as_str()),
+                Err(e) => tracing::warn!("Failed to delete derived blob {}: {}", path, e),
+            }
+        }
+        sqlx::query("DELETE FROM derived_blob_deletions WHERE file_path = ANY($1)")
+            .bind(&removed)
+            .execute(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if (paths.len() as i64) < DERIVED_BLOB_DELETION_BATCH || removed.len() < paths.len() {
+            return Ok(());
+        }
+    }
+}
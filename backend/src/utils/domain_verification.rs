@@ -0,0 +1,77 @@
+use serde::Deserialize;
+use std::time::Duration;
+
+use crate::error::{AppError, Result};
+
+/// DNS record type number of TXT
+const TXT_RECORD_TYPE: u16 = 16;
+
+/// Name of the TXT record proving control of a custom domain
+pub fn verification_record_name(domain: &str) -> String {
+    format!("_filerunner-challenge.{domain}")
+}
+
+#[derive(Deserialize)]
+struct DnsJsonResponse {
+    #[serde(rename = "Status")]
+    status: u32,
+    #[serde(rename = "Answer", default)]
+    answer: Vec<DnsJsonAnswer>,
+}
+
+#[derive(Deserialize)]
+struct DnsJsonAnswer {
+    #[serde(rename = "type")]
+    record_type: u16,
+    data: String,
+}
+
+/// The text of a TXT record as DNS-over-HTTPS JSON gives it: one or more
+/// quoted strings, joined
+fn txt_record_text(data: &str) -> String {
+    let data = data.trim();
+    if !data.starts_with('"') {
+        return data.to_string();
+    }
+    data.split('"')
+        .enumerate()
+        .filter(|(i, _)| i % 2 == 1)
+        .map(|(_, part)| part)
+        .collect()
+}
+
+/// Whether `domain` has a verification TXT record holding `token`, looked up
+/// through the JSON API of the resolver at `DNS_OVER_HTTPS_URL`
+pub async fn has_verification_record(
+    resolver_url: &str,
+    domain: &str,
+    token: &str,
+) -> Result<bool> {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .map_err(|e| AppError::InternalError(format!("Failed to build DNS client: {e}")))?;
+    let response = client
+        .get(resolver_url)
+        .query(&[
+            ("name", verification_record_name(domain).as_str()),
+            ("type", "TXT"),
+        ])
+        .header(reqwest::header::ACCEPT, "application/dns-json")
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| AppError::InternalError(format!("DNS lookup failed: {e}")))?;
+    let body: DnsJsonResponse = response
+        .json()
+        .await
+        .map_err(|e| AppError::InternalError(format!("Invalid DNS response: {e}")))?;
+
+    // Status 3 is NXDOMAIN: no record yet
+    Ok(body.status == 0
+        && body
+            .answer
+            .iter()
+            .filter(|a| a.record_type == TXT_RECORD_TYPE)
+            .any(|a| txt_record_text(&a.data) == token))
+}
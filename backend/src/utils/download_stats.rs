@@ -0,0 +1,71 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::{collections::HashMap, sync::Mutex};
+use uuid::Uuid;
+
+#[derive(Debug)]
+struct PendingDownloads {
+    count: i64,
+    last_accessed_at: DateTime<Utc>,
+}
+
+/// Buffers file download counts in memory so downloads never wait on a write.
+/// `flush` is called periodically from a background task.
+#[derive(Debug, Default)]
+pub struct DownloadTracker {
+    pending: Mutex<HashMap<Uuid, PendingDownloads>>,
+}
+
+impl DownloadTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count one download of `file_id`
+    pub fn record(&self, file_id: Uuid) {
+        let now = Utc::now();
+        let mut pending = self.pending.lock().unwrap();
+        let downloads = pending.entry(file_id).or_insert(PendingDownloads {
+            count: 0,
+            last_accessed_at: now,
+        });
+        downloads.count += 1;
+        downloads.last_accessed_at = now;
+    }
+
+    /// Add buffered downloads to `download_count` and advance `last_accessed_at`.
+    /// Files deleted since their download are dropped by the join.
+    pub async fn flush(&self, pool: &PgPool) -> Result<(), sqlx::Error> {
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap());
+        if pending.is_empty() {
+            return Ok(());
+        }
+
+        let mut ids = Vec::with_capacity(pending.len());
+        let mut counts = Vec::with_capacity(pending.len());
+        let mut accessed_at = Vec::with_capacity(pending.len());
+        for (id, downloads) in pending {
+            ids.push(id);
+            counts.push(downloads.count);
+            accessed_at.push(downloads.last_accessed_at);
+        }
+
+        sqlx::query(
+            r#"
+            UPDATE files f
+            SET download_count = f.download_count + d.count,
+                last_accessed_at = GREATEST(f.last_accessed_at, d.last_accessed_at)
+            FROM UNNEST($1::uuid[], $2::bigint[], $3::timestamptz[])
+                AS d(id, count, last_accessed_at)
+            WHERE f.id = d.id
+            "#,
+        )
+        .bind(&ids)
+        .bind(&counts)
+        .bind(&accessed_at)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+}
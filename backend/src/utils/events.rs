@@ -0,0 +1,364 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use sqlx::{FromRow, PgConnection, PgPool};
+use std::{collections::HashSet, time::Duration};
+use uuid::Uuid;
+
+use crate::{error::Result, models::File};
+
+/// Outbox events delivered per dispatcher pass
+const OUTBOX_BATCH_SIZE: i64 = 100;
+
+/// Upper bound for the retry backoff of an undeliverable event
+const OUTBOX_MAX_BACKOFF_SECS: i32 = 3600;
+
+/// File lifecycle events published to the configured broker
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileEventKind {
+    Uploaded,
+    Deleted,
+}
+
+impl FileEventKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FileEventKind::Uploaded => "file.uploaded",
+            FileEventKind::Deleted => "file.deleted",
+        }
+    }
+}
+
+impl std::str::FromStr for FileEventKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "file.uploaded" => Ok(FileEventKind::Uploaded),
+            "file.deleted" => Ok(FileEventKind::Deleted),
+            other => Err(format!("Unknown event type: {other}")),
+        }
+    }
+}
+
+/// JSON layout of published events
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum EventSchema {
+    /// Flat object: event metadata and file fields side by side
+    Native,
+    /// CloudEvents 1.0 structured mode, file fields under `data`
+    CloudEvents,
+}
+
+impl std::str::FromStr for EventSchema {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "" | "native" => Ok(EventSchema::Native),
+            "cloudevents" => Ok(EventSchema::CloudEvents),
+            other => Err(format!("Unknown EVENT_SCHEMA: {other}")),
+        }
+    }
+}
+
+/// Pending row of `event_outbox`; its ID doubles as the event ID, so consumers
+/// can drop the duplicates at-least-once delivery may produce
+#[derive(Debug, FromRow)]
+struct OutboxEvent {
+    id: Uuid,
+    event_type: String,
+    occurred_at: DateTime<Utc>,
+    file_id: Uuid,
+    project_id: Uuid,
+    folder_id: Option<Uuid>,
+    original_name: String,
+    size: i64,
+    mime_type: String,
+}
+
+impl OutboxEvent {
+    fn to_json(&self, schema: EventSchema) -> serde_json::Value {
+        let data = serde_json::json!({
+            "file_id": self.file_id,
+            "project_id": self.project_id,
+            "folder_id": self.folder_id,
+            "original_name": self.original_name,
+            "size": self.size,
+            "mime_type": self.mime_type,
+        });
+        match schema {
+            EventSchema::Native => {
+                let mut event = serde_json::json!({
+                    "id": self.id,
+                    "type": self.event_type,
+                    "occurred_at": self.occurred_at,
+                });
+                if let (Some(event), serde_json::Value::Object(data)) =
+                    (event.as_object_mut(), data)
+                {
+                    event.extend(data);
+                }
+                event
+            }
+            EventSchema::CloudEvents => serde_json::json!({
+                "specversion": "1.0",
+                "id": self.id,
+                "source": format!("/projects/{}", self.project_id),
+                "type": format!("filerunner.{}", self.event_type),
+                "subject": self.file_id,
+                "time": self.occurred_at,
+                "datacontenttype": "application/json",
+                "data": data,
+            }),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Broker {
+    Nats(async_nats::Client),
+    /// Kafka through a Confluent-compatible REST Proxy (v2 API)
+    KafkaRest {
+        client: reqwest::Client,
+        base_url: String,
+    },
+}
+
+/// Publishes file events to NATS subjects or Kafka topics named
+/// `<prefix>.file.uploaded` / `<prefix>.file.deleted`
+#[derive(Debug)]
+pub struct EventPublisher {
+    broker: Broker,
+    topic_prefix: String,
+    schema: EventSchema,
+}
+
+impl EventPublisher {
+    /// `nats://` / `tls://` URLs publish to NATS; `http(s)://` URLs point at a Kafka REST Proxy
+    pub async fn connect(
+        url: &str,
+        topic_prefix: String,
+        schema: EventSchema,
+    ) -> std::result::Result<Self, Box<dyn std::error::Error>> {
+        let broker = if url.starts_with("http://") || url.starts_with("https://") {
+            Broker::KafkaRest {
+                client: reqwest::Client::builder()
+                    .timeout(Duration::from_secs(10))
+                    .build()?,
+                base_url: url.trim_end_matches('/').to_string(),
+            }
+        } else {
+            // Keep retrying in the background if NATS is down at startup
+            Broker::Nats(
+                async_nats::ConnectOptions::new()
+                    .retry_on_initial_connect()
+                    .connect(url)
+                    .await?,
+            )
+        };
+
+        Ok(Self {
+            broker,
+            topic_prefix,
+            schema,
+        })
+    }
+
+    async fn send(&self, event: &OutboxEvent) -> std::result::Result<(), String> {
+        let topic = format!("{}.{}", self.topic_prefix, event.event_type);
+        let payload = event.to_json(self.schema);
+        match &self.broker {
+            Broker::Nats(client) => {
+                let payload = serde_json::to_vec(&payload).map_err(|e| e.to_string())?;
+                client
+                    .publish(topic, payload.into())
+                    .await
+                    .map_err(|e| e.to_string())?;
+                client.flush().await.map_err(|e| e.to_string())
+            }
+            Broker::KafkaRest { client, base_url } => {
+                // Keyed by file ID so all events for a file land on the same partition
+                let response = client
+                    .post(format!("{base_url}/topics/{topic}"))
+                    .header("Content-Type", "application/vnd.kafka.json.v2+json")
+                    .json(&serde_json::json!({
+                        "records": [{ "key": event.file_id.to_string(), "value": payload }]
+                    }))
+                    .send()
+                    .await
+                    .map_err(|e| e.to_string())?;
+                if !response.status().is_success() {
+                    return Err(format!("REST proxy returned {}", response.status()));
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Queue an event for each file in the outbox, if an event broker is configured.
+/// Call inside the transaction that performs the mutation, so the event is
+/// recorded exactly when the change commits.
+pub async fn record_file_events<'a>(
+    conn: &mut PgConnection,
+    events: Option<&EventPublisher>,
+    kind: FileEventKind,
+    files: impl IntoIterator<Item = &'a File>,
+) -> Result<()> {
+    if events.is_none() {
+        return Ok(());
+    }
+
+    let files: Vec<&File> = files.into_iter().collect();
+    if files.is_empty() {
+        return Ok(());
+    }
+    let file_ids: Vec<Uuid> = files.iter().map(|f| f.id).collect();
+    let project_ids: Vec<Uuid> = files.iter().map(|f| f.project_id).collect();
+    let folder_ids: Vec<Option<Uuid>> = files.iter().map(|f| f.folder_id).collect();
+    let names: Vec<&str> = files.iter().map(|f| f.original_name.as_str()).collect();
+    let sizes: Vec<i64> = files.iter().map(|f| f.size).collect();
+    let mime_types: Vec<&str> = files.iter().map(|f| f.mime_type.as_str()).collect();
+
+    sqlx::query(
+        r#"
+        INSERT INTO event_outbox (event_type, file_id, project_id, folder_id, original_name, size, mime_type)
+        SELECT $1, * FROM UNNEST($2::uuid[], $3::uuid[], $4::uuid[], $5::text[], $6::bigint[], $7::text[])
+        "#,
+    )
+    .bind(kind.as_str())
+    .bind(&file_ids)
+    .bind(&project_ids)
+    .bind(&folder_ids)
+    .bind(&names)
+    .bind(&sizes)
+    .bind(&mime_types)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Deliver one batch of due outbox events, oldest first. Delivered events are
+/// moved to `delivered_events` when they're kept for replays (`retention_days`
+/// above 0); failed ones are retried with exponential backoff. Later events for
+/// a file whose delivery failed wait too, so each file's events stay in order.
+/// Returns how many events were delivered.
+pub async fn dispatch_outbox(
+    pool: &PgPool,
+    publisher: &EventPublisher,
+    retention_days: i32,
+) -> Result<usize> {
+    let mut tx = pool.begin().await?;
+    let batch = sqlx::query_as::<_, OutboxEvent>(
+        r#"
+        SELECT id, event_type, occurred_at, file_id, project_id, folder_id, original_name, size, mime_type
+        FROM event_outbox o
+        WHERE next_attempt_at <= NOW()
+          AND NOT EXISTS (
+              SELECT 1 FROM event_outbox earlier
+              WHERE earlier.file_id = o.file_id AND earlier.next_attempt_at > NOW()
+                AND (earlier.occurred_at, earlier.id) < (o.occurred_at, o.id)
+          )
+        ORDER BY occurred_at, id
+        LIMIT $1
+        FOR UPDATE SKIP LOCKED
+        "#,
+    )
+    .bind(OUTBOX_BATCH_SIZE)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut delivered = Vec::with_capacity(batch.len());
+    let mut blocked_files = HashSet::new();
+    for event in &batch {
+        if blocked_files.contains(&event.file_id) {
+            continue;
+        }
+        match publisher.send(event).await {
+            Ok(()) => delivered.push(event.id),
+            Err(e) => {
+                tracing::warn!("Failed to publish event {}: {}", event.id, e);
+                blocked_files.insert(event.file_id);
+                sqlx::query(
+                    r#"
+                    UPDATE event_outbox
+                    SET attempts = attempts + 1, last_error = $1,
+                        next_attempt_at = NOW() + make_interval(secs => LEAST(POWER(2, attempts + 1), $2))
+                    WHERE id = $3
+                    "#,
+                )
+                .bind(&e)
+                .bind(OUTBOX_MAX_BACKOFF_SECS)
+                .bind(event.id)
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+    }
+
+    if retention_days > 0 {
+        // A replayed event is delivered again under the same ID
+        sqlx::query(
+            r#"
+            INSERT INTO delivered_events (id, event_type, occurred_at, file_id, project_id, folder_id, original_name, size, mime_type)
+            SELECT id, event_type, occurred_at, file_id, project_id, folder_id, original_name, size, mime_type
+            FROM event_outbox
+            WHERE id = ANY($1)
+            ON CONFLICT (id) DO UPDATE SET delivered_at = NOW()
+            "#,
+        )
+        .bind(&delivered)
+        .execute(&mut *tx)
+        .await?;
+    }
+    sqlx::query("DELETE FROM event_outbox WHERE id = ANY($1)")
+        .bind(&delivered)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(delivered.len())
+}
+
+/// Queue a project's delivered events from `[from, to)` for delivery again,
+/// keeping their IDs so consumers that did receive them can drop the copies.
+/// Events of every type are replayed when `kinds` is empty. Returns how many
+/// were queued; events still waiting in the outbox aren't queued twice.
+pub async fn replay_events(
+    pool: &PgPool,
+    project_id: Uuid,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    kinds: &[FileEventKind],
+) -> Result<u64> {
+    let types: Vec<&str> = kinds.iter().map(|kind| kind.as_str()).collect();
+    let result = sqlx::query(
+        r#"
+        INSERT INTO event_outbox (id, event_type, occurred_at, file_id, project_id, folder_id, original_name, size, mime_type)
+        SELECT id, event_type, occurred_at, file_id, project_id, folder_id, original_name, size, mime_type
+        FROM delivered_events
+        WHERE project_id = $1 AND occurred_at >= $2 AND occurred_at < $3
+            AND (cardinality($4::text[]) = 0 OR event_type = ANY($4))
+        ORDER BY occurred_at, id
+        ON CONFLICT (id) DO NOTHING
+        "#,
+    )
+    .bind(project_id)
+    .bind(from)
+    .bind(to)
+    .bind(&types)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// Forget delivered events older than `retention_days`; they can no longer be
+/// replayed
+pub async fn purge_delivered_events(pool: &PgPool, retention_days: i32) -> Result<u64> {
+    let result = sqlx::query(
+        "DELETE FROM delivered_events WHERE delivered_at < NOW() - make_interval(days => $1)",
+    )
+    .bind(retention_days)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
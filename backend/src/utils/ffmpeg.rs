@@ -0,0 +1,58 @@
+use std::{ffi::OsStr, process::Stdio, time::Duration};
+use tokio::{io::AsyncWriteExt, process::Command};
+
+/// Longest an ffmpeg run may take before it is killed
+const FFMPEG_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Run ffmpeg (`FFMPEG_PATH`) with `args`, feeding `input` to `pipe:0`, and
+/// return what it wrote to `pipe:1`. Without `input` ffmpeg reads its input
+/// from a path in `args` (needed for containers it has to seek in).
+pub async fn run_ffmpeg<I, S>(
+    ffmpeg_path: &str,
+    args: I,
+    input: Option<Vec<u8>>,
+) -> Result<Vec<u8>, String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let mut child = Command::new(ffmpeg_path)
+        .args(["-hide_banner", "-loglevel", "error", "-nostdin"])
+        .args(args)
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("Failed to start {ffmpeg_path}: {e}"))?;
+
+    // Feed the input while the output is read, or a full stdout pipe would
+    // stall ffmpeg. It may stop reading early, so write errors are ignored.
+    let writer = input.map(|input| {
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        tokio::spawn(async move {
+            let _ = stdin.write_all(&input).await;
+        })
+    });
+
+    let output = tokio::time::timeout(FFMPEG_TIMEOUT, child.wait_with_output())
+        .await
+        .map_err(|_| format!("ffmpeg timed out after {}s", FFMPEG_TIMEOUT.as_secs()))?
+        .map_err(|e| format!("ffmpeg failed: {e}"))?;
+    if let Some(writer) = writer {
+        writer.abort();
+    }
+
+    if !output.status.success() {
+        return Err(format!(
+            "ffmpeg exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
@@ -0,0 +1,189 @@
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+
+use crate::error::{AppError, Result};
+
+/// Longest accepted file name, in bytes
+pub const MAX_FILE_NAME_LENGTH: usize = 255;
+
+/// Characters left unescaped in an RFC 5987 `filename*` value (`attr-char`)
+const ATTR_CHAR_ESCAPES: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'!')
+    .remove(b'#')
+    .remove(b'$')
+    .remove(b'&')
+    .remove(b'+')
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'^')
+    .remove(b'_')
+    .remove(b'`')
+    .remove(b'|')
+    .remove(b'~');
+
+/// Invisible formatting characters: zero-width spaces/joiners, the byte order
+/// mark and bidi overrides (which can disguise `gpj.exe` as `exe.jpg`)
+fn is_format_char(c: char) -> bool {
+    matches!(
+        c,
+        '\u{00AD}'
+            | '\u{061C}'
+            | '\u{200B}'..='\u{200F}'
+            | '\u{202A}'..='\u{202E}'
+            | '\u{2060}'..='\u{2064}'
+            | '\u{2066}'..='\u{2069}'
+            | '\u{FEFF}'
+    )
+}
+
+fn invalid_file_name(reason: &'static str, message: String) -> AppError {
+    AppError::InvalidUploadField {
+        field: "file".to_string(),
+        reason,
+        message,
+    }
+}
+
+/// Clean up a client-supplied file name: drop any directory part (some browsers
+/// send `C:\fakepath\name`), control and invisible formatting characters, and
+/// surrounding whitespace or trailing dots, then check what's left is usable.
+/// Other Unicode (CJK, emoji, accents) is kept as sent.
+pub fn sanitize_file_name(name: &str) -> Result<String> {
+    let base = name.rsplit(['/', '\\']).next().unwrap_or(name);
+    let cleaned: String = base
+        .chars()
+        .filter(|&c| !c.is_control() && !is_format_char(c))
+        .collect();
+    let cleaned = cleaned.trim().trim_end_matches('.').trim_end();
+
+    if cleaned.is_empty() {
+        return Err(invalid_file_name(
+            "invalid_file_name",
+            "File name is empty after removing invalid characters".to_string(),
+        ));
+    }
+    if cleaned.len() > MAX_FILE_NAME_LENGTH {
+        return Err(invalid_file_name(
+            "file_name_too_long",
+            format!("File name must be at most {MAX_FILE_NAME_LENGTH} bytes"),
+        ));
+    }
+    Ok(cleaned.to_string())
+}
+
+/// `Content-Disposition` value for serving `file_name`, e.g.
+/// `inline; filename="na_ve.txt"; filename*=UTF-8''na%C3%AFve.txt`.
+/// `filename` is an ASCII fallback for old clients (non-ASCII, quotes and
+/// backslashes become `_`); `filename*` (RFC 5987/6266) carries the exact name.
+pub fn content_disposition(disposition: &str, file_name: &str) -> String {
+    let fallback: String = file_name
+        .chars()
+        .map(|c| match c {
+            ' '..='~' if c != '"' && c != '\\' => c,
+            _ => '_',
+        })
+        .collect();
+    let encoded = utf8_percent_encode(file_name, ATTR_CHAR_ESCAPES);
+    format!("{disposition}; filename=\"{fallback}\"; filename*=UTF-8''{encoded}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_unicode_names() {
+        assert_eq!(sanitize_file_name("🎉 party.png").unwrap(), "🎉 party.png");
+        assert_eq!(sanitize_file_name("報告書.pdf").unwrap(), "報告書.pdf");
+        assert_eq!(sanitize_file_name("naïve.txt").unwrap(), "naïve.txt");
+    }
+
+    #[test]
+    fn keeps_quotes_and_semicolons() {
+        assert_eq!(
+            sanitize_file_name("say \"hi\".txt").unwrap(),
+            "say \"hi\".txt"
+        );
+        assert_eq!(sanitize_file_name("a;b.txt").unwrap(), "a;b.txt");
+    }
+
+    #[test]
+    fn strips_control_and_format_characters() {
+        assert_eq!(
+            sanitize_file_name("evil\r\nSet-Cookie: x.txt").unwrap(),
+            "evilSet-Cookie: x.txt"
+        );
+        assert_eq!(sanitize_file_name("gpj\u{202E}.exe").unwrap(), "gpj.exe");
+        assert_eq!(
+            sanitize_file_name("\u{FEFF}a\u{200B}b.txt").unwrap(),
+            "ab.txt"
+        );
+    }
+
+    #[test]
+    fn drops_directories_and_trailing_dots() {
+        assert_eq!(
+            sanitize_file_name("C:\\fakepath\\photo.jpg").unwrap(),
+            "photo.jpg"
+        );
+        assert_eq!(sanitize_file_name("../../etc/passwd").unwrap(), "passwd");
+        assert_eq!(sanitize_file_name("  notes.txt.. ").unwrap(), "notes.txt");
+    }
+
+    #[test]
+    fn rejects_empty_and_long_names() {
+        assert!(sanitize_file_name("").is_err());
+        assert!(sanitize_file_name(" .. ").is_err());
+        assert!(sanitize_file_name("dir/").is_err());
+        assert!(sanitize_file_name("\r\n").is_err());
+        assert!(sanitize_file_name(&"a".repeat(MAX_FILE_NAME_LENGTH)).is_ok());
+        assert!(sanitize_file_name(&"a".repeat(MAX_FILE_NAME_LENGTH + 1)).is_err());
+        // The limit is in bytes: each CJK character takes three
+        assert!(sanitize_file_name(&"報".repeat(MAX_FILE_NAME_LENGTH / 3 + 1)).is_err());
+    }
+
+    #[test]
+    fn content_disposition_ascii() {
+        assert_eq!(
+            content_disposition("attachment", "report.pdf"),
+            "attachment; filename=\"report.pdf\"; filename*=UTF-8''report.pdf"
+        );
+    }
+
+    #[test]
+    fn content_disposition_emoji_and_cjk() {
+        assert_eq!(
+            content_disposition("inline", "🎉 party.png"),
+            "inline; filename=\"_ party.png\"; filename*=UTF-8''%F0%9F%8E%89%20party.png"
+        );
+        assert_eq!(
+            content_disposition("inline", "報告書.pdf"),
+            "inline; filename=\"___.pdf\"; filename*=UTF-8''%E5%A0%B1%E5%91%8A%E6%9B%B8.pdf"
+        );
+    }
+
+    #[test]
+    fn content_disposition_escapes_quotes_and_semicolons() {
+        assert_eq!(
+            content_disposition("attachment", "say \"hi\".txt"),
+            "attachment; filename=\"say _hi_.txt\"; filename*=UTF-8''say%20%22hi%22.txt"
+        );
+        assert_eq!(
+            content_disposition("attachment", "a\\b.txt"),
+            "attachment; filename=\"a_b.txt\"; filename*=UTF-8''a%5Cb.txt"
+        );
+        assert_eq!(
+            content_disposition("attachment", "a;b.txt"),
+            "attachment; filename=\"a;b.txt\"; filename*=UTF-8''a%3Bb.txt"
+        );
+    }
+
+    #[test]
+    fn content_disposition_never_breaks_the_header() {
+        let value = content_disposition("attachment", "a\r\nSet-Cookie: x=1.txt");
+        assert_eq!(
+            value,
+            "attachment; filename=\"a__Set-Cookie: x=1.txt\"; filename*=UTF-8''a%0D%0ASet-Cookie%3A%20x%3D1.txt"
+        );
+        assert!(axum::http::HeaderValue::from_str(&value).is_ok());
+    }
+}
@@ -0,0 +1,54 @@
+use maxminddb::{geoip2, Reader};
+use std::net::IpAddr;
+
+use crate::error::{AppError, Result};
+
+pub type GeoIpReader = Reader<Vec<u8>>;
+
+/// Load a MaxMind GeoIP2/GeoLite2 Country (or City) database
+pub fn open_geoip_database(
+    path: &str,
+) -> std::result::Result<GeoIpReader, maxminddb::MaxMindDBError> {
+    Reader::open_readfile(path)
+}
+
+/// ISO 3166-1 alpha-2 country code for an IP, if the database knows it
+pub fn lookup_country(reader: &GeoIpReader, ip: IpAddr) -> Option<String> {
+    reader
+        .lookup::<geoip2::Country>(ip)
+        .ok()
+        .and_then(|record| record.country)
+        .and_then(|country| country.iso_code)
+        .map(|code| code.to_uppercase())
+}
+
+/// Enforce a project's country allow/block lists for a download.
+/// - Blocked country: 451 Unavailable For Legal Reasons
+/// - Allowlist set and country not on it (or unknown): 403
+pub fn check_geo_access(
+    country: Option<&str>,
+    allowed_countries: &[String],
+    blocked_countries: &[String],
+) -> Result<()> {
+    if let Some(country) = country {
+        if blocked_countries
+            .iter()
+            .any(|c| c.eq_ignore_ascii_case(country))
+        {
+            return Err(AppError::GeoBlocked(country.to_string()));
+        }
+    }
+
+    if !allowed_countries.is_empty() {
+        let allowed = country.is_some_and(|country| {
+            allowed_countries
+                .iter()
+                .any(|c| c.eq_ignore_ascii_case(country))
+        });
+        if !allowed {
+            return Err(AppError::GeoNotAllowed(country.map(str::to_string)));
+        }
+    }
+
+    Ok(())
+}
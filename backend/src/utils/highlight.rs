@@ -0,0 +1,712 @@
+/// A language the highlighter knows: how its comments, strings and keywords look
+pub struct Language {
+    /// Name used in `language-*` classes and Markdown code fences
+    pub name: &'static str,
+    /// File extensions and other fence names
+    aliases: &'static [&'static str],
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [char],
+    keywords: &'static [&'static str],
+    case_insensitive: bool,
+}
+
+const C_KEYWORDS: &[&str] = &[
+    "auto", "break", "case", "char", "const", "continue", "default", "do", "double", "else",
+    "enum", "extern", "float", "for", "goto", "if", "inline", "int", "long", "register", "return",
+    "short", "signed", "sizeof", "static", "struct", "switch", "typedef", "union", "unsigned",
+    "void", "volatile", "while", "NULL", "true", "false",
+];
+
+const JS_KEYWORDS: &[&str] = &[
+    "async",
+    "await",
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "debugger",
+    "default",
+    "delete",
+    "do",
+    "else",
+    "export",
+    "extends",
+    "false",
+    "finally",
+    "for",
+    "from",
+    "function",
+    "if",
+    "import",
+    "in",
+    "instanceof",
+    "let",
+    "new",
+    "null",
+    "of",
+    "return",
+    "static",
+    "super",
+    "switch",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typeof",
+    "undefined",
+    "var",
+    "void",
+    "while",
+    "yield",
+];
+
+const LANGUAGES: &[Language] = &[
+    Language {
+        name: "rust",
+        aliases: &["rs"],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"'],
+        keywords: &[
+            "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+            "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod",
+            "move", "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super",
+            "trait", "true", "type", "unsafe", "use", "where", "while",
+        ],
+        case_insensitive: false,
+    },
+    Language {
+        name: "javascript",
+        aliases: &["js", "mjs", "cjs", "jsx"],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\'', '`'],
+        keywords: JS_KEYWORDS,
+        case_insensitive: false,
+    },
+    Language {
+        name: "typescript",
+        aliases: &["ts", "tsx", "mts", "cts"],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\'', '`'],
+        keywords: &[
+            "abstract",
+            "any",
+            "as",
+            "async",
+            "await",
+            "break",
+            "case",
+            "catch",
+            "class",
+            "const",
+            "continue",
+            "declare",
+            "default",
+            "delete",
+            "do",
+            "else",
+            "enum",
+            "export",
+            "extends",
+            "false",
+            "finally",
+            "for",
+            "from",
+            "function",
+            "if",
+            "implements",
+            "import",
+            "in",
+            "instanceof",
+            "interface",
+            "keyof",
+            "let",
+            "namespace",
+            "never",
+            "new",
+            "null",
+            "of",
+            "private",
+            "protected",
+            "public",
+            "readonly",
+            "return",
+            "static",
+            "super",
+            "switch",
+            "this",
+            "throw",
+            "true",
+            "try",
+            "type",
+            "typeof",
+            "undefined",
+            "unknown",
+            "var",
+            "void",
+            "while",
+            "yield",
+        ],
+        case_insensitive: false,
+    },
+    Language {
+        name: "python",
+        aliases: &["py", "pyi"],
+        line_comments: &["#"],
+        block_comment: None,
+        quotes: &['"', '\''],
+        keywords: &[
+            "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class",
+            "continue", "def", "del", "elif", "else", "except", "finally", "for", "from", "global",
+            "if", "import", "in", "is", "lambda", "nonlocal", "not", "or", "pass", "raise",
+            "return", "self", "try", "while", "with", "yield",
+        ],
+        case_insensitive: false,
+    },
+    Language {
+        name: "go",
+        aliases: &["golang"],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\'', '`'],
+        keywords: &[
+            "break",
+            "case",
+            "chan",
+            "const",
+            "continue",
+            "default",
+            "defer",
+            "else",
+            "fallthrough",
+            "false",
+            "for",
+            "func",
+            "go",
+            "goto",
+            "if",
+            "import",
+            "interface",
+            "map",
+            "nil",
+            "package",
+            "range",
+            "return",
+            "select",
+            "struct",
+            "switch",
+            "true",
+            "type",
+            "var",
+        ],
+        case_insensitive: false,
+    },
+    Language {
+        name: "java",
+        aliases: &[],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\''],
+        keywords: &[
+            "abstract",
+            "boolean",
+            "break",
+            "byte",
+            "case",
+            "catch",
+            "char",
+            "class",
+            "continue",
+            "default",
+            "do",
+            "double",
+            "else",
+            "enum",
+            "extends",
+            "false",
+            "final",
+            "finally",
+            "float",
+            "for",
+            "if",
+            "implements",
+            "import",
+            "instanceof",
+            "int",
+            "interface",
+            "long",
+            "new",
+            "null",
+            "package",
+            "private",
+            "protected",
+            "public",
+            "return",
+            "short",
+            "static",
+            "super",
+            "switch",
+            "this",
+            "throw",
+            "throws",
+            "true",
+            "try",
+            "var",
+            "void",
+            "volatile",
+            "while",
+        ],
+        case_insensitive: false,
+    },
+    Language {
+        name: "c",
+        aliases: &["h"],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\''],
+        keywords: C_KEYWORDS,
+        case_insensitive: false,
+    },
+    Language {
+        name: "cpp",
+        aliases: &["c++", "cc", "cxx", "hpp", "hh", "hxx"],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\''],
+        keywords: &[
+            "auto",
+            "bool",
+            "break",
+            "case",
+            "catch",
+            "char",
+            "class",
+            "const",
+            "constexpr",
+            "continue",
+            "default",
+            "delete",
+            "do",
+            "double",
+            "else",
+            "enum",
+            "explicit",
+            "extern",
+            "false",
+            "float",
+            "for",
+            "friend",
+            "if",
+            "inline",
+            "int",
+            "long",
+            "namespace",
+            "new",
+            "nullptr",
+            "operator",
+            "override",
+            "private",
+            "protected",
+            "public",
+            "return",
+            "short",
+            "signed",
+            "sizeof",
+            "static",
+            "struct",
+            "switch",
+            "template",
+            "this",
+            "throw",
+            "true",
+            "try",
+            "typedef",
+            "typename",
+            "union",
+            "unsigned",
+            "using",
+            "virtual",
+            "void",
+            "volatile",
+            "while",
+        ],
+        case_insensitive: false,
+    },
+    Language {
+        name: "csharp",
+        aliases: &["cs", "c#"],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\''],
+        keywords: &[
+            "abstract",
+            "as",
+            "async",
+            "await",
+            "base",
+            "bool",
+            "break",
+            "case",
+            "catch",
+            "class",
+            "const",
+            "continue",
+            "decimal",
+            "default",
+            "delegate",
+            "do",
+            "double",
+            "else",
+            "enum",
+            "event",
+            "false",
+            "finally",
+            "for",
+            "foreach",
+            "if",
+            "in",
+            "int",
+            "interface",
+            "internal",
+            "is",
+            "lock",
+            "long",
+            "namespace",
+            "new",
+            "null",
+            "object",
+            "out",
+            "override",
+            "private",
+            "protected",
+            "public",
+            "readonly",
+            "ref",
+            "return",
+            "sealed",
+            "static",
+            "string",
+            "struct",
+            "switch",
+            "this",
+            "throw",
+            "true",
+            "try",
+            "typeof",
+            "using",
+            "var",
+            "virtual",
+            "void",
+            "while",
+        ],
+        case_insensitive: false,
+    },
+    Language {
+        name: "ruby",
+        aliases: &["rb"],
+        line_comments: &["#"],
+        block_comment: None,
+        quotes: &['"', '\''],
+        keywords: &[
+            "alias", "and", "begin", "break", "case", "class", "def", "do", "else", "elsif", "end",
+            "ensure", "false", "for", "if", "in", "module", "next", "nil", "not", "or", "redo",
+            "require", "rescue", "retry", "return", "self", "super", "then", "true", "undef",
+            "unless", "until", "when", "while", "yield",
+        ],
+        case_insensitive: false,
+    },
+    Language {
+        name: "php",
+        aliases: &[],
+        line_comments: &["//", "#"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\''],
+        keywords: &[
+            "abstract",
+            "and",
+            "array",
+            "as",
+            "break",
+            "case",
+            "catch",
+            "class",
+            "const",
+            "continue",
+            "default",
+            "do",
+            "echo",
+            "else",
+            "elseif",
+            "empty",
+            "extends",
+            "false",
+            "final",
+            "for",
+            "foreach",
+            "function",
+            "if",
+            "implements",
+            "include",
+            "interface",
+            "isset",
+            "namespace",
+            "new",
+            "null",
+            "private",
+            "protected",
+            "public",
+            "require",
+            "return",
+            "static",
+            "switch",
+            "throw",
+            "trait",
+            "true",
+            "try",
+            "use",
+            "var",
+            "while",
+        ],
+        case_insensitive: false,
+    },
+    Language {
+        name: "shell",
+        aliases: &["sh", "bash", "zsh"],
+        line_comments: &["#"],
+        block_comment: None,
+        quotes: &['"', '\''],
+        keywords: &[
+            "case", "do", "done", "echo", "elif", "else", "esac", "exit", "export", "fi", "for",
+            "function", "if", "in", "local", "return", "then", "until", "while",
+        ],
+        case_insensitive: false,
+    },
+    Language {
+        name: "sql",
+        aliases: &[],
+        line_comments: &["--"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['\''],
+        keywords: &[
+            "all",
+            "alter",
+            "and",
+            "as",
+            "asc",
+            "begin",
+            "by",
+            "case",
+            "commit",
+            "create",
+            "default",
+            "delete",
+            "desc",
+            "distinct",
+            "drop",
+            "else",
+            "end",
+            "exists",
+            "from",
+            "group",
+            "having",
+            "in",
+            "index",
+            "inner",
+            "insert",
+            "into",
+            "is",
+            "join",
+            "key",
+            "left",
+            "limit",
+            "not",
+            "null",
+            "offset",
+            "on",
+            "or",
+            "order",
+            "outer",
+            "primary",
+            "references",
+            "returning",
+            "right",
+            "rollback",
+            "select",
+            "set",
+            "table",
+            "then",
+            "union",
+            "update",
+            "values",
+            "when",
+            "where",
+            "with",
+        ],
+        case_insensitive: true,
+    },
+    Language {
+        name: "json",
+        aliases: &[],
+        line_comments: &[],
+        block_comment: None,
+        quotes: &['"'],
+        keywords: &["true", "false", "null"],
+        case_insensitive: false,
+    },
+    Language {
+        name: "yaml",
+        aliases: &["yml"],
+        line_comments: &["#"],
+        block_comment: None,
+        quotes: &['"', '\''],
+        keywords: &["true", "false", "null", "yes", "no"],
+        case_insensitive: false,
+    },
+    Language {
+        name: "toml",
+        aliases: &[],
+        line_comments: &["#"],
+        block_comment: None,
+        quotes: &['"', '\''],
+        keywords: &["true", "false"],
+        case_insensitive: false,
+    },
+    Language {
+        name: "css",
+        aliases: &["scss", "less"],
+        line_comments: &[],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\''],
+        keywords: &["important", "inherit", "initial", "none", "unset"],
+        case_insensitive: false,
+    },
+];
+
+/// Look up a language by name, file extension or common alias (case-insensitive)
+pub fn find_language(name: &str) -> Option<&'static Language> {
+    let name = name.trim().to_ascii_lowercase();
+    LANGUAGES
+        .iter()
+        .find(|language| language.name == name || language.aliases.contains(&name.as_str()))
+}
+
+/// Escape text for HTML content and attribute values
+pub(crate) fn push_escaped(out: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+}
+
+fn push_token(out: &mut String, class: &str, text: &str) {
+    out.push_str("<span class=\"tok-");
+    out.push_str(class);
+    out.push_str("\">");
+    push_escaped(out, text);
+    out.push_str("</span>");
+}
+
+/// Byte length of the string literal opening `code`. Backslashes escape the
+/// next character; only backtick strings run past the end of a line.
+fn string_len(code: &str, quote: char) -> usize {
+    let mut chars = code.char_indices().skip(1);
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '\n' if quote != '`' => return i,
+            c if c == quote => return i + c.len_utf8(),
+            _ => {}
+        }
+    }
+    code.len()
+}
+
+/// Escaped HTML for `code` with comments, strings, numbers and keywords
+/// wrapped in `<span class="tok-comment|tok-string|tok-number|tok-keyword">`.
+/// A lexical approximation, not a parser: good enough for previews.
+pub fn highlight(code: &str, language: &Language) -> String {
+    let mut out = String::with_capacity(code.len() + code.len() / 4);
+    let mut rest = code;
+    while let Some(c) = rest.chars().next() {
+        let len = if language
+            .line_comments
+            .iter()
+            .any(|prefix| rest.starts_with(prefix))
+        {
+            let len = rest.find('\n').unwrap_or(rest.len());
+            push_token(&mut out, "comment", &rest[..len]);
+            len
+        } else if let Some((open, close)) = language
+            .block_comment
+            .filter(|(open, _)| rest.starts_with(open))
+        {
+            let len = rest[open.len()..]
+                .find(close)
+                .map_or(rest.len(), |end| open.len() + end + close.len());
+            push_token(&mut out, "comment", &rest[..len]);
+            len
+        } else if language.quotes.contains(&c) {
+            let len = string_len(rest, c);
+            push_token(&mut out, "string", &rest[..len]);
+            len
+        } else if c.is_ascii_digit() {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '.' || c == '_'))
+                .unwrap_or(rest.len());
+            push_token(&mut out, "number", &rest[..len]);
+            len
+        } else if c.is_alphabetic() || c == '_' {
+            let len = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            let word = &rest[..len];
+            let is_keyword = if language.case_insensitive {
+                language
+                    .keywords
+                    .iter()
+                    .any(|keyword| keyword.eq_ignore_ascii_case(word))
+            } else {
+                language.keywords.contains(&word)
+            };
+            if is_keyword {
+                push_token(&mut out, "keyword", word);
+            } else {
+                push_escaped(&mut out, word);
+            }
+            len
+        } else {
+            push_escaped(&mut out, &rest[..c.len_utf8()]);
+            c.len_utf8()
+        };
+        rest = &rest[len..];
+    }
+    out
+}
+
+/// `<pre><code>` block for `code`, highlighted when the language is known
+pub fn render_code_block(code: &str, language: Option<&Language>, out: &mut String) {
+    match language {
+        Some(language) => {
+            out.push_str(&format!("<pre><code class=\"language-{}\">", language.name));
+            out.push_str(&highlight(code, language));
+        }
+        None => {
+            out.push_str("<pre><code>");
+            push_escaped(out, code);
+        }
+    }
+    out.push_str("\n</code></pre>\n");
+}
@@ -0,0 +1,50 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+/// How long a Host -> project mapping is trusted before re-checking the database
+const HOST_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// Bound on cached hosts so arbitrary Host headers can't grow memory without limit
+const HOST_CACHE_MAX_ENTRIES: usize = 10_000;
+
+/// Caches which project (if any) serves public files for a Host header,
+/// including negative results, so ordinary API requests don't hit the database
+#[derive(Debug, Default)]
+pub struct HostProjectCache {
+    entries: Mutex<HashMap<String, (Option<Uuid>, Instant)>>,
+}
+
+impl HostProjectCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cached mapping for `host`; outer None means unknown or expired
+    pub fn get(&self, host: &str) -> Option<Option<Uuid>> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(host)
+            .filter(|(_, cached_at)| cached_at.elapsed() < HOST_CACHE_TTL)
+            .map(|(project_id, _)| *project_id)
+    }
+
+    /// Forget `host`, e.g. once its custom domain is verified
+    pub fn remove(&self, host: &str) {
+        self.entries.lock().unwrap().remove(host);
+    }
+
+    pub fn insert(&self, host: String, project_id: Option<Uuid>) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= HOST_CACHE_MAX_ENTRIES {
+            entries.retain(|_, (_, cached_at)| cached_at.elapsed() < HOST_CACHE_TTL);
+            if entries.len() >= HOST_CACHE_MAX_ENTRIES {
+                entries.clear();
+            }
+        }
+        entries.insert(host, (project_id, Instant::now()));
+    }
+}
@@ -0,0 +1,223 @@
+use super::ffmpeg::run_ffmpeg;
+use crate::models::Project;
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// EXIF tag holding how the camera was turned
+const EXIF_ORIENTATION_TAG: u16 = 0x0112;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ImageFormat {
+    Jpeg,
+    Png,
+}
+
+/// An upload re-encoded by `optimize_image`
+#[derive(Debug)]
+pub struct OptimizedImage {
+    pub data: Vec<u8>,
+    pub mime_type: &'static str,
+    /// New file extension when the format changed
+    pub extension: Option<&'static str>,
+}
+
+/// Width and height from a PNG's IHDR chunk
+fn png_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    if !data.starts_with(PNG_SIGNATURE) || data.get(12..16)? != b"IHDR" {
+        return None;
+    }
+    let width = u32::from_be_bytes(data.get(16..20)?.try_into().ok()?);
+    let height = u32::from_be_bytes(data.get(20..24)?.try_into().ok()?);
+    Some((width, height))
+}
+
+/// Orientation (1-8) from the first IFD of an APP1 Exif segment
+fn exif_orientation(segment: &[u8]) -> Option<u8> {
+    let tiff = segment.strip_prefix(b"Exif\0\0")?;
+    let big_endian = match tiff.get(0..2)? {
+        b"MM" => true,
+        b"II" => false,
+        _ => return None,
+    };
+    let read_u16 = |at: usize| -> Option<u16> {
+        let bytes: [u8; 2] = tiff.get(at..at + 2)?.try_into().ok()?;
+        Some(if big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    };
+    let read_u32 = |at: usize| -> Option<u32> {
+        let bytes: [u8; 4] = tiff.get(at..at + 4)?.try_into().ok()?;
+        Some(if big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    };
+
+    let ifd = read_u32(4)? as usize;
+    let entries = read_u16(ifd)? as usize;
+    (0..entries)
+        .map(|n| ifd + 2 + n * 12)
+        .find(|&entry| read_u16(entry) == Some(EXIF_ORIENTATION_TAG))
+        .and_then(|entry| read_u16(entry + 8))
+        .and_then(|orientation| u8::try_from(orientation).ok())
+        .filter(|orientation| (1..=8).contains(orientation))
+}
+
+/// Width, height and EXIF orientation of a JPEG, from the markers before its
+/// first scan
+fn jpeg_info(data: &[u8]) -> Option<(u32, u32, u8)> {
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+    let mut orientation = 1;
+    let mut i = 2;
+    while i + 4 <= data.len() {
+        if data[i] != 0xFF {
+            return None;
+        }
+        let marker = data[i + 1];
+        // Fill bytes and markers without a length
+        if marker == 0xFF {
+            i += 1;
+            continue;
+        }
+        if marker == 0x01 || (0xD0..=0xD8).contains(&marker) {
+            i += 2;
+            continue;
+        }
+        let length = u16::from_be_bytes([data[i + 2], data[i + 3]]) as usize;
+        if length < 2 {
+            return None;
+        }
+        let segment = data.get(i + 4..i + 2 + length)?;
+        match marker {
+            0xE1 => {
+                if let Some(o) = exif_orientation(segment) {
+                    orientation = o;
+                }
+            }
+            // Start of frame (C4, C8 and CC are other tables)
+            0xC0..=0xCF if !matches!(marker, 0xC4 | 0xC8 | 0xCC) => {
+                let height = u16::from_be_bytes(segment.get(1..3)?.try_into().ok()?);
+                let width = u16::from_be_bytes(segment.get(3..5)?.try_into().ok()?);
+                return Some((width.into(), height.into(), orientation));
+            }
+            // Start of scan without a frame header
+            0xDA => return None,
+            _ => {}
+        }
+        i += 2 + length;
+    }
+    None
+}
+
+/// ffmpeg filter turning an image with EXIF `orientation` upright, since the
+/// tag is dropped when the image is re-encoded
+fn orientation_filter(orientation: u8) -> Option<&'static str> {
+    match orientation {
+        2 => Some("hflip"),
+        3 => Some("hflip,vflip"),
+        4 => Some("vflip"),
+        5 => Some("transpose=0"),
+        6 => Some("transpose=1"),
+        7 => Some("transpose=3"),
+        8 => Some("transpose=2"),
+        _ => None,
+    }
+}
+
+/// ffmpeg's MJPEG `-q:v` scale (2 best, 31 worst) for a 1-100 quality
+fn jpeg_qscale(quality: i32) -> i32 {
+    2 + (100 - quality.clamp(1, 100)) * 29 / 99
+}
+
+/// Apply the project's image rules to an upload: JPEGs and PNGs wider or
+/// higher than `image_max_dimension` are scaled down to fit (JPEGs
+/// re-encoded at `image_quality`), and with `image_png_to_webp` PNGs are
+/// stored as lossless WebP. Re-encoding drops metadata such as EXIF location.
+/// Returns `None` when no rule applies or the result isn't smaller.
+pub async fn optimize_image(
+    ffmpeg_path: &str,
+    project: &Project,
+    data: &[u8],
+    mime_type: &str,
+) -> Result<Option<OptimizedImage>, String> {
+    let (format, width, height, orientation) = match mime_type {
+        "image/jpeg" => match jpeg_info(data) {
+            Some((width, height, orientation)) => (ImageFormat::Jpeg, width, height, orientation),
+            None => return Ok(None),
+        },
+        "image/png" => match png_dimensions(data) {
+            Some((width, height)) => (ImageFormat::Png, width, height, 1),
+            None => return Ok(None),
+        },
+        _ => return Ok(None),
+    };
+    let max_dimension = project
+        .image_max_dimension
+        .filter(|&max| width.max(height) > max as u32);
+    let to_webp = format == ImageFormat::Png && project.image_png_to_webp;
+    if max_dimension.is_none() && !to_webp {
+        return Ok(None);
+    }
+
+    let mut filters: Vec<String> = orientation_filter(orientation)
+        .map(str::to_string)
+        .into_iter()
+        .collect();
+    if let Some(max) = max_dimension {
+        filters.push(format!(
+            "scale={max}:{max}:force_original_aspect_ratio=decrease:flags=lanczos"
+        ));
+    }
+
+    let mut args: Vec<String> = vec![
+        "-noautorotate".into(),
+        "-f".into(),
+        match format {
+            ImageFormat::Jpeg => "jpeg_pipe",
+            ImageFormat::Png => "png_pipe",
+        }
+        .into(),
+        "-i".into(),
+        "pipe:0".into(),
+    ];
+    if !filters.is_empty() {
+        args.extend(["-vf".into(), filters.join(",")]);
+    }
+    args.extend([
+        "-frames:v".into(),
+        "1".into(),
+        "-map_metadata".into(),
+        "-1".into(),
+    ]);
+    let qscale = jpeg_qscale(project.image_quality).to_string();
+    let (output_args, mime_type, extension) = match format {
+        ImageFormat::Png if to_webp => (
+            vec!["-c:v", "libwebp", "-lossless", "1", "-f", "webp"],
+            "image/webp",
+            Some("webp"),
+        ),
+        ImageFormat::Png => (vec!["-c:v", "png", "-f", "image2pipe"], "image/png", None),
+        ImageFormat::Jpeg => (
+            vec!["-c:v", "mjpeg", "-q:v", &qscale, "-f", "image2pipe"],
+            "image/jpeg",
+            None,
+        ),
+    };
+    args.extend(output_args.into_iter().map(String::from));
+    args.push("pipe:1".into());
+
+    let optimized = run_ffmpeg(ffmpeg_path, &args, Some(data.to_vec())).await?;
+    if optimized.is_empty() || optimized.len() >= data.len() {
+        return Ok(None);
+    }
+    Ok(Some(OptimizedImage {
+        data: optimized,
+        mime_type,
+        extension,
+    }))
+}
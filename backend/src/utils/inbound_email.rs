@@ -0,0 +1,358 @@
+use base64::{
+    alphabet,
+    engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig},
+    Engine,
+};
+use encoding_rs::{Encoding, UTF_8};
+use quoted_printable::ParseMode;
+
+/// Nested multiparts deeper than this are ignored
+const MAX_MIME_DEPTH: usize = 10;
+
+/// Mail clients don't always pad base64
+const LENIENT_BASE64: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/// An email as a provider's inbound webhook delivered it
+pub struct InboundEmail {
+    /// Address in the `From` header
+    pub sender: Option<String>,
+    pub recipients: Vec<String>,
+    pub attachments: Vec<EmailAttachment>,
+}
+
+pub struct EmailAttachment {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+/// The address in a header value like `Name <user@Example.com>`, with the
+/// domain lowercased (the local part is left alone; it may name a folder)
+pub fn email_address(value: &str) -> Option<String> {
+    let address = match (value.rfind('<'), value.rfind('>')) {
+        (Some(start), Some(end)) if start < end => &value[start + 1..end],
+        _ => value,
+    };
+    let (local, domain) = address.trim().rsplit_once('@')?;
+    if local.is_empty() || domain.is_empty() || domain.contains(char::is_whitespace) {
+        return None;
+    }
+    Some(format!("{local}@{}", domain.to_ascii_lowercase()))
+}
+
+/// Whether `sender` is on an allowlist of addresses and `@domain`s
+pub fn sender_allowed(allowed: &[String], sender: &str) -> bool {
+    let sender = sender.to_lowercase();
+    let domain = sender.rsplit_once('@').map(|(_, domain)| domain);
+    allowed.iter().any(|entry| {
+        entry == &sender
+            || entry
+                .strip_prefix('@')
+                .is_some_and(|allowed_domain| Some(allowed_domain) == domain)
+    })
+}
+
+/// Last segment of an attachment name some clients send with a path
+pub fn attachment_base_name(name: &str) -> &str {
+    name.rsplit(['/', '\\']).next().unwrap_or(name)
+}
+
+fn decode_charset(charset: &str, bytes: &[u8]) -> String {
+    Encoding::for_label(charset.trim().as_bytes())
+        .unwrap_or(UTF_8)
+        .decode_without_bom_handling(bytes)
+        .0
+        .into_owned()
+}
+
+/// One RFC 2047 encoded word (`=?charset?B|Q?text?=`) at the start of `s`:
+/// its text and length
+fn decode_encoded_word(s: &str) -> Option<(String, usize)> {
+    let rest = s.strip_prefix("=?")?;
+    let (charset, rest) = rest.split_once('?')?;
+    let (encoding, rest) = rest.split_once('?')?;
+    let end = rest.find("?=")?;
+    let text = &rest[..end];
+    if text.contains(char::is_whitespace) {
+        return None;
+    }
+    let bytes = match encoding {
+        "B" | "b" => LENIENT_BASE64.decode(text).ok()?,
+        "Q" | "q" => quoted_printable::decode(text.replace('_', " "), ParseMode::Robust).ok()?,
+        _ => return None,
+    };
+    // A charset may carry an RFC 2231 language suffix (`utf-8*en`)
+    let charset = charset.split('*').next().unwrap_or(charset);
+    Some((
+        decode_charset(charset, &bytes),
+        s.len() - rest[end + 2..].len(),
+    ))
+}
+
+/// A header value with its RFC 2047 encoded words decoded
+fn decode_header_value(value: &str) -> String {
+    let mut out = String::new();
+    let mut rest = value;
+    let mut after_word = false;
+    while let Some(start) = rest.find("=?") {
+        let (before, candidate) = rest.split_at(start);
+        match decode_encoded_word(candidate) {
+            Some((text, len)) => {
+                // Whitespace between two encoded words isn't part of the text
+                if !(after_word && before.trim().is_empty()) {
+                    out.push_str(before);
+                }
+                out.push_str(&text);
+                rest = &candidate[len..];
+                after_word = true;
+            }
+            None => {
+                out.push_str(before);
+                out.push_str("=?");
+                rest = &candidate[2..];
+                after_word = false;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// A MIME part: headers with lowercase names and unfolded values, and body
+struct Part<'a> {
+    headers: Vec<(String, String)>,
+    body: &'a [u8],
+}
+
+impl Part<'_> {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Index just past the line starting at `pos`
+fn line_end(data: &[u8], pos: usize) -> usize {
+    data[pos..]
+        .iter()
+        .position(|&b| b == b'\n')
+        .map_or(data.len(), |i| pos + i + 1)
+}
+
+fn split_part(raw: &[u8]) -> Part<'_> {
+    // Headers end at the first empty line
+    let mut pos = 0;
+    let (mut header_end, mut body_start) = (raw.len(), raw.len());
+    while pos < raw.len() {
+        let end = line_end(raw, pos);
+        if matches!(&raw[pos..end], b"\n" | b"\r\n") {
+            (header_end, body_start) = (pos, end);
+            break;
+        }
+        pos = end;
+    }
+
+    let mut headers: Vec<(String, String)> = Vec::new();
+    for line in String::from_utf8_lossy(&raw[..header_end]).lines() {
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+    }
+    Part {
+        headers,
+        body: &raw[body_start..],
+    }
+}
+
+/// `value; key=value; key="quoted"` as the lowercase value and parameters
+/// with lowercase keys
+fn parse_params(header: &str) -> (String, Vec<(String, String)>) {
+    let mut segments = Vec::new();
+    let mut current = String::new();
+    let mut chars = header.chars();
+    let mut quoted = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                current.push(c);
+            }
+            '\\' if quoted => {
+                current.push(c);
+                current.extend(chars.next());
+            }
+            ';' if !quoted => segments.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    segments.push(current);
+
+    let mut segments = segments.into_iter();
+    let value = segments
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    let params = segments
+        .filter_map(|segment| {
+            let (key, value) = segment.split_once('=')?;
+            let value = value.trim();
+            let value = match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+                Some(inner) => inner.replace("\\\"", "\"").replace("\\\\", "\\"),
+                None => value.to_string(),
+            };
+            Some((key.trim().to_ascii_lowercase(), value))
+        })
+        .collect();
+    (value, params)
+}
+
+/// A parameter, decoding RFC 2231 (`name*=utf-8''%E2%82%AC.pdf`, and
+/// `name*0*=`, `name*1=` continuations) and RFC 2047 encoded words
+fn param(params: &[(String, String)], name: &str) -> Option<String> {
+    let get = |key: String| {
+        params
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, v)| v.as_str())
+    };
+
+    let mut charset = None;
+    let mut bytes = Vec::new();
+    let mut found = false;
+    for n in 0..100 {
+        let extended = if n == 0 {
+            get(format!("{name}*")).or_else(|| get(format!("{name}*0*")))
+        } else {
+            get(format!("{name}*{n}*"))
+        };
+        if let Some(mut value) = extended {
+            // The first extended piece starts with `charset'language'`
+            if n == 0 {
+                let mut pieces = value.splitn(3, '\'');
+                if let (Some(cs), Some(_), Some(rest)) =
+                    (pieces.next(), pieces.next(), pieces.next())
+                {
+                    charset = Some(cs);
+                    value = rest;
+                }
+            }
+            bytes.extend(percent_encoding::percent_decode_str(value));
+        } else if let Some(value) = get(format!("{name}*{n}")) {
+            bytes.extend_from_slice(value.as_bytes());
+        } else {
+            break;
+        }
+        found = true;
+    }
+    if found {
+        return Some(decode_charset(charset.unwrap_or("utf-8"), &bytes));
+    }
+    get(name.to_string()).map(decode_header_value)
+}
+
+/// Bodies of the parts of a multipart body
+fn multipart_bodies<'a>(body: &'a [u8], boundary: &str) -> Vec<&'a [u8]> {
+    let delimiter = format!("--{boundary}");
+    let mut parts = Vec::new();
+    let mut start = None;
+    let mut pos = 0;
+    while pos < body.len() {
+        let end = line_end(body, pos);
+        let line = body[pos..end].trim_ascii_end();
+        if let Some(rest) = line.strip_prefix(delimiter.as_bytes()) {
+            if rest.is_empty() || rest == b"--" {
+                if let Some(part_start) = start {
+                    // The line break before a delimiter belongs to it
+                    let mut part_end = pos;
+                    if body[..part_end].ends_with(b"\n") {
+                        part_end -= 1;
+                    }
+                    if body[..part_end].ends_with(b"\r") {
+                        part_end -= 1;
+                    }
+                    parts.push(&body[part_start..part_end.max(part_start)]);
+                }
+                if rest == b"--" {
+                    return parts;
+                }
+                start = Some(end);
+            }
+        }
+        pos = end;
+    }
+    // Unterminated: keep what arrived of the last part
+    if let Some(part_start) = start {
+        parts.push(&body[part_start..]);
+    }
+    parts
+}
+
+fn decode_body(part: &Part) -> Option<Vec<u8>> {
+    let encoding = part
+        .header("content-transfer-encoding")
+        .map(|value| value.trim().to_ascii_lowercase());
+    match encoding.as_deref() {
+        Some("base64") => {
+            let clean: Vec<u8> = part
+                .body
+                .iter()
+                .copied()
+                .filter(|b| !b.is_ascii_whitespace())
+                .collect();
+            LENIENT_BASE64.decode(clean).ok()
+        }
+        Some("quoted-printable") => quoted_printable::decode(part.body, ParseMode::Robust).ok(),
+        _ => Some(part.body.to_vec()),
+    }
+}
+
+fn collect_attachments(raw: &[u8], depth: usize, out: &mut Vec<EmailAttachment>) {
+    let part = split_part(raw);
+    let (content_type, type_params) =
+        parse_params(part.header("content-type").unwrap_or("text/plain"));
+    if content_type.starts_with("multipart/") {
+        if depth >= MAX_MIME_DEPTH {
+            return;
+        }
+        if let Some(boundary) = param(&type_params, "boundary") {
+            for body in multipart_bodies(part.body, &boundary) {
+                collect_attachments(body, depth + 1, out);
+            }
+        }
+        return;
+    }
+
+    let (disposition, disposition_params) = part
+        .header("content-disposition")
+        .map(parse_params)
+        .unwrap_or_default();
+    // Images shown in the message body (referenced by Content-ID) aren't
+    // attachments; parts without a file name are the body itself
+    if disposition == "inline" && part.header("content-id").is_some() {
+        return;
+    }
+    let Some(name) = param(&disposition_params, "filename").or_else(|| param(&type_params, "name"))
+    else {
+        return;
+    };
+    if let Some(data) = decode_body(&part) {
+        out.push(EmailAttachment { name, data });
+    }
+}
+
+/// Attachments of a raw MIME message
+pub fn mime_attachments(raw: &[u8]) -> Vec<EmailAttachment> {
+    let mut attachments = Vec::new();
+    collect_attachments(raw, 0, &mut attachments);
+    attachments
+}
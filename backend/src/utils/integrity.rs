@@ -0,0 +1,345 @@
+use sha2::{Digest, Sha256};
+use sqlx::{FromRow, PgPool};
+use std::{io, sync::Arc};
+use uuid::Uuid;
+
+use crate::{
+    error::{AppError, Result},
+    models::{File, IntegrityRun, Project, PROJECT_COLUMNS},
+};
+
+use super::{
+    cold_storage::{ColdStorage, COLD_TIER},
+    compression::{zstd_decompress, ZSTD_ENCODING},
+    metrics::Metrics,
+    notify::notify_file_corrupted,
+};
+
+pub const INTEGRITY_RUN_COLUMNS: &str = "id, status, sample_size, checked_files, checked_bytes, corrupted_files, skipped_files, last_error, started_at, finished_at";
+
+/// Files checked per progress update
+const INTEGRITY_BATCH_SIZE: usize = 100;
+
+#[derive(FromRow)]
+struct StoredFile {
+    #[sqlx(flatten)]
+    file: File,
+    stored_size: Option<i64>,
+    content_hash: Option<String>,
+}
+
+/// What is wrong with a blob
+#[derive(Debug, Clone, Copy)]
+enum Problem {
+    Missing,
+    SizeMismatch,
+    Unreadable,
+    HashMismatch,
+}
+
+impl Problem {
+    fn as_str(self) -> &'static str {
+        match self {
+            Problem::Missing => "missing",
+            Problem::SizeMismatch => "size_mismatch",
+            Problem::Unreadable => "unreadable",
+            Problem::HashMismatch => "hash_mismatch",
+        }
+    }
+}
+
+enum Outcome {
+    /// The blob matches its recorded size (and hash, if one was recorded)
+    Passed(u64),
+    Failed(Problem, String),
+    /// The blob couldn't be fetched for reasons unrelated to its health
+    Skipped(String),
+}
+
+/// Record a new integrity run; only one may run at a time.
+/// `sample_size` of `None` checks every file.
+pub async fn begin_integrity_run(pool: &PgPool, sample_size: Option<i64>) -> Result<IntegrityRun> {
+    let active: Option<Uuid> =
+        sqlx::query_scalar("SELECT id FROM integrity_runs WHERE status = 'running' LIMIT 1")
+            .fetch_optional(pool)
+            .await?;
+    if let Some(active) = active {
+        return Err(AppError::Conflict(format!(
+            "Integrity check {active} is already in progress"
+        )));
+    }
+
+    Ok(sqlx::query_as::<_, IntegrityRun>(&format!(
+        "INSERT INTO integrity_runs (sample_size) VALUES ($1) RETURNING {INTEGRITY_RUN_COLUMNS}"
+    ))
+    .bind(sample_size)
+    .fetch_one(pool)
+    .await?)
+}
+
+/// Read a blob back and compare it with what was recorded at upload
+async fn check_file(cold_storage: Option<&ColdStorage>, stored: &StoredFile) -> Outcome {
+    let file = &stored.file;
+    let data = if file.storage_tier == COLD_TIER {
+        let Some(cold) = cold_storage else {
+            return Outcome::Skipped("Cold storage is not configured".to_string());
+        };
+        match cold.read(file).await {
+            Ok(Some(data)) => data,
+            Ok(None) => {
+                return Outcome::Failed(Problem::Missing, "Cold copy not found".to_string())
+            }
+            Err(e) => return Outcome::Skipped(format!("Failed to read cold copy: {e}")),
+        }
+    } else {
+        match tokio::fs::read(&file.file_path).await {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Outcome::Failed(Problem::Missing, "Blob not found on disk".to_string())
+            }
+            Err(e) => return Outcome::Failed(Problem::Unreadable, format!("Failed to read: {e}")),
+        }
+    };
+
+    // Files without a recorded stored_size predate compression and are stored as-is
+    let expected_size = stored.stored_size.unwrap_or(file.size);
+    if data.len() as i64 != expected_size {
+        return Outcome::Failed(
+            Problem::SizeMismatch,
+            format!("Expected {expected_size} bytes, found {}", data.len()),
+        );
+    }
+    let stored_bytes = data.len() as u64;
+
+    // Files uploaded before hashes were recorded only get the size check
+    let Some(expected_hash) = stored.content_hash.clone() else {
+        return Outcome::Passed(stored_bytes);
+    };
+    let compressed = file.storage_encoding.as_deref() == Some(ZSTD_ENCODING);
+    let outcome = tokio::task::spawn_blocking(move || {
+        let content = if compressed {
+            match zstd_decompress(&data) {
+                Ok(content) => content,
+                Err(e) => {
+                    return Outcome::Failed(
+                        Problem::Unreadable,
+                        format!("Failed to decompress: {e}"),
+                    )
+                }
+            }
+        } else {
+            data
+        };
+        let actual = hex::encode(Sha256::digest(&content));
+        if actual.eq_ignore_ascii_case(&expected_hash) {
+            Outcome::Passed(stored_bytes)
+        } else {
+            Outcome::Failed(
+                Problem::HashMismatch,
+                format!("Expected SHA-256 {expected_hash}, found {actual}"),
+            )
+        }
+    })
+    .await;
+    outcome.unwrap_or_else(|e| Outcome::Skipped(format!("Check task failed: {e}")))
+}
+
+/// Open (or refresh) a failure for a file. The file must still have the blob
+/// that was checked, so files replaced or deleted mid-check aren't flagged.
+/// Returns whether the file was checked as it is now; newly flagged files
+/// notify the project's subscribed channels.
+async fn record_failure(
+    pool: &PgPool,
+    run_id: Uuid,
+    file: &File,
+    problem: Problem,
+    detail: &str,
+) -> Result<bool> {
+    let mut tx = pool.begin().await?;
+    let inserted: Option<bool> = sqlx::query_scalar(
+        r#"
+        INSERT INTO integrity_failures (file_id, project_id, run_id, problem, detail)
+        SELECT id, project_id, $1, $2, $3 FROM files
+        WHERE id = $4 AND file_path = $5 AND upload_date = $6 AND storage_tier = $7
+        ON CONFLICT (file_id) WHERE resolved_at IS NULL
+        DO UPDATE SET run_id = EXCLUDED.run_id, problem = EXCLUDED.problem,
+                      detail = EXCLUDED.detail, last_seen_at = NOW()
+        RETURNING xmax = 0
+        "#,
+    )
+    .bind(run_id)
+    .bind(problem.as_str())
+    .bind(detail)
+    .bind(file.id)
+    .bind(&file.file_path)
+    .bind(file.upload_date)
+    .bind(&file.storage_tier)
+    .fetch_optional(&mut *tx)
+    .await?;
+    let Some(inserted) = inserted else {
+        return Ok(false);
+    };
+
+    if inserted {
+        tracing::warn!(
+            "Integrity check flagged file {} ({}): {}",
+            file.id,
+            problem.as_str(),
+            detail
+        );
+        let project = sqlx::query_as::<_, Project>(&format!(
+            "SELECT {PROJECT_COLUMNS} FROM projects WHERE id = $1"
+        ))
+        .bind(file.project_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        let folder: Option<String> = match file.folder_id {
+            Some(folder_id) => {
+                sqlx::query_scalar("SELECT path FROM folders WHERE id = $1")
+                    .bind(folder_id)
+                    .fetch_optional(&mut *tx)
+                    .await?
+            }
+            None => None,
+        };
+        notify_file_corrupted(&mut tx, &project, file, folder.as_deref()).await?;
+    }
+    tx.commit().await?;
+    Ok(true)
+}
+
+async fn perform_integrity_run(
+    pool: &PgPool,
+    cold_storage: Option<&ColdStorage>,
+    metrics: &Metrics,
+    run: &IntegrityRun,
+) -> Result<()> {
+    // The sample is fixed up front so skipped files aren't picked again
+    let file_ids: Vec<Uuid> = sqlx::query_scalar(
+        "SELECT id FROM files ORDER BY integrity_checked_at NULLS FIRST, id LIMIT $1",
+    )
+    .bind(run.sample_size)
+    .fetch_all(pool)
+    .await?;
+
+    for batch in file_ids.chunks(INTEGRITY_BATCH_SIZE) {
+        let files = sqlx::query_as::<_, StoredFile>(
+            r#"
+            SELECT id, project_id, folder_id, original_name, stored_name, file_path, size, mime_type, upload_date, storage_encoding, precompressed_encodings, storage_tier, moderation_status, stored_size, content_hash
+            FROM files WHERE id = ANY($1)
+            "#,
+        )
+        .bind(batch)
+        .fetch_all(pool)
+        .await?;
+
+        let (mut passed, mut checked) = (Vec::new(), Vec::new());
+        let (mut bytes, mut corrupted, mut skipped) = (0i64, 0i64, 0i64);
+        let mut last_error = None;
+        for stored in &files {
+            let file = &stored.file;
+            match check_file(cold_storage, stored).await {
+                Outcome::Passed(size) => {
+                    passed.push(file.id);
+                    checked.push(file.id);
+                    bytes += size as i64;
+                }
+                Outcome::Failed(problem, detail) => {
+                    if record_failure(pool, run.id, file, problem, &detail).await? {
+                        checked.push(file.id);
+                        corrupted += 1;
+                    } else {
+                        skipped += 1;
+                    }
+                }
+                Outcome::Skipped(reason) => {
+                    tracing::warn!("Integrity check skipped file {}: {}", file.id, reason);
+                    skipped += 1;
+                    last_error = Some(format!("File {}: {reason}", file.id));
+                }
+            }
+        }
+
+        sqlx::query(
+            "UPDATE integrity_failures SET resolved_at = NOW() WHERE file_id = ANY($1) AND resolved_at IS NULL",
+        )
+        .bind(&passed)
+        .execute(pool)
+        .await?;
+        sqlx::query("UPDATE files SET integrity_checked_at = NOW() WHERE id = ANY($1)")
+            .bind(&checked)
+            .execute(pool)
+            .await?;
+        sqlx::query(
+            r#"
+            UPDATE integrity_runs
+            SET checked_files = checked_files + $1, checked_bytes = checked_bytes + $2,
+                corrupted_files = corrupted_files + $3, skipped_files = skipped_files + $4,
+                last_error = COALESCE($5, last_error)
+            WHERE id = $6
+            "#,
+        )
+        .bind(checked.len() as i64)
+        .bind(bytes)
+        .bind(corrupted)
+        .bind(skipped)
+        .bind(&last_error)
+        .bind(run.id)
+        .execute(pool)
+        .await?;
+        metrics.record_integrity_checks(checked.len() as u64);
+    }
+
+    sqlx::query(
+        "UPDATE integrity_runs SET status = 'completed', finished_at = NOW() WHERE id = $1",
+    )
+    .bind(run.id)
+    .execute(pool)
+    .await?;
+    let open_failures: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM integrity_failures WHERE resolved_at IS NULL")
+            .fetch_one(pool)
+            .await?;
+    metrics.record_integrity_run(open_failures);
+    Ok(())
+}
+
+/// Run an integrity check started with `begin_integrity_run`, recording failure
+/// on the run row
+pub async fn run_integrity_check(
+    pool: PgPool,
+    cold_storage: Option<Arc<ColdStorage>>,
+    metrics: Arc<Metrics>,
+    run: IntegrityRun,
+) {
+    let Err(e) = perform_integrity_run(&pool, cold_storage.as_deref(), &metrics, &run).await else {
+        tracing::info!("Integrity check {} completed", run.id);
+        return;
+    };
+
+    tracing::warn!("Integrity check {} failed: {}", run.id, e);
+    let result = sqlx::query(
+        "UPDATE integrity_runs SET status = 'failed', last_error = $1, finished_at = NOW() WHERE id = $2",
+    )
+    .bind(e.to_string())
+    .bind(run.id)
+    .execute(&pool)
+    .await;
+    if let Err(e) = result {
+        tracing::warn!("Failed to record integrity check failure: {}", e);
+    }
+}
+
+/// Mark integrity runs left running by a previous process as failed (called at startup)
+pub async fn fail_interrupted_integrity_runs(pool: &PgPool) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE integrity_runs
+        SET status = 'failed', last_error = 'Interrupted by server restart', finished_at = NOW()
+        WHERE status = 'running'
+        "#,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
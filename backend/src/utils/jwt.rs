@@ -28,6 +28,31 @@ pub struct RefreshTokenClaims {
     pub iat: i64,
 }
 
+/// Upload policy claims - short-lived permission for a browser to upload directly
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UploadPolicyClaims {
+    pub sub: String, // Project ID
+    pub folder_path: Option<String>,
+    pub max_size: Option<usize>,
+    /// Allowed MIME types; `type/*` wildcards allowed, empty means any
+    pub content_types: Vec<String>,
+    pub token_type: String, // "upload_policy"
+    pub exp: i64,
+    pub iat: i64,
+}
+
+/// Folder access claims - short-lived read access to every file under a folder,
+/// carried in a cookie
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FolderAccessClaims {
+    pub sub: String, // Project ID
+    /// `None` covers the whole project
+    pub folder_path: Option<String>,
+    pub token_type: String, // "folder_access"
+    pub exp: i64,
+    pub iat: i64,
+}
+
 /// Legacy claims for backward compatibility during migration
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
@@ -140,6 +165,94 @@ pub fn verify_refresh_token(token: &str, secret: &str) -> Result<RefreshTokenCla
     Ok(claims)
 }
 
+/// Create a signed upload policy for a project
+pub fn create_upload_policy_token(
+    project_id: Uuid,
+    folder_path: Option<String>,
+    max_size: Option<usize>,
+    content_types: Vec<String>,
+    secret: &str,
+    expiry_seconds: i64,
+) -> Result<(String, i64)> {
+    let now = Utc::now();
+    let claims = UploadPolicyClaims {
+        sub: project_id.to_string(),
+        folder_path,
+        max_size,
+        content_types,
+        token_type: "upload_policy".to_string(),
+        iat: now.timestamp(),
+        exp: (now + Duration::seconds(expiry_seconds)).timestamp(),
+    };
+    let token = encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(secret.as_ref()),
+    )
+    .map_err(|e| AppError::TokenError(e.to_string()))?;
+
+    Ok((token, claims.exp))
+}
+
+/// Verify upload policy (validates token_type = "upload_policy")
+pub fn verify_upload_policy_token(token: &str, secret: &str) -> Result<UploadPolicyClaims> {
+    let claims = decode::<UploadPolicyClaims>(
+        token,
+        &DecodingKey::from_secret(secret.as_ref()),
+        &Validation::default(),
+    )
+    .map(|data| data.claims)
+    .map_err(|e| AppError::TokenError(e.to_string()))?;
+
+    if claims.token_type != "upload_policy" {
+        return Err(AppError::TokenError("Invalid token type".to_string()));
+    }
+
+    Ok(claims)
+}
+
+/// Create a signed folder access grant for a project
+pub fn create_folder_access_token(
+    project_id: Uuid,
+    folder_path: Option<String>,
+    secret: &str,
+    expiry_seconds: i64,
+) -> Result<(String, i64)> {
+    let now = Utc::now();
+    let claims = FolderAccessClaims {
+        sub: project_id.to_string(),
+        folder_path,
+        token_type: "folder_access".to_string(),
+        iat: now.timestamp(),
+        exp: (now + Duration::seconds(expiry_seconds)).timestamp(),
+    };
+    let token = encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(secret.as_ref()),
+    )
+    .map_err(|e| AppError::TokenError(e.to_string()))?;
+
+    Ok((token, claims.exp))
+}
+
+/// Verify folder access grant (validates token_type = "folder_access")
+pub fn verify_folder_access_token(token: &str, secret: &str) -> Result<FolderAccessClaims> {
+    let claims = decode::<FolderAccessClaims>(
+        token,
+        &DecodingKey::from_secret(secret.as_ref()),
+        &Validation::default(),
+    )
+    .map(|data| data.claims)
+    .map_err(|e| AppError::TokenError(e.to_string()))?;
+
+    if claims.token_type != "folder_access" {
+        return Err(AppError::TokenError("Invalid token type".to_string()));
+    }
+
+    Ok(claims)
+}
+
 /// Hash a refresh token for secure database storage
 pub fn hash_token(token: &str) -> String {
     let mut hasher = Sha256::new();
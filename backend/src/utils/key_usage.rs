@@ -0,0 +1,78 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::{collections::HashMap, net::IpAddr, sync::Mutex};
+use uuid::Uuid;
+
+#[derive(Debug)]
+struct PendingUsage {
+    count: i64,
+    last_used_at: DateTime<Utc>,
+    last_ip: IpAddr,
+}
+
+/// Buffers API key usage in memory so requests never wait on a write.
+/// `flush` is called periodically from a background task.
+#[derive(Debug, Default)]
+pub struct ApiKeyUsageTracker {
+    pending: Mutex<HashMap<Uuid, PendingUsage>>,
+}
+
+impl ApiKeyUsageTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count one request made with `api_key` from `ip`
+    pub fn record(&self, api_key: Uuid, ip: IpAddr) {
+        let now = Utc::now();
+        let mut pending = self.pending.lock().unwrap();
+        let usage = pending.entry(api_key).or_insert(PendingUsage {
+            count: 0,
+            last_used_at: now,
+            last_ip: ip,
+        });
+        usage.count += 1;
+        usage.last_used_at = now;
+        usage.last_ip = ip;
+    }
+
+    /// Write buffered usage to the projects table. Keys that don't match a project
+    /// (mistyped or already rotated) are dropped by the join.
+    pub async fn flush(&self, pool: &PgPool) -> Result<(), sqlx::Error> {
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap());
+        if pending.is_empty() {
+            return Ok(());
+        }
+
+        let mut keys = Vec::with_capacity(pending.len());
+        let mut counts = Vec::with_capacity(pending.len());
+        let mut used_at = Vec::with_capacity(pending.len());
+        let mut ips = Vec::with_capacity(pending.len());
+        for (key, usage) in pending {
+            keys.push(key);
+            counts.push(usage.count);
+            used_at.push(usage.last_used_at);
+            ips.push(usage.last_ip.to_string());
+        }
+
+        sqlx::query(
+            r#"
+            UPDATE projects p
+            SET api_key_request_count = p.api_key_request_count + u.count,
+                api_key_last_used_at = u.last_used_at,
+                api_key_last_used_ip = u.ip::inet
+            FROM UNNEST($1::uuid[], $2::bigint[], $3::timestamptz[], $4::text[])
+                AS u(api_key, count, last_used_at, ip)
+            WHERE p.api_key = u.api_key
+            "#,
+        )
+        .bind(&keys)
+        .bind(&counts)
+        .bind(&used_at)
+        .bind(&ips)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+}
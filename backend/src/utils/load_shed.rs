@@ -0,0 +1,66 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// How often the runtime's scheduling delay is sampled
+pub const LAG_SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Tracks how busy the server is: requests in flight (until their response body
+/// is sent) and how long ready tasks wait for the runtime to poll them
+#[derive(Debug, Default)]
+pub struct LoadShedder {
+    in_flight: AtomicUsize,
+    /// Smoothed scheduling delay in microseconds
+    lag_micros: AtomicU64,
+}
+
+/// Counts a request as in flight until dropped
+#[derive(Debug)]
+pub struct InFlightRequest(Arc<LoadShedder>);
+
+impl Drop for InFlightRequest {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl LoadShedder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn begin(self: &Arc<Self>) -> InFlightRequest {
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        InFlightRequest(Arc::clone(self))
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Relaxed)
+    }
+
+    pub fn lag(&self) -> Duration {
+        Duration::from_micros(self.lag_micros.load(Ordering::Relaxed))
+    }
+
+    /// Whether either limit is reached (0 disables a limit)
+    pub fn is_saturated(&self, max_in_flight: usize, max_lag: Duration) -> bool {
+        (max_in_flight > 0 && self.in_flight() >= max_in_flight)
+            || (!max_lag.is_zero() && self.lag() >= max_lag)
+    }
+
+    /// Sleep for `LAG_SAMPLE_INTERVAL` and fold how late the wake-up was into
+    /// the smoothed lag. Run in a loop on the request runtime.
+    pub async fn sample_lag(&self) {
+        let started = Instant::now();
+        tokio::time::sleep(LAG_SAMPLE_INTERVAL).await;
+        let lag = started.elapsed().saturating_sub(LAG_SAMPLE_INTERVAL);
+        let lag = lag.as_micros().min(u64::MAX as u128) as u64;
+        let previous = self.lag_micros.load(Ordering::Relaxed);
+        self.lag_micros
+            .store((previous * 3 + lag) / 4, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,181 @@
+use rand::{distributions::Alphanumeric, Rng};
+use sqlx::PgPool;
+use std::{net::IpAddr, sync::Arc};
+use uuid::Uuid;
+
+use super::{jwt::hash_token, mailer::Mailer};
+use crate::error::Result;
+
+/// Length of the token that reports a login from its alert email
+const REPORT_TOKEN_LENGTH: usize = 43;
+
+/// A successful password login, checked against the user's known devices
+#[derive(Debug, Clone)]
+pub struct LoginEvent {
+    pub user_id: Uuid,
+    pub email: String,
+    /// Refresh token family of the session the login started
+    pub family_id: Uuid,
+    pub ip: IpAddr,
+    pub user_agent: Option<String>,
+    /// Country from GeoIP, when `GEOIP_DATABASE_PATH` is set
+    pub country: Option<String>,
+}
+
+/// User-Agent without version numbers, so a browser update isn't a new device
+fn device_name(user_agent: Option<&str>) -> String {
+    let mut device = String::new();
+    for c in user_agent.unwrap_or("").chars() {
+        if c.is_ascii_digit() || (c == '.' && device.ends_with(|p: char| !p.is_alphabetic())) {
+            continue;
+        }
+        device.push(c);
+    }
+    device
+}
+
+/// Network a login came from: /24 for IPv4, /48 for IPv6
+fn ip_prefix(ip: IpAddr) -> String {
+    match ip.to_canonical() {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+            format!("{a}.{b}.{c}.0/24")
+        }
+        IpAddr::V6(ip) => {
+            let s = ip.segments();
+            format!("{:x}:{:x}:{:x}::/48", s[0], s[1], s[2])
+        }
+    }
+}
+
+/// Record the device and network of `login`. When the user has signed in
+/// before but never from this device, or from this country (this network
+/// when the country is unknown), store an alert, write it to the audit log
+/// and email the user a token to report it. A user's first recorded login
+/// only sets the baseline.
+pub async fn check_login(
+    pool: &PgPool,
+    mailer: Option<&Mailer>,
+    app_url: Option<&str>,
+    login: &LoginEvent,
+) -> Result<()> {
+    let device = device_name(login.user_agent.as_deref());
+    let prefix = ip_prefix(login.ip);
+
+    let (seen_before, known_device, known_location): (bool, bool, bool) = sqlx::query_as(
+        r#"
+        SELECT
+            COUNT(*) > 0,
+            COALESCE(BOOL_OR(device = $2), FALSE),
+            COALESCE(BOOL_OR(CASE WHEN $4::text IS NULL THEN ip_prefix = $3::cidr ELSE country = $4 END), FALSE)
+        FROM login_devices
+        WHERE user_id = $1
+        "#,
+    )
+    .bind(login.user_id)
+    .bind(&device)
+    .bind(&prefix)
+    .bind(&login.country)
+    .fetch_one(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO login_devices (user_id, device, ip_prefix, country)
+        VALUES ($1, $2, $3::cidr, $4)
+        ON CONFLICT (user_id, device, ip_prefix)
+        DO UPDATE SET last_seen_at = NOW(), country = COALESCE(EXCLUDED.country, login_devices.country)
+        "#,
+    )
+    .bind(login.user_id)
+    .bind(&device)
+    .bind(&prefix)
+    .bind(&login.country)
+    .execute(pool)
+    .await?;
+
+    if !seen_before || (known_device && known_location) {
+        return Ok(());
+    }
+
+    let report_token: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(REPORT_TOKEN_LENGTH)
+        .map(char::from)
+        .collect();
+    let alert_id: Uuid = sqlx::query_scalar(
+        r#"
+        INSERT INTO login_alerts (user_id, family_id, user_agent, ip_address, country, new_device, new_location, report_token_hash)
+        VALUES ($1, $2, $3, $4::inet, $5, $6, $7, $8)
+        RETURNING id
+        "#,
+    )
+    .bind(login.user_id)
+    .bind(login.family_id)
+    .bind(&login.user_agent)
+    .bind(login.ip.to_string())
+    .bind(&login.country)
+    .bind(!known_device)
+    .bind(!known_location)
+    .bind(hash_token(&report_token))
+    .fetch_one(pool)
+    .await?;
+
+    tracing::info!(
+        target: "audit",
+        user_id = %login.user_id,
+        email = %login.email,
+        ip = %login.ip,
+        country = login.country.as_deref().unwrap_or("unknown"),
+        user_agent = login.user_agent.as_deref().unwrap_or(""),
+        new_device = !known_device,
+        new_location = !known_location,
+        %alert_id,
+        "Login from a new device or location"
+    );
+
+    let Some(mailer) = mailer else {
+        return Ok(());
+    };
+    let location = match login.country {
+        Some(ref country) => format!("{} (country: {country})", login.ip),
+        None => login.ip.to_string(),
+    };
+    let report = match app_url {
+        Some(url) => format!("{url}/report-login?token={report_token}"),
+        None => format!("Send this code to POST /api/v1/auth/login-alerts/report: {report_token}"),
+    };
+    let body = format!(
+        "Your FileRunner account was just signed in to from a {what}.\n\n\
+         Device: {}\nAddress: {location}\n\n\
+         If this was you, there's nothing to do. If it wasn't, report it to sign \
+         that session out, then change your password:\n\n{report}\n",
+        login.user_agent.as_deref().unwrap_or("unknown"),
+        what = match (known_device, known_location) {
+            (false, false) => "new device and location",
+            (false, true) => "new device",
+            _ => "new location",
+        },
+    );
+    if let Err(e) = mailer
+        .send(&login.email, "New sign-in to your FileRunner account", body)
+        .await
+    {
+        tracing::warn!("Failed to send login alert {}: {}", alert_id, e);
+    }
+    Ok(())
+}
+
+/// Run `check_login` in the background so the login response isn't held up
+pub fn spawn_login_check(
+    pool: PgPool,
+    mailer: Option<Arc<Mailer>>,
+    app_url: Option<String>,
+    login: LoginEvent,
+) {
+    tokio::spawn(async move {
+        if let Err(e) = check_login(&pool, mailer.as_deref(), app_url.as_deref(), &login).await {
+            tracing::warn!("Login check failed for user {}: {}", login.user_id, e);
+        }
+    });
+}
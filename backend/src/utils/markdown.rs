@@ -0,0 +1,728 @@
+use super::highlight::{find_language, push_escaped, render_code_block};
+
+/// Block quotes, lists and emphasis nested deeper than this are rendered as text
+const MAX_NESTING: usize = 16;
+
+/// How far ahead, in bytes, closers of emphasis, links, code spans and
+/// autolinks are looked for. Inline text is a single paragraph, so scans
+/// already stop at its end; the cap keeps a long paragraph full of unclosed
+/// openers from taking quadratic time.
+const MAX_INLINE_LOOKAHEAD: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Align {
+    None,
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ListMarker {
+    /// Bullet character, or `.`/`)` after the number of an ordered item
+    delimiter: char,
+    ordered: bool,
+    start: u64,
+    /// Column the item's content starts at
+    content_offset: usize,
+}
+
+/// Render Markdown (CommonMark basics plus GitHub tables and strikethrough)
+/// to HTML. Safe by construction: raw HTML in the source is escaped, and
+/// links and images only keep http(s), mailto and relative URLs.
+pub fn render_markdown(source: &str) -> String {
+    let source = source.replace("\r\n", "\n").replace('\t', "    ");
+    let lines: Vec<&str> = source.lines().collect();
+    let mut out = String::with_capacity(source.len() * 3 / 2);
+    render_blocks(&lines, &mut out, false, 0);
+    out
+}
+
+fn leading_spaces(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+/// Remove up to `n` leading spaces
+fn strip_indent(line: &str, n: usize) -> &str {
+    &line[leading_spaces(line).min(n)..]
+}
+
+fn is_blank(line: &str) -> bool {
+    line.trim().is_empty()
+}
+
+/// The line without up to three spaces of indentation, if it isn't indented further
+fn unindented(line: &str) -> Option<&str> {
+    (leading_spaces(line) <= 3).then(|| line.trim_start_matches(' '))
+}
+
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let line = unindented(line)?;
+    let level = line.len() - line.trim_start_matches('#').len();
+    let text = &line[level..];
+    if !(1..=6).contains(&level) || !(text.is_empty() || text.starts_with(' ')) {
+        return None;
+    }
+    // A closing run of #s is dropped
+    let text = text.trim();
+    let without_closing = text.trim_end_matches('#');
+    let text = if without_closing.is_empty() || without_closing.ends_with(' ') {
+        without_closing.trim_end()
+    } else {
+        text
+    };
+    Some((level, text))
+}
+
+fn is_rule(line: &str) -> bool {
+    let Some(line) = unindented(line) else {
+        return false;
+    };
+    let mut marks = line.chars().filter(|c| *c != ' ');
+    match marks.next() {
+        Some(mark @ ('-' | '*' | '_')) => marks.clone().all(|c| c == mark) && marks.count() >= 2,
+        _ => false,
+    }
+}
+
+/// Opening code fence: its character, length and info string
+fn fence(line: &str) -> Option<(char, usize, &str)> {
+    let line = unindented(line)?;
+    let mark = line.chars().next().filter(|c| matches!(c, '`' | '~'))?;
+    let length = line.len() - line.trim_start_matches(mark).len();
+    let info = line[length..].trim();
+    if length < 3 || (mark == '`' && info.contains('`')) {
+        return None;
+    }
+    Some((mark, length, info))
+}
+
+fn is_closing_fence(line: &str, mark: char, length: usize) -> bool {
+    unindented(line).is_some_and(|line| {
+        let run = line.len() - line.trim_start_matches(mark).len();
+        run >= length && line[run..].trim().is_empty()
+    })
+}
+
+fn is_blockquote(line: &str) -> bool {
+    unindented(line).is_some_and(|line| line.starts_with('>'))
+}
+
+fn list_marker(line: &str) -> Option<ListMarker> {
+    let indent = leading_spaces(line);
+    if indent > 3 {
+        return None;
+    }
+    let rest = &line[indent..];
+    let (delimiter, ordered, start, marker_len) = match rest.chars().next()? {
+        bullet @ ('-' | '*' | '+') => (bullet, false, 1, 1),
+        '0'..='9' => {
+            let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            let delimiter = rest[digits..].chars().next()?;
+            if digits > 9 || !matches!(delimiter, '.' | ')') {
+                return None;
+            }
+            (delimiter, true, rest[..digits].parse().ok()?, digits + 1)
+        }
+        _ => return None,
+    };
+    let after = &rest[marker_len..];
+    if !(after.is_empty() || after.starts_with(' ')) {
+        return None;
+    }
+    // Content starts after one to four spaces; more means indented code
+    let spaces = leading_spaces(after);
+    let gap = if spaces == 0 || spaces > 4 || after.trim().is_empty() {
+        1
+    } else {
+        spaces
+    };
+    Some(ListMarker {
+        delimiter,
+        ordered,
+        start,
+        content_offset: indent + marker_len + gap,
+    })
+}
+
+/// Cells of a table row; `\|` keeps a pipe inside a cell
+fn table_cells(line: &str) -> Vec<String> {
+    let line = line.trim();
+    let line = line.strip_prefix('|').unwrap_or(line);
+    let line = if line.ends_with('|') && !line.ends_with("\\|") {
+        &line[..line.len() - 1]
+    } else {
+        line
+    };
+    let mut cells = vec![String::new()];
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'|') => {
+                cells.last_mut().unwrap().push('|');
+                chars.next();
+            }
+            '|' => cells.push(String::new()),
+            c => cells.last_mut().unwrap().push(c),
+        }
+    }
+    cells.iter().map(|cell| cell.trim().to_string()).collect()
+}
+
+/// Alignments from a table's delimiter row (`| :--- | :---: | ---: |`)
+fn table_alignments(line: &str) -> Option<Vec<Align>> {
+    if !line.contains('-') || !line.chars().all(|c| matches!(c, '|' | '-' | ':' | ' ')) {
+        return None;
+    }
+    table_cells(line)
+        .iter()
+        .map(|cell| {
+            let dashes = cell.trim_matches(':');
+            if dashes.is_empty() || !dashes.chars().all(|c| c == '-') {
+                return None;
+            }
+            Some(match (cell.starts_with(':'), cell.ends_with(':')) {
+                (true, true) => Align::Center,
+                (true, false) => Align::Left,
+                (false, true) => Align::Right,
+                (false, false) => Align::None,
+            })
+        })
+        .collect()
+}
+
+/// Whether a line ends a paragraph by starting another block
+fn starts_block(line: &str) -> bool {
+    is_blank(line)
+        || heading(line).is_some()
+        || is_rule(line)
+        || fence(line).is_some()
+        || is_blockquote(line)
+        || list_marker(line).is_some_and(|marker| !marker.ordered || marker.start == 1)
+}
+
+/// Render block-level Markdown. In `tight` lists paragraphs aren't wrapped in `<p>`.
+fn render_blocks(lines: &[&str], out: &mut String, tight: bool, depth: usize) {
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        if is_blank(line) {
+            i += 1;
+        } else if let Some((mark, length, info)) = fence(line) {
+            let indent = leading_spaces(line);
+            let body_start = i + 1;
+            i = body_start;
+            while i < lines.len() && !is_closing_fence(lines[i], mark, length) {
+                i += 1;
+            }
+            let code: Vec<&str> = lines[body_start..i]
+                .iter()
+                .map(|line| strip_indent(line, indent))
+                .collect();
+            let language = info.split_whitespace().next().and_then(find_language);
+            render_code_block(&code.join("\n"), language, out);
+            i += 1;
+        } else if leading_spaces(line) >= 4 {
+            let start = i;
+            while i < lines.len() && (leading_spaces(lines[i]) >= 4 || is_blank(lines[i])) {
+                i += 1;
+            }
+            let mut end = i;
+            while end > start && is_blank(lines[end - 1]) {
+                end -= 1;
+            }
+            let code: Vec<&str> = lines[start..end]
+                .iter()
+                .map(|line| strip_indent(line, 4))
+                .collect();
+            render_code_block(&code.join("\n"), None, out);
+        } else if let Some((level, text)) = heading(line) {
+            out.push_str(&format!("<h{level}>"));
+            render_inline(text, out, depth);
+            out.push_str(&format!("</h{level}>\n"));
+            i += 1;
+        } else if is_rule(line) {
+            out.push_str("<hr />\n");
+            i += 1;
+        } else if is_blockquote(line) && depth < MAX_NESTING {
+            let mut quoted = Vec::new();
+            while i < lines.len() && is_blockquote(lines[i]) {
+                let inner = lines[i].trim_start_matches(' ')[1..].to_string();
+                quoted.push(inner.strip_prefix(' ').map(str::to_string).unwrap_or(inner));
+                i += 1;
+            }
+            let quoted: Vec<&str> = quoted.iter().map(String::as_str).collect();
+            out.push_str("<blockquote>\n");
+            render_blocks(&quoted, out, false, depth + 1);
+            out.push_str("</blockquote>\n");
+        } else if let Some(marker) = list_marker(line).filter(|_| depth < MAX_NESTING) {
+            i = render_list(lines, i, marker, out, depth);
+        } else if let Some(alignments) = lines
+            .get(i + 1)
+            .filter(|_| line.contains('|'))
+            .and_then(|next| table_alignments(next))
+            .filter(|alignments| alignments.len() == table_cells(line).len())
+        {
+            i = render_table(lines, i, &alignments, out, depth);
+        } else {
+            // Paragraph, or a setext heading when underlined with = or -
+            let start = i;
+            i += 1;
+            let mut setext = None;
+            while i < lines.len() {
+                let next = lines[i].trim();
+                if !next.is_empty() && leading_spaces(lines[i]) <= 3 {
+                    if next.chars().all(|c| c == '=') {
+                        setext = Some(1);
+                    } else if next.chars().all(|c| c == '-') {
+                        setext = Some(2);
+                    }
+                }
+                if setext.is_some() || starts_block(lines[i]) {
+                    break;
+                }
+                i += 1;
+            }
+            let text: Vec<&str> = lines[start..i]
+                .iter()
+                .map(|line| line.trim_start())
+                .collect();
+            let text = text.join("\n");
+            let text = text.trim_end();
+            match setext {
+                Some(level) => {
+                    out.push_str(&format!("<h{level}>"));
+                    render_inline(text, out, depth);
+                    out.push_str(&format!("</h{level}>\n"));
+                    i += 1;
+                }
+                None if tight => {
+                    render_inline(text, out, depth);
+                    out.push('\n');
+                }
+                None => {
+                    out.push_str("<p>");
+                    render_inline(text, out, depth);
+                    out.push_str("</p>\n");
+                }
+            }
+        }
+    }
+}
+
+/// Render the list starting at `lines[start]`; returns the index after it
+fn render_list(
+    lines: &[&str],
+    start: usize,
+    marker: ListMarker,
+    out: &mut String,
+    depth: usize,
+) -> usize {
+    let mut items: Vec<Vec<String>> = Vec::new();
+    let mut loose = false;
+    let mut offset = marker.content_offset;
+    let mut i = start;
+    while i < lines.len() {
+        let line = lines[i];
+        let item_marker = list_marker(line).filter(|m| {
+            m.ordered == marker.ordered
+                && m.delimiter == marker.delimiter
+                && leading_spaces(line) < offset
+        });
+        if let Some(item_marker) = item_marker {
+            offset = item_marker.content_offset;
+            let content = line.get(offset..).unwrap_or("").to_string();
+            items.push(vec![content]);
+        } else if is_blank(line) {
+            // A blank line continues the list only if more of it follows
+            let next = lines[i + 1..].iter().position(|line| !is_blank(line));
+            let continues = next.is_some_and(|n| {
+                let next = lines[i + 1 + n];
+                leading_spaces(next) >= offset
+                    || list_marker(next).is_some_and(|m| {
+                        m.ordered == marker.ordered && m.delimiter == marker.delimiter
+                    })
+            });
+            if !continues {
+                break;
+            }
+            loose = true;
+            items.last_mut().unwrap().push(String::new());
+        } else if leading_spaces(line) >= offset {
+            items.last_mut().unwrap().push(line[offset..].to_string());
+        } else if !starts_block(line)
+            && items
+                .last()
+                .and_then(|item| item.last())
+                .is_some_and(|previous| !is_blank(previous))
+        {
+            // Lazy continuation of the item's paragraph
+            items
+                .last_mut()
+                .unwrap()
+                .push(line.trim_start().to_string());
+        } else {
+            break;
+        }
+        i += 1;
+    }
+
+    let (open, close) = if marker.ordered {
+        if marker.start == 1 {
+            ("<ol>\n".to_string(), "</ol>\n")
+        } else {
+            (format!("<ol start=\"{}\">\n", marker.start), "</ol>\n")
+        }
+    } else {
+        ("<ul>\n".to_string(), "</ul>\n")
+    };
+    out.push_str(&open);
+    for item in &items {
+        let item: Vec<&str> = item.iter().map(String::as_str).collect();
+        out.push_str("<li>");
+        render_blocks(&item, out, !loose, depth + 1);
+        out.push_str("</li>\n");
+    }
+    out.push_str(close);
+    i
+}
+
+/// Render the table whose header is `lines[start]`; returns the index after it
+fn render_table(
+    lines: &[&str],
+    start: usize,
+    alignments: &[Align],
+    out: &mut String,
+    depth: usize,
+) -> usize {
+    let cell = |out: &mut String, tag: &str, text: &str, align: Align| {
+        match align {
+            Align::None => out.push_str(&format!("<{tag}>")),
+            Align::Left => out.push_str(&format!("<{tag} align=\"left\">")),
+            Align::Center => out.push_str(&format!("<{tag} align=\"center\">")),
+            Align::Right => out.push_str(&format!("<{tag} align=\"right\">")),
+        }
+        render_inline(text, out, depth);
+        out.push_str(&format!("</{tag}>"));
+    };
+
+    out.push_str("<table>\n<thead>\n<tr>");
+    for (text, &align) in table_cells(lines[start]).iter().zip(alignments) {
+        cell(out, "th", text, align);
+    }
+    out.push_str("</tr>\n</thead>\n");
+
+    let mut i = start + 2;
+    let mut body = false;
+    while i < lines.len() && !is_blank(lines[i]) && !starts_block(lines[i]) {
+        if !body {
+            out.push_str("<tbody>\n");
+            body = true;
+        }
+        let cells = table_cells(lines[i]);
+        out.push_str("<tr>");
+        for (n, &align) in alignments.iter().enumerate() {
+            cell(out, "td", cells.get(n).map_or("", String::as_str), align);
+        }
+        out.push_str("</tr>\n");
+        i += 1;
+    }
+    if body {
+        out.push_str("</tbody>\n");
+    }
+    out.push_str("</table>\n");
+    i
+}
+
+/// The URL if it is safe to link to: http(s), mailto, or relative
+fn safe_url(url: &str) -> Option<&str> {
+    let url = url.trim();
+    let scheme_end = url.find([':', '/', '?', '#']);
+    match scheme_end {
+        Some(end) if url[end..].starts_with(':') => {
+            let scheme = url[..end].to_ascii_lowercase();
+            matches!(scheme.as_str(), "http" | "https" | "mailto").then_some(url)
+        }
+        _ => Some(url),
+    }
+}
+
+/// The start of `text` that closers are looked for in
+fn lookahead(text: &str) -> &str {
+    if text.len() <= MAX_INLINE_LOOKAHEAD {
+        return text;
+    }
+    let mut end = MAX_INLINE_LOOKAHEAD;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+/// Position of the `]` closing the link text that opens at `text[0]`
+fn link_text_end(text: &str) -> Option<usize> {
+    let mut nesting = 0;
+    let mut chars = text.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '[' => nesting += 1,
+            ']' => {
+                nesting -= 1;
+                if nesting == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// `(destination "title")` at the start of `text`: destination, title and
+/// the length consumed
+fn link_destination(text: &str) -> Option<(&str, Option<&str>, usize)> {
+    let inner = text.strip_prefix('(')?;
+    let mut parens = 0;
+    let mut end = None;
+    for (i, c) in inner.char_indices() {
+        match c {
+            '(' => parens += 1,
+            ')' if parens == 0 => {
+                end = Some(i);
+                break;
+            }
+            ')' => parens -= 1,
+            '\n' => return None,
+            _ => {}
+        }
+    }
+    let end = end?;
+    let inside = inner[..end].trim();
+    let (url, title) = match inside.find(' ') {
+        Some(space) => {
+            let title = inside[space..].trim();
+            let title = title
+                .strip_prefix('"')
+                .and_then(|t| t.strip_suffix('"'))
+                .or_else(|| title.strip_prefix('\'').and_then(|t| t.strip_suffix('\'')))?;
+            (&inside[..space], Some(title))
+        }
+        None => (inside, None),
+    };
+    let url = url
+        .strip_prefix('<')
+        .and_then(|u| u.strip_suffix('>'))
+        .unwrap_or(url);
+    Some((url, title, end + 2))
+}
+
+/// Position of the delimiter closing emphasis opened just before `text`. The
+/// closer must follow a non-space character; code spans are skipped.
+fn emphasis_end(text: &str, delimiter: &str) -> Option<usize> {
+    let mark = delimiter.chars().next()?;
+    let bytes = text.as_bytes();
+    let mut i = 0;
+    while i < text.len() {
+        match bytes[i] {
+            b'\\' => i += 1 + text[i + 1..].chars().next().map_or(0, char::len_utf8),
+            b'`' => {
+                let run = text[i..].len() - text[i..].trim_start_matches('`').len();
+                let closing = "`".repeat(run);
+                i += run + text[i + run..].find(&closing).map_or(0, |end| end + run);
+            }
+            _ if text[i..].starts_with(mark) => {
+                let run = text[i..].len() - text[i..].trim_start_matches(mark).len();
+                let after_space = i == 0 || text[..i].ends_with(char::is_whitespace);
+                let word_follows =
+                    mark == '_' && text[i + run..].starts_with(|c: char| c.is_alphanumeric());
+                if run == delimiter.len() && !after_space && !word_follows {
+                    return Some(i);
+                }
+                i += run;
+            }
+            _ => i += text[i..].chars().next().map_or(1, char::len_utf8),
+        }
+    }
+    None
+}
+
+fn push_link(out: &mut String, url: &str, title: Option<&str>) {
+    out.push_str("<a href=\"");
+    push_escaped(out, url);
+    out.push('"');
+    if let Some(title) = title {
+        out.push_str(" title=\"");
+        push_escaped(out, title);
+        out.push('"');
+    }
+    out.push_str(" rel=\"nofollow noopener noreferrer\">");
+}
+
+/// Render inline Markdown: code spans, emphasis, strikethrough, links,
+/// images, autolinks, escapes and hard line breaks
+fn render_inline(text: &str, out: &mut String, depth: usize) {
+    if depth >= MAX_NESTING {
+        push_escaped(out, text);
+        return;
+    }
+    let mut i = 0;
+    while i < text.len() {
+        let rest = &text[i..];
+        let c = rest.chars().next().unwrap();
+        match c {
+            '\\' => match rest[1..].chars().next() {
+                Some('\n') => {
+                    out.push_str("<br />\n");
+                    i += 2;
+                }
+                Some(next) if next.is_ascii_punctuation() => {
+                    push_escaped(out, &rest[1..2]);
+                    i += 2;
+                }
+                _ => {
+                    out.push('\\');
+                    i += 1;
+                }
+            },
+            '`' => {
+                let run = rest.len() - rest.trim_start_matches('`').len();
+                let fence = &rest[..run];
+                let closing = lookahead(&rest[run..])
+                    .match_indices(fence)
+                    .find(|(at, _)| !rest[run + at + run..].starts_with('`'));
+                match closing {
+                    Some((at, _)) => {
+                        let code = rest[run..run + at].replace('\n', " ");
+                        let code = if code.len() > 2 && code.starts_with(' ') && code.ends_with(' ')
+                        {
+                            &code[1..code.len() - 1]
+                        } else {
+                            &code
+                        };
+                        out.push_str("<code>");
+                        push_escaped(out, code);
+                        out.push_str("</code>");
+                        i += run + at + run;
+                    }
+                    None => {
+                        out.push_str(fence);
+                        i += run;
+                    }
+                }
+            }
+            '!' | '[' => {
+                let image = c == '!';
+                let open = usize::from(image);
+                let link = rest[open..]
+                    .starts_with('[')
+                    .then(|| link_text_end(lookahead(&rest[open..])))
+                    .flatten()
+                    .and_then(|end| {
+                        link_destination(lookahead(&rest[open + end + 1..]))
+                            .map(|(url, title, len)| (end, url, title, open + end + 1 + len))
+                    });
+                match link {
+                    Some((end, url, title, len)) => {
+                        let label = &rest[open + 1..open + end];
+                        match (image, safe_url(url)) {
+                            (true, Some(url)) => {
+                                out.push_str("<img src=\"");
+                                push_escaped(out, url);
+                                out.push_str("\" alt=\"");
+                                push_escaped(out, label);
+                                out.push('"');
+                                if let Some(title) = title {
+                                    out.push_str(" title=\"");
+                                    push_escaped(out, title);
+                                    out.push('"');
+                                }
+                                out.push_str(" />");
+                            }
+                            (true, None) => push_escaped(out, label),
+                            (false, Some(url)) => {
+                                push_link(out, url, title);
+                                render_inline(label, out, depth + 1);
+                                out.push_str("</a>");
+                            }
+                            (false, None) => render_inline(label, out, depth + 1),
+                        }
+                        i += len;
+                    }
+                    None => {
+                        push_escaped(out, &rest[..1]);
+                        i += 1;
+                    }
+                }
+            }
+            '<' => {
+                let autolink = lookahead(&rest[1..])
+                    .find('>')
+                    .map(|end| &rest[1..1 + end])
+                    .filter(|url| {
+                        !url.contains(char::is_whitespace)
+                            && ["http://", "https://", "mailto:"]
+                                .iter()
+                                .any(|scheme| url.to_ascii_lowercase().starts_with(scheme))
+                    });
+                match autolink {
+                    Some(url) => {
+                        push_link(out, url, None);
+                        push_escaped(out, url.strip_prefix("mailto:").unwrap_or(url));
+                        out.push_str("</a>");
+                        i += url.len() + 2;
+                    }
+                    None => {
+                        out.push_str("&lt;");
+                        i += 1;
+                    }
+                }
+            }
+            '*' | '_' | '~' => {
+                let run = rest.len() - rest.trim_start_matches(c).len();
+                let opens = rest[run..].starts_with(|next: char| !next.is_whitespace())
+                    && !(c == '_' && text[..i].ends_with(|prev: char| prev.is_alphanumeric()));
+                let (tag, delimiter) = match (c, run) {
+                    ('~', 2) => ("del", "~~"),
+                    ('*', 2) => ("strong", "**"),
+                    ('_', 2) => ("strong", "__"),
+                    ('*', 1) => ("em", "*"),
+                    ('_', 1) => ("em", "_"),
+                    _ => ("", ""),
+                };
+                let end = (opens && !tag.is_empty())
+                    .then(|| emphasis_end(lookahead(&rest[run..]), delimiter))
+                    .flatten();
+                match end {
+                    Some(end) => {
+                        out.push_str(&format!("<{tag}>"));
+                        render_inline(&rest[run..run + end], out, depth + 1);
+                        out.push_str(&format!("</{tag}>"));
+                        i += run + end + run;
+                    }
+                    None => {
+                        out.push_str(&rest[..run]);
+                        i += run;
+                    }
+                }
+            }
+            '\n' => {
+                // Two trailing spaces make a hard break
+                if text[..i].ends_with("  ") {
+                    while out.ends_with(' ') {
+                        out.pop();
+                    }
+                    out.push_str("<br />");
+                }
+                out.push('\n');
+                i += 1;
+            }
+            _ => {
+                push_escaped(out, &rest[..c.len_utf8()]);
+                i += c.len_utf8();
+            }
+        }
+    }
+}
@@ -0,0 +1,381 @@
+use chrono::{DateTime, Utc};
+use sqlx::{FromRow, PgConnection, PgPool};
+use std::path::PathBuf;
+use uuid::Uuid;
+
+use super::{ensure_hot, ffmpeg::run_ffmpeg, ColdStorage};
+use crate::{config::Config, error::Result, models::File};
+
+/// Directory under a project's storage holding generated previews. Folder
+/// paths can't start with a dot, so it never collides with a folder.
+pub const PREVIEWS_DIR: &str = ".previews";
+
+/// Files processed per worker pass
+const MEDIA_PREVIEW_BATCH_SIZE: i64 = 5;
+
+/// How long a worker pass owns the jobs it claimed (a batch of ffmpeg runs
+/// with their timeouts fits well within it)
+const MEDIA_PREVIEW_CLAIM_SECS: f64 = 1800.0;
+
+/// Failed generations are retried with backoff; after this many attempts the
+/// preview is marked `failed`
+const MEDIA_PREVIEW_MAX_ATTEMPTS: i32 = 4;
+
+/// Widest poster generated; narrower videos keep their width
+const POSTER_MAX_WIDTH: u32 = 640;
+
+/// Widest animated preview generated
+const ANIMATION_MAX_WIDTH: u32 = 320;
+
+/// Frame rate of animated previews
+const ANIMATION_FPS: u32 = 10;
+
+/// Sample rate audio is decoded at for waveforms; plenty for peaks a player
+/// draws, and an hour of it is about 58MB of PCM
+const WAVEFORM_SAMPLE_RATE: u32 = 8000;
+
+/// Peaks stored per waveform; clients can ask for fewer
+pub const WAVEFORM_PEAKS: usize = 1000;
+
+/// Whether previews are generated for content of this type
+fn wants_preview(config: &Config, mime_type: &str) -> bool {
+    (config.video_posters && mime_type.starts_with("video/"))
+        || (config.audio_waveforms && mime_type.starts_with("audio/"))
+}
+
+/// Queue preview generation for new or replaced content, dropping previews of
+/// earlier content (a trigger queues their blobs for deletion). Call inside
+/// the upload transaction.
+pub async fn queue_media_preview(
+    conn: &mut PgConnection,
+    config: &Config,
+    file: &File,
+) -> Result<()> {
+    if wants_preview(config, &file.mime_type) {
+        sqlx::query(
+            r#"
+            INSERT INTO media_previews (file_id) VALUES ($1)
+            ON CONFLICT (file_id) DO UPDATE
+            SET status = 'pending', poster_path = NULL, animation_path = NULL,
+                waveform_peaks = NULL, duration_secs = NULL, attempts = 0,
+                next_attempt_at = NOW(), last_error = NULL, queued_at = NOW(), generated_at = NULL
+            "#,
+        )
+        .bind(file.id)
+        .execute(&mut *conn)
+        .await?;
+    } else {
+        sqlx::query("DELETE FROM media_previews WHERE file_id = $1")
+            .bind(file.id)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+/// `<storage_path>/<project_id>/.previews/<random>.<extension>`, a fresh name
+/// per generation so a requeued file never reuses a path queued for deletion
+fn preview_blob_path(storage_path: &str, project_id: Uuid, extension: &str) -> PathBuf {
+    let mut path = PathBuf::from(storage_path);
+    path.push(project_id.to_string());
+    path.push(PREVIEWS_DIR);
+    path.push(format!("{}.{extension}", Uuid::new_v4()));
+    path
+}
+
+/// A JPEG of a representative frame (ffmpeg's `thumbnail` filter skips black
+/// and blurry frames near the start)
+async fn render_poster(ffmpeg_path: &str, input: &str) -> std::result::Result<Vec<u8>, String> {
+    let filter = format!("thumbnail,scale='min({POSTER_MAX_WIDTH},iw)':-2");
+    let args = [
+        "-i",
+        input,
+        "-vf",
+        &filter,
+        "-frames:v",
+        "1",
+        "-an",
+        "-c:v",
+        "mjpeg",
+        "-q:v",
+        "3",
+        "-f",
+        "image2pipe",
+        "pipe:1",
+    ];
+    let poster = run_ffmpeg(ffmpeg_path, args, None).await?;
+    if poster.is_empty() {
+        return Err("ffmpeg found no video frame".to_string());
+    }
+    Ok(poster)
+}
+
+/// A looping animated WebP of the first `seconds` of the video
+async fn render_animation(
+    ffmpeg_path: &str,
+    input: &str,
+    seconds: u32,
+) -> std::result::Result<Vec<u8>, String> {
+    let seconds = seconds.to_string();
+    let filter = format!("fps={ANIMATION_FPS},scale='min({ANIMATION_MAX_WIDTH},iw)':-2");
+    let args = [
+        "-t", &seconds, "-i", input, "-vf", &filter, "-an", "-c:v", "libwebp", "-loop", "0",
+        "-quality", "60", "-f", "webp", "pipe:1",
+    ];
+    let animation = run_ffmpeg(ffmpeg_path, args, None).await?;
+    if animation.is_empty() {
+        return Err("ffmpeg produced no animated preview".to_string());
+    }
+    Ok(animation)
+}
+
+/// The largest absolute sample of each of `buckets` equal spans of mono
+/// 16-bit little-endian PCM, scaled to 0..=1
+fn pcm_peaks(pcm: &[u8], buckets: usize) -> Vec<f32> {
+    let samples: Vec<i16> = pcm
+        .chunks_exact(2)
+        .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+        .collect();
+    let buckets = buckets.min(samples.len());
+    (0..buckets)
+        .map(|i| {
+            let span = &samples[i * samples.len() / buckets..(i + 1) * samples.len() / buckets];
+            let peak = span.iter().map(|s| s.unsigned_abs()).max().unwrap_or(0);
+            f32::from(peak) / 32768.0
+        })
+        .collect()
+}
+
+/// Merge stored peaks into `points` peaks (keeping the largest of each span)
+pub fn downsample_peaks(peaks: &[f32], points: usize) -> Vec<f32> {
+    if points >= peaks.len() {
+        return peaks.to_vec();
+    }
+    (0..points)
+        .map(|i| {
+            peaks[i * peaks.len() / points..(i + 1) * peaks.len() / points]
+                .iter()
+                .fold(0.0, |max, &peak| f32::max(max, peak))
+        })
+        .collect()
+}
+
+/// Peaks and duration of an audio file, decoded by ffmpeg to mono PCM
+async fn render_waveform(
+    ffmpeg_path: &str,
+    input: &str,
+) -> std::result::Result<(Vec<f32>, f64), String> {
+    let sample_rate = WAVEFORM_SAMPLE_RATE.to_string();
+    let args = [
+        "-i",
+        input,
+        "-vn",
+        "-ac",
+        "1",
+        "-ar",
+        &sample_rate,
+        "-c:a",
+        "pcm_s16le",
+        "-f",
+        "s16le",
+        "pipe:1",
+    ];
+    let pcm = run_ffmpeg(ffmpeg_path, args, None).await?;
+    if pcm.len() < 2 {
+        return Err("ffmpeg found no audio".to_string());
+    }
+    let duration_secs = (pcm.len() / 2) as f64 / f64::from(WAVEFORM_SAMPLE_RATE);
+    let peaks = tokio::task::spawn_blocking(move || pcm_peaks(&pcm, WAVEFORM_PEAKS))
+        .await
+        .map_err(|e| format!("Waveform task failed: {e}"))?;
+    Ok((peaks, duration_secs))
+}
+
+#[derive(FromRow)]
+struct MediaPreviewJob {
+    attempts: i32,
+    /// Identifies this queueing of the file; an overwrite requeues it with a new time
+    queued_at: DateTime<Utc>,
+    #[sqlx(flatten)]
+    file: File,
+}
+
+/// Generated previews, written to disk but not yet recorded
+#[derive(Default)]
+struct RenderedPreview {
+    poster_path: Option<PathBuf>,
+    animation_path: Option<PathBuf>,
+    waveform_peaks: Option<Vec<f32>>,
+    duration_secs: Option<f64>,
+}
+
+impl RenderedPreview {
+    async fn remove(&self) {
+        for path in [&self.poster_path, &self.animation_path]
+            .into_iter()
+            .flatten()
+        {
+            let _ = tokio::fs::remove_file(path).await;
+        }
+    }
+}
+
+/// Render and write the previews for one file: poster and animated preview
+/// for videos, waveform for audio. Media types are never compressed at rest,
+/// so ffmpeg reads the blob directly (and can seek in it).
+async fn render_previews(
+    pool: &PgPool,
+    cold_storage: Option<&ColdStorage>,
+    config: &Config,
+    file: &File,
+) -> std::result::Result<RenderedPreview, String> {
+    ensure_hot(pool, cold_storage, file)
+        .await
+        .map_err(|e| e.to_string())?;
+    let input = &file.file_path;
+    if file.mime_type.starts_with("audio/") {
+        let (peaks, duration_secs) = render_waveform(&config.ffmpeg_path, input).await?;
+        return Ok(RenderedPreview {
+            waveform_peaks: Some(peaks),
+            duration_secs: Some(duration_secs),
+            ..Default::default()
+        });
+    }
+
+    let poster = render_poster(&config.ffmpeg_path, input).await?;
+    let animation = match config.video_preview_secs {
+        0 => None,
+        seconds => Some(render_animation(&config.ffmpeg_path, input, seconds).await?),
+    };
+
+    let poster_path = preview_blob_path(&config.storage_path, file.project_id, "jpg");
+    tokio::fs::create_dir_all(poster_path.parent().expect("preview paths have a parent"))
+        .await
+        .map_err(|e| format!("Failed to create previews directory: {e}"))?;
+    tokio::fs::write(&poster_path, &poster)
+        .await
+        .map_err(|e| format!("Failed to write poster: {e}"))?;
+    let mut rendered = RenderedPreview {
+        poster_path: Some(poster_path),
+        ..Default::default()
+    };
+    if let Some(animation) = animation {
+        let path = preview_blob_path(&config.storage_path, file.project_id, "webp");
+        if let Err(e) = tokio::fs::write(&path, &animation).await {
+            rendered.remove().await;
+            return Err(format!("Failed to write animated preview: {e}"));
+        }
+        rendered.animation_path = Some(path);
+    }
+    Ok(rendered)
+}
+
+/// Generate previews for one batch of queued files. Returns how many files
+/// were finished (ready or given up on).
+///
+/// Jobs are claimed the same way as moderation jobs: `next_attempt_at` is
+/// pushed past `MEDIA_PREVIEW_CLAIM_SECS`, so no locks are held while ffmpeg
+/// runs and a crashed worker's jobs are picked up again once the claim runs out.
+pub async fn run_media_previews(
+    pool: &PgPool,
+    cold_storage: Option<&ColdStorage>,
+    config: &Config,
+) -> Result<usize> {
+    let jobs = sqlx::query_as::<_, MediaPreviewJob>(
+        r#"
+        UPDATE media_previews m
+        SET next_attempt_at = NOW() + make_interval(secs => $2)
+        FROM files f
+        WHERE f.id = m.file_id
+          AND m.file_id IN (
+              SELECT file_id FROM media_previews
+              WHERE status = 'pending' AND next_attempt_at <= NOW()
+              ORDER BY queued_at
+              LIMIT $1
+              FOR UPDATE SKIP LOCKED
+          )
+        RETURNING m.attempts, m.queued_at, f.id, f.project_id, f.folder_id, f.original_name, f.stored_name, f.file_path, f.size, f.mime_type, f.upload_date, f.storage_encoding, f.precompressed_encodings, f.storage_tier, f.moderation_status
+        "#,
+    )
+    .bind(MEDIA_PREVIEW_BATCH_SIZE)
+    .bind(MEDIA_PREVIEW_CLAIM_SECS)
+    .fetch_all(pool)
+    .await?;
+
+    let mut finished = 0;
+    for job in &jobs {
+        let file = &job.file;
+        match render_previews(pool, cold_storage, config, file).await {
+            Ok(rendered) => {
+                let recorded = sqlx::query(
+                    r#"
+                    UPDATE media_previews
+                    SET status = 'ready', poster_path = $1, animation_path = $2,
+                        waveform_peaks = $3, duration_secs = $4, last_error = NULL,
+                        generated_at = NOW()
+                    WHERE file_id = $5 AND queued_at = $6
+                    "#,
+                )
+                .bind(rendered.poster_path.as_ref().map(|p| p.to_str().unwrap()))
+                .bind(
+                    rendered
+                        .animation_path
+                        .as_ref()
+                        .map(|p| p.to_str().unwrap()),
+                )
+                .bind(&rendered.waveform_peaks)
+                .bind(rendered.duration_secs)
+                .bind(file.id)
+                .bind(job.queued_at)
+                .execute(pool)
+                .await;
+                match recorded {
+                    // Replaced (and requeued) or deleted while ffmpeg ran
+                    Ok(result) if result.rows_affected() == 0 => rendered.remove().await,
+                    Ok(_) => finished += 1,
+                    Err(e) => {
+                        rendered.remove().await;
+                        return Err(e.into());
+                    }
+                }
+            }
+            Err(e) if job.attempts + 1 >= MEDIA_PREVIEW_MAX_ATTEMPTS => {
+                tracing::warn!(
+                    "Preview generation for file {} failed {} times, giving up: {}",
+                    file.id,
+                    job.attempts + 1,
+                    e
+                );
+                sqlx::query(
+                    r#"
+                    UPDATE media_previews
+                    SET status = 'failed', attempts = attempts + 1, last_error = $1
+                    WHERE file_id = $2 AND queued_at = $3
+                    "#,
+                )
+                .bind(&e)
+                .bind(file.id)
+                .bind(job.queued_at)
+                .execute(pool)
+                .await?;
+                finished += 1;
+            }
+            Err(e) => {
+                sqlx::query(
+                    r#"
+                    UPDATE media_previews
+                    SET attempts = attempts + 1, last_error = $1,
+                        next_attempt_at = NOW() + make_interval(secs => POWER(4, attempts + 1))
+                    WHERE file_id = $2 AND queued_at = $3
+                    "#,
+                )
+                .bind(&e)
+                .bind(file.id)
+                .bind(job.queued_at)
+                .execute(pool)
+                .await?;
+            }
+        }
+    }
+    Ok(finished)
+}
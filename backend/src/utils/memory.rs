@@ -0,0 +1,250 @@
+use bytes::{BufMut, Bytes};
+use futures::Stream;
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tokio::{
+    fs,
+    io::AsyncReadExt,
+    sync::{OwnedSemaphorePermit, Semaphore},
+};
+
+/// Size of the pooled buffers downloads are streamed through
+pub const STREAM_BUFFER_SIZE: usize = 64 * 1024;
+
+/// The budget is accounted in KiB so any reservation fits a semaphore's `u32` permit count
+const UNIT: usize = 1024;
+
+fn units(bytes: usize) -> u32 {
+    bytes.div_ceil(UNIT).min(u32::MAX as usize) as u32
+}
+
+/// Bounds the memory held by upload and download bodies. Uploads reserve
+/// their size before buffering it; downloads are streamed through pooled
+/// buffers, each reserved until the connection has written it.
+#[derive(Debug)]
+pub struct MemoryBudget {
+    /// `None` when the budget is unlimited
+    permits: Option<Arc<Semaphore>>,
+    capacity_units: u32,
+    /// How long an upload waits for room before it is turned away
+    wait: Duration,
+    in_use_units: AtomicUsize,
+    buffers: Mutex<Vec<Vec<u8>>>,
+    max_pooled_buffers: usize,
+}
+
+/// Memory reserved from a `MemoryBudget`, given back when dropped
+#[derive(Debug)]
+pub struct MemoryReservation {
+    budget: Arc<MemoryBudget>,
+    permit: Option<OwnedSemaphorePermit>,
+    units: u32,
+}
+
+impl Drop for MemoryReservation {
+    fn drop(&mut self) {
+        self.budget
+            .in_use_units
+            .fetch_sub(self.units as usize, Ordering::Relaxed);
+    }
+}
+
+/// Pooled buffer backing a chunk of a download body
+struct PooledChunk {
+    buffer: Vec<u8>,
+    reservation: MemoryReservation,
+}
+
+impl AsRef<[u8]> for PooledChunk {
+    fn as_ref(&self) -> &[u8] {
+        &self.buffer
+    }
+}
+
+impl Drop for PooledChunk {
+    fn drop(&mut self) {
+        let buffer = std::mem::take(&mut self.buffer);
+        self.reservation.budget.return_buffer(buffer);
+    }
+}
+
+/// Data kept alive by a `Bytes`, holding its reservation until the last clone is dropped
+struct ReservedData {
+    data: Vec<u8>,
+    _reservation: MemoryReservation,
+}
+
+impl AsRef<[u8]> for ReservedData {
+    fn as_ref(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl MemoryBudget {
+    /// `capacity` of 0 means unlimited; buffers are still pooled
+    pub fn new(capacity: usize, wait: Duration, max_pooled_buffers: usize) -> Self {
+        let capacity_units = units(capacity);
+        Self {
+            permits: (capacity_units > 0)
+                .then(|| Arc::new(Semaphore::new(capacity_units as usize))),
+            capacity_units,
+            wait,
+            in_use_units: AtomicUsize::new(0),
+            buffers: Mutex::new(Vec::new()),
+            max_pooled_buffers,
+        }
+    }
+
+    /// Bytes the budget allows, 0 when unlimited
+    pub fn capacity(&self) -> usize {
+        self.capacity_units as usize * UNIT
+    }
+
+    /// Bytes currently reserved
+    pub fn in_use(&self) -> usize {
+        self.in_use_units.load(Ordering::Relaxed) * UNIT
+    }
+
+    /// Idle buffers in the pool
+    pub fn pooled_buffers(&self) -> usize {
+        self.buffers.lock().unwrap().len()
+    }
+
+    /// Units covering `bytes`, capped at the whole budget when it is limited
+    fn clamp_units(&self, bytes: usize) -> u32 {
+        match self.capacity_units {
+            0 => units(bytes),
+            capacity => units(bytes).min(capacity),
+        }
+    }
+
+    /// Take `units` permits, waiting at most `wait` when one is given
+    async fn acquire(&self, units: u32, wait: Option<Duration>) -> Option<OwnedSemaphorePermit> {
+        let permits = Arc::clone(self.permits.as_ref()?);
+        let acquire = permits.acquire_many_owned(units);
+        let permit = match wait {
+            Some(wait) => tokio::time::timeout(wait, acquire).await.ok()?,
+            None => acquire.await,
+        };
+        Some(permit.expect("memory budget semaphore is never closed"))
+    }
+
+    /// Reserve `bytes`, waiting up to the configured time for room; `None` if
+    /// there still isn't any. A reservation larger than the whole budget takes
+    /// all of it, so a single oversized transfer can still go through alone.
+    pub async fn reserve(self: &Arc<Self>, bytes: usize) -> Option<MemoryReservation> {
+        let mut reservation = MemoryReservation {
+            budget: Arc::clone(self),
+            permit: None,
+            units: 0,
+        };
+        reservation.grow_to(bytes).await.then_some(reservation)
+    }
+
+    fn take_buffer(&self) -> Vec<u8> {
+        self.buffers
+            .lock()
+            .unwrap()
+            .pop()
+            .unwrap_or_else(|| Vec::with_capacity(STREAM_BUFFER_SIZE))
+    }
+
+    fn return_buffer(&self, mut buffer: Vec<u8>) {
+        if buffer.capacity() != STREAM_BUFFER_SIZE {
+            return;
+        }
+        buffer.clear();
+        let mut buffers = self.buffers.lock().unwrap();
+        if buffers.len() < self.max_pooled_buffers {
+            buffers.push(buffer);
+        }
+    }
+
+    /// Stream `file` in chunks of up to `chunk_size` bytes (at most
+    /// `STREAM_BUFFER_SIZE`) read into pooled buffers. Each chunk waits for
+    /// room in the budget rather than failing a download already under way.
+    pub fn stream_file(
+        self: &Arc<Self>,
+        file: fs::File,
+        chunk_size: usize,
+    ) -> impl Stream<Item = io::Result<Bytes>> {
+        let chunk_size = chunk_size.clamp(1, STREAM_BUFFER_SIZE);
+        futures::stream::try_unfold(
+            (Arc::clone(self), file),
+            move |(budget, mut file)| async move {
+                let units = budget.clamp_units(STREAM_BUFFER_SIZE);
+                let permit = budget.acquire(units, None).await;
+                let reservation = MemoryReservation::new(&budget, permit, units);
+                let mut buffer = budget.take_buffer();
+                let read = file.read_buf(&mut (&mut buffer).limit(chunk_size)).await;
+                let read = match read {
+                    Ok(read) => read,
+                    Err(e) => {
+                        budget.return_buffer(buffer);
+                        return Err(e);
+                    }
+                };
+                if read == 0 {
+                    budget.return_buffer(buffer);
+                    return Ok(None);
+                }
+                let chunk = Bytes::from_owner(PooledChunk {
+                    buffer,
+                    reservation,
+                });
+                Ok(Some((chunk, (budget, file))))
+            },
+        )
+    }
+}
+
+impl MemoryReservation {
+    fn new(budget: &Arc<MemoryBudget>, permit: Option<OwnedSemaphorePermit>, units: u32) -> Self {
+        budget
+            .in_use_units
+            .fetch_add(units as usize, Ordering::Relaxed);
+        Self {
+            budget: Arc::clone(budget),
+            permit,
+            units,
+        }
+    }
+
+    /// Extend the reservation to cover `bytes` (capped at the whole budget),
+    /// waiting up to the configured time. Returns false if there's no room.
+    pub async fn grow_to(&mut self, bytes: usize) -> bool {
+        let budget = Arc::clone(&self.budget);
+        let wanted = budget.clamp_units(bytes);
+        let Some(additional) = wanted.checked_sub(self.units).filter(|&n| n > 0) else {
+            return true;
+        };
+        if budget.permits.is_some() {
+            let Some(permit) = budget.acquire(additional, Some(budget.wait)).await else {
+                return false;
+            };
+            match self.permit {
+                Some(ref mut held) => held.merge(permit),
+                None => self.permit = Some(permit),
+            }
+        }
+        budget
+            .in_use_units
+            .fetch_add(additional as usize, Ordering::Relaxed);
+        self.units = wanted;
+        true
+    }
+
+    /// Wrap `data` in `Bytes` that keep this reservation until they are dropped
+    pub fn hold(self, data: Vec<u8>) -> Bytes {
+        Bytes::from_owner(ReservedData {
+            data,
+            _reservation: self,
+        })
+    }
+}
@@ -0,0 +1,317 @@
+use prometheus::{
+    Encoder, Gauge, HistogramOpts, HistogramVec, IntCounter, IntGauge, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+use sqlx::PgPool;
+use std::{sync::Arc, time::Instant};
+use tracing::{
+    field::{Field, Visit},
+    Event, Subscriber,
+};
+use tracing_subscriber::{layer::Context, Layer};
+
+/// Target of sqlx's per-statement log events
+pub const QUERY_LOG_TARGET: &str = "sqlx::query";
+
+/// Prometheus metrics served at `GET /metrics`
+#[derive(Debug)]
+pub struct Metrics {
+    registry: Registry,
+    pool_connections: IntGaugeVec,
+    pool_acquire_seconds: HistogramVec,
+    query_seconds: HistogramVec,
+    integrity_checked_files: IntCounter,
+    integrity_corrupted_files: IntGauge,
+    integrity_last_run: IntGauge,
+    requests_in_flight: IntGauge,
+    requests_shed: IntCounter,
+    runtime_lag_seconds: Gauge,
+    memory_budget_bytes: IntGauge,
+    memory_budget_in_use_bytes: IntGauge,
+    memory_budget_exhausted: IntCounter,
+    stream_buffers_pooled: IntGauge,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new();
+        let pool_connections = IntGaugeVec::new(
+            Opts::new(
+                "db_pool_connections",
+                "Open database connections by pool and state (idle, in_use)",
+            ),
+            &["pool", "state"],
+        )
+        .unwrap();
+        let pool_acquire_seconds = HistogramVec::new(
+            HistogramOpts::new(
+                "db_pool_acquire_seconds",
+                "Time to check out a pooled connection, sampled periodically",
+            )
+            .buckets(vec![
+                0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 30.0,
+            ]),
+            &["pool"],
+        )
+        .unwrap();
+        let query_seconds = HistogramVec::new(
+            HistogramOpts::new("db_query_seconds", "Statement execution time").buckets(vec![
+                0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 5.0,
+            ]),
+            &["query"],
+        )
+        .unwrap();
+        registry
+            .register(Box::new(pool_connections.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(pool_acquire_seconds.clone()))
+            .unwrap();
+        let integrity_checked_files = IntCounter::new(
+            "integrity_checked_files_total",
+            "Files re-hashed by the integrity check",
+        )
+        .unwrap();
+        let integrity_corrupted_files = IntGauge::new(
+            "integrity_corrupted_files",
+            "Files with an open integrity failure after the last check",
+        )
+        .unwrap();
+        let integrity_last_run = IntGauge::new(
+            "integrity_last_run_timestamp_seconds",
+            "Unix time the last integrity check finished",
+        )
+        .unwrap();
+        registry.register(Box::new(query_seconds.clone())).unwrap();
+        registry
+            .register(Box::new(integrity_checked_files.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(integrity_corrupted_files.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(integrity_last_run.clone()))
+            .unwrap();
+        let requests_in_flight = IntGauge::new(
+            "http_requests_in_flight",
+            "Requests being handled or sending their response body",
+        )
+        .unwrap();
+        let requests_shed = IntCounter::new(
+            "http_requests_shed_total",
+            "Low-priority requests rejected with 503 while the server was saturated",
+        )
+        .unwrap();
+        let runtime_lag_seconds = Gauge::new(
+            "runtime_scheduling_lag_seconds",
+            "Smoothed delay before the runtime polls a ready task",
+        )
+        .unwrap();
+        registry
+            .register(Box::new(requests_in_flight.clone()))
+            .unwrap();
+        registry.register(Box::new(requests_shed.clone())).unwrap();
+        registry
+            .register(Box::new(runtime_lag_seconds.clone()))
+            .unwrap();
+        let memory_budget_bytes = IntGauge::new(
+            "memory_budget_bytes",
+            "Memory transfer bodies may hold at once (0 = unlimited)",
+        )
+        .unwrap();
+        let memory_budget_in_use_bytes = IntGauge::new(
+            "memory_budget_in_use_bytes",
+            "Memory reserved by uploads and downloads in progress",
+        )
+        .unwrap();
+        let memory_budget_exhausted = IntCounter::new(
+            "memory_budget_exhausted_total",
+            "Transfers rejected with 503 after waiting for room in the memory budget",
+        )
+        .unwrap();
+        let stream_buffers_pooled = IntGauge::new(
+            "stream_buffers_pooled",
+            "Idle download buffers kept for reuse",
+        )
+        .unwrap();
+        registry
+            .register(Box::new(memory_budget_bytes.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(memory_budget_in_use_bytes.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(memory_budget_exhausted.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(stream_buffers_pooled.clone()))
+            .unwrap();
+
+        Self {
+            registry,
+            pool_connections,
+            pool_acquire_seconds,
+            query_seconds,
+            integrity_checked_files,
+            integrity_corrupted_files,
+            integrity_last_run,
+            requests_in_flight,
+            requests_shed,
+            runtime_lag_seconds,
+            memory_budget_bytes,
+            memory_budget_in_use_bytes,
+            memory_budget_exhausted,
+            stream_buffers_pooled,
+        }
+    }
+
+    /// Record the load figures `LoadShedder` bases its decisions on
+    pub fn record_load(&self, in_flight: usize, lag: std::time::Duration) {
+        self.requests_in_flight.set(in_flight as i64);
+        self.runtime_lag_seconds.set(lag.as_secs_f64());
+    }
+
+    /// Count a request turned away by load shedding
+    pub fn record_shed_request(&self) {
+        self.requests_shed.inc();
+    }
+
+    /// Record how much of the `MemoryBudget` is reserved
+    pub fn record_memory(&self, in_use: usize, capacity: usize, pooled_buffers: usize) {
+        self.memory_budget_in_use_bytes.set(in_use as i64);
+        self.memory_budget_bytes.set(capacity as i64);
+        self.stream_buffers_pooled.set(pooled_buffers as i64);
+    }
+
+    /// Count a transfer turned away for lack of room in the memory budget
+    pub fn record_memory_budget_exhausted(&self) {
+        self.memory_budget_exhausted.inc();
+    }
+
+    /// Count files checked by an integrity run
+    pub fn record_integrity_checks(&self, files: u64) {
+        self.integrity_checked_files.inc_by(files);
+    }
+
+    /// Record a finished integrity run and the number of files now known to be corrupted
+    pub fn record_integrity_run(&self, corrupted_files: i64) {
+        self.integrity_corrupted_files.set(corrupted_files);
+        self.integrity_last_run.set(chrono::Utc::now().timestamp());
+    }
+
+    /// Update the connection gauges of `pool` and time one connection checkout
+    pub async fn sample_pool(&self, name: &str, pool: &PgPool) -> Result<(), sqlx::Error> {
+        let started = Instant::now();
+        let conn = pool.acquire().await?;
+        self.pool_acquire_seconds
+            .with_label_values(&[name])
+            .observe(started.elapsed().as_secs_f64());
+        drop(conn);
+
+        let idle = pool.num_idle() as i64;
+        self.pool_connections
+            .with_label_values(&[name, "idle"])
+            .set(idle);
+        self.pool_connections
+            .with_label_values(&[name, "in_use"])
+            .set(i64::from(pool.size()) - idle);
+        Ok(())
+    }
+
+    /// Prometheus text exposition of all metrics
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .expect("Failed to encode metrics");
+        String::from_utf8(buffer).expect("Metrics are not UTF-8")
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Label for a statement: its verb and first table, e.g. `SELECT files`.
+/// Keeps the label set small while separating the hot queries.
+fn query_label(sql: &str) -> String {
+    let mut words = sql.split_whitespace();
+    let Some(verb) = words.next() else {
+        return "unknown".to_string();
+    };
+    let verb = verb.to_ascii_uppercase();
+    let marker = match verb.as_str() {
+        "SELECT" | "DELETE" | "WITH" => "FROM",
+        "INSERT" => "INTO",
+        "UPDATE" => return format!("UPDATE {}", words.next().unwrap_or("?")),
+        _ => return verb,
+    };
+    let table = words
+        .skip_while(|word| !word.eq_ignore_ascii_case(marker))
+        .nth(1)
+        .map(|table| table.trim_matches(|c: char| !c.is_alphanumeric() && c != '_'))
+        .filter(|table| !table.is_empty());
+    match table {
+        Some(table) => format!("{verb} {table}"),
+        None => verb,
+    }
+}
+
+#[derive(Default)]
+struct QueryEventVisitor {
+    summary: Option<String>,
+    statement: Option<String>,
+    elapsed_secs: Option<f64>,
+}
+
+impl Visit for QueryEventVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "summary" => self.summary = Some(value.to_string()),
+            "db.statement" if !value.is_empty() => self.statement = Some(value.to_string()),
+            _ => {}
+        }
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        if field.name() == "elapsed_secs" {
+            self.elapsed_secs = Some(value);
+        }
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn std::fmt::Debug) {}
+}
+
+/// Tracing layer that turns sqlx statement log events into `db_query_seconds`.
+/// sqlx only emits them when statement logging is on for the pool (see `db::create_pool`).
+pub struct QueryMetricsLayer {
+    metrics: Arc<Metrics>,
+}
+
+impl QueryMetricsLayer {
+    pub fn new(metrics: Arc<Metrics>) -> Self {
+        Self { metrics }
+    }
+}
+
+impl<S: Subscriber> Layer<S> for QueryMetricsLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        if event.metadata().target() != QUERY_LOG_TARGET {
+            return;
+        }
+        let mut visitor = QueryEventVisitor::default();
+        event.record(&mut visitor);
+        // Short statements are logged whole in `summary` with an empty `db.statement`
+        let (Some(sql), Some(elapsed)) =
+            (visitor.statement.or(visitor.summary), visitor.elapsed_secs)
+        else {
+            return;
+        };
+        self.metrics
+            .query_seconds
+            .with_label_values(&[&query_label(&sql)])
+            .observe(elapsed);
+    }
+}
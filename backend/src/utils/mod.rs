@@ -1,8 +1,150 @@
+pub mod access;
+pub mod append_hash;
+pub mod archive;
+pub mod backup;
+pub mod captcha;
+pub mod cdn_purge;
+pub mod changes;
+pub mod client_ip;
+pub mod cold_storage;
+pub mod compression;
+pub mod counters;
+pub mod delta;
+pub mod derived_blobs;
+pub mod domain_verification;
+pub mod download_stats;
+pub mod events;
+pub mod ffmpeg;
+pub mod filename;
+pub mod geoip;
+pub mod highlight;
+pub mod host_cache;
+pub mod image_optimize;
+pub mod inbound_email;
+pub mod integrity;
 pub mod jwt;
+pub mod key_usage;
+pub mod load_shed;
+pub mod login_alerts;
+pub mod mailer;
+pub mod markdown;
+pub mod media_preview;
+pub mod memory;
+pub mod metrics;
+pub mod moderation;
+pub mod mqtt;
+pub mod multipart;
+pub mod notify;
+pub mod offload;
+pub mod pagination;
 pub mod password;
+pub mod password_policy;
+pub mod path;
+pub mod permissions;
+pub mod precompress;
+pub mod public_resolver;
+pub mod purge;
+pub mod quota;
+pub mod replication;
+pub mod signing;
+pub mod throttle;
+pub mod token_revocation;
+pub mod upload_limiter;
+pub mod versions;
 
+pub use access::{
+    admin_override, can_list, can_read, can_upload, can_write, ensure_project_writable, is_allowed,
+    AdminQuery, Credentials, Permission,
+};
+pub use append_hash::{AppendHasher, SHA256_BLOCK_SIZE};
+pub use archive::{archive_entries, extract_archive, write_zip_stream, ArchiveKind, ExtractLimits};
+pub use backup::{
+    begin_backup, fail_interrupted_backups, run_backup, BackupTarget, BACKUP_COLUMNS,
+};
+pub use captcha::{create_pow_challenge, verify_captcha, PowReplayCache};
+pub use cdn_purge::{queue_cdn_purge, run_cdn_purges, CdnProvider, CdnPurger};
+pub use changes::{number_file_changes, purge_file_changes, ChangePositions};
+pub use client_ip::resolve_client_ip;
+pub use cold_storage::{
+    delete_cold_blob, ensure_hot, run_lifecycle, switch_to_cold, ColdStorage, COLD_TIER,
+};
+pub use compression::{
+    gzip_compress, is_compressible, negotiate_encoding, zstd_compress, zstd_decompress,
+    ResponseEncoding, MIN_COMPRESSIBLE_SIZE, ZSTD_ENCODING,
+};
+pub use counters::reconcile_file_counters;
+pub use delta::{
+    apply_delta, block_signatures, rolling_checksum, DEFAULT_DELTA_BLOCK_SIZE, MAX_DELTA_BLOCKS,
+    MAX_DELTA_BLOCK_SIZE, MIN_DELTA_BLOCK_SIZE,
+};
+pub use derived_blobs::remove_deleted_derived_blobs;
+pub use domain_verification::{has_verification_record, verification_record_name};
+pub use download_stats::DownloadTracker;
+pub use events::{
+    dispatch_outbox, purge_delivered_events, record_file_events, replay_events, EventPublisher,
+    FileEventKind,
+};
+pub use filename::{content_disposition, sanitize_file_name, MAX_FILE_NAME_LENGTH};
+pub use geoip::{check_geo_access, lookup_country, open_geoip_database, GeoIpReader};
+pub use highlight::{find_language, render_code_block, Language};
+pub use host_cache::HostProjectCache;
+pub use image_optimize::{optimize_image, OptimizedImage};
+pub use inbound_email::{
+    attachment_base_name, email_address, mime_attachments, sender_allowed, EmailAttachment,
+    InboundEmail,
+};
+pub use integrity::{
+    begin_integrity_run, fail_interrupted_integrity_runs, run_integrity_check,
+    INTEGRITY_RUN_COLUMNS,
+};
 pub use jwt::{
-    create_access_token, create_refresh_token, create_token, hash_token, verify_access_token,
-    verify_refresh_token, verify_token,
+    create_access_token, create_folder_access_token, create_refresh_token, create_token,
+    create_upload_policy_token, hash_token, verify_access_token, verify_folder_access_token,
+    verify_refresh_token, verify_token, verify_upload_policy_token,
+};
+pub use key_usage::ApiKeyUsageTracker;
+pub use load_shed::LoadShedder;
+pub use login_alerts::{spawn_login_check, LoginEvent};
+pub use mailer::Mailer;
+pub use markdown::render_markdown;
+pub use media_preview::{
+    downsample_peaks, queue_media_preview, run_media_previews, WAVEFORM_PEAKS,
+};
+pub use memory::{MemoryBudget, MemoryReservation, STREAM_BUFFER_SIZE};
+pub use metrics::{Metrics, QueryMetricsLayer, QUERY_LOG_TARGET};
+pub use moderation::{
+    clear_moderation, queue_moderation, read_file_content, run_moderation, HttpModerator,
+    Moderator, Verdict,
+};
+pub use mqtt::{MqttClient, MqttMessage, MqttRoute};
+pub use multipart::{
+    expire_multipart_uploads, multipart_upload_dir, remove_multipart_parts,
+    reopen_interrupted_multipart_uploads, MultipartProgress, ReceivingPart, MAX_MULTIPART_PARTS,
+    MULTIPART_DIR, MULTIPART_UPLOAD_COLUMNS, MULTIPART_UPLOAD_EXPIRY_HOURS,
+};
+pub use notify::{deliver_notifications, notify_file_corrupted, notify_large_upload};
+pub use offload::{offload_headers, DownloadOffload};
+pub use pagination::{
+    decode_cursor, encode_cursor, PageQuery, Paginated, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE,
+};
+pub use password::{hash_password, verify_password, PasswordHashing};
+pub use password_policy::{check_password_policy, CharacterClass};
+pub use path::{validate_folder_path, MAX_FOLDER_DEPTH, MAX_FOLDER_SEGMENT_LENGTH};
+pub use permissions::{perm, PermissionMarker, RolePermission};
+pub use precompress::{remove_variants, variant_path, PrecompressQueue, PRECOMPRESSED_ENCODINGS};
+pub use public_resolver::{ensure_public_url, PublicResolver};
+pub use purge::{
+    queue_project_purge, requeue_interrupted_purges, run_project_purges, PURGE_COLUMNS,
+};
+pub use quota::{check_quota, spawn_quota_warnings};
+pub use replication::{
+    queue_replication, queue_replication_catch_up, replica_path, run_replication, ReplicationPeer,
+    REPLICATION_SHA256_HEADER,
+};
+pub use signing::{
+    signing_payload, verify_signature, SignatureReplayCache, SIGNATURE_MAX_SKEW_SECS,
 };
-pub use password::{hash_password, verify_password};
+pub use throttle::{throttled_chunk_size, throttled_stream};
+pub use token_revocation::{revoke_access_tokens, TokenRevocationCache};
+pub use upload_limiter::UploadLimiter;
+pub use versions::{version_blob_path, FILE_VERSION_COLUMNS};
@@ -0,0 +1,335 @@
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use serde::Deserialize;
+use sqlx::{FromRow, PgConnection, PgPool};
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::{
+    error::{AppError, Result},
+    models::{File, ModerationStatus},
+    utils::{ensure_hot, zstd_decompress, ColdStorage, Mailer, ZSTD_ENCODING},
+};
+
+/// Files sent to the moderation service per worker pass
+const MODERATION_BATCH_SIZE: i64 = 10;
+
+/// How long a worker pass owns the jobs it claimed (a batch of service calls
+/// with their timeouts fits well within it)
+const MODERATION_CLAIM_SECS: f64 = 900.0;
+
+/// Failed checks are retried with backoff; after this many attempts the file is
+/// left `pending_review` for a project admin to decide
+const MODERATION_MAX_ATTEMPTS: i32 = 6;
+
+/// Outcome of inspecting an upload
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Approve,
+    Reject(Option<String>),
+    /// Not sure either way; a project admin has to decide
+    Review(Option<String>),
+}
+
+/// Inspects uploaded content before it becomes downloadable. Called from the
+/// moderation worker, never on the upload path, so slow services only delay
+/// approval.
+pub trait Moderator: Send + Sync {
+    fn moderate<'a>(
+        &'a self,
+        file: &'a File,
+        content: Vec<u8>,
+    ) -> BoxFuture<'a, std::result::Result<Verdict, String>>;
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum HttpVerdictKind {
+    Approve,
+    Reject,
+    Review,
+}
+
+#[derive(Debug, Deserialize)]
+struct HttpVerdict {
+    verdict: HttpVerdictKind,
+    reason: Option<String>,
+}
+
+/// Posts the file content to an external service (e.g. NSFW image detection)
+/// and expects `{"verdict": "approve" | "reject" | "review", "reason": "..."}`
+#[derive(Debug)]
+pub struct HttpModerator {
+    client: reqwest::Client,
+    url: String,
+    token: Option<String>,
+}
+
+impl HttpModerator {
+    pub fn new(url: &str, token: Option<String>) -> std::result::Result<Self, reqwest::Error> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(60))
+            .build()?;
+        Ok(Self {
+            client,
+            url: url.to_string(),
+            token,
+        })
+    }
+}
+
+impl Moderator for HttpModerator {
+    fn moderate<'a>(
+        &'a self,
+        file: &'a File,
+        content: Vec<u8>,
+    ) -> BoxFuture<'a, std::result::Result<Verdict, String>> {
+        Box::pin(async move {
+            let mut request = self
+                .client
+                .post(&self.url)
+                .header(reqwest::header::CONTENT_TYPE, &file.mime_type)
+                .header("X-File-Id", file.id.to_string())
+                .header("X-Project-Id", file.project_id.to_string())
+                .body(content);
+            if let Some(ref token) = self.token {
+                request = request.bearer_auth(token);
+            }
+
+            let response = request.send().await.map_err(|e| e.to_string())?;
+            if !response.status().is_success() {
+                return Err(format!("Moderation service returned {}", response.status()));
+            }
+            let body: HttpVerdict = response
+                .json()
+                .await
+                .map_err(|e| format!("Invalid moderation response: {e}"))?;
+            Ok(match body.verdict {
+                HttpVerdictKind::Approve => Verdict::Approve,
+                HttpVerdictKind::Reject => Verdict::Reject(body.reason),
+                HttpVerdictKind::Review => Verdict::Review(body.reason),
+            })
+        })
+    }
+}
+
+/// Hold a new or replaced file for moderation: mark it `pending_review` and
+/// queue it for the moderation worker. Call inside the upload transaction.
+pub async fn queue_moderation(conn: &mut PgConnection, file_id: Uuid) -> Result<()> {
+    sqlx::query(
+        "UPDATE files SET moderation_status = 'pending_review', moderation_reason = NULL WHERE id = $1",
+    )
+    .bind(file_id)
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query(
+        r#"
+        INSERT INTO moderation_jobs (file_id) VALUES ($1)
+        ON CONFLICT (file_id) DO UPDATE
+        SET attempts = 0, next_attempt_at = NOW(), last_error = NULL, created_at = NOW()
+        "#,
+    )
+    .bind(file_id)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// Release a replaced file from moderation when its new content isn't moderated
+pub async fn clear_moderation(conn: &mut PgConnection, file_id: Uuid) -> Result<()> {
+    sqlx::query(
+        "UPDATE files SET moderation_status = 'approved', moderation_reason = NULL WHERE id = $1",
+    )
+    .bind(file_id)
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query("DELETE FROM moderation_jobs WHERE file_id = $1")
+        .bind(file_id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+/// Original bytes of a file, decompressed if stored as zstd (fetched back from
+/// cold storage first if needed)
+pub async fn read_file_content(
+    pool: &PgPool,
+    cold_storage: Option<&ColdStorage>,
+    file: &File,
+) -> Result<Vec<u8>> {
+    ensure_hot(pool, cold_storage, file).await?;
+    let data = tokio::fs::read(&file.file_path)
+        .await
+        .map_err(|e| AppError::FileError(format!("Failed to read file: {e}")))?;
+    if file.storage_encoding.as_deref() == Some(ZSTD_ENCODING) {
+        return tokio::task::spawn_blocking(move || zstd_decompress(&data))
+            .await
+            .map_err(|e| AppError::InternalError(format!("Decompression task failed: {e}")))?
+            .map_err(|e| AppError::FileError(format!("Failed to decompress file: {e}")));
+    }
+    Ok(data)
+}
+
+#[derive(FromRow)]
+struct ModerationJob {
+    attempts: i32,
+    /// Identifies this queueing of the file; an overwrite requeues it with a new time
+    queued_at: DateTime<Utc>,
+    #[sqlx(flatten)]
+    file: File,
+}
+
+/// Email the project owner that a file was rejected or needs their review
+async fn notify_owner(pool: &PgPool, mailer: Option<&Mailer>, file: &File, verdict: &Verdict) {
+    let Some(mailer) = mailer else {
+        return;
+    };
+    let owner = sqlx::query_as::<_, (String, String)>(
+        "SELECT u.email, p.name FROM projects p JOIN users u ON u.id = p.user_id WHERE p.id = $1",
+    )
+    .bind(file.project_id)
+    .fetch_optional(pool)
+    .await;
+    let (email, project_name) = match owner {
+        Ok(Some(owner)) => owner,
+        Ok(None) => return,
+        Err(e) => {
+            tracing::warn!("Failed to look up owner of file {}: {}", file.id, e);
+            return;
+        }
+    };
+
+    let (subject, outcome, reason) = match verdict {
+        Verdict::Approve => return,
+        Verdict::Reject(reason) => (
+            format!("\"{}\" was rejected by moderation", file.original_name),
+            "was rejected by content moderation and will not be served",
+            reason,
+        ),
+        Verdict::Review(reason) => (
+            format!("\"{}\" needs moderation review", file.original_name),
+            "is held for review; it will not be served until a project admin approves it",
+            reason,
+        ),
+    };
+    let body = format!(
+        "The file \"{}\" (ID {}) in project \"{project_name}\" {outcome}.\n\nReason: {}\n",
+        file.original_name,
+        file.id,
+        reason.as_deref().unwrap_or("none given"),
+    );
+    if let Err(e) = mailer.send(&email, &subject, body).await {
+        tracing::warn!(
+            "Failed to send moderation notice for file {}: {}",
+            file.id,
+            e
+        );
+    }
+}
+
+/// Record the moderator's verdict, unless the file was replaced (and queued
+/// again) while it was being checked. Returns whether the verdict was applied.
+async fn apply_verdict(pool: &PgPool, job: &ModerationJob, verdict: &Verdict) -> Result<bool> {
+    let (status, reason) = match verdict {
+        Verdict::Approve => (ModerationStatus::Approved, None),
+        Verdict::Reject(reason) => (ModerationStatus::Rejected, reason.as_deref()),
+        Verdict::Review(reason) => (ModerationStatus::PendingReview, reason.as_deref()),
+    };
+
+    let mut tx = pool.begin().await?;
+    let finished =
+        sqlx::query("DELETE FROM moderation_jobs WHERE file_id = $1 AND created_at = $2")
+            .bind(job.file.id)
+            .bind(job.queued_at)
+            .execute(&mut *tx)
+            .await?;
+    if finished.rows_affected() == 0 {
+        return Ok(false);
+    }
+    sqlx::query("UPDATE files SET moderation_status = $1, moderation_reason = $2 WHERE id = $3")
+        .bind(status)
+        .bind(reason)
+        .bind(job.file.id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(true)
+}
+
+/// Send one batch of queued files to the moderator and record the verdicts.
+/// Returns how many files were decided.
+///
+/// Jobs are claimed by pushing `next_attempt_at` past `MODERATION_CLAIM_SECS`, so
+/// no locks are held while the service is called and a crashed worker's jobs are
+/// picked up again once the claim runs out.
+pub async fn run_moderation(
+    pool: &PgPool,
+    moderator: &dyn Moderator,
+    cold_storage: Option<&ColdStorage>,
+    mailer: Option<&Mailer>,
+) -> Result<usize> {
+    let jobs = sqlx::query_as::<_, ModerationJob>(
+        r#"
+        UPDATE moderation_jobs j
+        SET next_attempt_at = NOW() + make_interval(secs => $2)
+        FROM files f
+        WHERE f.id = j.file_id
+          AND j.file_id IN (
+              SELECT file_id FROM moderation_jobs
+              WHERE next_attempt_at <= NOW()
+              ORDER BY created_at
+              LIMIT $1
+              FOR UPDATE SKIP LOCKED
+          )
+        RETURNING j.attempts, j.created_at AS queued_at, f.id, f.project_id, f.folder_id, f.original_name, f.stored_name, f.file_path, f.size, f.mime_type, f.upload_date, f.storage_encoding, f.precompressed_encodings, f.storage_tier, f.moderation_status
+        "#,
+    )
+    .bind(MODERATION_BATCH_SIZE)
+    .bind(MODERATION_CLAIM_SECS)
+    .fetch_all(pool)
+    .await?;
+
+    let mut decided = 0;
+    for job in &jobs {
+        let file = &job.file;
+        let result = match read_file_content(pool, cold_storage, file).await {
+            Ok(content) => moderator.moderate(file, content).await,
+            Err(e) => Err(e.to_string()),
+        };
+
+        let verdict = match result {
+            Ok(verdict) => verdict,
+            Err(e) if job.attempts + 1 >= MODERATION_MAX_ATTEMPTS => {
+                tracing::warn!(
+                    "Moderation of file {} failed {} times, leaving it for review: {}",
+                    file.id,
+                    job.attempts + 1,
+                    e
+                );
+                Verdict::Review(Some(format!("Moderation service unavailable: {e}")))
+            }
+            Err(e) => {
+                sqlx::query(
+                    r#"
+                    UPDATE moderation_jobs
+                    SET attempts = attempts + 1, last_error = $1,
+                        next_attempt_at = NOW() + make_interval(secs => POWER(4, attempts + 1))
+                    WHERE file_id = $2 AND created_at = $3
+                    "#,
+                )
+                .bind(&e)
+                .bind(file.id)
+                .bind(job.queued_at)
+                .execute(pool)
+                .await?;
+                continue;
+            }
+        };
+
+        if apply_verdict(pool, job, &verdict).await? {
+            notify_owner(pool, mailer, file, &verdict).await;
+            decided += 1;
+        }
+    }
+    Ok(decided)
+}
@@ -0,0 +1,385 @@
+use std::{sync::Arc, time::Duration};
+
+use chrono::Utc;
+use serde::Deserialize;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+    net::TcpStream,
+    time::Instant,
+};
+use tokio_rustls::{
+    rustls::{self, pki_types::ServerName},
+    TlsConnector,
+};
+use uuid::Uuid;
+
+/// Seconds between pings when nothing else is sent; a broker that doesn't
+/// answer within another interval is considered gone
+pub const MQTT_KEEP_ALIVE_SECS: u16 = 60;
+
+const CONNECT: u8 = 0x10;
+const CONNACK: u8 = 0x20;
+const PUBLISH: u8 = 0x30;
+const PUBACK: u8 = 0x40;
+const SUBSCRIBE: u8 = 0x82;
+const SUBACK: u8 = 0x90;
+const PINGREQ: u8 = 0xc0;
+const PINGRESP: u8 = 0xd0;
+
+/// Where messages on matching topics are stored (one entry of `MQTT_ROUTES`)
+#[derive(Debug, Clone, Deserialize)]
+pub struct MqttRoute {
+    /// Topic filter to subscribe to; `+` and `#` wildcards are allowed
+    pub topic: String,
+    pub project_id: Uuid,
+    /// Folder template; files go to the project root without one
+    pub folder: Option<String>,
+    /// File name template
+    pub name: String,
+    /// `rename` (default), `overwrite`, `skip` or `error` for a same-name file
+    pub on_conflict: Option<String>,
+}
+
+impl MqttRoute {
+    /// Whether a topic name matches this route's filter
+    pub fn matches(&self, topic: &str) -> bool {
+        // Wildcards don't reach into broker topics such as $SYS
+        if topic.starts_with('$') && self.topic.starts_with(['+', '#']) {
+            return false;
+        }
+        let mut filter = self.topic.split('/');
+        let mut levels = topic.split('/');
+        loop {
+            match (filter.next(), levels.next()) {
+                (Some("#"), _) => return true,
+                (Some("+"), Some(_)) => {}
+                (Some(expected), Some(level)) if expected == level => {}
+                (None, None) => return true,
+                _ => return false,
+            }
+        }
+    }
+
+    /// Fill in a template for a message on `topic`: `{topic}` is the whole
+    /// topic with `/` replaced by `-`, `{topic.N}` its Nth level (from 1),
+    /// `{timestamp}` the UTC receive time and `{uuid}` a random UUID
+    pub fn render(template: &str, topic: &str) -> String {
+        let levels: Vec<&str> = topic.split('/').collect();
+        let mut out = String::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            out.push_str(&rest[..start]);
+            let Some(len) = rest[start..].find('}') else {
+                break;
+            };
+            let placeholder = &rest[start + 1..start + len];
+            match placeholder {
+                "topic" => out.push_str(&topic.replace('/', "-")),
+                "timestamp" => out.push_str(&Utc::now().format("%Y%m%dT%H%M%S%.3fZ").to_string()),
+                "uuid" => out.push_str(&Uuid::new_v4().to_string()),
+                _ => match placeholder
+                    .strip_prefix("topic.")
+                    .and_then(|n| n.parse::<usize>().ok())
+                {
+                    Some(n) if (1..=levels.len()).contains(&n) => out.push_str(levels[n - 1]),
+                    Some(_) => {}
+                    None => out.push_str(&rest[start..=start + len]),
+                },
+            }
+            rest = &rest[start + len + 1..];
+        }
+        out.push_str(rest);
+        out
+    }
+}
+
+/// A message received on a subscribed topic
+pub struct MqttMessage {
+    pub topic: String,
+    pub payload: Vec<u8>,
+    /// Set for QoS 1 messages, which are acknowledged with `ack`
+    pub packet_id: Option<u16>,
+}
+
+trait Io: AsyncRead + AsyncWrite + Unpin + Send + Sync {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send + Sync> Io for T {}
+
+/// A plain or TLS connection to the broker
+type Stream = Box<dyn Io>;
+
+/// A minimal MQTT 3.1.1 client: connects with a persistent session, so the
+/// broker keeps QoS 1 messages for us while we're disconnected, subscribes
+/// and receives messages. Brokers are reached over plain TCP (`mqtt://`) or
+/// TLS (`mqtts://`, verified against the bundled web PKI roots).
+pub struct MqttClient {
+    stream: BufReader<Stream>,
+    /// Largest message accepted; bigger ones are read past and dropped
+    max_payload: usize,
+    awaiting_pong: bool,
+    last_sent: Instant,
+    /// Filters of the last subscription, to name a refused one
+    subscribed: Vec<String>,
+}
+
+fn protocol_error(message: impl Into<String>) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message.into())
+}
+
+fn push_str(buf: &mut Vec<u8>, s: &str) {
+    buf.extend_from_slice(&(s.len() as u16).to_be_bytes());
+    buf.extend_from_slice(s.as_bytes());
+}
+
+/// Fixed header: packet type and flags, then the remaining length as a
+/// variable-length integer (7 bits per byte, high bit set on all but the last)
+fn packet(kind: u8, body: &[u8]) -> Vec<u8> {
+    let mut out = vec![kind];
+    let mut len = body.len();
+    loop {
+        let mut byte = (len % 128) as u8;
+        len /= 128;
+        if len > 0 {
+            byte |= 0x80;
+        }
+        out.push(byte);
+        if len == 0 {
+            break;
+        }
+    }
+    out.extend_from_slice(body);
+    out
+}
+
+/// Open a TLS connection to `host`, checking its certificate against the
+/// web PKI roots
+async fn connect_tls(host: &str, address: &str) -> std::io::Result<Stream> {
+    let roots = rustls::RootCertStore::from_iter(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    let config = rustls::ClientConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .map_err(|e| protocol_error(e.to_string()))?
+    .with_root_certificates(roots)
+    .with_no_client_auth();
+    let server_name = ServerName::try_from(host.trim_matches(['[', ']']).to_string())
+        .map_err(|_| protocol_error(format!("Invalid broker host {host}")))?;
+    let tcp = TcpStream::connect(address).await?;
+    let tls = TlsConnector::from(Arc::new(config))
+        .connect(server_name, tcp)
+        .await?;
+    Ok(Box::new(tls))
+}
+
+impl MqttClient {
+    /// Connect to `mqtt://[user[:password]@]host[:port]`, or `mqtts://` for TLS
+    pub async fn connect(url: &str, client_id: &str, max_payload: usize) -> std::io::Result<Self> {
+        let (tls, address) = match url.split_once("://") {
+            Some(("mqtt", address)) => (false, address),
+            Some(("mqtts", address)) => (true, address),
+            _ => {
+                return Err(protocol_error(
+                    "MQTT_URL must start with mqtt:// or mqtts://",
+                ))
+            }
+        };
+        let address = address.trim_end_matches('/');
+        let (credentials, host) = match address.rsplit_once('@') {
+            Some((credentials, host)) => (Some(credentials), host),
+            None => (None, address),
+        };
+        // A port follows the last `:`, unless that's inside an IPv6 literal
+        let (hostname, address) = match host.rsplit_once(':') {
+            Some((hostname, port)) if !port.ends_with(']') => (hostname, host.to_string()),
+            _ => (host, format!("{host}:{}", if tls { 8883 } else { 1883 })),
+        };
+        let (username, password) = match credentials.map(|c| c.split_once(':')) {
+            Some(Some((username, password))) => (Some(username), Some(password)),
+            Some(None) => (credentials, None),
+            None => (None, None),
+        };
+        let decode = |s: &str| {
+            percent_encoding::percent_decode_str(s)
+                .decode_utf8_lossy()
+                .into_owned()
+        };
+
+        let stream: Stream = if tls {
+            connect_tls(hostname, &address).await?
+        } else {
+            Box::new(TcpStream::connect(&address).await?)
+        };
+        let mut client = MqttClient {
+            stream: BufReader::new(stream),
+            max_payload,
+            awaiting_pong: false,
+            last_sent: Instant::now(),
+            subscribed: Vec::new(),
+        };
+
+        let mut flags = 0u8; // clean session off: keep subscriptions and queued messages
+        let mut body = Vec::new();
+        push_str(&mut body, "MQTT");
+        body.push(4); // protocol level 3.1.1
+        if username.is_some() {
+            flags |= 0x80;
+        }
+        if password.is_some() {
+            flags |= 0x40;
+        }
+        body.push(flags);
+        body.extend_from_slice(&MQTT_KEEP_ALIVE_SECS.to_be_bytes());
+        push_str(&mut body, client_id);
+        if let Some(username) = username {
+            push_str(&mut body, &decode(username));
+        }
+        if let Some(password) = password {
+            push_str(&mut body, &decode(password));
+        }
+        client.send(&packet(CONNECT, &body)).await?;
+
+        let (kind, body) = client.read_packet().await?;
+        if kind & 0xf0 != CONNACK || body.len() != 2 {
+            return Err(protocol_error("Expected CONNACK"));
+        }
+        match body[1] {
+            0 => Ok(client),
+            1 => Err(protocol_error("Broker refused the protocol version")),
+            2 => Err(protocol_error("Broker rejected the client ID")),
+            3 => Err(protocol_error("Broker unavailable")),
+            4 | 5 => Err(protocol_error("Broker rejected the credentials")),
+            code => Err(protocol_error(format!(
+                "Broker refused the connection ({code})"
+            ))),
+        }
+    }
+
+    /// Subscribe to `filters` with QoS 1. The broker's answer is read by
+    /// `next_message`, which fails if a filter was refused; messages queued
+    /// in the session may arrive before it.
+    pub async fn subscribe(&mut self, filters: &[&str]) -> std::io::Result<()> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&1u16.to_be_bytes());
+        for filter in filters {
+            push_str(&mut body, filter);
+            body.push(1);
+        }
+        self.subscribed = filters.iter().map(|f| f.to_string()).collect();
+        self.send(&packet(SUBSCRIBE, &body)).await
+    }
+
+    /// Wait for the next message, pinging the broker when we haven't sent
+    /// anything for half the keep-alive
+    pub async fn next_message(&mut self) -> std::io::Result<MqttMessage> {
+        loop {
+            let ping_due = self.last_sent + Duration::from_secs(MQTT_KEEP_ALIVE_SECS as u64 / 2);
+            if Instant::now() >= ping_due {
+                if self.awaiting_pong {
+                    return Err(protocol_error("Broker stopped answering pings"));
+                }
+                self.send(&packet(PINGREQ, &[])).await?;
+                self.awaiting_pong = true;
+                continue;
+            }
+            // Reading the first byte is cancel-safe: it's either consumed or not
+            let first = match tokio::time::timeout_at(ping_due, self.stream.read_u8()).await {
+                Ok(first) => first?,
+                Err(_) => continue,
+            };
+            let len = self.read_length().await?;
+            if first & 0xf0 == PUBLISH && len > self.max_payload + 2 + u16::MAX as usize {
+                // Drop messages too large to store instead of buffering them
+                tokio::io::copy(
+                    &mut (&mut self.stream).take(len as u64),
+                    &mut tokio::io::sink(),
+                )
+                .await?;
+                tracing::warn!("Dropped an MQTT message of {} bytes", len);
+                continue;
+            }
+            let mut body = vec![0u8; len];
+            self.stream.read_exact(&mut body).await?;
+
+            match first & 0xf0 {
+                PINGRESP => self.awaiting_pong = false,
+                SUBACK => {
+                    let codes = body.get(2..).unwrap_or_default();
+                    if let Some(index) = codes.iter().position(|&code| code == 0x80) {
+                        return Err(protocol_error(format!(
+                            "Broker refused the subscription to {}",
+                            self.subscribed.get(index).map_or("a topic", String::as_str)
+                        )));
+                    }
+                }
+                PUBLISH => {
+                    let qos = (first >> 1) & 0x03;
+                    if body.len() < 2 {
+                        return Err(protocol_error("Malformed PUBLISH"));
+                    }
+                    let topic_len = u16::from_be_bytes([body[0], body[1]]) as usize;
+                    let id_len = if qos > 0 { 2 } else { 0 };
+                    if body.len() < 2 + topic_len + id_len {
+                        return Err(protocol_error("Malformed PUBLISH"));
+                    }
+                    let topic = String::from_utf8(body[2..2 + topic_len].to_vec())
+                        .map_err(|_| protocol_error("Topic is not UTF-8"))?;
+                    let packet_id = (qos > 0)
+                        .then(|| u16::from_be_bytes([body[2 + topic_len], body[3 + topic_len]]));
+                    if qos > 1 {
+                        return Err(protocol_error("Broker sent a QoS 2 message"));
+                    }
+                    let payload = body.split_off(2 + topic_len + id_len);
+                    if payload.len() > self.max_payload {
+                        tracing::warn!("Dropped an MQTT message of {} bytes on {}", len, topic);
+                        if let Some(packet_id) = packet_id {
+                            self.ack(packet_id).await?;
+                        }
+                        continue;
+                    }
+                    return Ok(MqttMessage {
+                        topic,
+                        payload,
+                        packet_id,
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Acknowledge a QoS 1 message, so the broker won't deliver it again
+    pub async fn ack(&mut self, packet_id: u16) -> std::io::Result<()> {
+        self.send(&packet(PUBACK, &packet_id.to_be_bytes())).await
+    }
+
+    async fn send(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        let stream = self.stream.get_mut();
+        stream.write_all(bytes).await?;
+        stream.flush().await?;
+        self.last_sent = Instant::now();
+        Ok(())
+    }
+
+    async fn read_length(&mut self) -> std::io::Result<usize> {
+        let mut len = 0usize;
+        for shift in [0, 7, 14, 21] {
+            let byte = self.stream.read_u8().await?;
+            len |= ((byte & 0x7f) as usize) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(len);
+            }
+        }
+        Err(protocol_error("Malformed remaining length"))
+    }
+
+    async fn read_packet(&mut self) -> std::io::Result<(u8, Vec<u8>)> {
+        let kind = self.stream.read_u8().await?;
+        let len = self.read_length().await?;
+        if len > 64 * 1024 {
+            return Err(protocol_error("Unexpected large packet"));
+        }
+        let mut body = vec![0u8; len];
+        self.stream.read_exact(&mut body).await?;
+        Ok((kind, body))
+    }
+}
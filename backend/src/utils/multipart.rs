@@ -0,0 +1,133 @@
+use sqlx::PgPool;
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+use uuid::Uuid;
+
+use crate::error::Result;
+
+pub const MULTIPART_UPLOAD_COLUMNS: &str = "id, project_id, file_name, folder_path, description, on_conflict, status, created_at, expires_at, expected_size";
+
+/// Directory under a project's storage holding the parts of its multipart
+/// uploads. Folder paths can't start with a dot, so it never collides with a folder.
+pub const MULTIPART_DIR: &str = ".multipart";
+
+/// Highest accepted part number
+pub const MAX_MULTIPART_PARTS: i32 = 10_000;
+
+/// Hours a multipart upload may sit without a new part before it's aborted
+pub const MULTIPART_UPLOAD_EXPIRY_HOURS: i32 = 24;
+
+/// Bytes of parts being streamed in on this server, per upload, so progress
+/// moves while a large part is still arriving
+#[derive(Debug, Default)]
+pub struct MultipartProgress {
+    receiving: Mutex<HashMap<Uuid, i64>>,
+}
+
+impl MultipartProgress {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bytes of unfinished parts of an upload
+    pub fn receiving(&self, upload_id: Uuid) -> i64 {
+        self.receiving
+            .lock()
+            .unwrap()
+            .get(&upload_id)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Start counting a part; its bytes stop counting when the guard is dropped
+    pub fn start_part(self: &Arc<Self>, upload_id: Uuid) -> ReceivingPart {
+        ReceivingPart {
+            progress: Arc::clone(self),
+            upload_id,
+            bytes: 0,
+        }
+    }
+}
+
+/// A part being received, see `MultipartProgress::start_part`
+pub struct ReceivingPart {
+    progress: Arc<MultipartProgress>,
+    upload_id: Uuid,
+    bytes: i64,
+}
+
+impl ReceivingPart {
+    pub fn add(&mut self, bytes: i64) {
+        self.bytes += bytes;
+        *self
+            .progress
+            .receiving
+            .lock()
+            .unwrap()
+            .entry(self.upload_id)
+            .or_default() += bytes;
+    }
+}
+
+impl Drop for ReceivingPart {
+    fn drop(&mut self) {
+        let mut receiving = self.progress.receiving.lock().unwrap();
+        if let Some(total) = receiving.get_mut(&self.upload_id) {
+            *total -= self.bytes;
+            if *total <= 0 {
+                receiving.remove(&self.upload_id);
+            }
+        }
+    }
+}
+
+/// `<storage_path>/<project_id>/.multipart/<upload_id>`
+pub fn multipart_upload_dir(storage_path: &str, project_id: Uuid, upload_id: Uuid) -> PathBuf {
+    let mut dir = PathBuf::from(storage_path);
+    dir.push(project_id.to_string());
+    dir.push(MULTIPART_DIR);
+    dir.push(upload_id.to_string());
+    dir
+}
+
+/// Remove the parts of a finished or aborted upload; a leftover directory only
+/// costs disk space, so failures are just logged
+pub async fn remove_multipart_parts(storage_path: &str, project_id: Uuid, upload_id: Uuid) {
+    let dir = multipart_upload_dir(storage_path, project_id, upload_id);
+    match tokio::fs::remove_dir_all(&dir).await {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => tracing::warn!(
+            "Failed to remove parts of multipart upload {}: {}",
+            upload_id,
+            e
+        ),
+    }
+}
+
+/// Abort multipart uploads that received no part for `MULTIPART_UPLOAD_EXPIRY_HOURS`
+/// and remove their parts. Returns how many were aborted.
+pub async fn expire_multipart_uploads(pool: &PgPool, storage_path: &str) -> Result<usize> {
+    let expired = sqlx::query_as::<_, (Uuid, Uuid)>(
+        "DELETE FROM multipart_uploads WHERE status = 'uploading' AND expires_at <= NOW() RETURNING id, project_id",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    for &(upload_id, project_id) in &expired {
+        remove_multipart_parts(storage_path, project_id, upload_id).await;
+    }
+    Ok(expired.len())
+}
+
+/// Reopen uploads a previous process was assembling when it stopped (called at
+/// startup); the client can complete them again
+pub async fn reopen_interrupted_multipart_uploads(pool: &PgPool) -> Result<()> {
+    sqlx::query("UPDATE multipart_uploads SET status = 'uploading' WHERE status = 'completing'")
+        .execute(pool)
+        .await?;
+    Ok(())
+}
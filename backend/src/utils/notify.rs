@@ -0,0 +1,219 @@
+use sqlx::{FromRow, PgConnection, PgPool};
+use uuid::Uuid;
+
+use crate::{
+    error::Result,
+    models::{File, NotificationEvent, NotificationKind, Project},
+};
+
+/// Uploads at least this large trigger `large_upload` unless a channel sets its own threshold
+pub const DEFAULT_LARGE_UPLOAD_BYTES: i64 = 100 * 1024 * 1024;
+
+/// Jobs posted per worker pass
+const NOTIFICATION_BATCH_SIZE: i64 = 50;
+
+/// Failed posts are retried with backoff, then dropped after this many attempts
+const NOTIFICATION_MAX_ATTEMPTS: i32 = 8;
+
+fn default_template(event: NotificationEvent) -> &'static str {
+    match event {
+        NotificationEvent::LargeUpload => {
+            "Large upload in {project}: {file_name} ({size}) in {folder}"
+        }
+        NotificationEvent::FileCorrupted => {
+            "Integrity check failed in {project}: {file_name} ({size}) in {folder} is damaged or missing"
+        }
+    }
+}
+
+/// Human-readable size, e.g. `1.5 GB`
+fn format_size(bytes: i64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+/// Fill `{placeholders}` in a channel template
+fn render(
+    template: &str,
+    event: NotificationEvent,
+    project: &Project,
+    file: &File,
+    folder: Option<&str>,
+) -> String {
+    template
+        .replace("{project}", &project.name)
+        .replace("{event}", event.as_str())
+        .replace("{file_name}", &file.original_name)
+        .replace("{folder}", folder.unwrap_or("/"))
+        .replace("{size}", &format_size(file.size))
+}
+
+#[derive(FromRow)]
+struct SubscribedChannel {
+    id: Uuid,
+    template: Option<String>,
+}
+
+/// Queue `large_upload` notifications for channels whose threshold the file meets.
+/// Call inside the upload transaction so a rolled-back upload sends nothing.
+pub async fn notify_large_upload(
+    conn: &mut PgConnection,
+    project: &Project,
+    file: &File,
+    folder: Option<&str>,
+) -> Result<()> {
+    let event = NotificationEvent::LargeUpload;
+    let channels = sqlx::query_as::<_, SubscribedChannel>(
+        r#"
+        SELECT id, template FROM notification_channels
+        WHERE project_id = $1 AND $2 = ANY(events)
+          AND $3 >= COALESCE(large_upload_bytes, $4)
+        "#,
+    )
+    .bind(project.id)
+    .bind(event.as_str())
+    .bind(file.size)
+    .bind(DEFAULT_LARGE_UPLOAD_BYTES)
+    .fetch_all(&mut *conn)
+    .await?;
+    queue_messages(conn, event, channels, project, file, folder).await
+}
+
+/// Queue `file_corrupted` notifications for every subscribed channel of the project
+pub async fn notify_file_corrupted(
+    conn: &mut PgConnection,
+    project: &Project,
+    file: &File,
+    folder: Option<&str>,
+) -> Result<()> {
+    let event = NotificationEvent::FileCorrupted;
+    let channels = sqlx::query_as::<_, SubscribedChannel>(
+        "SELECT id, template FROM notification_channels WHERE project_id = $1 AND $2 = ANY(events)",
+    )
+    .bind(project.id)
+    .bind(event.as_str())
+    .fetch_all(&mut *conn)
+    .await?;
+    queue_messages(conn, event, channels, project, file, folder).await
+}
+
+async fn queue_messages(
+    conn: &mut PgConnection,
+    event: NotificationEvent,
+    channels: Vec<SubscribedChannel>,
+    project: &Project,
+    file: &File,
+    folder: Option<&str>,
+) -> Result<()> {
+    for channel in channels {
+        let template = channel
+            .template
+            .as_deref()
+            .unwrap_or(default_template(event));
+        sqlx::query("INSERT INTO notification_jobs (channel_id, message) VALUES ($1, $2)")
+            .bind(channel.id)
+            .bind(render(template, event, project, file, folder))
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+#[derive(FromRow)]
+struct NotificationJob {
+    id: Uuid,
+    kind: NotificationKind,
+    webhook_url: String,
+    message: String,
+    attempts: i32,
+}
+
+async fn post_message(
+    client: &reqwest::Client,
+    job: &NotificationJob,
+) -> std::result::Result<(), String> {
+    let body = match job.kind {
+        NotificationKind::Slack => serde_json::json!({ "text": job.message }),
+        NotificationKind::Discord => serde_json::json!({ "content": job.message }),
+    };
+    let response = client
+        .post(&job.webhook_url)
+        .json(&body)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("Webhook returned {}", response.status()));
+    }
+    Ok(())
+}
+
+/// Post one batch of due notification jobs. Returns how many were delivered.
+pub async fn deliver_notifications(pool: &PgPool, client: &reqwest::Client) -> Result<usize> {
+    let mut tx = pool.begin().await?;
+    let jobs = sqlx::query_as::<_, NotificationJob>(
+        r#"
+        SELECT j.id, c.kind, c.webhook_url, j.message, j.attempts
+        FROM notification_jobs j
+        JOIN notification_channels c ON c.id = j.channel_id
+        WHERE j.next_attempt_at <= NOW()
+        ORDER BY j.created_at
+        LIMIT $1
+        FOR UPDATE OF j SKIP LOCKED
+        "#,
+    )
+    .bind(NOTIFICATION_BATCH_SIZE)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut finished = Vec::with_capacity(jobs.len());
+    let mut delivered = 0;
+    for job in &jobs {
+        match post_message(client, job).await {
+            Ok(()) => {
+                finished.push(job.id);
+                delivered += 1;
+            }
+            Err(e) if job.attempts + 1 >= NOTIFICATION_MAX_ATTEMPTS => {
+                tracing::warn!(
+                    "Dropping notification {} after {} attempts: {}",
+                    job.id,
+                    job.attempts + 1,
+                    e
+                );
+                finished.push(job.id);
+            }
+            Err(e) => {
+                sqlx::query(
+                    r#"
+                    UPDATE notification_jobs
+                    SET attempts = attempts + 1, last_error = $1,
+                        next_attempt_at = NOW() + make_interval(secs => POWER(4, attempts + 1))
+                    WHERE id = $2
+                    "#,
+                )
+                .bind(&e)
+                .bind(job.id)
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+    }
+
+    sqlx::query("DELETE FROM notification_jobs WHERE id = ANY($1)")
+        .bind(&finished)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(delivered)
+}
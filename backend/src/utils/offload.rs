@@ -0,0 +1,81 @@
+use axum::http::{HeaderName, HeaderValue};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::Deserialize;
+use std::path::Path;
+
+/// Characters left unescaped in a path segment of an `X-Accel-Redirect` URI
+const SEGMENT_ESCAPES: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// How downloads hand the bytes of a blob to a reverse proxy in front of the server
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum DownloadOffload {
+    /// Stream blobs from this process
+    None,
+    /// nginx: `X-Accel-Redirect` to an internal location aliased to `STORAGE_PATH`
+    XAccelRedirect,
+    /// Apache `mod_xsendfile` / lighttpd: `X-Sendfile` with the blob's absolute path
+    XSendfile,
+}
+
+impl std::str::FromStr for DownloadOffload {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "" | "none" => Ok(DownloadOffload::None),
+            "x-accel-redirect" | "nginx" => Ok(DownloadOffload::XAccelRedirect),
+            "x-sendfile" | "apache" => Ok(DownloadOffload::XSendfile),
+            other => Err(format!("Unknown DOWNLOAD_OFFLOAD: {other}")),
+        }
+    }
+}
+
+/// Headers that make the proxy send `path` itself, or `None` when the blob
+/// has to be streamed from here (offloading is off, the blob lies outside
+/// `STORAGE_PATH`, or the proxy can't apply the project's bandwidth limit)
+pub async fn offload_headers(
+    mode: DownloadOffload,
+    storage_path: &str,
+    internal_prefix: &str,
+    path: &Path,
+    bandwidth_limit: Option<i64>,
+) -> Option<Vec<(HeaderName, HeaderValue)>> {
+    match mode {
+        DownloadOffload::None => None,
+        DownloadOffload::XAccelRedirect => {
+            let relative = path.strip_prefix(storage_path).ok()?;
+            let mut uri = internal_prefix.trim_end_matches('/').to_string();
+            for component in relative.components() {
+                uri.push('/');
+                uri.extend(utf8_percent_encode(
+                    component.as_os_str().to_str()?,
+                    SEGMENT_ESCAPES,
+                ));
+            }
+            let mut headers = vec![(
+                HeaderName::from_static("x-accel-redirect"),
+                HeaderValue::from_str(&uri).ok()?,
+            )];
+            // nginx throttles the response itself
+            if let Some(limit) = bandwidth_limit {
+                headers.push((
+                    HeaderName::from_static("x-accel-limit-rate"),
+                    HeaderValue::from(limit),
+                ));
+            }
+            Some(headers)
+        }
+        DownloadOffload::XSendfile => {
+            if bandwidth_limit.is_some() {
+                return None;
+            }
+            let absolute = tokio::fs::canonicalize(path).await.ok()?;
+            let value = HeaderValue::from_bytes(absolute.to_str()?.as_bytes()).ok()?;
+            Some(vec![(HeaderName::from_static("x-sendfile"), value)])
+        }
+    }
+}
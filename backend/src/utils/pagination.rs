@@ -0,0 +1,83 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::error::{AppError, Result};
+
+/// Page size of a listing asked for no `limit`
+pub const DEFAULT_PAGE_SIZE: i64 = 100;
+
+/// Largest page a listing returns
+pub const MAX_PAGE_SIZE: i64 = 1000;
+
+/// An opaque cursor holding `key`
+pub fn encode_cursor<K: Serialize>(key: &K) -> String {
+    URL_SAFE_NO_PAD.encode(serde_json::to_vec(key).expect("cursor keys serialize"))
+}
+
+/// The key of a cursor from `encode_cursor`, or `Invalid cursor`
+pub fn decode_cursor<K: DeserializeOwned>(cursor: &str) -> Result<K> {
+    URL_SAFE_NO_PAD
+        .decode(cursor)
+        .ok()
+        .and_then(|json| serde_json::from_slice(&json).ok())
+        .ok_or(AppError::BadRequest("Invalid cursor".to_string()))
+}
+
+/// `?cursor=&limit=` of a paginated listing
+#[derive(Debug, Default, Deserialize)]
+pub struct PageQuery {
+    /// `next_cursor` of the previous page; none for the first page
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+}
+
+impl PageQuery {
+    pub fn limit(&self) -> i64 {
+        self.limit
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE)
+    }
+
+    /// Sort key of the last item of the previous page, `None` for the first
+    /// page. A cursor from another listing or sort order is rejected.
+    pub fn after<K: DeserializeOwned>(&self) -> Result<Option<K>> {
+        let Some(cursor) = self.cursor.as_deref().filter(|c| !c.is_empty()) else {
+            return Ok(None);
+        };
+        decode_cursor(cursor).map(Some)
+    }
+}
+
+/// One page of a listing. Items are ordered by a unique sort key, and the
+/// cursor holds the key of the last one, so pages neither skip nor repeat
+/// items when others are added or removed in between.
+#[derive(Debug, Serialize)]
+pub struct Paginated<T> {
+    pub items: Vec<T>,
+    /// Send as `cursor` to get the next page; `null` on the last page
+    pub next_cursor: Option<String>,
+    /// Items in the whole listing, possibly slightly out of date
+    pub total_estimate: i64,
+}
+
+impl<T> Paginated<T> {
+    /// A page from up to `limit + 1` rows fetched after the cursor; an extra
+    /// row means there's a next page, which starts after the last item's `key`
+    pub fn new<K: Serialize>(
+        mut rows: Vec<T>,
+        limit: i64,
+        total_estimate: i64,
+        key: impl Fn(&T) -> K,
+    ) -> Self {
+        let mut next_cursor = None;
+        if rows.len() as i64 > limit {
+            rows.truncate(limit as usize);
+            next_cursor = rows.last().map(|last| encode_cursor(&key(last)));
+        }
+        Paginated {
+            items: rows,
+            next_cursor,
+            total_estimate,
+        }
+    }
+}
@@ -1,15 +1,55 @@
 use argon2::{
     password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
-    Argon2,
+    Algorithm, Argon2, Params, Version,
 };
 
-pub fn hash_password(password: &str) -> Result<String, argon2::password_hash::Error> {
+/// Argon2id cost parameters for new password hashes (`ARGON2_*` settings)
+#[derive(Debug, Clone, Default)]
+pub struct PasswordHashing {
+    params: Params,
+}
+
+impl PasswordHashing {
+    pub fn new(memory_kib: u32, iterations: u32, parallelism: u32) -> Result<Self, String> {
+        let params = Params::new(memory_kib, iterations, parallelism, None)
+            .map_err(|e| format!("Invalid Argon2 parameters: {e}"))?;
+        Ok(Self { params })
+    }
+
+    fn argon2(&self) -> Argon2<'static> {
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, self.params.clone())
+    }
+
+    /// Whether `hash` was made with an older algorithm or version, or cheaper
+    /// parameters than the configured ones, and should be replaced
+    pub fn needs_rehash(&self, hash: &str) -> bool {
+        let Ok(parsed) = PasswordHash::new(hash) else {
+            return true;
+        };
+        if parsed.algorithm != Algorithm::Argon2id.ident()
+            || parsed.version != Some(Version::V0x13.into())
+        {
+            return true;
+        }
+        let Ok(params) = Params::try_from(&parsed) else {
+            return true;
+        };
+        params.m_cost() < self.params.m_cost()
+            || params.t_cost() < self.params.t_cost()
+            || params.p_cost() < self.params.p_cost()
+    }
+}
+
+pub fn hash_password(
+    password: &str,
+    hashing: &PasswordHashing,
+) -> Result<String, argon2::password_hash::Error> {
     let salt = SaltString::generate(&mut OsRng);
-    let argon2 = Argon2::default();
-    let password_hash = argon2.hash_password(password.as_bytes(), &salt)?;
+    let password_hash = hashing.argon2().hash_password(password.as_bytes(), &salt)?;
     Ok(password_hash.to_string())
 }
 
+/// Check `password` against `hash`, whatever Argon2 variant and parameters it was made with
 pub fn verify_password(password: &str, hash: &str) -> Result<bool, argon2::password_hash::Error> {
     let parsed_hash = PasswordHash::new(hash)?;
     let argon2 = Argon2::default();
@@ -0,0 +1,149 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, sync::OnceLock};
+
+use crate::{
+    config::Config,
+    error::{AppError, Result},
+};
+
+/// Bundled list of frequently used passwords, one per line
+const COMMON_PASSWORDS: &str = include_str!("common_passwords.txt");
+
+/// Shortest email local part that passwords may not contain
+const MIN_EMAIL_PART_LENGTH: usize = 3;
+
+/// Kind of character a password can be required to contain (`PASSWORD_REQUIRED_CLASSES`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum CharacterClass {
+    Lowercase,
+    Uppercase,
+    Digit,
+    Symbol,
+}
+
+impl std::str::FromStr for CharacterClass {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "lower" | "lowercase" => Ok(CharacterClass::Lowercase),
+            "upper" | "uppercase" => Ok(CharacterClass::Uppercase),
+            "digit" | "number" => Ok(CharacterClass::Digit),
+            "symbol" | "special" => Ok(CharacterClass::Symbol),
+            other => Err(format!("Unknown password character class: {other}")),
+        }
+    }
+}
+
+impl CharacterClass {
+    fn matches(self, c: char) -> bool {
+        match self {
+            CharacterClass::Lowercase => c.is_lowercase(),
+            CharacterClass::Uppercase => c.is_uppercase(),
+            CharacterClass::Digit => c.is_numeric(),
+            CharacterClass::Symbol => !c.is_alphanumeric() && !c.is_whitespace(),
+        }
+    }
+
+    fn rule(self) -> (&'static str, &'static str) {
+        match self {
+            CharacterClass::Lowercase => ("lowercase", "Password must contain a lowercase letter"),
+            CharacterClass::Uppercase => ("uppercase", "Password must contain an uppercase letter"),
+            CharacterClass::Digit => ("digit", "Password must contain a digit"),
+            CharacterClass::Symbol => ("symbol", "Password must contain a symbol"),
+        }
+    }
+}
+
+/// A password policy rule a password failed
+#[derive(Debug, Clone, Serialize)]
+pub struct PolicyViolation {
+    pub rule: &'static str,
+    pub message: String,
+}
+
+impl PolicyViolation {
+    fn new(rule: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            rule,
+            message: message.into(),
+        }
+    }
+}
+
+fn common_passwords() -> &'static HashSet<&'static str> {
+    static PASSWORDS: OnceLock<HashSet<&'static str>> = OnceLock::new();
+    PASSWORDS.get_or_init(|| {
+        COMMON_PASSWORDS
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .collect()
+    })
+}
+
+/// Whether `password` is the account's email or contains (or is contained in) its local part
+fn derived_from_email(password: &str, email: &str) -> bool {
+    let password = password.to_lowercase();
+    let email = email.trim().to_lowercase();
+    if password == email {
+        return true;
+    }
+    let local = email.split('@').next().unwrap_or("");
+    local.chars().count() >= MIN_EMAIL_PART_LENGTH
+        && (password.contains(local) || local.contains(&password))
+}
+
+/// Every `PASSWORD_*` rule `password` breaks for the account with `email`
+pub fn password_policy_violations(
+    config: &Config,
+    password: &str,
+    email: &str,
+) -> Vec<PolicyViolation> {
+    let mut violations = Vec::new();
+    if password.chars().count() < config.password_min_length {
+        violations.push(PolicyViolation::new(
+            "min_length",
+            format!(
+                "Password must be at least {} characters",
+                config.password_min_length
+            ),
+        ));
+    }
+    for class in &config.password_required_classes {
+        if !password.chars().any(|c| class.matches(c)) {
+            let (rule, message) = class.rule();
+            violations.push(PolicyViolation::new(rule, message));
+        }
+    }
+    if config.password_reject_common
+        && common_passwords().contains(password.to_lowercase().as_str())
+    {
+        violations.push(PolicyViolation::new("common", "Password is too common"));
+    }
+    if config.password_reject_email && derived_from_email(password, email) {
+        violations.push(PolicyViolation::new(
+            "email",
+            "Password must not be based on your email address",
+        ));
+    }
+    violations
+}
+
+/// Reject a new password (sent as `field`) that breaks the password policy,
+/// listing every rule it failed
+pub fn check_password_policy(
+    config: &Config,
+    field: &str,
+    password: &str,
+    email: &str,
+) -> Result<()> {
+    let violations = password_policy_violations(config, password, email);
+    if violations.is_empty() {
+        return Ok(());
+    }
+    Err(AppError::WeakPassword {
+        field: field.to_string(),
+        violations,
+    })
+}
@@ -0,0 +1,51 @@
+use crate::error::{AppError, Result};
+
+/// Deepest accepted folder path, in segments (`a/b/c` is 3)
+pub const MAX_FOLDER_DEPTH: usize = 32;
+
+/// Longest accepted folder name (one path segment), in bytes
+pub const MAX_FOLDER_SEGMENT_LENGTH: usize = 255;
+
+fn invalid_folder_path(reason: &str) -> AppError {
+    AppError::BadRequest(format!("Invalid folder path: {reason}"))
+}
+
+/// Validate a client-supplied folder path and return it in canonical form.
+/// Trailing slashes and repeated separators are dropped (`docs//img/` becomes
+/// `docs/img`), so equivalent spellings map to the same folder record. Absolute
+/// paths, `..`, hidden folders and characters other than alphanumerics, `_`, `-`
+/// and `.` are rejected, as are paths deeper than `MAX_FOLDER_DEPTH` or with a
+/// segment longer than `MAX_FOLDER_SEGMENT_LENGTH`.
+pub fn validate_folder_path(path: &str) -> Result<String> {
+    if path.starts_with('/') || path.starts_with('\\') || path.contains("..") {
+        return Err(invalid_folder_path("path traversal not allowed"));
+    }
+
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    if segments.is_empty() {
+        return Err(invalid_folder_path("path is empty"));
+    }
+    if segments.len() > MAX_FOLDER_DEPTH {
+        return Err(invalid_folder_path(&format!(
+            "at most {MAX_FOLDER_DEPTH} levels deep"
+        )));
+    }
+    for segment in &segments {
+        if !segment
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '.')
+        {
+            return Err(invalid_folder_path("contains invalid characters"));
+        }
+        if segment.starts_with('.') {
+            return Err(invalid_folder_path("hidden folders not allowed"));
+        }
+        if segment.len() > MAX_FOLDER_SEGMENT_LENGTH {
+            return Err(invalid_folder_path(&format!(
+                "folder names must be at most {MAX_FOLDER_SEGMENT_LENGTH} bytes"
+            )));
+        }
+    }
+
+    Ok(segments.join("/"))
+}
@@ -0,0 +1,94 @@
+use serde::Serialize;
+
+use crate::models::UserRole;
+
+/// Implemented by the marker types in `perm`, naming the permission a
+/// `RequirePermission` extractor checks for
+pub trait PermissionMarker {
+    const PERMISSION: RolePermission;
+}
+
+macro_rules! role_permissions {
+    ($($(#[$doc:meta])* $name:ident => $value:literal,)*) => {
+        /// Account-wide action a role grants, written `area:action`; admin-only
+        /// areas are prefixed with `admin:`
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+        pub enum RolePermission {
+            $($(#[$doc])* #[serde(rename = $value)] $name,)*
+        }
+
+        impl RolePermission {
+            pub const ALL: &'static [RolePermission] = &[$(RolePermission::$name,)*];
+
+            pub fn as_str(self) -> &'static str {
+                match self {
+                    $(RolePermission::$name => $value,)*
+                }
+            }
+        }
+
+        /// Marker types for `RequirePermission<perm::...>`, one per `RolePermission`
+        pub mod perm {
+            $(
+                #[derive(Debug, Clone, Copy)]
+                pub struct $name;
+
+                impl super::PermissionMarker for $name {
+                    const PERMISSION: super::RolePermission = super::RolePermission::$name;
+                }
+            )*
+        }
+    };
+}
+
+role_permissions! {
+    /// Create new projects
+    ProjectsCreate => "projects:create",
+    /// Read any user's projects, their files and purges
+    AdminProjectsRead => "admin:projects:read",
+    /// Update, moderate and place legal holds on any user's files and folders
+    AdminFilesWrite => "admin:files:write",
+    /// Delete any user's files
+    AdminFilesDelete => "admin:files:delete",
+    AdminIntegrityRead => "admin:integrity:read",
+    AdminIntegrityRun => "admin:integrity:run",
+    AdminJobsRead => "admin:jobs:read",
+    AdminReplicationRead => "admin:replication:read",
+    AdminDatabaseRead => "admin:database:read",
+    AdminBackupsRead => "admin:backups:read",
+    AdminBackupsRun => "admin:backups:run",
+    AdminStorageRead => "admin:storage:read",
+    /// Start and cut over storage migrations
+    AdminStorageWrite => "admin:storage:write",
+    AdminPurgesRead => "admin:purges:read",
+    AdminPurgesWrite => "admin:purges:write",
+}
+
+impl RolePermission {
+    /// Whether using the permission is written to the audit log
+    pub fn is_audited(self) -> bool {
+        self.as_str().starts_with("admin:")
+    }
+}
+
+impl std::fmt::Display for RolePermission {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+const USER_PERMISSIONS: &[RolePermission] = &[RolePermission::ProjectsCreate];
+
+impl UserRole {
+    /// Permissions granted to every user with this role
+    pub fn permissions(&self) -> &'static [RolePermission] {
+        match self {
+            UserRole::Admin => RolePermission::ALL,
+            UserRole::User => USER_PERMISSIONS,
+        }
+    }
+
+    pub fn has_permission(&self, permission: RolePermission) -> bool {
+        self.permissions().contains(&permission)
+    }
+}
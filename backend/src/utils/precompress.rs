@@ -0,0 +1,128 @@
+use sqlx::PgPool;
+use std::{
+    collections::HashSet,
+    io,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+use uuid::Uuid;
+
+use crate::models::File;
+
+use super::compression::{
+    brotli_compress, gzip_compress_best, zstd_decompress, ResponseEncoding, ZSTD_ENCODING,
+};
+
+/// Encodings generated for public web assets
+pub const PRECOMPRESSED_ENCODINGS: [ResponseEncoding; 2] =
+    [ResponseEncoding::Brotli, ResponseEncoding::Gzip];
+
+/// Recorded in `precompressed_encodings` when no variant beats the original,
+/// so the file isn't queued again on every download
+const NO_VARIANTS_MARKER: &str = "identity";
+
+/// Where the cached variant of a blob lives: `<file_path>.br` / `<file_path>.gz`
+pub fn variant_path(file_path: &str, encoding: ResponseEncoding) -> PathBuf {
+    let suffix = match encoding {
+        ResponseEncoding::Brotli => "br",
+        _ => "gz",
+    };
+    PathBuf::from(format!("{file_path}.{suffix}"))
+}
+
+/// Remove any cached variants of a blob (missing ones are ignored)
+pub async fn remove_variants(file_path: &str) {
+    for encoding in PRECOMPRESSED_ENCODINGS {
+        let path = variant_path(file_path, encoding);
+        if let Err(e) = tokio::fs::remove_file(&path).await {
+            if e.kind() != io::ErrorKind::NotFound {
+                tracing::warn!("Failed to delete variant {}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+/// Generates gzip/brotli variants of public web assets in the background.
+/// Tracks in-flight files so concurrent downloads don't compress the same blob twice.
+#[derive(Debug, Default)]
+pub struct PrecompressQueue {
+    in_flight: Mutex<HashSet<Uuid>>,
+}
+
+impl PrecompressQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue variant generation for a file unless it's already being processed
+    pub fn schedule(self: &Arc<Self>, pool: PgPool, file: &File) {
+        if !self.in_flight.lock().unwrap().insert(file.id) {
+            return;
+        }
+
+        let queue = Arc::clone(self);
+        let file = file.clone();
+        tokio::spawn(async move {
+            if let Err(e) = generate_variants(&pool, &file).await {
+                tracing::warn!("Failed to precompress file {}: {}", file.id, e);
+            }
+            queue.in_flight.lock().unwrap().remove(&file.id);
+        });
+    }
+}
+
+async fn generate_variants(pool: &PgPool, file: &File) -> io::Result<()> {
+    let file_path = file.file_path.as_str();
+    let storage_encoding = file.storage_encoding.clone();
+    let stored = tokio::fs::read(file_path).await?;
+    let variants = tokio::task::spawn_blocking(move || -> io::Result<_> {
+        let data = if storage_encoding.as_deref() == Some(ZSTD_ENCODING) {
+            zstd_decompress(&stored)?
+        } else {
+            stored
+        };
+        let mut variants = Vec::new();
+        for encoding in PRECOMPRESSED_ENCODINGS {
+            let compressed = match encoding {
+                ResponseEncoding::Brotli => brotli_compress(&data)?,
+                _ => gzip_compress_best(&data)?,
+            };
+            // Only keep variants that actually save bandwidth
+            if compressed.len() < data.len() {
+                variants.push((encoding, compressed));
+            }
+        }
+        Ok(variants)
+    })
+    .await
+    .map_err(io::Error::other)??;
+
+    let mut encodings = Vec::with_capacity(variants.len());
+    for (encoding, data) in &variants {
+        tokio::fs::write(variant_path(file_path, *encoding), data).await?;
+        encodings.push(encoding.as_str().to_string());
+    }
+    if encodings.is_empty() {
+        encodings.push(NO_VARIANTS_MARKER.to_string());
+    }
+
+    // The file may have been replaced, moved or deleted meanwhile; drop stale variants
+    let updated = sqlx::query(
+        r#"
+        UPDATE files SET precompressed_encodings = $1
+        WHERE id = $2 AND file_path = $3 AND upload_date = $4
+        "#,
+    )
+    .bind(&encodings)
+    .bind(file.id)
+    .bind(file_path)
+    .bind(file.upload_date)
+    .execute(pool)
+    .await
+    .map_err(io::Error::other)?;
+
+    if updated.rows_affected() == 0 {
+        remove_variants(file_path).await;
+    }
+    Ok(())
+}
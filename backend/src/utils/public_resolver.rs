@@ -0,0 +1,88 @@
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+};
+
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+
+use crate::error::{AppError, Result};
+
+/// Whether an address is reachable on the public internet, i.e. not
+/// loopback, private, link-local, shared (CGNAT), multicast or unspecified
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(mapped) => is_public_ipv4(mapped),
+            None => is_public_ipv6(ip),
+        },
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        // 0.0.0.0/8 and 100.64.0.0/10 (shared address space)
+        || a == 0
+        || (a == 100 && (b & 0xc0) == 64))
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        || ip.is_unique_local()
+        || ip.is_unicast_link_local())
+}
+
+/// Resolve `host`, keeping only public addresses
+async fn lookup_public(host: &str) -> io::Result<Vec<SocketAddr>> {
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, 0))
+        .await?
+        .filter(|addr| is_public_ip(addr.ip()))
+        .collect();
+    if addrs.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{host} does not resolve to a public address"),
+        ));
+    }
+    Ok(addrs)
+}
+
+/// DNS resolver for requests to user-supplied URLs: names resolving only to
+/// internal addresses fail to connect. Checked on every connect, so a name
+/// re-pointed after it was configured is caught too.
+#[derive(Debug, Default)]
+pub struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs = lookup_public(name.as_str()).await?;
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Reject a user-supplied URL whose host is, or resolves to, an internal
+/// address. IP literals never reach the resolver, so they're only checked here.
+pub async fn ensure_public_url(url: &reqwest::Url) -> Result<()> {
+    let host = url.host_str().unwrap_or_default();
+    let public = match host.trim_matches(['[', ']']).parse::<IpAddr>() {
+        Ok(ip) => is_public_ip(ip),
+        Err(_) => !host.is_empty() && lookup_public(host).await.is_ok(),
+    };
+    if !public {
+        return Err(AppError::BadRequest(
+            "URL must point to a public address".to_string(),
+        ));
+    }
+    Ok(())
+}
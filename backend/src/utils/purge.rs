@@ -0,0 +1,253 @@
+use chrono::{DateTime, Utc};
+use object_store::path::Path as ObjectPath;
+use sqlx::{PgConnection, PgPool};
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+use uuid::Uuid;
+
+use crate::{
+    error::{AppError, Result},
+    models::ProjectPurge,
+};
+
+use super::cold_storage::ColdStorage;
+
+pub const PURGE_COLUMNS: &str = "id, project_id, project_name, requested_by, status, total_blobs, total_bytes, removed_blobs, removed_bytes, failed_blobs, last_error, created_at, run_after, started_at, finished_at";
+
+/// Blobs removed per progress update
+const PURGE_BATCH_SIZE: usize = 200;
+
+/// A stored object of a deleted project
+enum Blob {
+    /// File under `<storage_path>/<project_id>` (blob or precompressed variant)
+    Local(PathBuf),
+    /// Cold storage object keyed `<project_id>/<file_id>`
+    Cold(ObjectPath),
+}
+
+/// Queue removal of a project and its blobs once `run_after` has passed. Call in
+/// the transaction that schedules the delete, so a rolled back delete leaves
+/// nothing queued.
+pub async fn queue_project_purge(
+    conn: &mut PgConnection,
+    project_id: Uuid,
+    project_name: &str,
+    requested_by: Option<Uuid>,
+    run_after: DateTime<Utc>,
+) -> Result<ProjectPurge> {
+    Ok(sqlx::query_as::<_, ProjectPurge>(&format!(
+        "INSERT INTO project_purges (project_id, project_name, requested_by, run_after) VALUES ($1, $2, $3, $4) RETURNING {PURGE_COLUMNS}"
+    ))
+    .bind(project_id)
+    .bind(project_name)
+    .bind(requested_by)
+    .bind(run_after)
+    .fetch_one(conn)
+    .await?)
+}
+
+/// Every regular file under `dir` with its size; a missing directory is empty
+async fn local_blobs(dir: &Path) -> io::Result<Vec<(PathBuf, u64)>> {
+    let mut blobs = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let mut entries = match tokio::fs::read_dir(&dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        while let Some(entry) = entries.next_entry().await? {
+            if entry.file_type().await?.is_dir() {
+                pending.push(entry.path());
+            } else {
+                blobs.push((entry.path(), entry.metadata().await?.len()));
+            }
+        }
+    }
+    Ok(blobs)
+}
+
+async fn remove_blob(blob: &Blob, cold: Option<&ColdStorage>) -> io::Result<()> {
+    match (blob, cold) {
+        (Blob::Local(path), _) => match tokio::fs::remove_file(path).await {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        },
+        (Blob::Cold(key), Some(cold)) => cold.delete_object(key).await,
+        (Blob::Cold(_), None) => Ok(()),
+    }
+}
+
+/// Delete the project (its rows go with it through foreign key cascades), then
+/// remove everything it left in local and cold storage, updating the purge row
+/// after each batch
+async fn perform_purge(
+    pool: &PgPool,
+    storage_path: &str,
+    cold: Option<&ColdStorage>,
+    purge: &ProjectPurge,
+) -> Result<()> {
+    // Gone already if a retry got past this point before
+    sqlx::query("DELETE FROM projects WHERE id = $1 AND deletion_scheduled_at IS NOT NULL")
+        .bind(purge.project_id)
+        .execute(pool)
+        .await?;
+
+    let dir = Path::new(storage_path).join(purge.project_id.to_string());
+    let mut blobs: Vec<(Blob, u64)> = local_blobs(&dir)
+        .await
+        .map_err(|e| AppError::FileError(format!("Failed to list {}: {e}", dir.display())))?
+        .into_iter()
+        .map(|(path, size)| (Blob::Local(path), size))
+        .collect();
+    if let Some(cold) = cold {
+        let objects = cold.project_objects(purge.project_id).await.map_err(|e| {
+            AppError::FileError(format!("Failed to list cold storage objects: {e}"))
+        })?;
+        blobs.extend(
+            objects
+                .into_iter()
+                .map(|(key, size)| (Blob::Cold(key), size)),
+        );
+    }
+
+    let total_bytes: u64 = blobs.iter().map(|(_, size)| size).sum();
+    sqlx::query("UPDATE project_purges SET total_blobs = $1, total_bytes = $2 WHERE id = $3")
+        .bind(blobs.len() as i64)
+        .bind(total_bytes as i64)
+        .bind(purge.id)
+        .execute(pool)
+        .await?;
+
+    let mut failed_total = 0;
+    for batch in blobs.chunks(PURGE_BATCH_SIZE) {
+        let (mut removed_blobs, mut removed_bytes, mut failed_blobs) = (0i64, 0i64, 0i64);
+        let mut last_error = None;
+        for (blob, size) in batch {
+            match remove_blob(blob, cold).await {
+                Ok(()) => {
+                    removed_blobs += 1;
+                    removed_bytes += *size as i64;
+                }
+                Err(e) => {
+                    let name = match blob {
+                        Blob::Local(path) => path.display().to_string(),
+                        Blob::Cold(key) => key.to_string(),
+                    };
+                    tracing::warn!("Failed to remove {} of deleted project: {}", name, e);
+                    failed_blobs += 1;
+                    last_error = Some(format!("{name}: {e}"));
+                }
+            }
+        }
+        failed_total += failed_blobs;
+
+        sqlx::query(
+            r#"
+            UPDATE project_purges
+            SET removed_blobs = removed_blobs + $1, removed_bytes = removed_bytes + $2,
+                failed_blobs = failed_blobs + $3, last_error = COALESCE($4, last_error)
+            WHERE id = $5
+            "#,
+        )
+        .bind(removed_blobs)
+        .bind(removed_bytes)
+        .bind(failed_blobs)
+        .bind(&last_error)
+        .bind(purge.id)
+        .execute(pool)
+        .await?;
+    }
+
+    // The folder directories are empty once every blob is gone
+    if failed_total == 0 {
+        if let Err(e) = tokio::fs::remove_dir_all(&dir).await {
+            if e.kind() != io::ErrorKind::NotFound {
+                return Err(AppError::FileError(format!(
+                    "Failed to remove {}: {e}",
+                    dir.display()
+                )));
+            }
+        }
+    }
+
+    let status = if failed_total == 0 {
+        "completed"
+    } else {
+        "failed"
+    };
+    sqlx::query("UPDATE project_purges SET status = $1, finished_at = NOW() WHERE id = $2")
+        .bind(status)
+        .bind(purge.id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+async fn fail_purge(pool: &PgPool, id: Uuid, error: &str) {
+    tracing::warn!("Project purge {} failed: {}", id, error);
+    let result = sqlx::query(
+        "UPDATE project_purges SET status = 'failed', last_error = $1, finished_at = NOW() WHERE id = $2",
+    )
+    .bind(error)
+    .bind(id)
+    .execute(pool)
+    .await;
+    if let Err(e) = result {
+        tracing::warn!("Failed to record project purge failure: {}", e);
+    }
+}
+
+/// Work through purges whose grace period is over, oldest first. Returns how
+/// many were run.
+/// Purges that leave blobs behind end up `failed` and can be retried.
+pub async fn run_project_purges(
+    pool: &PgPool,
+    storage_path: &str,
+    cold: Option<&ColdStorage>,
+) -> Result<usize> {
+    let mut processed = 0;
+    loop {
+        let purge = sqlx::query_as::<_, ProjectPurge>(&format!(
+            r#"
+            UPDATE project_purges
+            SET status = 'running', started_at = NOW(), finished_at = NULL, last_error = NULL,
+                removed_blobs = 0, removed_bytes = 0, failed_blobs = 0
+            WHERE id = (
+                SELECT id FROM project_purges
+                WHERE status = 'pending' AND run_after <= NOW()
+                ORDER BY run_after
+                LIMIT 1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING {PURGE_COLUMNS}
+            "#
+        ))
+        .fetch_optional(pool)
+        .await?;
+        let Some(purge) = purge else {
+            return Ok(processed);
+        };
+
+        match perform_purge(pool, storage_path, cold, &purge).await {
+            Ok(()) => tracing::info!(
+                "Purged blobs of deleted project {} ({})",
+                purge.project_name,
+                purge.project_id
+            ),
+            Err(e) => fail_purge(pool, purge.id, &e.to_string()).await,
+        }
+        processed += 1;
+    }
+}
+
+/// Queue purges left running by a previous process again (called at startup).
+/// Removing blobs is idempotent, so they simply start over.
+pub async fn requeue_interrupted_purges(pool: &PgPool) -> Result<()> {
+    sqlx::query("UPDATE project_purges SET status = 'pending' WHERE status = 'running'")
+        .execute(pool)
+        .await?;
+    Ok(())
+}
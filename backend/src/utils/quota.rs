@@ -0,0 +1,137 @@
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+use crate::error::{AppError, Result};
+
+use super::mailer::Mailer;
+
+/// Usage levels (percent of quota) at which the owner is emailed, highest first
+const QUOTA_WARNING_LEVELS: [i16; 3] = [100, 90, 80];
+
+#[derive(FromRow)]
+struct QuotaUsage {
+    name: String,
+    user_id: Uuid,
+    storage_quota_bytes: Option<i64>,
+    used: i64,
+}
+
+async fn quota_usage(pool: &PgPool, project_id: Uuid) -> Result<QuotaUsage> {
+    Ok(sqlx::query_as::<_, QuotaUsage>(
+        r#"
+        SELECT p.name, p.user_id, p.storage_quota_bytes, COALESCE(s.total_size, 0) AS used
+        FROM projects p
+        LEFT JOIN project_stats s ON s.project_id = p.id
+        WHERE p.id = $1
+        "#,
+    )
+    .bind(project_id)
+    .fetch_one(pool)
+    .await?)
+}
+
+/// Reject adding `incoming` bytes to a project that would go over its storage quota
+pub async fn check_quota(pool: &PgPool, project_id: Uuid, incoming: i64) -> Result<()> {
+    let usage = quota_usage(pool, project_id).await?;
+    match usage.storage_quota_bytes {
+        Some(quota) if usage.used + incoming > quota => Err(AppError::QuotaExceeded {
+            used: usage.used,
+            quota,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Email the owner when usage crosses 80/90/100% of the quota. Each level is
+/// sent once; dropping back below a level re-arms it.
+pub async fn send_quota_warnings(
+    pool: &PgPool,
+    mailer: Option<&Mailer>,
+    project_id: Uuid,
+) -> Result<()> {
+    let usage = quota_usage(pool, project_id).await?;
+    let Some(quota) = usage.storage_quota_bytes else {
+        return Ok(());
+    };
+    let percent = usage.used.saturating_mul(100) / quota.max(1);
+    let level = QUOTA_WARNING_LEVELS
+        .into_iter()
+        .find(|&level| percent >= i64::from(level))
+        .unwrap_or(0);
+
+    // Claim the level atomically so concurrent uploads send one email
+    let claimed = sqlx::query(
+        "UPDATE projects SET quota_warning_percent = $1 WHERE id = $2 AND quota_warning_percent < $1",
+    )
+    .bind(level)
+    .bind(project_id)
+    .execute(pool)
+    .await?;
+    if claimed.rows_affected() == 0 {
+        sqlx::query(
+            "UPDATE projects SET quota_warning_percent = $1 WHERE id = $2 AND quota_warning_percent > $1",
+        )
+        .bind(level)
+        .bind(project_id)
+        .execute(pool)
+        .await?;
+        return Ok(());
+    }
+
+    let Some(mailer) = mailer else {
+        return Ok(());
+    };
+    let owner = sqlx::query_as::<_, (String, bool)>(
+        "SELECT email, notify_quota_warnings FROM users WHERE id = $1",
+    )
+    .bind(usage.user_id)
+    .fetch_optional(pool)
+    .await?;
+    let Some((email, true)) = owner else {
+        return Ok(());
+    };
+
+    let (subject, consequence) = if level >= 100 {
+        (
+            format!("Project \"{}\" is out of storage", usage.name),
+            "New uploads are rejected until files are deleted or the quota is raised.",
+        )
+    } else {
+        (
+            format!(
+                "Project \"{}\" has used {level}% of its storage",
+                usage.name
+            ),
+            "Uploads will be rejected once the quota is reached.",
+        )
+    };
+    let body = format!(
+        "Project \"{}\" is using {} of {quota} bytes ({percent}%).\n\n{consequence}\n",
+        usage.name, usage.used
+    );
+    if let Err(e) = mailer.send(&email, &subject, body).await {
+        tracing::warn!(
+            "Failed to send quota warning for project {}: {}",
+            project_id,
+            e
+        );
+    }
+    Ok(())
+}
+
+/// Run `send_quota_warnings` in the background after files were added
+pub fn spawn_quota_warnings(
+    pool: PgPool,
+    mailer: Option<std::sync::Arc<Mailer>>,
+    project_id: Uuid,
+) {
+    tokio::spawn(async move {
+        if let Err(e) = send_quota_warnings(&pool, mailer.as_deref(), project_id).await {
+            tracing::warn!(
+                "Quota warning check failed for project {}: {}",
+                project_id,
+                e
+            );
+        }
+    });
+}
@@ -0,0 +1,359 @@
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::{FromRow, PgConnection, PgPool};
+use std::{
+    collections::HashSet,
+    path::{Component, Path, PathBuf},
+    time::Duration,
+};
+use uuid::Uuid;
+
+use crate::{error::Result, models::File};
+
+use super::{
+    cold_storage::HOT_TIER,
+    precompress::{variant_path, PRECOMPRESSED_ENCODINGS},
+};
+
+/// Files pushed per worker pass
+const REPLICATION_BATCH_SIZE: i64 = 20;
+
+/// How long a worker pass owns the files it claimed
+const REPLICATION_CLAIM_SECS: f64 = 900.0;
+
+/// Upper bound for the retry backoff of a file that can't be pushed
+const REPLICATION_MAX_BACKOFF_SECS: i32 = 3600;
+
+/// Header carrying the hex SHA-256 of a pushed blob
+pub const REPLICATION_SHA256_HEADER: &str = "X-Content-SHA256";
+
+/// Standby FileRunner instance that blobs are mirrored to. It shares this
+/// instance's database, so only blobs are pushed, addressed by their path
+/// relative to `STORAGE_PATH`.
+#[derive(Debug)]
+pub struct ReplicationPeer {
+    client: reqwest::Client,
+    base_url: reqwest::Url,
+    token: String,
+    storage_path: PathBuf,
+}
+
+/// Blob (and cached variants) as the peer should hold them
+#[derive(Debug)]
+struct BlobState {
+    file_path: String,
+    upload_date: DateTime<Utc>,
+    variants: Vec<String>,
+}
+
+impl ReplicationPeer {
+    pub fn new(
+        base_url: &str,
+        token: String,
+        storage_path: &str,
+    ) -> std::result::Result<Self, Box<dyn std::error::Error>> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(300))
+            .build()?;
+        Ok(Self {
+            client,
+            base_url: reqwest::Url::parse(base_url)?,
+            token,
+            storage_path: PathBuf::from(storage_path),
+        })
+    }
+
+    pub fn base_url(&self) -> &str {
+        self.base_url.as_str()
+    }
+
+    /// `/api/v1/replication/blobs/<path relative to STORAGE_PATH>` on the peer
+    fn blob_url(&self, path: &Path) -> std::result::Result<reqwest::Url, String> {
+        let relative = path
+            .strip_prefix(&self.storage_path)
+            .map_err(|_| format!("{} is outside STORAGE_PATH", path.display()))?;
+        let mut url = self.base_url.clone();
+        {
+            let mut segments = url
+                .path_segments_mut()
+                .map_err(|_| "REPLICATION_PEER_URL is not a base URL".to_string())?;
+            segments
+                .pop_if_empty()
+                .extend(["api", "v1", "replication", "blobs"]);
+            for component in relative.components() {
+                segments.push(&component.as_os_str().to_string_lossy());
+            }
+        }
+        Ok(url)
+    }
+
+    async fn put(&self, path: &Path) -> std::result::Result<(), String> {
+        let data = tokio::fs::read(path)
+            .await
+            .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+        let response = self
+            .client
+            .put(self.blob_url(path)?)
+            .bearer_auth(&self.token)
+            .header(
+                REPLICATION_SHA256_HEADER,
+                hex::encode(Sha256::digest(&data)),
+            )
+            .body(data)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!("Peer returned {}", response.status()));
+        }
+        Ok(())
+    }
+
+    async fn delete(&self, path: &Path) -> std::result::Result<(), String> {
+        let response = self
+            .client
+            .delete(self.blob_url(path)?)
+            .bearer_auth(&self.token)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!("Peer returned {}", response.status()));
+        }
+        Ok(())
+    }
+
+    /// Bring the peer from `replicated` to `wanted`
+    async fn sync(
+        &self,
+        wanted: Option<&BlobState>,
+        replicated: Option<&BlobState>,
+    ) -> std::result::Result<(), String> {
+        if let Some(wanted) = wanted {
+            let blob = Path::new(&wanted.file_path);
+            let same_blob = replicated.is_some_and(|r| {
+                r.file_path == wanted.file_path && r.upload_date == wanted.upload_date
+            });
+            // The peer drops its variants of a blob it receives, so they all follow
+            if !same_blob {
+                self.put(blob).await?;
+            }
+            for encoding in PRECOMPRESSED_ENCODINGS {
+                let name = encoding.as_str().to_string();
+                let pushed = same_blob && replicated.is_some_and(|r| r.variants.contains(&name));
+                if wanted.variants.contains(&name) && !pushed {
+                    self.put(&variant_path(&wanted.file_path, encoding)).await?;
+                }
+            }
+        }
+        if let Some(replicated) = replicated {
+            if wanted.is_none_or(|w| w.file_path != replicated.file_path) {
+                self.delete(Path::new(&replicated.file_path)).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Check a pushed blob's path (relative to `STORAGE_PATH`) before writing it
+pub fn replica_path(storage_path: &str, relative: &str) -> Option<PathBuf> {
+    let relative = Path::new(relative);
+    let mut components = relative.components().peekable();
+    components.peek()?;
+    if !components.all(|c| matches!(c, Component::Normal(_))) {
+        return None;
+    }
+    Some(Path::new(storage_path).join(relative))
+}
+
+/// Queue files whose blob was written, moved or deleted for pushing to the
+/// standby, if replication is configured. Call inside the transaction that
+/// performs the change.
+pub async fn queue_replication<'a>(
+    conn: &mut PgConnection,
+    replication: Option<&ReplicationPeer>,
+    files: impl IntoIterator<Item = &'a File>,
+) -> Result<()> {
+    if replication.is_none() {
+        return Ok(());
+    }
+
+    let file_ids: Vec<Uuid> = files
+        .into_iter()
+        .map(|f| f.id)
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    if file_ids.is_empty() {
+        return Ok(());
+    }
+    sqlx::query(
+        r#"
+        INSERT INTO replication_queue (file_id)
+        SELECT * FROM UNNEST($1::uuid[])
+        ON CONFLICT (file_id) DO UPDATE
+        SET queued_at = NOW(), attempts = 0, next_attempt_at = NOW(), last_error = NULL
+        "#,
+    )
+    .bind(&file_ids)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Queue every file the peer is behind on: hot blobs it lacks or holds an old
+/// version of, and copies of files that were deleted or moved to cold storage.
+/// Covers changes made while replication was off and the ones not queued
+/// directly (project purges, cold storage moves). Returns how many were queued.
+pub async fn queue_replication_catch_up(pool: &PgPool) -> Result<u64> {
+    let queued = sqlx::query(
+        r#"
+        INSERT INTO replication_queue (file_id)
+        SELECT f.id FROM files f
+        LEFT JOIN replicated_blobs r ON r.file_id = f.id
+        WHERE f.storage_tier = 'hot'
+          AND (r.file_id IS NULL OR r.file_path <> f.file_path OR r.upload_date <> f.upload_date
+               OR NOT r.variants @> ARRAY(SELECT e FROM UNNEST(f.precompressed_encodings) e WHERE e <> 'identity'))
+        UNION
+        SELECT r.file_id FROM replicated_blobs r
+        LEFT JOIN files f ON f.id = r.file_id
+        WHERE f.id IS NULL OR f.storage_tier <> 'hot'
+        ON CONFLICT (file_id) DO NOTHING
+        "#,
+    )
+    .execute(pool)
+    .await?;
+    Ok(queued.rows_affected())
+}
+
+#[derive(FromRow)]
+struct ReplicationJob {
+    file_id: Uuid,
+    queued_at: DateTime<Utc>,
+}
+
+#[derive(FromRow)]
+struct StoredBlob {
+    file_path: String,
+    upload_date: DateTime<Utc>,
+    storage_tier: String,
+    precompressed_encodings: Vec<String>,
+}
+
+#[derive(FromRow)]
+struct ReplicatedBlob {
+    file_path: String,
+    upload_date: DateTime<Utc>,
+    variants: Vec<String>,
+}
+
+/// Push one batch of queued files to the peer. Returns how many were synced.
+///
+/// Files are claimed by pushing `next_attempt_at` past `REPLICATION_CLAIM_SECS`,
+/// like moderation jobs; a file queued again while it was being pushed is
+/// pushed again on a later pass.
+pub async fn run_replication(pool: &PgPool, peer: &ReplicationPeer) -> Result<usize> {
+    let jobs = sqlx::query_as::<_, ReplicationJob>(
+        r#"
+        UPDATE replication_queue
+        SET next_attempt_at = NOW() + make_interval(secs => $2)
+        WHERE file_id IN (
+            SELECT file_id FROM replication_queue
+            WHERE next_attempt_at <= NOW()
+            ORDER BY queued_at
+            LIMIT $1
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING file_id, queued_at
+        "#,
+    )
+    .bind(REPLICATION_BATCH_SIZE)
+    .bind(REPLICATION_CLAIM_SECS)
+    .fetch_all(pool)
+    .await?;
+
+    let mut synced = 0;
+    for job in &jobs {
+        let wanted = sqlx::query_as::<_, StoredBlob>(
+            "SELECT file_path, upload_date, storage_tier, precompressed_encodings FROM files WHERE id = $1",
+        )
+        .bind(job.file_id)
+        .fetch_optional(pool)
+        .await?
+        .filter(|blob| blob.storage_tier == HOT_TIER)
+        .map(|blob| BlobState {
+            file_path: blob.file_path,
+            upload_date: blob.upload_date,
+            variants: PRECOMPRESSED_ENCODINGS
+                .iter()
+                .map(|e| e.as_str().to_string())
+                .filter(|e| blob.precompressed_encodings.contains(e))
+                .collect(),
+        });
+        let replicated = sqlx::query_as::<_, ReplicatedBlob>(
+            "SELECT file_path, upload_date, variants FROM replicated_blobs WHERE file_id = $1",
+        )
+        .bind(job.file_id)
+        .fetch_optional(pool)
+        .await?
+        .map(|blob| BlobState {
+            file_path: blob.file_path,
+            upload_date: blob.upload_date,
+            variants: blob.variants,
+        });
+
+        if let Err(e) = peer.sync(wanted.as_ref(), replicated.as_ref()).await {
+            tracing::warn!("Failed to replicate file {}: {}", job.file_id, e);
+            sqlx::query(
+                r#"
+                UPDATE replication_queue
+                SET attempts = attempts + 1, last_error = $1,
+                    next_attempt_at = NOW() + make_interval(secs => LEAST(POWER(2, attempts + 1), $2))
+                WHERE file_id = $3 AND queued_at = $4
+                "#,
+            )
+            .bind(&e)
+            .bind(REPLICATION_MAX_BACKOFF_SECS)
+            .bind(job.file_id)
+            .bind(job.queued_at)
+            .execute(pool)
+            .await?;
+            continue;
+        }
+
+        let mut tx = pool.begin().await?;
+        match wanted {
+            Some(blob) => {
+                sqlx::query(
+                    r#"
+                    INSERT INTO replicated_blobs (file_id, file_path, upload_date, variants)
+                    VALUES ($1, $2, $3, $4)
+                    ON CONFLICT (file_id) DO UPDATE
+                    SET file_path = EXCLUDED.file_path, upload_date = EXCLUDED.upload_date,
+                        variants = EXCLUDED.variants, replicated_at = NOW()
+                    "#,
+                )
+                .bind(job.file_id)
+                .bind(&blob.file_path)
+                .bind(blob.upload_date)
+                .bind(&blob.variants)
+                .execute(&mut *tx)
+                .await?;
+            }
+            None => {
+                sqlx::query("DELETE FROM replicated_blobs WHERE file_id = $1")
+                    .bind(job.file_id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+        sqlx::query("DELETE FROM replication_queue WHERE file_id = $1 AND queued_at = $2")
+            .bind(job.file_id)
+            .bind(job.queued_at)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        synced += 1;
+    }
+    Ok(synced)
+}
@@ -0,0 +1,38 @@
+use bytes::Bytes;
+use futures::{Stream, TryStreamExt};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Read at most this many bytes per chunk so pacing stays smooth at low rates
+const MAX_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Chunk size to read a body throttled to `bytes_per_sec` in
+pub fn throttled_chunk_size(bytes_per_sec: u64) -> usize {
+    (bytes_per_sec as usize).clamp(1, MAX_CHUNK_SIZE)
+}
+
+/// Pass a body's chunks on while limiting throughput to `bytes_per_sec`.
+/// Chunks should be at most `throttled_chunk_size` bytes.
+///
+/// After each chunk the stream sleeps until the total sent matches the target
+/// rate, so short bursts are allowed but the average never exceeds the limit.
+pub fn throttled_stream<S>(
+    chunks: S,
+    bytes_per_sec: u64,
+) -> impl Stream<Item = std::io::Result<Bytes>>
+where
+    S: Stream<Item = std::io::Result<Bytes>> + Send + 'static,
+{
+    let bytes_per_sec = bytes_per_sec.max(1);
+    let start = Instant::now();
+    let mut sent: u64 = 0;
+
+    chunks.and_then(move |chunk| {
+        sent += chunk.len() as u64;
+        let due = start + Duration::from_secs_f64(sent as f64 / bytes_per_sec as f64);
+        async move {
+            tokio::time::sleep_until(due).await;
+            Ok(chunk)
+        }
+    })
+}
@@ -0,0 +1,107 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+/// Bound on cached users; the cache is cleared when it is reached
+const TOKEN_REVOCATION_MAX_ENTRIES: usize = 100_000;
+
+/// Cutoff for a user with no revocation: every token is accepted
+const NOT_REVOKED: i64 = i64::MIN;
+
+/// Cutoff for a user that no longer exists: every token is rejected
+const USER_GONE: i64 = i64::MAX;
+
+/// Caches each user's `token_valid_after` (as a Unix timestamp) so checking
+/// access tokens doesn't hit the database on every request. Revocations made
+/// through this instance take effect immediately; ones made elsewhere (another
+/// instance, the CLI) once the entry expires.
+#[derive(Debug)]
+pub struct TokenRevocationCache {
+    entries: Mutex<HashMap<Uuid, (i64, Instant)>>,
+    ttl: Duration,
+}
+
+impl TokenRevocationCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            ttl,
+        }
+    }
+
+    fn get(&self, user_id: Uuid) -> Option<i64> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(&user_id)
+            .filter(|(_, cached_at)| cached_at.elapsed() < self.ttl)
+            .map(|(cutoff, _)| *cutoff)
+    }
+
+    fn insert(&self, user_id: Uuid, cutoff: i64) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= TOKEN_REVOCATION_MAX_ENTRIES {
+            entries.retain(|_, (_, cached_at)| cached_at.elapsed() < self.ttl);
+            if entries.len() >= TOKEN_REVOCATION_MAX_ENTRIES {
+                entries.clear();
+            }
+        }
+        entries.insert(user_id, (cutoff, Instant::now()));
+    }
+
+    /// Whether an access token issued at `issued_at` (the `iat` claim) for
+    /// `user_id` has been revoked, or its user deleted
+    pub async fn is_revoked(
+        &self,
+        pool: &PgPool,
+        user_id: Uuid,
+        issued_at: i64,
+    ) -> sqlx::Result<bool> {
+        let cutoff = match self.get(user_id) {
+            Some(cutoff) => cutoff,
+            None => {
+                // Read from the primary so a revocation just made is seen right away
+                let valid_after: Option<Option<DateTime<Utc>>> =
+                    sqlx::query_scalar("SELECT token_valid_after FROM users WHERE id = $1")
+                        .bind(user_id)
+                        .fetch_optional(pool)
+                        .await?;
+                let cutoff = match valid_after {
+                    None => USER_GONE,
+                    Some(None) => NOT_REVOKED,
+                    Some(Some(valid_after)) => valid_after.timestamp(),
+                };
+                self.insert(user_id, cutoff);
+                cutoff
+            }
+        };
+        Ok(issued_at < cutoff)
+    }
+
+    /// Revoke every access token issued to `user_id` so far. `executor` lets
+    /// callers do it inside the transaction that changes the password.
+    pub async fn revoke_all<'e, E>(&self, executor: E, user_id: Uuid) -> sqlx::Result<()>
+    where
+        E: sqlx::PgExecutor<'e>,
+    {
+        revoke_access_tokens(executor, user_id).await?;
+        self.entries.lock().unwrap().remove(&user_id);
+        Ok(())
+    }
+}
+
+/// Set `token_valid_after` so access tokens issued before now are rejected
+pub async fn revoke_access_tokens<'e, E>(executor: E, user_id: Uuid) -> sqlx::Result<()>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query("UPDATE users SET token_valid_after = NOW() WHERE id = $1")
+        .bind(user_id)
+        .execute(executor)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,37 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use uuid::Uuid;
+
+/// Tracks in-flight uploads per project so one client can't monopolise disk and DB.
+/// Each project gets its own semaphore, rebuilt if the project's limit changes.
+#[derive(Debug, Default)]
+pub struct UploadLimiter {
+    semaphores: Mutex<HashMap<Uuid, (usize, Arc<Semaphore>)>>,
+}
+
+impl UploadLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Try to reserve an upload slot for a project.
+    /// Returns None when `limit` uploads are already in flight.
+    /// The slot is released when the returned permit is dropped.
+    pub fn try_acquire(&self, project_id: Uuid, limit: usize) -> Option<OwnedSemaphorePermit> {
+        let semaphore = {
+            let mut semaphores = self.semaphores.lock().unwrap();
+            let entry = semaphores
+                .entry(project_id)
+                .or_insert_with(|| (limit, Arc::new(Semaphore::new(limit))));
+            if entry.0 != limit {
+                *entry = (limit, Arc::new(Semaphore::new(limit)));
+            }
+            entry.1.clone()
+        };
+
+        semaphore.try_acquire_owned().ok()
+    }
+}
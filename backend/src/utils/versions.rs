@@ -0,0 +1,18 @@
+use std::path::PathBuf;
+use uuid::Uuid;
+
+/// Directory under a project's storage holding kept file versions. Folder
+/// paths can't start with a dot, so it never collides with a folder.
+pub const VERSIONS_DIR: &str = ".versions";
+
+pub const FILE_VERSION_COLUMNS: &str =
+    "id, file_id, original_name, file_path, size, mime_type, content_hash, reason, created_at";
+
+/// `<storage_path>/<project_id>/.versions/<version_id>`
+pub fn version_blob_path(storage_path: &str, project_id: Uuid, version_id: Uuid) -> PathBuf {
+    let mut path = PathBuf::from(storage_path);
+    path.push(project_id.to_string());
+    path.push(VERSIONS_DIR);
+    path.push(version_id.to_string());
+    path
+}
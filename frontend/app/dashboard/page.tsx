@@ -49,10 +49,7 @@ export default function DashboardPage() {
 
   const { data: projects, isLoading } = useQuery({
     queryKey: ["projects"],
-    queryFn: async () => {
-      const response = await projectsApi.list();
-      return response.data;
-    },
+    queryFn: () => projectsApi.list(),
   });
 
   const createMutation = useMutation({
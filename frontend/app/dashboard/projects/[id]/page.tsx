@@ -145,10 +145,7 @@ export default function ProjectDetailPage() {
 
   const { data: files, isLoading: filesLoading } = useQuery({
     queryKey: ["files", projectId],
-    queryFn: async () => {
-      const response = await projectsApi.listFiles(projectId);
-      return response.data;
-    },
+    queryFn: () => projectsApi.listFiles(projectId),
   });
 
   const deleteMutation = useMutation({
@@ -0,0 +1,82 @@
+"use client";
+
+import { Suspense, useState } from "react";
+import { useSearchParams } from "next/navigation";
+import Link from "next/link";
+import { authApi } from "@/lib/api";
+import { Button } from "@/components/ui/button";
+import {
+  Card,
+  CardContent,
+  CardDescription,
+  CardHeader,
+  CardTitle,
+} from "@/components/ui/card";
+import { ShieldAlert } from "lucide-react";
+
+function ReportLogin() {
+  const token = useSearchParams().get("token") || "";
+  const [message, setMessage] = useState("");
+  const [error, setError] = useState("");
+  const [loading, setLoading] = useState(false);
+
+  // Reporting only happens on an explicit click, so link scanners in mail
+  // clients can't sign a session out by opening the link
+  const handleReport = async () => {
+    setLoading(true);
+    setError("");
+    try {
+      const response = await authApi.reportLogin(token);
+      setMessage(response.data.message);
+    } catch (err: any) {
+      setError(err.response?.data?.error || "Failed to report the sign-in");
+    } finally {
+      setLoading(false);
+    }
+  };
+
+  return (
+    <Card className="w-full max-w-md mx-4">
+      <CardHeader className="text-center">
+        <div className="mx-auto mb-4 h-12 w-12 rounded-full bg-red-100 flex items-center justify-center">
+          <ShieldAlert className="h-6 w-6 text-red-600" />
+        </div>
+        <CardTitle>Wasn&apos;t you?</CardTitle>
+        <CardDescription>
+          Report the sign-in from the alert email to sign that session out.
+        </CardDescription>
+      </CardHeader>
+      <CardContent className="space-y-4">
+        {message ? (
+          <p className="text-sm text-center">{message}</p>
+        ) : (
+          <Button
+            className="w-full"
+            variant="destructive"
+            disabled={!token || loading}
+            onClick={handleReport}
+          >
+            {loading ? "Reporting..." : "This wasn't me"}
+          </Button>
+        )}
+        {error && <p className="text-sm text-center text-red-600">{error}</p>}
+        <p className="text-sm text-center text-muted-foreground">
+          <Link href="/login" className="underline">
+            Sign in
+          </Link>{" "}
+          to change your password.
+        </p>
+      </CardContent>
+    </Card>
+  );
+}
+
+export default function ReportLoginPage() {
+  return (
+    <div className="flex min-h-screen items-center justify-center">
+      <Suspense>
+        <ReportLogin />
+      </Suspense>
+    </div>
+  );
+}
@@ -0,0 +1,73 @@
+"use client";
+
+import { Suspense, useEffect, useState } from "react";
+import { useSearchParams } from "next/navigation";
+import { getApiUrl, initConfig } from "@/lib/config";
+import { Button } from "@/components/ui/button";
+import {
+  Card,
+  CardContent,
+  CardDescription,
+  CardHeader,
+  CardTitle,
+} from "@/components/ui/card";
+import { Download } from "lucide-react";
+
+function SharedFile() {
+  const params = useSearchParams();
+  const fileId = params.get("file") || "";
+  const token = params.get("token") || "";
+  const [downloadUrl, setDownloadUrl] = useState("");
+
+  // The API address is only known once the runtime config has loaded
+  useEffect(() => {
+    if (!fileId || !token) return;
+    initConfig().then(() => {
+      setDownloadUrl(
+        `${getApiUrl()}/files/${encodeURIComponent(fileId)}?share=${encodeURIComponent(token)}`
+      );
+    });
+  }, [fileId, token]);
+
+  return (
+    <Card className="w-full max-w-md mx-4">
+      <CardHeader className="text-center">
+        <div className="mx-auto mb-4 h-12 w-12 rounded-full bg-blue-100 flex items-center justify-center">
+          <Download className="h-6 w-6 text-blue-600" />
+        </div>
+        <CardTitle>A file was shared with you</CardTitle>
+        <CardDescription>
+          The link works until the date in the email that brought you here.
+        </CardDescription>
+      </CardHeader>
+      <CardContent className="space-y-4">
+        {!fileId || !token ? (
+          <p className="text-sm text-center text-red-600">This share link is incomplete.</p>
+        ) : (
+          downloadUrl && (
+            <div className="flex gap-2">
+              <Button asChild className="flex-1">
+                <a href={`${downloadUrl}&download=true`}>Download</a>
+              </Button>
+              <Button asChild variant="outline" className="flex-1">
+                <a href={downloadUrl} target="_blank" rel="noopener noreferrer">
+                  Open
+                </a>
+              </Button>
+            </div>
+          )
+        )}
+      </CardContent>
+    </Card>
+  );
+}
+
+export default function SharePage() {
+  return (
+    <div className="flex min-h-screen items-center justify-center">
+      <Suspense>
+        <SharedFile />
+      </Suspense>
+    </div>
+  );
+}
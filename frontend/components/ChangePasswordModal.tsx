@@ -25,7 +25,7 @@ export function ChangePasswordModal({ isForced = false }: ChangePasswordModalPro
   const [confirmPassword, setConfirmPassword] = useState("");
   const [error, setError] = useState("");
   const [isLoading, setIsLoading] = useState(false);
-  const { clearMustChangePassword } = useAuthStore();
+  const { user, setAuth, clearMustChangePassword } = useAuthStore();
 
   const handleSubmit = async (e: React.FormEvent) => {
     e.preventDefault();
@@ -50,7 +50,11 @@ export function ChangePasswordModal({ isForced = false }: ChangePasswordModalPro
     setIsLoading(true);
 
     try {
-      await authApi.changePassword(currentPassword, newPassword);
+      const { data } = await authApi.changePassword(currentPassword, newPassword);
+      // Changing the password signs out every session, including this one
+      if (user) {
+        setAuth(data.access_token, data.refresh_token, user);
+      }
       clearMustChangePassword();
       // Reset form
       setCurrentPassword("");
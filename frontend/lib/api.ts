@@ -73,9 +73,30 @@ export interface Folder {
   created_at: string;
 }
 
+export interface CategoryUsage {
+  file_count: number;
+  total_size: number;
+}
+
+// Storage of a folder's own files by kind, with `breakdown=true`
+export interface FolderUsage {
+  images: CategoryUsage;
+  video: CategoryUsage;
+  docs: CategoryUsage;
+  other: CategoryUsage;
+}
+
 export interface FolderResponse extends Folder {
   file_count?: number;
   total_size?: number;
+  usage?: FolderUsage;
+}
+
+// One page of a listing; pass next_cursor as `cursor` for the next one
+export interface Paginated<T> {
+  items: T[];
+  next_cursor: string | null;
+  total_estimate: number;
 }
 
 // Token refresh state
@@ -219,6 +240,20 @@ export const api = new Proxy({} as AxiosInstance, {
   },
 });
 
+// Fetch every page of a listing
+const listAll = async <T>(url: string, params: Record<string, string> = {}) => {
+  const items: T[] = [];
+  let cursor: string | null = null;
+  do {
+    const response: { data: Paginated<T> } = await api.get<Paginated<T>>(url, {
+      params: { ...params, limit: 1000, ...(cursor ? { cursor } : {}) },
+    });
+    items.push(...response.data.items);
+    cursor = response.data.next_cursor;
+  } while (cursor);
+  return items;
+};
+
 // Auth API
 export const authApi = {
   register: (email: string, password: string) =>
@@ -235,7 +270,7 @@ export const authApi = {
   me: () => api.get<User>("/auth/me"),
 
   changePassword: (currentPassword: string, newPassword: string) =>
-    api.put<{ message: string }>("/auth/change-password", {
+    api.put<TokenRefreshResponse & { message: string }>("/auth/change-password", {
       current_password: currentPassword,
       new_password: newPassword,
     }),
@@ -246,11 +281,14 @@ export const authApi = {
     }),
 
   logoutAll: () => api.post<{ message: string; revoked_count: number }>("/auth/logout-all"),
+
+  reportLogin: (token: string) =>
+    api.post<{ message: string; revoked_count: number }>("/auth/login-alerts/report", { token }),
 };
 
 // Projects API
 export const projectsApi = {
-  list: () => api.get<ProjectResponse[]>("/projects"),
+  list: () => listAll<ProjectResponse>("/projects"),
 
   get: (id: string) => api.get<ProjectResponse>(`/projects/${id}`),
 
@@ -263,12 +301,15 @@ export const projectsApi = {
       is_public: isPublic,
     }),
 
-  delete: (id: string) => api.delete(`/projects/${id}`),
+  delete: (id: string, confirmName: string) =>
+    api.delete(`/projects/${id}`, { params: { confirm: confirmName } }),
+
+  restore: (id: string) => api.post<Project>(`/projects/${id}/restore`),
 
   regenerateKey: (id: string) =>
     api.post<Project>(`/projects/${id}/regenerate-key`),
 
-  listFiles: (id: string) => api.get<FileMetadata[]>(`/projects/${id}/files`),
+  listFiles: (id: string) => listAll<FileMetadata>(`/projects/${id}/files`),
 
   emptyProject: (id: string) =>
     api.delete<{ message: string; deleted_count: number }>(
@@ -309,9 +350,7 @@ export const filesApi = {
 // Folders API
 export const foldersApi = {
   list: (projectId: string) =>
-    api.get<FolderResponse[]>("/folders", {
-      params: { project_id: projectId },
-    }),
+    listAll<FolderResponse>("/folders", { project_id: projectId }),
 
   create: (projectId: string, path: string, isPublic?: boolean) =>
     api.post<Folder>("/folders", {